@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE datasets
+DROP COLUMN cache_ttl_seconds;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE datasets
+ADD COLUMN cache_ttl_seconds INTEGER;
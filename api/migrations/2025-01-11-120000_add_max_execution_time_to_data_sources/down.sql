@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE data_sources
+DROP COLUMN max_execution_time_seconds;
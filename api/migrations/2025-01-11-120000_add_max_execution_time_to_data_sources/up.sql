@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE data_sources
+ADD COLUMN max_execution_time_seconds INTEGER;
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS dashboard_versions_dashboard_id_created_at_idx;
+
+ALTER TABLE dashboard_versions
+DROP COLUMN created_by,
+DROP COLUMN summary,
+DROP COLUMN thread_ids;
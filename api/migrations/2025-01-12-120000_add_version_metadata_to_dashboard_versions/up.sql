@@ -0,0 +1,8 @@
+-- Your SQL goes here
+ALTER TABLE dashboard_versions
+ADD COLUMN thread_ids JSONB NOT NULL DEFAULT '[]'::jsonb,
+ADD COLUMN summary TEXT,
+ADD COLUMN created_by UUID REFERENCES users(id);
+
+CREATE INDEX dashboard_versions_dashboard_id_created_at_idx
+ON dashboard_versions (dashboard_id, created_at DESC);
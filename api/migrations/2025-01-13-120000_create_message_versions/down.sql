@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE message_versions;
+
+DROP TYPE message_version_author_enum;
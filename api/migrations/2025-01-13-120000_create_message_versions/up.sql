@@ -0,0 +1,19 @@
+-- Your SQL goes here
+CREATE TYPE message_version_author_enum AS ENUM ('user', 'agent');
+
+CREATE TABLE message_versions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    version_number INTEGER NOT NULL,
+    code TEXT,
+    chart_config JSONB,
+    title TEXT,
+    time_frame TEXT,
+    author_type message_version_author_enum NOT NULL,
+    created_by UUID REFERENCES users(id),
+    summary TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deleted_at TIMESTAMPTZ,
+    UNIQUE (message_id, version_number)
+);
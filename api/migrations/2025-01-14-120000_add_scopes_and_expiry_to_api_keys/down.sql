@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+-- Hashed keys can not be recovered, so every key has to be reissued after this.
+ALTER TABLE api_keys
+RENAME COLUMN key_hash TO key;
+
+ALTER TABLE api_keys
+DROP COLUMN key_prefix,
+DROP COLUMN name,
+DROP COLUMN scopes,
+DROP COLUMN expires_at,
+DROP COLUMN last_used_at,
+DROP COLUMN last_used_ip;
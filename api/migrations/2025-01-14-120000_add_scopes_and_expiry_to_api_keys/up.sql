@@ -0,0 +1,24 @@
+-- Your SQL goes here
+ALTER TABLE api_keys
+ADD COLUMN key_prefix TEXT NOT NULL DEFAULT '',
+ADD COLUMN name TEXT,
+ADD COLUMN scopes JSONB NOT NULL DEFAULT '[]'::jsonb,
+ADD COLUMN expires_at TIMESTAMPTZ,
+ADD COLUMN last_used_at TIMESTAMPTZ,
+ADD COLUMN last_used_ip TEXT;
+
+-- Keys issued before scopes existed keep the full access of their owner. They are all JWTs that
+-- start the same way, so the end of the key is kept for display instead.
+UPDATE api_keys
+SET key_prefix = '...' || right(key, 8),
+    scopes = '["*"]'::jsonb;
+
+ALTER TABLE api_keys
+ALTER COLUMN key_prefix DROP DEFAULT;
+
+-- Only a hash of each key is stored from now on.
+ALTER TABLE api_keys
+RENAME COLUMN key TO key_hash;
+
+UPDATE api_keys
+SET key_hash = encode(sha256(convert_to(key_hash, 'UTF8')), 'hex');
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS audit_events_append_only ON audit_events;
+DROP FUNCTION IF EXISTS prevent_audit_event_changes();
+DROP TABLE IF EXISTS audit_events;
+DROP TYPE IF EXISTS audit_target_type_enum;
+DROP TYPE IF EXISTS audit_action_enum;
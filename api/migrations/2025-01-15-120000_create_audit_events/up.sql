@@ -0,0 +1,52 @@
+-- Your SQL goes here
+CREATE TYPE audit_action_enum AS ENUM (
+    'user_permissions_updated',
+    'team_permissions_updated',
+    'permission_group_updated',
+    'asset_permissions_updated',
+    'asset_publicly_shared',
+    'data_source_created',
+    'data_source_updated',
+    'data_source_deleted',
+    'datasets_deployed',
+    'sql_run'
+);
+
+CREATE TYPE audit_target_type_enum AS ENUM (
+    'user',
+    'team',
+    'permission_group',
+    'dashboard',
+    'thread',
+    'collection',
+    'data_source',
+    'dataset'
+);
+
+CREATE TABLE audit_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    organization_id UUID NOT NULL REFERENCES organizations(id),
+    actor_id UUID NOT NULL REFERENCES users(id),
+    action audit_action_enum NOT NULL,
+    target_type audit_target_type_enum NOT NULL,
+    target_id UUID,
+    before JSONB,
+    after JSONB,
+    request_metadata JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX audit_events_organization_id_created_at_idx ON audit_events (organization_id, created_at DESC);
+CREATE INDEX audit_events_actor_id_idx ON audit_events (actor_id);
+CREATE INDEX audit_events_target_id_idx ON audit_events (target_id);
+
+-- Audit events are append only.
+CREATE FUNCTION prevent_audit_event_changes() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only
+    BEFORE UPDATE OR DELETE ON audit_events
+    FOR EACH ROW EXECUTE FUNCTION prevent_audit_event_changes();
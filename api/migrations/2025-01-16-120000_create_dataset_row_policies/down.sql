@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS dataset_row_policies_to_identities;
+DROP TABLE IF EXISTS dataset_row_policies;
+ALTER TABLE users DROP COLUMN IF EXISTS attributes;
+DROP TYPE IF EXISTS row_policy_identity_type_enum;
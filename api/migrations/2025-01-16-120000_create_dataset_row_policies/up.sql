@@ -0,0 +1,35 @@
+-- Your SQL goes here
+CREATE TYPE row_policy_identity_type_enum AS ENUM ('user', 'team', 'permission_group');
+
+ALTER TABLE users ADD COLUMN attributes JSONB NOT NULL DEFAULT '{}'::jsonb;
+
+CREATE TABLE dataset_row_policies (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    dataset_id UUID NOT NULL REFERENCES datasets(id) ON DELETE CASCADE,
+    organization_id UUID NOT NULL REFERENCES organizations(id),
+    name TEXT NOT NULL,
+    filter TEXT NOT NULL,
+    created_by UUID NOT NULL REFERENCES users(id),
+    updated_by UUID NOT NULL REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deleted_at TIMESTAMPTZ
+);
+
+CREATE INDEX dataset_row_policies_dataset_id_idx ON dataset_row_policies (dataset_id)
+    WHERE deleted_at IS NULL;
+
+CREATE TABLE dataset_row_policies_to_identities (
+    row_policy_id UUID NOT NULL REFERENCES dataset_row_policies(id) ON DELETE CASCADE,
+    identity_id UUID NOT NULL,
+    identity_type row_policy_identity_type_enum NOT NULL,
+    created_by UUID NOT NULL REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deleted_at TIMESTAMPTZ,
+    PRIMARY KEY (row_policy_id, identity_id, identity_type)
+);
+
+CREATE INDEX dataset_row_policies_to_identities_identity_idx
+    ON dataset_row_policies_to_identities (identity_id, identity_type)
+    WHERE deleted_at IS NULL;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS dataset_column_policies;
+DROP TYPE IF EXISTS column_mask_enum;
+DROP TYPE IF EXISTS column_access_enum;
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+CREATE TYPE column_access_enum AS ENUM ('hidden', 'masked', 'clear');
+
+CREATE TYPE column_mask_enum AS ENUM ('hash', 'partial');
+
+CREATE TABLE dataset_column_policies (
+    dataset_column_id UUID NOT NULL REFERENCES dataset_columns(id) ON DELETE CASCADE,
+    permission_group_id UUID NOT NULL REFERENCES permission_groups(id) ON DELETE CASCADE,
+    access column_access_enum NOT NULL,
+    mask column_mask_enum,
+    created_by UUID NOT NULL REFERENCES users(id),
+    updated_by UUID NOT NULL REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deleted_at TIMESTAMPTZ,
+    PRIMARY KEY (dataset_column_id, permission_group_id),
+    CHECK ((access = 'masked') = (mask IS NOT NULL))
+);
+
+CREATE INDEX dataset_column_policies_permission_group_id_idx
+    ON dataset_column_policies (permission_group_id)
+    WHERE deleted_at IS NULL;
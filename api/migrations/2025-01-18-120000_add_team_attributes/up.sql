@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE teams ADD COLUMN attributes JSONB NOT NULL DEFAULT '{}'::jsonb;
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE identity_providers (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    organization_id UUID NOT NULL REFERENCES organizations(id),
+    name TEXT NOT NULL,
+    config JSONB NOT NULL,
+    client_secret_id UUID,
+    default_role user_organization_role_enum NOT NULL DEFAULT 'querier',
+    group_mappings JSONB NOT NULL DEFAULT '[]'::jsonb,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by UUID NOT NULL REFERENCES users(id),
+    updated_by UUID NOT NULL REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deleted_at TIMESTAMPTZ
+);
+
+CREATE INDEX identity_providers_organization_id_idx
+    ON identity_providers (organization_id)
+    WHERE deleted_at IS NULL;
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE llm_providers (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    organization_id UUID NOT NULL REFERENCES organizations(id),
+    config JSONB NOT NULL,
+    api_key_id UUID,
+    created_by UUID NOT NULL REFERENCES users(id),
+    updated_by UUID NOT NULL REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deleted_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX llm_providers_organization_id_idx
+    ON llm_providers (organization_id)
+    WHERE deleted_at IS NULL;
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+CREATE TABLE llm_routes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    organization_id UUID NOT NULL REFERENCES organizations(id),
+    prompt_name TEXT NOT NULL,
+    model JSONB NOT NULL,
+    fallback_model JSONB,
+    temperature REAL,
+    max_tokens INTEGER,
+    created_by UUID NOT NULL REFERENCES users(id),
+    updated_by UUID NOT NULL REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deleted_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX llm_routes_organization_id_prompt_name_idx
+    ON llm_routes (organization_id, prompt_name)
+    WHERE deleted_at IS NULL;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS llm_budgets;
+DROP TABLE IF EXISTS llm_usage;
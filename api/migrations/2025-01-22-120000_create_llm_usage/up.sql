@@ -0,0 +1,36 @@
+-- Your SQL goes here
+CREATE TABLE llm_usage (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    organization_id UUID NOT NULL REFERENCES organizations(id),
+    user_id UUID NOT NULL REFERENCES users(id),
+    -- Not foreign keys, since usage can be recorded before its thread and message are saved.
+    thread_id UUID,
+    message_id UUID,
+    prompt_name TEXT NOT NULL,
+    model TEXT NOT NULL,
+    prompt_tokens INTEGER NOT NULL,
+    completion_tokens INTEGER NOT NULL,
+    -- In US dollars.
+    cost DOUBLE PRECISION NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX llm_usage_organization_id_created_at_idx
+    ON llm_usage (organization_id, created_at);
+
+CREATE TABLE llm_budgets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    organization_id UUID NOT NULL REFERENCES organizations(id),
+    -- US dollars a calendar month, in UTC.
+    soft_limit DOUBLE PRECISION,
+    hard_limit DOUBLE PRECISION,
+    created_by UUID NOT NULL REFERENCES users(id),
+    updated_by UUID NOT NULL REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deleted_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX llm_budgets_organization_id_idx
+    ON llm_budgets (organization_id)
+    WHERE deleted_at IS NULL;
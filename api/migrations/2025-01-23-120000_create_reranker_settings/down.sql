@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS reranker_settings;
+DROP TYPE IF EXISTS reranker_enum;
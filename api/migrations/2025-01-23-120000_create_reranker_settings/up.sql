@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TYPE reranker_enum AS ENUM ('cohere', 'embedding', 'llm', 'bm25');
+
+CREATE TABLE reranker_settings (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    organization_id UUID NOT NULL REFERENCES organizations(id),
+    reranker reranker_enum NOT NULL,
+    created_by UUID NOT NULL REFERENCES users(id),
+    updated_by UUID NOT NULL REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deleted_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX reranker_settings_organization_id_idx
+    ON reranker_settings (organization_id)
+    WHERE deleted_at IS NULL;
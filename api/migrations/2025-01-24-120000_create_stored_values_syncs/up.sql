@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE stored_values_syncs (
+    dataset_column_id UUID PRIMARY KEY REFERENCES dataset_columns(id) ON DELETE CASCADE,
+    data_source_id UUID NOT NULL REFERENCES data_sources(id) ON DELETE CASCADE,
+    claimed_until TIMESTAMPTZ,
+    failed_attempts INTEGER NOT NULL DEFAULT 0,
+    retry_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX stored_values_syncs_data_source_id_idx
+    ON stored_values_syncs (data_source_id)
+    WHERE claimed_until IS NOT NULL;
@@ -1,26 +1,53 @@
 use anyhow::{anyhow, Result};
 use std::{collections::HashMap, env};
 
-use axum::{extract::Request, http::StatusCode, middleware::Next, response::Response};
+use axum::{
+    extract::{OriginalUri, Request},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
 use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::database::{lib::get_pg_pool, models::User};
+use crate::{
+    database::{lib::get_pg_pool, models::User},
+    utils::{
+        audit::audit_log::AuditRequestMetadata,
+        security::{
+            api_keys::{
+                client_ip, find_api_key, peer_ip, record_api_key_use, required_rest_scope,
+                ApiKeyAccess, API_KEY_PREFIX,
+            },
+            checks::is_user_deactivated,
+        },
+    },
+};
 
 /// Authentication is done via Bearer token with a JWT issued from Supabase.  We also offer API access that
-/// is done via a Bearer token with a JWT issued from us.
+/// is done via a Bearer token with an API key issued from us.
 ///
 /// The user ID is always included as the `sub` in the JWT.
 ///
-/// In the JWT that we issue, we provide an extra field of `api_key_id` that contains the ID of the API key that is being used.
-/// The reason why we have the api_key_id is because a user could have multiple API keys and we want to be able to route to the correct key and track it.
+/// API keys start with `bst_`. Keys issued before that are JWTs with an `api` audience. Either way the key is
+/// looked up by its hash, and the request is only let through if the key has the scope the route needs. The
+/// key's scopes are added to the request as `ApiKeyAccess` so the websocket can check them per message.
+///
+/// Every authenticated request also gets an `AuditRequestMetadata` for routes that write audit events.
+///
+/// Users deactivated in every organization they belong to, e.g. by SCIM, are turned away whatever they
+/// authenticate with. Sessions from an organization's identity provider are also turned away once the user
+/// is no longer active in that organization.
 
 #[derive(Serialize, Deserialize, Debug)]
 struct JwtClaims {
     pub aud: String,
     pub sub: String,
     pub exp: u64,
+    /// Set on sessions from signing in with an organization's identity provider.
+    #[serde(default)]
+    pub organization_id: Option<Uuid>,
 }
 
 pub async fn auth(mut req: Request, next: Next) -> Result<Response, StatusCode> {
@@ -45,8 +72,6 @@ pub async fn auth(mut req: Request, next: Next) -> Result<Response, StatusCode>
         }
     };
 
-    let buster_wh_token = env::var("BUSTER_WH_TOKEN").expect("BUSTER_WH_TOKEN is not set");
-
     let bearer_token = req.headers().get("Authorization").and_then(|value| {
         value.to_str().ok().and_then(|v| {
             if v.starts_with("Bearer ") {
@@ -57,12 +82,6 @@ pub async fn auth(mut req: Request, next: Next) -> Result<Response, StatusCode>
         })
     });
 
-    if let Some(token) = bearer_token {
-        if token == buster_wh_token {
-            return Ok(next.run(req).await);
-        }
-    }
-
     let token = if bearer_token.is_none() {
         match req
             .uri()
@@ -80,7 +99,8 @@ pub async fn auth(mut req: Request, next: Next) -> Result<Response, StatusCode>
         bearer_token.unwrap().to_string()
     };
 
-    let user = match authorize_current_user(&token).await {
+    let (user, api_key_access, session_organization_id) = match authorize_current_user(&token).await
+    {
         Ok(user) => match user {
             Some(user) => user,
             None => return Err(StatusCode::UNAUTHORIZED),
@@ -91,17 +111,67 @@ pub async fn auth(mut req: Request, next: Next) -> Result<Response, StatusCode>
         }
     };
 
+    match is_user_deactivated(&user.id, session_organization_id.as_ref()).await {
+        Ok(false) => (),
+        Ok(true) => return handle_auth_error("user is deactivated"),
+        Err(e) => {
+            tracing::error!("Error checking if user is deactivated: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    let path = match req.extensions().get::<OriginalUri>() {
+        Some(original_uri) => original_uri.path().to_string(),
+        None => req.uri().path().to_string(),
+    };
+
+    let request_metadata = AuditRequestMetadata::rest(
+        req.method(),
+        &path,
+        req.headers(),
+        peer_ip(&req),
+        api_key_access
+            .as_ref()
+            .map(|api_key_access| api_key_access.api_key_id),
+    );
+
+    if let Some(api_key_access) = api_key_access {
+        if let Some(scope) = required_rest_scope(req.method(), &path) {
+            if !api_key_access.allows(&scope) {
+                tracing::warn!(
+                    "API key {} is missing the {:?} scope for {}",
+                    api_key_access.api_key_id,
+                    scope,
+                    path
+                );
+                return Err(StatusCode::FORBIDDEN);
+            }
+        }
+
+        let api_key_id = api_key_access.api_key_id;
+        let ip = client_ip(req.headers(), peer_ip(&req));
+        tokio::spawn(async move {
+            if let Err(e) = record_api_key_use(&api_key_id, ip).await {
+                tracing::error!("Error recording API key use: {}", e);
+            }
+        });
+
+        req.extensions_mut().insert(api_key_access);
+    }
+
+    req.extensions_mut().insert(request_metadata);
     req.extensions_mut().insert(user);
     Ok(next.run(req).await)
 }
 
-async fn authorize_current_user(token: &str) -> Result<Option<User>> {
+async fn authorize_current_user(
+    token: &str,
+) -> Result<Option<(User, Option<ApiKeyAccess>, Option<Uuid>)>> {
+    if token.starts_with(API_KEY_PREFIX) {
+        return authorize_api_key(token).await;
+    }
+
     let pg_pool = get_pg_pool();
-    
-    let _conn = pg_pool.get().await.map_err(|e| {
-        tracing::error!("Pool connection error in auth: {:?}", e);
-        anyhow!("Database connection error in auth")
-    })?;
 
     let key = env::var("JWT_SECRET").expect("JWT_SECRET is not set");
 
@@ -116,12 +186,11 @@ async fn authorize_current_user(token: &str) -> Result<Option<User>> {
             }
         };
 
-    let user = match token_data.aud.contains("api") {
-        true => User::find_by_api_key(token, &pg_pool).await,
-        false => User::find_by_id(&Uuid::parse_str(&token_data.sub).unwrap(), &pg_pool).await,
-    };
+    if token_data.aud.contains("api") {
+        return authorize_api_key(token).await;
+    }
 
-    let user = match user {
+    let user = match User::find_by_id(&Uuid::parse_str(&token_data.sub)?, &pg_pool).await {
         Ok(user) => user,
         Err(e) => {
             tracing::error!("Error while querying user: {}", e);
@@ -129,5 +198,21 @@ async fn authorize_current_user(token: &str) -> Result<Option<User>> {
         }
     };
 
-    Ok(user)
+    Ok(user.map(|user| (user, None, token_data.organization_id)))
+}
+
+async fn authorize_api_key(
+    api_key: &str,
+) -> Result<Option<(User, Option<ApiKeyAccess>, Option<Uuid>)>> {
+    match find_api_key(api_key).await {
+        Ok(Some((user, api_key))) => {
+            let api_key_access = ApiKeyAccess::from_api_key(&api_key);
+            Ok(Some((user, Some(api_key_access), None)))
+        }
+        Ok(None) => Ok(None),
+        Err(e) => {
+            tracing::error!("Error while querying API key: {}", e);
+            Err(anyhow!("Error while querying API key: {}", e))
+        }
+    }
 }
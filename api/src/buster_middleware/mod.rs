@@ -1,2 +1,4 @@
 pub mod auth;
-pub mod cors;
\ No newline at end of file
+pub mod cors;
+pub mod scim_auth;
+pub mod webhook_auth;
\ No newline at end of file
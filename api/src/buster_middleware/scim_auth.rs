@@ -0,0 +1,69 @@
+use axum::{extract::Request, http::StatusCode, middleware::Next, response::Response};
+use uuid::Uuid;
+
+use crate::utils::security::{
+    api_keys::{
+        client_ip, find_api_key, peer_ip, record_api_key_use, ApiKeyAccess, ApiKeyScope,
+        API_KEY_PREFIX,
+    },
+    checks::is_user_workspace_admin,
+};
+
+/// Identity providers authenticate to the SCIM endpoints with an API key that has the `scim`
+/// scope. The key only provisions the organization it was issued in, and only while its owner is
+/// still a workspace admin there. Changes are made on behalf of the key's owner.
+#[derive(Debug, Clone)]
+pub struct ScimContext {
+    pub organization_id: Uuid,
+    pub actor_id: Uuid,
+}
+
+pub async fn scim_auth(mut req: Request, next: Next) -> Result<Response, StatusCode> {
+    let token = match req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        Some(token) if token.trim().starts_with(API_KEY_PREFIX) => token.trim().to_string(),
+        _ => return Err(StatusCode::UNAUTHORIZED),
+    };
+
+    let (user, api_key) = match find_api_key(&token).await {
+        Ok(Some(user_and_api_key)) => user_and_api_key,
+        Ok(None) => return Err(StatusCode::UNAUTHORIZED),
+        Err(e) => {
+            tracing::error!("Error while querying API key: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    if !ApiKeyAccess::from_api_key(&api_key).allows(&ApiKeyScope::Scim) {
+        tracing::warn!("API key {} is missing the scim scope", api_key.id);
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err(StatusCode::FORBIDDEN),
+        Err(e) => {
+            tracing::warn!("SCIM key owner {} is not an active member: {}", user.id, e);
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+
+    let api_key_id = api_key.id;
+    let ip = client_ip(req.headers(), peer_ip(&req));
+    tokio::spawn(async move {
+        if let Err(e) = record_api_key_use(&api_key_id, ip).await {
+            tracing::error!("Error recording API key use: {}", e);
+        }
+    });
+
+    req.extensions_mut().insert(ScimContext {
+        organization_id: api_key.organization_id,
+        actor_id: user.id,
+    });
+
+    Ok(next.run(req).await)
+}
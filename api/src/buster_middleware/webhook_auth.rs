@@ -0,0 +1,79 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+
+use crate::utils::security::webhook_signatures::{
+    claim_webhook_delivery, verify_webhook_signature, WebhookIntegration, WebhookSignatureError,
+    WEBHOOK_SIGNATURE_HEADER,
+};
+
+/// Webhook bodies are small records, anything bigger isn't one of ours.
+const MAX_WEBHOOK_BODY_BYTES: usize = 1024 * 1024;
+
+/// Webhooks aren't called by users, so instead of `auth` they are authenticated by an HMAC
+/// signature of the body made with the integration's signing key. Each signed request is only
+/// accepted once.
+pub async fn webhook_auth(
+    State(integration): State<WebhookIntegration>,
+    req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let header = match req
+        .headers()
+        .get(WEBHOOK_SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(header) => header.to_string(),
+        None => return Err(StatusCode::UNAUTHORIZED),
+    };
+
+    let (parts, body) = req.into_parts();
+
+    let body = match to_bytes(body, MAX_WEBHOOK_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => return Err(StatusCode::PAYLOAD_TOO_LARGE),
+    };
+
+    let timestamp = match verify_webhook_signature(
+        &integration.signing_keys(),
+        &header,
+        &body,
+        Utc::now().timestamp(),
+    ) {
+        Ok(timestamp) => timestamp,
+        Err(WebhookSignatureError::NoSigningKeys) => {
+            tracing::error!(
+                "No signing keys are configured for the {} webhook",
+                integration.as_str()
+            );
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        Err(e) => {
+            tracing::warn!(
+                "Rejected {} webhook with signature error {:?}",
+                integration.as_str(),
+                e
+            );
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    };
+
+    match claim_webhook_delivery(integration, timestamp, &body).await {
+        Ok(true) => (),
+        Ok(false) => {
+            tracing::warn!("Rejected replayed {} webhook", integration.as_str());
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        Err(e) => {
+            tracing::error!("Error checking for webhook replay: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    Ok(next.run(Request::from_parts(parts, Body::from(body))).await)
+}
@@ -436,28 +436,36 @@ impl FromSql<sql_types::VerificationEnum, Pg> for Verification {
 #[serde(rename_all = "lowercase")]
 pub enum DataSourceType {
     BigQuery,
+    ClickHouse,
     Databricks,
+    DuckDb,
     MySql,
     Mariadb,
     Postgres,
     Redshift,
     Snowflake,
     SqlServer,
+    StarRocks,
     Supabase,
+    Trino,
 }
 
 impl DataSourceType {
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "bigquery" => Some(DataSourceType::BigQuery),
+            "clickhouse" => Some(DataSourceType::ClickHouse),
             "databricks" => Some(DataSourceType::Databricks),
+            "duckdb" => Some(DataSourceType::DuckDb),
             "mysql" => Some(DataSourceType::MySql),
             "mariadb" => Some(DataSourceType::Mariadb),
             "postgres" => Some(DataSourceType::Postgres),
             "redshift" => Some(DataSourceType::Redshift),
             "snowflake" => Some(DataSourceType::Snowflake),
             "sqlserver" => Some(DataSourceType::SqlServer),
+            "starrocks" => Some(DataSourceType::StarRocks),
             "supabase" => Some(DataSourceType::Supabase),
+            "trino" => Some(DataSourceType::Trino),
             _ => None,
         }
     }
@@ -465,14 +473,18 @@ impl DataSourceType {
     pub fn to_string(&self) -> &'static str {
         match *self {
             DataSourceType::BigQuery => "bigquery",
+            DataSourceType::ClickHouse => "clickhouse",
             DataSourceType::Databricks => "databricks",
+            DataSourceType::DuckDb => "duckdb",
             DataSourceType::MySql => "mysql",
             DataSourceType::Mariadb => "mariadb",
             DataSourceType::Postgres => "postgres",
             DataSourceType::Redshift => "redshift",
             DataSourceType::Snowflake => "snowflake",
             DataSourceType::SqlServer => "sqlserver",
+            DataSourceType::StarRocks => "starrocks",
             DataSourceType::Supabase => "supabase",
+            DataSourceType::Trino => "trino",
         }
     }
 }
@@ -481,14 +493,18 @@ impl ToSql<Text, Pg> for DataSourceType {
     fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
         match *self {
             DataSourceType::BigQuery => out.write_all(b"bigquery")?,
+            DataSourceType::ClickHouse => out.write_all(b"clickhouse")?,
             DataSourceType::Databricks => out.write_all(b"databricks")?,
+            DataSourceType::DuckDb => out.write_all(b"duckdb")?,
             DataSourceType::MySql => out.write_all(b"mysql")?,
             DataSourceType::Mariadb => out.write_all(b"mariadb")?,
             DataSourceType::Postgres => out.write_all(b"postgres")?,
             DataSourceType::Redshift => out.write_all(b"redshift")?,
             DataSourceType::Snowflake => out.write_all(b"snowflake")?,
             DataSourceType::SqlServer => out.write_all(b"sqlserver")?,
+            DataSourceType::StarRocks => out.write_all(b"starrocks")?,
             DataSourceType::Supabase => out.write_all(b"supabase")?,
+            DataSourceType::Trino => out.write_all(b"trino")?,
         }
         Ok(IsNull::No)
     }
@@ -498,14 +514,18 @@ impl FromSql<Text, Pg> for DataSourceType {
     fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
         match bytes.as_bytes() {
             b"bigquery" => Ok(DataSourceType::BigQuery),
+            b"clickhouse" => Ok(DataSourceType::ClickHouse),
             b"databricks" => Ok(DataSourceType::Databricks),
+            b"duckdb" => Ok(DataSourceType::DuckDb),
             b"mysql" => Ok(DataSourceType::MySql),
             b"mariadb" => Ok(DataSourceType::Mariadb),
             b"postgres" => Ok(DataSourceType::Postgres),
             b"redshift" => Ok(DataSourceType::Redshift),
             b"snowflake" => Ok(DataSourceType::Snowflake),
             b"sqlserver" => Ok(DataSourceType::SqlServer),
+            b"starrocks" => Ok(DataSourceType::StarRocks),
             b"supabase" => Ok(DataSourceType::Supabase),
+            b"trino" => Ok(DataSourceType::Trino),
             _ => Err("Unrecognized enum variant".into()),
         }
     }
@@ -645,3 +665,340 @@ impl FromSql<sql_types::MessageFeedbackEnum, Pg> for MessageFeedback {
         }
     }
 }
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    diesel::AsExpression,
+    diesel::FromSqlRow,
+)]
+#[diesel(sql_type = sql_types::MessageVersionAuthorEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum MessageVersionAuthor {
+    User,
+    Agent,
+}
+
+impl ToSql<sql_types::MessageVersionAuthorEnum, Pg> for MessageVersionAuthor {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            MessageVersionAuthor::User => out.write_all(b"user")?,
+            MessageVersionAuthor::Agent => out.write_all(b"agent")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::MessageVersionAuthorEnum, Pg> for MessageVersionAuthor {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"user" => Ok(MessageVersionAuthor::User),
+            b"agent" => Ok(MessageVersionAuthor::Agent),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    diesel::AsExpression,
+    diesel::FromSqlRow,
+)]
+#[diesel(sql_type = sql_types::AuditActionEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditAction {
+    UserPermissionsUpdated,
+    TeamPermissionsUpdated,
+    PermissionGroupUpdated,
+    AssetPermissionsUpdated,
+    AssetPubliclyShared,
+    DataSourceCreated,
+    DataSourceUpdated,
+    DataSourceDeleted,
+    DatasetsDeployed,
+    SqlRun,
+}
+
+impl ToSql<sql_types::AuditActionEnum, Pg> for AuditAction {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            AuditAction::UserPermissionsUpdated => out.write_all(b"user_permissions_updated")?,
+            AuditAction::TeamPermissionsUpdated => out.write_all(b"team_permissions_updated")?,
+            AuditAction::PermissionGroupUpdated => out.write_all(b"permission_group_updated")?,
+            AuditAction::AssetPermissionsUpdated => out.write_all(b"asset_permissions_updated")?,
+            AuditAction::AssetPubliclyShared => out.write_all(b"asset_publicly_shared")?,
+            AuditAction::DataSourceCreated => out.write_all(b"data_source_created")?,
+            AuditAction::DataSourceUpdated => out.write_all(b"data_source_updated")?,
+            AuditAction::DataSourceDeleted => out.write_all(b"data_source_deleted")?,
+            AuditAction::DatasetsDeployed => out.write_all(b"datasets_deployed")?,
+            AuditAction::SqlRun => out.write_all(b"sql_run")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::AuditActionEnum, Pg> for AuditAction {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"user_permissions_updated" => Ok(AuditAction::UserPermissionsUpdated),
+            b"team_permissions_updated" => Ok(AuditAction::TeamPermissionsUpdated),
+            b"permission_group_updated" => Ok(AuditAction::PermissionGroupUpdated),
+            b"asset_permissions_updated" => Ok(AuditAction::AssetPermissionsUpdated),
+            b"asset_publicly_shared" => Ok(AuditAction::AssetPubliclyShared),
+            b"data_source_created" => Ok(AuditAction::DataSourceCreated),
+            b"data_source_updated" => Ok(AuditAction::DataSourceUpdated),
+            b"data_source_deleted" => Ok(AuditAction::DataSourceDeleted),
+            b"datasets_deployed" => Ok(AuditAction::DatasetsDeployed),
+            b"sql_run" => Ok(AuditAction::SqlRun),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    diesel::AsExpression,
+    diesel::FromSqlRow,
+)]
+#[diesel(sql_type = sql_types::AuditTargetTypeEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditTargetType {
+    User,
+    Team,
+    PermissionGroup,
+    Dashboard,
+    Thread,
+    Collection,
+    DataSource,
+    Dataset,
+}
+
+impl From<AssetType> for AuditTargetType {
+    fn from(asset_type: AssetType) -> Self {
+        match asset_type {
+            AssetType::Dashboard => AuditTargetType::Dashboard,
+            AssetType::Thread => AuditTargetType::Thread,
+            AssetType::Collection => AuditTargetType::Collection,
+        }
+    }
+}
+
+impl ToSql<sql_types::AuditTargetTypeEnum, Pg> for AuditTargetType {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            AuditTargetType::User => out.write_all(b"user")?,
+            AuditTargetType::Team => out.write_all(b"team")?,
+            AuditTargetType::PermissionGroup => out.write_all(b"permission_group")?,
+            AuditTargetType::Dashboard => out.write_all(b"dashboard")?,
+            AuditTargetType::Thread => out.write_all(b"thread")?,
+            AuditTargetType::Collection => out.write_all(b"collection")?,
+            AuditTargetType::DataSource => out.write_all(b"data_source")?,
+            AuditTargetType::Dataset => out.write_all(b"dataset")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::AuditTargetTypeEnum, Pg> for AuditTargetType {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"user" => Ok(AuditTargetType::User),
+            b"team" => Ok(AuditTargetType::Team),
+            b"permission_group" => Ok(AuditTargetType::PermissionGroup),
+            b"dashboard" => Ok(AuditTargetType::Dashboard),
+            b"thread" => Ok(AuditTargetType::Thread),
+            b"collection" => Ok(AuditTargetType::Collection),
+            b"data_source" => Ok(AuditTargetType::DataSource),
+            b"dataset" => Ok(AuditTargetType::Dataset),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    diesel::AsExpression,
+    diesel::FromSqlRow,
+)]
+#[diesel(sql_type = sql_types::RowPolicyIdentityTypeEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum RowPolicyIdentityType {
+    User,
+    Team,
+    PermissionGroup,
+}
+
+impl ToSql<sql_types::RowPolicyIdentityTypeEnum, Pg> for RowPolicyIdentityType {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            RowPolicyIdentityType::User => out.write_all(b"user")?,
+            RowPolicyIdentityType::Team => out.write_all(b"team")?,
+            RowPolicyIdentityType::PermissionGroup => out.write_all(b"permission_group")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::RowPolicyIdentityTypeEnum, Pg> for RowPolicyIdentityType {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"user" => Ok(RowPolicyIdentityType::User),
+            b"team" => Ok(RowPolicyIdentityType::Team),
+            b"permission_group" => Ok(RowPolicyIdentityType::PermissionGroup),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    diesel::AsExpression,
+    diesel::FromSqlRow,
+)]
+#[diesel(sql_type = sql_types::ColumnAccessEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum ColumnAccess {
+    Hidden,
+    Masked,
+    Clear,
+}
+
+impl ToSql<sql_types::ColumnAccessEnum, Pg> for ColumnAccess {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            ColumnAccess::Hidden => out.write_all(b"hidden")?,
+            ColumnAccess::Masked => out.write_all(b"masked")?,
+            ColumnAccess::Clear => out.write_all(b"clear")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::ColumnAccessEnum, Pg> for ColumnAccess {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"hidden" => Ok(ColumnAccess::Hidden),
+            b"masked" => Ok(ColumnAccess::Masked),
+            b"clear" => Ok(ColumnAccess::Clear),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    diesel::AsExpression,
+    diesel::FromSqlRow,
+)]
+#[diesel(sql_type = sql_types::ColumnMaskEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum ColumnMask {
+    /// The value is replaced by a hash keyed per organization, so it can still be grouped and
+    /// joined on.
+    Hash,
+    /// Only the last four characters are shown.
+    Partial,
+}
+
+impl ToSql<sql_types::ColumnMaskEnum, Pg> for ColumnMask {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            ColumnMask::Hash => out.write_all(b"hash")?,
+            ColumnMask::Partial => out.write_all(b"partial")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::ColumnMaskEnum, Pg> for ColumnMask {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"hash" => Ok(ColumnMask::Hash),
+            b"partial" => Ok(ColumnMask::Partial),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    diesel::AsExpression,
+    diesel::FromSqlRow,
+)]
+#[diesel(sql_type = sql_types::RerankerEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum Reranker {
+    /// Cohere's hosted rerank model.
+    Cohere,
+    /// Cosine similarity between embeddings from the configured embedding provider.
+    Embedding,
+    /// The LLM ranks the datasets itself, all at once.
+    Llm,
+    /// Keyword scoring that runs locally and never fails.
+    Bm25,
+}
+
+impl ToSql<sql_types::RerankerEnum, Pg> for Reranker {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            Reranker::Cohere => out.write_all(b"cohere")?,
+            Reranker::Embedding => out.write_all(b"embedding")?,
+            Reranker::Llm => out.write_all(b"llm")?,
+            Reranker::Bm25 => out.write_all(b"bm25")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::RerankerEnum, Pg> for Reranker {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"cohere" => Ok(Reranker::Cohere),
+            b"embedding" => Ok(Reranker::Embedding),
+            b"llm" => Ok(Reranker::Llm),
+            b"bm25" => Ok(Reranker::Bm25),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
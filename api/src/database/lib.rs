@@ -54,6 +54,16 @@ pub async fn init_pools() -> Result<()> {
     Ok(())
 }
 
+/// Sets up only the diesel pool, for tests that run against the local database.
+#[cfg(test)]
+pub async fn init_test_pg_pool() -> Result<()> {
+    if DIESEL_POOL.get().is_none() {
+        let _ = DIESEL_POOL.set(establish_diesel_connection().await?);
+    }
+
+    Ok(())
+}
+
 pub fn get_pg_pool() -> &'static PgPool {
     DIESEL_POOL.get().expect("DieselPool not initialized")
 }
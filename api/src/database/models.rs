@@ -12,14 +12,36 @@ use uuid::Uuid;
 pub struct ApiKey {
     pub id: Uuid,
     pub owner_id: Uuid,
-    pub key: String,
+    pub key_hash: String,
     pub organization_id: Uuid,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub deleted_at: Option<DateTime<Utc>>,
+    pub key_prefix: String,
+    pub name: Option<String>,
+    pub scopes: Value,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub last_used_ip: Option<String>,
 }
 
-#[derive(Queryable, Identifiable, Associations, Debug)]
+#[derive(Queryable, Insertable, Identifiable, Associations, Debug, Clone, Serialize)]
+#[diesel(belongs_to(User, foreign_key = actor_id))]
+#[diesel(table_name = audit_events)]
+pub struct AuditEvent {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub actor_id: Uuid,
+    pub action: AuditAction,
+    pub target_type: AuditTargetType,
+    pub target_id: Option<Uuid>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub request_metadata: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Insertable, Identifiable, Associations, Debug, Clone, Serialize)]
 #[diesel(belongs_to(Dashboard, foreign_key = dashboard_id))]
 #[diesel(table_name = dashboard_versions)]
 pub struct DashboardVersion {
@@ -29,6 +51,9 @@ pub struct DashboardVersion {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub deleted_at: Option<DateTime<Utc>>,
+    pub thread_ids: Value,
+    pub summary: Option<String>,
+    pub created_by: Option<Uuid>,
 }
 
 #[derive(
@@ -85,6 +110,7 @@ pub struct DataSource {
     pub updated_at: DateTime<Utc>,
     pub deleted_at: Option<DateTime<Utc>>,
     pub env: String,
+    pub max_execution_time_seconds: Option<i32>,
 }
 
 #[derive(
@@ -161,6 +187,53 @@ pub struct Dataset {
     pub deleted_at: Option<DateTime<Utc>>,
     pub model: Option<String>,
     pub yml_file: Option<String>,
+    pub cache_ttl_seconds: Option<i32>,
+}
+
+#[derive(Queryable, Insertable, Associations, Debug, Clone, Serialize)]
+#[diesel(belongs_to(DatasetColumn, foreign_key = dataset_column_id))]
+#[diesel(belongs_to(PermissionGroup, foreign_key = permission_group_id))]
+#[diesel(table_name = dataset_column_policies)]
+pub struct DatasetColumnPolicy {
+    pub dataset_column_id: Uuid,
+    pub permission_group_id: Uuid,
+    pub access: ColumnAccess,
+    /// Set only when `access` is `Masked`.
+    pub mask: Option<ColumnMask>,
+    pub created_by: Uuid,
+    pub updated_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Queryable, Insertable, Identifiable, Associations, Debug, Clone, Serialize)]
+#[diesel(belongs_to(Dataset, foreign_key = dataset_id))]
+#[diesel(table_name = dataset_row_policies)]
+pub struct DatasetRowPolicy {
+    pub id: Uuid,
+    pub dataset_id: Uuid,
+    pub organization_id: Uuid,
+    pub name: String,
+    pub filter: String,
+    pub created_by: Uuid,
+    pub updated_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Queryable, Insertable, Associations, Debug, Clone, Serialize)]
+#[diesel(belongs_to(DatasetRowPolicy, foreign_key = row_policy_id))]
+#[diesel(table_name = dataset_row_policies_to_identities)]
+pub struct DatasetRowPolicyToIdentity {
+    pub row_policy_id: Uuid,
+    pub identity_id: Uuid,
+    pub identity_type: RowPolicyIdentityType,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Insertable, Queryable, Associations, Debug)]
@@ -222,6 +295,8 @@ pub struct Team {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub deleted_at: Option<DateTime<Utc>>,
+    /// Attributes inherited by the team's members, unless a member sets the key themselves.
+    pub attributes: Value,
 }
 
 #[derive(Queryable, Insertable, Associations, Debug)]
@@ -248,6 +323,108 @@ pub struct User {
     pub config: Value,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Values such as a sales territory that policies and templated SQL refer to as
+    /// `{{user.attributes.<key>}}`. They override the attributes inherited from the user's teams.
+    pub attributes: Value,
+}
+
+#[derive(Queryable, Insertable, Identifiable, Associations, Debug, Clone, Serialize)]
+#[diesel(belongs_to(Organization))]
+#[diesel(table_name = identity_providers)]
+pub struct IdentityProvider {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub name: String,
+    /// An `IdentityProviderConfig`, tagged with its protocol.
+    pub config: Value,
+    /// Vault secret holding the OIDC client secret.
+    #[serde(skip_serializing)]
+    pub client_secret_id: Option<Uuid>,
+    /// The role users get when they first sign in with the provider.
+    pub default_role: UserOrganizationRole,
+    /// `SsoGroupMapping`s from the provider's groups to teams.
+    pub group_mappings: Value,
+    pub enabled: bool,
+    pub created_by: Uuid,
+    pub updated_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Queryable, Insertable, Identifiable, Associations, Debug, Clone, Serialize)]
+#[diesel(belongs_to(Organization))]
+#[diesel(table_name = llm_budgets)]
+pub struct LlmBudget {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    /// US dollars a month after which admins are warned.
+    pub soft_limit: Option<f64>,
+    /// US dollars a month after which LLM calls are refused.
+    pub hard_limit: Option<f64>,
+    pub created_by: Uuid,
+    pub updated_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Queryable, Insertable, Identifiable, Associations, Debug, Clone, Serialize)]
+#[diesel(belongs_to(Organization))]
+#[diesel(table_name = llm_providers)]
+pub struct LlmProvider {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    /// An `LlmProviderConfig`, tagged with its provider.
+    pub config: Value,
+    /// Vault secret holding the key for servers that want one.
+    #[serde(skip_serializing)]
+    pub api_key_id: Option<Uuid>,
+    pub created_by: Uuid,
+    pub updated_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Queryable, Insertable, Identifiable, Associations, Debug, Clone, Serialize)]
+#[diesel(belongs_to(Organization))]
+#[diesel(table_name = llm_routes)]
+pub struct LlmRoute {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    /// The prompt it routes, e.g. `generate_sql`, or `default` for every prompt without a route.
+    pub prompt_name: String,
+    /// An `LlmModelChoice`.
+    pub model: Value,
+    /// Tried when the model fails.
+    pub fallback_model: Option<Value>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<i32>,
+    pub created_by: Uuid,
+    pub updated_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Queryable, Insertable, Identifiable, Associations, Debug, Clone, Serialize)]
+#[diesel(belongs_to(Organization))]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = llm_usage)]
+pub struct LlmUsage {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub user_id: Uuid,
+    pub thread_id: Option<Uuid>,
+    pub message_id: Option<Uuid>,
+    pub prompt_name: String,
+    pub model: String,
+    pub prompt_tokens: i32,
+    pub completion_tokens: i32,
+    /// In US dollars.
+    pub cost: f64,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(
@@ -297,6 +474,26 @@ pub struct Message {
     pub sql_evaluation_id: Option<Uuid>,
 }
 
+#[derive(Queryable, Insertable, Identifiable, Associations, Debug, Clone, Serialize)]
+#[diesel(belongs_to(Message, foreign_key = message_id))]
+#[diesel(table_name = message_versions)]
+pub struct MessageVersion {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub version_number: i32,
+    #[serde(rename = "sql")]
+    pub code: Option<String>,
+    pub chart_config: Option<Value>,
+    pub title: Option<String>,
+    pub time_frame: Option<String>,
+    pub author_type: MessageVersionAuthor,
+    pub created_by: Option<Uuid>,
+    pub summary: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Selectable, Queryable, Insertable, Identifiable, Associations, Debug, Serialize)]
 #[diesel(belongs_to(User, foreign_key = created_by, foreign_key = updated_by))]
 #[diesel(table_name = permission_groups)]
@@ -311,6 +508,20 @@ pub struct PermissionGroup {
     pub deleted_at: Option<DateTime<Utc>>,
 }
 
+#[derive(Queryable, Insertable, Identifiable, Associations, Debug, Clone, Serialize)]
+#[diesel(belongs_to(Organization))]
+#[diesel(table_name = reranker_settings)]
+pub struct RerankerSetting {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub reranker: Reranker,
+    pub created_by: Uuid,
+    pub updated_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
 #[derive(
     Serialize,
     Insertable,
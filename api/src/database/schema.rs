@@ -9,6 +9,22 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "asset_type_enum"))]
     pub struct AssetTypeEnum;
 
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "audit_action_enum"))]
+    pub struct AuditActionEnum;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "audit_target_type_enum"))]
+    pub struct AuditTargetTypeEnum;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "column_access_enum"))]
+    pub struct ColumnAccessEnum;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "column_mask_enum"))]
+    pub struct ColumnMaskEnum;
+
     #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "data_source_onboarding_status_enum"))]
     pub struct DataSourceOnboardingStatusEnum;
@@ -25,6 +41,18 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "message_feedback_enum"))]
     pub struct MessageFeedbackEnum;
 
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "message_version_author_enum"))]
+    pub struct MessageVersionAuthorEnum;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "reranker_enum"))]
+    pub struct RerankerEnum;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "row_policy_identity_type_enum"))]
+    pub struct RowPolicyIdentityTypeEnum;
+
     #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "sharing_setting_enum"))]
     pub struct SharingSettingEnum;
@@ -54,11 +82,17 @@ diesel::table! {
     api_keys (id) {
         id -> Uuid,
         owner_id -> Uuid,
-        key -> Text,
+        key_hash -> Text,
         organization_id -> Uuid,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
         deleted_at -> Nullable<Timestamptz>,
+        key_prefix -> Text,
+        name -> Nullable<Text>,
+        scopes -> Jsonb,
+        expires_at -> Nullable<Timestamptz>,
+        last_used_at -> Nullable<Timestamptz>,
+        last_used_ip -> Nullable<Text>,
     }
 }
 
@@ -82,6 +116,25 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::AuditActionEnum;
+    use super::sql_types::AuditTargetTypeEnum;
+
+    audit_events (id) {
+        id -> Uuid,
+        organization_id -> Uuid,
+        actor_id -> Uuid,
+        action -> AuditActionEnum,
+        target_type -> AuditTargetTypeEnum,
+        target_id -> Nullable<Uuid>,
+        before -> Nullable<Jsonb>,
+        after -> Nullable<Jsonb>,
+        request_metadata -> Jsonb,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     collections (id) {
         id -> Uuid,
@@ -120,6 +173,9 @@ diesel::table! {
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
         deleted_at -> Nullable<Timestamptz>,
+        thread_ids -> Jsonb,
+        summary -> Nullable<Text>,
+        created_by -> Nullable<Uuid>,
     }
 }
 
@@ -161,6 +217,25 @@ diesel::table! {
         updated_at -> Timestamptz,
         deleted_at -> Nullable<Timestamptz>,
         env -> Varchar,
+        max_execution_time_seconds -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ColumnAccessEnum;
+    use super::sql_types::ColumnMaskEnum;
+
+    dataset_column_policies (dataset_column_id, permission_group_id) {
+        dataset_column_id -> Uuid,
+        permission_group_id -> Uuid,
+        access -> ColumnAccessEnum,
+        mask -> Nullable<ColumnMaskEnum>,
+        created_by -> Uuid,
+        updated_by -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -214,6 +289,36 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    dataset_row_policies (id) {
+        id -> Uuid,
+        dataset_id -> Uuid,
+        organization_id -> Uuid,
+        name -> Text,
+        filter -> Text,
+        created_by -> Uuid,
+        updated_by -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::RowPolicyIdentityTypeEnum;
+
+    dataset_row_policies_to_identities (row_policy_id, identity_id, identity_type) {
+        row_policy_id -> Uuid,
+        identity_id -> Uuid,
+        identity_type -> RowPolicyIdentityTypeEnum,
+        created_by -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::DatasetTypeEnum;
@@ -239,6 +344,7 @@ diesel::table! {
         deleted_at -> Nullable<Timestamptz>,
         model -> Nullable<Text>,
         yml_file -> Nullable<Text>,
+        cache_ttl_seconds -> Nullable<Int4>,
     }
 }
 
@@ -269,6 +375,109 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::UserOrganizationRoleEnum;
+
+    identity_providers (id) {
+        id -> Uuid,
+        organization_id -> Uuid,
+        name -> Text,
+        config -> Jsonb,
+        client_secret_id -> Nullable<Uuid>,
+        default_role -> UserOrganizationRoleEnum,
+        group_mappings -> Jsonb,
+        enabled -> Bool,
+        created_by -> Uuid,
+        updated_by -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    llm_budgets (id) {
+        id -> Uuid,
+        organization_id -> Uuid,
+        soft_limit -> Nullable<Float8>,
+        hard_limit -> Nullable<Float8>,
+        created_by -> Uuid,
+        updated_by -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    llm_providers (id) {
+        id -> Uuid,
+        organization_id -> Uuid,
+        config -> Jsonb,
+        api_key_id -> Nullable<Uuid>,
+        created_by -> Uuid,
+        updated_by -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    llm_routes (id) {
+        id -> Uuid,
+        organization_id -> Uuid,
+        prompt_name -> Text,
+        model -> Jsonb,
+        fallback_model -> Nullable<Jsonb>,
+        temperature -> Nullable<Float4>,
+        max_tokens -> Nullable<Int4>,
+        created_by -> Uuid,
+        updated_by -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    llm_usage (id) {
+        id -> Uuid,
+        organization_id -> Uuid,
+        user_id -> Uuid,
+        thread_id -> Nullable<Uuid>,
+        message_id -> Nullable<Uuid>,
+        prompt_name -> Text,
+        model -> Text,
+        prompt_tokens -> Int4,
+        completion_tokens -> Int4,
+        cost -> Float8,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::MessageVersionAuthorEnum;
+
+    message_versions (id) {
+        id -> Uuid,
+        message_id -> Uuid,
+        version_number -> Int4,
+        code -> Nullable<Text>,
+        chart_config -> Nullable<Jsonb>,
+        title -> Nullable<Text>,
+        time_frame -> Nullable<Text>,
+        author_type -> MessageVersionAuthorEnum,
+        created_by -> Nullable<Uuid>,
+        summary -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::MessageFeedbackEnum;
@@ -348,6 +557,22 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::RerankerEnum;
+
+    reranker_settings (id) {
+        id -> Uuid,
+        organization_id -> Uuid,
+        reranker -> RerankerEnum,
+        created_by -> Uuid,
+        updated_by -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::table! {
     sql_evaluations (id) {
         id -> Uuid,
@@ -360,6 +585,17 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    stored_values_syncs (dataset_column_id) {
+        dataset_column_id -> Uuid,
+        data_source_id -> Uuid,
+        claimed_until -> Nullable<Timestamptz>,
+        failed_attempts -> Int4,
+        retry_at -> Nullable<Timestamptz>,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::SharingSettingEnum;
@@ -377,6 +613,7 @@ diesel::table! {
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
         deleted_at -> Nullable<Timestamptz>,
+        attributes -> Jsonb,
     }
 }
 
@@ -470,6 +707,7 @@ diesel::table! {
         config -> Jsonb,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        attributes -> Jsonb,
     }
 }
 
@@ -500,25 +738,44 @@ diesel::table! {
 
 diesel::joinable!(api_keys -> organizations (organization_id));
 diesel::joinable!(api_keys -> users (owner_id));
+diesel::joinable!(audit_events -> organizations (organization_id));
+diesel::joinable!(audit_events -> users (actor_id));
 diesel::joinable!(collections -> organizations (organization_id));
 diesel::joinable!(dashboard_versions -> dashboards (dashboard_id));
+diesel::joinable!(dashboard_versions -> users (created_by));
 diesel::joinable!(dashboards -> organizations (organization_id));
 diesel::joinable!(data_sources -> organizations (organization_id));
+diesel::joinable!(dataset_column_policies -> dataset_columns (dataset_column_id));
+diesel::joinable!(dataset_column_policies -> permission_groups (permission_group_id));
 diesel::joinable!(dataset_groups -> organizations (organization_id));
 diesel::joinable!(dataset_permissions -> datasets (dataset_id));
 diesel::joinable!(dataset_permissions -> organizations (organization_id));
+diesel::joinable!(dataset_row_policies -> datasets (dataset_id));
+diesel::joinable!(dataset_row_policies -> organizations (organization_id));
+diesel::joinable!(dataset_row_policies_to_identities -> dataset_row_policies (row_policy_id));
 diesel::joinable!(datasets -> data_sources (data_source_id));
 diesel::joinable!(datasets -> organizations (organization_id));
 diesel::joinable!(datasets_to_dataset_groups -> dataset_groups (dataset_group_id));
 diesel::joinable!(datasets_to_dataset_groups -> datasets (dataset_id));
 diesel::joinable!(datasets_to_permission_groups -> datasets (dataset_id));
 diesel::joinable!(datasets_to_permission_groups -> permission_groups (permission_group_id));
+diesel::joinable!(identity_providers -> organizations (organization_id));
+diesel::joinable!(llm_budgets -> organizations (organization_id));
+diesel::joinable!(llm_providers -> organizations (organization_id));
+diesel::joinable!(llm_routes -> organizations (organization_id));
+diesel::joinable!(llm_usage -> organizations (organization_id));
+diesel::joinable!(llm_usage -> users (user_id));
+diesel::joinable!(message_versions -> messages (message_id));
+diesel::joinable!(message_versions -> users (created_by));
 diesel::joinable!(messages -> datasets (dataset_id));
 diesel::joinable!(messages -> threads (thread_id));
 diesel::joinable!(messages -> users (sent_by));
 diesel::joinable!(permission_groups -> organizations (organization_id));
 diesel::joinable!(permission_groups_to_users -> permission_groups (permission_group_id));
 diesel::joinable!(permission_groups_to_users -> users (user_id));
+diesel::joinable!(reranker_settings -> organizations (organization_id));
+diesel::joinable!(stored_values_syncs -> data_sources (data_source_id));
+diesel::joinable!(stored_values_syncs -> dataset_columns (dataset_column_id));
 diesel::joinable!(teams -> organizations (organization_id));
 diesel::joinable!(teams -> users (created_by));
 diesel::joinable!(teams_to_users -> teams (team_id));
@@ -536,24 +793,36 @@ diesel::joinable!(users_to_organizations -> organizations (organization_id));
 diesel::allow_tables_to_appear_in_same_query!(
     api_keys,
     asset_permissions,
+    audit_events,
     collections,
     collections_to_assets,
     dashboard_versions,
     dashboards,
     data_sources,
+    dataset_column_policies,
     dataset_columns,
     dataset_groups,
     dataset_permissions,
+    dataset_row_policies,
+    dataset_row_policies_to_identities,
     datasets,
     datasets_to_dataset_groups,
     datasets_to_permission_groups,
     entity_relationship,
+    identity_providers,
+    llm_budgets,
+    llm_providers,
+    llm_routes,
+    llm_usage,
+    message_versions,
     messages,
     organizations,
     permission_groups,
     permission_groups_to_identities,
     permission_groups_to_users,
+    reranker_settings,
     sql_evaluations,
+    stored_values_syncs,
     teams,
     teams_to_users,
     terms,
@@ -8,8 +8,7 @@ use super::{
     lib::{get_pg_pool, PgPool},
     models::{Dashboard, DataSource, Term, User},
     schema::{
-        api_keys, asset_permissions, dashboards, data_sources, datasets, teams_to_users, terms,
-        users,
+        asset_permissions, dashboards, data_sources, datasets, teams_to_users, terms, users,
     },
 };
 
@@ -38,33 +37,6 @@ impl User {
 
         Ok(user)
     }
-
-    pub async fn find_by_api_key(api_key: &str, pool: &PgPool) -> Result<Option<User>> {
-        let mut conn = match pool.get().await {
-            Ok(conn) => conn,
-            Err(e) => {
-                tracing::error!("Error getting connection from pool: {}", e);
-                return Err(anyhow!("Error getting connection: {}", e));
-            }
-        };
-
-        let user: Option<User> = match users::table
-            .inner_join(api_keys::table.on(api_keys::owner_id.eq(users::id)))
-            .filter(api_keys::key.eq(api_key))
-            .select(users::all_columns)
-            .first::<User>(&mut conn)
-            .await
-        {
-            Ok(user) => Some(user),
-            Err(diesel::result::Error::NotFound) => None,
-            Err(e) => {
-                tracing::error!("Error querying user by API key: {}", e);
-                return Err(anyhow!("Error querying user by API key: {}", e));
-            }
-        };
-
-        Ok(user)
-    }
 }
 
 impl DataSource {
@@ -93,6 +65,7 @@ impl DataSource {
                 data_sources::updated_at,
                 data_sources::deleted_at,
                 data_sources::env,
+                data_sources::max_execution_time_seconds,
             ))
             .first::<DataSource>(&mut conn)
             .await
@@ -134,6 +107,7 @@ impl DataSource {
                 data_sources::updated_at,
                 data_sources::deleted_at,
                 data_sources::env,
+                data_sources::max_execution_time_seconds,
             ))
             .first::<DataSource>(&mut conn)
             .await
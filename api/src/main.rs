@@ -5,6 +5,7 @@ mod types;
 pub mod utils;
 
 use std::env;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use axum::{middleware, Extension, Router};
@@ -60,7 +61,15 @@ async fn main() {
 
     tracing::info!("Successfully ran database migrations");
 
+    tokio::spawn(async {
+        if let Err(e) = utils::values_engine::values_engine::sync_values().await {
+            tracing::error!("Stored values sync stopped: {}", e);
+        }
+    });
+
     let protected_router = Router::new().nest("/api/v1", routes::protected_router());
+    let scim_router = Router::new().nest("/scim/v2", routes::scim::router());
+    let sso_router = Router::new().nest("/auth/sso", routes::sso::router());
     let public_router = Router::new().route("/health", axum::routing::get(|| async { "OK" }));
 
     let (shutdown_tx, _) = broadcast::channel::<()>(1);
@@ -68,6 +77,8 @@ async fn main() {
 
     let app = Router::new()
         .merge(protected_router)
+        .merge(scim_router)
+        .merge(sso_router)
         .merge(public_router)
         .layer(TraceLayer::new_for_http())
         .layer(cors())
@@ -87,7 +98,10 @@ async fn main() {
         }
     };
 
-    let server = axum::serve(listener, app);
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    );
 
     tokio::select! {
         _ = server => {},
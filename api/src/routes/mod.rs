@@ -1,4 +1,6 @@
 mod rest;
+pub mod scim;
+pub mod sso;
 pub mod ws;
 
 use axum::{middleware, routing::get, Router};
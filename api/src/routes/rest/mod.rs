@@ -1,22 +1,17 @@
 use axum::{
     body::Body,
     http::{Response, StatusCode},
-    middleware,
     response::IntoResponse,
     Json, Router,
 };
 
-use crate::buster_middleware::auth::auth;
-
 mod routes;
 mod webhooks;
 
 pub fn router() -> Router {
-    Router::new().nest("/", routes::router()).merge(
-        Router::new()
-            .nest("/webhooks", webhooks::router())
-            .route_layer(middleware::from_fn(auth)),
-    )
+    Router::new()
+        .nest("/", routes::router())
+        .nest("/webhooks", webhooks::router())
 }
 
 pub enum ApiResponse<T> {
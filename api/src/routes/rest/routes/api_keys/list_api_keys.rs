@@ -12,15 +12,39 @@ use crate::database::models::{ApiKey, User};
 use crate::database::schema::api_keys;
 use crate::routes::rest::ApiResponse;
 use crate::database::schema::users;
+use crate::utils::security::api_keys::{api_key_scopes, ApiKeyScope};
 
 #[derive(Debug, Serialize)]
 pub struct ApiKeyInfo {
     pub id: Uuid,
     pub owner_id: Uuid,
     pub owner_email: String,
+    pub name: Option<String>,
+    pub key_prefix: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub last_used_ip: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
+impl ApiKeyInfo {
+    pub fn new(api_key: ApiKey, owner_email: String) -> Self {
+        ApiKeyInfo {
+            id: api_key.id,
+            owner_id: api_key.owner_id,
+            owner_email,
+            scopes: api_key_scopes(&api_key),
+            name: api_key.name,
+            key_prefix: api_key.key_prefix,
+            expires_at: api_key.expires_at,
+            last_used_at: api_key.last_used_at,
+            last_used_ip: api_key.last_used_ip,
+            created_at: api_key.created_at,
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct ListApiKeysResponse {
     pub api_keys: Vec<ApiKeyInfo>,
@@ -54,11 +78,6 @@ async fn list_api_keys_handler(user: User) -> Result<Vec<ApiKeyInfo>> {
 
     Ok(api_keys
         .into_iter()
-        .map(|(key, email)| ApiKeyInfo {
-            id: key.id,
-            owner_id: key.owner_id,
-            owner_email: email,
-            created_at: key.created_at,
-        })
+        .map(|(key, email)| ApiKeyInfo::new(key, email))
         .collect())
 } 
\ No newline at end of file
@@ -1,64 +1,110 @@
 use anyhow::Result;
 use axum::http::StatusCode;
-use axum::Extension;
-use chrono::Utc;
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
 use diesel::insert_into;
 use diesel_async::RunQueryDsl;
-use jsonwebtoken::{encode, EncodingKey, Header};
 use serde::{Deserialize, Serialize};
-use std::env;
 use uuid::Uuid;
 
 use crate::database::lib::get_pg_pool;
 use crate::database::models::{ApiKey, User};
 use crate::database::schema::api_keys;
 use crate::routes::rest::ApiResponse;
+use crate::utils::security::api_keys::{
+    api_key_display_prefix, generate_api_key, hash_api_key, ApiKeyScope,
+};
+use crate::utils::security::checks::is_user_workspace_admin;
 use crate::utils::user::user_info::get_user_organization_id;
 
+const DEFAULT_EXPIRES_IN_DAYS: i64 = 365;
+const MAX_EXPIRES_IN_DAYS: i64 = 365 * 5;
+
+#[derive(Debug, Deserialize)]
+pub struct PostApiKeyRequest {
+    pub name: Option<String>,
+    /// Defaults to full access, which is what keys had before scopes.
+    pub scopes: Option<Vec<ApiKeyScope>>,
+    pub expires_in_days: Option<i64>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct PostApiKeyResponse {
+    pub id: Uuid,
+    /// The only time the full key is returned. Only its hash is stored.
     pub api_key: String,
-}
-
-// Add this struct for JWT claims
-#[derive(Debug, Serialize, Deserialize)]
-struct ApiKeyClaims {
-    exp: i64,
-    aud: String,
-    sub: String,
+    pub key_prefix: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub expires_at: DateTime<Utc>,
 }
 
 pub async fn post_api_key(
     Extension(user): Extension<User>,
+    request: Option<Json<PostApiKeyRequest>>,
 ) -> Result<ApiResponse<PostApiKeyResponse>, (StatusCode, &'static str)> {
-    let api_key = match post_api_key_handler(user).await {
-        Ok(api_key) => api_key,
+    let request = match request {
+        Some(Json(request)) => request,
+        None => PostApiKeyRequest {
+            name: None,
+            scopes: None,
+            expires_in_days: None,
+        },
+    };
+
+    let scopes = request.scopes.unwrap_or_else(|| vec![ApiKeyScope::All]);
+
+    if scopes.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "API key needs at least one scope"));
+    }
+
+    if scopes.contains(&ApiKeyScope::Scim) {
+        match is_user_workspace_admin(&user.id).await {
+            Ok(true) => (),
+            Ok(false) => {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    "Only workspace admins can create SCIM keys",
+                ))
+            }
+            Err(e) => {
+                tracing::error!("Error checking user permissions: {:?}", e);
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Error checking user permissions",
+                ));
+            }
+        }
+    }
+
+    let expires_in_days = request.expires_in_days.unwrap_or(DEFAULT_EXPIRES_IN_DAYS);
+
+    if !(1..=MAX_EXPIRES_IN_DAYS).contains(&expires_in_days) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "API key must expire in between 1 and 1825 days",
+        ));
+    }
+
+    let response = match post_api_key_handler(user, request.name, scopes, expires_in_days).await {
+        Ok(response) => response,
         Err(e) => {
             tracing::error!("Error creating API key: {:?}", e);
             return Err((StatusCode::INTERNAL_SERVER_ERROR, "Error creating API key"));
         }
     };
 
-    Ok(ApiResponse::JsonData(PostApiKeyResponse { api_key }))
+    Ok(ApiResponse::JsonData(response))
 }
 
-async fn post_api_key_handler(user: User) -> Result<String> {
-    let jwt_secret = env::var("JWT_SECRET").map_err(|_| anyhow::anyhow!("JWT_SECRET not set"))?;
-
-    // Create JWT claims
-    let claims = ApiKeyClaims {
-        exp: (Utc::now() + chrono::Duration::days(365 * 5)).timestamp(),
-        aud: "api".to_string(),
-        sub: user.id.to_string(),
-    };
-
-    // Generate JWT token
-    let api_key = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(jwt_secret.as_bytes()),
-    )
-    .map_err(|e| anyhow::anyhow!("Failed to create JWT: {}", e))?;
+async fn post_api_key_handler(
+    user: User,
+    name: Option<String>,
+    scopes: Vec<ApiKeyScope>,
+    expires_in_days: i64,
+) -> Result<PostApiKeyResponse> {
+    let api_key = generate_api_key();
+    let key_prefix = api_key_display_prefix(&api_key);
+    let expires_at = Utc::now() + chrono::Duration::days(expires_in_days);
 
     let mut conn = match get_pg_pool().get().await {
         Ok(conn) => conn,
@@ -79,13 +125,21 @@ async fn post_api_key_handler(user: User) -> Result<String> {
     let api_key_record = ApiKey {
         id: Uuid::new_v4(),
         owner_id: user.id,
-        key: api_key.clone(),
+        key_hash: hash_api_key(&api_key),
         organization_id,
         created_at: Utc::now(),
         updated_at: Utc::now(),
         deleted_at: None,
+        key_prefix: key_prefix.clone(),
+        name,
+        scopes: serde_json::to_value(&scopes)?,
+        expires_at: Some(expires_at),
+        last_used_at: None,
+        last_used_ip: None,
     };
 
+    let id = api_key_record.id;
+
     match insert_into(api_keys::table)
         .values(api_key_record)
         .execute(&mut *conn)
@@ -98,5 +152,11 @@ async fn post_api_key_handler(user: User) -> Result<String> {
         }
     };
 
-    Ok(api_key)
+    Ok(PostApiKeyResponse {
+        id,
+        api_key,
+        key_prefix,
+        scopes,
+        expires_at,
+    })
 }
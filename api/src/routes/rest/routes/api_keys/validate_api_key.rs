@@ -1,62 +1,68 @@
 use anyhow::Result;
 use axum::http::StatusCode;
 use axum::Json;
-use diesel::{ExpressionMethods, QueryDsl};
-use diesel_async::RunQueryDsl;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
 
-use crate::database::lib::get_pg_pool;
-use crate::database::schema::api_keys;
 use crate::routes::rest::ApiResponse;
+use crate::utils::security::api_keys::{find_api_key, ApiKeyAccess, ApiKeyScope};
 
 #[derive(Debug, Deserialize)]
 pub struct ValidateApiKeyRequest {
     pub api_key: String,
+    /// The key is only valid if it has all of these scopes.
+    #[serde(default)]
+    pub scopes: Vec<ApiKeyScope>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct ValidateApiKeyResponse {
     pub valid: bool,
+    pub scopes: Vec<ApiKeyScope>,
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 pub async fn validate_api_key(
     Json(request): Json<ValidateApiKeyRequest>,
 ) -> Result<ApiResponse<ValidateApiKeyResponse>, (StatusCode, &'static str)> {
-    let valid = match validate_api_key_handler(request.api_key).await {
-        Ok(api_key) => api_key,
+    let response = match validate_api_key_handler(request).await {
+        Ok(response) => response,
         Err(e) => {
-            tracing::error!("Error creating API key: {:?}", e);
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Error creating API key"));
+            tracing::error!("Error validating API key: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error validating API key",
+            ));
         }
     };
 
-    Ok(ApiResponse::JsonData(ValidateApiKeyResponse { valid }))
+    Ok(ApiResponse::JsonData(response))
 }
 
-async fn validate_api_key_handler(api_key: String) -> Result<bool> {
-    let mut conn = match get_pg_pool().get().await {
-        Ok(conn) => conn,
-        Err(e) => {
-            tracing::error!("Error getting database connection: {:?}", e);
-            return Err(anyhow::anyhow!("Error getting database connection"));
+async fn validate_api_key_handler(
+    request: ValidateApiKeyRequest,
+) -> Result<ValidateApiKeyResponse> {
+    let api_key = match find_api_key(&request.api_key).await {
+        Ok(Some((_, api_key))) => api_key,
+        Ok(None) => {
+            return Ok(ValidateApiKeyResponse {
+                valid: false,
+                scopes: vec![],
+                expires_at: None,
+            })
         }
-    };
-
-    let api_key_exists = match api_keys::table
-        .filter(api_keys::key.eq(api_key))
-        .filter(api_keys::deleted_at.is_null())
-        .select(api_keys::id)
-        .first::<Uuid>(&mut *conn)
-        .await
-    {
-        Ok(_) => true,
-        Err(diesel::NotFound) => false,
         Err(e) => {
             tracing::error!("Error getting API key: {:?}", e);
             return Err(anyhow::anyhow!("Error getting API key"));
         }
     };
 
-    Ok(api_key_exists)
+    let access = ApiKeyAccess::from_api_key(&api_key);
+    let valid = request.scopes.iter().all(|scope| access.allows(scope));
+
+    Ok(ValidateApiKeyResponse {
+        valid,
+        scopes: access.scopes,
+        expires_at: api_key.expires_at,
+    })
 }
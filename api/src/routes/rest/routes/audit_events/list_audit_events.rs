@@ -0,0 +1,65 @@
+use anyhow::Result;
+use axum::{extract::Query, http::StatusCode, Extension};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::models::{AuditEvent, User},
+    routes::rest::ApiResponse,
+    utils::{
+        audit::audit_log::{list_audit_events as query_audit_events, AuditEventFilters},
+        security::checks::is_user_workspace_admin,
+        user::user_info::get_user_organization_id,
+    },
+};
+
+const MAX_PAGE_SIZE: i64 = 500;
+
+#[derive(Deserialize)]
+pub struct ListAuditEventsQuery {
+    pub page: Option<i64>,
+    pub page_size: Option<i64>,
+}
+
+pub async fn list_audit_events(
+    Extension(user): Extension<User>,
+    Query(filters): Query<AuditEventFilters>,
+    Query(query): Query<ListAuditEventsQuery>,
+) -> Result<ApiResponse<Vec<AuditEvent>>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match list_audit_events_handler(&user.id, filters, query.page, query.page_size).await {
+        Ok(audit_events) => Ok(ApiResponse::JsonData(audit_events)),
+        Err(e) => {
+            tracing::error!("Error listing audit events: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error listing audit events",
+            ))
+        }
+    }
+}
+
+async fn list_audit_events_handler(
+    user_id: &Uuid,
+    filters: AuditEventFilters,
+    page: Option<i64>,
+    page_size: Option<i64>,
+) -> Result<Vec<AuditEvent>> {
+    let page = page.unwrap_or(0).max(0);
+    let page_size = page_size.unwrap_or(100).clamp(1, MAX_PAGE_SIZE);
+
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    query_audit_events(&organization_id, &filters, page * page_size, page_size).await
+}
@@ -0,0 +1,10 @@
+mod export_audit_events;
+mod list_audit_events;
+
+use axum::{routing::get, Router};
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/", get(list_audit_events::list_audit_events))
+        .route("/export", get(export_audit_events::export_audit_events))
+}
@@ -13,15 +13,18 @@ use diesel::ExpressionMethods;
 use diesel::QueryDsl;
 use diesel_async::RunQueryDsl;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use uuid::Uuid;
 
 use crate::database::enums::DataSourceOnboardingStatus;
 use crate::database::enums::UserOrganizationRole;
+use crate::database::enums::{AuditAction, AuditTargetType};
 use crate::database::lib::get_pg_pool;
 use crate::database::models::{DataSource, User};
 use crate::database::schema::data_sources;
 use crate::database::schema::users_to_organizations;
 use crate::routes::rest::ApiResponse;
+use crate::utils::audit::audit_log::{spawn_audit_event, AuditRequestMetadata, NewAuditEvent};
 use crate::utils::clients::supabase_vault::create_secrets;
 use crate::utils::query_engine::credentials::Credential;
 
@@ -29,6 +32,9 @@ use crate::utils::query_engine::credentials::Credential;
 pub struct CreateDataSourceRequest {
     pub name: String,
     pub env: String,
+    /// Longest a query may run before it is cancelled. Falls back to
+    /// `QUERY_MAX_EXECUTION_TIME_SECONDS`.
+    pub max_execution_time_seconds: Option<i32>,
     #[serde(flatten)]
     pub credential: Credential,
 }
@@ -40,9 +46,10 @@ pub struct CreateDataSourceResponse {
 
 pub async fn post_data_sources(
     Extension(user): Extension<User>,
+    Extension(request_metadata): Extension<AuditRequestMetadata>,
     Json(payload): Json<Vec<CreateDataSourceRequest>>,
 ) -> Result<ApiResponse<CreateDataSourceResponse>, (StatusCode, &'static str)> {
-    let ids = match post_data_sources_handler(&user.id, payload).await {
+    let ids = match post_data_sources_handler(&user.id, payload, request_metadata).await {
         Ok(ids) => ids,
         Err(e) => {
             tracing::error!("Error creating data sources: {:?}", e);
@@ -59,6 +66,7 @@ pub async fn post_data_sources(
 async fn post_data_sources_handler(
     user_id: &Uuid,
     requests: Vec<CreateDataSourceRequest>,
+    request_metadata: AuditRequestMetadata,
 ) -> Result<Vec<Uuid>> {
     let mut conn = match get_pg_pool().get().await {
         Ok(conn) => conn,
@@ -123,6 +131,7 @@ async fn post_data_sources_handler(
                 onboarding_status: DataSourceOnboardingStatus::NotStarted,
                 onboarding_error: None,
                 env: request.env.clone(),
+                max_execution_time_seconds: request.max_execution_time_seconds,
             }
         })
         .collect::<Vec<DataSource>>();
@@ -142,6 +151,8 @@ async fn post_data_sources_handler(
             data_sources::updated_at.eq(chrono::Utc::now()),
             data_sources::deleted_at.eq(Option::<DateTime<Utc>>::None),
             data_sources::env.eq(excluded(data_sources::env)),
+            data_sources::max_execution_time_seconds
+                .eq(excluded(data_sources::max_execution_time_seconds)),
         ))
         .execute(&mut conn)
         .await
@@ -152,5 +163,23 @@ async fn post_data_sources_handler(
         }
     };
 
+    // Credentials are left out of the audit log.
+    for data_source in &data_sources {
+        spawn_audit_event(NewAuditEvent {
+            actor_id: *user_id,
+            action: AuditAction::DataSourceCreated,
+            target_type: AuditTargetType::DataSource,
+            target_id: Some(data_source.id),
+            before: None,
+            after: Some(json!({
+                "name": data_source.name,
+                "env": data_source.env,
+                "type": data_source.type_,
+                "max_execution_time_seconds": data_source.max_execution_time_seconds,
+            })),
+            request_metadata: request_metadata.clone(),
+        });
+    }
+
     Ok(data_sources.iter().map(|ds| ds.id).collect())
 }
@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Result};
+use axum::http::StatusCode;
+use axum::{extract::Path, Extension};
+use diesel::{ExpressionMethods, JoinOnDsl, QueryDsl};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::{DatasetColumnPolicy, User},
+    schema::{dataset_column_policies, dataset_columns, datasets},
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::security::checks::is_user_workspace_admin_or_data_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+pub async fn list_column_policies(
+    Extension(user): Extension<User>,
+    Path(dataset_id): Path<Uuid>,
+) -> Result<ApiResponse<Vec<DatasetColumnPolicy>>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin_or_data_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match list_column_policies_handler(&user.id, &dataset_id).await {
+        Ok(column_policies) => Ok(ApiResponse::JsonData(column_policies)),
+        Err(e) => {
+            tracing::error!("Error listing column policies: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error listing column policies",
+            ))
+        }
+    }
+}
+
+async fn list_column_policies_handler(
+    user_id: &Uuid,
+    dataset_id: &Uuid,
+) -> Result<Vec<DatasetColumnPolicy>> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match dataset_column_policies::table
+        .inner_join(
+            dataset_columns::table
+                .on(dataset_columns::id.eq(dataset_column_policies::dataset_column_id)),
+        )
+        .inner_join(datasets::table.on(datasets::id.eq(dataset_columns::dataset_id)))
+        .filter(datasets::id.eq(dataset_id))
+        .filter(datasets::organization_id.eq(organization_id))
+        .filter(datasets::deleted_at.is_null())
+        .filter(dataset_columns::deleted_at.is_null())
+        .filter(dataset_column_policies::deleted_at.is_null())
+        .order((
+            dataset_columns::name.asc(),
+            dataset_column_policies::created_at.asc(),
+        ))
+        .select(dataset_column_policies::all_columns)
+        .load::<DatasetColumnPolicy>(&mut conn)
+        .await
+    {
+        Ok(column_policies) => Ok(column_policies),
+        Err(e) => Err(anyhow!("Error getting column policies: {}", e)),
+    }
+}
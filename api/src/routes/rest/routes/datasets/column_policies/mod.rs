@@ -0,0 +1,11 @@
+mod list_column_policies;
+mod put_column_policy;
+
+use axum::{routing::get, Router};
+
+pub fn router() -> Router {
+    Router::new().route(
+        "/",
+        get(list_column_policies::list_column_policies).put(put_column_policy::put_column_policy),
+    )
+}
@@ -0,0 +1,143 @@
+use anyhow::{anyhow, Result};
+use axum::http::StatusCode;
+use axum::{extract::Path, Extension, Json};
+use chrono::{DateTime, Utc};
+use diesel::{insert_into, upsert::excluded, ExpressionMethods, JoinOnDsl, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::database::{
+    enums::{ColumnAccess, ColumnMask},
+    lib::get_pg_pool,
+    models::{DatasetColumnPolicy, User},
+    schema::{dataset_column_policies, dataset_columns, datasets, permission_groups},
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::security::checks::is_user_workspace_admin_or_data_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+#[derive(Debug, Deserialize)]
+pub struct PutColumnPolicyRequest {
+    pub dataset_column_id: Uuid,
+    pub permission_group_id: Uuid,
+    pub access: ColumnAccess,
+    /// Required when `access` is `masked`, and not allowed otherwise.
+    pub mask: Option<ColumnMask>,
+}
+
+/// Sets how a dataset column reads for members of a permission group.
+pub async fn put_column_policy(
+    Extension(user): Extension<User>,
+    Path(dataset_id): Path<Uuid>,
+    Json(request): Json<PutColumnPolicyRequest>,
+) -> Result<ApiResponse<DatasetColumnPolicy>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin_or_data_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    if (request.access == ColumnAccess::Masked) != request.mask.is_some() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "A mask must be given for masked columns, and only for them",
+        ));
+    }
+
+    match put_column_policy_handler(&user.id, &dataset_id, request).await {
+        Ok(Some(column_policy)) => Ok(ApiResponse::JsonData(column_policy)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            "Dataset column or permission group not found",
+        )),
+        Err(e) => {
+            tracing::error!("Error setting column policy: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error setting column policy",
+            ))
+        }
+    }
+}
+
+async fn put_column_policy_handler(
+    user_id: &Uuid,
+    dataset_id: &Uuid,
+    request: PutColumnPolicyRequest,
+) -> Result<Option<DatasetColumnPolicy>> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match dataset_columns::table
+        .inner_join(datasets::table.on(datasets::id.eq(dataset_columns::dataset_id)))
+        .filter(dataset_columns::id.eq(request.dataset_column_id))
+        .filter(datasets::id.eq(dataset_id))
+        .filter(datasets::organization_id.eq(organization_id))
+        .filter(dataset_columns::deleted_at.is_null())
+        .filter(datasets::deleted_at.is_null())
+        .select(dataset_columns::id)
+        .first::<Uuid>(&mut conn)
+        .await
+    {
+        Ok(_) => (),
+        Err(diesel::NotFound) => return Ok(None),
+        Err(e) => return Err(anyhow!("Error getting dataset column: {}", e)),
+    };
+
+    match permission_groups::table
+        .filter(permission_groups::id.eq(request.permission_group_id))
+        .filter(permission_groups::organization_id.eq(organization_id))
+        .filter(permission_groups::deleted_at.is_null())
+        .select(permission_groups::id)
+        .first::<Uuid>(&mut conn)
+        .await
+    {
+        Ok(_) => (),
+        Err(diesel::NotFound) => return Ok(None),
+        Err(e) => return Err(anyhow!("Error getting permission group: {}", e)),
+    };
+
+    let column_policy = DatasetColumnPolicy {
+        dataset_column_id: request.dataset_column_id,
+        permission_group_id: request.permission_group_id,
+        access: request.access,
+        mask: request.mask,
+        created_by: *user_id,
+        updated_by: *user_id,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        deleted_at: None,
+    };
+
+    match insert_into(dataset_column_policies::table)
+        .values(&column_policy)
+        .on_conflict((
+            dataset_column_policies::dataset_column_id,
+            dataset_column_policies::permission_group_id,
+        ))
+        .do_update()
+        .set((
+            dataset_column_policies::access.eq(excluded(dataset_column_policies::access)),
+            dataset_column_policies::mask.eq(excluded(dataset_column_policies::mask)),
+            dataset_column_policies::updated_by.eq(excluded(dataset_column_policies::updated_by)),
+            dataset_column_policies::updated_at.eq(excluded(dataset_column_policies::updated_at)),
+            dataset_column_policies::deleted_at.eq(None::<DateTime<Utc>>),
+        ))
+        .get_result::<DatasetColumnPolicy>(&mut conn)
+        .await
+    {
+        Ok(column_policy) => Ok(Some(column_policy)),
+        Err(e) => Err(anyhow!("Error setting column policy: {}", e)),
+    }
+}
@@ -4,22 +4,26 @@ use chrono::{DateTime, Utc};
 use diesel::{upsert::excluded, ExpressionMethods, QueryDsl, SelectableHelper};
 use diesel_async::RunQueryDsl;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use serde_yaml;
 use std::collections::HashSet;
 use uuid::Uuid;
 
 use crate::{
     database::{
-        enums::DatasetType,
+        enums::{AuditAction, AuditTargetType, DatasetType},
         lib::get_pg_pool,
         models::{DataSource, Dataset, DatasetColumn, EntityRelationship, User},
         schema::{data_sources, dataset_columns, datasets, entity_relationship},
     },
     routes::rest::ApiResponse,
     utils::{
+        audit::audit_log::{spawn_audit_event, AuditRequestMetadata, NewAuditEvent},
         query_engine::{
             credentials::get_data_source_credentials,
             import_dataset_columns::retrieve_dataset_columns,
+            query_cache::invalidate_dataset_query_cache,
+            row_level_security::has_user_template,
             write_query_engine::write_query_engine,
         },
         user::user_info::get_user_organization_id,
@@ -48,6 +52,8 @@ pub struct FullDeployDatasetsRequest {
     pub entity_relationships: Option<Vec<DeployDatasetsEntityRelationshipsRequest>>,
     pub columns: Vec<DeployDatasetsColumnsRequest>,
     pub yml_file: Option<String>,
+    /// How long query results are cached for. Falls back to `QUERY_CACHE_TTL_SECONDS`; 0 disables.
+    pub cache_ttl_seconds: Option<i32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -90,6 +96,7 @@ pub struct Model {
     pub model: Option<String>,
     #[serde(rename = "type")]
     pub type_: String,
+    pub cache_ttl_seconds: Option<i32>,
     pub entities: Vec<Entity>,
     pub dimensions: Vec<Dimension>,
     pub measures: Vec<Measure>,
@@ -122,6 +129,7 @@ pub struct Measure {
 
 pub async fn deploy_datasets(
     Extension(user): Extension<User>,
+    Extension(request_metadata): Extension<AuditRequestMetadata>,
     Json(request): Json<DeployDatasetsRequest>,
 ) -> Result<ApiResponse<DeployDatasetsResponse>, (axum::http::StatusCode, String)> {
     let is_simple = match request {
@@ -137,7 +145,7 @@ pub async fn deploy_datasets(
         }
     };
 
-    let _ = match deploy_datasets_handler(&user.id, requests, is_simple).await {
+    let _ = match deploy_datasets_handler(&user.id, requests, is_simple, request_metadata).await {
         Ok(dataset) => dataset,
         Err(e) => {
             tracing::error!("Error creating dataset: {:?}", e);
@@ -209,6 +217,7 @@ async fn process_deploy_request(
                     entity_relationships: Some(entity_relationships),
                     columns,
                     yml_file: Some(yml.clone()),
+                    cache_ttl_seconds: semantic_model.cache_ttl_seconds,
                 });
             }
 
@@ -221,6 +230,7 @@ async fn deploy_datasets_handler(
     user_id: &Uuid,
     requests: Vec<FullDeployDatasetsRequest>,
     is_simple: bool,
+    request_metadata: AuditRequestMetadata,
 ) -> Result<()> {
     // Get the user organization id.
     let organization_id = get_user_organization_id(&user_id).await?;
@@ -294,6 +304,7 @@ async fn deploy_datasets_handler(
             organization_id,
             model: req.model.clone(),
             yml_file: req.yml_file.clone(),
+            cache_ttl_seconds: req.cache_ttl_seconds,
         };
 
         match req.id {
@@ -316,6 +327,7 @@ async fn deploy_datasets_handler(
                     datasets::definition.eq(excluded(datasets::definition)),
                     datasets::type_.eq(excluded(datasets::type_)),
                     datasets::schema.eq(excluded(datasets::schema)),
+                    datasets::cache_ttl_seconds.eq(excluded(datasets::cache_ttl_seconds)),
                     datasets::updated_at.eq(Utc::now()),
                     datasets::deleted_at.eq(None::<DateTime<Utc>>),
                 ))
@@ -336,6 +348,7 @@ async fn deploy_datasets_handler(
                     datasets::definition.eq(excluded(datasets::definition)),
                     datasets::type_.eq(excluded(datasets::type_)),
                     datasets::schema.eq(excluded(datasets::schema)),
+                    datasets::cache_ttl_seconds.eq(excluded(datasets::cache_ttl_seconds)),
                     datasets::updated_at.eq(Utc::now()),
                     datasets::deleted_at.eq(None::<DateTime<Utc>>),
                 ))
@@ -570,7 +583,12 @@ async fn deploy_datasets_handler(
     }
 
     if is_simple {
-        for dataset in inserted_datasets {
+        for dataset in &inserted_datasets {
+            // Templated definitions differ per user, so they are filled in at query time instead.
+            if has_user_template(&dataset.definition) {
+                continue;
+            }
+
             let view_name = format!("{}.{}", dataset.schema, dataset.database_name);
             let view_sql = format!(
                 "CREATE {} {} AS {}",
@@ -584,6 +602,31 @@ async fn deploy_datasets_handler(
         }
     }
 
+    // Cached results were computed against the old definitions.
+    for dataset in &inserted_datasets {
+        if let Err(e) = invalidate_dataset_query_cache(&dataset.id).await {
+            tracing::error!("Unable to invalidate query cache for {}: {}", dataset.id, e);
+        }
+    }
+
+    for dataset in &inserted_datasets {
+        spawn_audit_event(NewAuditEvent {
+            actor_id: *user_id,
+            action: AuditAction::DatasetsDeployed,
+            target_type: AuditTargetType::Dataset,
+            target_id: Some(dataset.id),
+            before: None,
+            after: Some(json!({
+                "name": dataset.name,
+                "schema": dataset.schema,
+                "data_source_id": dataset.data_source_id,
+                "definition": dataset.definition,
+                "model": dataset.model,
+            })),
+            request_metadata: request_metadata.clone(),
+        });
+    }
+
     // TODO: Need to send back the updated and inserated objects.
     Ok(())
 }
@@ -97,7 +97,7 @@ async fn get_dataset_data_sample_handler(
         let schema = dataset.schema.clone();
         let database_name = dataset.database_name.clone();
         let sql = format!("SELECT * FROM {}.{} LIMIT 25", schema, database_name);
-        match query_engine(dataset_id, &sql).await {
+        match query_engine(dataset_id, &sql, &user.id).await {
             Ok(data) => data,
             Err(e) => Vec::new(),
         }
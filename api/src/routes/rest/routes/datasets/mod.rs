@@ -1,9 +1,11 @@
 mod assets;
+mod column_policies;
 mod deploy_datasets;
 mod get_dataset;
 mod get_dataset_data_sample;
 mod list_datasets;
 mod post_dataset;
+mod row_policies;
 
 use axum::{
     routing::{get, post},
@@ -20,5 +22,7 @@ pub fn router() -> Router {
             "/:dataset_id/data/sample",
             get(get_dataset_data_sample::get_dataset_data_sample),
         )
+        .nest("/:dataset_id/column_policies", column_policies::router())
+        .nest("/:dataset_id/row_policies", row_policies::router())
         .nest("/:dataset_id", assets::router())
 }
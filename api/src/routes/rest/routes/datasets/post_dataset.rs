@@ -114,6 +114,7 @@ async fn post_dataset_handler(
         deleted_at: None,
         model: None,
         yml_file: None,
+        cache_ttl_seconds: None,
     };
 
     diesel::insert_into(datasets::table)
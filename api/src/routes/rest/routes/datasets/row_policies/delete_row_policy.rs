@@ -0,0 +1,71 @@
+use anyhow::{anyhow, Result};
+use axum::http::StatusCode;
+use axum::{extract::Path, Extension};
+use chrono::Utc;
+use diesel::{update, ExpressionMethods};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::database::{lib::get_pg_pool, models::User, schema::dataset_row_policies};
+use crate::routes::rest::ApiResponse;
+use crate::utils::security::checks::is_user_workspace_admin_or_data_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+pub async fn delete_row_policy(
+    Extension(user): Extension<User>,
+    Path((dataset_id, row_policy_id)): Path<(Uuid, Uuid)>,
+) -> Result<ApiResponse<()>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin_or_data_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match delete_row_policy_handler(&user, dataset_id, row_policy_id).await {
+        Ok(true) => Ok(ApiResponse::NoContent),
+        Ok(false) => Err((StatusCode::NOT_FOUND, "Row policy not found")),
+        Err(e) => {
+            tracing::error!("Error deleting row policy: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error deleting row policy",
+            ))
+        }
+    }
+}
+
+async fn delete_row_policy_handler(
+    user: &User,
+    dataset_id: Uuid,
+    row_policy_id: Uuid,
+) -> Result<bool> {
+    let organization_id = get_user_organization_id(&user.id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match update(dataset_row_policies::table)
+        .filter(dataset_row_policies::id.eq(row_policy_id))
+        .filter(dataset_row_policies::dataset_id.eq(dataset_id))
+        .filter(dataset_row_policies::organization_id.eq(organization_id))
+        .filter(dataset_row_policies::deleted_at.is_null())
+        .set((
+            dataset_row_policies::deleted_at.eq(Some(Utc::now())),
+            dataset_row_policies::updated_by.eq(user.id),
+            dataset_row_policies::updated_at.eq(Utc::now()),
+        ))
+        .execute(&mut conn)
+        .await
+    {
+        Ok(rows_affected) => Ok(rows_affected > 0),
+        Err(e) => Err(anyhow!("Error deleting row policy: {}", e)),
+    }
+}
@@ -0,0 +1,181 @@
+use anyhow::{anyhow, Result};
+use axum::http::StatusCode;
+use axum::{extract::Path, Extension};
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::database::{
+    enums::RowPolicyIdentityType,
+    lib::get_pg_pool,
+    models::{DatasetRowPolicy, User},
+    schema::{dataset_row_policies, dataset_row_policies_to_identities, datasets},
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::security::checks::is_user_workspace_admin_or_data_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RowPolicyIdentity {
+    pub id: Uuid,
+    #[serde(rename = "type")]
+    pub type_: RowPolicyIdentityType,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowPolicyResponse {
+    pub id: Uuid,
+    pub dataset_id: Uuid,
+    pub name: String,
+    pub filter: String,
+    pub identities: Vec<RowPolicyIdentity>,
+    pub created_by: Uuid,
+    pub updated_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub async fn list_row_policies(
+    Extension(user): Extension<User>,
+    Path(dataset_id): Path<Uuid>,
+) -> Result<ApiResponse<Vec<RowPolicyResponse>>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin_or_data_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    let organization_id = match get_user_organization_id(&user.id).await {
+        Ok(organization_id) => organization_id,
+        Err(e) => {
+            tracing::error!("Error getting organization ID: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error getting organization ID",
+            ));
+        }
+    };
+
+    match check_dataset_in_organization(&dataset_id, &organization_id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::NOT_FOUND, "Dataset not found")),
+        Err(e) => {
+            tracing::error!("Error getting dataset: {:?}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Error getting dataset"));
+        }
+    }
+
+    match get_row_policies(&dataset_id, None).await {
+        Ok(row_policies) => Ok(ApiResponse::JsonData(row_policies)),
+        Err(e) => {
+            tracing::error!("Error listing row policies: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error listing row policies",
+            ))
+        }
+    }
+}
+
+pub async fn check_dataset_in_organization(
+    dataset_id: &Uuid,
+    organization_id: &Uuid,
+) -> Result<bool> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match datasets::table
+        .filter(datasets::id.eq(dataset_id))
+        .filter(datasets::organization_id.eq(organization_id))
+        .filter(datasets::deleted_at.is_null())
+        .select(datasets::id)
+        .first::<Uuid>(&mut conn)
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(diesel::NotFound) => Ok(false),
+        Err(e) => Err(anyhow!("Error getting dataset: {}", e)),
+    }
+}
+
+/// The dataset's active policies with the identities they are bound to, optionally narrowed to one
+/// policy.
+pub async fn get_row_policies(
+    dataset_id: &Uuid,
+    row_policy_id: Option<&Uuid>,
+) -> Result<Vec<RowPolicyResponse>> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let mut query = dataset_row_policies::table
+        .filter(dataset_row_policies::dataset_id.eq(dataset_id))
+        .filter(dataset_row_policies::deleted_at.is_null())
+        .into_boxed();
+
+    if let Some(row_policy_id) = row_policy_id {
+        query = query.filter(dataset_row_policies::id.eq(row_policy_id));
+    }
+
+    let row_policies = match query
+        .order(dataset_row_policies::created_at.asc())
+        .load::<DatasetRowPolicy>(&mut conn)
+        .await
+    {
+        Ok(row_policies) => row_policies,
+        Err(e) => return Err(anyhow!("Error getting row policies: {}", e)),
+    };
+
+    let row_policy_ids = row_policies
+        .iter()
+        .map(|row_policy| row_policy.id)
+        .collect::<Vec<Uuid>>();
+
+    let identities = match dataset_row_policies_to_identities::table
+        .filter(dataset_row_policies_to_identities::row_policy_id.eq_any(&row_policy_ids))
+        .filter(dataset_row_policies_to_identities::deleted_at.is_null())
+        .select((
+            dataset_row_policies_to_identities::row_policy_id,
+            dataset_row_policies_to_identities::identity_id,
+            dataset_row_policies_to_identities::identity_type,
+        ))
+        .load::<(Uuid, Uuid, RowPolicyIdentityType)>(&mut conn)
+        .await
+    {
+        Ok(identities) => identities,
+        Err(e) => return Err(anyhow!("Error getting row policy identities: {}", e)),
+    };
+
+    Ok(row_policies
+        .into_iter()
+        .map(|row_policy| RowPolicyResponse {
+            identities: identities
+                .iter()
+                .filter(|(row_policy_id, _, _)| *row_policy_id == row_policy.id)
+                .map(|(_, id, type_)| RowPolicyIdentity {
+                    id: *id,
+                    type_: *type_,
+                })
+                .collect(),
+            id: row_policy.id,
+            dataset_id: row_policy.dataset_id,
+            name: row_policy.name,
+            filter: row_policy.filter,
+            created_by: row_policy.created_by,
+            updated_by: row_policy.updated_by,
+            created_at: row_policy.created_at,
+            updated_at: row_policy.updated_at,
+        })
+        .collect())
+}
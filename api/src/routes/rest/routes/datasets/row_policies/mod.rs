@@ -0,0 +1,21 @@
+mod delete_row_policy;
+mod list_row_policies;
+mod post_row_policy;
+mod put_row_policy;
+
+use axum::{
+    routing::{get, put},
+    Router,
+};
+
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/",
+            get(list_row_policies::list_row_policies).post(post_row_policy::post_row_policy),
+        )
+        .route(
+            "/:row_policy_id",
+            put(put_row_policy::put_row_policy).delete(delete_row_policy::delete_row_policy),
+        )
+}
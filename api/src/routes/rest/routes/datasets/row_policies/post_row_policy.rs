@@ -0,0 +1,186 @@
+use anyhow::{anyhow, Result};
+use axum::http::StatusCode;
+use axum::{extract::Path, Extension, Json};
+use chrono::{DateTime, Utc};
+use diesel::{insert_into, update, ExpressionMethods};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::{DatasetRowPolicy, DatasetRowPolicyToIdentity, User},
+    schema::{dataset_row_policies, dataset_row_policies_to_identities},
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::query_engine::row_level_security::validate_row_policy_filter;
+use crate::utils::security::checks::is_user_workspace_admin_or_data_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+use super::list_row_policies::{
+    check_dataset_in_organization, get_row_policies, RowPolicyIdentity, RowPolicyResponse,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct PostRowPolicyRequest {
+    pub name: String,
+    /// A SQL condition such as `region = {{user.attributes.region}}`.
+    pub filter: String,
+    pub identities: Vec<RowPolicyIdentity>,
+}
+
+pub async fn post_row_policy(
+    Extension(user): Extension<User>,
+    Path(dataset_id): Path<Uuid>,
+    Json(request): Json<PostRowPolicyRequest>,
+) -> Result<ApiResponse<RowPolicyResponse>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin_or_data_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    if let Err(e) = validate_row_policy_filter(&request.filter) {
+        tracing::debug!("Rejected row policy filter: {:?}", e);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Row policy filter must be a single SQL condition",
+        ));
+    }
+
+    let organization_id = match get_user_organization_id(&user.id).await {
+        Ok(organization_id) => organization_id,
+        Err(e) => {
+            tracing::error!("Error getting organization ID: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error getting organization ID",
+            ));
+        }
+    };
+
+    match check_dataset_in_organization(&dataset_id, &organization_id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::NOT_FOUND, "Dataset not found")),
+        Err(e) => {
+            tracing::error!("Error getting dataset: {:?}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Error getting dataset"));
+        }
+    }
+
+    match post_row_policy_handler(&user, dataset_id, organization_id, request).await {
+        Ok(row_policy) => Ok(ApiResponse::JsonData(row_policy)),
+        Err(e) => {
+            tracing::error!("Error creating row policy: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error creating row policy",
+            ))
+        }
+    }
+}
+
+async fn post_row_policy_handler(
+    user: &User,
+    dataset_id: Uuid,
+    organization_id: Uuid,
+    request: PostRowPolicyRequest,
+) -> Result<RowPolicyResponse> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let row_policy = DatasetRowPolicy {
+        id: Uuid::new_v4(),
+        dataset_id,
+        organization_id,
+        name: request.name,
+        filter: request.filter,
+        created_by: user.id,
+        updated_by: user.id,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        deleted_at: None,
+    };
+
+    match insert_into(dataset_row_policies::table)
+        .values(&row_policy)
+        .execute(&mut conn)
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => return Err(anyhow!("Error inserting row policy: {}", e)),
+    };
+
+    set_row_policy_identities(&mut conn, &row_policy.id, &request.identities, &user.id).await?;
+
+    match get_row_policies(&dataset_id, Some(&row_policy.id))
+        .await?
+        .pop()
+    {
+        Some(row_policy) => Ok(row_policy),
+        None => Err(anyhow!("Row policy not found after insert")),
+    }
+}
+
+/// Binds the policy to exactly these identities, unbinding any others.
+pub async fn set_row_policy_identities(
+    conn: &mut AsyncPgConnection,
+    row_policy_id: &Uuid,
+    identities: &[RowPolicyIdentity],
+    user_id: &Uuid,
+) -> Result<()> {
+    match update(dataset_row_policies_to_identities::table)
+        .filter(dataset_row_policies_to_identities::row_policy_id.eq(row_policy_id))
+        .filter(dataset_row_policies_to_identities::deleted_at.is_null())
+        .set(dataset_row_policies_to_identities::deleted_at.eq(Some(Utc::now())))
+        .execute(&mut *conn)
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => return Err(anyhow!("Error unbinding row policy identities: {}", e)),
+    };
+
+    if identities.is_empty() {
+        return Ok(());
+    }
+
+    let bindings = identities
+        .iter()
+        .map(|identity| DatasetRowPolicyToIdentity {
+            row_policy_id: *row_policy_id,
+            identity_id: identity.id,
+            identity_type: identity.type_,
+            created_by: *user_id,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        })
+        .collect::<Vec<DatasetRowPolicyToIdentity>>();
+
+    match insert_into(dataset_row_policies_to_identities::table)
+        .values(&bindings)
+        .on_conflict((
+            dataset_row_policies_to_identities::row_policy_id,
+            dataset_row_policies_to_identities::identity_id,
+            dataset_row_policies_to_identities::identity_type,
+        ))
+        .do_update()
+        .set((
+            dataset_row_policies_to_identities::updated_at.eq(Utc::now()),
+            dataset_row_policies_to_identities::deleted_at.eq(None::<DateTime<Utc>>),
+        ))
+        .execute(&mut *conn)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(anyhow!("Error binding row policy identities: {}", e)),
+    }
+}
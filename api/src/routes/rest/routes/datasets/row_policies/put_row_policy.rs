@@ -0,0 +1,111 @@
+use anyhow::{anyhow, Result};
+use axum::http::StatusCode;
+use axum::{extract::Path, Extension, Json};
+use chrono::Utc;
+use diesel::{update, ExpressionMethods};
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::database::{lib::get_pg_pool, models::User, schema::dataset_row_policies};
+use crate::routes::rest::ApiResponse;
+use crate::utils::query_engine::row_level_security::validate_row_policy_filter;
+use crate::utils::security::checks::is_user_workspace_admin_or_data_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+use super::list_row_policies::{get_row_policies, RowPolicyIdentity, RowPolicyResponse};
+use super::post_row_policy::set_row_policy_identities;
+
+#[derive(Debug, Deserialize)]
+pub struct PutRowPolicyRequest {
+    pub name: Option<String>,
+    pub filter: Option<String>,
+    /// Replaces the identities the policy is bound to.
+    pub identities: Option<Vec<RowPolicyIdentity>>,
+}
+
+pub async fn put_row_policy(
+    Extension(user): Extension<User>,
+    Path((dataset_id, row_policy_id)): Path<(Uuid, Uuid)>,
+    Json(request): Json<PutRowPolicyRequest>,
+) -> Result<ApiResponse<RowPolicyResponse>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin_or_data_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    if let Some(filter) = &request.filter {
+        if let Err(e) = validate_row_policy_filter(filter) {
+            tracing::debug!("Rejected row policy filter: {:?}", e);
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Row policy filter must be a single SQL condition",
+            ));
+        }
+    }
+
+    match put_row_policy_handler(&user, dataset_id, row_policy_id, request).await {
+        Ok(Some(row_policy)) => Ok(ApiResponse::JsonData(row_policy)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Row policy not found")),
+        Err(e) => {
+            tracing::error!("Error updating row policy: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error updating row policy",
+            ))
+        }
+    }
+}
+
+async fn put_row_policy_handler(
+    user: &User,
+    dataset_id: Uuid,
+    row_policy_id: Uuid,
+    request: PutRowPolicyRequest,
+) -> Result<Option<RowPolicyResponse>> {
+    let organization_id = get_user_organization_id(&user.id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let rows_affected = match update(dataset_row_policies::table)
+        .filter(dataset_row_policies::id.eq(row_policy_id))
+        .filter(dataset_row_policies::dataset_id.eq(dataset_id))
+        .filter(dataset_row_policies::organization_id.eq(organization_id))
+        .filter(dataset_row_policies::deleted_at.is_null())
+        .set((
+            request.name.map(|name| dataset_row_policies::name.eq(name)),
+            request
+                .filter
+                .map(|filter| dataset_row_policies::filter.eq(filter)),
+            dataset_row_policies::updated_by.eq(user.id),
+            dataset_row_policies::updated_at.eq(Utc::now()),
+        ))
+        .execute(&mut conn)
+        .await
+    {
+        Ok(rows_affected) => rows_affected,
+        Err(e) => return Err(anyhow!("Error updating row policy: {}", e)),
+    };
+
+    if rows_affected == 0 {
+        return Ok(None);
+    }
+
+    if let Some(identities) = &request.identities {
+        set_row_policy_identities(&mut conn, &row_policy_id, identities, &user.id).await?;
+    }
+
+    Ok(get_row_policies(&dataset_id, Some(&row_policy_id))
+        .await?
+        .pop())
+}
@@ -0,0 +1,79 @@
+use anyhow::{anyhow, Result};
+use axum::{extract::Path, http::StatusCode, Extension};
+use chrono::Utc;
+use diesel::{update, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::database::{lib::get_pg_pool, models::User, schema::identity_providers};
+use crate::routes::rest::ApiResponse;
+use crate::utils::clients::supabase_vault::delete_secret;
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+/// Removes an identity provider. Users it signed in keep their accounts and sessions.
+pub async fn delete_identity_provider(
+    Extension(user): Extension<User>,
+    Path(identity_provider_id): Path<Uuid>,
+) -> Result<ApiResponse<()>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match delete_identity_provider_handler(&user.id, &identity_provider_id).await {
+        Ok(true) => Ok(ApiResponse::NoContent),
+        Ok(false) => Err((StatusCode::NOT_FOUND, "Identity provider not found")),
+        Err(e) => {
+            tracing::error!("Error deleting identity provider: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error deleting identity provider",
+            ))
+        }
+    }
+}
+
+async fn delete_identity_provider_handler(
+    user_id: &Uuid,
+    identity_provider_id: &Uuid,
+) -> Result<bool> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let client_secret_id = match update(identity_providers::table)
+        .filter(identity_providers::id.eq(identity_provider_id))
+        .filter(identity_providers::organization_id.eq(organization_id))
+        .filter(identity_providers::deleted_at.is_null())
+        .set((
+            identity_providers::deleted_at.eq(Some(Utc::now())),
+            identity_providers::updated_by.eq(user_id),
+        ))
+        .returning(identity_providers::client_secret_id)
+        .get_result::<Option<Uuid>>(&mut conn)
+        .await
+    {
+        Ok(client_secret_id) => client_secret_id,
+        Err(diesel::NotFound) => return Ok(false),
+        Err(e) => return Err(anyhow!("Error deleting identity provider: {}", e)),
+    };
+
+    if let Some(client_secret_id) = client_secret_id {
+        if let Err(e) = delete_secret(&client_secret_id).await {
+            tracing::error!("Error deleting identity provider client secret: {}", e);
+        }
+    }
+
+    Ok(true)
+}
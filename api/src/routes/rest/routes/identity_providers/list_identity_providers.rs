@@ -0,0 +1,68 @@
+use anyhow::{anyhow, Result};
+use axum::{http::StatusCode, Extension};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::{IdentityProvider, User},
+    schema::identity_providers,
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+use super::IdentityProviderResponse;
+
+pub async fn list_identity_providers(
+    Extension(user): Extension<User>,
+) -> Result<ApiResponse<Vec<IdentityProviderResponse>>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match list_identity_providers_handler(&user.id).await {
+        Ok(identity_providers) => Ok(ApiResponse::JsonData(
+            identity_providers
+                .into_iter()
+                .map(IdentityProviderResponse::from)
+                .collect(),
+        )),
+        Err(e) => {
+            tracing::error!("Error listing identity providers: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error listing identity providers",
+            ))
+        }
+    }
+}
+
+async fn list_identity_providers_handler(user_id: &Uuid) -> Result<Vec<IdentityProvider>> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match identity_providers::table
+        .filter(identity_providers::organization_id.eq(organization_id))
+        .filter(identity_providers::deleted_at.is_null())
+        .order(identity_providers::created_at.asc())
+        .load::<IdentityProvider>(&mut conn)
+        .await
+    {
+        Ok(identity_providers) => Ok(identity_providers),
+        Err(e) => Err(anyhow!("Error getting identity providers: {}", e)),
+    }
+}
@@ -0,0 +1,136 @@
+mod delete_identity_provider;
+mod list_identity_providers;
+mod post_identity_provider;
+mod put_identity_provider;
+
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use axum::{
+    routing::{delete, get, post, put},
+    Router,
+};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    database::{enums::UserOrganizationRole, models::IdentityProvider, schema::teams},
+    utils::security::sso::{saml, sso_endpoint_url, IdentityProviderConfig, SsoGroupMapping},
+};
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/", get(list_identity_providers::list_identity_providers))
+        .route("/", post(post_identity_provider::post_identity_provider))
+        .route(
+            "/:identity_provider_id",
+            put(put_identity_provider::put_identity_provider),
+        )
+        .route(
+            "/:identity_provider_id",
+            delete(delete_identity_provider::delete_identity_provider),
+        )
+}
+
+/// An identity provider along with the URLs an admin needs to register us with it.
+#[derive(Serialize, Debug)]
+pub struct IdentityProviderResponse {
+    #[serde(flatten)]
+    pub identity_provider: IdentityProvider,
+    /// Where users go to sign in with the provider.
+    pub sign_in_url: String,
+    /// The OIDC redirect URI or the SAML ACS URL.
+    pub redirect_url: String,
+    pub metadata_url: Option<String>,
+}
+
+impl From<IdentityProvider> for IdentityProviderResponse {
+    fn from(identity_provider: IdentityProvider) -> Self {
+        let is_saml = identity_provider
+            .config
+            .get("protocol")
+            .and_then(|protocol| protocol.as_str())
+            == Some("saml");
+
+        let (redirect_url, metadata_url) = match is_saml {
+            true => (
+                sso_endpoint_url(&identity_provider.id, "acs"),
+                Some(sso_endpoint_url(&identity_provider.id, "metadata")),
+            ),
+            false => (sso_endpoint_url(&identity_provider.id, "callback"), None),
+        };
+
+        IdentityProviderResponse {
+            sign_in_url: sso_endpoint_url(&identity_provider.id, "login"),
+            redirect_url,
+            metadata_url,
+            identity_provider,
+        }
+    }
+}
+
+/// Checks a config from an admin before it is saved, returning why it can't be used.
+fn validate_config(
+    identity_provider_id: &Uuid,
+    config: &IdentityProviderConfig,
+) -> Result<(), &'static str> {
+    match config {
+        IdentityProviderConfig::Oidc(config) => {
+            if config.issuer.trim().is_empty() || config.client_id.trim().is_empty() {
+                return Err("OIDC providers need an issuer and a client id");
+            }
+            Ok(())
+        }
+        IdentityProviderConfig::Saml(config) => {
+            match saml::validate_config(identity_provider_id, config) {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    tracing::warn!("Invalid SAML config: {}", e);
+                    Err("Invalid identity provider metadata")
+                }
+            }
+        }
+    }
+}
+
+/// Users who sign in with a provider can't be made workspace admins by it.
+fn validate_default_role(default_role: UserOrganizationRole) -> Result<(), &'static str> {
+    match default_role {
+        UserOrganizationRole::WorkspaceAdmin => {
+            Err("Users can't be given the workspace admin role on sign in")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Whether every mapping points at a team in the organization.
+async fn group_mappings_are_valid(
+    conn: &mut AsyncPgConnection,
+    organization_id: &Uuid,
+    group_mappings: &[SsoGroupMapping],
+) -> Result<bool> {
+    let team_ids = group_mappings
+        .iter()
+        .map(|mapping| mapping.team_id)
+        .collect::<HashSet<Uuid>>();
+
+    if team_ids.is_empty() {
+        return Ok(true);
+    }
+
+    let found_team_ids = match teams::table
+        .filter(teams::id.eq_any(&team_ids))
+        .filter(teams::organization_id.eq(organization_id))
+        .filter(teams::deleted_at.is_null())
+        .select(teams::id)
+        .load::<Uuid>(&mut *conn)
+        .await
+    {
+        Ok(found_team_ids) => found_team_ids,
+        Err(e) => return Err(anyhow!("Error getting teams: {}", e)),
+    };
+
+    Ok(found_team_ids.len() == team_ids.len())
+}
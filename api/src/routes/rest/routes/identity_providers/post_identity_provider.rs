@@ -0,0 +1,146 @@
+use anyhow::{anyhow, Result};
+use axum::{http::StatusCode, Extension, Json};
+use chrono::Utc;
+use diesel::insert_into;
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::database::{
+    enums::UserOrganizationRole,
+    lib::get_pg_pool,
+    models::{IdentityProvider, User},
+    schema::identity_providers,
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::clients::supabase_vault::{create_secret, delete_secret};
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::security::sso::{IdentityProviderConfig, SsoGroupMapping};
+use crate::utils::user::user_info::get_user_organization_id;
+
+use super::{
+    group_mappings_are_valid, validate_config, validate_default_role, IdentityProviderResponse,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct PostIdentityProviderRequest {
+    pub name: String,
+    pub config: IdentityProviderConfig,
+    /// Required for OIDC providers.
+    pub client_secret: Option<String>,
+    #[serde(default = "default_role")]
+    pub default_role: UserOrganizationRole,
+    #[serde(default)]
+    pub group_mappings: Vec<SsoGroupMapping>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_role() -> UserOrganizationRole {
+    UserOrganizationRole::Querier
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+pub async fn post_identity_provider(
+    Extension(user): Extension<User>,
+    Json(request): Json<PostIdentityProviderRequest>,
+) -> Result<ApiResponse<IdentityProviderResponse>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    let identity_provider_id = Uuid::new_v4();
+
+    if let Err(reason) = validate_config(&identity_provider_id, &request.config) {
+        return Err((StatusCode::BAD_REQUEST, reason));
+    }
+
+    if let Err(reason) = validate_default_role(request.default_role) {
+        return Err((StatusCode::BAD_REQUEST, reason));
+    }
+
+    if matches!(request.config, IdentityProviderConfig::Oidc(_)) && request.client_secret.is_none()
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "OIDC providers need a client secret",
+        ));
+    }
+
+    match post_identity_provider_handler(&user.id, identity_provider_id, request).await {
+        Ok(Some(identity_provider)) => Ok(ApiResponse::JsonData(identity_provider.into())),
+        Ok(None) => Err((StatusCode::BAD_REQUEST, "Group mapping team not found")),
+        Err(e) => {
+            tracing::error!("Error creating identity provider: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error creating identity provider",
+            ))
+        }
+    }
+}
+
+async fn post_identity_provider_handler(
+    user_id: &Uuid,
+    identity_provider_id: Uuid,
+    request: PostIdentityProviderRequest,
+) -> Result<Option<IdentityProvider>> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    if !group_mappings_are_valid(&mut conn, &organization_id, &request.group_mappings).await? {
+        return Ok(None);
+    }
+
+    let client_secret_id = match &request.client_secret {
+        Some(client_secret) => Some(create_secret(client_secret).await?),
+        None => None,
+    };
+
+    let identity_provider = IdentityProvider {
+        id: identity_provider_id,
+        organization_id,
+        name: request.name,
+        config: serde_json::to_value(&request.config)?,
+        client_secret_id,
+        default_role: request.default_role,
+        group_mappings: serde_json::to_value(&request.group_mappings)?,
+        enabled: request.enabled,
+        created_by: *user_id,
+        updated_by: *user_id,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        deleted_at: None,
+    };
+
+    match insert_into(identity_providers::table)
+        .values(&identity_provider)
+        .execute(&mut conn)
+        .await
+    {
+        Ok(_) => Ok(Some(identity_provider)),
+        Err(e) => {
+            if let Some(client_secret_id) = client_secret_id {
+                if let Err(e) = delete_secret(&client_secret_id).await {
+                    tracing::error!("Error deleting identity provider client secret: {}", e);
+                }
+            }
+            Err(anyhow!("Error inserting identity provider: {}", e))
+        }
+    }
+}
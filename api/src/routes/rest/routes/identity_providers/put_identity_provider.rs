@@ -0,0 +1,175 @@
+use anyhow::{anyhow, Result};
+use axum::{extract::Path, http::StatusCode, Extension, Json};
+use chrono::Utc;
+use diesel::{update, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::database::{
+    enums::UserOrganizationRole,
+    lib::get_pg_pool,
+    models::{IdentityProvider, User},
+    schema::identity_providers,
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::clients::supabase_vault::{create_secret, update_secret};
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::security::sso::{IdentityProviderConfig, SsoGroupMapping};
+use crate::utils::user::user_info::get_user_organization_id;
+
+use super::{
+    group_mappings_are_valid, validate_config, validate_default_role, IdentityProviderResponse,
+};
+
+/// Fields left out are kept as they are.
+#[derive(Debug, Deserialize)]
+pub struct PutIdentityProviderRequest {
+    pub name: Option<String>,
+    pub config: Option<IdentityProviderConfig>,
+    pub client_secret: Option<String>,
+    pub default_role: Option<UserOrganizationRole>,
+    pub group_mappings: Option<Vec<SsoGroupMapping>>,
+    pub enabled: Option<bool>,
+}
+
+enum PutIdentityProviderResult {
+    Updated(IdentityProvider),
+    NotFound,
+    Invalid(&'static str),
+}
+
+pub async fn put_identity_provider(
+    Extension(user): Extension<User>,
+    Path(identity_provider_id): Path<Uuid>,
+    Json(request): Json<PutIdentityProviderRequest>,
+) -> Result<ApiResponse<IdentityProviderResponse>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    if let Some(config) = &request.config {
+        if let Err(reason) = validate_config(&identity_provider_id, config) {
+            return Err((StatusCode::BAD_REQUEST, reason));
+        }
+    }
+
+    if let Some(default_role) = request.default_role {
+        if let Err(reason) = validate_default_role(default_role) {
+            return Err((StatusCode::BAD_REQUEST, reason));
+        }
+    }
+
+    match put_identity_provider_handler(&user.id, &identity_provider_id, request).await {
+        Ok(PutIdentityProviderResult::Updated(identity_provider)) => {
+            Ok(ApiResponse::JsonData(identity_provider.into()))
+        }
+        Ok(PutIdentityProviderResult::NotFound) => {
+            Err((StatusCode::NOT_FOUND, "Identity provider not found"))
+        }
+        Ok(PutIdentityProviderResult::Invalid(reason)) => Err((StatusCode::BAD_REQUEST, reason)),
+        Err(e) => {
+            tracing::error!("Error updating identity provider: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error updating identity provider",
+            ))
+        }
+    }
+}
+
+async fn put_identity_provider_handler(
+    user_id: &Uuid,
+    identity_provider_id: &Uuid,
+    request: PutIdentityProviderRequest,
+) -> Result<PutIdentityProviderResult> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let mut identity_provider = match identity_providers::table
+        .filter(identity_providers::id.eq(identity_provider_id))
+        .filter(identity_providers::organization_id.eq(organization_id))
+        .filter(identity_providers::deleted_at.is_null())
+        .first::<IdentityProvider>(&mut conn)
+        .await
+    {
+        Ok(identity_provider) => identity_provider,
+        Err(diesel::NotFound) => return Ok(PutIdentityProviderResult::NotFound),
+        Err(e) => return Err(anyhow!("Error getting identity provider: {}", e)),
+    };
+
+    if let Some(group_mappings) = &request.group_mappings {
+        if !group_mappings_are_valid(&mut conn, &organization_id, group_mappings).await? {
+            return Ok(PutIdentityProviderResult::Invalid(
+                "Group mapping team not found",
+            ));
+        }
+        identity_provider.group_mappings = serde_json::to_value(group_mappings)?;
+    }
+
+    if let Some(config) = &request.config {
+        identity_provider.config = serde_json::to_value(config)?;
+    }
+
+    if let Some(client_secret) = &request.client_secret {
+        match identity_provider.client_secret_id {
+            Some(client_secret_id) => update_secret(&client_secret_id, client_secret).await?,
+            None => identity_provider.client_secret_id = Some(create_secret(client_secret).await?),
+        }
+    }
+
+    let is_oidc = identity_provider
+        .config
+        .get("protocol")
+        .and_then(|protocol| protocol.as_str())
+        == Some("oidc");
+
+    if is_oidc && identity_provider.client_secret_id.is_none() {
+        return Ok(PutIdentityProviderResult::Invalid(
+            "OIDC providers need a client secret",
+        ));
+    }
+
+    if let Some(name) = request.name {
+        identity_provider.name = name;
+    }
+
+    if let Some(default_role) = request.default_role {
+        identity_provider.default_role = default_role;
+    }
+
+    if let Some(enabled) = request.enabled {
+        identity_provider.enabled = enabled;
+    }
+
+    match update(identity_providers::table)
+        .filter(identity_providers::id.eq(identity_provider_id))
+        .set((
+            identity_providers::name.eq(&identity_provider.name),
+            identity_providers::config.eq(&identity_provider.config),
+            identity_providers::client_secret_id.eq(identity_provider.client_secret_id),
+            identity_providers::default_role.eq(identity_provider.default_role),
+            identity_providers::group_mappings.eq(&identity_provider.group_mappings),
+            identity_providers::enabled.eq(identity_provider.enabled),
+            identity_providers::updated_by.eq(user_id),
+            identity_providers::updated_at.eq(Utc::now()),
+        ))
+        .get_result::<IdentityProvider>(&mut conn)
+        .await
+    {
+        Ok(identity_provider) => Ok(PutIdentityProviderResult::Updated(identity_provider)),
+        Err(e) => Err(anyhow!("Error updating identity provider: {}", e)),
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::{anyhow, Result};
+use axum::{http::StatusCode, Extension};
+use chrono::Utc;
+use diesel::{update, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::database::{lib::get_pg_pool, models::User, schema::llm_budgets};
+use crate::routes::rest::ApiResponse;
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+/// Lifts the organization's limits. Usage keeps being recorded.
+pub async fn delete_llm_budget(
+    Extension(user): Extension<User>,
+) -> Result<ApiResponse<()>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match delete_llm_budget_handler(&user.id).await {
+        Ok(true) => Ok(ApiResponse::NoContent),
+        Ok(false) => Err((StatusCode::NOT_FOUND, "LLM budget not found")),
+        Err(e) => {
+            tracing::error!("Error deleting LLM budget: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error deleting LLM budget",
+            ))
+        }
+    }
+}
+
+async fn delete_llm_budget_handler(user_id: &Uuid) -> Result<bool> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match update(llm_budgets::table)
+        .filter(llm_budgets::organization_id.eq(organization_id))
+        .filter(llm_budgets::deleted_at.is_null())
+        .set((
+            llm_budgets::deleted_at.eq(Some(Utc::now())),
+            llm_budgets::updated_by.eq(user_id),
+        ))
+        .execute(&mut conn)
+        .await
+    {
+        Ok(deleted) => Ok(deleted > 0),
+        Err(e) => Err(anyhow!("Error deleting LLM budget: {}", e)),
+    }
+}
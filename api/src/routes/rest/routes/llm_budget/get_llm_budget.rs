@@ -0,0 +1,62 @@
+use anyhow::{anyhow, Result};
+use axum::{http::StatusCode, Extension};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::{LlmBudget, User},
+    schema::llm_budgets,
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+pub async fn get_llm_budget(
+    Extension(user): Extension<User>,
+) -> Result<ApiResponse<LlmBudget>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match get_llm_budget_handler(&user.id).await {
+        Ok(Some(llm_budget)) => Ok(ApiResponse::JsonData(llm_budget)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "LLM budget not found")),
+        Err(e) => {
+            tracing::error!("Error getting LLM budget: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error getting LLM budget",
+            ))
+        }
+    }
+}
+
+async fn get_llm_budget_handler(user_id: &Uuid) -> Result<Option<LlmBudget>> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match llm_budgets::table
+        .filter(llm_budgets::organization_id.eq(organization_id))
+        .filter(llm_budgets::deleted_at.is_null())
+        .first::<LlmBudget>(&mut conn)
+        .await
+    {
+        Ok(llm_budget) => Ok(Some(llm_budget)),
+        Err(diesel::NotFound) => Ok(None),
+        Err(e) => Err(anyhow!("Error getting LLM budget: {}", e)),
+    }
+}
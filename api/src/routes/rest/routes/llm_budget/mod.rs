@@ -0,0 +1,17 @@
+mod delete_llm_budget;
+mod get_llm_budget;
+mod put_llm_budget;
+
+use axum::{
+    routing::{delete, get, put},
+    Router,
+};
+
+/// What an organization is willing to spend on LLM calls each month. There is at most one per
+/// organization.
+pub fn router() -> Router {
+    Router::new()
+        .route("/", get(get_llm_budget::get_llm_budget))
+        .route("/", put(put_llm_budget::put_llm_budget))
+        .route("/", delete(delete_llm_budget::delete_llm_budget))
+}
@@ -0,0 +1,131 @@
+use anyhow::{anyhow, Result};
+use axum::{http::StatusCode, Extension, Json};
+use chrono::Utc;
+use diesel::{insert_into, update, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::{LlmBudget, User},
+    schema::llm_budgets,
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+/// Limits are in US dollars a month. Either can be left out.
+#[derive(Debug, Deserialize)]
+pub struct PutLlmBudgetRequest {
+    pub soft_limit: Option<f64>,
+    pub hard_limit: Option<f64>,
+}
+
+/// Sets the organization's monthly limits, replacing the ones it had.
+pub async fn put_llm_budget(
+    Extension(user): Extension<User>,
+    Json(request): Json<PutLlmBudgetRequest>,
+) -> Result<ApiResponse<LlmBudget>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    if request.soft_limit.is_none() && request.hard_limit.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "A soft or hard limit is required"));
+    }
+
+    for limit in [request.soft_limit, request.hard_limit]
+        .into_iter()
+        .flatten()
+    {
+        if !limit.is_finite() || limit <= 0.0 {
+            return Err((StatusCode::BAD_REQUEST, "Limits must be positive"));
+        }
+    }
+
+    if let (Some(soft_limit), Some(hard_limit)) = (request.soft_limit, request.hard_limit) {
+        if soft_limit > hard_limit {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "The soft limit can't be above the hard limit",
+            ));
+        }
+    }
+
+    match put_llm_budget_handler(&user.id, request).await {
+        Ok(llm_budget) => Ok(ApiResponse::JsonData(llm_budget)),
+        Err(e) => {
+            tracing::error!("Error setting LLM budget: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error setting LLM budget",
+            ))
+        }
+    }
+}
+
+async fn put_llm_budget_handler(user_id: &Uuid, request: PutLlmBudgetRequest) -> Result<LlmBudget> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let existing_id = match llm_budgets::table
+        .filter(llm_budgets::organization_id.eq(organization_id))
+        .filter(llm_budgets::deleted_at.is_null())
+        .select(llm_budgets::id)
+        .first::<Uuid>(&mut conn)
+        .await
+    {
+        Ok(existing_id) => existing_id,
+        Err(diesel::NotFound) => {
+            let llm_budget = LlmBudget {
+                id: Uuid::new_v4(),
+                organization_id,
+                soft_limit: request.soft_limit,
+                hard_limit: request.hard_limit,
+                created_by: *user_id,
+                updated_by: *user_id,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                deleted_at: None,
+            };
+
+            return match insert_into(llm_budgets::table)
+                .values(&llm_budget)
+                .execute(&mut conn)
+                .await
+            {
+                Ok(_) => Ok(llm_budget),
+                Err(e) => Err(anyhow!("Error inserting LLM budget: {}", e)),
+            };
+        }
+        Err(e) => return Err(anyhow!("Error getting LLM budget: {}", e)),
+    };
+
+    match update(llm_budgets::table)
+        .filter(llm_budgets::id.eq(existing_id))
+        .set((
+            llm_budgets::soft_limit.eq(request.soft_limit),
+            llm_budgets::hard_limit.eq(request.hard_limit),
+            llm_budgets::updated_by.eq(user_id),
+            llm_budgets::updated_at.eq(Utc::now()),
+        ))
+        .get_result::<LlmBudget>(&mut conn)
+        .await
+    {
+        Ok(llm_budget) => Ok(llm_budget),
+        Err(e) => Err(anyhow!("Error updating LLM budget: {}", e)),
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::{anyhow, Result};
+use axum::{http::StatusCode, Extension};
+use chrono::Utc;
+use diesel::{update, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::database::{lib::get_pg_pool, models::User, schema::llm_providers};
+use crate::routes::rest::ApiResponse;
+use crate::utils::clients::supabase_vault::delete_secret;
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+/// Sends the organization's prompts back to the hosted models.
+pub async fn delete_llm_provider(
+    Extension(user): Extension<User>,
+) -> Result<ApiResponse<()>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match delete_llm_provider_handler(&user.id).await {
+        Ok(true) => Ok(ApiResponse::NoContent),
+        Ok(false) => Err((StatusCode::NOT_FOUND, "LLM provider not found")),
+        Err(e) => {
+            tracing::error!("Error deleting LLM provider: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error deleting LLM provider",
+            ))
+        }
+    }
+}
+
+async fn delete_llm_provider_handler(user_id: &Uuid) -> Result<bool> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let api_key_id = match update(llm_providers::table)
+        .filter(llm_providers::organization_id.eq(organization_id))
+        .filter(llm_providers::deleted_at.is_null())
+        .set((
+            llm_providers::deleted_at.eq(Some(Utc::now())),
+            llm_providers::updated_by.eq(user_id),
+        ))
+        .returning(llm_providers::api_key_id)
+        .get_result::<Option<Uuid>>(&mut conn)
+        .await
+    {
+        Ok(api_key_id) => api_key_id,
+        Err(diesel::NotFound) => return Ok(false),
+        Err(e) => return Err(anyhow!("Error deleting LLM provider: {}", e)),
+    };
+
+    if let Some(api_key_id) = api_key_id {
+        if let Err(e) = delete_secret(&api_key_id).await {
+            tracing::error!("Error deleting LLM provider API key: {}", e);
+        }
+    }
+
+    Ok(true)
+}
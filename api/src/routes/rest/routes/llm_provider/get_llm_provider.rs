@@ -0,0 +1,62 @@
+use anyhow::{anyhow, Result};
+use axum::{http::StatusCode, Extension};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::{LlmProvider, User},
+    schema::llm_providers,
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+pub async fn get_llm_provider(
+    Extension(user): Extension<User>,
+) -> Result<ApiResponse<LlmProvider>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match get_llm_provider_handler(&user.id).await {
+        Ok(Some(llm_provider)) => Ok(ApiResponse::JsonData(llm_provider)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "LLM provider not found")),
+        Err(e) => {
+            tracing::error!("Error getting LLM provider: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error getting LLM provider",
+            ))
+        }
+    }
+}
+
+async fn get_llm_provider_handler(user_id: &Uuid) -> Result<Option<LlmProvider>> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match llm_providers::table
+        .filter(llm_providers::organization_id.eq(organization_id))
+        .filter(llm_providers::deleted_at.is_null())
+        .first::<LlmProvider>(&mut conn)
+        .await
+    {
+        Ok(llm_provider) => Ok(Some(llm_provider)),
+        Err(diesel::NotFound) => Ok(None),
+        Err(e) => Err(anyhow!("Error getting LLM provider: {}", e)),
+    }
+}
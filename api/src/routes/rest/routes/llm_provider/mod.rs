@@ -0,0 +1,16 @@
+mod delete_llm_provider;
+mod get_llm_provider;
+mod put_llm_provider;
+
+use axum::{
+    routing::{delete, get, put},
+    Router,
+};
+
+/// The model an organization runs itself, if it has one. There is at most one per organization.
+pub fn router() -> Router {
+    Router::new()
+        .route("/", get(get_llm_provider::get_llm_provider))
+        .route("/", put(put_llm_provider::put_llm_provider))
+        .route("/", delete(delete_llm_provider::delete_llm_provider))
+}
@@ -0,0 +1,153 @@
+use anyhow::{anyhow, Result};
+use axum::{http::StatusCode, Extension, Json};
+use chrono::Utc;
+use diesel::{insert_into, update, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+use url::Url;
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::{LlmProvider, User},
+    schema::llm_providers,
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::clients::ai::llm_providers::LlmProviderConfig;
+use crate::utils::clients::supabase_vault::{create_secret, update_secret};
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+#[derive(Debug, Deserialize)]
+pub struct PutLlmProviderRequest {
+    pub config: LlmProviderConfig,
+    /// Replaces the stored key when given. Left out, the current key is kept.
+    pub api_key: Option<String>,
+}
+
+/// Sets the model the organization runs itself, replacing the one it had.
+pub async fn put_llm_provider(
+    Extension(user): Extension<User>,
+    Json(request): Json<PutLlmProviderRequest>,
+) -> Result<ApiResponse<LlmProvider>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    let (base_url, model) = match &request.config {
+        LlmProviderConfig::Ollama {
+            base_url, model, ..
+        } => (base_url, model),
+        LlmProviderConfig::OpenAiCompatible {
+            base_url, model, ..
+        } => (base_url, model),
+    };
+
+    match Url::parse(base_url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => (),
+        _ => return Err((StatusCode::BAD_REQUEST, "The base URL must be an HTTP URL")),
+    }
+
+    if model.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "A model is required"));
+    }
+
+    match put_llm_provider_handler(&user.id, request).await {
+        Ok(llm_provider) => Ok(ApiResponse::JsonData(llm_provider)),
+        Err(e) => {
+            tracing::error!("Error setting LLM provider: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error setting LLM provider",
+            ))
+        }
+    }
+}
+
+async fn put_llm_provider_handler(
+    user_id: &Uuid,
+    request: PutLlmProviderRequest,
+) -> Result<LlmProvider> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let config = serde_json::to_value(&request.config)?;
+
+    let existing = match llm_providers::table
+        .filter(llm_providers::organization_id.eq(organization_id))
+        .filter(llm_providers::deleted_at.is_null())
+        .first::<LlmProvider>(&mut conn)
+        .await
+    {
+        Ok(existing) => Some(existing),
+        Err(diesel::NotFound) => None,
+        Err(e) => return Err(anyhow!("Error getting LLM provider: {}", e)),
+    };
+
+    let existing = match existing {
+        Some(existing) => existing,
+        None => {
+            let api_key_id = match &request.api_key {
+                Some(api_key) => Some(create_secret(api_key).await?),
+                None => None,
+            };
+
+            let llm_provider = LlmProvider {
+                id: Uuid::new_v4(),
+                organization_id,
+                config,
+                api_key_id,
+                created_by: *user_id,
+                updated_by: *user_id,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                deleted_at: None,
+            };
+
+            return match insert_into(llm_providers::table)
+                .values(&llm_provider)
+                .execute(&mut conn)
+                .await
+            {
+                Ok(_) => Ok(llm_provider),
+                Err(e) => Err(anyhow!("Error inserting LLM provider: {}", e)),
+            };
+        }
+    };
+
+    let api_key_id = match (&request.api_key, existing.api_key_id) {
+        (Some(api_key), Some(api_key_id)) => {
+            update_secret(&api_key_id, api_key).await?;
+            Some(api_key_id)
+        }
+        (Some(api_key), None) => Some(create_secret(api_key).await?),
+        (None, api_key_id) => api_key_id,
+    };
+
+    match update(llm_providers::table)
+        .filter(llm_providers::id.eq(existing.id))
+        .set((
+            llm_providers::config.eq(config),
+            llm_providers::api_key_id.eq(api_key_id),
+            llm_providers::updated_by.eq(user_id),
+            llm_providers::updated_at.eq(Utc::now()),
+        ))
+        .get_result::<LlmProvider>(&mut conn)
+        .await
+    {
+        Ok(llm_provider) => Ok(llm_provider),
+        Err(e) => Err(anyhow!("Error updating LLM provider: {}", e)),
+    }
+}
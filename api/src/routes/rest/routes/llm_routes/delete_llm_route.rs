@@ -0,0 +1,65 @@
+use anyhow::{anyhow, Result};
+use axum::{extract::Path, http::StatusCode, Extension};
+use chrono::Utc;
+use diesel::{update, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::database::{lib::get_pg_pool, models::User, schema::llm_routes};
+use crate::routes::rest::ApiResponse;
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+/// Sends the prompt back to the `default` route, or to the model it asks for if there is none.
+pub async fn delete_llm_route(
+    Extension(user): Extension<User>,
+    Path(prompt_name): Path<String>,
+) -> Result<ApiResponse<()>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match delete_llm_route_handler(&user.id, &prompt_name).await {
+        Ok(true) => Ok(ApiResponse::NoContent),
+        Ok(false) => Err((StatusCode::NOT_FOUND, "LLM route not found")),
+        Err(e) => {
+            tracing::error!("Error deleting LLM route: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error deleting LLM route",
+            ))
+        }
+    }
+}
+
+async fn delete_llm_route_handler(user_id: &Uuid, prompt_name: &str) -> Result<bool> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match update(llm_routes::table)
+        .filter(llm_routes::organization_id.eq(organization_id))
+        .filter(llm_routes::prompt_name.eq(prompt_name))
+        .filter(llm_routes::deleted_at.is_null())
+        .set((
+            llm_routes::deleted_at.eq(Some(Utc::now())),
+            llm_routes::updated_by.eq(user_id),
+        ))
+        .execute(&mut conn)
+        .await
+    {
+        Ok(deleted) => Ok(deleted > 0),
+        Err(e) => Err(anyhow!("Error deleting LLM route: {}", e)),
+    }
+}
@@ -0,0 +1,61 @@
+use anyhow::{anyhow, Result};
+use axum::{http::StatusCode, Extension};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::{LlmRoute, User},
+    schema::llm_routes,
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+pub async fn list_llm_routes(
+    Extension(user): Extension<User>,
+) -> Result<ApiResponse<Vec<LlmRoute>>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match list_llm_routes_handler(&user.id).await {
+        Ok(llm_routes) => Ok(ApiResponse::JsonData(llm_routes)),
+        Err(e) => {
+            tracing::error!("Error listing LLM routes: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error listing LLM routes",
+            ))
+        }
+    }
+}
+
+async fn list_llm_routes_handler(user_id: &Uuid) -> Result<Vec<LlmRoute>> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match llm_routes::table
+        .filter(llm_routes::organization_id.eq(organization_id))
+        .filter(llm_routes::deleted_at.is_null())
+        .order(llm_routes::prompt_name.asc())
+        .load::<LlmRoute>(&mut conn)
+        .await
+    {
+        Ok(llm_routes) => Ok(llm_routes),
+        Err(e) => Err(anyhow!("Error listing LLM routes: {}", e)),
+    }
+}
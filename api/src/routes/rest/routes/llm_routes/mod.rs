@@ -0,0 +1,17 @@
+mod delete_llm_route;
+mod list_llm_routes;
+mod put_llm_route;
+
+use axum::{
+    routing::{delete, get, put},
+    Router,
+};
+
+/// Which model each of the organization's prompts goes to, by prompt name. The `default` route
+/// covers every prompt without its own.
+pub fn router() -> Router {
+    Router::new()
+        .route("/", get(list_llm_routes::list_llm_routes))
+        .route("/:prompt_name", put(put_llm_route::put_llm_route))
+        .route("/:prompt_name", delete(delete_llm_route::delete_llm_route))
+}
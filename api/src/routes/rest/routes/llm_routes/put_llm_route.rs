@@ -0,0 +1,145 @@
+use anyhow::{anyhow, Result};
+use axum::{extract::Path, http::StatusCode, Extension, Json};
+use chrono::Utc;
+use diesel::{insert_into, update, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::{LlmRoute, User},
+    schema::llm_routes,
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::clients::ai::llm_routes::LlmModelChoice;
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+#[derive(Debug, Deserialize)]
+pub struct PutLlmRouteRequest {
+    pub model: LlmModelChoice,
+    pub fallback_model: Option<LlmModelChoice>,
+    /// Left out, the prompt keeps the temperature it asks for.
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<i32>,
+}
+
+/// Sets the model a prompt goes to, replacing its current route.
+pub async fn put_llm_route(
+    Extension(user): Extension<User>,
+    Path(prompt_name): Path<String>,
+    Json(request): Json<PutLlmRouteRequest>,
+) -> Result<ApiResponse<LlmRoute>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    if prompt_name.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "A prompt name is required"));
+    }
+
+    if let Some(temperature) = request.temperature {
+        if !(0.0..=2.0).contains(&temperature) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "The temperature must be between 0 and 2",
+            ));
+        }
+    }
+
+    if let Some(max_tokens) = request.max_tokens {
+        if max_tokens <= 0 {
+            return Err((StatusCode::BAD_REQUEST, "Max tokens must be positive"));
+        }
+    }
+
+    match put_llm_route_handler(&user.id, prompt_name, request).await {
+        Ok(llm_route) => Ok(ApiResponse::JsonData(llm_route)),
+        Err(e) => {
+            tracing::error!("Error setting LLM route: {:?}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Error setting LLM route"))
+        }
+    }
+}
+
+async fn put_llm_route_handler(
+    user_id: &Uuid,
+    prompt_name: String,
+    request: PutLlmRouteRequest,
+) -> Result<LlmRoute> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let model = serde_json::to_value(&request.model)?;
+    let fallback_model = match &request.fallback_model {
+        Some(fallback_model) => Some(serde_json::to_value(fallback_model)?),
+        None => None,
+    };
+
+    let existing_id = match llm_routes::table
+        .filter(llm_routes::organization_id.eq(organization_id))
+        .filter(llm_routes::prompt_name.eq(&prompt_name))
+        .filter(llm_routes::deleted_at.is_null())
+        .select(llm_routes::id)
+        .first::<Uuid>(&mut conn)
+        .await
+    {
+        Ok(existing_id) => existing_id,
+        Err(diesel::NotFound) => {
+            let llm_route = LlmRoute {
+                id: Uuid::new_v4(),
+                organization_id,
+                prompt_name,
+                model,
+                fallback_model,
+                temperature: request.temperature,
+                max_tokens: request.max_tokens,
+                created_by: *user_id,
+                updated_by: *user_id,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                deleted_at: None,
+            };
+
+            return match insert_into(llm_routes::table)
+                .values(&llm_route)
+                .execute(&mut conn)
+                .await
+            {
+                Ok(_) => Ok(llm_route),
+                Err(e) => Err(anyhow!("Error inserting LLM route: {}", e)),
+            };
+        }
+        Err(e) => return Err(anyhow!("Error getting LLM route: {}", e)),
+    };
+
+    match update(llm_routes::table)
+        .filter(llm_routes::id.eq(existing_id))
+        .set((
+            llm_routes::model.eq(model),
+            llm_routes::fallback_model.eq(fallback_model),
+            llm_routes::temperature.eq(request.temperature),
+            llm_routes::max_tokens.eq(request.max_tokens),
+            llm_routes::updated_by.eq(user_id),
+            llm_routes::updated_at.eq(Utc::now()),
+        ))
+        .get_result::<LlmRoute>(&mut conn)
+        .await
+    {
+        Ok(llm_route) => Ok(llm_route),
+        Err(e) => Err(anyhow!("Error updating LLM route: {}", e)),
+    }
+}
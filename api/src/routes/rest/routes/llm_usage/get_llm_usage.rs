@@ -0,0 +1,222 @@
+use anyhow::{anyhow, Result};
+use axum::{extract::Query, http::StatusCode, Extension};
+use chrono::{DateTime, Utc};
+use diesel::{dsl::count_star, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::{LlmBudget, User},
+    schema::{llm_budgets, llm_usage},
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::clients::ai::llm_usage::{
+    budget_status, month_start, organization_llm_cost, LlmBudgetStatus,
+};
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LlmUsageGroupBy {
+    #[default]
+    User,
+    Thread,
+    Model,
+    Prompt,
+}
+
+/// The period defaults to the current month.
+#[derive(Deserialize)]
+pub struct GetLlmUsageQuery {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub group_by: LlmUsageGroupBy,
+}
+
+#[derive(Serialize)]
+pub struct LlmUsageRow {
+    /// The user, thread, model or prompt. Calls made outside of a thread have no key when
+    /// grouping by thread.
+    pub key: Option<String>,
+    pub calls: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cost: f64,
+}
+
+#[derive(Serialize)]
+pub struct LlmUsageReport {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub group_by: LlmUsageGroupBy,
+    pub calls: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cost: f64,
+    /// Most expensive first.
+    pub rows: Vec<LlmUsageRow>,
+    pub budget: Option<LlmBudget>,
+    /// What the organization has spent this month, which is what the budget is checked against.
+    pub month_cost: f64,
+    pub budget_status: LlmBudgetStatus,
+}
+
+type UsageTotals = (i64, Option<i64>, Option<i64>, Option<f64>);
+
+pub async fn get_llm_usage(
+    Extension(user): Extension<User>,
+    Query(query): Query<GetLlmUsageQuery>,
+) -> Result<ApiResponse<LlmUsageReport>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    let now = Utc::now();
+    let start = query.start.unwrap_or(month_start(now));
+    let end = query.end.unwrap_or(now);
+
+    if start >= end {
+        return Err((StatusCode::BAD_REQUEST, "The start must be before the end"));
+    }
+
+    match get_llm_usage_handler(&user.id, start, end, query.group_by).await {
+        Ok(report) => Ok(ApiResponse::JsonData(report)),
+        Err(e) => {
+            tracing::error!("Error getting LLM usage: {:?}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Error getting LLM usage"))
+        }
+    }
+}
+
+fn usage_row(key: Option<String>, totals: UsageTotals) -> LlmUsageRow {
+    let (calls, prompt_tokens, completion_tokens, cost) = totals;
+
+    LlmUsageRow {
+        key,
+        calls,
+        prompt_tokens: prompt_tokens.unwrap_or(0),
+        completion_tokens: completion_tokens.unwrap_or(0),
+        cost: cost.unwrap_or(0.0),
+    }
+}
+
+async fn get_llm_usage_handler(
+    user_id: &Uuid,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    group_by: LlmUsageGroupBy,
+) -> Result<LlmUsageReport> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let totals = (
+        count_star(),
+        diesel::dsl::sum(llm_usage::prompt_tokens),
+        diesel::dsl::sum(llm_usage::completion_tokens),
+        diesel::dsl::sum(llm_usage::cost),
+    );
+
+    let usage_query = llm_usage::table
+        .filter(llm_usage::organization_id.eq(organization_id))
+        .filter(llm_usage::created_at.ge(start))
+        .filter(llm_usage::created_at.lt(end));
+
+    let rows = match group_by {
+        LlmUsageGroupBy::User => usage_query
+            .group_by(llm_usage::user_id)
+            .select((llm_usage::user_id, totals))
+            .load::<(Uuid, UsageTotals)>(&mut conn)
+            .await
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|(key, totals)| usage_row(Some(key.to_string()), totals))
+                    .collect::<Vec<LlmUsageRow>>()
+            }),
+        LlmUsageGroupBy::Thread => usage_query
+            .group_by(llm_usage::thread_id)
+            .select((llm_usage::thread_id, totals))
+            .load::<(Option<Uuid>, UsageTotals)>(&mut conn)
+            .await
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|(key, totals)| usage_row(key.map(|key| key.to_string()), totals))
+                    .collect::<Vec<LlmUsageRow>>()
+            }),
+        LlmUsageGroupBy::Model => usage_query
+            .group_by(llm_usage::model)
+            .select((llm_usage::model, totals))
+            .load::<(String, UsageTotals)>(&mut conn)
+            .await
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|(key, totals)| usage_row(Some(key), totals))
+                    .collect::<Vec<LlmUsageRow>>()
+            }),
+        LlmUsageGroupBy::Prompt => usage_query
+            .group_by(llm_usage::prompt_name)
+            .select((llm_usage::prompt_name, totals))
+            .load::<(String, UsageTotals)>(&mut conn)
+            .await
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|(key, totals)| usage_row(Some(key), totals))
+                    .collect::<Vec<LlmUsageRow>>()
+            }),
+    };
+
+    let mut rows = match rows {
+        Ok(rows) => rows,
+        Err(e) => return Err(anyhow!("Error getting LLM usage: {}", e)),
+    };
+
+    rows.sort_by(|a, b| b.cost.total_cmp(&a.cost));
+
+    let budget = match llm_budgets::table
+        .filter(llm_budgets::organization_id.eq(organization_id))
+        .filter(llm_budgets::deleted_at.is_null())
+        .first::<LlmBudget>(&mut conn)
+        .await
+    {
+        Ok(budget) => Some(budget),
+        Err(diesel::NotFound) => None,
+        Err(e) => return Err(anyhow!("Error getting LLM budget: {}", e)),
+    };
+
+    let month_cost =
+        organization_llm_cost(&mut conn, &organization_id, month_start(Utc::now())).await?;
+
+    let budget_status = match &budget {
+        Some(budget) => budget_status(month_cost, budget.soft_limit, budget.hard_limit),
+        None => LlmBudgetStatus::WithinBudget,
+    };
+
+    Ok(LlmUsageReport {
+        start,
+        end,
+        group_by,
+        calls: rows.iter().map(|row| row.calls).sum(),
+        prompt_tokens: rows.iter().map(|row| row.prompt_tokens).sum(),
+        completion_tokens: rows.iter().map(|row| row.completion_tokens).sum(),
+        cost: rows.iter().map(|row| row.cost).sum(),
+        rows,
+        budget,
+        month_cost,
+        budget_status,
+    })
+}
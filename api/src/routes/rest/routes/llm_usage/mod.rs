@@ -0,0 +1,7 @@
+mod get_llm_usage;
+
+use axum::{routing::get, Router};
+
+pub fn router() -> Router {
+    Router::new().route("/", get(get_llm_usage::get_llm_usage))
+}
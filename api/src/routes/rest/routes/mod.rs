@@ -1,10 +1,18 @@
 mod api_keys;
 mod assets;
+mod audit_events;
 mod data_sources;
 mod dataset_groups;
 mod datasets;
+mod identity_providers;
+mod llm_budget;
+mod llm_provider;
+mod llm_routes;
+mod llm_usage;
 mod permission_groups;
+mod reranker;
 mod sql;
+mod teams;
 mod users;
 
 use axum::{middleware, Router};
@@ -16,11 +24,19 @@ pub fn router() -> Router {
         Router::new()
             .nest("/users", users::router())
             .nest("/assets", assets::router())
+            .nest("/audit_events", audit_events::router())
             .nest("/datasets", datasets::router())
             .nest("/data_sources", data_sources::router())
+            .nest("/identity_providers", identity_providers::router())
+            .nest("/llm_budget", llm_budget::router())
+            .nest("/llm_provider", llm_provider::router())
+            .nest("/llm_routes", llm_routes::router())
+            .nest("/llm_usage", llm_usage::router())
             .nest("/permission_groups", permission_groups::router())
             .nest("/dataset_groups", dataset_groups::router())
+            .nest("/reranker", reranker::router())
             .nest("/sql", sql::router())
+            .nest("/teams", teams::router())
             .route_layer(middleware::from_fn(auth)),
     )
 }
@@ -0,0 +1,60 @@
+use anyhow::{anyhow, Result};
+use axum::{http::StatusCode, Extension};
+use chrono::Utc;
+use diesel::{update, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::database::{lib::get_pg_pool, models::User, schema::reranker_settings};
+use crate::routes::rest::ApiResponse;
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+/// Sends the organization back to the default reranker.
+pub async fn delete_reranker(
+    Extension(user): Extension<User>,
+) -> Result<ApiResponse<()>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match delete_reranker_handler(&user.id).await {
+        Ok(true) => Ok(ApiResponse::NoContent),
+        Ok(false) => Err((StatusCode::NOT_FOUND, "Reranker not found")),
+        Err(e) => {
+            tracing::error!("Error deleting reranker: {:?}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Error deleting reranker"))
+        }
+    }
+}
+
+async fn delete_reranker_handler(user_id: &Uuid) -> Result<bool> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match update(reranker_settings::table)
+        .filter(reranker_settings::organization_id.eq(organization_id))
+        .filter(reranker_settings::deleted_at.is_null())
+        .set((
+            reranker_settings::deleted_at.eq(Some(Utc::now())),
+            reranker_settings::updated_by.eq(user_id),
+        ))
+        .execute(&mut conn)
+        .await
+    {
+        Ok(deleted) => Ok(deleted > 0),
+        Err(e) => Err(anyhow!("Error deleting reranker: {}", e)),
+    }
+}
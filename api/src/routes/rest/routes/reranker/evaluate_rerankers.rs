@@ -0,0 +1,104 @@
+use anyhow::Result;
+use axum::{http::StatusCode, Extension, Json};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::database::{enums::Reranker, models::User};
+use crate::routes::rest::ApiResponse;
+use crate::routes::ws::threads_and_messages::post_thread::post_thread::get_user_datasets_with_metadata;
+use crate::utils::rerank_engine::evaluation::{
+    evaluate_reranker, LabelledQuestion, RerankerEvaluation,
+};
+use crate::utils::security::checks::is_user_workspace_admin;
+
+const MAX_QUESTIONS: usize = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct EvaluateRerankersRequest {
+    pub questions: Vec<LabelledQuestion>,
+    /// Left out, every reranker is evaluated.
+    pub rerankers: Option<Vec<Reranker>>,
+}
+
+/// Compares rerankers on questions labelled with the datasets that answer them, ranking the
+/// datasets the admin can see.
+pub async fn evaluate_rerankers(
+    Extension(user): Extension<User>,
+    Json(request): Json<EvaluateRerankersRequest>,
+) -> Result<ApiResponse<Vec<RerankerEvaluation>>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    if request.questions.is_empty() || request.questions.len() > MAX_QUESTIONS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Between 1 and 200 questions are required",
+        ));
+    }
+
+    if request
+        .questions
+        .iter()
+        .any(|question| question.dataset_ids.is_empty())
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Every question needs at least one dataset",
+        ));
+    }
+
+    let datasets = match get_user_datasets_with_metadata(&user.id).await {
+        Ok(datasets) => datasets,
+        Err(e) => {
+            tracing::error!("Error getting datasets: {:?}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Error getting datasets"));
+        }
+    };
+
+    let dataset_ids = datasets.iter().map(|d| d.dataset.id).collect::<Vec<Uuid>>();
+
+    if request.questions.iter().any(|question| {
+        question
+            .dataset_ids
+            .iter()
+            .any(|id| !dataset_ids.contains(id))
+    }) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Questions can only be labelled with datasets you can access",
+        ));
+    }
+
+    let documents = datasets
+        .into_iter()
+        .map(|d| d.dataset_ddl)
+        .collect::<Vec<String>>();
+
+    let rerankers = request.rerankers.unwrap_or_else(Reranker::all);
+
+    let mut evaluations = Vec::with_capacity(rerankers.len());
+
+    for reranker in rerankers {
+        evaluations.push(
+            evaluate_reranker(
+                reranker,
+                &request.questions,
+                &dataset_ids,
+                &documents,
+                &user.id,
+            )
+            .await,
+        );
+    }
+
+    Ok(ApiResponse::JsonData(evaluations))
+}
@@ -0,0 +1,59 @@
+use anyhow::{anyhow, Result};
+use axum::{http::StatusCode, Extension};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::{RerankerSetting, User},
+    schema::reranker_settings,
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+pub async fn get_reranker(
+    Extension(user): Extension<User>,
+) -> Result<ApiResponse<RerankerSetting>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match get_reranker_handler(&user.id).await {
+        Ok(Some(reranker_setting)) => Ok(ApiResponse::JsonData(reranker_setting)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Reranker not found")),
+        Err(e) => {
+            tracing::error!("Error getting reranker: {:?}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Error getting reranker"))
+        }
+    }
+}
+
+async fn get_reranker_handler(user_id: &Uuid) -> Result<Option<RerankerSetting>> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match reranker_settings::table
+        .filter(reranker_settings::organization_id.eq(organization_id))
+        .filter(reranker_settings::deleted_at.is_null())
+        .first::<RerankerSetting>(&mut conn)
+        .await
+    {
+        Ok(reranker_setting) => Ok(Some(reranker_setting)),
+        Err(diesel::NotFound) => Ok(None),
+        Err(e) => Err(anyhow!("Error getting reranker: {}", e)),
+    }
+}
@@ -0,0 +1,19 @@
+mod delete_reranker;
+mod evaluate_rerankers;
+mod get_reranker;
+mod put_reranker;
+
+use axum::{
+    routing::{delete, get, post, put},
+    Router,
+};
+
+/// How an organization's datasets are ranked against each question. There is at most one per
+/// organization.
+pub fn router() -> Router {
+    Router::new()
+        .route("/", get(get_reranker::get_reranker))
+        .route("/", put(put_reranker::put_reranker))
+        .route("/", delete(delete_reranker::delete_reranker))
+        .route("/evaluate", post(evaluate_rerankers::evaluate_rerankers))
+}
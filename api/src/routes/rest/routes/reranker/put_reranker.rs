@@ -0,0 +1,103 @@
+use anyhow::{anyhow, Result};
+use axum::{http::StatusCode, Extension, Json};
+use chrono::Utc;
+use diesel::{insert_into, update, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::database::{
+    enums::Reranker,
+    lib::get_pg_pool,
+    models::{RerankerSetting, User},
+    schema::reranker_settings,
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::security::checks::is_user_workspace_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+#[derive(Debug, Deserialize)]
+pub struct PutRerankerRequest {
+    pub reranker: Reranker,
+}
+
+/// Sets the reranker the organization's questions use, replacing the one it had.
+pub async fn put_reranker(
+    Extension(user): Extension<User>,
+    Json(request): Json<PutRerankerRequest>,
+) -> Result<ApiResponse<RerankerSetting>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match put_reranker_handler(&user.id, request.reranker).await {
+        Ok(reranker_setting) => Ok(ApiResponse::JsonData(reranker_setting)),
+        Err(e) => {
+            tracing::error!("Error setting reranker: {:?}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Error setting reranker"))
+        }
+    }
+}
+
+async fn put_reranker_handler(user_id: &Uuid, reranker: Reranker) -> Result<RerankerSetting> {
+    let organization_id = get_user_organization_id(user_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let existing_id = match reranker_settings::table
+        .filter(reranker_settings::organization_id.eq(organization_id))
+        .filter(reranker_settings::deleted_at.is_null())
+        .select(reranker_settings::id)
+        .first::<Uuid>(&mut conn)
+        .await
+    {
+        Ok(existing_id) => existing_id,
+        Err(diesel::NotFound) => {
+            let reranker_setting = RerankerSetting {
+                id: Uuid::new_v4(),
+                organization_id,
+                reranker,
+                created_by: *user_id,
+                updated_by: *user_id,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                deleted_at: None,
+            };
+
+            return match insert_into(reranker_settings::table)
+                .values(&reranker_setting)
+                .execute(&mut conn)
+                .await
+            {
+                Ok(_) => Ok(reranker_setting),
+                Err(e) => Err(anyhow!("Error inserting reranker: {}", e)),
+            };
+        }
+        Err(e) => return Err(anyhow!("Error getting reranker: {}", e)),
+    };
+
+    match update(reranker_settings::table)
+        .filter(reranker_settings::id.eq(existing_id))
+        .set((
+            reranker_settings::reranker.eq(reranker),
+            reranker_settings::updated_by.eq(user_id),
+            reranker_settings::updated_at.eq(Utc::now()),
+        ))
+        .get_result::<RerankerSetting>(&mut conn)
+        .await
+    {
+        Ok(reranker_setting) => Ok(reranker_setting),
+        Err(e) => Err(anyhow!("Error updating reranker: {}", e)),
+    }
+}
@@ -0,0 +1,21 @@
+use axum::{extract::Path, http::StatusCode, Extension};
+use uuid::Uuid;
+
+use crate::database::models::User;
+use crate::routes::rest::ApiResponse;
+use crate::utils::query_engine::query_cancellation::cancel_query;
+
+/// Cancels a query started with the `query_id` passed to `/sql/run`. Only the user who started
+/// the query can cancel it.
+pub async fn cancel_sql(
+    Extension(user): Extension<User>,
+    Path(query_id): Path<Uuid>,
+) -> Result<ApiResponse<()>, (StatusCode, &'static str)> {
+    match cancel_query(&query_id, &user.id) {
+        Ok(_) => Ok(ApiResponse::NoContent),
+        Err(e) => {
+            tracing::error!("Error cancelling query {}: {:?}", query_id, e);
+            Err((StatusCode::NOT_FOUND, "Query not found"))
+        }
+    }
+}
@@ -1,7 +1,16 @@
-use axum::{routing::post, Router};
+use axum::{
+    routing::{delete, post},
+    Router,
+};
 
+mod cancel_sql;
+mod page_sql;
 mod run_sql;
 
 pub fn router() -> Router {
-    Router::new().route("/run", post(run_sql::run_sql))
+    Router::new()
+        .route("/run", post(run_sql::run_sql))
+        .route("/page", post(page_sql::page_sql))
+        .route("/page/:query_handle", delete(page_sql::close_page_sql))
+        .route("/:query_id", delete(cancel_sql::cancel_sql))
 }
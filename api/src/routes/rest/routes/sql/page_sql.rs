@@ -0,0 +1,69 @@
+use anyhow::{anyhow, Result};
+use axum::{extract::Path, http::StatusCode, Extension, Json};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::database::models::User;
+use crate::routes::rest::ApiResponse;
+use crate::utils::query_engine::{
+    query_cancellation::QueryContext,
+    query_pagination::{close_paged_query, get_query_page, get_query_page_at_cursor},
+};
+
+use super::run_sql::{paged_data_object, PagedDataObject};
+
+/// Either `cursor` (the `next_cursor` of an earlier page) or `query_handle` with an `offset`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PageSqlRequest {
+    pub query_handle: Option<Uuid>,
+    pub offset: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+/// Fetches another page of a query started through `/sql/run` with a `page_size`.
+pub async fn page_sql(
+    Extension(user): Extension<User>,
+    Json(req): Json<PageSqlRequest>,
+) -> Result<ApiResponse<PagedDataObject>, (StatusCode, &'static str)> {
+    let paged_data_object = match page_sql_handler(&req, &user.id).await {
+        Ok(paged_data_object) => paged_data_object,
+        Err(e) => {
+            tracing::error!("Error fetching SQL page: {:?}", e);
+            let err_msg = format!("Error fetching SQL page: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Box::leak(err_msg.into_boxed_str()),
+            ));
+        }
+    };
+
+    Ok(ApiResponse::JsonData(paged_data_object))
+}
+
+/// Releases a paged query before it idles out.
+pub async fn close_page_sql(
+    Extension(user): Extension<User>,
+    Path(query_handle): Path<Uuid>,
+) -> Result<ApiResponse<()>, (StatusCode, &'static str)> {
+    match close_paged_query(&query_handle, &user.id) {
+        Ok(_) => Ok(ApiResponse::NoContent),
+        Err(e) => {
+            tracing::error!("Error closing paged query {}: {:?}", query_handle, e);
+            Err((StatusCode::NOT_FOUND, "Query handle not found"))
+        }
+    }
+}
+
+async fn page_sql_handler(req: &PageSqlRequest, user_id: &Uuid) -> Result<PagedDataObject> {
+    let context = QueryContext::for_user(None, user_id);
+
+    let page = match (&req.cursor, &req.query_handle) {
+        (Some(cursor), _) => get_query_page_at_cursor(cursor, &context).await?,
+        (None, Some(query_handle)) => {
+            get_query_page(query_handle, req.offset.unwrap_or(0), &context).await?
+        }
+        (None, None) => return Err(anyhow!("No cursor or query handle provided")),
+    };
+
+    paged_data_object(page).await
+}
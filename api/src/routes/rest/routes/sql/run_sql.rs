@@ -1,13 +1,24 @@
 use anyhow::{anyhow, Result};
-use axum::{Extension, Json};
+use arrow::ipc::writer::StreamWriter;
+use axum::{
+    body::Body,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
 use diesel::{BoolExpressionMethods, ExpressionMethods, JoinOnDsl, QueryDsl};
+use futures::StreamExt;
 use indexmap::IndexMap;
+use parquet::arrow::ArrowWriter;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use reqwest::StatusCode;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use uuid::Uuid;
 
 use diesel_async::RunQueryDsl;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 use crate::{
     database::{
@@ -18,30 +29,152 @@ use crate::{
     },
     routes::rest::ApiResponse,
     utils::{
+        audit::audit_log::{spawn_audit_event, AuditRequestMetadata, NewAuditEvent},
         query_engine::{
+            arrow_conversion::RecordBatchStream,
             data_types::DataType,
-            query_engine::{modeling_query_engine, query_engine},
+            query_cache::QueryCacheMetadata,
+            query_cancellation::QueryContext,
+            query_engine::{
+                cached_query_engine, modeling_paged_query_engine, modeling_query_engine,
+                modeling_query_engine_stream, paged_query_engine, query_engine_stream,
+            },
+            query_pagination::QueryPage,
+        },
+        security::{
+            checks::is_user_workspace_admin_or_data_admin, dataset_security::has_dataset_access,
         },
-        security::dataset_security::has_dataset_access,
     },
 };
 
 const MAX_UNIQUE_VALUES: usize = 100;
 
+const ARROW_STREAM_MIME_TYPE: &str = "application/vnd.apache.arrow.stream";
+const PARQUET_MIME_TYPE: &str = "application/vnd.apache.parquet";
+
+/// Rough size of a Parquet row group before it is flushed to the response, which bounds how much
+/// of the file is held in memory.
+const PARQUET_ROW_GROUP_BYTES: usize = 16 * 1024 * 1024;
+
+/// Result encodings supported by `/sql/run`, negotiated through the `Accept` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResultFormat {
+    Json,
+    ArrowIpc,
+    Parquet,
+}
+
+impl ResultFormat {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let accept = match headers
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+        {
+            Some(accept) => accept.to_lowercase(),
+            None => return ResultFormat::Json,
+        };
+
+        for media_type in accept.split(',').map(|part| part.split(';').next().unwrap_or("").trim()) {
+            match media_type {
+                ARROW_STREAM_MIME_TYPE => return ResultFormat::ArrowIpc,
+                PARQUET_MIME_TYPE | "application/x-parquet" => return ResultFormat::Parquet,
+                "application/json" | "*/*" => return ResultFormat::Json,
+                _ => (),
+            }
+        }
+
+        ResultFormat::Json
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RunSqlRequest {
     pub dataset_id: Option<Uuid>,
     pub data_source_id: Option<Uuid>,
     pub sql: String,
+    /// Lets the client cancel the query through `DELETE /sql/{query_id}` while it runs.
+    pub query_id: Option<Uuid>,
+    /// Returns only the first page of this many rows, always as JSON, along with a query handle
+    /// for fetching the rest through `POST /sql/page`.
+    pub page_size: Option<i64>,
+    /// Runs a dataset query as if the user had these attributes, to check what policies and
+    /// templated SQL resolve to for them. Only workspace and data admins can preview.
+    pub preview_attributes: Option<Map<String, Value>>,
 }
 
 pub async fn run_sql(
     Extension(user): Extension<User>,
+    Extension(request_metadata): Extension<AuditRequestMetadata>,
+    headers: HeaderMap,
     Json(req): Json<RunSqlRequest>,
-) -> Result<ApiResponse<DataObject>, (StatusCode, &'static str)> {
-    let data_object =
-        match run_sql_handler(&req.sql, &req.data_source_id, &req.dataset_id, &user.id).await {
-            Ok(data_object) => data_object,
+) -> Result<Response, (StatusCode, &'static str)> {
+    spawn_audit_event(NewAuditEvent::sql_run(
+        user.id,
+        &req.sql,
+        req.dataset_id,
+        req.data_source_id,
+        request_metadata,
+    ));
+
+    if req.preview_attributes.is_some() {
+        match is_user_workspace_admin_or_data_admin(&user.id).await {
+            Ok(true) => (),
+            Ok(false) => {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    "Only admins can preview results as other attributes",
+                ))
+            }
+            Err(e) => {
+                tracing::error!("Error checking user permissions: {:?}", e);
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Error checking user permissions",
+                ));
+            }
+        }
+    }
+
+    if req.page_size.is_some() {
+        let paged_data_object = match run_paged_sql_handler(
+            &req.sql,
+            &req.data_source_id,
+            &req.dataset_id,
+            &user.id,
+            req.page_size,
+            req.query_id,
+            req.preview_attributes.clone(),
+        )
+        .await
+        {
+            Ok(paged_data_object) => paged_data_object,
+            Err(e) => {
+                tracing::error!("Error running SQL: {:?}", e);
+                let err_msg = format!("Error running SQL: {:?}", e);
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Box::leak(err_msg.into_boxed_str()),
+                ));
+            }
+        };
+
+        return Ok(ApiResponse::JsonData(paged_data_object).into_response());
+    }
+
+    let format = ResultFormat::from_headers(&headers);
+
+    if format != ResultFormat::Json {
+        let stream = match run_sql_stream_handler(
+            &req.sql,
+            &req.data_source_id,
+            &req.dataset_id,
+            &user.id,
+            req.query_id,
+            req.preview_attributes.clone(),
+        )
+        .await
+        {
+            Ok(stream) => stream,
             Err(e) => {
                 tracing::error!("Error running SQL: {:?}", e);
                 let err_msg = format!("Error running SQL: {:?}", e);
@@ -52,7 +185,182 @@ pub async fn run_sql(
             }
         };
 
-    Ok(ApiResponse::JsonData(data_object))
+        let encoded = match format {
+            ResultFormat::ArrowIpc => encode_arrow_ipc(stream).await,
+            _ => encode_parquet(stream).await,
+        };
+
+        return match encoded {
+            Ok(response) => Ok(response),
+            Err(e) => {
+                tracing::error!("Error encoding SQL results: {:?}", e);
+                let err_msg = format!("Error running SQL: {:?}", e);
+                Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Box::leak(err_msg.into_boxed_str()),
+                ))
+            }
+        };
+    }
+
+    let data_object = match run_sql_handler(
+        &req.sql,
+        &req.data_source_id,
+        &req.dataset_id,
+        &user.id,
+        req.query_id,
+        req.preview_attributes,
+    )
+    .await
+    {
+        Ok(data_object) => data_object,
+        Err(e) => {
+            tracing::error!("Error running SQL: {:?}", e);
+            let err_msg = format!("Error running SQL: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Box::leak(err_msg.into_boxed_str()),
+            ));
+        }
+    };
+
+    Ok(ApiResponse::JsonData(data_object).into_response())
+}
+
+async fn run_sql_stream_handler(
+    sql: &String,
+    data_source_id: &Option<Uuid>,
+    dataset_id: &Option<Uuid>,
+    user_id: &Uuid,
+    query_id: Option<Uuid>,
+    preview_attributes: Option<Map<String, Value>>,
+) -> Result<RecordBatchStream> {
+    if let Some(data_source_id) = data_source_id {
+        modeling_query_engine_stream(data_source_id, sql, user_id, query_id).await
+    } else if let Some(dataset_id) = dataset_id {
+        check_dataset_sql_access(dataset_id, user_id).await?;
+        let context =
+            QueryContext::for_user(query_id, user_id).with_preview_attributes(preview_attributes);
+        query_engine_stream(dataset_id, sql, &context).await
+    } else {
+        Err(anyhow!("No data source or dataset id provided"))
+    }
+}
+
+/// Streams the results as an Arrow IPC stream. The first batch is awaited before the response is
+/// built so that query errors still surface as a 500 instead of a truncated body.
+async fn encode_arrow_ipc(mut stream: RecordBatchStream) -> Result<Response> {
+    let first_batch = stream.next().await.transpose()?;
+
+    let schema = match &first_batch {
+        Some(batch) => batch.schema(),
+        None => std::sync::Arc::new(arrow::datatypes::Schema::empty()),
+    };
+
+    let mut writer = StreamWriter::try_new(Vec::new(), &schema)?;
+
+    if let Some(batch) = &first_batch {
+        writer.write(batch)?;
+    }
+
+    let (tx, rx) = mpsc::channel::<Result<Vec<u8>, std::io::Error>>(4);
+
+    tokio::spawn(async move {
+        let _ = tx.send(Ok(std::mem::take(writer.get_mut()))).await;
+
+        while let Some(batch) = stream.next().await {
+            let written = batch.and_then(|batch| writer.write(&batch).map_err(|e| anyhow!(e)));
+
+            if let Err(e) = written {
+                tracing::error!("Error streaming SQL results: {:?}", e);
+                let _ = tx.send(Err(std::io::Error::other(e.to_string()))).await;
+                return;
+            }
+
+            if tx.send(Ok(std::mem::take(writer.get_mut()))).await.is_err() {
+                return;
+            }
+        }
+
+        match writer.finish() {
+            Ok(_) => {
+                let _ = tx.send(Ok(std::mem::take(writer.get_mut()))).await;
+            }
+            Err(e) => {
+                let _ = tx.send(Err(std::io::Error::other(e.to_string()))).await;
+            }
+        }
+    });
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, ARROW_STREAM_MIME_TYPE)],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response())
+}
+
+/// Streams the results as a Parquet file, sending each row group as soon as it is written; the
+/// footer follows the last one. As with Arrow IPC, the first batch is awaited before the response
+/// is built so that query errors still surface as a 500.
+async fn encode_parquet(mut stream: RecordBatchStream) -> Result<Response> {
+    let first_batch = stream.next().await.transpose()?;
+
+    let schema = match &first_batch {
+        Some(batch) => batch.schema(),
+        None => std::sync::Arc::new(arrow::datatypes::Schema::empty()),
+    };
+
+    let mut writer = ArrowWriter::try_new(Vec::new(), schema, None)?;
+
+    if let Some(batch) = &first_batch {
+        writer.write(batch)?;
+    }
+
+    let (tx, rx) = mpsc::channel::<Result<Vec<u8>, std::io::Error>>(4);
+
+    tokio::spawn(async move {
+        while let Some(batch) = stream.next().await {
+            let written = batch.and_then(|batch| {
+                writer.write(&batch)?;
+
+                if writer.in_progress_size() >= PARQUET_ROW_GROUP_BYTES {
+                    writer.flush()?;
+                }
+
+                Ok(())
+            });
+
+            if let Err(e) = written {
+                tracing::error!("Error streaming SQL results: {:?}", e);
+                let _ = tx.send(Err(std::io::Error::other(e.to_string()))).await;
+                return;
+            }
+
+            // The writer only ever appends, so whatever it has written so far can be sent on.
+            let bytes = std::mem::take(writer.inner_mut());
+
+            if !bytes.is_empty() && tx.send(Ok(bytes)).await.is_err() {
+                return;
+            }
+        }
+
+        match writer.into_inner() {
+            Ok(bytes) => {
+                let _ = tx.send(Ok(bytes)).await;
+            }
+            Err(e) => {
+                let _ = tx.send(Err(std::io::Error::other(e.to_string()))).await;
+            }
+        }
+    });
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, PARQUET_MIME_TYPE)],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response())
 }
 
 async fn run_sql_handler(
@@ -60,21 +368,62 @@ async fn run_sql_handler(
     data_source_id: &Option<Uuid>,
     dataset_id: &Option<Uuid>,
     user_id: &Uuid,
+    query_id: Option<Uuid>,
+    preview_attributes: Option<Map<String, Value>>,
 ) -> Result<DataObject> {
     if let Some(data_source_id) = data_source_id {
-        return run_data_source_sql_handler(sql, &data_source_id, user_id).await;
+        return run_data_source_sql_handler(sql, &data_source_id, user_id, query_id).await;
     } else if let Some(dataset_id) = dataset_id {
-        return run_dataset_sql_handler(sql, &dataset_id, user_id).await;
+        return run_dataset_sql_handler(sql, &dataset_id, user_id, query_id, preview_attributes)
+            .await;
     } else {
         return Err(anyhow!("No data source or dataset id provided"));
     }
 }
 
+async fn run_paged_sql_handler(
+    sql: &String,
+    data_source_id: &Option<Uuid>,
+    dataset_id: &Option<Uuid>,
+    user_id: &Uuid,
+    page_size: Option<i64>,
+    query_id: Option<Uuid>,
+    preview_attributes: Option<Map<String, Value>>,
+) -> Result<PagedDataObject> {
+    let page = if let Some(data_source_id) = data_source_id {
+        modeling_paged_query_engine(data_source_id, sql, user_id, page_size, query_id).await?
+    } else if let Some(dataset_id) = dataset_id {
+        check_dataset_sql_access(dataset_id, user_id).await?;
+        paged_query_engine(
+            dataset_id,
+            sql,
+            page_size,
+            &QueryContext::for_user(query_id, user_id).with_preview_attributes(preview_attributes),
+        )
+        .await?
+    } else {
+        return Err(anyhow!("No data source or dataset id provided"));
+    };
+
+    paged_data_object(page).await
+}
+
 async fn run_dataset_sql_handler(
     sql: &String,
     dataset_id: &Uuid,
     user_id: &Uuid,
+    query_id: Option<Uuid>,
+    preview_attributes: Option<Map<String, Value>>,
 ) -> Result<DataObject> {
+    check_dataset_sql_access(dataset_id, user_id).await?;
+
+    let context =
+        QueryContext::for_user(query_id, user_id).with_preview_attributes(preview_attributes);
+
+    fetch_data(sql, dataset_id, &context).await
+}
+
+async fn check_dataset_sql_access(dataset_id: &Uuid, user_id: &Uuid) -> Result<()> {
     let has_dataset_access = match has_dataset_access(user_id, dataset_id).await {
         Ok(has_access) => has_access,
         Err(e) => return Err(e),
@@ -102,27 +451,55 @@ async fn run_dataset_sql_handler(
         .await
         .is_ok();
 
-    let results = if is_org_admin_or_owner || has_dataset_access {
-        match fetch_data(sql, dataset_id).await {
-            Ok(results) => results,
-            Err(e) => return Err(e),
-        }
+    if is_org_admin_or_owner || has_dataset_access {
+        Ok(())
     } else {
-        return Err(anyhow!("User does not have access to this dataset"));
-    };
-
-    Ok(results)
+        Err(anyhow!("User does not have access to this dataset"))
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct DataObject {
     pub data: Vec<IndexMap<String, DataType>>,
     pub data_metadata: DataMetadataJsonBody,
+    pub cache: Option<QueryCacheMetadata>,
 }
 
-pub async fn fetch_data(sql: &String, dataset_id: &Uuid) -> Result<DataObject> {
-    let data = match query_engine(&dataset_id, &sql).await {
-        Ok(data) => data,
+#[derive(Debug, Serialize)]
+pub struct PagedDataObject {
+    pub query_handle: Uuid,
+    pub data: Vec<IndexMap<String, DataType>>,
+    pub data_metadata: DataMetadataJsonBody,
+    pub offset: i64,
+    pub page_size: i64,
+    pub total_rows: Option<i64>,
+    pub has_more: bool,
+    pub next_cursor: Option<String>,
+}
+
+/// The metadata describes the rows on the page, not the whole result.
+pub async fn paged_data_object(page: QueryPage) -> Result<PagedDataObject> {
+    let data_metadata = process_data_metadata(&page.rows).await?;
+
+    Ok(PagedDataObject {
+        query_handle: page.query_handle,
+        data: page.rows,
+        data_metadata,
+        offset: page.offset,
+        page_size: page.page_size,
+        total_rows: page.total_rows,
+        has_more: page.has_more,
+        next_cursor: page.next_cursor,
+    })
+}
+
+pub async fn fetch_data(
+    sql: &String,
+    dataset_id: &Uuid,
+    context: &QueryContext,
+) -> Result<DataObject> {
+    let (data, cache) = match cached_query_engine(&dataset_id, &sql, context).await {
+        Ok(results) => results,
         Err(e) => {
             return Err(anyhow!(e));
         }
@@ -138,6 +515,7 @@ pub async fn fetch_data(sql: &String, dataset_id: &Uuid) -> Result<DataObject> {
     Ok(DataObject {
         data,
         data_metadata,
+        cache: Some(cache),
     })
 }
 
@@ -312,8 +690,9 @@ async fn run_data_source_sql_handler(
     sql: &String,
     data_source_id: &Uuid,
     user_id: &Uuid,
+    query_id: Option<Uuid>,
 ) -> Result<DataObject> {
-    let data = match modeling_query_engine(data_source_id, sql, user_id).await {
+    let data = match modeling_query_engine(data_source_id, sql, user_id, query_id).await {
         Ok(data) => data,
         Err(e) => return Err(e),
     };
@@ -326,7 +705,146 @@ async fn run_data_source_sql_handler(
     let data_object = DataObject {
         data,
         data_metadata,
+        cache: None,
     };
 
     Ok(data_object)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::query_engine::arrow_conversion::{
+        rows_to_record_batch_stream, RECORD_BATCH_SIZE,
+    };
+    use arrow::{
+        array::{Array, Int32Array, StringArray},
+        ipc::reader::StreamReader,
+        record_batch::RecordBatch,
+    };
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    const ROW_COUNT: usize = RECORD_BATCH_SIZE * 2 + 5;
+
+    fn rows() -> RecordBatchStream {
+        let rows = (0..ROW_COUNT)
+            .map(|i| {
+                let mut row = IndexMap::new();
+                row.insert("id".to_string(), DataType::Int4(Some(i as i32)));
+                row.insert(
+                    "name".to_string(),
+                    DataType::Text((i % 2 == 0).then(|| format!("row {}", i))),
+                );
+                row
+            })
+            .collect();
+
+        rows_to_record_batch_stream(rows)
+    }
+
+    async fn body(response: Response) -> Result<axum::body::Bytes, axum::Error> {
+        axum::body::to_bytes(response.into_body(), usize::MAX).await
+    }
+
+    fn assert_rows(batches: &[RecordBatch]) {
+        assert_eq!(
+            batches.iter().map(|b| b.num_rows()).sum::<usize>(),
+            ROW_COUNT
+        );
+
+        let ids = batches
+            .iter()
+            .flat_map(|batch| {
+                let ids = batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .unwrap();
+                ids.values().to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids, (0..ROW_COUNT as i32).collect::<Vec<_>>());
+
+        let names = batches[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(names.value(0), "row 0");
+        assert!(names.is_null(1));
+    }
+
+    #[tokio::test]
+    async fn arrow_ipc_carries_every_batch() {
+        let response = encode_arrow_ipc(rows()).await.unwrap();
+        let bytes = body(response).await.unwrap();
+
+        let batches = StreamReader::try_new(std::io::Cursor::new(bytes), None)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_rows(&batches);
+    }
+
+    #[tokio::test]
+    async fn parquet_carries_every_batch() {
+        let response = encode_parquet(rows()).await.unwrap();
+        let bytes = body(response).await.unwrap();
+
+        assert_eq!(&bytes[..4], b"PAR1");
+        assert_eq!(&bytes[bytes.len() - 4..], b"PAR1");
+
+        let batches = ParquetRecordBatchReaderBuilder::try_new(bytes)
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_rows(&batches);
+    }
+
+    #[tokio::test]
+    async fn empty_results_are_valid_files() {
+        let empty = || -> RecordBatchStream { Box::pin(futures::stream::empty()) };
+
+        let bytes = body(encode_parquet(empty()).await.unwrap()).await.unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(bytes).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 0);
+
+        let bytes = body(encode_arrow_ipc(empty()).await.unwrap())
+            .await
+            .unwrap();
+        let reader = StreamReader::try_new(std::io::Cursor::new(bytes), None).unwrap();
+        assert_eq!(reader.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn errors_before_the_first_batch_fail_the_request() {
+        let failing = || -> RecordBatchStream {
+            Box::pin(futures::stream::iter(vec![Err(anyhow!(
+                "relation does not exist"
+            ))]))
+        };
+
+        assert!(encode_arrow_ipc(failing()).await.is_err());
+        assert!(encode_parquet(failing()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn errors_after_the_first_batch_abort_the_body() {
+        let failing = || -> RecordBatchStream {
+            Box::pin(rows().chain(futures::stream::iter(vec![Err(anyhow!(
+                "connection reset"
+            ))])))
+        };
+
+        assert!(body(encode_arrow_ipc(failing()).await.unwrap())
+            .await
+            .is_err());
+        assert!(body(encode_parquet(failing()).await.unwrap())
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,10 @@
+use axum::{routing::put, Router};
+
+pub mod update_team_attributes;
+
+pub fn router() -> Router {
+    Router::new().route(
+        "/:team_id/attributes",
+        put(update_team_attributes::update_team_attributes),
+    )
+}
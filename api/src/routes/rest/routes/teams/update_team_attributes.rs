@@ -0,0 +1,76 @@
+use anyhow::{anyhow, Result};
+use axum::http::StatusCode;
+use axum::{extract::Path, Extension, Json};
+use chrono::Utc;
+use diesel::{update, ExpressionMethods};
+use diesel_async::RunQueryDsl;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::database::{lib::get_pg_pool, models::User, schema::teams};
+use crate::routes::rest::ApiResponse;
+use crate::utils::clients::sentry_utils::send_sentry_error;
+use crate::utils::security::checks::is_user_workspace_admin_or_data_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+/// Replaces the attributes a team's members inherit. Members' own attributes take precedence.
+pub async fn update_team_attributes(
+    Extension(user): Extension<User>,
+    Path(team_id): Path<Uuid>,
+    Json(attributes): Json<Map<String, Value>>,
+) -> Result<ApiResponse<Value>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin_or_data_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match update_team_attributes_handler(&user.id, &team_id, Value::Object(attributes)).await {
+        Ok(Some(attributes)) => Ok(ApiResponse::JsonData(attributes)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Team not found")),
+        Err(e) => {
+            tracing::error!("Error updating team attributes: {:?}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error updating team attributes",
+            ))
+        }
+    }
+}
+
+async fn update_team_attributes_handler(
+    admin_id: &Uuid,
+    team_id: &Uuid,
+    attributes: Value,
+) -> Result<Option<Value>> {
+    let organization_id = get_user_organization_id(admin_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match update(teams::table)
+        .filter(teams::id.eq(team_id))
+        .filter(teams::organization_id.eq(organization_id))
+        .filter(teams::deleted_at.is_null())
+        .set((
+            teams::attributes.eq(&attributes),
+            teams::updated_at.eq(Utc::now()),
+        ))
+        .returning(teams::attributes)
+        .get_result::<Value>(&mut conn)
+        .await
+    {
+        Ok(attributes) => Ok(Some(attributes)),
+        Err(diesel::NotFound) => Ok(None),
+        Err(e) => Err(anyhow!("Error updating team attributes: {}", e)),
+    }
+}
@@ -10,11 +10,13 @@ use crate::database::schema::{
 };
 use crate::routes::rest::ApiResponse;
 use crate::utils::clients::sentry_utils::send_sentry_error;
+use crate::utils::user::user_attributes::get_user_attributes;
 use axum::http::StatusCode;
 use diesel::{
     BoolExpressionMethods, ExpressionMethods, JoinOnDsl, NullableExpressionMethods, QueryDsl,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use uuid::Uuid;
 
 pub async fn get_user(
@@ -54,6 +56,8 @@ pub struct UserInfoObject {
     pub user: User,
     pub organizations: Vec<UserOrganization>,
     pub teams: Vec<UserTeam>,
+    /// The user's own attributes merged with the ones inherited from their teams.
+    pub attributes: Map<String, Value>,
 }
 
 pub async fn get_user_information(user_id: &Uuid) -> Result<UserInfoObject> {
@@ -95,6 +99,7 @@ pub async fn get_user_information(user_id: &Uuid) -> Result<UserInfoObject> {
                 users::config,
                 users::created_at,
                 users::updated_at,
+                users::attributes,
             ),
             (
                 teams::id,
@@ -109,6 +114,7 @@ pub async fn get_user_information(user_id: &Uuid) -> Result<UserInfoObject> {
                 teams::created_at,
                 teams::updated_at,
                 teams::deleted_at,
+                teams::attributes,
             )
                 .nullable(),
             (
@@ -166,9 +172,12 @@ pub async fn get_user_information(user_id: &Uuid) -> Result<UserInfoObject> {
         })
         .collect();
 
+    let attributes = get_user_attributes(user_id).await?;
+
     Ok(UserInfoObject {
         user,
         teams,
         organizations,
+        attributes,
     })
 }
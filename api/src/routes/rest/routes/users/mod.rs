@@ -5,9 +5,14 @@ use axum::{
 
 pub mod get_user;
 pub mod update_user;
+pub mod update_user_attributes;
 
 pub fn router() -> Router {
     Router::new()
         .route("/", get(get_user::get_user))
         .route("/", put(update_user::update_user))
+        .route(
+            "/:user_id/attributes",
+            put(update_user_attributes::update_user_attributes),
+        )
 }
@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Result};
+use axum::http::StatusCode;
+use axum::{extract::Path, Extension, Json};
+use chrono::Utc;
+use diesel::{update, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::User,
+    schema::{users, users_to_organizations},
+};
+use crate::routes::rest::ApiResponse;
+use crate::utils::clients::sentry_utils::send_sentry_error;
+use crate::utils::security::checks::is_user_workspace_admin_or_data_admin;
+use crate::utils::user::user_info::get_user_organization_id;
+
+/// Replaces the attributes set on a user, which take precedence over the ones inherited from their
+/// teams. Only admins can set them, since users could otherwise widen what their policies let
+/// them see.
+pub async fn update_user_attributes(
+    Extension(user): Extension<User>,
+    Path(user_id): Path<Uuid>,
+    Json(attributes): Json<Map<String, Value>>,
+) -> Result<ApiResponse<Value>, (StatusCode, &'static str)> {
+    match is_user_workspace_admin_or_data_admin(&user.id).await {
+        Ok(true) => (),
+        Ok(false) => return Err((StatusCode::FORBIDDEN, "Insufficient permissions")),
+        Err(e) => {
+            tracing::error!("Error checking user permissions: {:?}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error checking user permissions",
+            ));
+        }
+    }
+
+    match update_user_attributes_handler(&user.id, &user_id, Value::Object(attributes)).await {
+        Ok(Some(attributes)) => Ok(ApiResponse::JsonData(attributes)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "User not found")),
+        Err(e) => {
+            tracing::error!("Error updating user attributes: {:?}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error updating user attributes",
+            ))
+        }
+    }
+}
+
+async fn update_user_attributes_handler(
+    admin_id: &Uuid,
+    user_id: &Uuid,
+    attributes: Value,
+) -> Result<Option<Value>> {
+    let organization_id = get_user_organization_id(admin_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let organization_user_ids = users_to_organizations::table
+        .filter(users_to_organizations::organization_id.eq(organization_id))
+        .filter(users_to_organizations::deleted_at.is_null())
+        .select(users_to_organizations::user_id);
+
+    match update(users::table)
+        .filter(users::id.eq(user_id))
+        .filter(users::id.eq_any(organization_user_ids))
+        .set((
+            users::attributes.eq(&attributes),
+            users::updated_at.eq(Utc::now()),
+        ))
+        .returning(users::attributes)
+        .get_result::<Value>(&mut conn)
+        .await
+    {
+        Ok(attributes) => Ok(Some(attributes)),
+        Err(diesel::NotFound) => Ok(None),
+        Err(e) => Err(anyhow!("Error updating user attributes: {}", e)),
+    }
+}
@@ -1,9 +1,19 @@
 mod embeddings;
 
-use axum::{routing::post, Router};
+use axum::{middleware, routing::post, Router};
+
+use crate::{
+    buster_middleware::webhook_auth::webhook_auth,
+    utils::security::webhook_signatures::WebhookIntegration,
+};
 
 use self::embeddings::create_embedding::create_record_embedding;
 
 pub fn router() -> Router {
-    Router::new().route("/embeddings", post(create_record_embedding))
+    Router::new()
+        .route("/embeddings", post(create_record_embedding))
+        .route_layer(middleware::from_fn_with_state(
+            WebhookIntegration::Embeddings,
+            webhook_auth,
+        ))
 }
@@ -0,0 +1,479 @@
+//! Runs the SCIM endpoints on a local server against the local database, the way an identity
+//! provider drives them: provisioning, lookups by filter, deactivation and group membership.
+//!
+//! Needs the database from `make dev` (or `DATABASE_URL`), so it is ignored by default:
+//!
+//! ```sh
+//! cargo test scim_conformance -- --ignored
+//! ```
+//!
+//! Every run provisions into a fresh organization, so it can be run repeatedly.
+
+use axum::{Extension, Router};
+use chrono::Utc;
+use diesel_async::RunQueryDsl;
+use reqwest::{Client, Method, StatusCode};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    buster_middleware::scim_auth::ScimContext,
+    database::{
+        enums::{SharingSetting, UserOrganizationRole, UserOrganizationStatus},
+        lib::{get_pg_pool, init_test_pg_pool},
+        models::{Organization, User, UserToOrganization},
+        schema::{organizations, users, users_to_organizations},
+    },
+    utils::security::checks::is_user_deactivated,
+};
+
+use super::{
+    routes,
+    service_provider_config::{GROUP_SCHEMA, USER_SCHEMA},
+};
+
+struct ScimServer {
+    client: Client,
+    base_url: String,
+}
+
+impl ScimServer {
+    /// Serves the SCIM routes for a new organization administered by a new workspace admin.
+    async fn start() -> ScimServer {
+        init_test_pg_pool().await.unwrap();
+        let mut conn = get_pg_pool().get().await.unwrap();
+
+        let organization = Organization {
+            id: Uuid::new_v4(),
+            name: "SCIM conformance".to_string(),
+            domain: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        };
+
+        let admin = User {
+            id: Uuid::new_v4(),
+            email: format!("scim-admin+{}@example.com", organization.id),
+            name: Some("SCIM Admin".to_string()),
+            config: json!({}),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            attributes: json!({}),
+        };
+
+        let membership = UserToOrganization {
+            user_id: admin.id,
+            organization_id: organization.id,
+            role: UserOrganizationRole::WorkspaceAdmin,
+            sharing_setting: SharingSetting::Public,
+            edit_sql: true,
+            upload_csv: true,
+            export_assets: true,
+            email_slack_enabled: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+            created_by: admin.id,
+            updated_by: admin.id,
+            deleted_by: None,
+            status: UserOrganizationStatus::Active,
+        };
+
+        diesel::insert_into(organizations::table)
+            .values(&organization)
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        diesel::insert_into(users::table)
+            .values(&admin)
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        diesel::insert_into(users_to_organizations::table)
+            .values(&membership)
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let app = Router::new()
+            .nest("/scim/v2", routes())
+            .layer(Extension(ScimContext {
+                organization_id: organization.id,
+                actor_id: admin.id,
+            }));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        ScimServer {
+            client: Client::new(),
+            base_url: format!("http://{}/scim/v2", addr),
+        }
+    }
+
+    async fn send(&self, method: Method, path: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", self.base_url, path));
+
+        if let Some(body) = body {
+            request = request
+                .header("Content-Type", "application/scim+json")
+                .body(body.to_string());
+        }
+
+        let response = request.send().await.unwrap();
+        let status = response.status();
+
+        if status != StatusCode::NO_CONTENT {
+            assert_eq!(
+                response.headers()["Content-Type"],
+                "application/scim+json",
+                "{} {}",
+                status,
+                path
+            );
+        }
+
+        let body = response.text().await.unwrap();
+
+        (status, serde_json::from_str(&body).unwrap_or(Value::Null))
+    }
+
+    /// Lists resources matching a filter and returns the total and the ids on the page.
+    async fn list(&self, resource: &str, filter: &str) -> (i64, Vec<String>) {
+        let path = with_filter(&format!("/{}", resource), filter);
+        let (status, body) = self.send(Method::GET, &path, None).await;
+
+        assert_eq!(status, StatusCode::OK, "{}: {}", filter, body);
+        assert_eq!(
+            body["schemas"],
+            json!(["urn:ietf:params:scim:api:messages:2.0:ListResponse"])
+        );
+
+        let ids = body["Resources"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|resource| resource["id"].as_str().unwrap().to_string())
+            .collect();
+
+        (body["totalResults"].as_i64().unwrap(), ids)
+    }
+}
+
+fn with_filter(path: &str, filter: &str) -> String {
+    let url = reqwest::Url::parse_with_params("http://localhost", &[("filter", filter)]).unwrap();
+
+    format!("{}?{}", path, url.query().unwrap())
+}
+
+fn assert_scim_error(status: StatusCode, body: &Value, expected: StatusCode) {
+    assert_eq!(status, expected, "{}", body);
+    assert_eq!(
+        body["schemas"],
+        json!(["urn:ietf:params:scim:api:messages:2.0:Error"])
+    );
+    assert_eq!(body["status"], json!(expected.as_u16().to_string()));
+}
+
+#[tokio::test]
+#[ignore = "needs the local database"]
+async fn scim_conformance() {
+    let scim = ScimServer::start().await;
+    let run = Uuid::new_v4().simple().to_string();
+
+    // Discovery.
+    let (status, config) = scim.send(Method::GET, "/ServiceProviderConfig", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(config["patch"]["supported"], json!(true));
+    assert_eq!(config["filter"]["supported"], json!(true));
+
+    let (status, resource_types) = scim.send(Method::GET, "/ResourceTypes", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(resource_types["totalResults"], json!(2));
+
+    // Provisioning users.
+    let jane_email = format!("jane.doe+{}@example.com", run);
+    let (status, jane) = scim
+        .send(
+            Method::POST,
+            "/Users",
+            Some(json!({
+                "schemas": [USER_SCHEMA],
+                "userName": jane_email,
+                "name": {"givenName": "Jane", "familyName": "Doe"},
+                "emails": [{"value": jane_email, "type": "work", "primary": true}],
+                "active": true,
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", jane);
+    assert_eq!(jane["schemas"], json!([USER_SCHEMA]));
+    assert_eq!(jane["userName"], json!(jane_email));
+    assert_eq!(jane["displayName"], json!("Jane Doe"));
+    assert_eq!(jane["active"], json!(true));
+    let jane_id = jane["id"].as_str().unwrap().to_string();
+    assert_eq!(
+        jane["meta"]["location"],
+        json!(format!("/scim/v2/Users/{}", jane_id))
+    );
+
+    let (status, body) = scim
+        .send(
+            Method::POST,
+            "/Users",
+            Some(json!({"schemas": [USER_SCHEMA], "userName": jane_email})),
+        )
+        .await;
+    assert_scim_error(status, &body, StatusCode::CONFLICT);
+    assert_eq!(body["scimType"], json!("uniqueness"));
+
+    let john_email = format!("john.smith+{}@example.com", run);
+    let (status, john) = scim
+        .send(
+            Method::POST,
+            "/Users",
+            Some(json!({
+                "schemas": [USER_SCHEMA],
+                "userName": john_email,
+                "displayName": "John Smith",
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", john);
+    let john_id = john["id"].as_str().unwrap().to_string();
+
+    let (status, body) = scim
+        .send(
+            Method::POST,
+            "/Users",
+            Some(json!({"schemas": [USER_SCHEMA]})),
+        )
+        .await;
+    assert_scim_error(status, &body, StatusCode::BAD_REQUEST);
+    assert_eq!(body["scimType"], json!("invalidValue"));
+
+    // Filtering users.
+    assert_eq!(
+        scim.list(
+            "Users",
+            &format!(r#"userName eq "{}""#, jane_email.to_uppercase())
+        )
+        .await,
+        (1, vec![jane_id.clone()])
+    );
+    assert_eq!(
+        scim.list(
+            "Users",
+            &format!(
+                r#"urn:ietf:params:scim:schemas:core:2.0:User:userName sw "jane.doe+{}""#,
+                run
+            )
+        )
+        .await,
+        (1, vec![jane_id.clone()])
+    );
+    assert_eq!(
+        scim.list("Users", &format!(r#"userName co "{}""#, run))
+            .await,
+        (2, vec![jane_id.clone(), john_id.clone()])
+    );
+    assert_eq!(
+        scim.list(
+            "Users",
+            &format!(r#"userName co "{}" and displayName co "smith""#, run)
+        )
+        .await,
+        (1, vec![john_id.clone()])
+    );
+    assert_eq!(
+        scim.list("Users", &format!(r#"id eq "{}""#, john_id)).await,
+        (1, vec![john_id.clone()])
+    );
+    assert_eq!(
+        scim.list(
+            "Users",
+            &format!(r#"userName eq "nobody+{}@example.com""#, run)
+        )
+        .await,
+        (0, vec![])
+    );
+
+    let (status, page) = scim
+        .send(
+            Method::GET,
+            &format!(
+                "{}&startIndex=2&count=1",
+                with_filter("/Users", &format!(r#"userName co "{}""#, run))
+            ),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["totalResults"], json!(2));
+    assert_eq!(page["startIndex"], json!(2));
+    assert_eq!(page["itemsPerPage"], json!(1));
+    assert_eq!(page["Resources"][0]["id"], json!(john_id));
+
+    for unsupported in [
+        r#"userName gt "a""#,
+        r#"userName eq "a" or userName eq "b""#,
+        r#"title eq "Analyst""#,
+    ] {
+        let (status, body) = scim
+            .send(Method::GET, &with_filter("/Users", unsupported), None)
+            .await;
+        assert_scim_error(status, &body, StatusCode::BAD_REQUEST);
+        assert_eq!(body["scimType"], json!("invalidFilter"));
+    }
+
+    // Deactivating a user signs them out straight away.
+    let jane_uuid = Uuid::parse_str(&jane_id).unwrap();
+    assert!(!is_user_deactivated(&jane_uuid, None).await.unwrap());
+
+    let (status, patched) = scim
+        .send(
+            Method::PATCH,
+            &format!("/Users/{}", jane_id),
+            Some(json!({
+                "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+                "Operations": [{"op": "Replace", "path": "active", "value": "False"}],
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", patched);
+    assert_eq!(patched["active"], json!(false));
+    assert!(is_user_deactivated(&jane_uuid, None).await.unwrap());
+
+    let (status, replaced) = scim
+        .send(
+            Method::PUT,
+            &format!("/Users/{}", jane_id),
+            Some(json!({
+                "schemas": [USER_SCHEMA],
+                "userName": jane_email,
+                "displayName": "Jane Doe-Smith",
+                "active": true,
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", replaced);
+    assert_eq!(replaced["displayName"], json!("Jane Doe-Smith"));
+    assert_eq!(replaced["active"], json!(true));
+    assert!(!is_user_deactivated(&jane_uuid, None).await.unwrap());
+
+    // Groups.
+    let group_name = format!("Sales {}", run);
+    let (status, group) = scim
+        .send(
+            Method::POST,
+            "/Groups",
+            Some(json!({
+                "schemas": [GROUP_SCHEMA],
+                "displayName": group_name,
+                "members": [{"value": jane_id}],
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", group);
+    assert_eq!(group["schemas"], json!([GROUP_SCHEMA]));
+    assert_eq!(group["members"][0]["value"], json!(jane_id));
+    let group_id = group["id"].as_str().unwrap().to_string();
+
+    let (status, body) = scim
+        .send(
+            Method::POST,
+            "/Groups",
+            Some(json!({"schemas": [GROUP_SCHEMA], "displayName": group_name})),
+        )
+        .await;
+    assert_scim_error(status, &body, StatusCode::CONFLICT);
+
+    assert_eq!(
+        scim.list("Groups", &format!(r#"displayName eq "{}""#, group_name))
+            .await,
+        (1, vec![group_id.clone()])
+    );
+    assert_eq!(
+        scim.list("Groups", &format!(r#"displayName sw "sales {}""#, run))
+            .await,
+        (1, vec![group_id.clone()])
+    );
+    assert_eq!(
+        scim.list(
+            "Groups",
+            &format!(r#"displayName co "{}" and id eq "{}""#, run, group_id)
+        )
+        .await,
+        (1, vec![group_id.clone()])
+    );
+
+    let (status, group) = scim
+        .send(
+            Method::PATCH,
+            &format!("/Groups/{}", group_id),
+            Some(json!({
+                "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+                "Operations": [
+                    {"op": "add", "path": "members", "value": [{"value": john_id}]},
+                    {"op": "remove", "path": format!(r#"members[value eq "{}"]"#, jane_id)},
+                ],
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", group);
+    assert_eq!(
+        group["members"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|member| member["value"].clone())
+            .collect::<Vec<Value>>(),
+        vec![json!(john_id)]
+    );
+
+    let (status, john) = scim
+        .send(Method::GET, &format!("/Users/{}", john_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(john["groups"][0]["value"], json!(group_id));
+
+    // Deprovisioning.
+    let (status, _) = scim
+        .send(Method::DELETE, &format!("/Users/{}", john_id), None)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, body) = scim
+        .send(Method::GET, &format!("/Users/{}", john_id), None)
+        .await;
+    assert_scim_error(status, &body, StatusCode::NOT_FOUND);
+    assert!(
+        is_user_deactivated(&Uuid::parse_str(&john_id).unwrap(), None)
+            .await
+            .unwrap()
+    );
+
+    let (status, group) = scim
+        .send(Method::GET, &format!("/Groups/{}", group_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(group["members"], json!([]));
+
+    let (status, _) = scim
+        .send(Method::DELETE, &format!("/Groups/{}", group_id), None)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, body) = scim
+        .send(Method::GET, &format!("/Groups/{}", group_id), None)
+        .await;
+    assert_scim_error(status, &body, StatusCode::NOT_FOUND);
+
+    let (status, body) = scim.send(Method::GET, "/Users/not-a-uuid", None).await;
+    assert_scim_error(status, &body, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,262 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+/// A comparison in a SCIM filter, e.g. `userName eq "jane@example.com"`, which is what identity
+/// providers use to look up a user or group before provisioning it. `eq`, `co` and `sw` are
+/// supported; the other operators are not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScimFilter {
+    /// Lowercased, since SCIM attribute names are case insensitive.
+    pub attribute: String,
+    pub operator: FilterOperator,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterOperator {
+    /// Equal.
+    Eq,
+    /// Contains.
+    Co,
+    /// Starts with.
+    Sw,
+}
+
+impl FilterOperator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilterOperator::Eq => "eq",
+            FilterOperator::Co => "co",
+            FilterOperator::Sw => "sw",
+        }
+    }
+}
+
+impl ScimFilter {
+    pub fn value_as_str(&self) -> Result<&str> {
+        match &self.value {
+            Value::String(value) => Ok(value),
+            _ => Err(anyhow!("Filter on {} needs a string value", self.attribute)),
+        }
+    }
+
+    /// An `ILIKE` pattern that matches the filter's value case insensitively.
+    pub fn like_pattern(&self) -> Result<String> {
+        let value = escape_like(self.value_as_str()?);
+
+        Ok(match self.operator {
+            FilterOperator::Eq => value,
+            FilterOperator::Co => format!("%{}%", value),
+            FilterOperator::Sw => format!("{}%", value),
+        })
+    }
+}
+
+/// Parses a filter of comparisons joined with `and`, e.g.
+/// `userName sw "j" and displayName co "Doe"`. `or`, `not` and grouping are not supported.
+pub fn parse_filters(filter: &str) -> Result<Vec<ScimFilter>> {
+    let mut comparisons = Vec::new();
+    let mut start = 0;
+
+    for (token_start, token_end) in tokenize(filter)? {
+        if filter[token_start..token_end].eq_ignore_ascii_case("and") {
+            comparisons.push(&filter[start..token_start]);
+            start = token_end;
+        }
+    }
+
+    comparisons.push(&filter[start..]);
+
+    comparisons.into_iter().map(parse_filter).collect()
+}
+
+/// Returns the byte ranges of the whitespace separated words of a filter, keeping quoted values,
+/// which may contain spaces or `and`, as single words.
+fn tokenize(filter: &str) -> Result<Vec<(usize, usize)>> {
+    let mut tokens = Vec::new();
+    let mut chars = filter.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let mut end = start + c.len_utf8();
+
+        if c == '"' {
+            let mut escaped = false;
+            let mut closed = false;
+
+            for (i, c) in chars.by_ref() {
+                end = i + c.len_utf8();
+
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => {
+                        closed = true;
+                        break;
+                    }
+                    _ => (),
+                }
+            }
+
+            if !closed {
+                return Err(anyhow!("Invalid filter: {}", filter.trim()));
+            }
+        } else {
+            while let Some(&(i, c)) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+
+                end = i + c.len_utf8();
+                chars.next();
+            }
+        }
+
+        tokens.push((start, end));
+    }
+
+    Ok(tokens)
+}
+
+pub fn parse_filter(filter: &str) -> Result<ScimFilter> {
+    let filter = filter.trim();
+
+    let (attribute, rest) = match filter.split_once(char::is_whitespace) {
+        Some(parts) => parts,
+        None => return Err(anyhow!("Invalid filter: {}", filter)),
+    };
+
+    let (operator, value) = match rest.trim_start().split_once(char::is_whitespace) {
+        Some(parts) => parts,
+        None => return Err(anyhow!("Invalid filter: {}", filter)),
+    };
+
+    let operator = match operator.to_ascii_lowercase().as_str() {
+        "eq" => FilterOperator::Eq,
+        "co" => FilterOperator::Co,
+        "sw" => FilterOperator::Sw,
+        _ => return Err(anyhow!("Unsupported filter operator: {}", operator)),
+    };
+
+    let value = match serde_json::from_str::<Value>(value.trim()) {
+        Ok(value @ (Value::String(_) | Value::Bool(_) | Value::Number(_) | Value::Null)) => value,
+        _ => return Err(anyhow!("Invalid filter value: {}", value.trim())),
+    };
+
+    Ok(ScimFilter {
+        attribute: normalize_attribute(attribute),
+        operator,
+        value,
+    })
+}
+
+/// Attribute names may be qualified with their schema, e.g.
+/// `urn:ietf:params:scim:schemas:core:2.0:User:userName`. The schema is dropped and the name
+/// lowercased.
+pub fn normalize_attribute(attribute: &str) -> String {
+    let attribute = if attribute.to_ascii_lowercase().starts_with("urn:") {
+        attribute.rsplit(':').next().unwrap_or(attribute)
+    } else {
+        attribute
+    };
+
+    attribute.to_ascii_lowercase()
+}
+
+/// Escapes a value for an exact, case insensitive match with `ILIKE`.
+pub fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_equality_filters() {
+        assert_eq!(
+            parse_filter(r#"userName eq "jane.doe@example.com""#).unwrap(),
+            ScimFilter {
+                attribute: "username".to_string(),
+                operator: FilterOperator::Eq,
+                value: json!("jane.doe@example.com"),
+            }
+        );
+        assert_eq!(
+            parse_filter(
+                r#"urn:ietf:params:scim:schemas:core:2.0:Group:displayName EQ "Sales \"EMEA\"""#
+            )
+            .unwrap(),
+            ScimFilter {
+                attribute: "displayname".to_string(),
+                operator: FilterOperator::Eq,
+                value: json!("Sales \"EMEA\""),
+            }
+        );
+        assert_eq!(parse_filter("active eq true").unwrap().value, json!(true));
+    }
+
+    #[test]
+    fn parses_combined_filters() {
+        let filters =
+            parse_filters(r#"userName SW "jane" and displayName co "Smith and Sons""#).unwrap();
+
+        assert_eq!(
+            filters,
+            vec![
+                ScimFilter {
+                    attribute: "username".to_string(),
+                    operator: FilterOperator::Sw,
+                    value: json!("jane"),
+                },
+                ScimFilter {
+                    attribute: "displayname".to_string(),
+                    operator: FilterOperator::Co,
+                    value: json!("Smith and Sons"),
+                },
+            ]
+        );
+        assert_eq!(
+            parse_filters(r#"displayName eq "a \" and b""#)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_filters() {
+        assert!(parse_filter(r#"userName gt "jane""#).is_err());
+        assert!(parse_filters(r#"userName eq "a" or userName eq "b""#).is_err());
+        assert!(parse_filters(r#"not (userName eq "a")"#).is_err());
+        assert!(parse_filters(r#"(userName eq "a") and active eq true"#).is_err());
+        assert!(parse_filters(r#"userName eq "a" and"#).is_err());
+        assert!(parse_filters(r#"userName eq "a"#).is_err());
+        assert!(parse_filter("userName eq").is_err());
+        assert!(parse_filter(r#"userName eq jane"#).is_err());
+        assert!(parse_filter(r#"emails[type eq "work"]"#).is_err());
+    }
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like("first_last%@x.com"), "first\\_last\\%@x.com");
+
+        let patterns = ["eq", "co", "sw"]
+            .iter()
+            .map(|operator| {
+                parse_filter(&format!(r#"userName {} "50%""#, operator))
+                    .unwrap()
+                    .like_pattern()
+                    .unwrap()
+            })
+            .collect::<Vec<String>>();
+        assert_eq!(patterns, vec!["50\\%", "%50\\%%", "50\\%%"]);
+    }
+}
@@ -0,0 +1,645 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Path, Query},
+    Extension, Json,
+};
+use chrono::Utc;
+use diesel::{
+    insert_into, update, ExpressionMethods, JoinOnDsl, OptionalExtension, PgTextExpressionMethods,
+    QueryDsl,
+};
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    buster_middleware::scim_auth::ScimContext,
+    database::{
+        enums::{SharingSetting, TeamToUserRole},
+        lib::get_pg_pool,
+        models::{Team, TeamToUser},
+        schema::{teams, teams_to_users, users, users_to_organizations},
+    },
+};
+
+use super::{
+    filter::{parse_filters, FilterOperator},
+    patch::{PatchOp, PatchOperation, PatchRequest},
+    resource_meta,
+    service_provider_config::GROUP_SCHEMA,
+    ListParams, ListResponse, ScimError, ScimResponse,
+};
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimGroupRequest {
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub members: Vec<Value>,
+}
+
+/// The parts of a SCIM group we store: the team's name and its members' user ids.
+#[derive(Debug, Clone, PartialEq)]
+struct GroupFields {
+    name: String,
+    members: Vec<Uuid>,
+}
+
+impl GroupFields {
+    fn from_request(request: ScimGroupRequest) -> Result<GroupFields> {
+        let name = match request.display_name {
+            Some(name) if !name.trim().is_empty() => name.trim().to_string(),
+            _ => return Err(anyhow!("displayName is required")),
+        };
+
+        Ok(GroupFields {
+            name,
+            members: member_ids(&Value::Array(request.members))?,
+        })
+    }
+
+    fn apply_patch(&mut self, operations: &[PatchOperation]) -> Result<()> {
+        for operation in operations {
+            let op = operation.op()?;
+
+            for (path, value) in operation.targets()? {
+                match (op, path.attribute.as_str()) {
+                    (PatchOp::Add | PatchOp::Replace, "displayname") => match value {
+                        Value::String(name) if !name.trim().is_empty() => {
+                            self.name = name.trim().to_string()
+                        }
+                        _ => return Err(anyhow!("displayName must be a non-empty string")),
+                    },
+                    (PatchOp::Remove, "members") => match path.value_filter {
+                        Some(filter) if filter.operator == FilterOperator::Eq => {
+                            let member_id = Uuid::parse_str(filter.value_as_str()?)?;
+                            self.members.retain(|id| *id != member_id);
+                        }
+                        Some(filter) => {
+                            return Err(anyhow!(
+                                "Removing members by {} is not supported",
+                                filter.operator.as_str()
+                            ))
+                        }
+                        None if value.is_null() => self.members.clear(),
+                        None => {
+                            let removed = member_ids(&value)?;
+                            self.members.retain(|id| !removed.contains(id));
+                        }
+                    },
+                    (PatchOp::Add, "members") => {
+                        for member_id in member_ids(&value)? {
+                            if !self.members.contains(&member_id) {
+                                self.members.push(member_id);
+                            }
+                        }
+                    }
+                    (PatchOp::Replace, "members") => self.members = member_ids(&value)?,
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Members are given as `{"value": "<user id>"}`, either on their own or in a list.
+fn member_ids(value: &Value) -> Result<Vec<Uuid>> {
+    let members = match value {
+        Value::Array(members) => members.iter().collect::<Vec<&Value>>(),
+        Value::Null => vec![],
+        member => vec![member],
+    };
+
+    let mut member_ids: Vec<Uuid> = Vec::new();
+
+    for member in members {
+        let member_id = match member.get("value").and_then(Value::as_str) {
+            Some(member_id) => Uuid::parse_str(member_id)?,
+            None => return Err(anyhow!("Members need a value")),
+        };
+
+        if !member_ids.contains(&member_id) {
+            member_ids.push(member_id);
+        }
+    }
+
+    Ok(member_ids)
+}
+
+pub async fn list_groups(
+    Extension(scim): Extension<ScimContext>,
+    Query(params): Query<ListParams>,
+) -> Result<ScimResponse<ListResponse<Value>>, ScimError> {
+    let mut conn = get_pg_pool()
+        .get()
+        .await
+        .map_err(|e| ScimError::internal("Error getting pg connection", anyhow!(e)))?;
+
+    let mut query = teams::table
+        .filter(teams::organization_id.eq(scim.organization_id))
+        .filter(teams::deleted_at.is_null())
+        .order((teams::created_at.asc(), teams::id.asc()))
+        .into_boxed();
+
+    if let Some(filter) = &params.filter {
+        let filters =
+            parse_filters(filter).map_err(|e| ScimError::bad_request("invalidFilter", e))?;
+
+        for filter in filters {
+            query = match (filter.attribute.as_str(), filter.operator) {
+                ("displayname", FilterOperator::Eq) => {
+                    let name = filter
+                        .value_as_str()
+                        .map_err(|e| ScimError::bad_request("invalidFilter", e))?;
+                    query.filter(teams::name.eq(name.to_string()))
+                }
+                ("displayname", _) => {
+                    let pattern = filter
+                        .like_pattern()
+                        .map_err(|e| ScimError::bad_request("invalidFilter", e))?;
+                    query.filter(teams::name.ilike(pattern))
+                }
+                ("id", FilterOperator::Eq) => match filter
+                    .value_as_str()
+                    .ok()
+                    .and_then(|id| Uuid::parse_str(id).ok())
+                {
+                    Some(id) => query.filter(teams::id.eq(id)),
+                    None => return Ok(ScimResponse::Ok(ListResponse::new(vec![], 0, 1))),
+                },
+                _ => {
+                    return Err(ScimError::bad_request(
+                        "invalidFilter",
+                        format!(
+                            "Filtering groups on {} with {} is not supported",
+                            filter.attribute,
+                            filter.operator.as_str()
+                        ),
+                    ))
+                }
+            };
+        }
+    }
+
+    let teams = query
+        .load::<Team>(&mut conn)
+        .await
+        .map_err(|e| ScimError::internal("Error listing groups", anyhow!(e)))?;
+
+    let (start_index, offset, limit) = params.page();
+
+    let page = teams
+        .iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .collect::<Vec<&Team>>();
+
+    let members = if params.excludes("members") {
+        None
+    } else {
+        let team_ids = page.iter().map(|team| team.id).collect::<Vec<Uuid>>();
+
+        Some(
+            load_members(&team_ids)
+                .await
+                .map_err(|e| ScimError::internal("Error listing group members", e))?,
+        )
+    };
+
+    let resources = page
+        .into_iter()
+        .map(|team| group_resource(team, members.as_deref()))
+        .collect();
+
+    Ok(ScimResponse::Ok(ListResponse::new(
+        resources,
+        teams.len() as i64,
+        start_index,
+    )))
+}
+
+pub async fn get_group(
+    Extension(scim): Extension<ScimContext>,
+    Path(id): Path<String>,
+) -> Result<ScimResponse<Value>, ScimError> {
+    let team = find_team(&scim.organization_id, &id).await?;
+
+    Ok(ScimResponse::Ok(load_group_resource(&team).await?))
+}
+
+pub async fn create_group(
+    Extension(scim): Extension<ScimContext>,
+    Json(request): Json<ScimGroupRequest>,
+) -> Result<ScimResponse<Value>, ScimError> {
+    let fields = GroupFields::from_request(request)
+        .map_err(|e| ScimError::bad_request("invalidValue", e))?;
+
+    check_name_available(&scim.organization_id, &fields.name, None).await?;
+    check_members(&scim.organization_id, &fields.members).await?;
+
+    let team = Team {
+        id: Uuid::new_v4(),
+        name: fields.name.clone(),
+        organization_id: scim.organization_id,
+        sharing_setting: SharingSetting::Team,
+        edit_sql: true,
+        upload_csv: true,
+        export_assets: true,
+        email_slack_enabled: true,
+        created_by: scim.actor_id,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        deleted_at: None,
+        attributes: json!({}),
+    };
+
+    let mut conn = get_pg_pool()
+        .get()
+        .await
+        .map_err(|e| ScimError::internal("Error getting pg connection", anyhow!(e)))?;
+
+    insert_into(teams::table)
+        .values(&team)
+        .execute(&mut conn)
+        .await
+        .map_err(|e| ScimError::internal("Error creating team", anyhow!(e)))?;
+
+    drop(conn);
+
+    save_members(&team.id, &fields.members).await?;
+
+    Ok(ScimResponse::Created(load_group_resource(&team).await?))
+}
+
+pub async fn replace_group(
+    Extension(scim): Extension<ScimContext>,
+    Path(id): Path<String>,
+    Json(request): Json<ScimGroupRequest>,
+) -> Result<ScimResponse<Value>, ScimError> {
+    let team = find_team(&scim.organization_id, &id).await?;
+    let fields = GroupFields::from_request(request)
+        .map_err(|e| ScimError::bad_request("invalidValue", e))?;
+
+    save_group(&scim, team, fields).await
+}
+
+pub async fn patch_group(
+    Extension(scim): Extension<ScimContext>,
+    Path(id): Path<String>,
+    Json(request): Json<PatchRequest>,
+) -> Result<ScimResponse<Value>, ScimError> {
+    let team = find_team(&scim.organization_id, &id).await?;
+
+    let members = load_members(&[team.id])
+        .await
+        .map_err(|e| ScimError::internal("Error getting group members", e))?;
+
+    let mut fields = GroupFields {
+        name: team.name.clone(),
+        members: members.into_iter().map(|(_, user_id, _)| user_id).collect(),
+    };
+
+    fields
+        .apply_patch(&request.operations)
+        .map_err(|e| ScimError::bad_request("invalidValue", e))?;
+
+    save_group(&scim, team, fields).await
+}
+
+/// Deletes the team and removes its members from it.
+pub async fn delete_group(
+    Extension(scim): Extension<ScimContext>,
+    Path(id): Path<String>,
+) -> Result<ScimResponse<()>, ScimError> {
+    let team = find_team(&scim.organization_id, &id).await?;
+
+    let mut conn = get_pg_pool()
+        .get()
+        .await
+        .map_err(|e| ScimError::internal("Error getting pg connection", anyhow!(e)))?;
+
+    update(teams::table)
+        .filter(teams::id.eq(team.id))
+        .set((
+            teams::deleted_at.eq(Some(Utc::now())),
+            teams::updated_at.eq(Utc::now()),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(|e| ScimError::internal("Error deleting team", anyhow!(e)))?;
+
+    update(teams_to_users::table)
+        .filter(teams_to_users::team_id.eq(team.id))
+        .filter(teams_to_users::deleted_at.is_null())
+        .set((
+            teams_to_users::deleted_at.eq(Some(Utc::now())),
+            teams_to_users::updated_at.eq(Utc::now()),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(|e| ScimError::internal("Error removing team members", anyhow!(e)))?;
+
+    Ok(ScimResponse::NoContent)
+}
+
+async fn save_group(
+    scim: &ScimContext,
+    team: Team,
+    fields: GroupFields,
+) -> Result<ScimResponse<Value>, ScimError> {
+    if fields.name != team.name {
+        check_name_available(&scim.organization_id, &fields.name, Some(&team.id)).await?;
+    }
+
+    check_members(&scim.organization_id, &fields.members).await?;
+
+    let mut conn = get_pg_pool()
+        .get()
+        .await
+        .map_err(|e| ScimError::internal("Error getting pg connection", anyhow!(e)))?;
+
+    update(teams::table)
+        .filter(teams::id.eq(team.id))
+        .set((
+            teams::name.eq(&fields.name),
+            teams::updated_at.eq(Utc::now()),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(|e| ScimError::internal("Error updating team", anyhow!(e)))?;
+
+    drop(conn);
+
+    save_members(&team.id, &fields.members).await?;
+
+    let team = Team {
+        name: fields.name,
+        updated_at: Utc::now(),
+        ..team
+    };
+
+    Ok(ScimResponse::Ok(load_group_resource(&team).await?))
+}
+
+/// Adds and removes team members so the team has exactly `members`. Members keep their role.
+async fn save_members(team_id: &Uuid, members: &[Uuid]) -> Result<(), ScimError> {
+    let current_members = load_members(&[*team_id])
+        .await
+        .map_err(|e| ScimError::internal("Error getting team members", e))?
+        .into_iter()
+        .map(|(_, user_id, _)| user_id)
+        .collect::<HashSet<Uuid>>();
+
+    let removed_members = current_members
+        .iter()
+        .filter(|user_id| !members.contains(user_id))
+        .copied()
+        .collect::<Vec<Uuid>>();
+
+    let new_members = members
+        .iter()
+        .filter(|user_id| !current_members.contains(*user_id))
+        .map(|user_id| TeamToUser {
+            team_id: *team_id,
+            user_id: *user_id,
+            role: TeamToUserRole::Member,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        })
+        .collect::<Vec<TeamToUser>>();
+
+    let mut conn = get_pg_pool()
+        .get()
+        .await
+        .map_err(|e| ScimError::internal("Error getting pg connection", anyhow!(e)))?;
+
+    if !removed_members.is_empty() {
+        update(teams_to_users::table)
+            .filter(teams_to_users::team_id.eq(team_id))
+            .filter(teams_to_users::user_id.eq_any(&removed_members))
+            .filter(teams_to_users::deleted_at.is_null())
+            .set((
+                teams_to_users::deleted_at.eq(Some(Utc::now())),
+                teams_to_users::updated_at.eq(Utc::now()),
+            ))
+            .execute(&mut conn)
+            .await
+            .map_err(|e| ScimError::internal("Error removing team members", anyhow!(e)))?;
+    }
+
+    if !new_members.is_empty() {
+        insert_into(teams_to_users::table)
+            .values(&new_members)
+            .on_conflict((teams_to_users::team_id, teams_to_users::user_id))
+            .do_update()
+            .set((
+                teams_to_users::deleted_at.eq(None::<chrono::DateTime<Utc>>),
+                teams_to_users::updated_at.eq(Utc::now()),
+            ))
+            .execute(&mut conn)
+            .await
+            .map_err(|e| ScimError::internal("Error adding team members", anyhow!(e)))?;
+    }
+
+    Ok(())
+}
+
+async fn find_team(organization_id: &Uuid, id: &str) -> Result<Team, ScimError> {
+    let team_id = Uuid::parse_str(id).map_err(|_| ScimError::not_found("Group not found"))?;
+
+    let mut conn = get_pg_pool()
+        .get()
+        .await
+        .map_err(|e| ScimError::internal("Error getting pg connection", anyhow!(e)))?;
+
+    match teams::table
+        .filter(teams::id.eq(team_id))
+        .filter(teams::organization_id.eq(organization_id))
+        .filter(teams::deleted_at.is_null())
+        .first::<Team>(&mut conn)
+        .await
+    {
+        Ok(team) => Ok(team),
+        Err(diesel::NotFound) => Err(ScimError::not_found("Group not found")),
+        Err(e) => Err(ScimError::internal("Error getting team", anyhow!(e))),
+    }
+}
+
+/// Identity providers match groups by name, so names are kept unique within an organization.
+async fn check_name_available(
+    organization_id: &Uuid,
+    name: &str,
+    team_id: Option<&Uuid>,
+) -> Result<(), ScimError> {
+    let mut conn = get_pg_pool()
+        .get()
+        .await
+        .map_err(|e| ScimError::internal("Error getting pg connection", anyhow!(e)))?;
+
+    let existing_team_id = teams::table
+        .filter(teams::organization_id.eq(organization_id))
+        .filter(teams::name.eq(name))
+        .filter(teams::deleted_at.is_null())
+        .select(teams::id)
+        .first::<Uuid>(&mut conn)
+        .await
+        .optional()
+        .map_err(|e| ScimError::internal("Error getting team", anyhow!(e)))?;
+
+    match existing_team_id {
+        Some(existing_team_id) if Some(&existing_team_id) != team_id => Err(ScimError::conflict(
+            "A group with this displayName already exists",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Only members of the organization can be added to its teams.
+async fn check_members(organization_id: &Uuid, members: &[Uuid]) -> Result<(), ScimError> {
+    if members.is_empty() {
+        return Ok(());
+    }
+
+    let mut conn = get_pg_pool()
+        .get()
+        .await
+        .map_err(|e| ScimError::internal("Error getting pg connection", anyhow!(e)))?;
+
+    let organization_members = users_to_organizations::table
+        .filter(users_to_organizations::organization_id.eq(organization_id))
+        .filter(users_to_organizations::user_id.eq_any(members))
+        .filter(users_to_organizations::deleted_at.is_null())
+        .select(users_to_organizations::user_id)
+        .load::<Uuid>(&mut conn)
+        .await
+        .map_err(|e| ScimError::internal("Error getting organization members", anyhow!(e)))?;
+
+    match members
+        .iter()
+        .find(|user_id| !organization_members.contains(user_id))
+    {
+        Some(user_id) => Err(ScimError::bad_request(
+            "invalidValue",
+            format!("Member {} is not a user in the organization", user_id),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The current members of the teams, as `(team_id, user_id, email)`.
+async fn load_members(team_ids: &[Uuid]) -> Result<Vec<(Uuid, Uuid, String)>> {
+    if team_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match teams_to_users::table
+        .inner_join(users::table.on(users::id.eq(teams_to_users::user_id)))
+        .filter(teams_to_users::team_id.eq_any(team_ids))
+        .filter(teams_to_users::deleted_at.is_null())
+        .order(users::email.asc())
+        .select((teams_to_users::team_id, users::id, users::email))
+        .load::<(Uuid, Uuid, String)>(&mut conn)
+        .await
+    {
+        Ok(members) => Ok(members),
+        Err(e) => Err(anyhow!("Error getting team members: {}", e)),
+    }
+}
+
+async fn load_group_resource(team: &Team) -> Result<Value, ScimError> {
+    let members = load_members(&[team.id])
+        .await
+        .map_err(|e| ScimError::internal("Error getting group members", e))?;
+
+    Ok(group_resource(team, Some(&members)))
+}
+
+fn group_resource(team: &Team, members: Option<&[(Uuid, Uuid, String)]>) -> Value {
+    let mut resource = json!({
+        "schemas": [GROUP_SCHEMA],
+        "id": team.id,
+        "displayName": team.name,
+        "meta": resource_meta(
+            "Group",
+            format!("/scim/v2/Groups/{}", team.id),
+            team.created_at,
+            team.updated_at,
+        ),
+    });
+
+    if let Some(members) = members {
+        resource["members"] = members
+            .iter()
+            .filter(|(team_id, _, _)| *team_id == team.id)
+            .map(|(_, user_id, email)| {
+                json!({
+                    "value": user_id,
+                    "display": email,
+                    "$ref": format!("/scim/v2/Users/{}", user_id),
+                })
+            })
+            .collect();
+    }
+
+    resource
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn member_patches_add_and_remove_users() {
+        let jane = Uuid::new_v4();
+        let john = Uuid::new_v4();
+        let ana = Uuid::new_v4();
+
+        let mut fields = GroupFields {
+            name: "Sales".to_string(),
+            members: vec![jane, john],
+        };
+
+        let request: PatchRequest = serde_json::from_value(json!({
+            "Operations": [
+                {
+                    "op": "add",
+                    "path": "members",
+                    "value": [{"value": ana.to_string()}, {"value": jane.to_string()}]
+                },
+                {"op": "remove", "path": format!("members[value eq \"{}\"]", john)},
+                {"op": "replace", "value": {"displayName": "Sales EMEA"}}
+            ]
+        }))
+        .unwrap();
+
+        fields.apply_patch(&request.operations).unwrap();
+
+        assert_eq!(
+            fields,
+            GroupFields {
+                name: "Sales EMEA".to_string(),
+                members: vec![jane, ana],
+            }
+        );
+
+        let request: PatchRequest = serde_json::from_value(json!({
+            "Operations": [{"op": "remove", "path": "members"}]
+        }))
+        .unwrap();
+
+        fields.apply_patch(&request.operations).unwrap();
+
+        assert!(fields.members.is_empty());
+    }
+}
@@ -0,0 +1,224 @@
+#[cfg(test)]
+mod conformance_tests;
+mod filter;
+mod groups;
+mod patch;
+mod service_provider_config;
+mod users;
+
+use axum::{
+    body::Body,
+    http::{header, Response, StatusCode},
+    middleware,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::buster_middleware::scim_auth::scim_auth;
+
+const SCIM_CONTENT_TYPE: &str = "application/scim+json";
+const LIST_RESPONSE_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+const ERROR_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+
+/// Results per page when the identity provider doesn't ask for a count.
+const DEFAULT_PAGE_SIZE: i64 = 100;
+const MAX_PAGE_SIZE: i64 = 1000;
+
+/// SCIM 2.0 provisioning of an organization's users and teams, mounted at `/scim/v2`. Users are
+/// members of the organization and groups are its teams. See `scim_auth` for how identity
+/// providers authenticate.
+pub fn router() -> Router {
+    routes().route_layer(middleware::from_fn(scim_auth))
+}
+
+/// The SCIM routes without authentication. Handlers expect a `ScimContext` extension.
+fn routes() -> Router {
+    Router::new()
+        .route("/Users", get(users::list_users).post(users::create_user))
+        .route(
+            "/Users/:id",
+            get(users::get_user)
+                .put(users::replace_user)
+                .patch(users::patch_user)
+                .delete(users::delete_user),
+        )
+        .route(
+            "/Groups",
+            get(groups::list_groups).post(groups::create_group),
+        )
+        .route(
+            "/Groups/:id",
+            get(groups::get_group)
+                .put(groups::replace_group)
+                .patch(groups::patch_group)
+                .delete(groups::delete_group),
+        )
+        .route(
+            "/ServiceProviderConfig",
+            get(service_provider_config::get_service_provider_config),
+        )
+        .route(
+            "/ResourceTypes",
+            get(service_provider_config::list_resource_types),
+        )
+}
+
+pub enum ScimResponse<T> {
+    Ok(T),
+    Created(T),
+    NoContent,
+}
+
+impl<T> IntoResponse for ScimResponse<T>
+where
+    T: Serialize,
+{
+    fn into_response(self) -> Response<Body> {
+        let content_type = [(header::CONTENT_TYPE, SCIM_CONTENT_TYPE)];
+
+        match self {
+            Self::Ok(data) => (StatusCode::OK, content_type, Json(data)).into_response(),
+            Self::Created(data) => (StatusCode::CREATED, content_type, Json(data)).into_response(),
+            Self::NoContent => (StatusCode::NO_CONTENT).into_response(),
+        }
+    }
+}
+
+/// Errors are returned in the SCIM error format, which identity providers show to admins.
+#[derive(Debug)]
+pub struct ScimError {
+    pub status: StatusCode,
+    pub scim_type: Option<&'static str>,
+    pub detail: String,
+}
+
+impl ScimError {
+    pub fn bad_request(scim_type: &'static str, detail: impl ToString) -> Self {
+        ScimError {
+            status: StatusCode::BAD_REQUEST,
+            scim_type: Some(scim_type),
+            detail: detail.to_string(),
+        }
+    }
+
+    pub fn not_found(detail: &str) -> Self {
+        ScimError {
+            status: StatusCode::NOT_FOUND,
+            scim_type: None,
+            detail: detail.to_string(),
+        }
+    }
+
+    pub fn conflict(detail: &str) -> Self {
+        ScimError {
+            status: StatusCode::CONFLICT,
+            scim_type: Some("uniqueness"),
+            detail: detail.to_string(),
+        }
+    }
+
+    /// Logs the underlying error and hides it from the identity provider.
+    pub fn internal(context: &str, e: anyhow::Error) -> Self {
+        tracing::error!("{}: {}", context, e);
+
+        ScimError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            scim_type: None,
+            detail: context.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ScimError {
+    fn into_response(self) -> Response<Body> {
+        let mut body = json!({
+            "schemas": [ERROR_SCHEMA],
+            "status": self.status.as_u16().to_string(),
+            "detail": self.detail,
+        });
+
+        if let Some(scim_type) = self.scim_type {
+            body["scimType"] = json!(scim_type);
+        }
+
+        (
+            self.status,
+            [(header::CONTENT_TYPE, SCIM_CONTENT_TYPE)],
+            Json(body),
+        )
+            .into_response()
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ListResponse<T> {
+    pub schemas: Vec<&'static str>,
+    pub total_results: i64,
+    pub start_index: i64,
+    pub items_per_page: i64,
+    #[serde(rename = "Resources")]
+    pub resources: Vec<T>,
+}
+
+impl<T> ListResponse<T> {
+    pub fn new(resources: Vec<T>, total_results: i64, start_index: i64) -> Self {
+        ListResponse {
+            schemas: vec![LIST_RESPONSE_SCHEMA],
+            total_results,
+            start_index,
+            items_per_page: resources.len() as i64,
+            resources,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ListParams {
+    pub filter: Option<String>,
+    pub start_index: Option<i64>,
+    pub count: Option<i64>,
+    pub excluded_attributes: Option<String>,
+}
+
+impl ListParams {
+    /// SCIM pages are 1-indexed. Returns the start index and the offset and limit to query with.
+    pub fn page(&self) -> (i64, i64, i64) {
+        let start_index = self.start_index.unwrap_or(1).max(1);
+        let count = self
+            .count
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(0, MAX_PAGE_SIZE);
+
+        (start_index, start_index - 1, count)
+    }
+
+    pub fn excludes(&self, attribute: &str) -> bool {
+        self.excluded_attributes
+            .as_deref()
+            .map(|excluded| {
+                excluded
+                    .split(',')
+                    .any(|excluded| excluded.trim().eq_ignore_ascii_case(attribute))
+            })
+            .unwrap_or(false)
+    }
+}
+
+pub fn resource_meta(
+    resource_type: &str,
+    location: String,
+    created: chrono::DateTime<chrono::Utc>,
+    last_modified: chrono::DateTime<chrono::Utc>,
+) -> Value {
+    json!({
+        "resourceType": resource_type,
+        "created": created,
+        "lastModified": last_modified,
+        "location": location,
+    })
+}
@@ -0,0 +1,183 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::filter::{normalize_attribute, parse_filter, ScimFilter};
+
+#[derive(Deserialize, Debug)]
+pub struct PatchRequest {
+    #[serde(rename = "Operations")]
+    pub operations: Vec<PatchOperation>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PatchOperation {
+    pub op: String,
+    pub path: Option<String>,
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchOp {
+    Add,
+    Remove,
+    Replace,
+}
+
+/// The attribute a patch operation applies to, e.g. `name.givenName` or
+/// `members[value eq "2819c223-7f76-453a-919d-413861904646"]`. Names are lowercased.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchPath {
+    pub attribute: String,
+    pub value_filter: Option<ScimFilter>,
+    pub sub_attribute: Option<String>,
+}
+
+impl PatchOperation {
+    /// Some identity providers capitalize the operation, e.g. `Replace`.
+    pub fn op(&self) -> Result<PatchOp> {
+        match self.op.to_ascii_lowercase().as_str() {
+            "add" => Ok(PatchOp::Add),
+            "remove" => Ok(PatchOp::Remove),
+            "replace" => Ok(PatchOp::Replace),
+            _ => Err(anyhow!("Unsupported patch operation: {}", self.op)),
+        }
+    }
+
+    /// The attributes the operation sets, with their values. An operation without a path sets
+    /// every attribute of its value, which then has to be an object. Removals may have no value.
+    pub fn targets(&self) -> Result<Vec<(PatchPath, Value)>> {
+        match (&self.path, &self.value) {
+            (Some(path), value) => Ok(vec![(
+                parse_path(path)?,
+                value.clone().unwrap_or(Value::Null),
+            )]),
+            (None, Some(Value::Object(values))) => values
+                .iter()
+                .map(|(path, value)| Ok((parse_path(path)?, value.clone())))
+                .collect(),
+            (None, _) => Err(anyhow!(
+                "Patch operation without a path needs an object value"
+            )),
+        }
+    }
+}
+
+pub fn parse_path(path: &str) -> Result<PatchPath> {
+    let path = path.trim();
+
+    let (attribute_path, value_filter, rest) = match path.find('[') {
+        Some(start) => {
+            let end = match path.rfind(']') {
+                Some(end) if end > start => end,
+                _ => return Err(anyhow!("Invalid patch path: {}", path)),
+            };
+
+            (
+                &path[..start],
+                Some(parse_filter(&path[start + 1..end])?),
+                &path[end + 1..],
+            )
+        }
+        None => (path, None, ""),
+    };
+
+    let attribute_path = normalize_attribute(attribute_path);
+
+    let (attribute, sub_attribute) = match (value_filter.is_some(), rest.strip_prefix('.')) {
+        (true, Some(sub_attribute)) => (attribute_path, Some(sub_attribute.to_ascii_lowercase())),
+        (true, None) if rest.is_empty() => (attribute_path, None),
+        (true, None) => return Err(anyhow!("Invalid patch path: {}", path)),
+        (false, _) => match attribute_path.split_once('.') {
+            Some((attribute, sub_attribute)) => {
+                (attribute.to_string(), Some(sub_attribute.to_string()))
+            }
+            None => (attribute_path, None),
+        },
+    };
+
+    if attribute.is_empty() {
+        return Err(anyhow!("Invalid patch path: {}", path));
+    }
+
+    Ok(PatchPath {
+        attribute,
+        value_filter,
+        sub_attribute,
+    })
+}
+
+/// Identity providers send booleans both as JSON booleans and as strings such as `"False"`.
+pub fn patch_bool(value: &Value) -> Result<bool> {
+    match value {
+        Value::Bool(value) => Ok(*value),
+        Value::String(value) if value.eq_ignore_ascii_case("true") => Ok(true),
+        Value::String(value) if value.eq_ignore_ascii_case("false") => Ok(false),
+        _ => Err(anyhow!("Expected a boolean, got {}", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::scim::filter::FilterOperator;
+    use serde_json::json;
+
+    #[test]
+    fn parses_paths() {
+        assert_eq!(
+            parse_path("name.givenName").unwrap(),
+            PatchPath {
+                attribute: "name".to_string(),
+                value_filter: None,
+                sub_attribute: Some("givenname".to_string()),
+            }
+        );
+        assert_eq!(
+            parse_path(r#"members[value eq "5f2e2b2c-4c1e-4b0a-9a59-0e7f1c3f9b1d"]"#).unwrap(),
+            PatchPath {
+                attribute: "members".to_string(),
+                value_filter: Some(ScimFilter {
+                    attribute: "value".to_string(),
+                    operator: FilterOperator::Eq,
+                    value: json!("5f2e2b2c-4c1e-4b0a-9a59-0e7f1c3f9b1d"),
+                }),
+                sub_attribute: None,
+            }
+        );
+        assert_eq!(
+            parse_path(r#"emails[type eq "work"].value"#)
+                .unwrap()
+                .sub_attribute,
+            Some("value".to_string())
+        );
+        assert_eq!(
+            parse_path("urn:ietf:params:scim:schemas:core:2.0:User:name.familyName").unwrap(),
+            PatchPath {
+                attribute: "name".to_string(),
+                value_filter: None,
+                sub_attribute: Some("familyname".to_string()),
+            }
+        );
+        assert!(parse_path(r#"members[value eq "1""#).is_err());
+    }
+
+    #[test]
+    fn operations_without_a_path_set_each_attribute() {
+        let request: PatchRequest = serde_json::from_value(json!({
+            "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+            "Operations": [
+                {"op": "Replace", "value": {"active": "False", "name.givenName": "Jane"}}
+            ]
+        }))
+        .unwrap();
+
+        let operation = &request.operations[0];
+        let targets = operation.targets().unwrap();
+
+        assert_eq!(operation.op().unwrap(), PatchOp::Replace);
+        assert_eq!(targets[0].0.attribute, "active");
+        assert!(!patch_bool(&targets[0].1).unwrap());
+        assert_eq!(targets[1].0.sub_attribute, Some("givenname".to_string()));
+    }
+}
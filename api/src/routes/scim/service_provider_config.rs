@@ -0,0 +1,56 @@
+use serde_json::{json, Value};
+
+use super::{ListResponse, ScimResponse, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+pub const USER_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+pub const GROUP_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+
+/// Tells identity providers which parts of SCIM we support: PATCH and `eq`, `co` and `sw` filters
+/// joined with `and`, but no bulk operations, sorting, ETags or password changes.
+pub async fn get_service_provider_config() -> ScimResponse<Value> {
+    ScimResponse::Ok(json!({
+        "schemas": ["urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig"],
+        "patch": {"supported": true},
+        "bulk": {"supported": false, "maxOperations": 0, "maxPayloadSize": 0},
+        "filter": {"supported": true, "maxResults": MAX_PAGE_SIZE},
+        "changePassword": {"supported": false},
+        "sort": {"supported": false},
+        "etag": {"supported": false},
+        "pagination": {"cursor": false, "index": true, "defaultPageSize": DEFAULT_PAGE_SIZE},
+        "authenticationSchemes": [{
+            "type": "oauthbearertoken",
+            "name": "API key",
+            "description": "A Buster API key with the scim scope, sent as a bearer token",
+            "primary": true,
+        }],
+        "meta": {
+            "resourceType": "ServiceProviderConfig",
+            "location": "/scim/v2/ServiceProviderConfig",
+        },
+    }))
+}
+
+pub async fn list_resource_types() -> ScimResponse<ListResponse<Value>> {
+    let resource_types = vec![
+        json!({
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:ResourceType"],
+            "id": "User",
+            "name": "User",
+            "endpoint": "/Users",
+            "schema": USER_SCHEMA,
+            "meta": {"resourceType": "ResourceType", "location": "/scim/v2/ResourceTypes/User"},
+        }),
+        json!({
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:ResourceType"],
+            "id": "Group",
+            "name": "Group",
+            "endpoint": "/Groups",
+            "schema": GROUP_SCHEMA,
+            "meta": {"resourceType": "ResourceType", "location": "/scim/v2/ResourceTypes/Group"},
+        }),
+    ];
+
+    let total_results = resource_types.len() as i64;
+
+    ScimResponse::Ok(ListResponse::new(resource_types, total_results, 1))
+}
@@ -0,0 +1,746 @@
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Path, Query},
+    Extension, Json,
+};
+use chrono::Utc;
+use diesel::{
+    insert_into, update, upsert::excluded, ExpressionMethods, JoinOnDsl, OptionalExtension,
+    PgTextExpressionMethods, QueryDsl,
+};
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    buster_middleware::scim_auth::ScimContext,
+    database::{
+        enums::{SharingSetting, UserOrganizationRole, UserOrganizationStatus},
+        lib::{get_pg_pool, UserConfig},
+        models::{User, UserToOrganization},
+        schema::{teams, teams_to_users, users, users_to_organizations},
+    },
+    utils::security::checks::forget_user_memberships,
+};
+
+use super::{
+    filter::{escape_like, parse_filters, FilterOperator},
+    patch::{patch_bool, PatchOp, PatchOperation, PatchRequest},
+    resource_meta,
+    service_provider_config::USER_SCHEMA,
+    ListParams, ListResponse, ScimError, ScimResponse,
+};
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimUserRequest {
+    pub user_name: Option<String>,
+    pub display_name: Option<String>,
+    pub name: Option<ScimName>,
+    #[serde(default)]
+    pub emails: Vec<ScimEmail>,
+    pub active: Option<Value>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimName {
+    pub formatted: Option<String>,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ScimEmail {
+    pub value: String,
+    pub primary: Option<bool>,
+}
+
+/// The parts of a SCIM user we store. `userName` and the primary email are both the user's email,
+/// and `active` is whether their membership of the organization is inactive.
+#[derive(Debug, Clone, PartialEq)]
+struct UserFields {
+    email: String,
+    name: Option<String>,
+    active: bool,
+}
+
+impl UserFields {
+    fn from_request(request: ScimUserRequest) -> Result<UserFields> {
+        let primary_email = request
+            .emails
+            .iter()
+            .find(|email| email.primary.unwrap_or(false))
+            .or_else(|| request.emails.as_slice().first())
+            .map(|email| email.value.clone());
+
+        let email = match request.user_name.or(primary_email) {
+            Some(email) if !email.trim().is_empty() => email.trim().to_string(),
+            _ => return Err(anyhow!("userName is required")),
+        };
+
+        let name = match request.name {
+            Some(name) => full_name(name.formatted, name.given_name, name.family_name),
+            None => None,
+        };
+
+        let active = match request.active {
+            Some(active) => patch_bool(&active)?,
+            None => true,
+        };
+
+        Ok(UserFields {
+            email,
+            name: request.display_name.or(name),
+            active,
+        })
+    }
+
+    /// Attributes we don't store, such as `title` or the enterprise extension, are ignored.
+    fn apply_patch(&mut self, operations: &[PatchOperation]) -> Result<()> {
+        let mut display_name = None;
+        let mut formatted = None;
+        let mut given_name = None;
+        let mut family_name = None;
+
+        for operation in operations {
+            let op = operation.op()?;
+
+            for (path, value) in operation.targets()? {
+                if op == PatchOp::Remove {
+                    if path.attribute == "displayname" || path.attribute == "name" {
+                        self.name = None;
+                    }
+                    continue;
+                }
+
+                match (path.attribute.as_str(), path.sub_attribute.as_deref()) {
+                    ("active", None) => self.active = patch_bool(&value)?,
+                    ("username", None) => self.email = patch_string(&value)?,
+                    ("emails", _) => {
+                        if let Some(email) = email_from_value(&value) {
+                            self.email = email;
+                        }
+                    }
+                    ("displayname", None) => display_name = Some(patch_string(&value)?),
+                    ("name", Some("formatted")) => formatted = Some(patch_string(&value)?),
+                    ("name", Some("givenname")) => given_name = Some(patch_string(&value)?),
+                    ("name", Some("familyname")) => family_name = Some(patch_string(&value)?),
+                    ("name", None) => {
+                        let name: ScimName = serde_json::from_value(value)?;
+                        formatted = name.formatted;
+                        given_name = name.given_name;
+                        family_name = name.family_name;
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        if let Some(name) = display_name.or(full_name(formatted, given_name, family_name)) {
+            self.name = Some(name);
+        }
+
+        Ok(())
+    }
+}
+
+fn full_name(
+    formatted: Option<String>,
+    given_name: Option<String>,
+    family_name: Option<String>,
+) -> Option<String> {
+    if formatted.is_some() {
+        return formatted;
+    }
+
+    let parts = [given_name, family_name]
+        .into_iter()
+        .flatten()
+        .filter(|part| !part.trim().is_empty())
+        .collect::<Vec<String>>();
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(" "))
+    }
+}
+
+fn patch_string(value: &Value) -> Result<String> {
+    match value {
+        Value::String(value) => Ok(value.trim().to_string()),
+        _ => Err(anyhow!("Expected a string, got {}", value)),
+    }
+}
+
+/// `emails` is patched as a list of emails, a single email or, with a `.value` path, the address.
+fn email_from_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(email) => Some(email.trim().to_string()),
+        Value::Object(email) => email.get("value").and_then(email_from_value),
+        Value::Array(emails) => emails
+            .iter()
+            .find(|email| email.get("primary").and_then(Value::as_bool) == Some(true))
+            .or_else(|| emails.as_slice().first())
+            .and_then(email_from_value),
+        _ => None,
+    }
+}
+
+/// Deactivating a user makes their membership inactive, and reactivating them makes it active
+/// again. Pending and guest members keep their status otherwise.
+fn next_status(status: UserOrganizationStatus, active: bool) -> UserOrganizationStatus {
+    match (status, active) {
+        (_, false) => UserOrganizationStatus::Inactive,
+        (UserOrganizationStatus::Inactive, true) => UserOrganizationStatus::Active,
+        (status, true) => status,
+    }
+}
+
+pub async fn list_users(
+    Extension(scim): Extension<ScimContext>,
+    Query(params): Query<ListParams>,
+) -> Result<ScimResponse<ListResponse<Value>>, ScimError> {
+    let mut conn = get_pg_pool()
+        .get()
+        .await
+        .map_err(|e| ScimError::internal("Error getting pg connection", anyhow!(e)))?;
+
+    let mut query = users::table
+        .inner_join(users_to_organizations::table.on(users_to_organizations::user_id.eq(users::id)))
+        .filter(users_to_organizations::organization_id.eq(scim.organization_id))
+        .filter(users_to_organizations::deleted_at.is_null())
+        .select(users::id)
+        .order((users::created_at.asc(), users::id.asc()))
+        .into_boxed();
+
+    if let Some(filter) = &params.filter {
+        let filters =
+            parse_filters(filter).map_err(|e| ScimError::bad_request("invalidFilter", e))?;
+
+        for filter in filters {
+            query = match (filter.attribute.as_str(), filter.operator) {
+                ("username" | "emails" | "emails.value", _) => {
+                    let pattern = filter
+                        .like_pattern()
+                        .map_err(|e| ScimError::bad_request("invalidFilter", e))?;
+                    query.filter(users::email.ilike(pattern))
+                }
+                ("id", FilterOperator::Eq) => match filter
+                    .value_as_str()
+                    .ok()
+                    .and_then(|id| Uuid::parse_str(id).ok())
+                {
+                    Some(id) => query.filter(users::id.eq(id)),
+                    None => return Ok(ScimResponse::Ok(ListResponse::new(vec![], 0, 1))),
+                },
+                ("displayname", FilterOperator::Eq) => {
+                    let name = filter
+                        .value_as_str()
+                        .map_err(|e| ScimError::bad_request("invalidFilter", e))?;
+                    query.filter(users::name.eq(name.to_string()))
+                }
+                ("displayname", _) => {
+                    let pattern = filter
+                        .like_pattern()
+                        .map_err(|e| ScimError::bad_request("invalidFilter", e))?;
+                    query.filter(users::name.ilike(pattern))
+                }
+                _ => {
+                    return Err(ScimError::bad_request(
+                        "invalidFilter",
+                        format!(
+                            "Filtering users on {} with {} is not supported",
+                            filter.attribute,
+                            filter.operator.as_str()
+                        ),
+                    ))
+                }
+            };
+        }
+    }
+
+    let user_ids = query
+        .load::<Uuid>(&mut conn)
+        .await
+        .map_err(|e| ScimError::internal("Error listing users", anyhow!(e)))?;
+
+    let (start_index, offset, limit) = params.page();
+
+    let page_ids = user_ids
+        .iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .copied()
+        .collect::<Vec<Uuid>>();
+
+    let resources = load_user_resources(&scim.organization_id, &page_ids)
+        .await
+        .map_err(|e| ScimError::internal("Error listing users", e))?;
+
+    Ok(ScimResponse::Ok(ListResponse::new(
+        resources,
+        user_ids.len() as i64,
+        start_index,
+    )))
+}
+
+pub async fn get_user(
+    Extension(scim): Extension<ScimContext>,
+    Path(id): Path<String>,
+) -> Result<ScimResponse<Value>, ScimError> {
+    let user_id = parse_user_id(&id)?;
+
+    Ok(ScimResponse::Ok(
+        load_user_resource(&scim.organization_id, &user_id).await?,
+    ))
+}
+
+pub async fn create_user(
+    Extension(scim): Extension<ScimContext>,
+    Json(request): Json<ScimUserRequest>,
+) -> Result<ScimResponse<Value>, ScimError> {
+    let fields =
+        UserFields::from_request(request).map_err(|e| ScimError::bad_request("invalidValue", e))?;
+
+    let user_id = create_user_handler(&scim, fields).await?;
+
+    Ok(ScimResponse::Created(
+        load_user_resource(&scim.organization_id, &user_id).await?,
+    ))
+}
+
+pub async fn replace_user(
+    Extension(scim): Extension<ScimContext>,
+    Path(id): Path<String>,
+    Json(request): Json<ScimUserRequest>,
+) -> Result<ScimResponse<Value>, ScimError> {
+    let user_id = parse_user_id(&id)?;
+    let fields =
+        UserFields::from_request(request).map_err(|e| ScimError::bad_request("invalidValue", e))?;
+
+    let (_, status) = find_member(&scim.organization_id, &user_id).await?;
+
+    save_user(&scim, &user_id, status, fields).await?;
+
+    Ok(ScimResponse::Ok(
+        load_user_resource(&scim.organization_id, &user_id).await?,
+    ))
+}
+
+pub async fn patch_user(
+    Extension(scim): Extension<ScimContext>,
+    Path(id): Path<String>,
+    Json(request): Json<PatchRequest>,
+) -> Result<ScimResponse<Value>, ScimError> {
+    let user_id = parse_user_id(&id)?;
+
+    let (user, status) = find_member(&scim.organization_id, &user_id).await?;
+
+    let mut fields = UserFields {
+        email: user.email,
+        name: user.name,
+        active: status != UserOrganizationStatus::Inactive,
+    };
+
+    fields
+        .apply_patch(&request.operations)
+        .map_err(|e| ScimError::bad_request("invalidValue", e))?;
+
+    save_user(&scim, &user_id, status, fields).await?;
+
+    Ok(ScimResponse::Ok(
+        load_user_resource(&scim.organization_id, &user_id).await?,
+    ))
+}
+
+/// Removes the user from the organization and its teams. The user itself is kept, since it may
+/// belong to other organizations and owns the assets it created.
+pub async fn delete_user(
+    Extension(scim): Extension<ScimContext>,
+    Path(id): Path<String>,
+) -> Result<ScimResponse<()>, ScimError> {
+    let user_id = parse_user_id(&id)?;
+
+    find_member(&scim.organization_id, &user_id).await?;
+
+    let mut conn = get_pg_pool()
+        .get()
+        .await
+        .map_err(|e| ScimError::internal("Error getting pg connection", anyhow!(e)))?;
+
+    update(users_to_organizations::table)
+        .filter(users_to_organizations::user_id.eq(user_id))
+        .filter(users_to_organizations::organization_id.eq(scim.organization_id))
+        .filter(users_to_organizations::deleted_at.is_null())
+        .set((
+            users_to_organizations::deleted_at.eq(Some(Utc::now())),
+            users_to_organizations::deleted_by.eq(Some(scim.actor_id)),
+            users_to_organizations::updated_at.eq(Utc::now()),
+            users_to_organizations::updated_by.eq(scim.actor_id),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(|e| ScimError::internal("Error removing user", anyhow!(e)))?;
+
+    forget_user_memberships(&user_id);
+
+    update(teams_to_users::table)
+        .filter(teams_to_users::user_id.eq(user_id))
+        .filter(
+            teams_to_users::team_id.eq_any(
+                teams::table
+                    .filter(teams::organization_id.eq(scim.organization_id))
+                    .select(teams::id),
+            ),
+        )
+        .filter(teams_to_users::deleted_at.is_null())
+        .set((
+            teams_to_users::deleted_at.eq(Some(Utc::now())),
+            teams_to_users::updated_at.eq(Utc::now()),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(|e| ScimError::internal("Error removing user from teams", anyhow!(e)))?;
+
+    Ok(ScimResponse::NoContent)
+}
+
+fn parse_user_id(id: &str) -> Result<Uuid, ScimError> {
+    Uuid::parse_str(id).map_err(|_| ScimError::not_found("User not found"))
+}
+
+/// Users that already exist, e.g. because they belong to another organization, are added to this
+/// one. Users that were removed from it are added back.
+async fn create_user_handler(scim: &ScimContext, fields: UserFields) -> Result<Uuid, ScimError> {
+    let mut conn = get_pg_pool()
+        .get()
+        .await
+        .map_err(|e| ScimError::internal("Error getting pg connection", anyhow!(e)))?;
+
+    let existing_user = users::table
+        .filter(users::email.ilike(escape_like(&fields.email)))
+        .first::<User>(&mut conn)
+        .await
+        .optional()
+        .map_err(|e| ScimError::internal("Error getting user", anyhow!(e)))?;
+
+    let user_id = match existing_user {
+        Some(user) => {
+            let is_member = users_to_organizations::table
+                .filter(users_to_organizations::user_id.eq(user.id))
+                .filter(users_to_organizations::organization_id.eq(scim.organization_id))
+                .filter(users_to_organizations::deleted_at.is_null())
+                .select(users_to_organizations::user_id)
+                .first::<Uuid>(&mut conn)
+                .await
+                .optional()
+                .map_err(|e| ScimError::internal("Error getting user membership", anyhow!(e)))?
+                .is_some();
+
+            if is_member {
+                return Err(ScimError::conflict("User already exists"));
+            }
+
+            if fields.name.is_some() && user.name.is_none() {
+                update(users::table)
+                    .filter(users::id.eq(user.id))
+                    .set((
+                        users::name.eq(&fields.name),
+                        users::updated_at.eq(Utc::now()),
+                    ))
+                    .execute(&mut conn)
+                    .await
+                    .map_err(|e| ScimError::internal("Error updating user", anyhow!(e)))?;
+            }
+
+            user.id
+        }
+        None => {
+            let user_config = UserConfig {
+                color_palettes: None,
+                last_used_color_palette: None,
+            };
+
+            let user = User {
+                id: Uuid::new_v4(),
+                email: fields.email.clone(),
+                name: fields.name.clone(),
+                config: json!(user_config),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                attributes: json!({}),
+            };
+
+            insert_into(users::table)
+                .values(&user)
+                .execute(&mut conn)
+                .await
+                .map_err(|e| ScimError::internal("Error creating user", anyhow!(e)))?;
+
+            user.id
+        }
+    };
+
+    let user_to_organization = UserToOrganization {
+        user_id,
+        organization_id: scim.organization_id,
+        role: UserOrganizationRole::Querier,
+        sharing_setting: SharingSetting::Public,
+        edit_sql: true,
+        upload_csv: true,
+        export_assets: true,
+        email_slack_enabled: true,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        deleted_at: None,
+        created_by: scim.actor_id,
+        updated_by: scim.actor_id,
+        deleted_by: None,
+        status: next_status(UserOrganizationStatus::Active, fields.active),
+    };
+
+    insert_into(users_to_organizations::table)
+        .values(&user_to_organization)
+        .on_conflict((
+            users_to_organizations::user_id,
+            users_to_organizations::organization_id,
+        ))
+        .do_update()
+        .set((
+            users_to_organizations::status.eq(excluded(users_to_organizations::status)),
+            users_to_organizations::deleted_at.eq(excluded(users_to_organizations::deleted_at)),
+            users_to_organizations::deleted_by.eq(excluded(users_to_organizations::deleted_by)),
+            users_to_organizations::updated_at.eq(excluded(users_to_organizations::updated_at)),
+            users_to_organizations::updated_by.eq(excluded(users_to_organizations::updated_by)),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(|e| ScimError::internal("Error adding user to organization", anyhow!(e)))?;
+
+    forget_user_memberships(&user_id);
+
+    Ok(user_id)
+}
+
+async fn save_user(
+    scim: &ScimContext,
+    user_id: &Uuid,
+    status: UserOrganizationStatus,
+    fields: UserFields,
+) -> Result<(), ScimError> {
+    let mut conn = get_pg_pool()
+        .get()
+        .await
+        .map_err(|e| ScimError::internal("Error getting pg connection", anyhow!(e)))?;
+
+    let email_taken = users::table
+        .filter(users::email.ilike(escape_like(&fields.email)))
+        .filter(users::id.ne(user_id))
+        .select(users::id)
+        .first::<Uuid>(&mut conn)
+        .await
+        .optional()
+        .map_err(|e| ScimError::internal("Error getting user", anyhow!(e)))?
+        .is_some();
+
+    if email_taken {
+        return Err(ScimError::conflict("Another user has this userName"));
+    }
+
+    update(users::table)
+        .filter(users::id.eq(user_id))
+        .set((
+            users::email.eq(&fields.email),
+            users::name.eq(&fields.name),
+            users::updated_at.eq(Utc::now()),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(|e| ScimError::internal("Error updating user", anyhow!(e)))?;
+
+    let next_status = next_status(status, fields.active);
+
+    if next_status != status {
+        update(users_to_organizations::table)
+            .filter(users_to_organizations::user_id.eq(user_id))
+            .filter(users_to_organizations::organization_id.eq(scim.organization_id))
+            .filter(users_to_organizations::deleted_at.is_null())
+            .set((
+                users_to_organizations::status.eq(next_status),
+                users_to_organizations::updated_at.eq(Utc::now()),
+                users_to_organizations::updated_by.eq(scim.actor_id),
+            ))
+            .execute(&mut conn)
+            .await
+            .map_err(|e| ScimError::internal("Error updating user status", anyhow!(e)))?;
+
+        forget_user_memberships(user_id);
+    }
+
+    Ok(())
+}
+
+async fn find_member(
+    organization_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<(User, UserOrganizationStatus), ScimError> {
+    let mut conn = get_pg_pool()
+        .get()
+        .await
+        .map_err(|e| ScimError::internal("Error getting pg connection", anyhow!(e)))?;
+
+    match users::table
+        .inner_join(users_to_organizations::table.on(users_to_organizations::user_id.eq(users::id)))
+        .filter(users::id.eq(user_id))
+        .filter(users_to_organizations::organization_id.eq(organization_id))
+        .filter(users_to_organizations::deleted_at.is_null())
+        .select((users::all_columns, users_to_organizations::status))
+        .first::<(User, UserOrganizationStatus)>(&mut conn)
+        .await
+    {
+        Ok(member) => Ok(member),
+        Err(diesel::NotFound) => Err(ScimError::not_found("User not found")),
+        Err(e) => Err(ScimError::internal("Error getting user", anyhow!(e))),
+    }
+}
+
+async fn load_user_resource(organization_id: &Uuid, user_id: &Uuid) -> Result<Value, ScimError> {
+    match load_user_resources(organization_id, &[*user_id]).await {
+        Ok(resources) => match resources.into_iter().next() {
+            Some(resource) => Ok(resource),
+            None => Err(ScimError::not_found("User not found")),
+        },
+        Err(e) => Err(ScimError::internal("Error getting user", e)),
+    }
+}
+
+/// Returns the users in the order of `user_ids`, with the organization's teams they are on.
+async fn load_user_resources(organization_id: &Uuid, user_ids: &[Uuid]) -> Result<Vec<Value>> {
+    if user_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let members = match users::table
+        .inner_join(users_to_organizations::table.on(users_to_organizations::user_id.eq(users::id)))
+        .filter(users::id.eq_any(user_ids))
+        .filter(users_to_organizations::organization_id.eq(organization_id))
+        .filter(users_to_organizations::deleted_at.is_null())
+        .select((users::all_columns, users_to_organizations::status))
+        .load::<(User, UserOrganizationStatus)>(&mut conn)
+        .await
+    {
+        Ok(members) => members,
+        Err(e) => return Err(anyhow!("Error getting users: {}", e)),
+    };
+
+    let team_memberships = match teams_to_users::table
+        .inner_join(teams::table.on(teams::id.eq(teams_to_users::team_id)))
+        .filter(teams_to_users::user_id.eq_any(user_ids))
+        .filter(teams::organization_id.eq(organization_id))
+        .filter(teams_to_users::deleted_at.is_null())
+        .filter(teams::deleted_at.is_null())
+        .order(teams::name.asc())
+        .select((teams_to_users::user_id, teams::id, teams::name))
+        .load::<(Uuid, Uuid, String)>(&mut conn)
+        .await
+    {
+        Ok(team_memberships) => team_memberships,
+        Err(e) => return Err(anyhow!("Error getting user teams: {}", e)),
+    };
+
+    let resources = user_ids
+        .iter()
+        .filter_map(|user_id| members.iter().find(|(user, _)| user.id == *user_id))
+        .map(|(user, status)| {
+            let groups = team_memberships
+                .iter()
+                .filter(|(member_id, _, _)| *member_id == user.id)
+                .map(|(_, team_id, team_name)| {
+                    json!({
+                        "value": team_id,
+                        "display": team_name,
+                        "$ref": format!("/scim/v2/Groups/{}", team_id),
+                    })
+                })
+                .collect::<Vec<Value>>();
+
+            user_resource(user, status, groups)
+        })
+        .collect();
+
+    Ok(resources)
+}
+
+fn user_resource(user: &User, status: &UserOrganizationStatus, groups: Vec<Value>) -> Value {
+    json!({
+        "schemas": [USER_SCHEMA],
+        "id": user.id,
+        "userName": user.email,
+        "displayName": user.name,
+        "name": {"formatted": user.name},
+        "emails": [{"value": user.email, "type": "work", "primary": true}],
+        "active": *status != UserOrganizationStatus::Inactive,
+        "groups": groups,
+        "meta": resource_meta(
+            "User",
+            format!("/scim/v2/Users/{}", user.id),
+            user.created_at,
+            user.updated_at,
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patches_apply_to_stored_fields() {
+        let mut fields = UserFields {
+            email: "jane@example.com".to_string(),
+            name: Some("Jane".to_string()),
+            active: true,
+        };
+
+        let request: PatchRequest = serde_json::from_value(json!({
+            "Operations": [
+                {"op": "replace", "path": "name.givenName", "value": "Jane"},
+                {"op": "replace", "path": "name.familyName", "value": "Doe"},
+                {
+                    "op": "replace",
+                    "path": "emails[type eq \"work\"].value",
+                    "value": "jane.doe@example.com"
+                },
+                {"op": "Replace", "value": {"active": "False", "title": "Analyst"}}
+            ]
+        }))
+        .unwrap();
+
+        fields.apply_patch(&request.operations).unwrap();
+
+        assert_eq!(
+            fields,
+            UserFields {
+                email: "jane.doe@example.com".to_string(),
+                name: Some("Jane Doe".to_string()),
+                active: false,
+            }
+        );
+        assert_eq!(
+            next_status(UserOrganizationStatus::Pending, fields.active),
+            UserOrganizationStatus::Inactive
+        );
+        assert_eq!(
+            next_status(UserOrganizationStatus::Inactive, true),
+            UserOrganizationStatus::Active
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use axum::{
+    extract::{Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect},
+    Form,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::models::IdentityProvider,
+    utils::{
+        clients::supabase_vault::read_secret,
+        security::sso::{
+            clear_sso_browser_cookie, issue_session_token, oidc,
+            provisioning::{provision_sso_user, SsoSignIn},
+            read_sso_browser_cookie, saml, session_redirect_url, IdentityProviderConfig,
+            SsoIdentity,
+        },
+    },
+};
+
+use super::get_enabled_provider;
+
+#[derive(Deserialize, Debug)]
+pub struct OidcCallbackParams {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SamlAcsForm {
+    #[serde(rename = "SAMLResponse")]
+    pub saml_response: String,
+    #[serde(rename = "RelayState")]
+    pub relay_state: String,
+}
+
+pub async fn oidc_callback(
+    Path(provider_id): Path<Uuid>,
+    headers: HeaderMap,
+    Query(params): Query<OidcCallbackParams>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let (provider, config) = get_enabled_provider(&provider_id).await?;
+
+    let config = match config {
+        IdentityProviderConfig::Oidc(config) => config,
+        _ => return Err((StatusCode::NOT_FOUND, "Identity provider not found")),
+    };
+
+    let (code, state) = match (params.code, params.state, params.error) {
+        (Some(code), Some(state), None) => (code, state),
+        (_, _, Some(error)) => {
+            tracing::warn!(
+                "Identity provider {} refused sign in: {}",
+                provider.id,
+                error
+            );
+            return Err((StatusCode::UNAUTHORIZED, "Sign in was refused"));
+        }
+        _ => return Err((StatusCode::BAD_REQUEST, "Missing code or state")),
+    };
+
+    let browser_secret = browser_secret(&headers)?;
+
+    let client_secret = match provider.client_secret_id {
+        Some(client_secret_id) => match read_secret(&client_secret_id).await {
+            Ok(client_secret) => client_secret,
+            Err(e) => {
+                tracing::error!("Error reading client secret: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Error signing in"));
+            }
+        },
+        None => {
+            tracing::error!("Identity provider {} has no client secret", provider.id);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Error signing in"));
+        }
+    };
+
+    let identity = match oidc::complete_login(
+        &provider.id,
+        &config,
+        &client_secret,
+        &code,
+        &state,
+        &browser_secret,
+    )
+    .await
+    {
+        Ok(identity) => identity,
+        Err(e) => {
+            tracing::warn!("Sign in with {} failed: {}", provider.id, e);
+            return Err((StatusCode::UNAUTHORIZED, "Sign in failed"));
+        }
+    };
+
+    finish_sign_in(&provider, &identity).await
+}
+
+pub async fn saml_acs(
+    Path(provider_id): Path<Uuid>,
+    headers: HeaderMap,
+    Form(form): Form<SamlAcsForm>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let (provider, config) = get_enabled_provider(&provider_id).await?;
+
+    let config = match config {
+        IdentityProviderConfig::Saml(config) => config,
+        _ => return Err((StatusCode::NOT_FOUND, "Identity provider not found")),
+    };
+
+    let browser_secret = browser_secret(&headers)?;
+
+    let identity = match saml::complete_login(
+        &provider.id,
+        &config,
+        &form.saml_response,
+        &form.relay_state,
+        &browser_secret,
+    )
+    .await
+    {
+        Ok(identity) => identity,
+        Err(e) => {
+            tracing::warn!("Sign in with {} failed: {}", provider.id, e);
+            return Err((StatusCode::UNAUTHORIZED, "Sign in failed"));
+        }
+    };
+
+    finish_sign_in(&provider, &identity).await
+}
+
+/// Sign ins can only be finished in the browser that started them, see `login`.
+fn browser_secret(headers: &HeaderMap) -> Result<String, (StatusCode, &'static str)> {
+    match read_sso_browser_cookie(headers) {
+        Some(browser_secret) => Ok(browser_secret),
+        None => Err((
+            StatusCode::BAD_REQUEST,
+            "Sign in was not started in this browser",
+        )),
+    }
+}
+
+async fn finish_sign_in(
+    provider: &IdentityProvider,
+    identity: &SsoIdentity,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let user_id = match provision_sso_user(provider, identity).await {
+        Ok(SsoSignIn::SignedIn(user_id)) => user_id,
+        Ok(SsoSignIn::Deactivated) => {
+            return Err((StatusCode::FORBIDDEN, "User is deactivated"));
+        }
+        Ok(SsoSignIn::DomainNotAllowed) => {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Email is not at the organization's domain",
+            ));
+        }
+        Ok(SsoSignIn::OtherOrganization) => {
+            return Err((StatusCode::CONFLICT, "User belongs to another organization"));
+        }
+        Err(e) => {
+            tracing::error!("Error provisioning SSO user: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Error signing in"));
+        }
+    };
+
+    match issue_session_token(&user_id, &provider.organization_id) {
+        Ok(token) => Ok((
+            [(header::SET_COOKIE, clear_sso_browser_cookie(&provider.id))],
+            Redirect::to(&session_redirect_url(&token)),
+        )),
+        Err(e) => {
+            tracing::error!("Error issuing session: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Error signing in"))
+        }
+    }
+}
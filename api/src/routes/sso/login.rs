@@ -0,0 +1,50 @@
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect},
+};
+use uuid::Uuid;
+
+use crate::utils::security::sso::{
+    oidc, saml, sso_browser_cookie, sso_nonce, IdentityProviderConfig,
+};
+
+use super::get_enabled_provider;
+
+/// Sends the user to the identity provider to sign in. The browser gets a secret in a cookie
+/// that the callback checks against the state, so only this browser can finish the sign in.
+pub async fn login(
+    Path(provider_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let (provider, config) = get_enabled_provider(&provider_id).await?;
+
+    let browser_secret = sso_nonce();
+
+    let (sign_in_url, cross_site) = match config {
+        IdentityProviderConfig::Oidc(config) => (
+            oidc::authorization_url(&provider.id, &config, &browser_secret).await,
+            false,
+        ),
+        IdentityProviderConfig::Saml(config) => (
+            saml::authn_request_url(&provider.id, &config, &browser_secret),
+            true,
+        ),
+    };
+
+    match sign_in_url {
+        Ok(sign_in_url) => Ok((
+            [(
+                header::SET_COOKIE,
+                sso_browser_cookie(&provider.id, &browser_secret, cross_site),
+            )],
+            Redirect::to(&sign_in_url),
+        )),
+        Err(e) => {
+            tracing::error!("Error starting sign in with {}: {}", provider.id, e);
+            Err((
+                StatusCode::BAD_GATEWAY,
+                "Error reaching the identity provider",
+            ))
+        }
+    }
+}
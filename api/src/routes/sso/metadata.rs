@@ -0,0 +1,25 @@
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use uuid::Uuid;
+
+use crate::utils::security::sso::{saml, IdentityProviderConfig};
+
+use super::get_enabled_provider;
+
+/// The service provider metadata admins upload to their SAML identity provider.
+pub async fn saml_metadata(
+    Path(provider_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let (provider, config) = get_enabled_provider(&provider_id).await?;
+
+    match config {
+        IdentityProviderConfig::Saml(_) => Ok((
+            [(header::CONTENT_TYPE, "application/samlmetadata+xml")],
+            saml::sp_metadata_xml(&provider.id),
+        )),
+        _ => Err((StatusCode::NOT_FOUND, "Identity provider not found")),
+    }
+}
@@ -0,0 +1,69 @@
+mod callback;
+mod login;
+mod metadata;
+
+use axum::{
+    http::StatusCode,
+    routing::{get, post},
+    Router,
+};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::{
+    database::{lib::get_pg_pool, models::IdentityProvider, schema::identity_providers},
+    utils::security::sso::IdentityProviderConfig,
+};
+
+/// Sign in with an organization's own identity provider, mounted at `/auth/sso`. Users start at
+/// `/:provider_id/login`, sign in at the provider and are sent back to the web app with a
+/// session, see `issue_session_token`.
+pub fn router() -> Router {
+    Router::new()
+        .route("/:provider_id/login", get(login::login))
+        .route("/:provider_id/callback", get(callback::oidc_callback))
+        .route("/:provider_id/acs", post(callback::saml_acs))
+        .route("/:provider_id/metadata", get(metadata::saml_metadata))
+}
+
+async fn get_enabled_provider(
+    provider_id: &Uuid,
+) -> Result<(IdentityProvider, IdentityProviderConfig), (StatusCode, &'static str)> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            tracing::error!("Error getting pg connection: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Error signing in"));
+        }
+    };
+
+    let provider = match identity_providers::table
+        .filter(identity_providers::id.eq(provider_id))
+        .filter(identity_providers::enabled.eq(true))
+        .filter(identity_providers::deleted_at.is_null())
+        .first::<IdentityProvider>(&mut conn)
+        .await
+    {
+        Ok(provider) => provider,
+        Err(diesel::NotFound) => {
+            return Err((StatusCode::NOT_FOUND, "Identity provider not found"))
+        }
+        Err(e) => {
+            tracing::error!("Error getting identity provider: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Error signing in"));
+        }
+    };
+
+    match serde_json::from_value::<IdentityProviderConfig>(provider.config.clone()) {
+        Ok(config) => Ok((provider, config)),
+        Err(e) => {
+            tracing::error!(
+                "Invalid config for identity provider {}: {}",
+                provider.id,
+                e
+            );
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Error signing in"))
+        }
+    }
+}
@@ -21,6 +21,7 @@ use crate::{
         ws_utils::{send_error_message, send_ws_message, subscribe_to_stream},
     },
     utils::{
+        audit::audit_log::AuditRequestMetadata,
         clients::sentry_utils::send_sentry_error,
         sharing::asset_sharing::{
             update_asset_permissions, ShareWithTeamsReqObject, ShareWithUsersReqObject,
@@ -163,6 +164,7 @@ pub async fn update_collection(
                 req.user_permissions,
                 req.remove_teams,
                 req.remove_users,
+                AuditRequestMetadata::ws(WsRoutes::Collections(CollectionRoute::Update)),
             )
             .await
             {
@@ -0,0 +1,507 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use diesel::{insert_into, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::DashboardVersion,
+    schema::{dashboard_versions, dashboards, threads_to_dashboards},
+};
+
+/// The parts of a dashboard that are versioned: its layout config and the metrics on it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DashboardSnapshot {
+    pub config: Value,
+    pub thread_ids: Vec<Uuid>,
+}
+
+impl DashboardSnapshot {
+    pub fn from_version(version: &DashboardVersion) -> Self {
+        DashboardSnapshot {
+            config: version.config.clone(),
+            thread_ids: serde_json::from_value(version.thread_ids.clone()).unwrap_or_default(),
+        }
+    }
+
+    fn empty() -> Self {
+        DashboardSnapshot {
+            config: Value::Null,
+            thread_ids: Vec::new(),
+        }
+    }
+
+    /// Every metric on the dashboard, whether it is only attached or also placed in the layout.
+    fn metrics(&self) -> HashSet<Uuid> {
+        let mut metrics: HashSet<Uuid> = self.thread_ids.iter().cloned().collect();
+
+        for row in layout_rows(&self.config) {
+            metrics.extend(row.items.iter().cloned());
+        }
+
+        metrics
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct DashboardVersionDiff {
+    pub metrics_added: Vec<Uuid>,
+    pub metrics_removed: Vec<Uuid>,
+    pub metrics_moved: Vec<Uuid>,
+    pub rows_added: Vec<String>,
+    pub rows_removed: Vec<String>,
+    pub rows_resized: Vec<String>,
+    pub rows_reordered: bool,
+}
+
+impl DashboardVersionDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == DashboardVersionDiff::default()
+    }
+
+    pub fn summary(&self) -> String {
+        let mut changes = Vec::new();
+
+        if !self.metrics_added.is_empty() {
+            changes.push(format!("added {}", count_metrics(self.metrics_added.len())));
+        }
+
+        if !self.metrics_removed.is_empty() {
+            changes.push(format!(
+                "removed {}",
+                count_metrics(self.metrics_removed.len())
+            ));
+        }
+
+        if !self.metrics_moved.is_empty() {
+            changes.push(format!("moved {}", count_metrics(self.metrics_moved.len())));
+        }
+
+        if !self.rows_added.is_empty()
+            || !self.rows_removed.is_empty()
+            || !self.rows_resized.is_empty()
+            || self.rows_reordered
+        {
+            changes.push("changed the layout".to_string());
+        }
+
+        let summary = changes.join(", ");
+
+        let mut chars = summary.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => "No changes".to_string(),
+        }
+    }
+}
+
+fn count_metrics(count: usize) -> String {
+    if count == 1 {
+        "1 metric".to_string()
+    } else {
+        format!("{} metrics", count)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct LayoutRow {
+    id: String,
+    items: Vec<Uuid>,
+    column_sizes: Option<Value>,
+    row_height: Option<Value>,
+}
+
+/// Reads the `rows` of a dashboard config as the frontend writes them:
+/// `{ rows: [{ id, items: [{ id }], columnSizes, rowHeight }] }`.
+fn layout_rows(config: &Value) -> Vec<LayoutRow> {
+    let rows = match config.get("rows").and_then(|rows| rows.as_array()) {
+        Some(rows) => rows,
+        None => return Vec::new(),
+    };
+
+    rows.iter()
+        .enumerate()
+        .map(|(index, row)| LayoutRow {
+            id: match row.get("id") {
+                Some(Value::String(id)) => id.clone(),
+                Some(id) if !id.is_null() => id.to_string(),
+                _ => index.to_string(),
+            },
+            items: row
+                .get("items")
+                .and_then(|items| items.as_array())
+                .map(|items| {
+                    items
+                        .iter()
+                        .filter_map(|item| item.get("id").and_then(|id| id.as_str()))
+                        .filter_map(|id| Uuid::parse_str(id).ok())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            column_sizes: row.get("columnSizes").cloned(),
+            row_height: row.get("rowHeight").cloned(),
+        })
+        .collect()
+}
+
+/// The structural changes that turn `from` into `to`.
+pub fn diff_dashboard_snapshots(
+    from: &DashboardSnapshot,
+    to: &DashboardSnapshot,
+) -> DashboardVersionDiff {
+    let from_metrics = from.metrics();
+    let to_metrics = to.metrics();
+
+    let mut metrics_added: Vec<Uuid> = to_metrics.difference(&from_metrics).cloned().collect();
+    let mut metrics_removed: Vec<Uuid> = from_metrics.difference(&to_metrics).cloned().collect();
+    metrics_added.sort();
+    metrics_removed.sort();
+
+    let from_rows = layout_rows(&from.config);
+    let to_rows = layout_rows(&to.config);
+
+    let from_positions = metric_positions(&from_rows);
+    let to_positions = metric_positions(&to_rows);
+
+    let mut metrics_moved: Vec<Uuid> = to_positions
+        .iter()
+        .filter(|(metric, position)| {
+            from_positions
+                .get(metric)
+                .map_or(false, |from_position| from_position != *position)
+        })
+        .map(|(metric, _)| *metric)
+        .collect();
+    metrics_moved.sort();
+
+    let from_row_ids: HashSet<&String> = from_rows.iter().map(|row| &row.id).collect();
+    let to_row_ids: HashSet<&String> = to_rows.iter().map(|row| &row.id).collect();
+
+    let rows_added = to_rows
+        .iter()
+        .filter(|row| !from_row_ids.contains(&row.id))
+        .map(|row| row.id.clone())
+        .collect();
+    let rows_removed = from_rows
+        .iter()
+        .filter(|row| !to_row_ids.contains(&row.id))
+        .map(|row| row.id.clone())
+        .collect();
+
+    let rows_resized = to_rows
+        .iter()
+        .filter(|row| {
+            from_rows.iter().any(|from_row| {
+                from_row.id == row.id
+                    && (from_row.column_sizes != row.column_sizes
+                        || from_row.row_height != row.row_height)
+            })
+        })
+        .map(|row| row.id.clone())
+        .collect();
+
+    let kept_from_order: Vec<&String> = from_rows
+        .iter()
+        .map(|row| &row.id)
+        .filter(|id| to_row_ids.contains(id))
+        .collect();
+    let kept_to_order: Vec<&String> = to_rows
+        .iter()
+        .map(|row| &row.id)
+        .filter(|id| from_row_ids.contains(id))
+        .collect();
+
+    DashboardVersionDiff {
+        metrics_added,
+        metrics_removed,
+        metrics_moved,
+        rows_added,
+        rows_removed,
+        rows_resized,
+        rows_reordered: kept_from_order != kept_to_order,
+    }
+}
+
+fn metric_positions(rows: &[LayoutRow]) -> HashMap<Uuid, (String, usize)> {
+    let mut positions = HashMap::new();
+
+    for row in rows {
+        for (index, metric) in row.items.iter().enumerate() {
+            positions.insert(*metric, (row.id.clone(), index));
+        }
+    }
+
+    positions
+}
+
+/// Reads the current config and active metrics of a dashboard.
+pub async fn get_dashboard_snapshot(dashboard_id: &Uuid) -> Result<DashboardSnapshot> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let config = match dashboards::table
+        .select(dashboards::config)
+        .filter(dashboards::id.eq(dashboard_id))
+        .filter(dashboards::deleted_at.is_null())
+        .first::<Value>(&mut conn)
+        .await
+    {
+        Ok(config) => config,
+        Err(e) => return Err(anyhow!("Error getting dashboard config: {}", e)),
+    };
+
+    let mut thread_ids = match threads_to_dashboards::table
+        .select(threads_to_dashboards::thread_id)
+        .filter(threads_to_dashboards::dashboard_id.eq(dashboard_id))
+        .filter(threads_to_dashboards::deleted_at.is_null())
+        .load::<Uuid>(&mut conn)
+        .await
+    {
+        Ok(thread_ids) => thread_ids,
+        Err(e) => return Err(anyhow!("Error getting dashboard metrics: {}", e)),
+    };
+
+    thread_ids.sort();
+
+    Ok(DashboardSnapshot { config, thread_ids })
+}
+
+pub async fn get_dashboard_version(
+    dashboard_id: &Uuid,
+    version_id: &Uuid,
+) -> Result<DashboardVersion> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match dashboard_versions::table
+        .filter(dashboard_versions::id.eq(version_id))
+        .filter(dashboard_versions::dashboard_id.eq(dashboard_id))
+        .filter(dashboard_versions::deleted_at.is_null())
+        .first::<DashboardVersion>(&mut conn)
+        .await
+    {
+        Ok(version) => Ok(version),
+        Err(diesel::NotFound) => Err(anyhow!("Dashboard version not found")),
+        Err(e) => Err(anyhow!("Error getting dashboard version: {}", e)),
+    }
+}
+
+async fn get_latest_dashboard_version(dashboard_id: &Uuid) -> Result<Option<DashboardVersion>> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match dashboard_versions::table
+        .filter(dashboard_versions::dashboard_id.eq(dashboard_id))
+        .filter(dashboard_versions::deleted_at.is_null())
+        .order(dashboard_versions::created_at.desc())
+        .first::<DashboardVersion>(&mut conn)
+        .await
+    {
+        Ok(version) => Ok(Some(version)),
+        Err(diesel::NotFound) => Ok(None),
+        Err(e) => Err(anyhow!("Error getting latest dashboard version: {}", e)),
+    }
+}
+
+/// Dashboards created before versioning existed have no history. Their state is recorded as the
+/// first version before it is changed, so it can always be restored.
+pub async fn ensure_dashboard_baseline_version(dashboard_id: &Uuid) -> Result<()> {
+    if get_latest_dashboard_version(dashboard_id).await?.is_some() {
+        return Ok(());
+    }
+
+    let snapshot = get_dashboard_snapshot(dashboard_id).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let created_by = match dashboards::table
+        .select(dashboards::created_by)
+        .filter(dashboards::id.eq(dashboard_id))
+        .first::<Uuid>(&mut conn)
+        .await
+    {
+        Ok(created_by) => created_by,
+        Err(e) => return Err(anyhow!("Error getting dashboard owner: {}", e)),
+    };
+
+    drop(conn);
+
+    insert_dashboard_version(
+        dashboard_id,
+        snapshot,
+        Some(created_by),
+        "Initial version".to_string(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Appends a version with the dashboard's current state if it differs from the latest version.
+/// Without a `summary`, one is generated from the changes.
+pub async fn record_dashboard_version(
+    dashboard_id: &Uuid,
+    user_id: &Uuid,
+    summary: Option<String>,
+) -> Result<Option<DashboardVersion>> {
+    let snapshot = get_dashboard_snapshot(dashboard_id).await?;
+
+    let previous = match get_latest_dashboard_version(dashboard_id).await? {
+        Some(version) => DashboardSnapshot::from_version(&version),
+        None => DashboardSnapshot::empty(),
+    };
+
+    let diff = diff_dashboard_snapshots(&previous, &snapshot);
+
+    if diff.is_empty() && previous.config == snapshot.config {
+        return Ok(None);
+    }
+
+    let summary = summary.unwrap_or_else(|| {
+        if diff.is_empty() {
+            "Updated the dashboard config".to_string()
+        } else {
+            diff.summary()
+        }
+    });
+
+    let version = insert_dashboard_version(dashboard_id, snapshot, Some(*user_id), summary).await?;
+
+    Ok(Some(version))
+}
+
+async fn insert_dashboard_version(
+    dashboard_id: &Uuid,
+    snapshot: DashboardSnapshot,
+    created_by: Option<Uuid>,
+    summary: String,
+) -> Result<DashboardVersion> {
+    let thread_ids = match serde_json::to_value(&snapshot.thread_ids) {
+        Ok(thread_ids) => thread_ids,
+        Err(e) => return Err(anyhow!("Error serializing dashboard metrics: {}", e)),
+    };
+
+    let version = DashboardVersion {
+        id: Uuid::new_v4(),
+        dashboard_id: *dashboard_id,
+        config: snapshot.config,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        deleted_at: None,
+        thread_ids,
+        summary: Some(summary),
+        created_by,
+    };
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match insert_into(dashboard_versions::table)
+        .values(&version)
+        .execute(&mut conn)
+        .await
+    {
+        Ok(_) => Ok(version),
+        Err(e) => Err(anyhow!("Error inserting dashboard version: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn snapshot(rows: Value, thread_ids: Vec<Uuid>) -> DashboardSnapshot {
+        DashboardSnapshot {
+            config: json!({ "rows": rows }),
+            thread_ids,
+        }
+    }
+
+    #[test]
+    fn finds_added_and_removed_metrics() {
+        let kept = Uuid::new_v4();
+        let removed = Uuid::new_v4();
+        let added = Uuid::new_v4();
+
+        let from = snapshot(
+            json!([{ "id": "a", "items": [{ "id": kept }, { "id": removed }] }]),
+            vec![kept, removed],
+        );
+        let to = snapshot(
+            json!([{ "id": "a", "items": [{ "id": kept }] }]),
+            vec![kept, added],
+        );
+
+        let diff = diff_dashboard_snapshots(&from, &to);
+
+        assert_eq!(diff.metrics_added, vec![added]);
+        assert_eq!(diff.metrics_removed, vec![removed]);
+        assert!(diff.metrics_moved.is_empty());
+        assert_eq!(diff.summary(), "Added 1 metric, removed 1 metric");
+    }
+
+    #[test]
+    fn finds_layout_changes() {
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+
+        let from = snapshot(
+            json!([
+                { "id": "a", "items": [{ "id": first }], "columnSizes": [12] },
+                { "id": "b", "items": [{ "id": second }] },
+                { "id": "c", "items": [] }
+            ]),
+            vec![first, second],
+        );
+        let to = snapshot(
+            json!([
+                { "id": "b", "items": [{ "id": second }, { "id": first }] },
+                { "id": "a", "items": [], "columnSizes": [6, 6] },
+                { "id": "d", "items": [] }
+            ]),
+            vec![first, second],
+        );
+
+        let diff = diff_dashboard_snapshots(&from, &to);
+
+        assert!(diff.metrics_added.is_empty());
+        assert!(diff.metrics_removed.is_empty());
+        assert_eq!(diff.metrics_moved, vec![first]);
+        assert_eq!(diff.rows_added, vec!["d".to_string()]);
+        assert_eq!(diff.rows_removed, vec!["c".to_string()]);
+        assert_eq!(diff.rows_resized, vec!["a".to_string()]);
+        assert!(diff.rows_reordered);
+        assert_eq!(diff.summary(), "Moved 1 metric, changed the layout");
+    }
+
+    #[test]
+    fn identical_snapshots_have_no_changes() {
+        let metric = Uuid::new_v4();
+        let from = snapshot(
+            json!([{ "id": "a", "items": [{ "id": metric }] }]),
+            vec![metric],
+        );
+
+        let diff = diff_dashboard_snapshots(&from, &from.clone());
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.summary(), "No changes");
+    }
+}
@@ -9,8 +9,10 @@ use uuid::Uuid;
 use crate::{database::models::User, routes::ws::ws::SubscriptionRwLock};
 
 use super::{
-    delete_dashboard::delete_dashboard, get_dashboard::get_dashboard,
-    list_dashboards::list_dashboards, post_dashboard::post_dashboard, unsubscribe::unsubscribe,
+    delete_dashboard::delete_dashboard, diff_dashboard_versions::diff_dashboard_versions,
+    get_dashboard::get_dashboard, list_dashboard_versions::list_dashboard_versions,
+    list_dashboards::list_dashboards, post_dashboard::post_dashboard,
+    restore_dashboard_version::restore_dashboard_version, unsubscribe::unsubscribe,
     update_dashboard::update_dashboard,
 };
 
@@ -28,6 +30,12 @@ pub enum DashboardRoute {
     Update,
     #[serde(rename = "/dashboards/delete")]
     Delete,
+    #[serde(rename = "/dashboards/versions/list")]
+    ListVersions,
+    #[serde(rename = "/dashboards/versions/diff")]
+    DiffVersions,
+    #[serde(rename = "/dashboards/versions/restore")]
+    RestoreVersion,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -42,6 +50,9 @@ pub enum DashboardEvent {
     UpdateDashboard,
     JoinedDashboard,
     DeleteDashboard,
+    ListDashboardVersions,
+    DiffDashboardVersions,
+    RestoreDashboardVersion,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -117,6 +128,30 @@ pub async fn dashboards_router(
 
             delete_dashboard(user, req).await?;
         }
+        DashboardRoute::ListVersions => {
+            let req = match serde_json::from_value(data) {
+                Ok(req) => req,
+                Err(e) => return Err(anyhow!("Error parsing request: {}", e)),
+            };
+
+            list_dashboard_versions(user, req).await?;
+        }
+        DashboardRoute::DiffVersions => {
+            let req = match serde_json::from_value(data) {
+                Ok(req) => req,
+                Err(e) => return Err(anyhow!("Error parsing request: {}", e)),
+            };
+
+            diff_dashboard_versions(user, req).await?;
+        }
+        DashboardRoute::RestoreVersion => {
+            let req = match serde_json::from_value(data) {
+                Ok(req) => req,
+                Err(e) => return Err(anyhow!("Error parsing request: {}", e)),
+            };
+
+            restore_dashboard_version(subscriptions, user_group, user, req).await?;
+        }
     };
 
     Ok(())
@@ -131,6 +166,9 @@ impl DashboardRoute {
             "/dashboards/unsubscribe" => Ok(Self::Unsubscribe),
             "/dashboards/update" => Ok(Self::Update),
             "/dashboards/delete" => Ok(Self::Delete),
+            "/dashboards/versions/list" => Ok(Self::ListVersions),
+            "/dashboards/versions/diff" => Ok(Self::DiffVersions),
+            "/dashboards/versions/restore" => Ok(Self::RestoreVersion),
             _ => Err(anyhow!("Invalid path")),
         }
     }
@@ -0,0 +1,111 @@
+use anyhow::{anyhow, Result};
+use uuid::Uuid;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::models::User,
+    routes::ws::{
+        dashboards::dashboards_router::{DashboardEvent, DashboardRoute},
+        ws::{WsErrorCode, WsEvent, WsResponseMessage, WsSendMethod},
+        ws_router::WsRoutes,
+        ws_utils::{send_error_message, send_ws_message},
+    },
+    utils::clients::sentry_utils::send_sentry_error,
+};
+
+use super::{
+    dashboard_utils::get_user_dashboard_permission,
+    dashboard_versions::{
+        diff_dashboard_snapshots, get_dashboard_snapshot, get_dashboard_version, DashboardSnapshot,
+        DashboardVersionDiff,
+    },
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DiffDashboardVersionsRequest {
+    pub dashboard_id: Uuid,
+    pub from_version_id: Uuid,
+    /// Compares against the dashboard as it is now when left out.
+    pub to_version_id: Option<Uuid>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DiffDashboardVersionsResponse {
+    pub dashboard_id: Uuid,
+    pub from_version_id: Uuid,
+    pub to_version_id: Option<Uuid>,
+    pub summary: String,
+    pub diff: DashboardVersionDiff,
+}
+
+pub async fn diff_dashboard_versions(user: &User, req: DiffDashboardVersionsRequest) -> Result<()> {
+    let response = match diff_dashboard_versions_handler(&user.id, req).await {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::error!("Error diffing dashboard versions: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            send_error_message(
+                &user.id.to_string(),
+                WsRoutes::Dashboards(DashboardRoute::DiffVersions),
+                WsEvent::Dashboards(DashboardEvent::DiffDashboardVersions),
+                WsErrorCode::InternalServerError,
+                "Failed to compare dashboard versions.".to_string(),
+                user,
+            )
+            .await?;
+            return Err(e);
+        }
+    };
+
+    let diff_versions_message = WsResponseMessage::new(
+        WsRoutes::Dashboards(DashboardRoute::DiffVersions),
+        WsEvent::Dashboards(DashboardEvent::DiffDashboardVersions),
+        response,
+        None,
+        user,
+        WsSendMethod::SenderOnly,
+    );
+
+    match send_ws_message(&user.id.to_string(), &diff_versions_message).await {
+        Ok(_) => (),
+        Err(e) => {
+            tracing::error!("Error sending ws message: {}", e);
+            let err = anyhow!("Error sending ws message: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+async fn diff_dashboard_versions_handler(
+    user_id: &Uuid,
+    req: DiffDashboardVersionsRequest,
+) -> Result<DiffDashboardVersionsResponse> {
+    match get_user_dashboard_permission(user_id, &req.dashboard_id).await {
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(anyhow!("No dashboard permission found")),
+        Err(e) => return Err(anyhow!("Error getting dashboard permission: {}", e)),
+    };
+
+    let from = get_dashboard_version(&req.dashboard_id, &req.from_version_id).await?;
+
+    let to = match req.to_version_id {
+        Some(to_version_id) => DashboardSnapshot::from_version(
+            &get_dashboard_version(&req.dashboard_id, &to_version_id).await?,
+        ),
+        None => get_dashboard_snapshot(&req.dashboard_id).await?,
+    };
+
+    let diff = diff_dashboard_snapshots(&DashboardSnapshot::from_version(&from), &to);
+
+    Ok(DiffDashboardVersionsResponse {
+        dashboard_id: req.dashboard_id,
+        from_version_id: req.from_version_id,
+        to_version_id: req.to_version_id,
+        summary: diff.summary(),
+        diff,
+    })
+}
@@ -24,7 +24,10 @@ use crate::{
             },
             sentry_utils::send_sentry_error,
         },
-        query_engine::{data_types::DataType, query_engine::query_engine},
+        query_engine::{
+            data_types::DataType, query_cache::QueryCacheMetadata,
+            query_cancellation::QueryContext, query_engine::cached_query_engine,
+        },
     },
 };
 
@@ -169,6 +172,7 @@ pub struct FetchingData {
     pub progress: StepProgress,
     pub data: Option<Vec<IndexMap<String, DataType>>>,
     pub metric_id: Uuid,
+    pub cache: Option<QueryCacheMetadata>,
 }
 
 async fn fetch_data_handler(subscription: &String, metric: &Metric, user: &User) -> Result<()> {
@@ -177,8 +181,14 @@ async fn fetch_data_handler(subscription: &String, metric: &Metric, user: &User)
     let user = user.clone();
 
     tokio::spawn(async move {
-        let data = match query_engine(&metric.dataset_id, &metric.sql).await {
-            Ok(data) => data,
+        let (data, cache) = match cached_query_engine(
+            &metric.dataset_id,
+            &metric.sql,
+            &QueryContext::for_user(None, &user.id),
+        )
+        .await
+        {
+            Ok(results) => results,
             Err(e) => {
                 tracing::error!("Unable to query engine: {:?}", e);
                 send_sentry_error(&e.to_string(), None);
@@ -203,6 +213,7 @@ async fn fetch_data_handler(subscription: &String, metric: &Metric, user: &User)
                 Some(data)
             },
             metric_id: metric.id,
+            cache: Some(cache),
         };
 
         let fetching_data_ws_response = WsResponseMessage::new(
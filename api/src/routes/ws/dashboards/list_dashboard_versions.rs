@@ -0,0 +1,167 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, JoinOnDsl, NullableExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::{
+        lib::get_pg_pool,
+        models::User,
+        schema::{dashboard_versions, users},
+    },
+    routes::ws::{
+        dashboards::dashboards_router::{DashboardEvent, DashboardRoute},
+        ws::{WsErrorCode, WsEvent, WsResponseMessage, WsSendMethod},
+        ws_router::WsRoutes,
+        ws_utils::{send_error_message, send_ws_message},
+    },
+    utils::clients::sentry_utils::send_sentry_error,
+};
+
+use super::dashboard_utils::get_user_dashboard_permission;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ListDashboardVersionsRequest {
+    pub dashboard_id: Uuid,
+    pub page: Option<i64>,
+    pub page_size: Option<i64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DashboardVersionAuthor {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DashboardVersionListItem {
+    pub id: Uuid,
+    pub summary: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub created_by: Option<DashboardVersionAuthor>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ListDashboardVersionsResponse {
+    pub dashboard_id: Uuid,
+    pub versions: Vec<DashboardVersionListItem>,
+}
+
+pub async fn list_dashboard_versions(user: &User, req: ListDashboardVersionsRequest) -> Result<()> {
+    let response = match list_dashboard_versions_handler(&user.id, req).await {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::error!("Error listing dashboard versions: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            send_error_message(
+                &user.id.to_string(),
+                WsRoutes::Dashboards(DashboardRoute::ListVersions),
+                WsEvent::Dashboards(DashboardEvent::ListDashboardVersions),
+                WsErrorCode::InternalServerError,
+                "Failed to list dashboard versions.".to_string(),
+                user,
+            )
+            .await?;
+            return Err(e);
+        }
+    };
+
+    let list_versions_message = WsResponseMessage::new(
+        WsRoutes::Dashboards(DashboardRoute::ListVersions),
+        WsEvent::Dashboards(DashboardEvent::ListDashboardVersions),
+        response,
+        None,
+        user,
+        WsSendMethod::SenderOnly,
+    );
+
+    match send_ws_message(&user.id.to_string(), &list_versions_message).await {
+        Ok(_) => (),
+        Err(e) => {
+            tracing::error!("Error sending ws message: {}", e);
+            let err = anyhow!("Error sending ws message: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+async fn list_dashboard_versions_handler(
+    user_id: &Uuid,
+    req: ListDashboardVersionsRequest,
+) -> Result<ListDashboardVersionsResponse> {
+    match get_user_dashboard_permission(user_id, &req.dashboard_id).await {
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(anyhow!("No dashboard permission found")),
+        Err(e) => return Err(anyhow!("Error getting dashboard permission: {}", e)),
+    };
+
+    let page = req.page.unwrap_or(0);
+    let page_size = req.page_size.unwrap_or(25);
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let version_records = match dashboard_versions::table
+        .left_join(users::table.on(dashboard_versions::created_by.eq(users::id.nullable())))
+        .select((
+            dashboard_versions::id,
+            dashboard_versions::summary,
+            dashboard_versions::created_at,
+            users::id.nullable(),
+            users::name.nullable(),
+            users::email.nullable(),
+        ))
+        .filter(dashboard_versions::dashboard_id.eq(req.dashboard_id))
+        .filter(dashboard_versions::deleted_at.is_null())
+        .order(dashboard_versions::created_at.desc())
+        .limit(page_size)
+        .offset(page * page_size)
+        .load::<(
+            Uuid,
+            Option<String>,
+            DateTime<Utc>,
+            Option<Uuid>,
+            Option<String>,
+            Option<String>,
+        )>(&mut conn)
+        .await
+    {
+        Ok(version_records) => version_records,
+        Err(e) => return Err(anyhow!("Error getting dashboard versions: {}", e)),
+    };
+
+    let versions = version_records
+        .into_iter()
+        .map(
+            |(id, summary, created_at, author_id, author_name, author_email)| {
+                DashboardVersionListItem {
+                    id,
+                    summary,
+                    created_at,
+                    created_by: match (author_id, author_email) {
+                        (Some(author_id), Some(author_email)) => Some(DashboardVersionAuthor {
+                            id: author_id,
+                            name: author_name.unwrap_or(author_email.clone()),
+                            email: author_email,
+                        }),
+                        _ => None,
+                    },
+                }
+            },
+        )
+        .collect();
+
+    Ok(ListDashboardVersionsResponse {
+        dashboard_id: req.dashboard_id,
+        versions,
+    })
+}
@@ -1,8 +1,12 @@
 pub mod dashboards_router;
 mod delete_dashboard;
+mod diff_dashboard_versions;
 mod get_dashboard;
+mod list_dashboard_versions;
 mod list_dashboards;
 mod post_dashboard;
+mod restore_dashboard_version;
 mod unsubscribe;
 mod update_dashboard;
 mod dashboard_utils;
+mod dashboard_versions;
\ No newline at end of file
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use uuid::Uuid;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::{enums::AssetPermissionRole, models::User},
+    routes::ws::{
+        dashboards::dashboards_router::{DashboardEvent, DashboardRoute},
+        ws::{SubscriptionRwLock, WsErrorCode, WsEvent},
+        ws_router::WsRoutes,
+        ws_utils::send_error_message,
+    },
+    utils::clients::sentry_utils::send_sentry_error,
+};
+
+use super::{
+    dashboard_utils::get_user_dashboard_permission,
+    dashboard_versions::{get_dashboard_version, DashboardSnapshot},
+    update_dashboard::{update_dashboard, UpdateDashboardRequest},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RestoreDashboardVersionRequest {
+    pub dashboard_id: Uuid,
+    pub version_id: Uuid,
+}
+
+/// Puts the config and metrics of an earlier version back on the dashboard. The restore is itself
+/// recorded as a new version, so it can be undone, and subscribers get the updated dashboard the
+/// same way as for any other update.
+pub async fn restore_dashboard_version(
+    subscriptions: &Arc<SubscriptionRwLock>,
+    user_group: &String,
+    user: &User,
+    req: RestoreDashboardVersionRequest,
+) -> Result<()> {
+    let update_req = match restore_dashboard_version_request(&user.id, &req).await {
+        Ok(update_req) => update_req,
+        Err(e) => {
+            tracing::error!("Error restoring dashboard version: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            send_error_message(
+                &user.id.to_string(),
+                WsRoutes::Dashboards(DashboardRoute::RestoreVersion),
+                WsEvent::Dashboards(DashboardEvent::RestoreDashboardVersion),
+                WsErrorCode::InternalServerError,
+                "Failed to restore dashboard version.".to_string(),
+                user,
+            )
+            .await?;
+            return Err(e);
+        }
+    };
+
+    update_dashboard(subscriptions, user_group, user, update_req).await
+}
+
+async fn restore_dashboard_version_request(
+    user_id: &Uuid,
+    req: &RestoreDashboardVersionRequest,
+) -> Result<UpdateDashboardRequest> {
+    match get_user_dashboard_permission(user_id, &req.dashboard_id).await {
+        Ok(Some(AssetPermissionRole::Viewer)) => {
+            return Err(anyhow!(
+                "User does not have permission to restore dashboard versions"
+            ))
+        }
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(anyhow!("No dashboard permission found")),
+        Err(e) => return Err(anyhow!("Error getting dashboard permission: {}", e)),
+    };
+
+    let version = get_dashboard_version(&req.dashboard_id, &req.version_id).await?;
+
+    let summary = format!(
+        "Restored the version from {}",
+        version.created_at.format("%Y-%m-%d %H:%M UTC")
+    );
+
+    let snapshot = DashboardSnapshot::from_version(&version);
+
+    Ok(UpdateDashboardRequest {
+        id: req.dashboard_id,
+        name: None,
+        description: None,
+        config: Some(snapshot.config),
+        threads: Some(snapshot.thread_ids),
+        publicly_accessible: None,
+        public_password: None,
+        public_expiry_date: None,
+        team_permissions: None,
+        user_permissions: None,
+        remove_teams: None,
+        remove_users: None,
+        add_to_collections: None,
+        remove_from_collections: None,
+        version_summary: Some(summary),
+    })
+}
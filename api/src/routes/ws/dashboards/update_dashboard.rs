@@ -2,7 +2,7 @@ use anyhow::{anyhow, Result};
 use chrono::{DateTime, Utc};
 use diesel::{dsl::not, query_builder::AsChangeset, update, ExpressionMethods};
 use diesel_async::RunQueryDsl;
-use serde_json::Value;
+use serde_json::{json, Value};
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -10,7 +10,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     database::{
-        enums::{AssetPermissionRole, AssetType},
+        enums::{AssetPermissionRole, AssetType, AuditAction, AuditTargetType},
         lib::{get_pg_pool, get_sqlx_pool},
         models::{ThreadToDashboard, User},
         schema::{dashboards, threads_to_dashboards},
@@ -22,6 +22,7 @@ use crate::{
         ws_utils::{send_error_message, send_ws_message, subscribe_to_stream},
     },
     utils::{
+        audit::audit_log::{spawn_audit_event, AuditRequestMetadata, NewAuditEvent},
         clients::{sentry_utils::send_sentry_error, supabase_vault::create_secret},
         sharing::asset_sharing::{
             create_asset_collection_association, delete_asset_collection_association,
@@ -30,7 +31,10 @@ use crate::{
     },
 };
 
-use super::dashboard_utils::{get_dashboard_state_by_id, get_user_dashboard_permission};
+use super::{
+    dashboard_utils::{get_dashboard_state_by_id, get_user_dashboard_permission},
+    dashboard_versions::{ensure_dashboard_baseline_version, record_dashboard_version},
+};
 use crate::utils::serde_helpers::deserialization_helpers::deserialize_double_option;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -53,6 +57,9 @@ pub struct UpdateDashboardRequest {
     pub remove_users: Option<Vec<Uuid>>,
     pub add_to_collections: Option<Vec<Uuid>>,
     pub remove_from_collections: Option<Vec<Uuid>>,
+    /// Describes the version recorded for a config or metric change. Generated from the changes
+    /// when left out.
+    pub version_summary: Option<String>,
 }
 
 pub async fn update_dashboard(
@@ -93,6 +100,28 @@ pub async fn update_dashboard(
         }
     };
 
+    let versioned_change = req.config.is_some() || req.threads.is_some();
+
+    if versioned_change {
+        if let Err(e) = ensure_dashboard_baseline_version(&dashboard_id).await {
+            tracing::error!("Error recording baseline dashboard version: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+        }
+    }
+
+    let public_sharing_change = if req.publicly_accessible.is_some()
+        || req.public_password.is_some()
+        || req.public_expiry_date.is_some()
+    {
+        Some(json!({
+            "publicly_accessible": req.publicly_accessible,
+            "password_protected": req.public_password.as_ref().map(|password| password.is_some()),
+            "public_expiry_date": req.public_expiry_date,
+        }))
+    } else {
+        None
+    };
+
     let user_id = Arc::new(user.id.clone());
     let dashboard_id = Arc::new(dashboard_id.clone());
 
@@ -170,6 +199,7 @@ pub async fn update_dashboard(
                 req.user_permissions,
                 req.remove_teams,
                 req.remove_users,
+                AuditRequestMetadata::ws(WsRoutes::Dashboards(DashboardRoute::Update)),
             )
             .await
             {
@@ -205,6 +235,20 @@ pub async fn update_dashboard(
         }
     }
 
+    if let Some(public_sharing_change) = public_sharing_change {
+        spawn_audit_event(NewAuditEvent {
+            actor_id: user.id,
+            action: AuditAction::AssetPubliclyShared,
+            target_type: AuditTargetType::Dashboard,
+            target_id: Some(*dashboard_id),
+            before: None,
+            after: Some(public_sharing_change),
+            request_metadata: AuditRequestMetadata::ws(WsRoutes::Dashboards(
+                DashboardRoute::Update,
+            )),
+        });
+    }
+
     if let Some(update_dashboard_collections_handle) = update_dashboard_collections_handle {
         match update_dashboard_collections_handle.await {
             Ok(_) => (),
@@ -227,6 +271,13 @@ pub async fn update_dashboard(
         }
     }
 
+    if versioned_change {
+        if let Err(e) = record_dashboard_version(&req.id, &user.id, req.version_summary).await {
+            tracing::error!("Error recording dashboard version: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+        }
+    }
+
     let dashboard = match get_dashboard_state_by_id(&user.id, &req.id).await {
         Ok(dashboard) => dashboard,
         Err(e) => {
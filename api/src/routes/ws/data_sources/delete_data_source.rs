@@ -8,7 +8,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     database::{
-        enums::UserOrganizationRole,
+        enums::{AuditAction, AuditTargetType, UserOrganizationRole},
         lib::get_pg_pool,
         models::User,
         schema::{data_sources, users_to_organizations},
@@ -19,7 +19,11 @@ use crate::{
         ws_router::WsRoutes,
         ws_utils::{send_error_message, send_ws_message},
     },
-    utils::clients::{sentry_utils::send_sentry_error, supabase_vault::delete_secret},
+    utils::{
+        audit::audit_log::{spawn_audit_event, AuditRequestMetadata, NewAuditEvent},
+        clients::{sentry_utils::send_sentry_error, supabase_vault::delete_secret},
+        query_engine::connection_cache::invalidate_data_source_connections,
+    },
 };
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -52,6 +56,16 @@ pub async fn delete_data_source(user: &User, req: DeleteDataSourceReq) -> Result
         }
     };
 
+    spawn_audit_event(NewAuditEvent {
+        actor_id: user.id,
+        action: AuditAction::DataSourceDeleted,
+        target_type: AuditTargetType::DataSource,
+        target_id: Some(req.id),
+        before: None,
+        after: None,
+        request_metadata: AuditRequestMetadata::ws(WsRoutes::DataSources(DataSourceRoute::Delete)),
+    });
+
     let delete_data_source_res = DeleteDataSourceRes { id: req.id };
 
     let post_data_source_message = WsResponseMessage::new(
@@ -148,5 +162,7 @@ async fn delete_data_source_handler(user_id: &Uuid, id: Uuid) -> Result<()> {
         }
     };
 
+    invalidate_data_source_connections(&id).await;
+
     Ok(())
 }
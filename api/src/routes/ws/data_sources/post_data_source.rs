@@ -2,13 +2,17 @@ use anyhow::{anyhow, Result};
 use chrono::Utc;
 use diesel::{insert_into, BoolExpressionMethods, ExpressionMethods, QueryDsl};
 use diesel_async::RunQueryDsl;
+use serde_json::json;
 use uuid::Uuid;
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
     database::{
-        enums::{DataSourceOnboardingStatus, DataSourceType, UserOrganizationRole},
+        enums::{
+            AuditAction, AuditTargetType, DataSourceOnboardingStatus, DataSourceType,
+            UserOrganizationRole,
+        },
         lib::get_pg_pool,
         models::{DataSource, User},
         schema::{data_sources, users_to_organizations},
@@ -20,6 +24,7 @@ use crate::{
         ws_utils::{send_error_message, send_ws_message},
     },
     utils::{
+        audit::audit_log::{spawn_audit_event, AuditRequestMetadata, NewAuditEvent},
         clients::{sentry_utils::send_sentry_error, supabase_vault::create_secret},
         query_engine::{
             credentials::Credential, import_datasets::import_datasets,
@@ -66,6 +71,20 @@ pub async fn post_data_source(user: &User, req: PostDataSourceReq) -> Result<()>
             }
         };
 
+    // Credentials are left out of the audit log.
+    spawn_audit_event(NewAuditEvent {
+        actor_id: user.id,
+        action: AuditAction::DataSourceCreated,
+        target_type: AuditTargetType::DataSource,
+        target_id: Some(post_data_source_res.id),
+        before: None,
+        after: Some(json!({
+            "name": post_data_source_res.name,
+            "type": post_data_source_res.type_,
+        })),
+        request_metadata: AuditRequestMetadata::ws(WsRoutes::DataSources(DataSourceRoute::Post)),
+    });
+
     match import_datasets(
         &req.credentials,
         &post_data_source_res.id,
@@ -163,6 +182,7 @@ async fn post_data_source_handler(
         onboarding_status: DataSourceOnboardingStatus::NotStarted,
         onboarding_error: None,
         env: "dev".to_string(),
+        max_execution_time_seconds: None,
     };
 
     match insert_into(data_sources::table)
@@ -2,13 +2,14 @@ use anyhow::{anyhow, Result};
 use chrono::Utc;
 use diesel::{update, BoolExpressionMethods, ExpressionMethods, JoinOnDsl, QueryDsl};
 use diesel_async::RunQueryDsl;
+use serde_json::json;
 use uuid::Uuid;
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
     database::{
-        enums::{DataSourceType, UserOrganizationRole},
+        enums::{AuditAction, AuditTargetType, DataSourceType, UserOrganizationRole},
         lib::get_pg_pool,
         models::User,
         schema::{data_sources, users_to_organizations},
@@ -20,9 +21,11 @@ use crate::{
         ws_utils::{send_error_message, send_ws_message},
     },
     utils::{
+        audit::audit_log::{spawn_audit_event, AuditRequestMetadata, NewAuditEvent},
         clients::{sentry_utils::send_sentry_error, supabase_vault::update_secret},
         query_engine::{
-            credentials::Credential, test_data_source_connections::test_data_source_connection,
+            connection_cache::invalidate_data_source_connections, credentials::Credential,
+            test_data_source_connections::test_data_source_connection,
         },
     },
 };
@@ -33,6 +36,9 @@ use super::data_source_utils::data_source_utils::{get_data_source_state, DataSou
 pub struct UpdateDataSourceReq {
     pub id: Uuid,
     pub credentials: Credential,
+    /// Longest a query may run before it is cancelled. Left unchanged when not sent; 0 goes back
+    /// to `QUERY_MAX_EXECUTION_TIME_SECONDS`.
+    pub max_execution_time_seconds: Option<i32>,
 }
 
 pub async fn update_data_source(user: &User, req: UpdateDataSourceReq) -> Result<()> {
@@ -66,7 +72,13 @@ pub async fn update_data_source(user: &User, req: UpdateDataSourceReq) -> Result
     };
 
     let post_data_source_res =
-        match update_data_source_handler(&user.id, req.id, req.credentials).await {
+        match update_data_source_handler(
+            &user.id,
+            req.id,
+            req.credentials,
+            req.max_execution_time_seconds,
+        )
+        .await {
             Ok(res) => res,
             Err(e) => {
                 tracing::error!("Error getting data source: {}", e);
@@ -85,6 +97,20 @@ pub async fn update_data_source(user: &User, req: UpdateDataSourceReq) -> Result
             }
         };
 
+    // The new credentials are left out of the audit log.
+    spawn_audit_event(NewAuditEvent {
+        actor_id: user.id,
+        action: AuditAction::DataSourceUpdated,
+        target_type: AuditTargetType::DataSource,
+        target_id: Some(req.id),
+        before: None,
+        after: Some(json!({
+            "credentials_rotated": true,
+            "max_execution_time_seconds": req.max_execution_time_seconds,
+        })),
+        request_metadata: AuditRequestMetadata::ws(WsRoutes::DataSources(DataSourceRoute::Update)),
+    });
+
     let post_data_source_message = WsResponseMessage::new(
         WsRoutes::DataSources(DataSourceRoute::Update),
         WsEvent::DataSources(DataSourceEvent::UpdateDataSource),
@@ -111,6 +137,7 @@ async fn update_data_source_handler(
     user_id: &Uuid,
     id: Uuid,
     credentials: Credential,
+    max_execution_time_seconds: Option<i32>,
 ) -> Result<DataSourceState> {
     let mut conn = match get_pg_pool().get().await {
         Ok(conn) => conn,
@@ -180,6 +207,20 @@ async fn update_data_source_handler(
                 }
             };
 
+            if let Some(max_execution_time_seconds) = max_execution_time_seconds {
+                match update(data_sources::table)
+                    .set(data_sources::max_execution_time_seconds.eq(max_execution_time_seconds))
+                    .filter(data_sources::id.eq(&id))
+                    .execute(&mut conn)
+                    .await
+                {
+                    Ok(_) => (),
+                    Err(e) => {
+                        return Err(anyhow!("Error updating data source: {}", e));
+                    }
+                };
+            }
+
             Ok(())
         })
     };
@@ -191,6 +232,8 @@ async fn update_data_source_handler(
         }
     };
 
+    invalidate_data_source_connections(&id).await;
+
     let data_source_state = match get_data_source_state(user_id, id).await {
         Ok(data_source_state) => data_source_state,
         Err(e) => {
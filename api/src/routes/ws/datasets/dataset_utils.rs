@@ -21,6 +21,7 @@ use crate::{
     utils::clients::ai::{
         langfuse::PromptName,
         llm_router::{llm_chat, LlmMessage, LlmModel, LlmRole},
+        llm_usage::LlmUsageScope,
         openai::OpenAiChatModel,
     },
 };
@@ -205,6 +206,7 @@ async fn get_dataset_and_columns(
                 data_sources::updated_at,
                 data_sources::deleted_at.nullable(),
                 data_sources::env,
+                data_sources::max_execution_time_seconds,
             ),
             users::name.nullable(),
             users::email,
@@ -565,6 +567,7 @@ Output in json with each key being the column name and the value being the descr
         dataset_id,
         user_id,
         PromptName::GenerateColDescriptions,
+        LlmUsageScope::default(),
     )
     .await
     {
@@ -620,6 +623,7 @@ Output the description in JSON with 'when_to_use' and 'when_not_to_use' as the k
         dataset_id,
         user_id,
         PromptName::GenerateDatasetDescription,
+        LlmUsageScope::default(),
     )
     .await
     {
@@ -55,7 +55,7 @@ pub async fn get_dataset(user: &User, req: GetDatasetReq) -> Result<()> {
         let schema = dataset_state.dataset.schema.clone();
         let database_name = dataset_state.dataset.database_name.clone();
         let sql = format!("SELECT * FROM {}.{} LIMIT 25", schema, database_name);
-        match query_engine(&req.id, &sql).await {
+        match query_engine(&req.id, &sql, &user.id).await {
             Ok(data) => data,
             Err(e) => Vec::new(),
         }
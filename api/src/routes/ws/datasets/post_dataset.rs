@@ -234,6 +234,7 @@ async fn create_dataset(user_id: &Uuid, name: &String, data_source_id: &Uuid) ->
         imported: false,
         organization_id: user_org_id,
         yml_file: None,
+        cache_ttl_seconds: None,
         model: None,
     };
 
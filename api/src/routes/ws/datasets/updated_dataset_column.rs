@@ -20,7 +20,7 @@ use crate::{
     },
     utils::{
         clients::{sentry_utils::send_sentry_error, typesense},
-        query_engine::values_index::start_stored_values_sync,
+        values_engine::values_engine::sync_column_values_now,
     },
 };
 
@@ -106,7 +106,7 @@ async fn update_dataset_column_handler(
         {
             let dataset_column_id = dataset_column_id.clone();
             tokio::spawn(async move {
-                match start_stored_values_sync(&dataset_column_id).await {
+                match sync_column_values_now(&dataset_column_id).await {
                     Ok(_) => (),
                     Err(e) => return Err(anyhow!("Error starting stored values sync: {}", e)),
                 }
@@ -700,6 +700,7 @@ async fn get_team_and_creator(team_id: Arc<Uuid>) -> Result<(Team, CreatedByUser
                 teams::created_at,
                 teams::updated_at,
                 teams::deleted_at,
+                teams::attributes,
             ),
             users::id,
             users::name.nullable(),
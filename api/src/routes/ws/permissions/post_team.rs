@@ -3,6 +3,7 @@ use diesel::insert_into;
 use diesel_async::RunQueryDsl;
 
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use uuid::Uuid;
 
 use crate::{
@@ -90,6 +91,7 @@ async fn post_team_handler(user_id: &Uuid, name: String) -> Result<TeamPermissio
         upload_csv: true,
         export_assets: true,
         email_slack_enabled: true,
+        attributes: json!({}),
     };
 
     let mut conn = get_pg_pool().get().await?;
@@ -92,6 +92,7 @@ async fn post_user_handler(
         })?,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        attributes: serde_json::json!({}),
     };
 
     let user_to_organization = UserToOrganization {
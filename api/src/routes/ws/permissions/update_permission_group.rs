@@ -8,7 +8,7 @@ use uuid::Uuid;
 
 use crate::{
     database::{
-        enums::IdentityType,
+        enums::{AuditAction, AuditTargetType, IdentityType},
         lib::get_pg_pool,
         models::{DatasetToPermissionGroup, User},
         schema::{datasets_to_permission_groups, permission_groups},
@@ -19,7 +19,10 @@ use crate::{
         ws_router::WsRoutes,
         ws_utils::{send_error_message, send_ws_message},
     },
-    utils::clients::sentry_utils::send_sentry_error,
+    utils::{
+        audit::audit_log::{spawn_audit_event, AuditRequestMetadata, NewAuditEvent},
+        clients::sentry_utils::send_sentry_error,
+    },
 };
 
 use super::permissions_utils::{
@@ -46,6 +49,14 @@ pub struct UpdatePermissionGroupRequest {
 }
 
 pub async fn update_permission_group(user: &User, req: UpdatePermissionGroupRequest) -> Result<()> {
+    let before = match get_permission_group_state(&req.id).await {
+        Ok(state) => serde_json::to_value(&state).ok(),
+        Err(e) => {
+            tracing::error!("Error getting state before update: {}", e);
+            None
+        }
+    };
+
     let permission_group_state = match update_permission_group_handler(
         &user.id,
         &req.id,
@@ -73,6 +84,18 @@ pub async fn update_permission_group(user: &User, req: UpdatePermissionGroupRequ
         }
     };
 
+    spawn_audit_event(NewAuditEvent {
+        actor_id: user.id,
+        action: AuditAction::PermissionGroupUpdated,
+        target_type: AuditTargetType::PermissionGroup,
+        target_id: Some(req.id),
+        before,
+        after: serde_json::to_value(&permission_group_state).ok(),
+        request_metadata: AuditRequestMetadata::ws(WsRoutes::Permissions(
+            PermissionRoute::UpdatePermissionGroup,
+        )),
+    });
+
     let update_permission_group_message = WsResponseMessage::new(
         WsRoutes::Permissions(PermissionRoute::UpdatePermissionGroup),
         WsEvent::Permissions(PermissionEvent::UpdatePermissionGroup),
@@ -8,7 +8,7 @@ use uuid::Uuid;
 
 use crate::{
     database::{
-        enums::{IdentityType, SharingSetting, TeamToUserRole},
+        enums::{AuditAction, AuditTargetType, IdentityType, SharingSetting, TeamToUserRole},
         lib::get_pg_pool,
         models::{PermissionGroupToIdentity, TeamToUser, User},
         schema::{permission_groups_to_identities, teams, teams_to_users},
@@ -19,7 +19,10 @@ use crate::{
         ws_router::WsRoutes,
         ws_utils::{send_error_message, send_ws_message},
     },
-    utils::clients::sentry_utils::send_sentry_error,
+    utils::{
+        audit::audit_log::{spawn_audit_event, AuditRequestMetadata, NewAuditEvent},
+        clients::sentry_utils::send_sentry_error,
+    },
 };
 
 use super::permissions_utils::{get_team_permission_group_state, TeamPermissionGroupState};
@@ -52,6 +55,14 @@ pub struct UpdateTeamPermissionRequest {
 }
 
 pub async fn update_team_permission(user: &User, req: UpdateTeamPermissionRequest) -> Result<()> {
+    let before = match get_team_permission_group_state(&req.id).await {
+        Ok(state) => serde_json::to_value(&state).ok(),
+        Err(e) => {
+            tracing::error!("Error getting state before update: {}", e);
+            None
+        }
+    };
+
     let team_permission_state = match update_team_permission_handler(
         &user.id,
         &req.id,
@@ -78,6 +89,18 @@ pub async fn update_team_permission(user: &User, req: UpdateTeamPermissionReques
         }
     };
 
+    spawn_audit_event(NewAuditEvent {
+        actor_id: user.id,
+        action: AuditAction::TeamPermissionsUpdated,
+        target_type: AuditTargetType::Team,
+        target_id: Some(req.id),
+        before,
+        after: serde_json::to_value(&team_permission_state).ok(),
+        request_metadata: AuditRequestMetadata::ws(WsRoutes::Permissions(
+            PermissionRoute::UpdateTeamPermission,
+        )),
+    });
+
     let update_permission_group_message = WsResponseMessage::new(
         WsRoutes::Permissions(PermissionRoute::UpdateTeamPermission),
         WsEvent::Permissions(PermissionEvent::UpdateTeamPermission),
@@ -8,7 +8,7 @@ use uuid::Uuid;
 
 use crate::{
     database::{
-        enums::{IdentityType, SharingSetting, TeamToUserRole},
+        enums::{AuditAction, AuditTargetType, IdentityType, SharingSetting, TeamToUserRole},
         lib::get_pg_pool,
         models::{PermissionGroupToIdentity, TeamToUser, User},
         schema::{permission_groups_to_identities, teams_to_users, users, users_to_organizations},
@@ -19,7 +19,11 @@ use crate::{
         ws_router::WsRoutes,
         ws_utils::{send_error_message, send_ws_message},
     },
-    utils::{clients::sentry_utils::send_sentry_error, user::user_info::get_user_organization_id},
+    utils::{
+        audit::audit_log::{spawn_audit_event, AuditRequestMetadata, NewAuditEvent},
+        clients::sentry_utils::send_sentry_error,
+        user::user_info::get_user_organization_id,
+    },
 };
 
 use super::permissions_utils::{get_user_permission_group_state, UserPermissionGroupState};
@@ -52,6 +56,14 @@ pub struct UpdateUserPermissionRequest {
 }
 
 pub async fn update_user_permission(user: &User, req: UpdateUserPermissionRequest) -> Result<()> {
+    let before = match get_user_permission_group_state(&req.id).await {
+        Ok(state) => serde_json::to_value(&state).ok(),
+        Err(e) => {
+            tracing::error!("Error getting state before update: {}", e);
+            None
+        }
+    };
+
     let user_permission_state = match update_user_permission_handler(
         &user.id,
         &req.id,
@@ -79,6 +91,18 @@ pub async fn update_user_permission(user: &User, req: UpdateUserPermissionReques
         }
     };
 
+    spawn_audit_event(NewAuditEvent {
+        actor_id: user.id,
+        action: AuditAction::UserPermissionsUpdated,
+        target_type: AuditTargetType::User,
+        target_id: Some(req.id),
+        before,
+        after: serde_json::to_value(&user_permission_state).ok(),
+        request_metadata: AuditRequestMetadata::ws(WsRoutes::Permissions(
+            PermissionRoute::UpdateUserPermission,
+        )),
+    });
+
     let update_permission_group_message = WsResponseMessage::new(
         WsRoutes::Permissions(PermissionRoute::UpdateUserPermission),
         WsEvent::Permissions(PermissionEvent::UpdateUserPermission),
@@ -0,0 +1,71 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    database::models::User,
+    routes::ws::{
+        sql::sql_router::{SqlEvent, SqlRoute},
+        ws::{WsErrorCode, WsEvent, WsResponseMessage, WsSendMethod},
+        ws_router::WsRoutes,
+        ws_utils::{send_error_message, send_ws_message},
+    },
+    utils::{
+        clients::sentry_utils::send_sentry_error,
+        query_engine::query_cancellation::cancel_query,
+    },
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CancelSqlRequest {
+    pub query_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CancelSqlResponse {
+    pub query_id: Uuid,
+}
+
+/// Cancels a query started through `/sql/run` with the same `query_id`. The run itself answers
+/// with an error once the data source has stopped it.
+pub async fn cancel_sql(user: &User, req: CancelSqlRequest) -> Result<()> {
+    match cancel_query(&req.query_id, &user.id) {
+        Ok(_) => (),
+        Err(e) => {
+            tracing::error!("Error cancelling SQL: {}", e);
+            send_error_message(
+                &user.id.to_string(),
+                WsRoutes::Sql(SqlRoute::Cancel),
+                WsEvent::Sql(SqlEvent::CancelSql),
+                WsErrorCode::NotFound,
+                e.to_string(),
+                user,
+            )
+            .await?;
+            return Err(anyhow!("Error cancelling SQL: {}", e));
+        }
+    };
+
+    let cancel_sql_message = WsResponseMessage::new(
+        WsRoutes::Sql(SqlRoute::Cancel),
+        WsEvent::Sql(SqlEvent::CancelSql),
+        CancelSqlResponse {
+            query_id: req.query_id,
+        },
+        None,
+        user,
+        WsSendMethod::SenderOnly,
+    );
+
+    match send_ws_message(&user.id.to_string(), &cancel_sql_message).await {
+        Ok(_) => (),
+        Err(e) => {
+            tracing::error!("Error sending ws message: {}", e);
+            let err = anyhow!("Error sending ws message: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
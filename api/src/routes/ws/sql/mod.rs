@@ -1,2 +1,4 @@
+mod cancel_sql;
+mod page_sql;
 pub mod sql_router;
 mod run_sql;
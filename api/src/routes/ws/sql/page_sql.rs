@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    database::models::User,
+    routes::ws::{
+        sql::sql_router::{SqlEvent, SqlRoute},
+        ws::{WsErrorCode, WsEvent, WsResponseMessage, WsSendMethod},
+        ws_router::WsRoutes,
+        ws_utils::{send_error_message, send_ws_message},
+    },
+    utils::{
+        clients::sentry_utils::send_sentry_error,
+        query_engine::{
+            query_cancellation::QueryContext,
+            query_pagination::{close_paged_query, get_query_page, get_query_page_at_cursor},
+        },
+    },
+};
+
+use super::run_sql::{paged_data_object, PagedDataObject};
+
+/// Either `cursor` (the `next_cursor` of an earlier page) or `query_handle` with an `offset`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PageSqlRequest {
+    pub query_handle: Option<Uuid>,
+    pub offset: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClosePageSqlRequest {
+    pub query_handle: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClosePageSqlResponse {
+    pub query_handle: Uuid,
+}
+
+/// Fetches another page of a query started through `/sql/run` with a `page_size`.
+pub async fn page_sql(user: &User, req: PageSqlRequest) -> Result<()> {
+    let page_sql_res = match page_sql_handler(&req, &user.id).await {
+        Ok(res) => res,
+        Err(e) => {
+            tracing::error!("Error fetching SQL page: {}", e);
+            let err = anyhow!("Error fetching SQL page: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            send_error_message(
+                &user.id.to_string(),
+                WsRoutes::Sql(SqlRoute::Page),
+                WsEvent::Sql(SqlEvent::PageSql),
+                WsErrorCode::InternalServerError,
+                e.to_string(),
+                user,
+            )
+            .await?;
+            return Err(err);
+        }
+    };
+
+    let page_sql_message = WsResponseMessage::new(
+        WsRoutes::Sql(SqlRoute::Page),
+        WsEvent::Sql(SqlEvent::PageSql),
+        page_sql_res,
+        None,
+        user,
+        WsSendMethod::SenderOnly,
+    );
+
+    match send_ws_message(&user.id.to_string(), &page_sql_message).await {
+        Ok(_) => (),
+        Err(e) => {
+            tracing::error!("Error sending ws message: {}", e);
+            let err = anyhow!("Error sending ws message: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Releases a paged query before it idles out.
+pub async fn close_page_sql(user: &User, req: ClosePageSqlRequest) -> Result<()> {
+    match close_paged_query(&req.query_handle, &user.id) {
+        Ok(_) => (),
+        Err(e) => {
+            tracing::error!("Error closing SQL page: {}", e);
+            send_error_message(
+                &user.id.to_string(),
+                WsRoutes::Sql(SqlRoute::ClosePage),
+                WsEvent::Sql(SqlEvent::ClosePageSql),
+                WsErrorCode::NotFound,
+                e.to_string(),
+                user,
+            )
+            .await?;
+            return Err(anyhow!("Error closing SQL page: {}", e));
+        }
+    };
+
+    let close_page_sql_message = WsResponseMessage::new(
+        WsRoutes::Sql(SqlRoute::ClosePage),
+        WsEvent::Sql(SqlEvent::ClosePageSql),
+        ClosePageSqlResponse {
+            query_handle: req.query_handle,
+        },
+        None,
+        user,
+        WsSendMethod::SenderOnly,
+    );
+
+    match send_ws_message(&user.id.to_string(), &close_page_sql_message).await {
+        Ok(_) => (),
+        Err(e) => {
+            tracing::error!("Error sending ws message: {}", e);
+            let err = anyhow!("Error sending ws message: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+async fn page_sql_handler(req: &PageSqlRequest, user_id: &Uuid) -> Result<PagedDataObject> {
+    let context = QueryContext::for_user(None, user_id);
+
+    let page = match (&req.cursor, &req.query_handle) {
+        (Some(cursor), _) => get_query_page_at_cursor(cursor, &context).await?,
+        (None, Some(query_handle)) => {
+            get_query_page(query_handle, req.offset.unwrap_or(0), &context).await?
+        }
+        (None, None) => return Err(anyhow!("No cursor or query handle provided")),
+    };
+
+    paged_data_object(page).await
+}
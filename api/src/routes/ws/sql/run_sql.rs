@@ -21,10 +21,17 @@ use crate::{
         ws_utils::{send_error_message, send_ws_message},
     },
     utils::{
+        audit::audit_log::{spawn_audit_event, AuditRequestMetadata, NewAuditEvent},
         clients::sentry_utils::send_sentry_error,
         query_engine::{
             data_types::DataType,
-            query_engine::{modeling_query_engine, query_engine},
+            query_cache::QueryCacheMetadata,
+            query_cancellation::QueryContext,
+            query_engine::{
+                cached_query_engine, modeling_paged_query_engine, modeling_query_engine,
+                paged_query_engine,
+            },
+            query_pagination::QueryPage,
         },
         security::dataset_security::has_dataset_access,
     },
@@ -37,28 +44,103 @@ pub struct RunSqlRequest {
     pub dataset_id: Option<Uuid>,
     pub data_source_id: Option<Uuid>,
     pub sql: String,
+    /// Lets the client cancel the query through `/sql/cancel` while it runs.
+    pub query_id: Option<Uuid>,
+    /// Returns only the first page of this many rows, along with a query handle for fetching the
+    /// rest through `/sql/page`.
+    pub page_size: Option<i64>,
 }
 
 pub async fn run_sql(user: &User, req: RunSqlRequest) -> Result<()> {
-    let run_sql_res =
-        match run_sql_handler(&req.sql, &req.data_source_id, &req.dataset_id, &user.id).await {
-            Ok(res) => res,
-            Err(e) => {
-                tracing::error!("Error running SQL: {}", e);
-                let err = anyhow!("Error running SQL: {}", e);
-                send_sentry_error(&e.to_string(), Some(&user.id));
-                send_error_message(
-                    &user.id.to_string(),
-                    WsRoutes::Sql(SqlRoute::Run),
-                    WsEvent::Sql(SqlEvent::RunSql),
-                    WsErrorCode::InternalServerError,
-                    e.to_string(),
-                    user,
-                )
-                .await?;
-                return Err(err);
-            }
-        };
+    spawn_audit_event(NewAuditEvent::sql_run(
+        user.id,
+        &req.sql,
+        req.dataset_id,
+        req.data_source_id,
+        AuditRequestMetadata::ws(WsRoutes::Sql(SqlRoute::Run)),
+    ));
+
+    if req.page_size.is_some() {
+        return run_paged_sql(user, req).await;
+    }
+
+    let run_sql_res = match run_sql_handler(
+        &req.sql,
+        &req.data_source_id,
+        &req.dataset_id,
+        &user.id,
+        req.query_id,
+    )
+    .await
+    {
+        Ok(res) => res,
+        Err(e) => {
+            tracing::error!("Error running SQL: {}", e);
+            let err = anyhow!("Error running SQL: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            send_error_message(
+                &user.id.to_string(),
+                WsRoutes::Sql(SqlRoute::Run),
+                WsEvent::Sql(SqlEvent::RunSql),
+                WsErrorCode::InternalServerError,
+                e.to_string(),
+                user,
+            )
+            .await?;
+            return Err(err);
+        }
+    };
+
+    let run_sql_message = WsResponseMessage::new(
+        WsRoutes::Sql(SqlRoute::Run),
+        WsEvent::Sql(SqlEvent::RunSql),
+        run_sql_res,
+        None,
+        user,
+        WsSendMethod::SenderOnly,
+    );
+
+    match send_ws_message(&user.id.to_string(), &run_sql_message).await {
+        Ok(_) => (),
+        Err(e) => {
+            tracing::error!("Error sending ws message: {}", e);
+            let err = anyhow!("Error sending ws message: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_paged_sql(user: &User, req: RunSqlRequest) -> Result<()> {
+    let run_sql_res = match run_paged_sql_handler(
+        &req.sql,
+        &req.data_source_id,
+        &req.dataset_id,
+        &user.id,
+        req.page_size,
+        req.query_id,
+    )
+    .await
+    {
+        Ok(res) => res,
+        Err(e) => {
+            tracing::error!("Error running SQL: {}", e);
+            let err = anyhow!("Error running SQL: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            send_error_message(
+                &user.id.to_string(),
+                WsRoutes::Sql(SqlRoute::Run),
+                WsEvent::Sql(SqlEvent::RunSql),
+                WsErrorCode::InternalServerError,
+                e.to_string(),
+                user,
+            )
+            .await?;
+            return Err(err);
+        }
+    };
 
     let run_sql_message = WsResponseMessage::new(
         WsRoutes::Sql(SqlRoute::Run),
@@ -82,16 +164,43 @@ pub async fn run_sql(user: &User, req: RunSqlRequest) -> Result<()> {
     Ok(())
 }
 
+async fn run_paged_sql_handler(
+    sql: &String,
+    data_source_id: &Option<Uuid>,
+    dataset_id: &Option<Uuid>,
+    user_id: &Uuid,
+    page_size: Option<i64>,
+    query_id: Option<Uuid>,
+) -> Result<PagedDataObject> {
+    let page = if let Some(data_source_id) = data_source_id {
+        modeling_paged_query_engine(data_source_id, sql, user_id, page_size, query_id).await?
+    } else if let Some(dataset_id) = dataset_id {
+        check_dataset_sql_access(dataset_id, user_id).await?;
+        paged_query_engine(
+            dataset_id,
+            sql,
+            page_size,
+            &QueryContext::for_user(query_id, user_id),
+        )
+        .await?
+    } else {
+        return Err(anyhow!("No data source or dataset id provided"));
+    };
+
+    paged_data_object(page).await
+}
+
 async fn run_sql_handler(
     sql: &String,
     data_source_id: &Option<Uuid>,
     dataset_id: &Option<Uuid>,
     user_id: &Uuid,
+    query_id: Option<Uuid>,
 ) -> Result<DataObject> {
     if let Some(data_source_id) = data_source_id {
-        return run_data_source_sql_handler(sql, &data_source_id, user_id).await;
+        return run_data_source_sql_handler(sql, &data_source_id, user_id, query_id).await;
     } else if let Some(dataset_id) = dataset_id {
-        return run_dataset_sql_handler(sql, &dataset_id, user_id).await;
+        return run_dataset_sql_handler(sql, &dataset_id, user_id, query_id).await;
     } else {
         return Err(anyhow!("No data source or dataset id provided"));
     }
@@ -101,7 +210,14 @@ async fn run_dataset_sql_handler(
     sql: &String,
     dataset_id: &Uuid,
     user_id: &Uuid,
+    query_id: Option<Uuid>,
 ) -> Result<DataObject> {
+    check_dataset_sql_access(dataset_id, user_id).await?;
+
+    fetch_data(sql, dataset_id, &QueryContext::for_user(query_id, user_id)).await
+}
+
+async fn check_dataset_sql_access(dataset_id: &Uuid, user_id: &Uuid) -> Result<()> {
     let has_dataset_access = match has_dataset_access(user_id, dataset_id).await {
         Ok(has_access) => has_access,
         Err(e) => return Err(e),
@@ -129,27 +245,55 @@ async fn run_dataset_sql_handler(
         .await
         .is_ok();
 
-    let results = if is_org_admin_or_owner || has_dataset_access {
-        match fetch_data(sql, dataset_id).await {
-            Ok(results) => results,
-            Err(e) => return Err(e),
-        }
+    if is_org_admin_or_owner || has_dataset_access {
+        Ok(())
     } else {
-        return Err(anyhow!("User does not have access to this dataset"));
-    };
-
-    Ok(results)
+        Err(anyhow!("User does not have access to this dataset"))
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct DataObject {
     pub data: Vec<IndexMap<String, DataType>>,
     pub data_metadata: DataMetadataJsonBody,
+    pub cache: Option<QueryCacheMetadata>,
 }
 
-pub async fn fetch_data(sql: &String, dataset_id: &Uuid) -> Result<DataObject> {
-    let data = match query_engine(&dataset_id, &sql).await {
-        Ok(data) => data,
+#[derive(Debug, Serialize)]
+pub struct PagedDataObject {
+    pub query_handle: Uuid,
+    pub data: Vec<IndexMap<String, DataType>>,
+    pub data_metadata: DataMetadataJsonBody,
+    pub offset: i64,
+    pub page_size: i64,
+    pub total_rows: Option<i64>,
+    pub has_more: bool,
+    pub next_cursor: Option<String>,
+}
+
+/// The metadata describes the rows on the page, not the whole result.
+pub async fn paged_data_object(page: QueryPage) -> Result<PagedDataObject> {
+    let data_metadata = process_data_metadata(&page.rows).await?;
+
+    Ok(PagedDataObject {
+        query_handle: page.query_handle,
+        data: page.rows,
+        data_metadata,
+        offset: page.offset,
+        page_size: page.page_size,
+        total_rows: page.total_rows,
+        has_more: page.has_more,
+        next_cursor: page.next_cursor,
+    })
+}
+
+pub async fn fetch_data(
+    sql: &String,
+    dataset_id: &Uuid,
+    context: &QueryContext,
+) -> Result<DataObject> {
+    let (data, cache) = match cached_query_engine(&dataset_id, &sql, context).await {
+        Ok(results) => results,
         Err(e) => {
             return Err(anyhow!(e));
         }
@@ -165,6 +309,7 @@ pub async fn fetch_data(sql: &String, dataset_id: &Uuid) -> Result<DataObject> {
     Ok(DataObject {
         data,
         data_metadata,
+        cache: Some(cache),
     })
 }
 
@@ -339,8 +484,9 @@ async fn run_data_source_sql_handler(
     sql: &String,
     data_source_id: &Uuid,
     user_id: &Uuid,
+    query_id: Option<Uuid>,
 ) -> Result<DataObject> {
-    let data = match modeling_query_engine(data_source_id, sql, user_id).await {
+    let data = match modeling_query_engine(data_source_id, sql, user_id, query_id).await {
         Ok(data) => data,
         Err(e) => return Err(e),
     };
@@ -353,6 +499,7 @@ async fn run_data_source_sql_handler(
     let data_object = DataObject {
         data,
         data_metadata,
+        cache: None,
     };
 
     Ok(data_object)
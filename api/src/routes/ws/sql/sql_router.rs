@@ -1,22 +1,34 @@
-
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::database::models::User;
 
-use super::run_sql::run_sql;
+use super::{
+    cancel_sql::cancel_sql,
+    page_sql::{close_page_sql, page_sql},
+    run_sql::run_sql,
+};
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub enum SqlRoute {
     #[serde(rename = "/sql/run")]
     Run,
+    #[serde(rename = "/sql/cancel")]
+    Cancel,
+    #[serde(rename = "/sql/page")]
+    Page,
+    #[serde(rename = "/sql/page/close")]
+    ClosePage,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub enum SqlEvent {
     RunSql,
+    CancelSql,
+    PageSql,
+    ClosePageSql,
 }
 
 pub async fn sql_router(route: SqlRoute, data: Value, user: &User) -> Result<()> {
@@ -29,6 +41,30 @@ pub async fn sql_router(route: SqlRoute, data: Value, user: &User) -> Result<()>
 
             run_sql(user, req).await?;
         }
+        SqlRoute::Cancel => {
+            let req = match serde_json::from_value(data) {
+                Ok(req) => req,
+                Err(e) => return Err(anyhow!("Error parsing request: {}", e)),
+            };
+
+            cancel_sql(user, req).await?;
+        }
+        SqlRoute::Page => {
+            let req = match serde_json::from_value(data) {
+                Ok(req) => req,
+                Err(e) => return Err(anyhow!("Error parsing request: {}", e)),
+            };
+
+            page_sql(user, req).await?;
+        }
+        SqlRoute::ClosePage => {
+            let req = match serde_json::from_value(data) {
+                Ok(req) => req,
+                Err(e) => return Err(anyhow!("Error parsing request: {}", e)),
+            };
+
+            close_page_sql(user, req).await?;
+        }
     };
 
     Ok(())
@@ -38,6 +74,9 @@ impl SqlRoute {
     pub fn from_str(path: &str) -> Result<Self> {
         match path {
             "/sql/run" => Ok(Self::Run),
+            "/sql/cancel" => Ok(Self::Cancel),
+            "/sql/page" => Ok(Self::Page),
+            "/sql/page/close" => Ok(Self::ClosePage),
             _ => Err(anyhow!("Invalid path")),
         }
     }
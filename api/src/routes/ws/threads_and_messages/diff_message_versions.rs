@@ -0,0 +1,107 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    database::models::User,
+    routes::ws::{
+        ws::{WsErrorCode, WsEvent, WsResponseMessage, WsSendMethod},
+        ws_router::WsRoutes,
+        ws_utils::{send_error_message, send_ws_message},
+    },
+    utils::clients::sentry_utils::send_sentry_error,
+};
+
+use super::{
+    message_versions::{
+        diff_message_snapshots, get_message_version, MessageSnapshot, MessageVersionDiff,
+    },
+    messages_utils::get_message_with_permission,
+    threads_router::{ThreadEvent, ThreadRoute},
+};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DiffMessageVersionsRequest {
+    pub message_id: Uuid,
+    pub from_version_id: Uuid,
+    /// Compares against the message as it is now when left out.
+    pub to_version_id: Option<Uuid>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DiffMessageVersionsResponse {
+    pub message_id: Uuid,
+    pub from_version_id: Uuid,
+    pub to_version_id: Option<Uuid>,
+    pub summary: String,
+    pub diff: MessageVersionDiff,
+}
+
+pub async fn diff_message_versions(user: &User, req: DiffMessageVersionsRequest) -> Result<()> {
+    let response = match diff_message_versions_handler(&user.id, req).await {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::error!("Error diffing message versions: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            send_error_message(
+                &user.id.to_string(),
+                WsRoutes::Threads(ThreadRoute::DiffMessageVersions),
+                WsEvent::Threads(ThreadEvent::DiffMessageVersions),
+                WsErrorCode::InternalServerError,
+                "Failed to compare message versions.".to_string(),
+                user,
+            )
+            .await?;
+            return Err(e);
+        }
+    };
+
+    let diff_versions_message = WsResponseMessage::new(
+        WsRoutes::Threads(ThreadRoute::DiffMessageVersions),
+        WsEvent::Threads(ThreadEvent::DiffMessageVersions),
+        response,
+        None,
+        user,
+        WsSendMethod::SenderOnly,
+    );
+
+    match send_ws_message(&user.id.to_string(), &diff_versions_message).await {
+        Ok(_) => (),
+        Err(e) => {
+            let err = anyhow!("Error sending ws message: {}", e);
+            send_sentry_error(&err.to_string(), Some(&user.id));
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+async fn diff_message_versions_handler(
+    user_id: &Uuid,
+    req: DiffMessageVersionsRequest,
+) -> Result<DiffMessageVersionsResponse> {
+    let (message, _) = match get_message_with_permission(&req.message_id, user_id).await {
+        Ok(message) => message,
+        Err(e) => return Err(anyhow!("Error getting message: {}", e)),
+    };
+
+    let from = get_message_version(&req.message_id, &req.from_version_id).await?;
+
+    let to = match req.to_version_id {
+        Some(to_version_id) => MessageSnapshot::from_version(
+            &get_message_version(&req.message_id, &to_version_id).await?,
+        ),
+        None => MessageSnapshot::from_message(&message),
+    };
+
+    let diff = diff_message_snapshots(&MessageSnapshot::from_version(&from), &to);
+
+    Ok(DiffMessageVersionsResponse {
+        message_id: req.message_id,
+        from_version_id: req.from_version_id,
+        to_version_id: req.to_version_id,
+        summary: diff.summary(),
+        diff,
+    })
+}
@@ -138,7 +138,7 @@ async fn fetch_data_handler(
         }
     }
 
-    let data = match query_engine(&dataset_id, &sql).await {
+    let data = match query_engine(&dataset_id, &sql, &user.id).await {
         Ok(data) => data,
         Err(e) => {
             tracing::error!("Unable to query engine: {:?}", e);
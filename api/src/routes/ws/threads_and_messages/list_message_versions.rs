@@ -0,0 +1,155 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, JoinOnDsl, NullableExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    database::{
+        enums::MessageVersionAuthor,
+        lib::get_pg_pool,
+        models::User,
+        schema::{message_versions, users},
+    },
+    routes::ws::{
+        ws::{WsErrorCode, WsEvent, WsResponseMessage, WsSendMethod},
+        ws_router::WsRoutes,
+        ws_utils::{send_error_message, send_ws_message},
+    },
+    utils::clients::sentry_utils::send_sentry_error,
+};
+
+use super::{
+    messages_utils::get_message_with_permission,
+    threads_router::{ThreadEvent, ThreadRoute},
+};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ListMessageVersionsRequest {
+    pub message_id: Uuid,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MessageVersionListItem {
+    pub id: Uuid,
+    pub version_number: i32,
+    pub summary: Option<String>,
+    pub author_type: MessageVersionAuthor,
+    pub created_by_id: Option<Uuid>,
+    pub created_by_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ListMessageVersionsResponse {
+    pub message_id: Uuid,
+    pub versions: Vec<MessageVersionListItem>,
+}
+
+pub async fn list_message_versions(user: &User, req: ListMessageVersionsRequest) -> Result<()> {
+    let response = match list_message_versions_handler(&user.id, &req.message_id).await {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::error!("Error listing message versions: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            send_error_message(
+                &user.id.to_string(),
+                WsRoutes::Threads(ThreadRoute::ListMessageVersions),
+                WsEvent::Threads(ThreadEvent::ListMessageVersions),
+                WsErrorCode::InternalServerError,
+                "Failed to list message versions.".to_string(),
+                user,
+            )
+            .await?;
+            return Err(e);
+        }
+    };
+
+    let list_versions_message = WsResponseMessage::new(
+        WsRoutes::Threads(ThreadRoute::ListMessageVersions),
+        WsEvent::Threads(ThreadEvent::ListMessageVersions),
+        response,
+        None,
+        user,
+        WsSendMethod::SenderOnly,
+    );
+
+    match send_ws_message(&user.id.to_string(), &list_versions_message).await {
+        Ok(_) => (),
+        Err(e) => {
+            let err = anyhow!("Error sending ws message: {}", e);
+            send_sentry_error(&err.to_string(), Some(&user.id));
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+async fn list_message_versions_handler(
+    user_id: &Uuid,
+    message_id: &Uuid,
+) -> Result<ListMessageVersionsResponse> {
+    if let Err(e) = get_message_with_permission(message_id, user_id).await {
+        return Err(anyhow!("Error getting message: {}", e));
+    }
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let version_records = match message_versions::table
+        .left_join(users::table.on(message_versions::created_by.eq(users::id.nullable())))
+        .select((
+            message_versions::id,
+            message_versions::version_number,
+            message_versions::summary,
+            message_versions::author_type,
+            message_versions::created_by,
+            users::name.nullable(),
+            users::email.nullable(),
+            message_versions::created_at,
+        ))
+        .filter(message_versions::message_id.eq(message_id))
+        .filter(message_versions::deleted_at.is_null())
+        .order(message_versions::version_number.desc())
+        .load::<(
+            Uuid,
+            i32,
+            Option<String>,
+            MessageVersionAuthor,
+            Option<Uuid>,
+            Option<String>,
+            Option<String>,
+            DateTime<Utc>,
+        )>(&mut conn)
+        .await
+    {
+        Ok(version_records) => version_records,
+        Err(e) => return Err(anyhow!("Error getting message versions: {}", e)),
+    };
+
+    let versions = version_records
+        .into_iter()
+        .map(
+            |(id, version_number, summary, author_type, created_by_id, name, email, created_at)| {
+                MessageVersionListItem {
+                    id,
+                    version_number,
+                    summary,
+                    author_type,
+                    created_by_id,
+                    created_by_name: name.or(email),
+                    created_at,
+                }
+            },
+        )
+        .collect();
+
+    Ok(ListMessageVersionsResponse {
+        message_id: *message_id,
+        versions,
+    })
+}
@@ -0,0 +1,461 @@
+use std::collections::BTreeSet;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use diesel::{insert_into, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::database::{
+    enums::MessageVersionAuthor,
+    lib::get_pg_pool,
+    models::{Message, MessageVersion},
+    schema::message_versions,
+};
+
+/// The parts of a message that are versioned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageSnapshot {
+    pub code: Option<String>,
+    pub chart_config: Option<Value>,
+    pub title: Option<String>,
+    pub time_frame: Option<String>,
+}
+
+impl MessageSnapshot {
+    pub fn from_message(message: &Message) -> Self {
+        MessageSnapshot {
+            code: message.code.clone(),
+            chart_config: message.chart_config.clone(),
+            title: message.title.clone(),
+            time_frame: message.time_frame.clone(),
+        }
+    }
+
+    pub fn from_version(version: &MessageVersion) -> Self {
+        MessageSnapshot {
+            code: version.code.clone(),
+            chart_config: version.chart_config.clone(),
+            title: version.title.clone(),
+            time_frame: version.time_frame.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SqlLineChange {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SqlDiffLine {
+    pub change: SqlLineChange,
+    pub line: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChartConfigChange {
+    /// Dot separated path to the changed key, e.g. `barAndLineAxis.x`.
+    pub path: String,
+    pub from: Option<Value>,
+    pub to: Option<Value>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct MessageVersionDiff {
+    /// Line by line diff of the SQL. Empty when the SQL did not change.
+    pub sql: Vec<SqlDiffLine>,
+    pub title: Option<FieldChange>,
+    pub time_frame: Option<FieldChange>,
+    pub chart_config: Vec<ChartConfigChange>,
+}
+
+impl MessageVersionDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == MessageVersionDiff::default()
+    }
+
+    pub fn summary(&self) -> String {
+        let mut changes = Vec::new();
+
+        if !self.sql.is_empty() {
+            changes.push("SQL");
+        }
+
+        if !self.chart_config.is_empty() {
+            changes.push("chart config");
+        }
+
+        if self.title.is_some() {
+            changes.push("title");
+        }
+
+        if self.time_frame.is_some() {
+            changes.push("time frame");
+        }
+
+        if changes.is_empty() {
+            return "No changes".to_string();
+        }
+
+        format!("Changed {}", changes.join(", "))
+    }
+}
+
+/// The changes that turn `from` into `to`.
+pub fn diff_message_snapshots(from: &MessageSnapshot, to: &MessageSnapshot) -> MessageVersionDiff {
+    let sql = if from.code != to.code {
+        diff_lines(
+            from.code.as_deref().unwrap_or_default(),
+            to.code.as_deref().unwrap_or_default(),
+        )
+    } else {
+        Vec::new()
+    };
+
+    let mut chart_config = Vec::new();
+    diff_values(
+        "",
+        from.chart_config.as_ref(),
+        to.chart_config.as_ref(),
+        &mut chart_config,
+    );
+
+    MessageVersionDiff {
+        sql,
+        title: field_change(&from.title, &to.title),
+        time_frame: field_change(&from.time_frame, &to.time_frame),
+        chart_config,
+    }
+}
+
+fn field_change(from: &Option<String>, to: &Option<String>) -> Option<FieldChange> {
+    if from == to {
+        return None;
+    }
+
+    Some(FieldChange {
+        from: from.clone(),
+        to: to.clone(),
+    })
+}
+
+/// Walks both configs and reports every key whose value differs. Arrays are compared as a whole.
+fn diff_values(
+    path: &str,
+    from: Option<&Value>,
+    to: Option<&Value>,
+    changes: &mut Vec<ChartConfigChange>,
+) {
+    if from == to {
+        return;
+    }
+
+    if let (Some(Value::Object(from_object)), Some(Value::Object(to_object))) = (from, to) {
+        let keys: BTreeSet<&String> = from_object.keys().chain(to_object.keys()).collect();
+
+        for key in keys {
+            let key_path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", path, key)
+            };
+
+            diff_values(&key_path, from_object.get(key), to_object.get(key), changes);
+        }
+
+        return;
+    }
+
+    changes.push(ChartConfigChange {
+        path: path.to_string(),
+        from: from.cloned(),
+        to: to.cloned(),
+    });
+}
+
+/// Longest common subsequence diff over the lines of two SQL statements.
+fn diff_lines(from: &str, to: &str) -> Vec<SqlDiffLine> {
+    let from_lines: Vec<&str> = from.lines().collect();
+    let to_lines: Vec<&str> = to.lines().collect();
+
+    let mut lengths = vec![vec![0usize; to_lines.len() + 1]; from_lines.len() + 1];
+
+    for i in (0..from_lines.len()).rev() {
+        for j in (0..to_lines.len()).rev() {
+            lengths[i][j] = if from_lines[i] == to_lines[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < from_lines.len() && j < to_lines.len() {
+        if from_lines[i] == to_lines[j] {
+            diff.push(sql_diff_line(SqlLineChange::Unchanged, from_lines[i]));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            diff.push(sql_diff_line(SqlLineChange::Removed, from_lines[i]));
+            i += 1;
+        } else {
+            diff.push(sql_diff_line(SqlLineChange::Added, to_lines[j]));
+            j += 1;
+        }
+    }
+
+    for line in &from_lines[i..] {
+        diff.push(sql_diff_line(SqlLineChange::Removed, line));
+    }
+
+    for line in &to_lines[j..] {
+        diff.push(sql_diff_line(SqlLineChange::Added, line));
+    }
+
+    diff
+}
+
+fn sql_diff_line(change: SqlLineChange, line: &str) -> SqlDiffLine {
+    SqlDiffLine {
+        change,
+        line: line.to_string(),
+    }
+}
+
+pub async fn get_message_version(message_id: &Uuid, version_id: &Uuid) -> Result<MessageVersion> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match message_versions::table
+        .filter(message_versions::id.eq(version_id))
+        .filter(message_versions::message_id.eq(message_id))
+        .filter(message_versions::deleted_at.is_null())
+        .first::<MessageVersion>(&mut conn)
+        .await
+    {
+        Ok(version) => Ok(version),
+        Err(diesel::NotFound) => Err(anyhow!("Message version not found")),
+        Err(e) => Err(anyhow!("Error getting message version: {}", e)),
+    }
+}
+
+async fn get_latest_message_version(message_id: &Uuid) -> Result<Option<MessageVersion>> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match message_versions::table
+        .filter(message_versions::message_id.eq(message_id))
+        .filter(message_versions::deleted_at.is_null())
+        .order(message_versions::version_number.desc())
+        .first::<MessageVersion>(&mut conn)
+        .await
+    {
+        Ok(version) => Ok(Some(version)),
+        Err(diesel::NotFound) => Ok(None),
+        Err(e) => Err(anyhow!("Error getting latest message version: {}", e)),
+    }
+}
+
+/// Messages answered before versioning existed have no history. Their current state is recorded
+/// as an agent version before a user changes it, so the change can be audited and reverted.
+pub async fn ensure_message_baseline_version(message: &Message) -> Result<()> {
+    if get_latest_message_version(&message.id).await?.is_some() {
+        return Ok(());
+    }
+
+    insert_message_version(
+        &message.id,
+        1,
+        MessageSnapshot::from_message(message),
+        MessageVersionAuthor::Agent,
+        Some(message.sent_by),
+        "Initial version".to_string(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Appends a version with `snapshot` if it differs from the latest version of the message.
+/// Without a `summary`, one is generated from the changes.
+pub async fn record_message_version(
+    message_id: &Uuid,
+    snapshot: MessageSnapshot,
+    author_type: MessageVersionAuthor,
+    created_by: Option<Uuid>,
+    summary: Option<String>,
+) -> Result<Option<MessageVersion>> {
+    let latest = get_latest_message_version(message_id).await?;
+
+    let (version_number, summary) = match &latest {
+        Some(latest) => {
+            let diff = diff_message_snapshots(&MessageSnapshot::from_version(latest), &snapshot);
+
+            if diff.is_empty() {
+                return Ok(None);
+            }
+
+            (
+                latest.version_number + 1,
+                summary.unwrap_or_else(|| diff.summary()),
+            )
+        }
+        None => (1, summary.unwrap_or_else(|| "Initial version".to_string())),
+    };
+
+    let version = insert_message_version(
+        message_id,
+        version_number,
+        snapshot,
+        author_type,
+        created_by,
+        summary,
+    )
+    .await?;
+
+    Ok(Some(version))
+}
+
+async fn insert_message_version(
+    message_id: &Uuid,
+    version_number: i32,
+    snapshot: MessageSnapshot,
+    author_type: MessageVersionAuthor,
+    created_by: Option<Uuid>,
+    summary: String,
+) -> Result<MessageVersion> {
+    let version = MessageVersion {
+        id: Uuid::new_v4(),
+        message_id: *message_id,
+        version_number,
+        code: snapshot.code,
+        chart_config: snapshot.chart_config,
+        title: snapshot.title,
+        time_frame: snapshot.time_frame,
+        author_type,
+        created_by,
+        summary: Some(summary),
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        deleted_at: None,
+    };
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match insert_into(message_versions::table)
+        .values(&version)
+        .execute(&mut conn)
+        .await
+    {
+        Ok(_) => Ok(version),
+        Err(e) => Err(anyhow!("Error inserting message version: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn snapshot(code: &str, chart_config: Value) -> MessageSnapshot {
+        MessageSnapshot {
+            code: Some(code.to_string()),
+            chart_config: Some(chart_config),
+            title: Some("Revenue by month".to_string()),
+            time_frame: Some("Last 12 months".to_string()),
+        }
+    }
+
+    #[test]
+    fn diffs_sql_line_by_line() {
+        let from = snapshot(
+            "SELECT month, SUM(revenue)\nFROM orders\nGROUP BY month",
+            json!({}),
+        );
+        let to = snapshot(
+            "SELECT month, SUM(revenue)\nFROM orders\nWHERE status = 'paid'\nGROUP BY month",
+            json!({}),
+        );
+
+        let diff = diff_message_snapshots(&from, &to);
+
+        let changed: Vec<(SqlLineChange, &str)> = diff
+            .sql
+            .iter()
+            .filter(|line| line.change != SqlLineChange::Unchanged)
+            .map(|line| (line.change, line.line.as_str()))
+            .collect();
+
+        assert_eq!(
+            changed,
+            vec![(SqlLineChange::Added, "WHERE status = 'paid'")]
+        );
+        assert_eq!(diff.sql.len(), 4);
+        assert_eq!(diff.summary(), "Changed SQL");
+    }
+
+    #[test]
+    fn diffs_chart_config_by_key() {
+        let from = snapshot(
+            "SELECT 1",
+            json!({ "selectedChartType": "bar", "axis": { "x": ["month"], "y": ["revenue"] } }),
+        );
+        let mut to = snapshot(
+            "SELECT 1",
+            json!({ "selectedChartType": "line", "axis": { "x": ["month"], "y": ["revenue"] }, "showLegend": true }),
+        );
+        to.title = Some("Paid revenue by month".to_string());
+
+        let diff = diff_message_snapshots(&from, &to);
+
+        assert!(diff.sql.is_empty());
+        assert_eq!(
+            diff.chart_config,
+            vec![
+                ChartConfigChange {
+                    path: "selectedChartType".to_string(),
+                    from: Some(json!("bar")),
+                    to: Some(json!("line")),
+                },
+                ChartConfigChange {
+                    path: "showLegend".to_string(),
+                    from: None,
+                    to: Some(json!(true)),
+                },
+            ]
+        );
+        assert_eq!(diff.summary(), "Changed chart config, title");
+    }
+
+    #[test]
+    fn identical_snapshots_have_no_changes() {
+        let from = snapshot("SELECT 1", json!({ "selectedChartType": "bar" }));
+
+        let diff = diff_message_snapshots(&from, &from.clone());
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.summary(), "No changes");
+    }
+}
@@ -1,10 +1,14 @@
 mod delete_thread;
+mod diff_message_versions;
 mod duplicate_thread;
 mod get_message_data;
 mod get_thread;
+mod list_message_versions;
 mod list_threads;
+mod message_versions;
 mod messages_utils;
-mod post_thread;
+pub mod post_thread;
+mod revert_message_version;
 mod thread_utils;
 pub mod threads_router;
 mod unsubscribe;
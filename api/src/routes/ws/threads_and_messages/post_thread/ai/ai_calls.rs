@@ -18,6 +18,7 @@ use crate::{
         clients::ai::{
             langfuse::PromptName,
             llm_router::{llm_chat, llm_chat_stream, LlmMessage, LlmModel, LlmRole},
+            llm_usage::LlmUsageScope,
             openai::OpenAiChatModel,
         },
         query_engine::data_types::DataType,
@@ -97,6 +98,7 @@ Output in json with the key of 'dataset' and the value of the dataset name. Your
         thread_id,
         &user.id,
         PromptName::SelectDataset,
+        LlmUsageScope::thread(*thread_id),
     )
     .await
     {
@@ -184,6 +186,7 @@ Output in markdown (```sql```) format. Make sure to close the SQL statement with
         thread_id,
         user_id,
         PromptName::GenerateSql,
+        LlmUsageScope::thread(*thread_id),
     )
     .await
     {
@@ -257,6 +260,7 @@ SQL should be escaped with backticks (```sql```) like in markdown format.",
         thread_id,
         user_id,
         PromptName::FixSql,
+        LlmUsageScope::thread(*thread_id),
     )
     .await
     {
@@ -269,14 +273,18 @@ SQL should be escaped with backticks (```sql```) like in markdown format.",
 
 pub fn route_to_data_source_instructions(data_source_type: &DataSourceType) -> &'static str {
     let instructions = match data_source_type {
+        DataSourceType::Trino => TRINO_INSTRUCTIONS,
         DataSourceType::BigQuery => BIGQUERY_INSTRUCTIONS,
+        DataSourceType::ClickHouse => CLICKHOUSE_INSTRUCTIONS,
         DataSourceType::Databricks => DATABRICKS_INSTRUCTIONS,
+        DataSourceType::DuckDb => DUCKDB_INSTRUCTIONS,
         DataSourceType::MySql => MYSQL_INSTRUCTIONS,
         DataSourceType::Mariadb => MARIADB_INSTRUCTIONS,
         DataSourceType::Postgres => POSTGRES_INSTRUCTIONS,
         DataSourceType::Redshift => REDSHIFT_INSTRUCTIONS,
         DataSourceType::Snowflake => SNOWFLAKE_INSTRUCTIONS,
         DataSourceType::SqlServer => SQLSERVER_INSTRUCTIONS,
+        DataSourceType::StarRocks => STARROCKS_INSTRUCTIONS,
         DataSourceType::Supabase => SUPABASE_INSTRUCTIONS,
     };
 
@@ -341,6 +349,7 @@ No longer than 2 sentences.",
         thread_id,
         user_id,
         PromptName::DataSummary,
+        LlmUsageScope::thread(*thread_id),
     )
     .await
     {
@@ -392,6 +401,7 @@ No longer than 2 sentences.",
         thread_id,
         user_id,
         PromptName::NoDataReturnedResponse,
+        LlmUsageScope::thread(*thread_id),
     )
     .await
     {
@@ -444,6 +454,7 @@ Speak casually.
         thread_id,
         user_id,
         PromptName::DataExplanation,
+        LlmUsageScope::thread(*thread_id),
     )
     .await
     {
@@ -494,6 +505,7 @@ The title should contain no special characters.",
         thread_id,
         user_id,
         PromptName::MetricTitle,
+        LlmUsageScope::thread(*thread_id),
     )
     .await
     {
@@ -544,6 +556,7 @@ The question should contain no special characters.",
         thread_id,
         user_id,
         PromptName::SummaryQuestion,
+        LlmUsageScope::thread(*thread_id),
     )
     .await
     {
@@ -611,6 +624,7 @@ Just output the time frame in the format I mentioned above. Do not wrap in any s
         thread_id,
         user_id,
         PromptName::TimeFrame,
+        LlmUsageScope::thread(*thread_id),
     )
     .await
     {
@@ -680,6 +694,7 @@ Output the results in a json object with 'terms' as the key and an array contain
         thread_id,
         user_id,
         PromptName::SelectTerm,
+        LlmUsageScope::thread(*thread_id),
     )
     .await
     {
@@ -7,3 +7,7 @@ pub const REDSHIFT_INSTRUCTIONS: &'static str = "Use Redshift syntax";
 pub const DATABRICKS_INSTRUCTIONS: &'static str = "Use Databricks syntax";
 pub const BIGQUERY_INSTRUCTIONS: &'static str = "Use BigQuery syntax";
 pub const SUPABASE_INSTRUCTIONS: &'static str = "Use Supabase syntax";
+pub const DUCKDB_INSTRUCTIONS: &str = "Use DuckDB syntax";
+pub const CLICKHOUSE_INSTRUCTIONS: &str = "Use ClickHouse syntax";
+pub const TRINO_INSTRUCTIONS: &str = "Use Trino syntax";
+pub const STARROCKS_INSTRUCTIONS: &str = "Use StarRocks syntax";
@@ -1,6 +1,6 @@
 use crate::{
     database::{
-        enums::{AssetPermissionRole, AssetType, IdentityType},
+        enums::{AssetPermissionRole, AssetType, IdentityType, MessageVersionAuthor},
         lib::{get_pg_pool, get_sqlx_pool, ContextJsonBody, MessageResponses},
         models::{AssetPermission, DataSource, Dataset, DatasetColumn},
         schema::{
@@ -11,6 +11,7 @@ use crate::{
     },
     routes::ws::{
         threads_and_messages::{
+            message_versions::{record_message_version, MessageSnapshot},
             messages_utils::MessageDraftState,
             thread_utils::{
                 check_if_thread_saved, get_thread_state_by_id, MessageWithUserInfo, ThreadState,
@@ -25,19 +26,23 @@ use crate::{
             Thoughts,
         },
         clients::{
-            ai::embedding_router::embedding_router,
+            ai::{embedding_router::embedding_router, llm_usage::LlmUsageScope},
             sentry_utils::send_sentry_error,
             typesense::{self, CollectionName, SearchRequestObject, StoredValueDocument},
         },
+        query_engine::{
+            column_level_security::{get_user_column_restrictions, ColumnRestriction},
+            row_level_security::{
+                get_template_user, get_user_identities, get_user_row_restricted_dataset_ids,
+                has_user_template, render_user_template,
+            },
+        },
+        rerank_engine::rerank_engine::{rerank_documents, RERANK_TOP_N},
         user::user_info::get_user_organization_id,
     },
 };
 use anyhow::{anyhow, Result};
 use chrono::Utc;
-use cohere_rust::{
-    api::rerank::{ReRankModel, ReRankRequest},
-    Cohere,
-};
 use diesel::{
     insert_into, update, upsert::excluded, BoolExpressionMethods, ExpressionMethods, JoinOnDsl,
     NullableExpressionMethods, QueryDsl, SelectableHelper,
@@ -191,13 +196,20 @@ pub async fn post_thread(
         }
     };
 
-    let reranked_datasets_with_metadata =
-        match rerank_datasets(&req.prompt, datasets_with_metadata).await {
-            Ok(reranked_datasets_with_metadata) => reranked_datasets_with_metadata,
-            Err(e) => {
-                return Err(anyhow!("Error reranking datasets: {}", e));
-            }
-        };
+    let reranked_datasets_with_metadata = match rerank_datasets(
+        &user.id,
+        &thread.thread.id,
+        &message.id,
+        &req.prompt,
+        datasets_with_metadata,
+    )
+    .await
+    {
+        Ok(reranked_datasets_with_metadata) => reranked_datasets_with_metadata,
+        Err(e) => {
+            return Err(anyhow!("Error reranking datasets: {}", e));
+        }
+    };
 
     let dataset_ids = reranked_datasets_with_metadata
         .iter()
@@ -248,6 +260,18 @@ pub async fn post_thread(
         }
     };
 
+    // Values of hidden or masked columns, or sampled from rows the user can't see, would give away
+    // what the column and row policies protect.
+    let relevant_values = relevant_values
+        .into_iter()
+        .filter(|value| {
+            reranked_datasets_with_metadata.iter().any(|d| {
+                d.dataset.id == value.dataset_id
+                    && d.column_values_visible(&value.dataset_column_id)
+            })
+        })
+        .collect::<Vec<StoredValueDocument>>();
+
     let data_analyst_options = DataAnalystAgentOptions {
         input: req.prompt.clone(),
         message_history: assemble_message_history(&thread),
@@ -257,6 +281,7 @@ pub async fn post_thread(
         relevant_values,
         thread_id: thread.thread.id,
         message_id: message.id,
+        user_id: user.id,
     };
 
     let result = match data_analyst_agent(data_analyst_options).await {
@@ -707,6 +732,16 @@ pub async fn get_user_datasets_with_metadata(user_id: &Uuid) -> Result<Vec<Datas
         Err(e) => return Err(anyhow!("Unable to get datasets from database: {}", e)),
     };
 
+    let dataset_ids = dataset_records
+        .iter()
+        .map(|(dataset, _)| dataset.id)
+        .collect::<Vec<Uuid>>();
+
+    let identities = get_user_identities(user_id).await?;
+    let column_restrictions = get_user_column_restrictions(&identities, &dataset_ids).await?;
+    let row_restricted_dataset_ids =
+        get_user_row_restricted_dataset_ids(user_id, &identities, &dataset_ids).await?;
+
     let mut datasets_with_metadata = Vec::new();
     let mut column_fetch_tasks = Vec::new();
 
@@ -736,13 +771,30 @@ pub async fn get_user_datasets_with_metadata(user_id: &Uuid) -> Result<Vec<Datas
     for task in column_fetch_tasks {
         match task.await {
             Ok(Ok((dataset, data_source, columns))) => {
-                let dataset_ddl = create_dataset_ddl(&dataset, &columns);
+                // Hidden columns are left out of the DDL so the LLM never writes SQL against them.
+                let columns = columns
+                    .into_iter()
+                    .filter(|c| column_restrictions.get(&c.id) != Some(&ColumnRestriction::Hidden))
+                    .collect::<Vec<DatasetColumn>>();
+
+                let masked_column_ids = columns
+                    .iter()
+                    .filter(|c| column_restrictions.contains_key(&c.id))
+                    .map(|c| c.id)
+                    .collect::<Vec<Uuid>>();
+
+                let dataset_ddl = create_dataset_ddl(&dataset, &columns, &masked_column_ids);
+
+                let rows_restricted = row_restricted_dataset_ids.contains(&dataset.id)
+                    || has_user_template(&dataset.definition);
 
                 datasets_with_metadata.push(DatasetWithMetadata {
                     dataset,
                     columns,
                     data_source,
                     dataset_ddl,
+                    masked_column_ids,
+                    rows_restricted,
                 });
             }
             Ok(Err(e)) => return Err(anyhow!("Error fetching columns: {}", e)),
@@ -753,7 +805,11 @@ pub async fn get_user_datasets_with_metadata(user_id: &Uuid) -> Result<Vec<Datas
     Ok(datasets_with_metadata)
 }
 
-fn create_dataset_ddl(dataset: &Dataset, dataset_columns: &Vec<DatasetColumn>) -> String {
+fn create_dataset_ddl(
+    dataset: &Dataset,
+    dataset_columns: &Vec<DatasetColumn>,
+    masked_column_ids: &[Uuid],
+) -> String {
     let mut ddl = String::new();
 
     // Add header with table name and description
@@ -787,6 +843,10 @@ fn create_dataset_ddl(dataset: &Dataset, dataset_columns: &Vec<DatasetColumn>) -
         if let Some(description) = &column.description {
             ddl.push_str(&format!("        -- {}\n", description));
         }
+
+        if masked_column_ids.contains(&column.id) {
+            ddl.push_str("        -- Values are masked, so avoid filtering or joining on them\n");
+        }
     }
 
     ddl.push_str("    );");
@@ -1125,6 +1185,36 @@ limit
         });
     }
 
+    // Snippets can refer to the user's attributes, so they are only given once filled in.
+    let term_ids = terms.iter().map(|term| term.id).collect::<Vec<Uuid>>();
+
+    let mut pg_conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Unable to get connection from pool: {}", e)),
+    };
+
+    let snippets = match terms::table
+        .filter(terms::id.eq_any(&term_ids))
+        .filter(terms::sql_snippet.is_not_null())
+        .select((terms::id, terms::sql_snippet.assume_not_null()))
+        .load::<(Uuid, String)>(&mut pg_conn)
+        .await
+    {
+        Ok(snippets) => snippets,
+        Err(e) => return Err(anyhow!("Error getting term sql snippets: {}", e)),
+    };
+
+    if !snippets.is_empty() {
+        let template_user = get_template_user(user_id, None).await?;
+
+        for term in terms.iter_mut() {
+            term.sql_snippet = snippets
+                .iter()
+                .find(|(term_id, _)| *term_id == term.id)
+                .and_then(|(_, snippet)| render_user_template(snippet, &template_user));
+        }
+    }
+
     Ok(terms)
 }
 
@@ -1483,8 +1573,24 @@ async fn update_thread_and_message(
             Err(e) => {
                 tracing::error!("Unable to update message: {:?}", e);
                 send_sentry_error(&e.to_string(), None);
+                return;
             }
         }
+
+        drop(conn);
+
+        if let Err(e) = record_message_version(
+            &update_message.id,
+            MessageSnapshot::from_message(&update_message),
+            MessageVersionAuthor::Agent,
+            Some(update_message.sent_by),
+            None,
+        )
+        .await
+        {
+            tracing::error!("Unable to record message version: {:?}", e);
+            send_sentry_error(&e.to_string(), None);
+        }
     });
 
     let thread_id = thread.thread.id.clone();
@@ -1543,6 +1649,9 @@ async fn update_thread_and_message(
 }
 
 async fn rerank_datasets(
+    user_id: &Uuid,
+    thread_id: &Uuid,
+    message_id: &Uuid,
     input: &String,
     datasets: Vec<DatasetWithMetadata>,
 ) -> Result<Vec<DatasetWithMetadata>> {
@@ -1551,28 +1660,20 @@ async fn rerank_datasets(
         .map(|d| d.dataset_ddl.clone())
         .collect::<Vec<String>>();
 
-    let co = Cohere::default();
-
-    let request = ReRankRequest {
-        query: input,
-        documents: &dataset_strings,
-        model: ReRankModel::EnglishV3,
-        top_n: Some(20),
-        max_chunks_per_doc: None,
-    };
-
-    let response = match co.rerank(&request).await {
-        Ok(res) => res,
-        Err(e) => {
-            tracing::error!("Error reranking datasets: {:?}", e);
-            return Ok(datasets);
-        }
-    };
+    let ranking = rerank_documents(
+        input,
+        &dataset_strings,
+        RERANK_TOP_N,
+        user_id,
+        thread_id,
+        LlmUsageScope::message(*thread_id, *message_id),
+    )
+    .await;
 
     let mut reranked_datasets = vec![];
 
-    for result in response {
-        reranked_datasets.push(datasets[result.index as usize].clone());
+    for index in ranking {
+        reranked_datasets.push(datasets[index].clone());
     }
 
     Ok(reranked_datasets)
@@ -0,0 +1,165 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use diesel::{update, ExpressionMethods};
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    database::{
+        enums::{AssetPermissionRole, MessageVersionAuthor},
+        lib::get_pg_pool,
+        models::User,
+        schema::messages,
+    },
+    routes::ws::{
+        ws::{SubscriptionRwLock, WsErrorCode, WsEvent, WsResponseMessage, WsSendMethod},
+        ws_router::WsRoutes,
+        ws_utils::{get_key_value, send_error_message, send_ws_message, subscribe_to_stream},
+    },
+    utils::clients::sentry_utils::send_sentry_error,
+};
+
+use super::{
+    message_versions::{
+        ensure_message_baseline_version, get_message_version, record_message_version,
+        MessageSnapshot,
+    },
+    messages_utils::get_message_with_permission,
+    thread_utils::get_thread_state_by_id,
+    threads_router::{ThreadEvent, ThreadRoute},
+};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RevertMessageVersionRequest {
+    pub message_id: Uuid,
+    pub version_id: Uuid,
+}
+
+/// Puts the SQL, chart config, title and time frame of an earlier version back on the message.
+/// A revert is an explicit decision about the saved metric, so it is written straight to the
+/// message and replaces any pending draft of it. The revert is recorded as a new version.
+pub async fn revert_message_version(
+    subscriptions: &Arc<SubscriptionRwLock>,
+    user_group: &String,
+    user: &User,
+    req: RevertMessageVersionRequest,
+) -> Result<()> {
+    let thread_id = match revert_message_version_handler(user, &req).await {
+        Ok(thread_id) => thread_id,
+        Err(e) => {
+            tracing::error!("Error reverting message version: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            send_error_message(
+                &user.id.to_string(),
+                WsRoutes::Threads(ThreadRoute::RevertMessageVersion),
+                WsEvent::Threads(ThreadEvent::UpdateThreadState),
+                WsErrorCode::InternalServerError,
+                "Failed to revert message.".to_string(),
+                user,
+            )
+            .await?;
+            return Err(e);
+        }
+    };
+
+    let subscription = format!("thread:{}", thread_id);
+
+    match subscribe_to_stream(subscriptions, &subscription, user_group, &user.id).await {
+        Ok(_) => (),
+        Err(e) => {
+            let err = anyhow!("Error subscribing to stream: {}", e);
+            send_sentry_error(&err.to_string(), Some(&user.id));
+            return Err(err);
+        }
+    }
+
+    let draft_session_id = match get_key_value(&format!("draft:thread:{}", thread_id)).await {
+        Ok(value) => value.and_then(|value| Uuid::parse_str(&value).ok()),
+        Err(e) => {
+            tracing::error!("Error getting draft session id: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+            return Err(e);
+        }
+    };
+
+    let thread = match get_thread_state_by_id(&user.id, &thread_id, &draft_session_id).await {
+        Ok(thread) => thread,
+        Err(e) => return Err(anyhow!("Error getting thread: {}", e)),
+    };
+
+    let revert_message = WsResponseMessage::new(
+        WsRoutes::Threads(ThreadRoute::RevertMessageVersion),
+        WsEvent::Threads(ThreadEvent::UpdateThreadState),
+        vec![thread],
+        None,
+        user,
+        WsSendMethod::All,
+    );
+
+    match send_ws_message(&subscription, &revert_message).await {
+        Ok(_) => {}
+        Err(e) => {
+            let err = anyhow!("Error sending message to pubsub: {}", e);
+            send_sentry_error(&err.to_string(), Some(&user.id));
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+async fn revert_message_version_handler(
+    user: &User,
+    req: &RevertMessageVersionRequest,
+) -> Result<Uuid> {
+    let (message, user_role) = match get_message_with_permission(&req.message_id, &user.id).await {
+        Ok(message) => message,
+        Err(e) => return Err(anyhow!("Error getting message: {}", e)),
+    };
+
+    if user_role == AssetPermissionRole::Viewer {
+        return Err(anyhow!("User does not have permission to revert message."));
+    }
+
+    let version = get_message_version(&req.message_id, &req.version_id).await?;
+
+    ensure_message_baseline_version(&message).await?;
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match update(messages::table)
+        .filter(messages::id.eq(&req.message_id))
+        .set((
+            messages::code.eq(&version.code),
+            messages::chart_config.eq(&version.chart_config),
+            messages::title.eq(&version.title),
+            messages::time_frame.eq(&version.time_frame),
+            messages::draft_state.eq(None::<Value>),
+            messages::updated_at.eq(Utc::now()),
+        ))
+        .execute(&mut conn)
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => return Err(anyhow!("Error reverting message: {}", e)),
+    };
+
+    drop(conn);
+
+    record_message_version(
+        &req.message_id,
+        MessageSnapshot::from_version(&version),
+        MessageVersionAuthor::User,
+        Some(user.id),
+        Some(format!("Reverted to version {}", version.version_number)),
+    )
+    .await?;
+
+    Ok(message.thread_id)
+}
@@ -27,7 +27,10 @@ use crate::{
     routes::ws::threads_and_messages::messages_utils::MessageDraftState,
     utils::{
         clients::{sentry_utils::send_sentry_error, supabase_vault::read_secret},
-        query_engine::{data_types::DataType, query_engine::query_engine},
+        query_engine::{
+            data_types::DataType, query_cache::QueryCacheMetadata,
+            query_cancellation::QueryContext, query_engine::cached_query_engine,
+        },
         sharing::asset_sharing::{
             get_asset_collections, get_asset_sharing_info, CollectionNameAndId,
             IndividualPermission, TeamPermissions,
@@ -853,11 +856,18 @@ async fn get_thread_dashboards(thread_id: Arc<Uuid>) -> Result<Vec<DashboardName
 pub struct DataObject {
     pub data: Vec<IndexMap<String, DataType>>,
     pub data_metadata: DataMetadataJsonBody,
+    pub cache: Option<QueryCacheMetadata>,
 }
 
-pub async fn fetch_data(sql: &String, dataset_id: &Uuid) -> Result<DataObject> {
-    let data = match query_engine(&dataset_id, &sql).await {
-        Ok(data) => data,
+pub async fn fetch_data(sql: &String, dataset_id: &Uuid, user_id: &Uuid) -> Result<DataObject> {
+    let (data, cache) = match cached_query_engine(
+        &dataset_id,
+        &sql,
+        &QueryContext::for_user(None, user_id),
+    )
+    .await
+    {
+        Ok(results) => results,
         Err(e) => {
             return Err(anyhow!("Unable to query engine: {}", e));
         }
@@ -871,6 +881,7 @@ pub async fn fetch_data(sql: &String, dataset_id: &Uuid) -> Result<DataObject> {
     Ok(DataObject {
         data,
         data_metadata,
+        cache: Some(cache),
     })
 }
 
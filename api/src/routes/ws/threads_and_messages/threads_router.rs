@@ -10,10 +10,11 @@ use crate::{
 };
 
 use super::{
-    delete_thread::delete_thread, duplicate_thread::duplicate_thread,
-    get_message_data::get_message_data, get_thread::get_thread, list_threads::list_threads,
-    post_thread::post_thread::post_thread, update_message::update_message,
-    update_thread::update_thread,
+    delete_thread::delete_thread, diff_message_versions::diff_message_versions,
+    duplicate_thread::duplicate_thread, get_message_data::get_message_data, get_thread::get_thread,
+    list_message_versions::list_message_versions, list_threads::list_threads,
+    post_thread::post_thread::post_thread, revert_message_version::revert_message_version,
+    update_message::update_message, update_thread::update_thread,
 };
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -36,6 +37,12 @@ pub enum ThreadRoute {
     MessageData,
     #[serde(rename = "/threads/duplicate")]
     DuplicateThread,
+    #[serde(rename = "/threads/messages/versions/list")]
+    ListMessageVersions,
+    #[serde(rename = "/threads/messages/versions/diff")]
+    DiffMessageVersions,
+    #[serde(rename = "/threads/messages/versions/revert")]
+    RevertMessageVersion,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -70,6 +77,8 @@ pub enum ThreadEvent {
     Unsubscribed,
     DuplicateThread,
     SqlEvaluation,
+    ListMessageVersions,
+    DiffMessageVersions,
 }
 
 pub async fn threads_router(
@@ -125,6 +134,21 @@ pub async fn threads_router(
 
             duplicate_thread(subscriptions, user_group, user, req).await?;
         }
+        ThreadRoute::ListMessageVersions => {
+            let req = serde_json::from_value(data)?;
+
+            list_message_versions(user, req).await?;
+        }
+        ThreadRoute::DiffMessageVersions => {
+            let req = serde_json::from_value(data)?;
+
+            diff_message_versions(user, req).await?;
+        }
+        ThreadRoute::RevertMessageVersion => {
+            let req = serde_json::from_value(data)?;
+
+            revert_message_version(subscriptions, user_group, user, req).await?;
+        }
     };
 
     Ok(())
@@ -142,6 +166,9 @@ impl ThreadRoute {
             "/threads/duplicate" => Ok(Self::DuplicateThread),
             "/threads/messages/update" => Ok(Self::UpdateMessage),
             "/threads/messages/data" => Ok(Self::MessageData),
+            "/threads/messages/versions/list" => Ok(Self::ListMessageVersions),
+            "/threads/messages/versions/diff" => Ok(Self::DiffMessageVersions),
+            "/threads/messages/versions/revert" => Ok(Self::RevertMessageVersion),
             _ => Err(anyhow!("Invalid path")),
         }
     }
@@ -9,7 +9,7 @@ use uuid::Uuid;
 
 use crate::{
     database::{
-        enums::{AssetPermissionRole, MessageFeedback, Verification},
+        enums::{AssetPermissionRole, MessageFeedback, MessageVersionAuthor, Verification},
         lib::{get_pg_pool, get_sqlx_pool},
         models::User,
         schema::{messages, threads},
@@ -25,6 +25,7 @@ use crate::{
 };
 
 use super::{
+    message_versions::{ensure_message_baseline_version, record_message_version, MessageSnapshot},
     messages_utils::{get_message_with_permission, MessageDraftState},
     thread_utils::{check_if_thread_saved, get_thread_state_by_id},
     threads_router::{ThreadEvent, ThreadRoute},
@@ -180,10 +181,12 @@ async fn update_message_handler(
         return Err(anyhow!("User does not have permission to update message."));
     };
 
+    let versioned_change = code.is_some() || chart_config.is_some() || title.is_some();
+
     let draft_session_id = if let Some(draft_session_id) = draft_session_id {
         Some(draft_session_id)
     } else {
-        let draft_session_id = if code.is_some() || chart_config.is_some() || title.is_some() {
+        let draft_session_id = if versioned_change {
             let thread_saved = match check_if_thread_saved(&message.thread_id).await {
                 Ok(result) => result,
                 Err(e) => {
@@ -243,6 +246,13 @@ async fn update_message_handler(
 
         message.draft_state = Some(serde_json::to_value(draft_state).unwrap());
     } else {
+        if versioned_change {
+            if let Err(e) = ensure_message_baseline_version(&message).await {
+                tracing::error!("Error recording baseline message version: {}", e);
+                send_sentry_error(&e.to_string(), Some(&user.id));
+            }
+        }
+
         if let Some(code) = code {
             message.code = Some(code);
         }
@@ -264,6 +274,8 @@ async fn update_message_handler(
         message.verification = verification;
     }
 
+    let message_snapshot = MessageSnapshot::from_message(&message);
+
     let message_update_handle = {
         let message = message.clone();
         tokio::spawn(async move {
@@ -324,6 +336,21 @@ async fn update_message_handler(
         return Err(e);
     }
 
+    if versioned_change && draft_session_id.is_none() {
+        if let Err(e) = record_message_version(
+            message_id,
+            message_snapshot,
+            MessageVersionAuthor::User,
+            Some(user.id),
+            None,
+        )
+        .await
+        {
+            tracing::error!("Error recording message version: {}", e);
+            send_sentry_error(&e.to_string(), Some(&user.id));
+        }
+    }
+
     if let Some(handle) = thread_search_handle {
         if let Err(e) = handle.await {
             return Err(anyhow!("Error in thread search update: {:?}", e));
@@ -6,13 +6,14 @@ use diesel::{
 };
 use diesel_async::RunQueryDsl;
 use serde::Deserialize;
+use serde_json::json;
 use std::sync::Arc;
 
 use uuid::Uuid;
 
 use crate::{
     database::{
-        enums::AssetType,
+        enums::{AssetType, AuditAction, AuditTargetType, MessageVersionAuthor},
         lib::get_pg_pool,
         models::{Message, ThreadToDashboard, User},
         schema::{messages, threads, threads_to_dashboards},
@@ -23,6 +24,7 @@ use crate::{
         ws_utils::{get_key_value, send_error_message, send_ws_message, subscribe_to_stream},
     },
     utils::{
+        audit::audit_log::{spawn_audit_event, AuditRequestMetadata, NewAuditEvent},
         clients::{sentry_utils::send_sentry_error, supabase_vault::create_secret},
         sharing::asset_sharing::{
             create_asset_collection_association, delete_asset_collection_association,
@@ -34,6 +36,7 @@ use crate::{
 use crate::utils::serde_helpers::deserialization_helpers::deserialize_double_option;
 
 use super::{
+    message_versions::{ensure_message_baseline_version, record_message_version, MessageSnapshot},
     messages_utils::MessageDraftState,
     thread_utils::get_thread_state_by_id,
     threads_router::{ThreadEvent, ThreadRoute},
@@ -90,6 +93,19 @@ pub async fn update_thread(
         }
     };
 
+    let public_sharing_change = if req.publicly_accessible.is_some()
+        || req.public_password.is_some()
+        || req.public_expiry_date.is_some()
+    {
+        Some(json!({
+            "publicly_accessible": req.publicly_accessible,
+            "password_protected": req.public_password.as_ref().map(|password| password.is_some()),
+            "public_expiry_date": req.public_expiry_date,
+        }))
+    } else {
+        None
+    };
+
     let thread_id = Arc::new(req.id);
     let user_id = Arc::new(user.id.clone());
 
@@ -162,6 +178,7 @@ pub async fn update_thread(
                 req.user_permissions,
                 req.remove_teams,
                 req.remove_users,
+                AuditRequestMetadata::ws(WsRoutes::Threads(ThreadRoute::Update)),
             )
             .await
             {
@@ -193,6 +210,18 @@ pub async fn update_thread(
         }
     };
 
+    if let Some(public_sharing_change) = public_sharing_change {
+        spawn_audit_event(NewAuditEvent {
+            actor_id: user.id,
+            action: AuditAction::AssetPubliclyShared,
+            target_type: AuditTargetType::Thread,
+            target_id: Some(*thread_id),
+            before: None,
+            after: Some(public_sharing_change),
+            request_metadata: AuditRequestMetadata::ws(WsRoutes::Threads(ThreadRoute::Update)),
+        });
+    }
+
     if let Some(update_thread_collections_handle) = update_thread_collections_handle {
         match update_thread_collections_handle.await.unwrap() {
             Ok(_) => (),
@@ -336,8 +365,9 @@ async fn update_thread_record(
     let save_draft_handle =
         if let (Some(_), Some(draft_session_id)) = (save_draft, draft_session_id) {
             let thread_id = Arc::clone(&thread_id);
+            let user_id = Arc::clone(&user_id);
             Some(tokio::spawn(async move {
-                save_draft_handler(thread_id, draft_session_id).await
+                save_draft_handler(thread_id, user_id, draft_session_id).await
             }))
         } else {
             None
@@ -530,7 +560,11 @@ async fn update_thread_collections(
     Ok(())
 }
 
-async fn save_draft_handler(thread_id: Arc<Uuid>, draft_session_id: Uuid) -> Result<()> {
+async fn save_draft_handler(
+    thread_id: Arc<Uuid>,
+    user_id: Arc<Uuid>,
+    draft_session_id: Uuid,
+) -> Result<()> {
     let mut conn = match get_pg_pool().get().await {
         Ok(conn) => conn,
         Err(e) => {
@@ -596,6 +630,11 @@ async fn save_draft_handler(thread_id: Arc<Uuid>, draft_session_id: Uuid) -> Res
             let message_draft_state: MessageDraftState =
                 serde_json::from_value(message_draft_state.clone()).unwrap();
 
+            if let Err(e) = ensure_message_baseline_version(&most_recent_message).await {
+                tracing::error!("Error recording baseline message version: {}", e);
+                send_sentry_error(&e.to_string(), Some(&user_id));
+            }
+
             if let Some(title) = message_draft_state.title {
                 most_recent_message.title = Some(title);
             };
@@ -608,6 +647,9 @@ async fn save_draft_handler(thread_id: Arc<Uuid>, draft_session_id: Uuid) -> Res
                 most_recent_message.code = Some(code);
             };
 
+            let message_id = most_recent_message.id;
+            let message_snapshot = MessageSnapshot::from_message(&most_recent_message);
+
             tokio::spawn(async move {
                 match update(messages::table)
                     .filter(messages::id.eq(most_recent_message.id))
@@ -615,9 +657,24 @@ async fn save_draft_handler(thread_id: Arc<Uuid>, draft_session_id: Uuid) -> Res
                     .execute(&mut conn)
                     .await
                 {
-                    Ok(_) => Ok(()),
-                    Err(e) => Err(anyhow!("Error saving draft:thread: {}", e)),
+                    Ok(_) => (),
+                    Err(e) => return Err(anyhow!("Error saving draft:thread: {}", e)),
+                };
+
+                if let Err(e) = record_message_version(
+                    &message_id,
+                    message_snapshot,
+                    MessageVersionAuthor::User,
+                    Some(*user_id),
+                    None,
+                )
+                .await
+                {
+                    tracing::error!("Error recording message version: {}", e);
+                    send_sentry_error(&e.to_string(), Some(&user_id));
                 }
+
+                Ok(())
             })
         } else {
             tokio::spawn(async move { Ok(()) })
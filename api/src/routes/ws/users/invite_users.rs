@@ -85,6 +85,7 @@ async fn invite_users_handler(user: &User, req: InviteUsersRequest) -> Result<()
                 config: json!(user_config),
                 created_at: Utc::now(),
                 updated_at: Utc::now(),
+                attributes: json!({}),
             };
 
             new_user
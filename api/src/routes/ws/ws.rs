@@ -5,7 +5,10 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crate::database::{lib::get_redis_pool, models::User};
+use crate::{
+    database::{lib::get_redis_pool, models::User},
+    utils::security::api_keys::ApiKeyAccess,
+};
 use async_compression::tokio::bufread::GzipDecoder;
 use axum::{
     extract::{
@@ -224,13 +227,21 @@ pub async fn ws(
     ws: WebSocketUpgrade,
     Extension(user): Extension<User>,
     Extension(shutdown_tx): Extension<Arc<broadcast::Sender<()>>>,
+    api_key_access: Option<Extension<ApiKeyAccess>>,
 ) -> impl IntoResponse {
+    let api_key_access = api_key_access.map(|Extension(api_key_access)| api_key_access);
+
     ws.on_upgrade(|ws| async move {
-        ws_handler(ws, user, shutdown_tx).await;
+        ws_handler(ws, user, api_key_access, shutdown_tx).await;
     })
 }
 
-async fn ws_handler(stream: WebSocket, user: User, shutdown_tx: Arc<broadcast::Sender<()>>) {
+async fn ws_handler(
+    stream: WebSocket,
+    user: User,
+    api_key_access: Option<ApiKeyAccess>,
+    shutdown_tx: Arc<broadcast::Sender<()>>,
+) {
     let mut shutdown_rx = shutdown_tx.subscribe();
 
     let (sender, mut receiver) = stream.split();
@@ -319,9 +330,10 @@ async fn ws_handler(stream: WebSocket, user: User, shutdown_tx: Arc<broadcast::S
                             let subscriptions = subscriptions.clone();
                             let user_group = user_group.clone();
                             let user = user.clone();
+                            let api_key_access = api_key_access.clone();
 
                             tasks.spawn(async move {
-                                if let Err(e) = ws_router(message.route, message.payload, &subscriptions, &user_group, &user).await {
+                                if let Err(e) = ws_router(message.route, message.payload, &subscriptions, &user_group, &user, api_key_access.as_ref()).await {
                                     tracing::error!("Error processing websocket message: {:?}", e);
                                 }
                             });
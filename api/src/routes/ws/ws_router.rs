@@ -10,6 +10,7 @@ use crate::{
         dashboards::dashboards_router::dashboards_router,
         datasets::datasets_router::datasets_router,
     },
+    utils::security::api_keys::{ApiKeyAccess, ApiKeyScope},
 };
 
 use super::{
@@ -68,6 +69,32 @@ impl WsRoutes {
             _ => Err(anyhow!("Invalid path")),
         }
     }
+
+    /// The scope an API key needs to send a message to this route. Routes that are not listed
+    /// need the `*` scope.
+    pub fn api_key_scope(&self) -> ApiKeyScope {
+        match self {
+            Self::Threads(route) => match route {
+                ThreadRoute::List
+                | ThreadRoute::Get
+                | ThreadRoute::Unsubscribe
+                | ThreadRoute::MessageData
+                | ThreadRoute::ListMessageVersions
+                | ThreadRoute::DiffMessageVersions => ApiKeyScope::ThreadsRead,
+                _ => ApiKeyScope::ThreadsWrite,
+            },
+            Self::Dashboards(route) => match route {
+                DashboardRoute::List
+                | DashboardRoute::Get
+                | DashboardRoute::Unsubscribe
+                | DashboardRoute::ListVersions
+                | DashboardRoute::DiffVersions => ApiKeyScope::DashboardsRead,
+                _ => ApiKeyScope::DashboardsWrite,
+            },
+            Self::Sql(_) => ApiKeyScope::SqlRun,
+            _ => ApiKeyScope::All,
+        }
+    }
 }
 
 pub async fn ws_router(
@@ -76,6 +103,7 @@ pub async fn ws_router(
     subscriptions: &Arc<SubscriptionRwLock>,
     user_group: &String,
     user: &User,
+    api_key_access: Option<&ApiKeyAccess>,
 ) -> Result<()> {
     let parsed_route: WsRoutes = match WsRoutes::from_str(&route) {
         Ok(parsed_route) => parsed_route,
@@ -84,6 +112,19 @@ pub async fn ws_router(
         }
     };
 
+    if let Some(api_key_access) = api_key_access {
+        let scope = parsed_route.api_key_scope();
+
+        if !api_key_access.allows(&scope) {
+            return Err(anyhow!(
+                "API key {} is missing the {:?} scope for {}",
+                api_key_access.api_key_id,
+                scope,
+                route
+            ));
+        }
+    }
+
     let result = match parsed_route {
         WsRoutes::Threads(threads_route) => {
             threads_router(threads_route, payload, subscriptions, user_group, user).await
@@ -6,7 +6,7 @@ use diesel::{ExpressionMethods, JoinOnDsl, NullableExpressionMethods, QueryDsl};
 use diesel_async::RunQueryDsl;
 use redis::{streams::StreamMaxlen, AsyncCommands};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use tokio::io::AsyncWriteExt;
 use uuid::Uuid;
 
@@ -17,6 +17,8 @@ use crate::database::{
     schema::{organizations, teams, teams_to_users, users, users_to_organizations},
 };
 
+use crate::utils::user::user_attributes::get_user_attributes;
+
 use super::{
     ws::{SubscriptionRwLock, WsError, WsErrorCode, WsEvent, WsResponseMessage, WsSendMethod},
     ws_router::WsRoutes,
@@ -357,6 +359,8 @@ pub struct UserInfoObject {
     pub user: User,
     pub organizations: Vec<UserOrganization>,
     pub teams: Vec<UserTeam>,
+    /// The user's own attributes merged with the ones inherited from their teams.
+    pub attributes: Map<String, Value>,
 }
 
 pub async fn get_user_information(user_id: &Uuid) -> Result<UserInfoObject> {
@@ -382,6 +386,7 @@ pub async fn get_user_information(user_id: &Uuid) -> Result<UserInfoObject> {
                 users::config,
                 users::created_at,
                 users::updated_at,
+                users::attributes,
             ),
             (
                 teams::id,
@@ -396,6 +401,7 @@ pub async fn get_user_information(user_id: &Uuid) -> Result<UserInfoObject> {
                 teams::created_at,
                 teams::updated_at,
                 teams::deleted_at,
+                teams::attributes,
             )
                 .nullable(),
             (
@@ -463,9 +469,12 @@ pub async fn get_user_information(user_id: &Uuid) -> Result<UserInfoObject> {
         })
         .collect::<Vec<UserOrganization>>();
 
+    let attributes = get_user_attributes(user_id).await?;
+
     Ok(UserInfoObject {
         user,
         teams,
         organizations,
+        attributes,
     })
 }
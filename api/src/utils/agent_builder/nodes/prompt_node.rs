@@ -7,6 +7,7 @@ use uuid::Uuid;
 use crate::utils::clients::ai::{
     langfuse::PromptName,
     llm_router::{llm_chat, llm_chat_stream, LlmMessage, LlmModel},
+    llm_usage::LlmUsageScope,
     openai::OpenAiChatModel,
 };
 
@@ -30,6 +31,7 @@ pub struct PromptNodeSettings {
     pub user_id: Uuid,
     pub session_id: Uuid,
     pub prompt_name: String,
+    pub usage_scope: LlmUsageScope,
 }
 
 impl Default for PromptNodeSettings {
@@ -47,6 +49,7 @@ impl Default for PromptNodeSettings {
             user_id: Uuid::new_v4(),
             session_id: Uuid::new_v4(),
             prompt_name: String::from("Unknown Prompt"),
+            usage_scope: LlmUsageScope::default(),
         }
     }
 }
@@ -63,10 +66,27 @@ impl fmt::Display for PromptNodeError {
     }
 }
 
+/// The model named in the settings, or GPT-4o when none is. Organizations' LLM routes can still
+/// send the prompt elsewhere.
+fn prompt_node_model(model: &str) -> LlmModel {
+    match LlmModel::from_name(model) {
+        Some(model) => model,
+        None => {
+            if !model.is_empty() {
+                tracing::warn!("Unknown prompt node model {}, using gpt-4o", model);
+            }
+
+            LlmModel::OpenAi(OpenAiChatModel::Gpt4o)
+        }
+    }
+}
+
 pub async fn prompt_node(settings: PromptNodeSettings) -> Result<Value, ErrorNode> {
+    let model = prompt_node_model(&settings.model);
+
     let llm_response = if let Some(stream) = settings.stream {
         let (mut llm_stream, response_future) = match llm_chat_stream(
-            LlmModel::OpenAi(OpenAiChatModel::Gpt4o),
+            model,
             settings
                 .messages
                 .into_iter()
@@ -79,6 +99,7 @@ pub async fn prompt_node(settings: PromptNodeSettings) -> Result<Value, ErrorNod
             &settings.session_id,
             &settings.user_id,
             PromptName::CustomPrompt(settings.prompt_name.clone()),
+            settings.usage_scope,
         )
         .await
         {
@@ -137,7 +158,7 @@ pub async fn prompt_node(settings: PromptNodeSettings) -> Result<Value, ErrorNod
         }
     } else {
         let response = match llm_chat(
-            LlmModel::OpenAi(OpenAiChatModel::Gpt4o),
+            model,
             &settings
                 .messages
                 .into_iter()
@@ -152,6 +173,7 @@ pub async fn prompt_node(settings: PromptNodeSettings) -> Result<Value, ErrorNod
             &settings.session_id,
             &settings.user_id,
             PromptName::CustomPrompt(settings.prompt_name.clone()),
+            settings.usage_scope,
         )
         .await
         {
@@ -1,10 +1,12 @@
 use serde_json::Value;
+use uuid::Uuid;
 
 use crate::utils::{
     agent_builder::nodes::{
         error_node::ErrorNode,
         prompt_node::{prompt_node, PromptNodeMessage, PromptNodeSettings},
     },
+    clients::ai::llm_usage::LlmUsageScope,
     prompts::modify_visualization_prompts::styling_prompts::column_styling_prompts::{
         column_styling_system_prompt, column_styling_user_prompt,
     },
@@ -16,6 +18,8 @@ pub struct ColumnStylingAgentOptions {
     pub chart_config: String,
     pub sql_statement: String,
     pub data_metadata: String,
+    pub user_id: Uuid,
+    pub usage_scope: LlmUsageScope,
 }
 
 pub struct FormatLabelsAgentResult {
@@ -41,6 +45,8 @@ pub async fn column_styling_agent(options: ColumnStylingAgentOptions) -> Result<
         ],
         prompt_name: "column_styling".to_string(),
         json_mode: true,
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
 
@@ -1,6 +1,7 @@
 use serde_json::Value;
 use std::fmt;
 use tokio::sync::mpsc;
+use uuid::Uuid;
 
 use crate::utils::{
     agent_builder::nodes::{
@@ -8,6 +9,7 @@ use crate::utils::{
         prompt_node::{prompt_node, PromptNodeMessage, PromptNodeSettings},
     },
     charting::types::ChartType,
+    clients::ai::llm_usage::LlmUsageScope,
     prompts::modify_visualization_prompts::build_charts_prompts::{
         bar_line_chart_prompt::{bar_line_chart_system_prompt, bar_line_chart_user_prompt},
         combo_chart_prompt::{combo_chart_system_prompt, combo_chart_user_prompt},
@@ -41,6 +43,8 @@ pub struct BuildChartsAgentOptions {
     pub sql: String,
     pub user_message: String,
     pub output_sender: mpsc::Sender<Value>,
+    pub user_id: Uuid,
+    pub usage_scope: LlmUsageScope,
 }
 
 use serde::{Deserialize, Serialize};
@@ -160,6 +164,8 @@ pub async fn configure_charts_agent(
         ],
         prompt_name: "bar_line_chart".to_string(),
         json_mode: true,
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
     let bar_line_future = tokio::spawn(async move { prompt_node(bar_line_chart_settings).await });
@@ -184,6 +190,8 @@ pub async fn configure_charts_agent(
         ],
         prompt_name: "scatter_chart".to_string(),
         json_mode: true,
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
     let scatter_future = tokio::spawn(async move { prompt_node(scatter_chart_settings).await });
@@ -208,6 +216,8 @@ pub async fn configure_charts_agent(
         ],
         prompt_name: "pie_chart".to_string(),
         json_mode: true,
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
     let pie_future = tokio::spawn(async move { prompt_node(pie_chart_settings).await });
@@ -232,6 +242,8 @@ pub async fn configure_charts_agent(
         ],
         prompt_name: "metric_chart".to_string(),
         json_mode: true,
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
     let metric_future = tokio::spawn(async move { prompt_node(metric_chart_settings).await });
@@ -257,6 +269,8 @@ pub async fn configure_charts_agent(
         messages,
         prompt_name: "combo_chart".to_string(),
         json_mode: true,
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
     let combo_future = tokio::spawn(async move { prompt_node(combo_chart_settings).await });
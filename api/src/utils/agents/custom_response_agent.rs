@@ -1,12 +1,14 @@
 use serde_json::{json, Value};
 use std::fmt;
 use tokio::sync::mpsc;
+use uuid::Uuid;
 
 use crate::utils::{
     agent_builder::nodes::{
         error_node::ErrorNode,
         prompt_node::{prompt_node, PromptNodeMessage, PromptNodeSettings},
     },
+    clients::ai::llm_usage::LlmUsageScope,
     prompts::custom_response_prompts::custom_response_prompt::{
         custom_response_system_prompt, custom_response_user_prompt,
     },
@@ -36,6 +38,8 @@ pub struct CustomResponseAgentOptions {
     pub datasets: String,
     pub orchestrator_output: String,
     pub output_sender: mpsc::Sender<Value>,
+    pub user_id: Uuid,
+    pub usage_scope: LlmUsageScope,
 }
 
 pub async fn custom_response_agent(
@@ -51,6 +55,8 @@ pub async fn custom_response_agent(
         stream: Some(options.output_sender.clone()),
         stream_name: Some("custom_response".to_string()),
         prompt_name: "custom_response".to_string(),
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
 
@@ -33,7 +33,7 @@ use crate::{
             multiple_datasets_response_agent::handle_multiple_datasets_agent,
             sql_evaluation_agent::{sql_evaluation_agent, SqlEvaluationAgentOptions},
         },
-        clients::typesense::StoredValueDocument,
+        clients::{ai::llm_usage::LlmUsageScope, typesense::StoredValueDocument},
         prompts::analyst_chat_prompts::orchestrator_prompt::{
             orchestrator_prompt_schema, orchestrator_system_prompt,
         },
@@ -63,6 +63,11 @@ pub struct DatasetWithMetadata {
     pub columns: Vec<DatasetColumn>,
     pub data_source: DataSource,
     pub dataset_ddl: String,
+    /// Columns whose values are masked for the user. Hidden columns are left out of `columns`.
+    pub masked_column_ids: Vec<Uuid>,
+    /// Whether the user only sees some of the dataset's rows, through a row policy or a
+    /// definition templated on the user.
+    pub rows_restricted: bool,
 }
 
 impl DatasetWithMetadata {
@@ -72,6 +77,14 @@ impl DatasetWithMetadata {
             .find(|c| c.id == *column_id)
             .map(|c| c.name.clone())
     }
+
+    /// Whether the user may see raw values of the column, e.g. as examples given to the LLM.
+    /// Stored values are sampled from every row, so none are visible when rows are restricted.
+    pub fn column_values_visible(&self, column_id: &Uuid) -> bool {
+        !self.rows_restricted
+            && self.columns.iter().any(|c| c.id == *column_id)
+            && !self.masked_column_ids.contains(column_id)
+    }
 }
 
 #[derive(Clone)]
@@ -84,6 +97,8 @@ pub struct DataAnalystAgentOptions {
     pub relevant_values: Vec<StoredValueDocument>,
     pub thread_id: Uuid,
     pub message_id: Uuid,
+    /// Generated SQL runs as this user, so their dataset row policies apply.
+    pub user_id: Uuid,
 }
 
 pub enum DataAnalystAgentError {
@@ -124,6 +139,7 @@ pub struct Thought {
 
 pub async fn data_analyst_agent(options: DataAnalystAgentOptions) -> Result<Value, ErrorNode> {
     let start_time = Instant::now();
+    let usage_scope = LlmUsageScope::message(options.thread_id, options.message_id);
 
     let mut thoughts = Thoughts {
         title: "Understanding Your Request".to_string(),
@@ -141,6 +157,8 @@ pub async fn data_analyst_agent(options: DataAnalystAgentOptions) -> Result<Valu
         messages: create_orchestrator_messages(options.input.clone(), &options.message_history),
         json_schema: Some(orchestrator_prompt_schema()),
         prompt_name: "orchestrator".to_string(),
+        user_id: options.user_id,
+        usage_scope,
         ..Default::default()
     };
 
@@ -186,6 +204,8 @@ pub async fn data_analyst_agent(options: DataAnalystAgentOptions) -> Result<Valu
             datasets: String::new(),
             orchestrator_output: String::new(),
             output_sender: options.output_sender.clone(),
+            user_id: options.user_id,
+            usage_scope,
         };
 
         let custome_response = match custom_response_agent(custom_response_options).await {
@@ -314,6 +334,8 @@ pub async fn data_analyst_agent(options: DataAnalystAgentOptions) -> Result<Valu
             datasets: datasets_string,
             orchestrator_output: data_analyst_ticket.to_string(),
             output_sender: options.output_sender.clone(),
+            user_id: options.user_id,
+            usage_scope,
         };
 
         let custome_response = match custom_response_agent(custom_response_options).await {
@@ -451,6 +473,8 @@ pub async fn data_analyst_agent(options: DataAnalystAgentOptions) -> Result<Valu
                 datasets: datasets_string,
                 orchestrator_output: prompt,
                 output_sender: options.output_sender.clone(),
+                user_id: options.user_id,
+                usage_scope,
             };
 
             let custome_response = match custom_response_agent(custom_response_options).await {
@@ -540,6 +564,8 @@ pub async fn data_analyst_agent(options: DataAnalystAgentOptions) -> Result<Valu
             message_history: options.message_history.clone(),
             start_time,
             relevant_values: options.relevant_values.clone(),
+            user_id: options.user_id,
+            usage_scope,
         };
 
         let future = tokio::spawn(async move { generate_sql_agent(generate_sql_options).await });
@@ -568,6 +594,8 @@ pub async fn data_analyst_agent(options: DataAnalystAgentOptions) -> Result<Valu
                     datasets: datasets_string,
                     orchestrator_output: prompt,
                     output_sender: options.output_sender.clone(),
+                    user_id: options.user_id,
+                    usage_scope,
                 };
 
                 let custome_response = match custom_response_agent(custom_response_options).await {
@@ -696,6 +724,8 @@ pub async fn data_analyst_agent(options: DataAnalystAgentOptions) -> Result<Valu
                     datasets: datasets_string,
                     output_sender: options.output_sender.clone(),
                     dataset_selector_output: dataset_selector_output.clone(),
+                    user_id: options.user_id,
+                    usage_scope,
                 };
 
                 let response = match handle_multiple_datasets_agent(multiple_datasets_options).await
@@ -813,6 +843,8 @@ pub async fn data_analyst_agent(options: DataAnalystAgentOptions) -> Result<Valu
                     output_sender: options.output_sender.clone(),
                     outputs: outputs.clone(),
                     message_history: options.message_history.clone(),
+                    user_id: options.user_id,
+                    usage_scope,
                 };
 
                 let could_not_fix_sql_response =
@@ -939,6 +971,8 @@ pub async fn data_analyst_agent(options: DataAnalystAgentOptions) -> Result<Valu
                 sql: sql.clone(),
                 thoughts: sql_thoughts.clone(),
                 output_sender: options.output_sender.clone(),
+                user_id: options.user_id,
+                usage_scope,
             };
 
             Some(tokio::spawn(async move {
@@ -972,6 +1006,8 @@ pub async fn data_analyst_agent(options: DataAnalystAgentOptions) -> Result<Valu
             sql: sql.clone(),
             output_sender: output_sender.clone(),
             datasets: datasets_string,
+            user_id: options.user_id,
+            usage_scope,
         };
 
         let sql_evaluation_id = Uuid::new_v4();
@@ -1107,6 +1143,8 @@ pub async fn data_analyst_agent(options: DataAnalystAgentOptions) -> Result<Valu
             user_message: options.input.clone(),
             data_metadata,
             sql: sql.clone(),
+            user_id: options.user_id,
+            usage_scope,
         };
 
         let results = match modify_visualization_agent(modify_visualization_options).await {
@@ -1145,6 +1183,8 @@ pub async fn data_analyst_agent(options: DataAnalystAgentOptions) -> Result<Valu
                 user_message: options.input.clone(),
                 data_metadata,
                 sql: sql.clone(),
+                user_id: options.user_id,
+                usage_scope,
             };
 
             let results = match modify_visualization_agent(modify_visualization_options).await {
@@ -1481,6 +1521,8 @@ pub async fn data_analyst_agent(options: DataAnalystAgentOptions) -> Result<Valu
         datasets: datasets_string.clone(),
         input: options.input.clone(),
         output_sender: options.output_sender.clone(),
+        user_id: options.user_id,
+        usage_scope,
     };
 
     let master_response_handle =
@@ -1,12 +1,14 @@
 use serde_json::Value;
 use std::fmt;
 use tokio::sync::mpsc;
+use uuid::Uuid;
 
 use crate::utils::{
     agent_builder::nodes::{
         error_node::ErrorNode,
         prompt_node::{prompt_node, PromptNodeMessage, PromptNodeSettings},
     },
+    clients::ai::llm_usage::LlmUsageScope,
     prompts::analyst_chat_prompts::failed_to_fix_sql_prompts::{
             failed_to_fix_sql_system_prompt, failed_to_fix_sql_user_prompt,
         },
@@ -33,6 +35,8 @@ pub struct FailedToFixSqlAgentOptions {
     pub message_history: Vec<Value>,
     pub input: String,
     pub output_sender: mpsc::Sender<Value>,
+    pub user_id: Uuid,
+    pub usage_scope: LlmUsageScope,
 }
 
 pub async fn failed_to_fix_sql_agent(
@@ -82,6 +86,8 @@ pub async fn failed_to_fix_sql_agent(
         stream: Some(options.output_sender),
         stream_name: Some("failed_to_fix_sql".to_string()),
         prompt_name: "failed_to_fix_sql".to_string(),
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
 
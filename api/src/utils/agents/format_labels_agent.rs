@@ -1,12 +1,14 @@
 use serde_json::Value;
 use std::fmt;
 use tokio::sync::mpsc;
+use uuid::Uuid;
 
 use crate::utils::{
     agent_builder::nodes::{
         error_node::ErrorNode,
         prompt_node::{prompt_node, PromptNodeMessage, PromptNodeSettings},
     },
+    clients::ai::llm_usage::LlmUsageScope,
     prompts::modify_visualization_prompts::format_label_prompt::{
         format_label_system_prompt, format_label_user_prompt,
     },
@@ -35,6 +37,8 @@ pub struct FormatLabelsAgentOptions {
     pub sql_statement: String,
     pub data_metadata: String,
     pub output_sender: mpsc::Sender<Value>,
+    pub user_id: Uuid,
+    pub usage_scope: LlmUsageScope,
 }
 
 pub struct FormatLabelsAgentResult {
@@ -60,6 +64,8 @@ pub async fn format_labels_agent(options: FormatLabelsAgentOptions) -> Result<Va
         ],
         prompt_name: "format_labels".to_string(),
         json_mode: true,
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
 
@@ -14,7 +14,7 @@ use crate::{
             error_node::ErrorNode,
             prompt_node::{prompt_node, PromptNodeMessage, PromptNodeSettings},
         },
-        clients::typesense::StoredValueDocument,
+        clients::{ai::llm_usage::LlmUsageScope, typesense::StoredValueDocument},
         prompts::generate_sql_prompts::{
             dataset_selector_prompt::{
                 dataset_selector_prompt_schema, dataset_selector_system_prompt,
@@ -64,6 +64,8 @@ pub struct GenerateSqlAgentOptions {
     pub relevant_values: Vec<StoredValueDocument>,
     pub start_time: Instant,
     pub output_sender: mpsc::Sender<Value>,
+    pub user_id: Uuid,
+    pub usage_scope: LlmUsageScope,
 }
 
 pub async fn generate_sql_agent(options: GenerateSqlAgentOptions) -> Result<Value, ErrorNode> {
@@ -99,6 +101,8 @@ pub async fn generate_sql_agent(options: GenerateSqlAgentOptions) -> Result<Valu
         ),
         json_schema: Some(dataset_selector_json_schema),
         prompt_name: "dataset_selector".to_string(),
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
 
@@ -452,6 +456,8 @@ pub async fn generate_sql_agent(options: GenerateSqlAgentOptions) -> Result<Valu
         prompt_name: "sql_gen_thought".to_string(),
         stream: Some(thought_tx.clone()),
         stream_name: Some("generating_sql_thought".to_string()),
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
 
@@ -633,6 +639,8 @@ pub async fn generate_sql_agent(options: GenerateSqlAgentOptions) -> Result<Valu
         stream: Some(options.output_sender.clone()),
         stream_name: Some("generating_sql".to_string()),
         prompt_name: "sql_gen".to_string(),
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
 
@@ -661,6 +669,8 @@ pub async fn generate_sql_agent(options: GenerateSqlAgentOptions) -> Result<Valu
         output_sender: options.output_sender.clone(),
         thoughts: thoughts.clone(),
         start_time: options.start_time,
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
     };
 
     let run_sql_result = match run_and_fix_sql_agent(run_and_fix_sql_agent_options).await {
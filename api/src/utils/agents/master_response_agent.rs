@@ -1,12 +1,14 @@
 use serde_json::Value;
 use std::fmt;
 use tokio::sync::mpsc;
+use uuid::Uuid;
 
 use crate::utils::{
     agent_builder::nodes::{
         error_node::ErrorNode,
         prompt_node::{prompt_node, PromptNodeMessage, PromptNodeSettings},
     },
+    clients::ai::llm_usage::LlmUsageScope,
     prompts::analyst_chat_prompts::master_response_prompt::{
         master_response_system_prompt, master_response_user_prompt,
     },
@@ -34,6 +36,8 @@ pub struct MasterResponseAgentOptions {
     pub datasets: String,
     pub input: String,
     pub output_sender: mpsc::Sender<Value>,
+    pub user_id: Uuid,
+    pub usage_scope: LlmUsageScope,
 }
 
 pub async fn master_response_agent(
@@ -98,6 +102,8 @@ pub async fn master_response_agent(
         stream: Some(options.output_sender),
         stream_name: Some("master_response".to_string()),
         prompt_name: "master_response".to_string(),
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
 
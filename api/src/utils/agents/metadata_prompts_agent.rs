@@ -2,12 +2,14 @@ use serde::Deserialize;
 use serde_json::Value;
 use std::fmt;
 use tokio::sync::mpsc;
+use uuid::Uuid;
 
 use crate::utils::{
     agent_builder::nodes::{
         error_node::ErrorNode,
         prompt_node::{prompt_node, PromptNodeMessage, PromptNodeSettings},
     },
+    clients::ai::llm_usage::LlmUsageScope,
     prompts::modify_visualization_prompts::title_description_time_frame_prompts::{
         title_description_time_frame_system_prompt, title_description_time_frame_user_prompt,
     },
@@ -34,6 +36,8 @@ pub struct MetadataPromptsAgentOptions {
     pub sql: String,
     pub thoughts: String,
     pub output_sender: mpsc::Sender<Value>,
+    pub user_id: Uuid,
+    pub usage_scope: LlmUsageScope,
 }
 
 #[derive(Deserialize)]
@@ -88,6 +92,8 @@ pub async fn metadata_prompts_agent(
         ],
         prompt_name: "title_description_time_frame_prompt".to_string(),
         json_mode: true,
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
 
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::{fmt, time::Instant};
 use tokio::sync::mpsc;
+use uuid::Uuid;
 
 use crate::utils::{
     agent_builder::nodes::{
@@ -9,6 +10,7 @@ use crate::utils::{
         prompt_node::{prompt_node, PromptNodeMessage, PromptNodeSettings},
     },
     charting::types::ChartType,
+    clients::ai::llm_usage::LlmUsageScope,
     prompts::modify_visualization_prompts::modify_visualization_orchestrator_prompt::{
         modify_visualization_system_prompt, modify_visualization_user_prompt,
     },
@@ -36,6 +38,8 @@ pub struct ModifyVisualizationAgentOptions {
     pub user_message: String,
     pub data_metadata: Value,
     pub sql: String,
+    pub user_id: Uuid,
+    pub usage_scope: LlmUsageScope,
 }
 
 pub enum ModifyVisualizationAgentError {
@@ -172,6 +176,8 @@ pub async fn modify_visualization_agent(
             ],
             prompt_name: "visualization_orchestrator".to_string(),
             json_schema: Some(modify_visualization_prompt_schema()),
+            user_id: options.user_id,
+            usage_scope: options.usage_scope,
             ..Default::default()
         };
 
@@ -270,6 +276,8 @@ pub async fn modify_visualization_agent(
         let output_sender = options.output_sender.clone();
         let previous_chart_config = previous_message_chart_config_context.clone();
         let sql = options.sql.clone();
+        let user_id = options.user_id;
+        let usage_scope = options.usage_scope;
 
         Some(tokio::spawn(async move {
             // Single call to format_labels_agent with all columns
@@ -279,6 +287,8 @@ pub async fn modify_visualization_agent(
                 sql_statement: sql.clone(),
                 data_metadata: data_metadata.to_string(),
                 output_sender,
+                user_id,
+                usage_scope,
             };
 
             format_labels_agent(format_labels_options).await
@@ -309,6 +319,8 @@ pub async fn modify_visualization_agent(
             output_sender: options.output_sender.clone(),
             sql: options.sql.clone(),
             user_message: options.user_message.clone(),
+            user_id: options.user_id,
+            usage_scope: options.usage_scope,
         };
         Some(tokio::spawn(async move {
             configure_charts_agent(build_charts_options).await
@@ -329,6 +341,8 @@ pub async fn modify_visualization_agent(
             chart_config: previous_message_chart_config_context.clone(),
             sql_statement: options.sql.clone(),
             data_metadata: options.data_metadata.to_string(),
+            user_id: options.user_id,
+            usage_scope: options.usage_scope,
         };
 
         Some(tokio::spawn(async move {
@@ -354,6 +368,8 @@ pub async fn modify_visualization_agent(
                 chart_config: previous_message_chart_config_context.clone(),
                 sql_statement: options.sql.clone(),
                 data_metadata: options.data_metadata.to_string(),
+                user_id: options.user_id,
+                usage_scope: options.usage_scope,
             };
 
             Some(tokio::spawn(async move {
@@ -1,12 +1,14 @@
 use serde_json::{json, Value};
 use std::fmt;
 use tokio::sync::mpsc;
+use uuid::Uuid;
 
 use crate::utils::{
     agent_builder::nodes::{
         error_node::ErrorNode,
         prompt_node::{prompt_node, PromptNodeMessage, PromptNodeSettings},
     },
+    clients::ai::llm_usage::LlmUsageScope,
     prompts::generate_sql_prompts::multiple_datasets_prompt::{
         multiple_datasets_system_prompt, multiple_datasets_user_prompt,
     },
@@ -35,6 +37,8 @@ pub struct MultipleDatasetAgentOptions {
     pub datasets: String,
     pub dataset_selector_output: Value,
     pub output_sender: mpsc::Sender<Value>,
+    pub user_id: Uuid,
+    pub usage_scope: LlmUsageScope,
 }
 
 pub async fn handle_multiple_datasets_agent(
@@ -48,6 +52,8 @@ pub async fn handle_multiple_datasets_agent(
         stream: Some(options.output_sender.clone()),
         stream_name: Some("dataset_breakout".to_string()),
         prompt_name: "multiple_datasets_response".to_string(),
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
 
@@ -16,7 +16,11 @@ use crate::{
             error_node::ErrorNode,
             prompt_node::{prompt_node, PromptNodeMessage, PromptNodeSettings},
         },
-        query_engine::{data_types::DataType, query_engine::query_engine},
+        clients::ai::llm_usage::LlmUsageScope,
+        query_engine::{
+            data_types::DataType, query_cache::QueryCacheMetadata,
+            query_cancellation::QueryContext, query_engine::cached_query_engine,
+        },
     },
 };
 
@@ -29,6 +33,8 @@ pub struct RunAndFixSqlAgentOptions {
     pub thoughts: Thoughts,
     pub start_time: Instant,
     pub output_sender: mpsc::Sender<Value>,
+    pub user_id: Uuid,
+    pub usage_scope: LlmUsageScope,
 }
 
 pub enum RunAndFixSqlAgentError {
@@ -83,7 +89,7 @@ pub async fn run_and_fix_sql_agent(options: RunAndFixSqlAgentOptions) -> Result<
         )
         .await?;
 
-        match fetch_data(&current_sql, &options.dataset_id).await {
+        match fetch_data(&current_sql, &options.dataset_id, &options.user_id).await {
             Ok(result) => {
                 final_result = Some(result);
 
@@ -205,6 +211,8 @@ pub async fn run_and_fix_sql_agent(options: RunAndFixSqlAgentOptions) -> Result<
                         &options.dataset,
                     ),
                     prompt_name: "fix_sql".to_string(),
+                    user_id: options.user_id,
+                    usage_scope: options.usage_scope,
                     ..Default::default()
                 };
 
@@ -264,11 +272,18 @@ pub async fn run_and_fix_sql_agent(options: RunAndFixSqlAgentOptions) -> Result<
 pub struct DataObject {
     pub data: Vec<IndexMap<String, DataType>>,
     pub data_metadata: DataMetadataJsonBody,
+    pub cache: Option<QueryCacheMetadata>,
 }
 
-pub async fn fetch_data(sql: &String, dataset_id: &Uuid) -> Result<DataObject, ErrorNode> {
-    let data = match query_engine(&dataset_id, &sql).await {
-        Ok(data) => data,
+pub async fn fetch_data(
+    sql: &String,
+    dataset_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<DataObject, ErrorNode> {
+    let context = QueryContext::for_user(None, user_id);
+
+    let (data, cache) = match cached_query_engine(&dataset_id, &sql, &context).await {
+        Ok(results) => results,
         Err(e) => {
             return Err(ErrorNode::new(
                 RunAndFixSqlAgentError::SqlExecutionError.to_string(),
@@ -287,6 +302,7 @@ pub async fn fetch_data(sql: &String, dataset_id: &Uuid) -> Result<DataObject, E
     Ok(DataObject {
         data,
         data_metadata,
+        cache: Some(cache),
     })
 }
 
@@ -1,12 +1,14 @@
 use serde_json::Value;
 use std::fmt;
 use tokio::sync::mpsc;
+use uuid::Uuid;
 
 use crate::utils::{
     agent_builder::nodes::{
         error_node::ErrorNode,
         prompt_node::{prompt_node, PromptNodeMessage, PromptNodeSettings},
     },
+    clients::ai::llm_usage::LlmUsageScope,
     prompts::sql_evaluator_prompts::{
         sql_evaluation_summary_prompts::{
             sql_evaluation_summary_system_prompt, sql_evaluation_summary_user_prompt,
@@ -57,6 +59,8 @@ pub struct SqlEvaluationAgentOptions {
     pub sql: String,
     pub datasets: String,
     pub output_sender: mpsc::Sender<Value>,
+    pub user_id: Uuid,
+    pub usage_scope: LlmUsageScope,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -75,6 +79,8 @@ pub async fn sql_evaluation_agent(
         messages: create_sql_evaluation_messages(&options.request, &options.sql, &options.datasets),
         prompt_name: "sql_evaluation".to_string(),
         json_schema: Some(sql_evaluation_json_schema()),
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
 
@@ -106,6 +112,8 @@ pub async fn sql_evaluation_agent(
     let evaluation_summary_options = PromptNodeSettings {
         messages: create_sql_evaluation_summary_messages(&score, &evaluation_obj),
         prompt_name: "sql_evaluation_summary".to_string(),
+        user_id: options.user_id,
+        usage_scope: options.usage_scope,
         ..Default::default()
     };
 
@@ -0,0 +1,289 @@
+use std::net::IpAddr;
+
+use anyhow::{anyhow, Result};
+use axum::http::{header, HeaderMap, Method};
+use chrono::{DateTime, Utc};
+use diesel::{insert_into, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    database::{
+        enums::{AuditAction, AuditTargetType},
+        lib::get_pg_pool,
+        models::AuditEvent,
+        schema::audit_events,
+    },
+    routes::ws::ws_router::WsRoutes,
+    utils::{
+        clients::sentry_utils::send_sentry_error, security::api_keys::client_ip,
+        user::user_info::get_user_organization_id,
+    },
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditRequestSource {
+    Rest,
+    Ws,
+}
+
+/// Where a change came from. The auth middleware adds one of these to every REST request, and
+/// websocket handlers build one from their route.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditRequestMetadata {
+    pub source: AuditRequestSource,
+    pub method: Option<String>,
+    pub route: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub api_key_id: Option<Uuid>,
+}
+
+impl AuditRequestMetadata {
+    pub fn rest(
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+        peer: Option<IpAddr>,
+        api_key_id: Option<Uuid>,
+    ) -> Self {
+        AuditRequestMetadata {
+            source: AuditRequestSource::Rest,
+            method: Some(method.to_string()),
+            route: path.to_string(),
+            ip: client_ip(headers, peer),
+            user_agent: headers
+                .get(header::USER_AGENT)
+                .and_then(|user_agent| user_agent.to_str().ok())
+                .map(|user_agent| user_agent.to_string()),
+            api_key_id,
+        }
+    }
+
+    pub fn ws(route: WsRoutes) -> Self {
+        let route = match serde_json::to_value(route) {
+            Ok(Value::String(route)) => route,
+            _ => String::new(),
+        };
+
+        AuditRequestMetadata {
+            source: AuditRequestSource::Ws,
+            method: None,
+            route,
+            ip: None,
+            user_agent: None,
+            api_key_id: None,
+        }
+    }
+}
+
+pub struct NewAuditEvent {
+    pub actor_id: Uuid,
+    pub action: AuditAction,
+    pub target_type: AuditTargetType,
+    pub target_id: Option<Uuid>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub request_metadata: AuditRequestMetadata,
+}
+
+impl NewAuditEvent {
+    /// Ad hoc SQL is audited whether or not the query succeeds.
+    pub fn sql_run(
+        actor_id: Uuid,
+        sql: &str,
+        dataset_id: Option<Uuid>,
+        data_source_id: Option<Uuid>,
+        request_metadata: AuditRequestMetadata,
+    ) -> Self {
+        let (target_type, target_id) = match (dataset_id, data_source_id) {
+            (_, Some(data_source_id)) => (AuditTargetType::DataSource, Some(data_source_id)),
+            (dataset_id, None) => (AuditTargetType::Dataset, dataset_id),
+        };
+
+        NewAuditEvent {
+            actor_id,
+            action: AuditAction::SqlRun,
+            target_type,
+            target_id,
+            before: None,
+            after: Some(json!({ "sql": sql })),
+            request_metadata,
+        }
+    }
+}
+
+pub async fn record_audit_event(event: NewAuditEvent) -> Result<()> {
+    let organization_id = get_user_organization_id(&event.actor_id).await?;
+
+    let request_metadata = match serde_json::to_value(&event.request_metadata) {
+        Ok(request_metadata) => request_metadata,
+        Err(e) => return Err(anyhow!("Error serializing request metadata: {}", e)),
+    };
+
+    let audit_event = AuditEvent {
+        id: Uuid::new_v4(),
+        organization_id,
+        actor_id: event.actor_id,
+        action: event.action,
+        target_type: event.target_type,
+        target_id: event.target_id,
+        before: event.before,
+        after: event.after,
+        request_metadata,
+        created_at: Utc::now(),
+    };
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match insert_into(audit_events::table)
+        .values(&audit_event)
+        .execute(&mut conn)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(anyhow!("Error inserting audit event: {}", e)),
+    }
+}
+
+/// Records the event in the background. A failed write is reported but does not fail the change
+/// that is being audited.
+pub fn spawn_audit_event(event: NewAuditEvent) {
+    tokio::spawn(async move {
+        let actor_id = event.actor_id;
+
+        if let Err(e) = record_audit_event(event).await {
+            tracing::error!("Error recording audit event: {}", e);
+            send_sentry_error(&e.to_string(), Some(&actor_id));
+        }
+    });
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct AuditEventFilters {
+    pub actor_id: Option<Uuid>,
+    pub action: Option<AuditAction>,
+    pub target_type: Option<AuditTargetType>,
+    pub target_id: Option<Uuid>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Newest first.
+pub async fn list_audit_events(
+    organization_id: &Uuid,
+    filters: &AuditEventFilters,
+    offset: i64,
+    limit: i64,
+) -> Result<Vec<AuditEvent>> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let mut query = audit_events::table
+        .filter(audit_events::organization_id.eq(organization_id))
+        .into_boxed();
+
+    if let Some(actor_id) = filters.actor_id {
+        query = query.filter(audit_events::actor_id.eq(actor_id));
+    }
+
+    if let Some(action) = filters.action {
+        query = query.filter(audit_events::action.eq(action));
+    }
+
+    if let Some(target_type) = filters.target_type {
+        query = query.filter(audit_events::target_type.eq(target_type));
+    }
+
+    if let Some(target_id) = filters.target_id {
+        query = query.filter(audit_events::target_id.eq(target_id));
+    }
+
+    if let Some(from) = filters.from {
+        query = query.filter(audit_events::created_at.ge(from));
+    }
+
+    if let Some(to) = filters.to {
+        query = query.filter(audit_events::created_at.lt(to));
+    }
+
+    match query
+        .order((audit_events::created_at.desc(), audit_events::id.desc()))
+        .offset(offset)
+        .limit(limit)
+        .load::<AuditEvent>(&mut conn)
+        .await
+    {
+        Ok(audit_events) => Ok(audit_events),
+        Err(e) => Err(anyhow!("Error listing audit events: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::routes::ws::threads_and_messages::threads_router::ThreadRoute;
+
+    #[test]
+    fn ws_metadata_uses_the_route_path() {
+        let metadata = AuditRequestMetadata::ws(WsRoutes::Threads(ThreadRoute::Update));
+
+        assert_eq!(metadata.source, AuditRequestSource::Ws);
+        assert_eq!(metadata.route, "/threads/update");
+        assert_eq!(metadata.method, None);
+    }
+
+    #[test]
+    fn sql_runs_target_the_data_source_they_run_against() {
+        let data_source_id = Uuid::new_v4();
+        let metadata = AuditRequestMetadata::rest(
+            &Method::POST,
+            "/api/v1/sql/run",
+            &HeaderMap::new(),
+            None,
+            None,
+        );
+
+        let event = NewAuditEvent::sql_run(
+            Uuid::new_v4(),
+            "select 1",
+            None,
+            Some(data_source_id),
+            metadata,
+        );
+
+        assert_eq!(event.action, AuditAction::SqlRun);
+        assert_eq!(event.target_type, AuditTargetType::DataSource);
+        assert_eq!(event.target_id, Some(data_source_id));
+        assert_eq!(event.after, Some(json!({ "sql": "select 1" })));
+    }
+
+    #[test]
+    fn rest_metadata_reads_the_client_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "10.0.0.1, 10.0.0.2".parse().unwrap());
+        headers.insert(header::USER_AGENT, "buster-cli/0.1".parse().unwrap());
+
+        let metadata = AuditRequestMetadata::rest(
+            &Method::POST,
+            "/api/v1/datasets/deploy",
+            &headers,
+            "203.0.113.7".parse().ok(),
+            None,
+        );
+
+        assert_eq!(metadata.source, AuditRequestSource::Rest);
+        assert_eq!(metadata.method.as_deref(), Some("POST"));
+        assert_eq!(metadata.ip.as_deref(), Some("203.0.113.7"));
+        assert_eq!(metadata.user_agent.as_deref(), Some("buster-cli/0.1"));
+    }
+}
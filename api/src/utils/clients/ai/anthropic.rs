@@ -8,6 +8,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::utils::clients::sentry_utils::send_sentry_error;
 
+use super::llm_retries::{llm_error_for_status, llm_send_error};
+
 const ANTHROPIC_CHAT_URL: &str = "https://api.anthropic.com/v1/messages";
 
 lazy_static::lazy_static! {
@@ -19,7 +21,7 @@ lazy_static::lazy_static! {
         .expect("MONITORING_ENABLED must be a boolean");
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum AnthropicChatModel {
     #[serde(rename = "claude-3-opus-20240229")]
     Claude3Opus20240229,
@@ -120,12 +122,14 @@ pub async fn anthropic_chat(
         Ok(response) => response,
         Err(e) => {
             tracing::error!("Unable to send request to Anthropic: {:?}", e);
-            let err = anyhow!("Unable to send request to Anthropic: {}", e);
+            let err = llm_send_error("Anthropic", e);
             send_sentry_error(&err.to_string(), None);
             return Err(err);
         }
     };
 
+    let response = llm_error_for_status("Anthropic", response).await?;
+
     let completion_res = match response.json::<ChatCompletionResponse>().await {
         Ok(res) => res,
         Err(e) => {
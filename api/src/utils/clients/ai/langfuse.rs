@@ -11,7 +11,10 @@ use uuid::Uuid;
 
 use crate::utils::clients::sentry_utils::send_sentry_error;
 
-use super::{anthropic::AnthropicChatModel, llm_router::LlmModel, openai::OpenAiChatModel};
+use super::{
+    anthropic::AnthropicChatModel, llm_retries::LlmFailure, llm_router::LlmModel,
+    openai::OpenAiChatModel,
+};
 
 lazy_static::lazy_static! {
     static ref LANGFUSE_API_URL: String = env::var("LANGFUSE_API_URL").unwrap_or("https://us.cloud.langfuse.com".to_string());
@@ -63,6 +66,14 @@ impl LlmModel {
                 total_cost: (input_token.len() as f64 / 1_000_000.0) * 3.0
                     + (output_token.len() as f64 / 1_000_000.0) * 15.0,
             },
+            LlmModel::Ollama(_) | LlmModel::OpenAiCompatible(_) => Usage {
+                input: input_token.len() as u32,
+                output: output_token.len() as u32,
+                unit: "TOKENS".to_string(),
+                input_cost: 0.0,
+                output_cost: 0.0,
+                total_cost: 0.0,
+            },
         }
     }
 }
@@ -92,11 +103,12 @@ pub enum PromptName {
     GenerateColDescriptions,
     GenerateDatasetDescription,
     SummaryQuestion,
+    RerankDatasets,
     CustomPrompt(String),
 }
 
 impl PromptName {
-    fn to_string(&self) -> String {
+    pub fn to_string(&self) -> String {
         match self {
             PromptName::SelectDataset => "select_dataset".to_string(),
             PromptName::GenerateSql => "generate_sql".to_string(),
@@ -120,6 +132,7 @@ impl PromptName {
             PromptName::GenerateColDescriptions => "generate_col_descriptions".to_string(),
             PromptName::GenerateDatasetDescription => "generate_dataset_description".to_string(),
             PromptName::SummaryQuestion => "summary_question".to_string(),
+            PromptName::RerankDatasets => "rerank_datasets".to_string(),
             PromptName::CustomPrompt(prompt) => prompt.clone(),
         }
     }
@@ -168,16 +181,20 @@ struct GenerationCreateBody {
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Usage {
-    input: u32,
-    output: u32,
-    unit: String,
-    input_cost: f64,
-    output_cost: f64,
-    total_cost: f64,
+    pub input: u32,
+    pub output: u32,
+    pub unit: String,
+    pub input_cost: f64,
+    pub output_cost: f64,
+    pub total_cost: f64,
 }
 
 #[derive(Serialize, Debug)]
-struct Metadata {}
+struct Metadata {
+    /// Tries that failed before the reply, including any on a model that was failed over from.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failures: Vec<LlmFailure>,
+}
 
 #[derive(Serialize)]
 #[serde(untagged)]
@@ -224,6 +241,7 @@ struct LangfuseResponse {
 
 /// Args:
 /// - session_id: this can be a thread_id or any other type of chain event we have
+/// - failures: retries and fallbacks it took to get the reply, traced as a warning
 ///
 
 pub async fn send_langfuse_request(
@@ -236,6 +254,7 @@ pub async fn send_langfuse_request(
     output: String,
     user_id: &Uuid,
     langfuse_model: &LlmModel,
+    failures: Vec<LlmFailure>,
 ) -> () {
     let session_id = session_id.clone();
     let user_id = user_id.clone();
@@ -252,6 +271,7 @@ pub async fn send_langfuse_request(
             output,
             user_id,
             langfuse_model,
+            failures,
         )
         .await
         {
@@ -274,6 +294,7 @@ async fn langfuse_handler(
     output: String,
     user_id: Uuid,
     langfuse_model: LlmModel,
+    failures: Vec<LlmFailure>,
 ) -> Result<()> {
     let input = match context {
         Some(context) => format!("{} \n\n {}", context, input),
@@ -282,6 +303,11 @@ async fn langfuse_handler(
 
     let trace_id = Uuid::new_v4();
 
+    let level = match failures.is_empty() {
+        true => "DEBUG",
+        false => "WARNING",
+    };
+
     let langfuse_trace = LangfuseBatchItem {
         id: Uuid::new_v4(),
         r#type: LangfuseIngestionType::TraceCreate,
@@ -295,7 +321,7 @@ async fn langfuse_handler(
             session_id: session_id,
             release: "1.0.0".to_string(),
             version: "1.0.0".to_string(),
-            metadata: Metadata {},
+            metadata: Metadata { failures },
             tags: vec![],
             public: false,
         }),
@@ -313,7 +339,7 @@ async fn langfuse_handler(
             trace_id,
             start_time,
             completion_start_time: start_time,
-            level: "DEBUG".to_string(),
+            level: level.to_string(),
             end_time,
             model: langfuse_model.clone(),
             usage: langfuse_model.generate_usage(&input, &output),
@@ -0,0 +1,219 @@
+use anyhow::{anyhow, Result};
+use diesel::{ExpressionMethods, JoinOnDsl, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    database::{
+        lib::get_pg_pool,
+        models::LlmProvider,
+        schema::{llm_providers, users_to_organizations},
+    },
+    utils::clients::supabase_vault::read_secret,
+};
+
+use super::{
+    llm_router::LlmModel, ollama::OllamaChatModel, openai_compatible::OpenAiCompatibleChatModel,
+};
+
+/// A model an organization runs itself, stored in `llm_providers.config`. Every prompt for the
+/// organization's users goes to it instead of the hosted models.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum LlmProviderConfig {
+    Ollama {
+        /// E.g. `http://ollama:11434`.
+        base_url: String,
+        model: String,
+        #[serde(default)]
+        structured_output: StructuredOutput,
+    },
+    /// Anything that serves OpenAI's chat completions API, such as vLLM, LM Studio or the
+    /// llama.cpp server.
+    OpenAiCompatible {
+        /// The URL `/chat/completions` is under, e.g. `http://vllm:8000/v1`.
+        base_url: String,
+        model: String,
+        #[serde(default)]
+        structured_output: StructuredOutput,
+    },
+}
+
+/// How much of structured output a server supports. Whatever it can't enforce is asked for in
+/// the system prompt instead.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StructuredOutput {
+    /// Replies are constrained to the JSON schema.
+    #[default]
+    JsonSchema,
+    /// Replies are constrained to JSON but not to a schema.
+    JsonObject,
+    /// Nothing is constrained.
+    None,
+}
+
+/// The JSON a prompt's reply has to be in, as far as the server can enforce it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonFormat {
+    Any,
+    Object,
+    /// The bare schema, without the `name` OpenAI wants around it.
+    Schema(Value),
+}
+
+impl StructuredOutput {
+    /// Works out what to ask the server for, along with instructions for the system prompt when
+    /// the server can't enforce everything itself.
+    pub fn json_format(
+        &self,
+        json_mode: bool,
+        json_schema: Option<&Value>,
+    ) -> (JsonFormat, Option<String>) {
+        let schema = json_schema.map(|json_schema| match json_schema.get("schema") {
+            Some(schema) => schema.clone(),
+            None => json_schema.clone(),
+        });
+
+        match (self, schema) {
+            (StructuredOutput::JsonSchema, Some(schema)) => (JsonFormat::Schema(schema), None),
+            (StructuredOutput::JsonObject, Some(schema)) => (
+                JsonFormat::Object,
+                Some(format!(
+                    "Respond with a JSON object that matches this JSON schema:\n{}",
+                    schema
+                )),
+            ),
+            (StructuredOutput::None, Some(schema)) => (
+                JsonFormat::Any,
+                Some(format!(
+                    "Respond with only a JSON object that matches this JSON schema, without any \
+                     other text:\n{}",
+                    schema
+                )),
+            ),
+            (StructuredOutput::JsonSchema | StructuredOutput::JsonObject, None) if json_mode => {
+                (JsonFormat::Object, None)
+            }
+            (StructuredOutput::None, None) if json_mode => (
+                JsonFormat::Any,
+                Some("Respond with only a JSON object, without any other text.".to_string()),
+            ),
+            (_, None) => (JsonFormat::Any, None),
+        }
+    }
+}
+
+/// Pulls the JSON out of a reply from a model that wasn't held to it, which tend to wrap it in a
+/// code fence or a sentence.
+pub fn extract_json(response: &str) -> String {
+    let start = response.find(['{', '[']);
+    let end = response.rfind(['}', ']']);
+
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => response[start..=end].to_string(),
+        _ => response.trim().to_string(),
+    }
+}
+
+/// The model the user's organization runs itself, if it has one.
+pub async fn organization_llm_model(user_id: &Uuid) -> Result<Option<LlmModel>> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let llm_provider = match llm_providers::table
+        .inner_join(
+            users_to_organizations::table
+                .on(users_to_organizations::organization_id.eq(llm_providers::organization_id)),
+        )
+        .filter(users_to_organizations::user_id.eq(user_id))
+        .filter(users_to_organizations::deleted_at.is_null())
+        .filter(llm_providers::deleted_at.is_null())
+        .select(llm_providers::all_columns)
+        .first::<LlmProvider>(&mut conn)
+        .await
+    {
+        Ok(llm_provider) => llm_provider,
+        Err(diesel::NotFound) => return Ok(None),
+        Err(e) => return Err(anyhow!("Error getting LLM provider: {}", e)),
+    };
+
+    let config = match serde_json::from_value::<LlmProviderConfig>(llm_provider.config) {
+        Ok(config) => config,
+        Err(e) => return Err(anyhow!("Invalid LLM provider config: {}", e)),
+    };
+
+    let api_key = match llm_provider.api_key_id {
+        Some(api_key_id) => Some(read_secret(&api_key_id).await?),
+        None => None,
+    };
+
+    let model = match config {
+        LlmProviderConfig::Ollama {
+            base_url,
+            model,
+            structured_output,
+        } => LlmModel::Ollama(OllamaChatModel {
+            base_url,
+            model,
+            api_key,
+            structured_output,
+        }),
+        LlmProviderConfig::OpenAiCompatible {
+            base_url,
+            model,
+            structured_output,
+        } => LlmModel::OpenAiCompatible(OpenAiCompatibleChatModel {
+            base_url,
+            model,
+            api_key,
+            structured_output,
+        }),
+    };
+
+    Ok(Some(model))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn falls_back_to_prompting_for_what_the_server_cant_enforce() {
+        let json_schema = json!({"name": "answer", "schema": {"type": "object"}});
+
+        assert_eq!(
+            StructuredOutput::JsonSchema.json_format(true, Some(&json_schema)),
+            (JsonFormat::Schema(json!({"type": "object"})), None)
+        );
+
+        let (json_format, instructions) =
+            StructuredOutput::JsonObject.json_format(true, Some(&json_schema));
+        assert_eq!(json_format, JsonFormat::Object);
+        assert!(instructions.unwrap().contains(r#"{"type":"object"}"#));
+
+        let (json_format, instructions) = StructuredOutput::None.json_format(true, None);
+        assert_eq!(json_format, JsonFormat::Any);
+        assert!(instructions.is_some());
+
+        assert_eq!(
+            StructuredOutput::None.json_format(false, None),
+            (JsonFormat::Any, None)
+        );
+    }
+
+    #[test]
+    fn extracts_json_from_chatty_replies() {
+        assert_eq!(
+            extract_json("Sure! Here it is:\n```json\n{\"dataset\": \"orders\"}\n```"),
+            r#"{"dataset": "orders"}"#
+        );
+        assert_eq!(extract_json("  [1, 2]  "), "[1, 2]");
+        assert_eq!(extract_json(" no json "), "no json");
+    }
+}
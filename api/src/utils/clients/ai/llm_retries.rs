@@ -0,0 +1,409 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use rand::Rng;
+use reqwest::{header::RETRY_AFTER, Response};
+use serde::Serialize;
+
+use super::llm_router::LlmModel;
+
+/// Tries per model, counting the first.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// The backoff before the first retry, doubling for each one after.
+const BASE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// A provider asking us to wait longer than this is failed over instead.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Failed tries in a row before a provider's circuit opens.
+const CIRCUIT_FAILURE_THRESHOLD: u32 = 5;
+
+/// How long an open circuit turns calls away before letting one through to test the provider.
+const CIRCUIT_COOLDOWN: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    static ref CIRCUITS: Mutex<HashMap<String, Circuit>> = Mutex::new(HashMap::new());
+}
+
+/// Why a provider didn't answer. Other errors, like a reply that can't be parsed, are ours and
+/// aren't retried.
+#[derive(Debug)]
+pub enum LlmRequestError {
+    /// No response came back, e.g. the connection failed or timed out.
+    Send { provider: String, error: String },
+    Status {
+        provider: String,
+        status: u16,
+        retry_after: Option<Duration>,
+        body: String,
+    },
+    /// The provider has been failing, so it wasn't called.
+    CircuitOpen { provider: String },
+}
+
+impl fmt::Display for LlmRequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LlmRequestError::Send { provider, error } => {
+                write!(f, "Unable to send request to {}: {}", provider, error)
+            }
+            LlmRequestError::Status {
+                provider,
+                status,
+                body,
+                ..
+            } => write!(f, "{} returned {}: {}", provider, status, body),
+            LlmRequestError::CircuitOpen { provider } => {
+                write!(f, "{} is failing, its circuit is open", provider)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LlmRequestError {}
+
+impl LlmRequestError {
+    /// Whether the provider is at fault, as opposed to the request.
+    fn is_provider_failure(&self) -> bool {
+        match self {
+            LlmRequestError::Send { .. } => true,
+            LlmRequestError::Status { status, .. } => {
+                *status == 408 || *status == 429 || *status >= 500
+            }
+            LlmRequestError::CircuitOpen { .. } => false,
+        }
+    }
+}
+
+pub fn llm_send_error(provider: &str, error: reqwest::Error) -> anyhow::Error {
+    anyhow!(LlmRequestError::Send {
+        provider: provider.to_string(),
+        error: error.to_string(),
+    })
+}
+
+/// Passes successful responses through and turns the rest into an `LlmRequestError`.
+pub async fn llm_error_for_status(provider: &str, response: Response) -> Result<Response> {
+    let status = response.status();
+
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_retry_after(value, Utc::now()));
+
+    Err(anyhow!(LlmRequestError::Status {
+        provider: provider.to_string(),
+        status: status.as_u16(),
+        retry_after,
+        body: response.text().await.unwrap_or_default(),
+    }))
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    match DateTime::parse_from_rfc2822(value.trim()) {
+        Ok(date) => Some(
+            (date.with_timezone(&Utc) - now)
+                .to_std()
+                .unwrap_or(Duration::ZERO),
+        ),
+        Err(_) => None,
+    }
+}
+
+/// Exponential backoff with jitter, so callers that failed together don't retry together.
+fn backoff(attempt: u32) -> Duration {
+    let delay = BASE_RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_RETRY_DELAY);
+
+    delay / 2 + delay.mul_f64(rand::thread_rng().gen_range(0.0..0.5))
+}
+
+/// How long to wait before trying again after `error`, or `None` if it shouldn't be retried.
+fn retry_delay(error: &anyhow::Error, attempt: u32) -> Option<Duration> {
+    let error = match error.downcast_ref::<LlmRequestError>() {
+        Some(error) if error.is_provider_failure() => error,
+        _ => return None,
+    };
+
+    match error {
+        LlmRequestError::Status {
+            retry_after: Some(retry_after),
+            ..
+        } if *retry_after > MAX_RETRY_AFTER => None,
+        LlmRequestError::Status {
+            retry_after: Some(retry_after),
+            ..
+        } => Some(*retry_after),
+        _ => Some(backoff(attempt)),
+    }
+}
+
+#[derive(Debug, Default)]
+struct Circuit {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl Circuit {
+    /// Once the cooldown is over calls are let through again. If the next one fails the
+    /// circuit opens straight back up, since the failures in a row are still over the threshold.
+    fn allows(&self, now: Instant) -> bool {
+        match self.open_until {
+            Some(open_until) => now >= open_until,
+            None => true,
+        }
+    }
+
+    fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.open_until = None;
+    }
+
+    fn record_failure(&mut self, now: Instant) {
+        self.consecutive_failures += 1;
+
+        if self.consecutive_failures >= CIRCUIT_FAILURE_THRESHOLD {
+            self.open_until = Some(now + CIRCUIT_COOLDOWN);
+        }
+    }
+}
+
+impl LlmModel {
+    /// The provider whose circuit the model's calls go through.
+    fn circuit_key(&self) -> String {
+        match self {
+            LlmModel::OpenAi(_) => "openai".to_string(),
+            LlmModel::Anthropic(_) => "anthropic".to_string(),
+            LlmModel::Ollama(model) => format!("ollama:{}", model.base_url),
+            LlmModel::OpenAiCompatible(model) => format!("openai_compatible:{}", model.base_url),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(name)) => name,
+            _ => "unknown".to_string(),
+        }
+    }
+}
+
+fn circuit_allows(key: &str) -> bool {
+    match CIRCUITS.lock() {
+        Ok(circuits) => circuits
+            .get(key)
+            .map_or(true, |circuit| circuit.allows(Instant::now())),
+        Err(_) => true,
+    }
+}
+
+fn record_call(key: &str, provider_failed: bool) {
+    if let Ok(mut circuits) = CIRCUITS.lock() {
+        let circuit = circuits.entry(key.to_string()).or_default();
+
+        match provider_failed {
+            true => circuit.record_failure(Instant::now()),
+            false => circuit.record_success(),
+        }
+    }
+}
+
+/// A try that didn't get a reply, kept for the trace.
+#[derive(Serialize, Debug, Clone)]
+pub struct LlmFailure {
+    pub model: String,
+    pub error: String,
+}
+
+impl LlmFailure {
+    pub fn new(model: &LlmModel, error: &anyhow::Error) -> Self {
+        Self {
+            model: model.name(),
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Makes a call to a model, retrying it while the provider is failing. Calls are only made
+/// while the provider's circuit is closed. Every failed try is added to `failures`.
+pub async fn call_with_retries<T, F, Fut>(
+    model: &LlmModel,
+    failures: &mut Vec<LlmFailure>,
+    mut call: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let key = model.circuit_key();
+    let mut attempt = 0;
+
+    loop {
+        if !circuit_allows(&key) {
+            let error = anyhow!(LlmRequestError::CircuitOpen {
+                provider: key.clone()
+            });
+            failures.push(LlmFailure::new(model, &error));
+            return Err(error);
+        }
+
+        let error = match call().await {
+            Ok(response) => {
+                record_call(&key, false);
+                return Ok(response);
+            }
+            Err(error) => error,
+        };
+
+        let provider_failed = error
+            .downcast_ref::<LlmRequestError>()
+            .is_some_and(|error| error.is_provider_failure());
+        record_call(&key, provider_failed);
+
+        failures.push(LlmFailure::new(model, &error));
+        attempt += 1;
+
+        match retry_delay(&error, attempt - 1) {
+            Some(delay) if attempt < MAX_ATTEMPTS => {
+                tracing::warn!("Retrying {} in {:?} after: {}", model.name(), delay, error);
+                tokio::time::sleep(delay).await;
+            }
+            _ => return Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::clients::ai::openai::OpenAiChatModel;
+    use chrono::TimeZone;
+
+    fn status_error(status: u16, retry_after: Option<Duration>) -> anyhow::Error {
+        anyhow!(LlmRequestError::Status {
+            provider: "OpenAI".to_string(),
+            status,
+            retry_after,
+            body: String::new(),
+        })
+    }
+
+    #[test]
+    fn reads_retry_after_seconds_and_dates() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 21, 12, 0, 0).unwrap();
+
+        assert_eq!(parse_retry_after("7", now), Some(Duration::from_secs(7)));
+        assert_eq!(
+            parse_retry_after("Tue, 21 Jan 2025 12:00:20 GMT", now),
+            Some(Duration::from_secs(20))
+        );
+        assert_eq!(
+            parse_retry_after("Tue, 21 Jan 2025 11:59:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn only_retries_provider_failures() {
+        assert_eq!(
+            retry_delay(&status_error(429, Some(Duration::from_secs(2))), 0),
+            Some(Duration::from_secs(2))
+        );
+        assert!(retry_delay(&status_error(503, None), 0).is_some());
+        assert!(retry_delay(&status_error(429, Some(MAX_RETRY_AFTER * 2)), 0).is_none());
+        assert!(retry_delay(&status_error(400, None), 0).is_none());
+        assert!(retry_delay(&anyhow!("Unable to parse response"), 0).is_none());
+    }
+
+    #[test]
+    fn backs_off_with_jitter() {
+        for attempt in 0..10 {
+            let delay = backoff(attempt);
+            let ceiling = BASE_RETRY_DELAY
+                .saturating_mul(2u32.saturating_pow(attempt))
+                .min(MAX_RETRY_DELAY);
+
+            assert!(delay >= ceiling / 2 && delay <= ceiling);
+        }
+    }
+
+    #[test]
+    fn circuits_open_after_repeated_failures() {
+        let now = Instant::now();
+        let mut circuit = Circuit::default();
+
+        for _ in 0..CIRCUIT_FAILURE_THRESHOLD - 1 {
+            circuit.record_failure(now);
+        }
+        assert!(circuit.allows(now));
+
+        circuit.record_failure(now);
+        assert!(!circuit.allows(now));
+        assert!(circuit.allows(now + CIRCUIT_COOLDOWN));
+
+        circuit.record_success();
+        assert!(circuit.allows(now));
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_errors_that_are_not_the_providers() {
+        let model = LlmModel::OpenAi(OpenAiChatModel::Gpt4oMini);
+        let mut failures = Vec::new();
+        let mut calls = 0;
+
+        let result = call_with_retries(&model, &mut failures, || {
+            calls += 1;
+            async { Err::<String, _>(status_error(400, None)) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].model, "gpt-4o-mini");
+    }
+
+    #[tokio::test]
+    async fn retries_until_the_provider_answers() {
+        let model = LlmModel::OpenAi(OpenAiChatModel::Gpt35Turbo);
+        let mut failures = Vec::new();
+        let mut calls = 0;
+
+        let result = call_with_retries(&model, &mut failures, || {
+            calls += 1;
+            let calls = calls;
+            async move {
+                match calls {
+                    1 => Err(status_error(429, Some(Duration::ZERO))),
+                    _ => Ok("SELECT 1".to_string()),
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), "SELECT 1");
+        assert_eq!(failures.len(), 1);
+    }
+}
@@ -17,10 +17,19 @@ use super::{
         AnthropicChatRole, AnthropicContent, AnthropicContentType,
     },
     langfuse::{send_langfuse_request, PromptName},
+    llm_providers::{extract_json, JsonFormat},
+    llm_retries::{call_with_retries, LlmFailure},
+    llm_routes::route_llm_call,
+    llm_usage::{check_llm_budget, record_llm_usage, LlmBudgetStatus, LlmUsageScope},
+    ollama::{ollama_chat, ollama_chat_stream, OllamaChatMessage, OllamaChatModel, OllamaChatRole},
     openai::{
         openai_chat, openai_chat_stream, OpenAiChatContent, OpenAiChatMessage, OpenAiChatModel,
         OpenAiChatRole,
     },
+    openai_compatible::{
+        openai_compatible_chat, openai_compatible_chat_stream, OpenAiCompatibleChatMessage,
+        OpenAiCompatibleChatModel,
+    },
 };
 use lazy_static::lazy_static;
 
@@ -36,6 +45,43 @@ lazy_static! {
 pub enum LlmModel {
     Anthropic(AnthropicChatModel),
     OpenAi(OpenAiChatModel),
+    Ollama(OllamaChatModel),
+    OpenAiCompatible(OpenAiCompatibleChatModel),
+}
+
+impl LlmModel {
+    /// Models an organization runs itself. Prompts to them aren't traced, since they hold
+    /// warehouse schemas the organization keeps inside its network.
+    pub fn is_self_hosted(&self) -> bool {
+        matches!(self, LlmModel::Ollama(_) | LlmModel::OpenAiCompatible(_))
+    }
+
+    /// Looks a hosted model up by the name its provider gives it, e.g. `gpt-4o-mini`.
+    pub fn from_name(name: &str) -> Option<LlmModel> {
+        let name = Value::String(name.to_string());
+
+        match serde_json::from_value::<OpenAiChatModel>(name.clone()) {
+            Ok(model) => Some(LlmModel::OpenAi(model)),
+            Err(_) => serde_json::from_value::<AnthropicChatModel>(name)
+                .ok()
+                .map(LlmModel::Anthropic),
+        }
+    }
+}
+
+/// Hosted models are given room for long replies unless a route caps them.
+const HOSTED_MAX_TOKENS: u32 = 7048;
+
+/// A prompt as it is sent to whichever model it is routed to.
+struct LlmChatCall<'a> {
+    messages: &'a Vec<LlmMessage>,
+    temperature: f32,
+    max_tokens: u32,
+    hosted_max_tokens: u32,
+    timeout: u64,
+    stop: Option<Vec<String>>,
+    json_mode: bool,
+    json_schema: Option<Value>,
 }
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -75,27 +121,40 @@ pub async fn llm_chat(
     session_id: &Uuid,
     user_id: &Uuid,
     prompt_name: PromptName,
+    usage_scope: LlmUsageScope,
 ) -> Result<String> {
+    enforce_llm_budget(user_id).await?;
+
+    let route = route_llm_call(user_id, &prompt_name, model, temperature).await?;
+
+    let call = LlmChatCall {
+        messages,
+        temperature: route.temperature,
+        max_tokens: route.max_tokens.unwrap_or(max_tokens),
+        hosted_max_tokens: route.max_tokens.unwrap_or(HOSTED_MAX_TOKENS),
+        timeout,
+        stop,
+        json_mode,
+        json_schema,
+    };
+
     let start_time = Utc::now();
 
-    let response_result = match &model {
-        LlmModel::Anthropic(model) => {
-            anthropic_chat_compiler(model, messages, max_tokens, temperature, timeout, stop).await
-        }
-        LlmModel::OpenAi(model) => {
-            openai_chat_compiler(
-                model,
-                messages,
-                7048,
-                temperature,
-                timeout,
-                stop,
-                json_mode,
-                json_schema,
-            )
-            .await
+    let mut failures = Vec::new();
+    let mut model = route.model;
+    let mut response_result =
+        call_with_retries(&model, &mut failures, || chat_with_model(&model, &call)).await;
+
+    if let Some(fallback_model) = route.fallback_model {
+        if let Err(e) = &response_result {
+            tracing::warn!("LLM chat failed, trying the fallback model: {}", e);
+            response_result = call_with_retries(&fallback_model, &mut failures, || {
+                chat_with_model(&fallback_model, &call)
+            })
+            .await;
+            model = fallback_model;
         }
-    };
+    }
 
     let response = match response_result {
         Ok(response) => response,
@@ -104,19 +163,35 @@ pub async fn llm_chat(
 
     let end_time = Utc::now();
 
-    send_langfuse_request(
-        session_id,
-        prompt_name,
-        None,
-        start_time,
-        end_time,
-        serde_json::to_string(&messages).unwrap(),
-        serde_json::to_string(&response).unwrap(),
+    let input = serde_json::to_string(&messages).unwrap();
+    let output = serde_json::to_string(&response).unwrap();
+
+    record_llm_usage(
         user_id,
+        &prompt_name,
         &model,
+        input.clone(),
+        output.clone(),
+        usage_scope,
     )
     .await;
 
+    if !model.is_self_hosted() {
+        send_langfuse_request(
+            session_id,
+            prompt_name,
+            None,
+            start_time,
+            end_time,
+            input,
+            output,
+            user_id,
+            &model,
+            failures,
+        )
+        .await;
+    }
+
     Ok(response)
 }
 
@@ -130,19 +205,48 @@ pub async fn llm_chat_stream(
     session_id: &Uuid,
     user_id: &Uuid,
     prompt_name: PromptName,
+    usage_scope: LlmUsageScope,
 ) -> Result<(Receiver<String>, JoinHandle<Result<String>>)> {
+    enforce_llm_budget(user_id).await?;
+
+    let route = route_llm_call(user_id, &prompt_name, model, temperature).await?;
+    let max_tokens = route.max_tokens.unwrap_or(max_tokens);
+
     let start_time = Utc::now();
 
-    let stream_result = match &model {
-        LlmModel::Anthropic(model) => {
-            anthropic_chat_stream_compiler(model, &messages, max_tokens, temperature, timeout, stop)
-                .await
-        }
-        LlmModel::OpenAi(model) => {
-            openai_chat_stream_compiler(model, &messages, max_tokens, temperature, timeout, stop)
-                .await
+    let temperature = route.temperature;
+    let mut fallback_model = route.fallback_model;
+    let mut failures = Vec::new();
+    let mut model = route.model;
+    let mut stream_result = call_with_retries(&model, &mut failures, || {
+        stream_with_model(
+            &model,
+            &messages,
+            max_tokens,
+            temperature,
+            timeout,
+            stop.clone(),
+        )
+    })
+    .await;
+
+    if let Err(e) = &stream_result {
+        if let Some(next_model) = fallback_model.take() {
+            tracing::warn!("LLM chat stream failed, trying the fallback model: {}", e);
+            stream_result = call_with_retries(&next_model, &mut failures, || {
+                stream_with_model(
+                    &next_model,
+                    &messages,
+                    max_tokens,
+                    temperature,
+                    timeout,
+                    stop.clone(),
+                )
+            })
+            .await;
+            model = next_model;
         }
-    };
+    }
 
     let mut stream = match stream_result {
         Ok(stream) => stream,
@@ -157,30 +261,85 @@ pub async fn llm_chat_stream(
 
         tokio::spawn(async move {
             let mut response = String::new();
-            while let Some(content) = stream.next().await {
-                response.push_str(&content);
+            let mut reopened = false;
 
-                match tx.send(content).await {
-                    Ok(_) => (),
-                    Err(e) => return Err(anyhow!("Streaming Error: {}", e)),
+            loop {
+                while let Some(content) = stream.next().await {
+                    response.push_str(&content);
+
+                    match tx.send(content).await {
+                        Ok(_) => (),
+                        Err(e) => return Err(anyhow!("Streaming Error: {}", e)),
+                    }
+                }
+
+                // A stream that ends without a token dropped before it got going, so it can be
+                // opened again, on the fallback model if there is one, without the caller
+                // noticing. One that drops partway keeps what it sent.
+                if !response.is_empty() || reopened {
+                    break;
                 }
+
+                reopened = true;
+                failures.push(LlmFailure::new(
+                    &model,
+                    &anyhow!("Stream ended without a reply"),
+                ));
+
+                if let Some(next_model) = fallback_model.take() {
+                    model = next_model;
+                }
+
+                tracing::warn!("LLM chat stream ended without a reply, reopening it");
+
+                stream = match call_with_retries(&model, &mut failures, || {
+                    stream_with_model(
+                        &model,
+                        &messages,
+                        max_tokens,
+                        temperature,
+                        timeout,
+                        stop.clone(),
+                    )
+                })
+                .await
+                {
+                    Ok(stream) => stream,
+                    Err(e) => return Err(anyhow!("LLM chat error: {}", e)),
+                };
             }
 
             let end_time = Utc::now();
 
-            send_langfuse_request(
-                &session_id,
-                prompt_name,
-                None,
-                start_time,
-                end_time,
-                serde_json::to_string(&messages).unwrap(),
-                serde_json::to_string(&response).unwrap(),
+            let input = serde_json::to_string(&messages).unwrap();
+            let output = serde_json::to_string(&response).unwrap();
+
+            record_llm_usage(
                 &user_id,
+                &prompt_name,
                 &model,
+                input.clone(),
+                output.clone(),
+                usage_scope,
             )
             .await;
 
+            if !model.is_self_hosted() {
+                send_langfuse_request(
+                    &session_id,
+                    prompt_name,
+                    None,
+                    start_time,
+                    end_time,
+                    input,
+                    output,
+                    &user_id,
+                    &model,
+                    failures,
+                )
+                .await;
+            }
+
             Ok(response)
         })
     };
@@ -188,6 +347,114 @@ pub async fn llm_chat_stream(
     Ok((rx, res_future))
 }
 
+/// Refuses calls once the organization is past its hard limit for the month.
+async fn enforce_llm_budget(user_id: &Uuid) -> Result<()> {
+    match check_llm_budget(user_id).await {
+        Ok(LlmBudgetStatus::OverHardLimit) => Err(anyhow!(
+            "The organization has used its LLM budget for the month"
+        )),
+        Ok(LlmBudgetStatus::OverSoftLimit) => {
+            tracing::warn!(
+                "User {}'s organization is over the soft limit of its LLM budget",
+                user_id
+            );
+            Ok(())
+        }
+        Ok(LlmBudgetStatus::WithinBudget) => Ok(()),
+        Err(e) => Err(anyhow!("Error checking LLM budget: {}", e)),
+    }
+}
+
+async fn chat_with_model(model: &LlmModel, call: &LlmChatCall<'_>) -> Result<String> {
+    match model {
+        LlmModel::Anthropic(model) => {
+            anthropic_chat_compiler(
+                model,
+                call.messages,
+                call.hosted_max_tokens,
+                call.temperature,
+                call.timeout,
+                call.stop.clone(),
+            )
+            .await
+        }
+        LlmModel::OpenAi(model) => {
+            openai_chat_compiler(
+                model,
+                call.messages,
+                call.hosted_max_tokens,
+                call.temperature,
+                call.timeout,
+                call.stop.clone(),
+                call.json_mode,
+                call.json_schema.clone(),
+            )
+            .await
+        }
+        LlmModel::Ollama(model) => {
+            ollama_chat_compiler(
+                model,
+                call.messages,
+                call.max_tokens,
+                call.temperature,
+                call.timeout,
+                call.stop.clone(),
+                call.json_mode,
+                call.json_schema.clone(),
+            )
+            .await
+        }
+        LlmModel::OpenAiCompatible(model) => {
+            openai_compatible_chat_compiler(
+                model,
+                call.messages,
+                call.max_tokens,
+                call.temperature,
+                call.timeout,
+                call.stop.clone(),
+                call.json_mode,
+                call.json_schema.clone(),
+            )
+            .await
+        }
+    }
+}
+
+async fn stream_with_model(
+    model: &LlmModel,
+    messages: &Vec<LlmMessage>,
+    max_tokens: u32,
+    temperature: f32,
+    timeout: u64,
+    stop: Option<Vec<String>>,
+) -> Result<ReceiverStream<String>> {
+    match model {
+        LlmModel::Anthropic(model) => {
+            anthropic_chat_stream_compiler(model, messages, max_tokens, temperature, timeout, stop)
+                .await
+        }
+        LlmModel::OpenAi(model) => {
+            openai_chat_stream_compiler(model, messages, max_tokens, temperature, timeout, stop)
+                .await
+        }
+        LlmModel::Ollama(model) => {
+            ollama_chat_stream_compiler(model, messages, max_tokens, temperature, timeout, stop)
+                .await
+        }
+        LlmModel::OpenAiCompatible(model) => {
+            openai_compatible_chat_stream_compiler(
+                model,
+                messages,
+                max_tokens,
+                temperature,
+                timeout,
+                stop,
+            )
+            .await
+        }
+    }
+}
+
 async fn anthropic_chat_compiler(
     model: &AnthropicChatModel,
     messages: &Vec<LlmMessage>,
@@ -225,7 +492,7 @@ async fn anthropic_chat_compiler(
         system_message,
         &anthropic_messages,
         temperature,
-        7048,
+        max_tokens,
         timeout,
         stop,
     )
@@ -381,3 +648,183 @@ async fn openai_chat_stream_compiler(
 
     Ok(stream)
 }
+
+/// Adds instructions for the JSON a self-hosted model can't be held to onto the system message.
+fn with_json_instructions(
+    messages: &[LlmMessage],
+    json_instructions: Option<String>,
+) -> Vec<LlmMessage> {
+    let json_instructions = match json_instructions {
+        Some(json_instructions) => json_instructions,
+        None => return messages.to_vec(),
+    };
+
+    let mut messages = messages.to_vec();
+
+    match messages.iter_mut().find(|m| m.role == LlmRole::System) {
+        Some(message) => {
+            message.content = format!("{}\n\n{}", message.content, json_instructions);
+        }
+        None => messages.insert(
+            0,
+            LlmMessage {
+                role: LlmRole::System,
+                content: json_instructions,
+            },
+        ),
+    }
+
+    messages
+}
+
+fn ollama_messages(messages: &[LlmMessage]) -> Vec<OllamaChatMessage> {
+    messages
+        .iter()
+        .map(|message| OllamaChatMessage {
+            role: match message.role {
+                LlmRole::System => OllamaChatRole::System,
+                LlmRole::User => OllamaChatRole::User,
+                LlmRole::Assistant => OllamaChatRole::Assistant,
+            },
+            content: message.content.clone(),
+        })
+        .collect()
+}
+
+fn openai_compatible_messages(messages: &[LlmMessage]) -> Vec<OpenAiCompatibleChatMessage> {
+    messages
+        .iter()
+        .map(|message| OpenAiCompatibleChatMessage {
+            role: match message.role {
+                LlmRole::System => OpenAiChatRole::System,
+                LlmRole::User => OpenAiChatRole::User,
+                LlmRole::Assistant => OpenAiChatRole::Assistant,
+            },
+            content: message.content.clone(),
+        })
+        .collect()
+}
+
+async fn ollama_chat_compiler(
+    model: &OllamaChatModel,
+    messages: &Vec<LlmMessage>,
+    max_tokens: u32,
+    temperature: f32,
+    timeout: u64,
+    stop: Option<Vec<String>>,
+    json_mode: bool,
+    json_schema: Option<Value>,
+) -> Result<String> {
+    let wants_json = json_mode || json_schema.is_some();
+    let (json_format, json_instructions) = model
+        .structured_output
+        .json_format(json_mode, json_schema.as_ref());
+    let enforced = json_format != JsonFormat::Any;
+
+    let messages = ollama_messages(&with_json_instructions(messages, json_instructions));
+
+    let response = match ollama_chat(
+        model,
+        messages,
+        temperature,
+        max_tokens,
+        timeout,
+        stop,
+        json_format,
+    )
+    .await
+    {
+        Ok(response) => response,
+        Err(e) => return Err(anyhow!("Ollama chat error: {}", e)),
+    };
+
+    match wants_json && !enforced {
+        true => Ok(extract_json(&response)),
+        false => Ok(response),
+    }
+}
+
+async fn openai_compatible_chat_compiler(
+    model: &OpenAiCompatibleChatModel,
+    messages: &Vec<LlmMessage>,
+    max_tokens: u32,
+    temperature: f32,
+    timeout: u64,
+    stop: Option<Vec<String>>,
+    json_mode: bool,
+    json_schema: Option<Value>,
+) -> Result<String> {
+    let wants_json = json_mode || json_schema.is_some();
+    let (json_format, json_instructions) = model
+        .structured_output
+        .json_format(json_mode, json_schema.as_ref());
+    let enforced = json_format != JsonFormat::Any;
+
+    let messages = openai_compatible_messages(&with_json_instructions(messages, json_instructions));
+
+    let response = match openai_compatible_chat(
+        model,
+        messages,
+        temperature,
+        max_tokens,
+        timeout,
+        stop,
+        json_format,
+    )
+    .await
+    {
+        Ok(response) => response,
+        Err(e) => return Err(anyhow!("OpenAI-compatible chat error: {}", e)),
+    };
+
+    match wants_json && !enforced {
+        true => Ok(extract_json(&response)),
+        false => Ok(response),
+    }
+}
+
+async fn ollama_chat_stream_compiler(
+    model: &OllamaChatModel,
+    messages: &Vec<LlmMessage>,
+    max_tokens: u32,
+    temperature: f32,
+    timeout: u64,
+    stop: Option<Vec<String>>,
+) -> Result<ReceiverStream<String>> {
+    match ollama_chat_stream(
+        model,
+        ollama_messages(messages),
+        temperature,
+        max_tokens,
+        timeout,
+        stop,
+    )
+    .await
+    {
+        Ok(stream) => Ok(stream),
+        Err(e) => Err(anyhow!("Ollama chat error: {}", e)),
+    }
+}
+
+async fn openai_compatible_chat_stream_compiler(
+    model: &OpenAiCompatibleChatModel,
+    messages: &Vec<LlmMessage>,
+    max_tokens: u32,
+    temperature: f32,
+    timeout: u64,
+    stop: Option<Vec<String>>,
+) -> Result<ReceiverStream<String>> {
+    match openai_compatible_chat_stream(
+        model,
+        openai_compatible_messages(messages),
+        temperature,
+        max_tokens,
+        timeout,
+        stop,
+    )
+    .await
+    {
+        Ok(stream) => Ok(stream),
+        Err(e) => Err(anyhow!("OpenAI-compatible chat error: {}", e)),
+    }
+}
@@ -0,0 +1,259 @@
+use std::env;
+
+use anyhow::{anyhow, Result};
+use diesel::{ExpressionMethods, JoinOnDsl, QueryDsl};
+use diesel_async::RunQueryDsl;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::LlmRoute,
+    schema::{llm_routes, users_to_organizations},
+};
+
+use super::{
+    anthropic::AnthropicChatModel, langfuse::PromptName, llm_providers::organization_llm_model,
+    llm_router::LlmModel, openai::OpenAiChatModel,
+};
+
+/// The route used for prompts that don't have their own.
+pub const DEFAULT_LLM_ROUTE: &str = "default";
+
+lazy_static! {
+    /// Where hosted models fail over to when their route doesn't say, e.g. `gpt-4o-mini`.
+    static ref LLM_FALLBACK_MODEL: Option<LlmModel> = env::var("LLM_FALLBACK_MODEL")
+        .ok()
+        .and_then(|name| LlmModel::from_name(&name));
+}
+
+/// A model a route sends prompts to, e.g. `{"provider": "openai", "model": "gpt-4o-mini"}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "provider", content = "model")]
+pub enum LlmModelChoice {
+    #[serde(rename = "openai")]
+    OpenAi(OpenAiChatModel),
+    #[serde(rename = "anthropic")]
+    Anthropic(AnthropicChatModel),
+    /// A model on the organization's own LLM provider, by the name the provider knows it by.
+    #[serde(rename = "self_hosted")]
+    SelfHosted(String),
+}
+
+/// Where a prompt goes once the organization's routes are applied.
+pub struct RoutedLlmCall {
+    pub model: LlmModel,
+    pub fallback_model: Option<LlmModel>,
+    pub temperature: f32,
+    /// Set when a route caps the reply.
+    pub max_tokens: Option<u32>,
+}
+
+impl LlmModelChoice {
+    fn into_model(self, self_hosted: &Option<LlmModel>) -> Result<LlmModel> {
+        match (self, self_hosted) {
+            (LlmModelChoice::OpenAi(model), _) => Ok(LlmModel::OpenAi(model)),
+            (LlmModelChoice::Anthropic(model), _) => Ok(LlmModel::Anthropic(model)),
+            (LlmModelChoice::SelfHosted(name), Some(LlmModel::Ollama(model))) => {
+                let mut model = model.clone();
+                model.model = name;
+                Ok(LlmModel::Ollama(model))
+            }
+            (LlmModelChoice::SelfHosted(name), Some(LlmModel::OpenAiCompatible(model))) => {
+                let mut model = model.clone();
+                model.model = name;
+                Ok(LlmModel::OpenAiCompatible(model))
+            }
+            (LlmModelChoice::SelfHosted(_), _) => Err(anyhow!(
+                "Route uses a self-hosted model but the organization has no LLM provider"
+            )),
+        }
+    }
+}
+
+/// The prompt's own route if it has one, and the default route otherwise.
+fn pick_route(routes: Vec<LlmRoute>, prompt_name: &str) -> Option<LlmRoute> {
+    let (own, default): (Vec<LlmRoute>, Vec<LlmRoute>) = routes
+        .into_iter()
+        .partition(|route| route.prompt_name == prompt_name);
+
+    own.into_iter().chain(default).next()
+}
+
+/// Self-hosted models are never failed over to a hosted one, their prompts stay with the
+/// organization.
+fn default_fallback_model(model: &LlmModel, fallback_model: &Option<LlmModel>) -> Option<LlmModel> {
+    match fallback_model {
+        Some(fallback_model)
+            if !model.is_self_hosted() && fallback_model.name() != model.name() =>
+        {
+            Some(fallback_model.clone())
+        }
+        _ => None,
+    }
+}
+
+/// Resolves which model answers a prompt. A route for the prompt comes first, then the
+/// organization's `default` route, then its own LLM provider and finally `model`, the one the
+/// caller asked for.
+pub async fn route_llm_call(
+    user_id: &Uuid,
+    prompt_name: &PromptName,
+    model: LlmModel,
+    temperature: f32,
+) -> Result<RoutedLlmCall> {
+    let prompt_name = prompt_name.to_string();
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let routes = match llm_routes::table
+        .inner_join(
+            users_to_organizations::table
+                .on(users_to_organizations::organization_id.eq(llm_routes::organization_id)),
+        )
+        .filter(users_to_organizations::user_id.eq(user_id))
+        .filter(users_to_organizations::deleted_at.is_null())
+        .filter(llm_routes::prompt_name.eq_any([prompt_name.as_str(), DEFAULT_LLM_ROUTE]))
+        .filter(llm_routes::deleted_at.is_null())
+        .select(llm_routes::all_columns)
+        .load::<LlmRoute>(&mut conn)
+        .await
+    {
+        Ok(routes) => routes,
+        Err(e) => return Err(anyhow!("Error getting LLM routes: {}", e)),
+    };
+
+    drop(conn);
+
+    let self_hosted = organization_llm_model(user_id).await?;
+
+    let route = match pick_route(routes, &prompt_name) {
+        Some(route) => route,
+        None => {
+            let model = self_hosted.unwrap_or(model);
+
+            return Ok(RoutedLlmCall {
+                fallback_model: default_fallback_model(&model, &LLM_FALLBACK_MODEL),
+                model,
+                temperature,
+                max_tokens: None,
+            });
+        }
+    };
+
+    let routed_model = match serde_json::from_value::<LlmModelChoice>(route.model) {
+        Ok(choice) => choice.into_model(&self_hosted)?,
+        Err(e) => return Err(anyhow!("Invalid model in LLM route {}: {}", route.id, e)),
+    };
+
+    let fallback_model = match route.fallback_model {
+        Some(fallback_model) => match serde_json::from_value::<LlmModelChoice>(fallback_model) {
+            Ok(choice) => Some(choice.into_model(&self_hosted)?),
+            Err(e) => return Err(anyhow!("Invalid fallback in LLM route {}: {}", route.id, e)),
+        },
+        None => default_fallback_model(&routed_model, &LLM_FALLBACK_MODEL),
+    };
+
+    Ok(RoutedLlmCall {
+        model: routed_model,
+        fallback_model,
+        temperature: route.temperature.unwrap_or(temperature),
+        max_tokens: route.max_tokens.map(|max_tokens| max_tokens as u32),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::clients::ai::{llm_providers::StructuredOutput, ollama::OllamaChatModel};
+    use chrono::Utc;
+    use serde_json::json;
+
+    fn route(prompt_name: &str) -> LlmRoute {
+        LlmRoute {
+            id: Uuid::new_v4(),
+            organization_id: Uuid::new_v4(),
+            prompt_name: prompt_name.to_string(),
+            model: json!({"provider": "openai", "model": "gpt-4o-mini"}),
+            fallback_model: None,
+            temperature: None,
+            max_tokens: None,
+            created_by: Uuid::new_v4(),
+            updated_by: Uuid::new_v4(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn prompt_routes_win_over_the_default() {
+        let routes = vec![route(DEFAULT_LLM_ROUTE), route("generate_sql")];
+
+        assert_eq!(
+            pick_route(routes.clone(), "generate_sql")
+                .unwrap()
+                .prompt_name,
+            "generate_sql"
+        );
+        assert_eq!(
+            pick_route(routes, "metric_title").unwrap().prompt_name,
+            DEFAULT_LLM_ROUTE
+        );
+        assert!(pick_route(vec![], "metric_title").is_none());
+    }
+
+    #[test]
+    fn reads_model_choices() {
+        assert_eq!(
+            serde_json::from_value::<LlmModelChoice>(
+                json!({"provider": "openai", "model": "gpt-4o"})
+            )
+            .unwrap(),
+            LlmModelChoice::OpenAi(OpenAiChatModel::Gpt4o)
+        );
+        assert_eq!(
+            serde_json::from_value::<LlmModelChoice>(
+                json!({"provider": "self_hosted", "model": "llama3.2:3b"})
+            )
+            .unwrap(),
+            LlmModelChoice::SelfHosted("llama3.2:3b".to_string())
+        );
+        assert!(serde_json::from_value::<LlmModelChoice>(
+            json!({"provider": "openai", "model": "gpt-5-turbo"})
+        )
+        .is_err());
+        assert!(LlmModelChoice::SelfHosted("llama3.2:3b".to_string())
+            .into_model(&None)
+            .is_err());
+    }
+
+    #[test]
+    fn only_hosted_models_fail_over_by_default() {
+        let fallback_model = Some(LlmModel::OpenAi(OpenAiChatModel::Gpt4oMini));
+
+        assert!(
+            default_fallback_model(&LlmModel::OpenAi(OpenAiChatModel::Gpt4o), &fallback_model)
+                .is_some()
+        );
+        assert!(default_fallback_model(
+            &LlmModel::OpenAi(OpenAiChatModel::Gpt4oMini),
+            &fallback_model
+        )
+        .is_none());
+        assert!(default_fallback_model(
+            &LlmModel::Ollama(OllamaChatModel {
+                base_url: "http://ollama:11434".to_string(),
+                model: "llama3.2:3b".to_string(),
+                api_key: None,
+                structured_output: StructuredOutput::JsonSchema,
+            }),
+            &fallback_model
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,222 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use diesel::{insert_into, ExpressionMethods, JoinOnDsl, QueryDsl};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    database::{
+        lib::get_pg_pool,
+        models::{LlmBudget, LlmUsage},
+        schema::{llm_budgets, llm_usage, users_to_organizations},
+    },
+    utils::{clients::sentry_utils::send_sentry_error, user::user_info::get_user_organization_id},
+};
+
+use super::{langfuse::PromptName, llm_router::LlmModel};
+
+/// The thread and message an LLM call was made for, when it was made for one.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LlmUsageScope {
+    pub thread_id: Option<Uuid>,
+    pub message_id: Option<Uuid>,
+}
+
+impl LlmUsageScope {
+    pub fn thread(thread_id: Uuid) -> Self {
+        Self {
+            thread_id: Some(thread_id),
+            message_id: None,
+        }
+    }
+
+    pub fn message(thread_id: Uuid, message_id: Uuid) -> Self {
+        Self {
+            thread_id: Some(thread_id),
+            message_id: Some(message_id),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LlmBudgetStatus {
+    WithinBudget,
+    /// Past the soft limit. Calls still go through, but admins are warned.
+    OverSoftLimit,
+    /// Past the hard limit. Calls are refused until the month is over.
+    OverHardLimit,
+}
+
+pub fn budget_status(
+    month_cost: f64,
+    soft_limit: Option<f64>,
+    hard_limit: Option<f64>,
+) -> LlmBudgetStatus {
+    match (soft_limit, hard_limit) {
+        (_, Some(hard_limit)) if month_cost >= hard_limit => LlmBudgetStatus::OverHardLimit,
+        (Some(soft_limit), _) if month_cost >= soft_limit => LlmBudgetStatus::OverSoftLimit,
+        _ => LlmBudgetStatus::WithinBudget,
+    }
+}
+
+/// Budgets are for calendar months in UTC.
+pub fn month_start(now: DateTime<Utc>) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .single()
+        .unwrap_or(now)
+}
+
+/// What the organization has spent on LLM calls since `since`, in US dollars.
+pub async fn organization_llm_cost(
+    conn: &mut AsyncPgConnection,
+    organization_id: &Uuid,
+    since: DateTime<Utc>,
+) -> Result<f64> {
+    match llm_usage::table
+        .filter(llm_usage::organization_id.eq(organization_id))
+        .filter(llm_usage::created_at.ge(since))
+        .select(diesel::dsl::sum(llm_usage::cost))
+        .first::<Option<f64>>(conn)
+        .await
+    {
+        Ok(cost) => Ok(cost.unwrap_or(0.0)),
+        Err(e) => Err(anyhow!("Error getting LLM usage cost: {}", e)),
+    }
+}
+
+/// Where the user's organization stands against its budget this month.
+pub async fn check_llm_budget(user_id: &Uuid) -> Result<LlmBudgetStatus> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let budget = match llm_budgets::table
+        .inner_join(
+            users_to_organizations::table
+                .on(users_to_organizations::organization_id.eq(llm_budgets::organization_id)),
+        )
+        .filter(users_to_organizations::user_id.eq(user_id))
+        .filter(users_to_organizations::deleted_at.is_null())
+        .filter(llm_budgets::deleted_at.is_null())
+        .select(llm_budgets::all_columns)
+        .first::<LlmBudget>(&mut conn)
+        .await
+    {
+        Ok(budget) => budget,
+        Err(diesel::NotFound) => return Ok(LlmBudgetStatus::WithinBudget),
+        Err(e) => return Err(anyhow!("Error getting LLM budget: {}", e)),
+    };
+
+    let month_cost =
+        organization_llm_cost(&mut conn, &budget.organization_id, month_start(Utc::now())).await?;
+
+    Ok(budget_status(
+        month_cost,
+        budget.soft_limit,
+        budget.hard_limit,
+    ))
+}
+
+/// Records the tokens a reply took and what they cost. Tokens are counted the same way as for
+/// Langfuse, so the two agree.
+pub async fn record_llm_usage(
+    user_id: &Uuid,
+    prompt_name: &PromptName,
+    model: &LlmModel,
+    input: String,
+    output: String,
+    scope: LlmUsageScope,
+) {
+    let user_id = *user_id;
+    let prompt_name = prompt_name.to_string();
+    let model = model.clone();
+
+    tokio::spawn(async move {
+        if let Err(e) =
+            record_llm_usage_handler(user_id, prompt_name, model, input, output, scope).await
+        {
+            let err = anyhow!("Error recording LLM usage: {}", e);
+            tracing::error!("{}", err);
+            send_sentry_error(&err.to_string(), Some(&user_id));
+        }
+    });
+}
+
+async fn record_llm_usage_handler(
+    user_id: Uuid,
+    prompt_name: String,
+    model: LlmModel,
+    input: String,
+    output: String,
+    scope: LlmUsageScope,
+) -> Result<()> {
+    let organization_id = get_user_organization_id(&user_id).await?;
+
+    let usage = model.generate_usage(&input, &output);
+
+    let llm_usage = LlmUsage {
+        id: Uuid::new_v4(),
+        organization_id,
+        user_id,
+        thread_id: scope.thread_id,
+        message_id: scope.message_id,
+        prompt_name,
+        model: model.name(),
+        prompt_tokens: usage.input as i32,
+        completion_tokens: usage.output as i32,
+        cost: usage.total_cost,
+        created_at: Utc::now(),
+    };
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match insert_into(llm_usage::table)
+        .values(&llm_usage)
+        .execute(&mut conn)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(anyhow!("Error inserting LLM usage: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hard_limits_win_over_soft_ones() {
+        assert_eq!(
+            budget_status(10.0, Some(50.0), Some(100.0)),
+            LlmBudgetStatus::WithinBudget
+        );
+        assert_eq!(
+            budget_status(75.0, Some(50.0), Some(100.0)),
+            LlmBudgetStatus::OverSoftLimit
+        );
+        assert_eq!(
+            budget_status(100.0, Some(50.0), Some(100.0)),
+            LlmBudgetStatus::OverHardLimit
+        );
+        assert_eq!(
+            budget_status(1_000.0, None, None),
+            LlmBudgetStatus::WithinBudget
+        );
+    }
+
+    #[test]
+    fn months_start_at_midnight_on_the_first() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 21, 15, 30, 0).unwrap();
+
+        assert_eq!(
+            month_start(now),
+            Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+        );
+    }
+}
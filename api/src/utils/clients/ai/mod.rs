@@ -2,6 +2,11 @@ mod anthropic;
 pub mod embedding_router;
 mod hugging_face;
 pub mod langfuse;
+pub mod llm_providers;
+pub mod llm_retries;
 pub mod llm_router;
+pub mod llm_routes;
+pub mod llm_usage;
 pub mod ollama;
 pub mod openai;
+pub mod openai_compatible;
@@ -1,8 +1,19 @@
-use std::env;
+use std::{env, time::Duration};
 
 use anyhow::{anyhow, Result};
 use axum::http::HeaderMap;
-use serde::{Deserialize, Serialize};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{json, Value};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::utils::clients::sentry_utils::send_sentry_error;
+
+use super::{
+    llm_providers::{JsonFormat, StructuredOutput},
+    llm_retries::{llm_error_for_status, llm_send_error},
+};
 
 #[derive(Serialize)]
 pub struct OllamaEmbeddingRequest {
@@ -65,3 +76,341 @@ pub async fn ollama_embedding(prompt: String, for_retrieval: bool) -> Result<Vec
 
     Ok(ollama_res.embedding)
 }
+
+/// A model served by an organization's own Ollama server.
+#[derive(Clone, Debug)]
+pub struct OllamaChatModel {
+    pub base_url: String,
+    pub model: String,
+    /// Sent as a bearer token, for servers behind a proxy that wants one.
+    pub api_key: Option<String>,
+    pub structured_output: StructuredOutput,
+}
+
+/// Traces only need to know which model answered.
+impl Serialize for OllamaChatModel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.model)
+    }
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum OllamaChatRole {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Serialize, Clone)]
+pub struct OllamaChatMessage {
+    pub role: OllamaChatRole,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+struct OllamaChatOptions {
+    temperature: f32,
+    num_predict: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+struct OllamaChatRequest {
+    model: String,
+    messages: Vec<OllamaChatMessage>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<Value>,
+    options: OllamaChatOptions,
+}
+
+#[derive(Deserialize, Debug)]
+struct OllamaChatResponseMessage {
+    content: String,
+}
+
+/// A whole reply, or one line of a streamed one.
+#[derive(Deserialize, Debug)]
+struct OllamaChatResponse {
+    message: Option<OllamaChatResponseMessage>,
+    error: Option<String>,
+}
+
+fn ollama_chat_request(
+    model: &OllamaChatModel,
+    messages: Vec<OllamaChatMessage>,
+    temperature: f32,
+    max_tokens: u32,
+    stop: Option<Vec<String>>,
+    json_format: JsonFormat,
+    stream: bool,
+) -> OllamaChatRequest {
+    let format = match json_format {
+        JsonFormat::Any => None,
+        JsonFormat::Object => Some(json!("json")),
+        JsonFormat::Schema(schema) => Some(schema),
+    };
+
+    OllamaChatRequest {
+        model: model.model.clone(),
+        messages,
+        stream,
+        format,
+        options: OllamaChatOptions {
+            temperature,
+            num_predict: max_tokens,
+            stop,
+        },
+    }
+}
+
+fn ollama_chat_builder(
+    model: &OllamaChatModel,
+    request: &OllamaChatRequest,
+    timeout: u64,
+) -> reqwest::RequestBuilder {
+    let builder = reqwest::Client::new()
+        .post(format!("{}/api/chat", model.base_url.trim_end_matches('/')))
+        .json(request)
+        .timeout(Duration::from_secs(timeout));
+
+    match &model.api_key {
+        Some(api_key) => builder.bearer_auth(api_key),
+        None => builder,
+    }
+}
+
+pub async fn ollama_chat(
+    model: &OllamaChatModel,
+    messages: Vec<OllamaChatMessage>,
+    temperature: f32,
+    max_tokens: u32,
+    timeout: u64,
+    stop: Option<Vec<String>>,
+    json_format: JsonFormat,
+) -> Result<String> {
+    let request = ollama_chat_request(
+        model,
+        messages,
+        temperature,
+        max_tokens,
+        stop,
+        json_format,
+        false,
+    );
+
+    let response = match ollama_chat_builder(model, &request, timeout).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::error!("Unable to send request to Ollama: {:?}", e);
+            return Err(llm_send_error("Ollama", e));
+        }
+    };
+
+    let status = response.status();
+
+    // Ollama explains bad requests in the body, which is parsed below. Anything else is the
+    // server failing.
+    let response = match status.is_client_error() {
+        true => response,
+        false => llm_error_for_status("Ollama", response).await?,
+    };
+
+    let chat_response = match response.json::<OllamaChatResponse>().await {
+        Ok(chat_response) => chat_response,
+        Err(e) => return Err(anyhow!("Unable to parse response from Ollama: {}", e)),
+    };
+
+    match (chat_response.message, chat_response.error) {
+        (_, Some(error)) => Err(anyhow!("Ollama returned an error ({}): {}", status, error)),
+        (Some(message), None) => Ok(message.content),
+        (None, None) => Err(anyhow!("No content returned from Ollama")),
+    }
+}
+
+/// Streamed replies are one JSON object per line.
+fn parse_ollama_stream_line(line: &str) -> Result<Option<String>> {
+    let line = line.trim();
+
+    if line.is_empty() {
+        return Ok(None);
+    }
+
+    match serde_json::from_str::<OllamaChatResponse>(line) {
+        Ok(OllamaChatResponse {
+            error: Some(error), ..
+        }) => Err(anyhow!("Ollama returned an error: {}", error)),
+        Ok(response) => Ok(response
+            .message
+            .map(|message| message.content)
+            .filter(|content| !content.is_empty())),
+        Err(e) => Err(anyhow!("Error parsing Ollama stream: {}", e)),
+    }
+}
+
+pub async fn ollama_chat_stream(
+    model: &OllamaChatModel,
+    messages: Vec<OllamaChatMessage>,
+    temperature: f32,
+    max_tokens: u32,
+    timeout: u64,
+    stop: Option<Vec<String>>,
+) -> Result<ReceiverStream<String>> {
+    let request = ollama_chat_request(
+        model,
+        messages,
+        temperature,
+        max_tokens,
+        stop,
+        JsonFormat::Any,
+        true,
+    );
+
+    let response = match ollama_chat_builder(model, &request, timeout).send().await {
+        Ok(response) => llm_error_for_status("Ollama", response).await?,
+        Err(e) => {
+            tracing::error!("Unable to send request to Ollama: {:?}", e);
+            return Err(llm_send_error("Ollama", e));
+        }
+    };
+
+    let (tx, rx): (Sender<String>, Receiver<String>) = mpsc::channel(100);
+
+    tokio::spawn(async move {
+        let mut stream = response.bytes_stream();
+        let mut buffer = String::new();
+
+        while let Some(item) = stream.next().await {
+            let bytes = match item {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    let err = anyhow!("Error while streaming response: {}", e);
+                    tracing::error!("{}", err);
+                    send_sentry_error(&err.to_string(), None);
+                    return;
+                }
+            };
+
+            buffer.push_str(&String::from_utf8_lossy(&bytes));
+
+            while let Some(pos) = buffer.find('\n') {
+                let line = buffer[..pos].to_string();
+                buffer.drain(..=pos);
+
+                match parse_ollama_stream_line(&line) {
+                    Ok(Some(content)) => {
+                        if tx.send(content).await.is_err() {
+                            return;
+                        }
+                    }
+                    Ok(None) => (),
+                    Err(e) => {
+                        tracing::error!("{}", e);
+                        send_sentry_error(&e.to_string(), None);
+                        return;
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(ReceiverStream::new(rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Json, Router};
+
+    async fn stub_server(reply: Value) -> String {
+        let app = Router::new().route(
+            "/api/chat",
+            post(move |Json(request): Json<Value>| async move {
+                assert_eq!(request["model"], "llama3.1");
+                assert_eq!(request["format"], "json");
+                Json(reply)
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        format!("http://{}", address)
+    }
+
+    fn model(base_url: String) -> OllamaChatModel {
+        OllamaChatModel {
+            base_url,
+            model: "llama3.1".to_string(),
+            api_key: None,
+            structured_output: StructuredOutput::JsonObject,
+        }
+    }
+
+    #[tokio::test]
+    async fn chats_with_a_stub_server() {
+        let base_url = stub_server(json!({
+            "model": "llama3.1",
+            "message": {"role": "assistant", "content": "{\"dataset\": \"orders\"}"},
+            "done": true
+        }))
+        .await;
+
+        let response = ollama_chat(
+            &model(base_url),
+            vec![OllamaChatMessage {
+                role: OllamaChatRole::User,
+                content: "Which dataset?".to_string(),
+            }],
+            0.0,
+            50,
+            10,
+            None,
+            JsonFormat::Object,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response, r#"{"dataset": "orders"}"#);
+    }
+
+    #[tokio::test]
+    async fn surfaces_server_errors() {
+        let base_url = stub_server(json!({"error": "model \"llama3.1\" not found"})).await;
+
+        let response = ollama_chat(
+            &model(base_url),
+            vec![],
+            0.0,
+            50,
+            10,
+            None,
+            JsonFormat::Object,
+        )
+        .await;
+
+        assert!(response.unwrap_err().to_string().contains("not found"));
+    }
+
+    #[test]
+    fn parses_stream_lines() {
+        assert_eq!(
+            parse_ollama_stream_line(
+                r#"{"message":{"role":"assistant","content":"Hi"},"done":false}"#
+            )
+            .unwrap(),
+            Some("Hi".to_string())
+        );
+        assert_eq!(
+            parse_ollama_stream_line(
+                r#"{"message":{"role":"assistant","content":""},"done":true}"#
+            )
+            .unwrap(),
+            None
+        );
+        assert!(parse_ollama_stream_line(r#"{"error":"out of memory"}"#).is_err());
+    }
+}
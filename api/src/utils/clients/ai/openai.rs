@@ -9,6 +9,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::utils::clients::sentry_utils::send_sentry_error;
 
+use super::llm_retries::{llm_error_for_status, llm_send_error};
+
 const OPENAI_EMBEDDING_URL: &str = "https://api.openai.com/v1/embeddings";
 
 lazy_static::lazy_static! {
@@ -17,9 +19,9 @@ lazy_static::lazy_static! {
     static ref OPENAI_CHAT_URL: String = env::var("OPENAI_CHAT_URL").unwrap_or("https://api.openai.com/v1/chat/completions".to_string());
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum OpenAiChatModel {
-    #[serde(rename = "gpt-4o-2024-11-20")]
+    #[serde(rename = "gpt-4o-2024-11-20", alias = "gpt-4o")]
     Gpt4o,
     #[serde(rename = "gpt-4o-mini")]
     Gpt4oMini,
@@ -135,12 +137,14 @@ pub async fn openai_chat(
         Ok(response) => response,
         Err(e) => {
             tracing::error!("Unable to send request to OpenAI: {:?}", e);
-            let err = anyhow!("Unable to send request to OpenAI: {}", e);
+            let err = llm_send_error("OpenAI", e);
             send_sentry_error(&err.to_string(), None);
             return Err(err);
         }
     };
 
+    let response = llm_error_for_status("OpenAI", response).await?;
+
     let response_text = response.text().await.unwrap();
 
     let completion_res = match serde_json::from_str::<ChatCompletionResponse>(&response_text) {
@@ -221,28 +225,29 @@ pub async fn openai_chat_stream(
         headers
     };
 
+    // The request is sent before streaming starts so a failure to connect can be retried.
+    let response = match client
+        .post(OPENAI_CHAT_URL.to_string())
+        .headers(headers)
+        .json(&chat_request)
+        .timeout(Duration::from_secs(timeout))
+        .send()
+        .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::error!("Unable to send request to OpenAI: {:?}", e);
+            let err = llm_send_error("OpenAI", e);
+            send_sentry_error(&err.to_string(), None);
+            return Err(err);
+        }
+    };
+
+    let response = llm_error_for_status("OpenAI", response).await?;
+
     let (tx, rx): (Sender<String>, Receiver<String>) = mpsc::channel(100);
 
     tokio::spawn(async move {
-        let response = client
-            .post(OPENAI_CHAT_URL.to_string())
-            .headers(headers)
-            .json(&chat_request)
-            .timeout(Duration::from_secs(timeout))
-            .send()
-            .await
-            .map_err(|e| {
-                tracing::error!("Unable to send request to OpenAI: {:?}", e);
-                let err = anyhow!("Unable to send request to OpenAI: {}", e);
-                send_sentry_error(&err.to_string(), None);
-                err
-            });
-
-        if let Err(_e) = response {
-            return;
-        }
-
-        let response = response.unwrap();
         let mut stream = response.bytes_stream();
 
         let mut buffer = String::new();
@@ -0,0 +1,358 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{json, Value};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::utils::clients::sentry_utils::send_sentry_error;
+
+use super::{
+    llm_providers::{JsonFormat, StructuredOutput},
+    llm_retries::{llm_error_for_status, llm_send_error},
+    openai::OpenAiChatRole,
+};
+
+/// A model served by anything that speaks OpenAI's chat completions API, e.g. vLLM, LM Studio or
+/// the llama.cpp server.
+#[derive(Clone, Debug)]
+pub struct OpenAiCompatibleChatModel {
+    pub base_url: String,
+    pub model: String,
+    pub api_key: Option<String>,
+    pub structured_output: StructuredOutput,
+}
+
+/// Traces only need to know which model answered.
+impl Serialize for OpenAiCompatibleChatModel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.model)
+    }
+}
+
+/// Content is sent as a plain string, which more servers understand than content parts.
+#[derive(Serialize, Clone)]
+pub struct OpenAiCompatibleChatMessage {
+    pub role: OpenAiChatRole,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+struct OpenAiCompatibleChatRequest {
+    model: String,
+    messages: Vec<OpenAiCompatibleChatMessage>,
+    temperature: f32,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAiCompatibleMessage {
+    content: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAiCompatibleChoice {
+    message: Option<OpenAiCompatibleMessage>,
+    delta: Option<OpenAiCompatibleMessage>,
+}
+
+/// A whole reply, or one event of a streamed one.
+#[derive(Deserialize, Debug)]
+struct OpenAiCompatibleChatResponse {
+    #[serde(default)]
+    choices: Vec<OpenAiCompatibleChoice>,
+}
+
+fn openai_compatible_builder(
+    model: &OpenAiCompatibleChatModel,
+    request: &OpenAiCompatibleChatRequest,
+    timeout: u64,
+) -> reqwest::RequestBuilder {
+    let builder = reqwest::Client::new()
+        .post(format!(
+            "{}/chat/completions",
+            model.base_url.trim_end_matches('/')
+        ))
+        .json(request)
+        .timeout(Duration::from_secs(timeout));
+
+    match &model.api_key {
+        Some(api_key) => builder.bearer_auth(api_key),
+        None => builder,
+    }
+}
+
+pub async fn openai_compatible_chat(
+    model: &OpenAiCompatibleChatModel,
+    messages: Vec<OpenAiCompatibleChatMessage>,
+    temperature: f32,
+    max_tokens: u32,
+    timeout: u64,
+    stop: Option<Vec<String>>,
+    json_format: JsonFormat,
+) -> Result<String> {
+    let response_format = match json_format {
+        JsonFormat::Any => None,
+        JsonFormat::Object => Some(json!({"type": "json_object"})),
+        JsonFormat::Schema(schema) => Some(json!({
+            "type": "json_schema",
+            "json_schema": {"name": "response", "schema": schema}
+        })),
+    };
+
+    let request = OpenAiCompatibleChatRequest {
+        model: model.model.clone(),
+        messages,
+        temperature,
+        max_tokens,
+        stop,
+        stream: false,
+        response_format,
+    };
+
+    let response = match openai_compatible_builder(model, &request, timeout)
+        .send()
+        .await
+    {
+        Ok(response) => llm_error_for_status(&model.base_url, response).await?,
+        Err(e) => {
+            tracing::error!("Unable to send request to {}: {:?}", model.base_url, e);
+            return Err(llm_send_error(&model.base_url, e));
+        }
+    };
+
+    let chat_response = match response.json::<OpenAiCompatibleChatResponse>().await {
+        Ok(chat_response) => chat_response,
+        Err(e) => {
+            return Err(anyhow!(
+                "Unable to parse response from {}: {}",
+                model.base_url,
+                e
+            ))
+        }
+    };
+
+    match chat_response
+        .choices
+        .into_iter()
+        .next()
+        .and_then(|choice| choice.message)
+        .and_then(|message| message.content)
+    {
+        Some(content) => Ok(content),
+        None => Err(anyhow!("No content returned from {}", model.base_url)),
+    }
+}
+
+/// Streamed replies are server-sent events, ending with `data: [DONE]`. Returns `None` once the
+/// stream is done.
+fn parse_openai_compatible_stream_line(line: &str) -> Result<Option<Option<String>>> {
+    let data = match line.trim().strip_prefix("data:") {
+        Some(data) => data.trim(),
+        None => return Ok(Some(None)),
+    };
+
+    if data == "[DONE]" {
+        return Ok(None);
+    }
+
+    match serde_json::from_str::<OpenAiCompatibleChatResponse>(data) {
+        Ok(response) => Ok(Some(
+            response
+                .choices
+                .into_iter()
+                .next()
+                .and_then(|choice| choice.delta)
+                .and_then(|delta| delta.content)
+                .filter(|content| !content.is_empty()),
+        )),
+        Err(e) => Err(anyhow!("Error parsing chat completion stream: {}", e)),
+    }
+}
+
+pub async fn openai_compatible_chat_stream(
+    model: &OpenAiCompatibleChatModel,
+    messages: Vec<OpenAiCompatibleChatMessage>,
+    temperature: f32,
+    max_tokens: u32,
+    timeout: u64,
+    stop: Option<Vec<String>>,
+) -> Result<ReceiverStream<String>> {
+    let request = OpenAiCompatibleChatRequest {
+        model: model.model.clone(),
+        messages,
+        temperature,
+        max_tokens,
+        stop,
+        stream: true,
+        response_format: None,
+    };
+
+    let response = match openai_compatible_builder(model, &request, timeout)
+        .send()
+        .await
+    {
+        Ok(response) => llm_error_for_status(&model.base_url, response).await?,
+        Err(e) => {
+            tracing::error!("Unable to send request to {}: {:?}", model.base_url, e);
+            return Err(llm_send_error(&model.base_url, e));
+        }
+    };
+
+    let (tx, rx): (Sender<String>, Receiver<String>) = mpsc::channel(100);
+
+    tokio::spawn(async move {
+        let mut stream = response.bytes_stream();
+        let mut buffer = String::new();
+
+        while let Some(item) = stream.next().await {
+            let bytes = match item {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    let err = anyhow!("Error while streaming response: {}", e);
+                    tracing::error!("{}", err);
+                    send_sentry_error(&err.to_string(), None);
+                    return;
+                }
+            };
+
+            buffer.push_str(&String::from_utf8_lossy(&bytes));
+
+            while let Some(pos) = buffer.find('\n') {
+                let line = buffer[..pos].to_string();
+                buffer.drain(..=pos);
+
+                match parse_openai_compatible_stream_line(&line) {
+                    Ok(Some(Some(content))) => {
+                        if tx.send(content).await.is_err() {
+                            return;
+                        }
+                    }
+                    Ok(Some(None)) => (),
+                    Ok(None) => return,
+                    Err(e) => {
+                        tracing::error!("{}", e);
+                        send_sentry_error(&e.to_string(), None);
+                        return;
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(ReceiverStream::new(rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::header::CONTENT_TYPE,
+        response::{IntoResponse, Response},
+        routing::post,
+        Json, Router,
+    };
+
+    async fn stub_server() -> String {
+        let app = Router::new().route(
+            "/v1/chat/completions",
+            post(|Json(request): Json<Value>| async move {
+                assert_eq!(request["model"], "qwen2.5-coder");
+
+                if request["stream"] == true {
+                    let events = [
+                        r#"data: {"choices":[{"index":0,"delta":{"role":"assistant"}}]}"#,
+                        r#"data: {"choices":[{"index":0,"delta":{"content":"SELECT "}}]}"#,
+                        r#"data: {"choices":[{"index":0,"delta":{"content":"1"}}]}"#,
+                        "data: [DONE]",
+                    ];
+
+                    return Response::builder()
+                        .header(CONTENT_TYPE, "text/event-stream")
+                        .body(Body::from(events.join("\n\n") + "\n\n"))
+                        .unwrap();
+                }
+
+                assert_eq!(request["response_format"]["type"], "json_schema");
+                assert_eq!(
+                    request["response_format"]["json_schema"]["schema"]["type"],
+                    "object"
+                );
+
+                Json(json!({
+                    "choices": [{
+                        "index": 0,
+                        "message": {"role": "assistant", "content": "{\"answerable\": true}"}
+                    }]
+                }))
+                .into_response()
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        format!("http://{}/v1", address)
+    }
+
+    fn model(base_url: String) -> OpenAiCompatibleChatModel {
+        OpenAiCompatibleChatModel {
+            base_url,
+            model: "qwen2.5-coder".to_string(),
+            api_key: None,
+            structured_output: StructuredOutput::JsonSchema,
+        }
+    }
+
+    fn messages() -> Vec<OpenAiCompatibleChatMessage> {
+        vec![OpenAiCompatibleChatMessage {
+            role: OpenAiChatRole::User,
+            content: "Can this be answered?".to_string(),
+        }]
+    }
+
+    #[tokio::test]
+    async fn chats_with_a_stub_server() {
+        let base_url = stub_server().await;
+
+        let response = openai_compatible_chat(
+            &model(base_url),
+            messages(),
+            0.0,
+            50,
+            10,
+            None,
+            JsonFormat::Schema(json!({"type": "object"})),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response, r#"{"answerable": true}"#);
+    }
+
+    #[tokio::test]
+    async fn streams_from_a_stub_server() {
+        let base_url = stub_server().await;
+
+        let mut stream =
+            openai_compatible_chat_stream(&model(base_url), messages(), 0.0, 50, 10, None)
+                .await
+                .unwrap();
+
+        let mut response = String::new();
+        while let Some(content) = stream.next().await {
+            response.push_str(&content);
+        }
+
+        assert_eq!(response, "SELECT 1");
+    }
+}
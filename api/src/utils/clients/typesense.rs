@@ -276,6 +276,7 @@ pub async fn delete_collection(collection_name: &String, filter_by: &String) ->
     Ok(())
 }
 
+/// Documents whose id already exists in the collection are replaced, so re-importing is idempotent.
 pub async fn bulk_insert_documents<T>(collection_name: &String, documents: &Vec<T>) -> Result<()>
 where
     T: serde::Serialize,
@@ -296,7 +297,7 @@ where
         .request(
             reqwest::Method::POST,
             format!(
-                "{}/collections/{}/documents/import?action=create",
+                "{}/collections/{}/documents/import?action=upsert",
                 *TYPESENSE_API_HOST, collection_name
             ),
         )
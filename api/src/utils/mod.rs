@@ -1,11 +1,14 @@
 pub mod agent_builder;
 pub mod agents;
+pub mod audit;
 pub mod charting;
 pub mod clients;
 pub mod prompts;
 pub mod query_engine;
+pub mod rerank_engine;
 pub mod search_engine;
 pub mod security;
 pub mod sharing;
 pub mod user;
+pub mod values_engine;
 pub mod serde_helpers;
\ No newline at end of file
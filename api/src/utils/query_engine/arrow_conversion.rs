@@ -0,0 +1,359 @@
+use std::{pin::Pin, sync::Arc};
+
+use anyhow::{anyhow, Error, Result};
+use arrow::{
+    array::{
+        ArrayRef, BinaryBuilder, BooleanBuilder, Date32Builder, Float32Builder, Float64Builder,
+        Int16Builder, Int32Builder, Int64Builder, StringBuilder, Time64MicrosecondBuilder,
+        TimestampMicrosecondBuilder, UInt32Builder,
+    },
+    datatypes::{DataType as ArrowDataType, Field, Schema, SchemaRef, TimeUnit},
+    record_batch::RecordBatch,
+};
+use chrono::{NaiveDate, Timelike};
+use futures::Stream;
+use indexmap::IndexMap;
+use num_traits::ToPrimitive;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use super::data_types::DataType;
+
+/// Number of rows that are buffered before a `RecordBatch` is emitted.
+pub const RECORD_BATCH_SIZE: usize = 1024;
+
+pub type RecordBatchStream = Pin<Box<dyn Stream<Item = Result<RecordBatch>> + Send>>;
+
+/// Creates a bounded channel whose receiving half is a `RecordBatchStream`. Query routes push
+/// rows into the sender as they come off the wire, so only one batch is in memory at a time.
+pub fn record_batch_channel() -> (RecordBatchSender, RecordBatchStream) {
+    let (tx, rx) = mpsc::channel(4);
+
+    (
+        RecordBatchSender { tx, schema: None },
+        Box::pin(ReceiverStream::new(rx)),
+    )
+}
+
+pub struct RecordBatchSender {
+    tx: mpsc::Sender<Result<RecordBatch>>,
+    schema: Option<SchemaRef>,
+}
+
+impl RecordBatchSender {
+    /// Converts `rows` into a `RecordBatch` and sends it down the stream. The schema is fixed by
+    /// the first non-empty set of rows so every batch in a stream shares it. Returns `false` once
+    /// the receiver has been dropped so producers can stop fetching.
+    pub async fn send_rows(&mut self, rows: &[IndexMap<String, DataType>]) -> bool {
+        if rows.is_empty() {
+            return !self.tx.is_closed();
+        }
+
+        let schema = self
+            .schema
+            .get_or_insert_with(|| infer_arrow_schema(rows))
+            .clone();
+
+        let batch = rows_to_record_batch(&schema, rows);
+
+        self.tx.send(batch).await.is_ok()
+    }
+
+    /// Sends `rows` as batches of at most `RECORD_BATCH_SIZE` rows. Used by routes that read
+    /// results a page at a time, where pages can be much larger than a batch.
+    pub async fn send_rows_chunked(&mut self, rows: &[IndexMap<String, DataType>]) -> bool {
+        for chunk in rows.chunks(RECORD_BATCH_SIZE) {
+            if !self.send_rows(chunk).await {
+                return false;
+            }
+        }
+
+        !self.tx.is_closed()
+    }
+
+    pub async fn send_error(&self, error: Error) {
+        let _ = self.tx.send(Err(error)).await;
+    }
+}
+
+/// Wraps an already materialized result in a `RecordBatchStream`. Used by the routes whose
+/// clients hand back a whole result set at once.
+pub fn rows_to_record_batch_stream(rows: Vec<IndexMap<String, DataType>>) -> RecordBatchStream {
+    let (mut sender, stream) = record_batch_channel();
+
+    tokio::spawn(async move {
+        sender.send_rows_chunked(&rows).await;
+    });
+
+    stream
+}
+
+/// Builds an Arrow schema from the column order of the first row, using the first typed value in
+/// each column. Columns that never carry a type are exposed as strings.
+pub fn infer_arrow_schema(rows: &[IndexMap<String, DataType>]) -> SchemaRef {
+    let fields = match rows.first() {
+        Some(first_row) => first_row
+            .keys()
+            .map(|column_name| {
+                let arrow_type = rows
+                    .iter()
+                    .filter_map(|row| row.get(column_name))
+                    .find_map(|value| value.arrow_data_type())
+                    .unwrap_or(ArrowDataType::Utf8);
+
+                Field::new(column_name, arrow_type, true)
+            })
+            .collect::<Vec<Field>>(),
+        None => Vec::new(),
+    };
+
+    Arc::new(Schema::new(fields))
+}
+
+pub fn rows_to_record_batch(
+    schema: &SchemaRef,
+    rows: &[IndexMap<String, DataType>],
+) -> Result<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| build_column(field, rows))
+        .collect::<Result<Vec<ArrayRef>>>()?;
+
+    RecordBatch::try_new(schema.clone(), columns)
+        .map_err(|e| anyhow!("Error building record batch: {}", e))
+}
+
+fn build_column(field: &Field, rows: &[IndexMap<String, DataType>]) -> Result<ArrayRef> {
+    let values = rows.iter().map(|row| row.get(field.name()));
+
+    let array: ArrayRef = match field.data_type() {
+        ArrowDataType::Boolean => {
+            let mut builder = BooleanBuilder::with_capacity(rows.len());
+            for value in values {
+                builder.append_option(match value {
+                    Some(DataType::Bool(v)) => *v,
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish())
+        }
+        ArrowDataType::Binary => {
+            let mut builder = BinaryBuilder::new();
+            for value in values {
+                builder.append_option(match value {
+                    Some(DataType::Bytea(v)) => v.as_deref(),
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish())
+        }
+        ArrowDataType::Int16 => {
+            let mut builder = Int16Builder::with_capacity(rows.len());
+            for value in values {
+                builder.append_option(value.and_then(|v| v.as_i64()).and_then(|v| v.to_i16()));
+            }
+            Arc::new(builder.finish())
+        }
+        ArrowDataType::Int32 => {
+            let mut builder = Int32Builder::with_capacity(rows.len());
+            for value in values {
+                builder.append_option(value.and_then(|v| v.as_i64()).and_then(|v| v.to_i32()));
+            }
+            Arc::new(builder.finish())
+        }
+        ArrowDataType::Int64 => {
+            let mut builder = Int64Builder::with_capacity(rows.len());
+            for value in values {
+                builder.append_option(value.and_then(|v| v.as_i64()));
+            }
+            Arc::new(builder.finish())
+        }
+        ArrowDataType::UInt32 => {
+            let mut builder = UInt32Builder::with_capacity(rows.len());
+            for value in values {
+                builder.append_option(value.and_then(|v| v.as_i64()).and_then(|v| v.to_u32()));
+            }
+            Arc::new(builder.finish())
+        }
+        ArrowDataType::Float32 => {
+            let mut builder = Float32Builder::with_capacity(rows.len());
+            for value in values {
+                builder.append_option(value.and_then(|v| v.as_f64()).map(|v| v as f32));
+            }
+            Arc::new(builder.finish())
+        }
+        ArrowDataType::Float64 => {
+            let mut builder = Float64Builder::with_capacity(rows.len());
+            for value in values {
+                builder.append_option(value.and_then(|v| v.as_f64()));
+            }
+            Arc::new(builder.finish())
+        }
+        ArrowDataType::Date32 => {
+            let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+            let mut builder = Date32Builder::with_capacity(rows.len());
+            for value in values {
+                builder.append_option(match value {
+                    Some(DataType::Date(Some(date))) => {
+                        Some(date.signed_duration_since(epoch).num_days() as i32)
+                    }
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish())
+        }
+        ArrowDataType::Time64(TimeUnit::Microsecond) => {
+            let mut builder = Time64MicrosecondBuilder::with_capacity(rows.len());
+            for value in values {
+                builder.append_option(match value {
+                    Some(DataType::Time(Some(time))) => Some(
+                        time.num_seconds_from_midnight() as i64 * 1_000_000
+                            + (time.nanosecond() / 1_000) as i64,
+                    ),
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish())
+        }
+        ArrowDataType::Timestamp(TimeUnit::Microsecond, timezone) => {
+            let mut builder = TimestampMicrosecondBuilder::with_capacity(rows.len());
+            for value in values {
+                builder.append_option(match value {
+                    Some(DataType::Timestamp(Some(ts))) => Some(ts.and_utc().timestamp_micros()),
+                    Some(DataType::Timestamptz(Some(ts))) => Some(ts.timestamp_micros()),
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish().with_timezone_opt(timezone.clone()))
+        }
+        ArrowDataType::Utf8 => {
+            let mut builder = StringBuilder::new();
+            for value in values {
+                builder.append_option(value.and_then(|v| v.as_string()));
+            }
+            Arc::new(builder.finish())
+        }
+        other => return Err(anyhow!("Unsupported arrow type in result set: {}", other)),
+    };
+
+    Ok(array)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, Int32Array, StringArray, TimestampMicrosecondArray};
+    use chrono::{TimeZone, Utc};
+    use futures::StreamExt;
+
+    fn row(values: Vec<(&str, DataType)>) -> IndexMap<String, DataType> {
+        values
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect()
+    }
+
+    #[test]
+    fn infers_schema_past_untyped_nulls() {
+        let rows = vec![
+            row(vec![("id", DataType::Int4(Some(1))), ("name", DataType::Null)]),
+            row(vec![
+                ("id", DataType::Int4(None)),
+                ("name", DataType::Text(Some("a".to_string()))),
+            ]),
+        ];
+
+        let schema = infer_arrow_schema(&rows);
+
+        assert_eq!(schema.field(0).name(), "id");
+        assert_eq!(schema.field(0).data_type(), &ArrowDataType::Int32);
+        assert_eq!(schema.field(1).data_type(), &ArrowDataType::Utf8);
+    }
+
+    #[test]
+    fn converts_rows_into_a_record_batch() {
+        let ts = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let rows = vec![
+            row(vec![
+                ("id", DataType::Int4(Some(1))),
+                ("name", DataType::Text(Some("a".to_string()))),
+                ("created_at", DataType::Timestamptz(Some(ts))),
+            ]),
+            row(vec![
+                ("id", DataType::Int4(None)),
+                ("name", DataType::Null),
+                ("created_at", DataType::Null),
+            ]),
+        ];
+
+        let schema = infer_arrow_schema(&rows);
+        let batch = rows_to_record_batch(&schema, &rows).unwrap();
+
+        assert_eq!(batch.num_rows(), 2);
+
+        let ids = batch.column(0).as_any().downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(ids.value(0), 1);
+        assert!(ids.is_null(1));
+
+        let names = batch.column(1).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(names.value(0), "a");
+        assert!(names.is_null(1));
+
+        let created_at = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap();
+        assert_eq!(created_at.value(0), ts.timestamp_micros());
+    }
+
+    #[tokio::test]
+    async fn splits_results_into_batches_at_the_batch_size() {
+        let rows = (0..RECORD_BATCH_SIZE * 2 + 1)
+            .map(|i| row(vec![("id", DataType::Int4(Some(i as i32)))]))
+            .collect::<Vec<_>>();
+
+        let batches = rows_to_record_batch_stream(rows)
+            .map(|batch| batch.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(
+            batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+            vec![RECORD_BATCH_SIZE, RECORD_BATCH_SIZE, 1]
+        );
+
+        let last = batches[2]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(last.value(0), (RECORD_BATCH_SIZE * 2) as i32);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_first_schema_across_pages() {
+        let (mut sender, stream) = record_batch_channel();
+
+        tokio::spawn(async move {
+            sender
+                .send_rows_chunked(&[row(vec![("amount", DataType::Float8(Some(1.5)))])])
+                .await;
+            // A later page with a null typed differently still lands in the first page's schema.
+            sender
+                .send_rows_chunked(&[row(vec![("amount", DataType::Int4(None))])])
+                .await;
+        });
+
+        let batches = stream.collect::<Vec<_>>().await;
+
+        assert_eq!(batches.len(), 2);
+        let second = batches[1].as_ref().unwrap();
+        assert_eq!(
+            second.schema().field(0).data_type(),
+            &ArrowDataType::Float64
+        );
+        assert!(second.column(0).is_null(0));
+    }
+}
@@ -0,0 +1,601 @@
+use std::{borrow::Cow, collections::HashMap, env, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use arrow::{
+    array::{Array, ArrayRef, AsArray, LargeStringArray, StringArray},
+    datatypes::DataType as ArrowDataType,
+    record_batch::RecordBatch,
+};
+use diesel::{ExpressionMethods, JoinOnDsl, QueryDsl};
+use diesel_async::RunQueryDsl;
+use hmac::{Hmac, Mac};
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde_json::Value;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::database::{
+    enums::{ColumnAccess, ColumnMask, DataSourceType},
+    lib::get_pg_pool,
+    models::DataSource,
+    schema::{dataset_column_policies, dataset_columns, datasets},
+};
+
+use super::{
+    data_types::DataType,
+    row_level_security::{TablePolicy, UserIdentities},
+};
+
+/// Tags the MD5 digests data sources compute for hash masked columns, so the API can find them in
+/// results wherever the query put them.
+const MASKED_HASH_PREFIX: &str = "masked:";
+
+lazy_static! {
+    static ref COLUMN_MASK_SECRET: Option<String> = env::var("COLUMN_MASK_SECRET")
+        .ok()
+        .filter(|secret| !secret.is_empty());
+    static ref MASKED_HASH_REGEX: Regex =
+        Regex::new(&format!("{}([0-9a-f]{{32}})", MASKED_HASH_PREFIX)).unwrap();
+}
+
+/// How a column reads for a user once the policies of all their permission groups are combined.
+/// Columns the user can read in the clear have no restriction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnRestriction {
+    Hidden,
+    Masked(ColumnMask),
+}
+
+/// Loads the restricted columns of the datasets for a user, keyed by column id. Policies only
+/// apply to members of their permission group, and when several of a user's groups have a policy
+/// on the same column the most permissive one wins.
+pub async fn get_user_column_restrictions(
+    identities: &UserIdentities,
+    dataset_ids: &[Uuid],
+) -> Result<HashMap<Uuid, ColumnRestriction>> {
+    if identities.permission_group_ids.is_empty() || dataset_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let policies = match dataset_column_policies::table
+        .inner_join(
+            dataset_columns::table
+                .on(dataset_columns::id.eq(dataset_column_policies::dataset_column_id)),
+        )
+        .filter(dataset_columns::dataset_id.eq_any(dataset_ids))
+        .filter(
+            dataset_column_policies::permission_group_id.eq_any(&identities.permission_group_ids),
+        )
+        .filter(dataset_column_policies::deleted_at.is_null())
+        .filter(dataset_columns::deleted_at.is_null())
+        .select((
+            dataset_column_policies::dataset_column_id,
+            dataset_column_policies::access,
+            dataset_column_policies::mask,
+        ))
+        .load::<(Uuid, ColumnAccess, Option<ColumnMask>)>(&mut conn)
+        .await
+    {
+        Ok(policies) => policies,
+        Err(e) => return Err(anyhow!("Error getting column policies: {}", e)),
+    };
+
+    Ok(effective_column_restrictions(policies))
+}
+
+fn effective_column_restrictions(
+    policies: Vec<(Uuid, ColumnAccess, Option<ColumnMask>)>,
+) -> HashMap<Uuid, ColumnRestriction> {
+    let mut most_permissive: HashMap<Uuid, Option<ColumnRestriction>> = HashMap::new();
+
+    for (column_id, access, mask) in policies {
+        let restriction = match access {
+            ColumnAccess::Clear => None,
+            ColumnAccess::Masked => {
+                Some(ColumnRestriction::Masked(mask.unwrap_or(ColumnMask::Hash)))
+            }
+            ColumnAccess::Hidden => Some(ColumnRestriction::Hidden),
+        };
+
+        let current = most_permissive.entry(column_id).or_insert(restriction);
+
+        if permissiveness(&restriction) > permissiveness(current) {
+            *current = restriction;
+        }
+    }
+
+    most_permissive
+        .into_iter()
+        .filter_map(|(column_id, restriction)| restriction.map(|r| (column_id, r)))
+        .collect()
+}
+
+fn permissiveness(restriction: &Option<ColumnRestriction>) -> u8 {
+    match restriction {
+        None => 3,
+        Some(ColumnRestriction::Masked(ColumnMask::Partial)) => 2,
+        Some(ColumnRestriction::Masked(ColumnMask::Hash)) => 1,
+        Some(ColumnRestriction::Hidden) => 0,
+    }
+}
+
+/// The key hash masks are finished with in one organization, derived from `COLUMN_MASK_SECRET`.
+/// Data sources only see a plain MD5 of masked values, so equal values still compare equal in
+/// queries, and the API replaces each digest in the results with its HMAC under this key. The key
+/// never leaves the API. Without it a masked value could be looked up by hashing guesses, and the
+/// same value would mask the same way in every organization.
+#[derive(Clone, PartialEq)]
+pub struct MaskingKey {
+    key: Vec<u8>,
+}
+
+impl std::fmt::Debug for MaskingKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MaskingKey(..)")
+    }
+}
+
+impl MaskingKey {
+    /// `None` when no secret is configured, in which case hash masked columns are hidden instead.
+    pub fn for_organization(organization_id: &Uuid) -> Option<Self> {
+        COLUMN_MASK_SECRET
+            .as_ref()
+            .map(|secret| MaskingKey::derive(secret, organization_id))
+    }
+
+    fn derive(secret: &str, organization_id: &Uuid) -> Self {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(b"column-mask");
+        mac.update(organization_id.as_bytes());
+
+        MaskingKey {
+            key: mac.finalize().into_bytes().to_vec(),
+        }
+    }
+
+    fn mask_digest(&self, digest: &str) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC can take a key of any size");
+        mac.update(digest.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// Replaces every tagged digest in the text with its mask.
+    pub fn mask_text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        MASKED_HASH_REGEX.replace_all(text, |captures: &Captures| self.mask_digest(&captures[1]))
+    }
+
+    fn mask_string(&self, text: String) -> String {
+        match self.mask_text(&text) {
+            Cow::Owned(masked) => masked,
+            Cow::Borrowed(_) => text,
+        }
+    }
+
+    fn mask_json(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.mask_string(std::mem::take(text)),
+            Value::Array(values) => values.iter_mut().for_each(|value| self.mask_json(value)),
+            Value::Object(values) => values.values_mut().for_each(|value| self.mask_json(value)),
+            _ => (),
+        }
+    }
+
+    pub fn mask_rows(
+        &self,
+        rows: Vec<IndexMap<String, DataType>>,
+    ) -> Vec<IndexMap<String, DataType>> {
+        rows.into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|(name, value)| {
+                        let value = match value {
+                            DataType::Text(Some(text)) => {
+                                DataType::Text(Some(self.mask_string(text)))
+                            }
+                            DataType::Char(Some(text)) => {
+                                DataType::Char(Some(self.mask_string(text)))
+                            }
+                            DataType::Unknown(Some(text)) => {
+                                DataType::Unknown(Some(self.mask_string(text)))
+                            }
+                            DataType::Json(Some(mut json)) => {
+                                self.mask_json(&mut json);
+                                DataType::Json(Some(json))
+                            }
+                            value => value,
+                        };
+
+                        (name, value)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn mask_record_batch(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        let columns = batch
+            .columns()
+            .iter()
+            .map(|column| match column.data_type() {
+                ArrowDataType::Utf8 => Arc::new(
+                    column
+                        .as_string::<i32>()
+                        .iter()
+                        .map(|text| text.map(|text| self.mask_text(text)))
+                        .collect::<StringArray>(),
+                ) as ArrayRef,
+                ArrowDataType::LargeUtf8 => Arc::new(
+                    column
+                        .as_string::<i64>()
+                        .iter()
+                        .map(|text| text.map(|text| self.mask_text(text)))
+                        .collect::<LargeStringArray>(),
+                ) as ArrayRef,
+                _ => column.clone(),
+            })
+            .collect::<Vec<ArrayRef>>();
+
+        match RecordBatch::try_new(batch.schema(), columns) {
+            Ok(batch) => Ok(batch),
+            Err(e) => Err(anyhow!("Error masking record batch: {}", e)),
+        }
+    }
+}
+
+/// Builds the select lists for the data source's tables that have columns hidden or masked from
+/// the user. Hidden columns are left out, so queries that use them fail, and masked columns are
+/// replaced by their masked value under the same name. Hash masks are only finished once results
+/// are fetched, so the key to finish them with is returned when any column is hash masked.
+pub async fn get_user_column_projections(
+    identities: &UserIdentities,
+    data_source: &DataSource,
+) -> Result<(Vec<TablePolicy>, Option<MaskingKey>)> {
+    if identities.permission_group_ids.is_empty() {
+        return Ok((vec![], None));
+    }
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let dataset_records = match datasets::table
+        .filter(datasets::data_source_id.eq(data_source.id))
+        .filter(datasets::deleted_at.is_null())
+        .select((datasets::id, datasets::schema, datasets::database_name))
+        .load::<(Uuid, String, String)>(&mut conn)
+        .await
+    {
+        Ok(dataset_records) => dataset_records,
+        Err(e) => return Err(anyhow!("Error getting datasets: {}", e)),
+    };
+
+    let dataset_ids = dataset_records
+        .iter()
+        .map(|(id, _, _)| *id)
+        .collect::<Vec<Uuid>>();
+
+    let restrictions = get_user_column_restrictions(identities, &dataset_ids).await?;
+
+    if restrictions.is_empty() {
+        return Ok((vec![], None));
+    }
+
+    let restricted_column_ids = restrictions.keys().copied().collect::<Vec<Uuid>>();
+
+    let masking_key = if restrictions
+        .values()
+        .any(|restriction| *restriction == ColumnRestriction::Masked(ColumnMask::Hash))
+    {
+        let masking_key = MaskingKey::for_organization(&data_source.organization_id);
+
+        if masking_key.is_none() {
+            tracing::warn!("COLUMN_MASK_SECRET is not set, hash masked columns are hidden instead");
+        }
+
+        masking_key
+    } else {
+        None
+    };
+
+    let restricted_dataset_ids = match dataset_columns::table
+        .filter(dataset_columns::id.eq_any(&restricted_column_ids))
+        .select(dataset_columns::dataset_id)
+        .distinct()
+        .load::<Uuid>(&mut conn)
+        .await
+    {
+        Ok(restricted_dataset_ids) => restricted_dataset_ids,
+        Err(e) => return Err(anyhow!("Error getting restricted datasets: {}", e)),
+    };
+
+    let columns = match dataset_columns::table
+        .filter(dataset_columns::dataset_id.eq_any(&restricted_dataset_ids))
+        .filter(dataset_columns::deleted_at.is_null())
+        .order((
+            dataset_columns::created_at.asc(),
+            dataset_columns::name.asc(),
+        ))
+        .select((
+            dataset_columns::id,
+            dataset_columns::dataset_id,
+            dataset_columns::name,
+        ))
+        .load::<(Uuid, Uuid, String)>(&mut conn)
+        .await
+    {
+        Ok(columns) => columns,
+        Err(e) => return Err(anyhow!("Error getting dataset columns: {}", e)),
+    };
+
+    let mut projections: Vec<TablePolicy> = Vec::new();
+
+    for (dataset_id, schema, table) in dataset_records {
+        let dataset_columns = columns
+            .iter()
+            .filter(|(_, column_dataset_id, _)| *column_dataset_id == dataset_id)
+            .collect::<Vec<_>>();
+
+        if dataset_columns.is_empty() {
+            continue;
+        }
+
+        let select_list = dataset_columns
+            .into_iter()
+            .filter_map(|(column_id, _, name)| {
+                let column = quote_identifier(&data_source.type_, name);
+
+                match restrictions.get(column_id) {
+                    None => Some(column),
+                    Some(ColumnRestriction::Hidden) => None,
+                    Some(ColumnRestriction::Masked(mask)) => {
+                        mask_expression(&data_source.type_, &column, mask, masking_key.is_some())
+                            .map(|masked| format!("{} AS {}", masked, column))
+                    }
+                }
+            })
+            .collect::<Vec<String>>();
+
+        projections.push(TablePolicy {
+            schema,
+            table,
+            filters: vec![],
+            columns: Some(select_list),
+            source: None,
+        });
+    }
+
+    Ok((projections, masking_key))
+}
+
+/// Column names that aren't plain identifiers are quoted. Plain ones are left as they are, so the
+/// data source applies its usual case folding.
+fn quote_identifier(data_source_type: &DataSourceType, name: &str) -> String {
+    let is_plain = name
+        .chars()
+        .next()
+        .map(|c| c.is_ascii_alphabetic() || c == '_')
+        .unwrap_or(false)
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if is_plain {
+        return name.to_string();
+    }
+
+    match data_source_type {
+        DataSourceType::BigQuery
+        | DataSourceType::Databricks
+        | DataSourceType::MySql
+        | DataSourceType::Mariadb
+        | DataSourceType::StarRocks => format!("`{}`", name.replace('`', "``")),
+        _ => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+/// The SQL for a masked column value, or `None` if the column has to be hidden instead. Hashes
+/// are the lowercase hex MD5 of the value, tagged with `MASKED_HASH_PREFIX` for the API to finish
+/// with the masking key, so they can only be used when there is one. MD5 is the one hash every
+/// dialect we query has.
+fn mask_expression(
+    data_source_type: &DataSourceType,
+    column: &str,
+    mask: &ColumnMask,
+    has_masking_key: bool,
+) -> Option<String> {
+    match (mask, data_source_type) {
+        (ColumnMask::Hash, _) if !has_masking_key => None,
+        (ColumnMask::Hash, DataSourceType::ClickHouse | DataSourceType::Trino) => Some(format!(
+            "concat('{}', {})",
+            MASKED_HASH_PREFIX,
+            md5_hex(data_source_type, &as_text(data_source_type, column))
+        )),
+        (ColumnMask::Hash, data_source_type) => Some(format!(
+            "CONCAT('{}', {})",
+            MASKED_HASH_PREFIX,
+            md5_hex(data_source_type, &as_text(data_source_type, column))
+        )),
+        (ColumnMask::Partial, DataSourceType::ClickHouse) => {
+            Some(format!("concat('****', right(toString({}), 4))", column))
+        }
+        (ColumnMask::Partial, DataSourceType::Trino) => Some(format!(
+            "concat('****', substr(CAST({} AS VARCHAR), -4))",
+            column
+        )),
+        (ColumnMask::Partial, data_source_type) => Some(format!(
+            "CONCAT('****', RIGHT({}, 4))",
+            as_text(data_source_type, column)
+        )),
+    }
+}
+
+/// The lowercase hex MD5 of a text value.
+fn md5_hex(data_source_type: &DataSourceType, value: &str) -> String {
+    match data_source_type {
+        DataSourceType::BigQuery => format!("TO_HEX(MD5({}))", value),
+        DataSourceType::ClickHouse => format!("lower(hex(MD5({})))", value),
+        DataSourceType::Trino => format!("lower(to_hex(md5(to_utf8({}))))", value),
+        DataSourceType::SqlServer => format!(
+            "LOWER(CONVERT(VARCHAR(32), HASHBYTES('MD5', {}), 2))",
+            value
+        ),
+        _ => format!("MD5({})", value),
+    }
+}
+
+fn as_text(data_source_type: &DataSourceType, column: &str) -> String {
+    match data_source_type {
+        DataSourceType::ClickHouse => format!("toString({})", column),
+        data_source_type => format!("CAST({} AS {})", column, text_type(data_source_type)),
+    }
+}
+
+fn text_type(data_source_type: &DataSourceType) -> &'static str {
+    match data_source_type {
+        DataSourceType::BigQuery | DataSourceType::Databricks => "STRING",
+        DataSourceType::MySql | DataSourceType::Mariadb => "CHAR",
+        DataSourceType::SqlServer => "NVARCHAR(MAX)",
+        _ => "VARCHAR",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn most_permissive_policy_wins() {
+        let email = Uuid::new_v4();
+        let phone = Uuid::new_v4();
+        let ssn = Uuid::new_v4();
+
+        let restrictions = effective_column_restrictions(vec![
+            (email, ColumnAccess::Hidden, None),
+            (email, ColumnAccess::Masked, Some(ColumnMask::Hash)),
+            (phone, ColumnAccess::Masked, Some(ColumnMask::Partial)),
+            (phone, ColumnAccess::Clear, None),
+            (ssn, ColumnAccess::Hidden, None),
+        ]);
+
+        assert_eq!(
+            restrictions.get(&email),
+            Some(&ColumnRestriction::Masked(ColumnMask::Hash))
+        );
+        assert_eq!(restrictions.get(&phone), None);
+        assert_eq!(restrictions.get(&ssn), Some(&ColumnRestriction::Hidden));
+    }
+
+    #[test]
+    fn masks_are_written_for_the_dialect() {
+        assert_eq!(
+            mask_expression(&DataSourceType::Postgres, "email", &ColumnMask::Hash, true).unwrap(),
+            "CONCAT('masked:', MD5(CAST(email AS VARCHAR)))"
+        );
+        assert_eq!(
+            mask_expression(&DataSourceType::BigQuery, "email", &ColumnMask::Hash, true).unwrap(),
+            "CONCAT('masked:', TO_HEX(MD5(CAST(email AS STRING))))"
+        );
+        assert_eq!(
+            mask_expression(
+                &DataSourceType::SqlServer,
+                "phone",
+                &ColumnMask::Partial,
+                false
+            )
+            .unwrap(),
+            "CONCAT('****', RIGHT(CAST(phone AS NVARCHAR(MAX)), 4))"
+        );
+        assert_eq!(
+            mask_expression(&DataSourceType::Postgres, "email", &ColumnMask::Hash, false),
+            None
+        );
+    }
+
+    #[test]
+    fn hashes_are_keyed_per_organization() {
+        let acme = Uuid::new_v4();
+        let globex = Uuid::new_v4();
+
+        let acme_key = MaskingKey::derive("secret", &acme);
+
+        assert_eq!(acme_key, MaskingKey::derive("secret", &acme));
+        assert_ne!(acme_key, MaskingKey::derive("secret", &globex));
+        assert_ne!(acme_key, MaskingKey::derive("other secret", &acme));
+
+        let digest = "0c83f57c786a0b4a39efab23731c7ebc";
+        let tagged = format!("masked:{}", digest);
+
+        assert_eq!(acme_key.mask_text(&tagged), acme_key.mask_digest(digest));
+        assert_ne!(
+            acme_key.mask_text(&tagged),
+            MaskingKey::derive("secret", &globex).mask_text(&tagged)
+        );
+        assert!(!acme_key.mask_text(&tagged).contains(digest));
+        assert_eq!(acme_key.mask_text("no masks here"), "no masks here");
+    }
+
+    #[test]
+    fn tagged_digests_are_masked_wherever_they_are_returned() {
+        let masking_key = MaskingKey::derive("secret", &Uuid::new_v4());
+        let digest = "0c83f57c786a0b4a39efab23731c7ebc";
+        let tagged = format!("masked:{}", digest);
+        let masked = masking_key.mask_digest(digest);
+
+        let rows = masking_key.mask_rows(vec![IndexMap::from([
+            ("email".to_string(), DataType::Text(Some(tagged.clone()))),
+            (
+                "emails".to_string(),
+                DataType::Json(Some(serde_json::json!([tagged.clone()]))),
+            ),
+            ("id".to_string(), DataType::Int4(Some(1))),
+        ])]);
+
+        assert_eq!(rows[0]["email"], DataType::Text(Some(masked.clone())));
+        assert_eq!(
+            rows[0]["emails"],
+            DataType::Json(Some(serde_json::json!([masked.clone()])))
+        );
+        assert_eq!(rows[0]["id"], DataType::Int4(Some(1)));
+
+        let batch = RecordBatch::try_from_iter(vec![(
+            "email",
+            Arc::new(StringArray::from(vec![
+                Some(tagged.as_str()),
+                None,
+                Some("a"),
+            ])) as ArrayRef,
+        )])
+        .unwrap();
+
+        let batch = masking_key.mask_record_batch(batch).unwrap();
+        let emails = batch.column(0).as_string::<i32>();
+
+        assert_eq!(emails.value(0), masked);
+        assert!(emails.is_null(1));
+        assert_eq!(emails.value(2), "a");
+    }
+
+    #[test]
+    fn only_unusual_names_are_quoted() {
+        assert_eq!(
+            quote_identifier(&DataSourceType::Postgres, "email"),
+            "email"
+        );
+        assert_eq!(
+            quote_identifier(&DataSourceType::Postgres, "E-mail \"work\""),
+            "\"E-mail \"\"work\"\"\""
+        );
+        assert_eq!(
+            quote_identifier(&DataSourceType::MySql, "phone number"),
+            "`phone number`"
+        );
+    }
+}
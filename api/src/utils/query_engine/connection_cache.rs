@@ -0,0 +1,713 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    env,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    process::Child,
+    sync::{Arc, Mutex as StdMutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use sqlx::{MySql, Pool, Postgres};
+use tempfile::NamedTempFile;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{
+    credentials::{MySqlCredentials, PostgresCredentials, SqlServerCredentials},
+    data_source_connections::{
+        get_mysql_connection::{get_mysql_connection, get_starrocks_connection},
+        get_postgres_connection::get_postgres_connection,
+        get_redshift_connection::get_redshift_connection,
+        get_sql_server_connection::{get_sql_server_pool, SqlServerPool},
+    },
+};
+
+/// How long an unused connection is kept around before it is closed.
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 600;
+/// How often the background sweeper evicts idle and unhealthy connections.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+/// Checkouts ping the data source unless a checkout already did within this long, so a burst of
+/// queries doesn't ping once per query.
+const PING_INTERVAL: Duration = Duration::from_secs(10);
+/// How long a ping may take before the connection is considered dead.
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+static CONNECTION_CACHE: Lazy<Mutex<HashMap<Uuid, CachedConnection>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static SWEEPER: Lazy<()> = Lazy::new(|| {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            evict_idle_connections(idle_timeout()).await;
+        }
+    });
+});
+
+#[derive(Clone)]
+enum CachedClient {
+    Postgres(Pool<Postgres>),
+    Redshift(Pool<Postgres>),
+    MySql(Pool<MySql>),
+    SqlServer(SqlServerPool),
+}
+
+/// A pool checked out of the cache. It holds on to the ssh tunnel the pool connects through, so
+/// a connection evicted from the cache keeps its tunnel until the queries using it are done.
+pub struct CachedPool<P> {
+    pool: P,
+    _ssh_tunnel: Option<Arc<SshTunnel>>,
+}
+
+impl<P> CachedPool<P> {
+    fn new(pool: P, ssh_tunnel: Option<Arc<SshTunnel>>) -> Self {
+        CachedPool {
+            pool,
+            _ssh_tunnel: ssh_tunnel,
+        }
+    }
+}
+
+impl<P> Deref for CachedPool<P> {
+    type Target = P;
+
+    fn deref(&self) -> &P {
+        &self.pool
+    }
+}
+
+/// An ssh tunnel that lives as long as the cached connection and every pool checked out of it.
+struct SshTunnel {
+    process: StdMutex<Child>,
+    temp_files: Option<Vec<NamedTempFile>>,
+}
+
+impl SshTunnel {
+    fn new(process: Option<Child>, temp_files: Option<Vec<NamedTempFile>>) -> Option<Self> {
+        process.map(|process| SshTunnel {
+            process: StdMutex::new(process),
+            temp_files,
+        })
+    }
+
+    fn is_alive(&self) -> bool {
+        match self.process.lock() {
+            Ok(mut process) => matches!(process.try_wait(), Ok(None)),
+            Err(_) => false,
+        }
+    }
+}
+
+impl Drop for SshTunnel {
+    fn drop(&mut self) {
+        if let Ok(process) = self.process.get_mut() {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+        self.temp_files.take();
+    }
+}
+
+struct CachedConnection {
+    id: Uuid,
+    credentials_hash: u64,
+    client: CachedClient,
+    ssh_tunnel: Option<Arc<SshTunnel>>,
+    last_used: Instant,
+    last_pinged: Instant,
+}
+
+impl CachedConnection {
+    /// Whether the connection can still be used without asking the data source. SQL Server pools
+    /// can't be closed, so one without any open connections, which it reopens as soon as it can
+    /// reach the server, is treated as down.
+    fn is_open(&self) -> bool {
+        let tunnel_alive = match &self.ssh_tunnel {
+            Some(ssh_tunnel) => ssh_tunnel.is_alive(),
+            None => true,
+        };
+
+        let client_open = match &self.client {
+            CachedClient::Postgres(pool) | CachedClient::Redshift(pool) => !pool.is_closed(),
+            CachedClient::MySql(pool) => !pool.is_closed(),
+            CachedClient::SqlServer(pool) => pool.state().connections > 0,
+        };
+
+        tunnel_alive && client_open
+    }
+
+    /// Closes the pool. Queries that already checked it out keep their connection and ssh
+    /// tunnel until they finish.
+    async fn close(self) {
+        match &self.client {
+            CachedClient::Postgres(pool) | CachedClient::Redshift(pool) => pool.close().await,
+            CachedClient::MySql(pool) => pool.close().await,
+            CachedClient::SqlServer(_) => (),
+        }
+    }
+}
+
+/// Returns a pooled Postgres (or Supabase) connection for the data source, creating it and any
+/// ssh tunnel it needs on first use.
+pub async fn get_cached_postgres_pool(
+    data_source_id: &Uuid,
+    credentials_string: &String,
+) -> Result<CachedPool<Pool<Postgres>>> {
+    let credentials_hash = hash_credentials(credentials_string);
+
+    if let Some((CachedClient::Postgres(pool), ssh_tunnel)) =
+        checkout(data_source_id, credentials_hash).await
+    {
+        return Ok(CachedPool::new(pool, ssh_tunnel));
+    }
+
+    let credentials: PostgresCredentials = serde_json::from_str(credentials_string)?;
+    let (pool, ssh_tunnel, temp_files) = get_postgres_connection(&credentials, pool_size()).await?;
+
+    let (client, ssh_tunnel) = checkin(
+        data_source_id,
+        credentials_hash,
+        CachedClient::Postgres(pool),
+        SshTunnel::new(ssh_tunnel, temp_files),
+    )
+    .await;
+
+    match client {
+        CachedClient::Postgres(pool) => Ok(CachedPool::new(pool, ssh_tunnel)),
+        _ => Err(anyhow!("Cached connection has an unexpected type")),
+    }
+}
+
+pub async fn get_cached_redshift_pool(
+    data_source_id: &Uuid,
+    credentials_string: &String,
+) -> Result<CachedPool<Pool<Postgres>>> {
+    let credentials_hash = hash_credentials(credentials_string);
+
+    if let Some((CachedClient::Redshift(pool), ssh_tunnel)) =
+        checkout(data_source_id, credentials_hash).await
+    {
+        return Ok(CachedPool::new(pool, ssh_tunnel));
+    }
+
+    let credentials: PostgresCredentials = serde_json::from_str(credentials_string)?;
+    let pool = get_redshift_connection(&credentials, pool_size()).await?;
+
+    let (client, ssh_tunnel) = checkin(
+        data_source_id,
+        credentials_hash,
+        CachedClient::Redshift(pool),
+        None,
+    )
+    .await;
+
+    match client {
+        CachedClient::Redshift(pool) => Ok(CachedPool::new(pool, ssh_tunnel)),
+        _ => Err(anyhow!("Cached connection has an unexpected type")),
+    }
+}
+
+pub async fn get_cached_mysql_pool(
+    data_source_id: &Uuid,
+    credentials_string: &String,
+) -> Result<CachedPool<Pool<MySql>>> {
+    let credentials_hash = hash_credentials(credentials_string);
+
+    if let Some((CachedClient::MySql(pool), ssh_tunnel)) =
+        checkout(data_source_id, credentials_hash).await
+    {
+        return Ok(CachedPool::new(pool, ssh_tunnel));
+    }
+
+    let credentials: MySqlCredentials = serde_json::from_str(credentials_string)?;
+    let (pool, ssh_tunnel, temp_files) = get_mysql_connection(&credentials, pool_size()).await?;
+
+    let (client, ssh_tunnel) = checkin(
+        data_source_id,
+        credentials_hash,
+        CachedClient::MySql(pool),
+        SshTunnel::new(ssh_tunnel, temp_files),
+    )
+    .await;
+
+    match client {
+        CachedClient::MySql(pool) => Ok(CachedPool::new(pool, ssh_tunnel)),
+        _ => Err(anyhow!("Cached connection has an unexpected type")),
+    }
+}
+
+pub async fn get_cached_starrocks_pool(
+    data_source_id: &Uuid,
+    credentials_string: &String,
+) -> Result<CachedPool<Pool<MySql>>> {
+    let credentials_hash = hash_credentials(credentials_string);
+
+    if let Some((CachedClient::MySql(pool), ssh_tunnel)) =
+        checkout(data_source_id, credentials_hash).await
+    {
+        return Ok(CachedPool::new(pool, ssh_tunnel));
+    }
+
+    let credentials: MySqlCredentials = serde_json::from_str(credentials_string)?;
+    let (pool, ssh_tunnel, temp_files) =
+        get_starrocks_connection(&credentials, pool_size()).await?;
+
+    let (client, ssh_tunnel) = checkin(
+        data_source_id,
+        credentials_hash,
+        CachedClient::MySql(pool),
+        SshTunnel::new(ssh_tunnel, temp_files),
+    )
+    .await;
+
+    match client {
+        CachedClient::MySql(pool) => Ok(CachedPool::new(pool, ssh_tunnel)),
+        _ => Err(anyhow!("Cached connection has an unexpected type")),
+    }
+}
+
+pub async fn get_cached_sql_server_pool(
+    data_source_id: &Uuid,
+    credentials_string: &String,
+) -> Result<CachedPool<SqlServerPool>> {
+    let credentials_hash = hash_credentials(credentials_string);
+
+    if let Some((CachedClient::SqlServer(pool), ssh_tunnel)) =
+        checkout(data_source_id, credentials_hash).await
+    {
+        return Ok(CachedPool::new(pool, ssh_tunnel));
+    }
+
+    let credentials: SqlServerCredentials = serde_json::from_str(credentials_string)?;
+    let (pool, ssh_tunnel, temp_files) = get_sql_server_pool(&credentials, pool_size()).await?;
+
+    let (client, ssh_tunnel) = checkin(
+        data_source_id,
+        credentials_hash,
+        CachedClient::SqlServer(pool),
+        SshTunnel::new(ssh_tunnel, temp_files),
+    )
+    .await;
+
+    match client {
+        CachedClient::SqlServer(pool) => Ok(CachedPool::new(pool, ssh_tunnel)),
+        _ => Err(anyhow!("Cached connection has an unexpected type")),
+    }
+}
+
+/// Closes and forgets every cached connection for a data source. Called whenever its
+/// credentials change or it is deleted.
+pub async fn invalidate_data_source_connections(data_source_id: &Uuid) {
+    let removed = CONNECTION_CACHE.lock().await.remove(data_source_id);
+
+    if let Some(connection) = removed {
+        connection.close().await;
+    }
+}
+
+/// Hands out the cached connection for the data source if it was created with the same
+/// credentials and still works. The data source is pinged outside of the cache lock, so a slow
+/// data source doesn't hold up checkouts for the others.
+async fn checkout(
+    data_source_id: &Uuid,
+    credentials_hash: u64,
+) -> Option<(CachedClient, Option<Arc<SshTunnel>>)> {
+    Lazy::force(&SWEEPER);
+
+    let mut cache = CONNECTION_CACHE.lock().await;
+
+    let connection = cache.get_mut(data_source_id)?;
+
+    if connection.credentials_hash != credentials_hash || !connection.is_open() {
+        let connection_id = connection.id;
+        drop(cache);
+        remove_connection(data_source_id, &connection_id).await;
+        return None;
+    }
+
+    connection.last_used = Instant::now();
+
+    let connection_id = connection.id;
+    let client = connection.client.clone();
+    let ssh_tunnel = connection.ssh_tunnel.clone();
+    let needs_ping = connection.last_pinged.elapsed() > PING_INTERVAL;
+
+    drop(cache);
+
+    if needs_ping {
+        if let Err(e) = ping(&client).await {
+            tracing::warn!("Evicting cached data source connection: {}", e);
+            remove_connection(data_source_id, &connection_id).await;
+            return None;
+        }
+
+        if let Some(connection) = CONNECTION_CACHE.lock().await.get_mut(data_source_id) {
+            if connection.id == connection_id {
+                connection.last_pinged = Instant::now();
+            }
+        }
+    }
+
+    Some((client, ssh_tunnel))
+}
+
+/// Stores a freshly created connection. If another request raced us and already cached a
+/// connection for the same credentials, that one wins and ours is closed.
+async fn checkin(
+    data_source_id: &Uuid,
+    credentials_hash: u64,
+    client: CachedClient,
+    ssh_tunnel: Option<SshTunnel>,
+) -> (CachedClient, Option<Arc<SshTunnel>>) {
+    let ssh_tunnel = ssh_tunnel.map(Arc::new);
+
+    let new_connection = CachedConnection {
+        id: Uuid::new_v4(),
+        credentials_hash,
+        client: client.clone(),
+        ssh_tunnel: ssh_tunnel.clone(),
+        last_used: Instant::now(),
+        last_pinged: Instant::now(),
+    };
+
+    let mut cache = CONNECTION_CACHE.lock().await;
+
+    if let Some(existing) = cache.get_mut(data_source_id) {
+        if existing.credentials_hash == credentials_hash && existing.is_open() {
+            existing.last_used = Instant::now();
+            let existing_client = existing.client.clone();
+            let existing_ssh_tunnel = existing.ssh_tunnel.clone();
+            drop(cache);
+            new_connection.close().await;
+            return (existing_client, existing_ssh_tunnel);
+        }
+    }
+
+    let replaced = cache.insert(*data_source_id, new_connection);
+    drop(cache);
+
+    if let Some(replaced) = replaced {
+        replaced.close().await;
+    }
+
+    (client, ssh_tunnel)
+}
+
+/// Closes the cached connection, unless it was already replaced by another one in the meantime.
+async fn remove_connection(data_source_id: &Uuid, connection_id: &Uuid) {
+    let removed = {
+        let mut cache = CONNECTION_CACHE.lock().await;
+
+        match cache.get(data_source_id) {
+            Some(connection) if connection.id == *connection_id => cache.remove(data_source_id),
+            _ => None,
+        }
+    };
+
+    if let Some(connection) = removed {
+        connection.close().await;
+    }
+}
+
+async fn ping(client: &CachedClient) -> Result<()> {
+    let ping = async {
+        match client {
+            CachedClient::Postgres(pool) | CachedClient::Redshift(pool) => {
+                sqlx::query("SELECT 1").execute(pool).await?;
+            }
+            CachedClient::MySql(pool) => {
+                sqlx::query("SELECT 1").execute(pool).await?;
+            }
+            CachedClient::SqlServer(pool) => {
+                let mut conn = match pool.get().await {
+                    Ok(conn) => conn,
+                    Err(e) => return Err(anyhow!(e)),
+                };
+
+                conn.in_query = true;
+                conn.client
+                    .simple_query("SELECT 1")
+                    .await?
+                    .into_row()
+                    .await?;
+                conn.in_query = false;
+            }
+        }
+
+        Ok(())
+    };
+
+    match tokio::time::timeout(PING_TIMEOUT, ping).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(anyhow!("Error pinging data source: {}", e)),
+        Err(_) => Err(anyhow!("Pinging the data source timed out")),
+    }
+}
+
+async fn evict_idle_connections(idle_timeout: Duration) {
+    let evicted = {
+        let mut cache = CONNECTION_CACHE.lock().await;
+
+        let evicted_ids = cache
+            .iter()
+            .filter_map(|(id, connection)| {
+                if connection.last_used.elapsed() > idle_timeout || !connection.is_open() {
+                    Some(*id)
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<Uuid>>();
+
+        evicted_ids
+            .iter()
+            .filter_map(|id| cache.remove(id))
+            .collect::<Vec<CachedConnection>>()
+    };
+
+    for connection in evicted {
+        connection.close().await;
+    }
+}
+
+fn hash_credentials(credentials_string: &String) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    credentials_string.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn pool_size() -> u32 {
+    env::var("DATA_SOURCE_POOL_SIZE")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(5)
+}
+
+fn idle_timeout() -> Duration {
+    let secs = env::var("DATA_SOURCE_IDLE_TIMEOUT_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(DEFAULT_IDLE_TIMEOUT_SECS);
+
+    Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    use bb8_redis::bb8;
+    use futures::future::join_all;
+    use sqlx::postgres::PgPoolOptions;
+
+    use crate::utils::query_engine::data_source_connections::get_sql_server_connection::SqlServerConnectionManager;
+
+    /// A pool that never connects, which is enough to track what the cache closes.
+    fn lazy_pool() -> Pool<Postgres> {
+        PgPoolOptions::new()
+            .connect_lazy("postgres://postgres@127.0.0.1:1/postgres")
+            .unwrap()
+    }
+
+    async fn cache_pool(data_source_id: &Uuid, credentials_hash: u64) -> Pool<Postgres> {
+        let pool = lazy_pool();
+        checkin(
+            data_source_id,
+            credentials_hash,
+            CachedClient::Postgres(pool.clone()),
+            None,
+        )
+        .await;
+        pool
+    }
+
+    #[tokio::test]
+    async fn reuses_the_connection_for_the_same_credentials() {
+        let data_source_id = Uuid::new_v4();
+        let pool = cache_pool(&data_source_id, 1).await;
+
+        assert!(matches!(
+            checkout(&data_source_id, 1).await,
+            Some((CachedClient::Postgres(_), None))
+        ));
+        assert!(!pool.is_closed());
+    }
+
+    #[tokio::test]
+    async fn closes_the_connection_when_the_credentials_change() {
+        let data_source_id = Uuid::new_v4();
+        let pool = cache_pool(&data_source_id, 1).await;
+
+        assert!(checkout(&data_source_id, 2).await.is_none());
+        assert!(pool.is_closed());
+        assert!(!CONNECTION_CACHE.lock().await.contains_key(&data_source_id));
+    }
+
+    #[tokio::test]
+    async fn closes_the_connection_when_the_data_source_is_invalidated() {
+        let data_source_id = Uuid::new_v4();
+        let pool = cache_pool(&data_source_id, 1).await;
+
+        invalidate_data_source_connections(&data_source_id).await;
+
+        assert!(pool.is_closed());
+        assert!(checkout(&data_source_id, 1).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn evicts_idle_connections_only() {
+        let idle_id = Uuid::new_v4();
+        let active_id = Uuid::new_v4();
+        let idle_pool = cache_pool(&idle_id, 1).await;
+        let active_pool = cache_pool(&active_id, 1).await;
+
+        CONNECTION_CACHE
+            .lock()
+            .await
+            .get_mut(&idle_id)
+            .unwrap()
+            .last_used = Instant::now() - Duration::from_secs(20);
+
+        evict_idle_connections(Duration::from_secs(10)).await;
+
+        assert!(idle_pool.is_closed());
+        assert!(checkout(&idle_id, 1).await.is_none());
+        assert!(!active_pool.is_closed());
+        assert!(checkout(&active_id, 1).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn replaces_closed_connections() {
+        let data_source_id = Uuid::new_v4();
+        let pool = cache_pool(&data_source_id, 1).await;
+
+        pool.close().await;
+
+        assert!(checkout(&data_source_id, 1).await.is_none());
+
+        let replacement = cache_pool(&data_source_id, 1).await;
+        assert!(checkout(&data_source_id, 1).await.is_some());
+        assert!(!replacement.is_closed());
+    }
+
+    #[tokio::test]
+    async fn concurrent_checkouts_share_one_connection() {
+        let data_source_id = Uuid::new_v4();
+        let pool = cache_pool(&data_source_id, 1).await;
+
+        let checkouts = join_all((0..50).map(|_| checkout(&data_source_id, 1))).await;
+
+        assert!(checkouts
+            .iter()
+            .all(|client| matches!(client, Some((CachedClient::Postgres(_), _)))));
+
+        // Every checkout is a handle on the same pool, so closing one closes them all.
+        pool.close().await;
+        assert!(checkouts.into_iter().all(|client| match client {
+            Some((CachedClient::Postgres(pool), _)) => pool.is_closed(),
+            _ => false,
+        }));
+    }
+
+    #[tokio::test]
+    async fn racing_checkins_keep_the_first_connection() {
+        let data_source_id = Uuid::new_v4();
+        let first = lazy_pool();
+        let second = lazy_pool();
+
+        let (first_client, second_client) = tokio::join!(
+            checkin(
+                &data_source_id,
+                1,
+                CachedClient::Postgres(first.clone()),
+                None
+            ),
+            checkin(
+                &data_source_id,
+                1,
+                CachedClient::Postgres(second.clone()),
+                None
+            ),
+        );
+
+        // Both callers end up with the same pool and the loser's is closed.
+        let ((CachedClient::Postgres(first_client), _), (CachedClient::Postgres(second_client), _)) =
+            (first_client, second_client)
+        else {
+            panic!("Cached connection has an unexpected type");
+        };
+        assert_eq!(first.is_closed(), !second.is_closed());
+
+        let winner = if first.is_closed() { &second } else { &first };
+        winner.close().await;
+        assert!(first_client.is_closed() && second_client.is_closed());
+    }
+
+    #[tokio::test]
+    async fn evicts_connections_that_fail_their_ping() {
+        let data_source_id = Uuid::new_v4();
+        let pool = cache_pool(&data_source_id, 1).await;
+
+        CONNECTION_CACHE
+            .lock()
+            .await
+            .get_mut(&data_source_id)
+            .unwrap()
+            .last_pinged = Instant::now() - PING_INTERVAL * 2;
+
+        // Nothing listens on the pool's port, so the ping fails.
+        assert!(checkout(&data_source_id, 1).await.is_none());
+        assert!(pool.is_closed());
+        assert!(!CONNECTION_CACHE.lock().await.contains_key(&data_source_id));
+    }
+
+    #[tokio::test]
+    async fn evicts_sql_server_pools_without_connections() {
+        let data_source_id = Uuid::new_v4();
+
+        let mut config = tiberius::Config::new();
+        config.host("127.0.0.1");
+        config.port(1);
+
+        let pool = bb8::Pool::builder()
+            .retry_connection(false)
+            .build_unchecked(SqlServerConnectionManager::new(config));
+
+        checkin(&data_source_id, 1, CachedClient::SqlServer(pool), None).await;
+
+        assert!(checkout(&data_source_id, 1).await.is_none());
+        assert!(!CONNECTION_CACHE.lock().await.contains_key(&data_source_id));
+    }
+
+    #[tokio::test]
+    async fn ssh_tunnels_outlive_eviction_while_checked_out() {
+        let data_source_id = Uuid::new_v4();
+        let process = Command::new("sleep").arg("60").spawn().unwrap();
+
+        checkin(
+            &data_source_id,
+            1,
+            CachedClient::Postgres(lazy_pool()),
+            SshTunnel::new(Some(process), None),
+        )
+        .await;
+
+        let (_, ssh_tunnel) = checkout(&data_source_id, 1).await.unwrap();
+        let ssh_tunnel = ssh_tunnel.unwrap();
+        let tunnel = Arc::downgrade(&ssh_tunnel);
+
+        invalidate_data_source_connections(&data_source_id).await;
+
+        // The query that checked the connection out still has its tunnel.
+        assert!(tunnel.upgrade().unwrap().is_alive());
+
+        // Once it is done with the connection, the tunnel is shut down.
+        drop(ssh_tunnel);
+        assert!(tunnel.upgrade().is_none());
+    }
+}
@@ -16,14 +16,10 @@ pub enum Credential {
     Redshift(RedshiftCredentials),
     Databricks(DatabricksCredentials),
     Snowflake(SnowflakeCredentials),
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct AthenaCredentials {
-    pub data_source: String,
-    pub db_database: String,
-    pub aws_access_key: String,
-    pub aws_secret_access: String,
+    DuckDb(DuckDbCredentials),
+    ClickHouse(ClickHouseCredentials),
+    StarRocks(MySqlCredentials),
+    Trino(TrinoCredentials),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -33,6 +29,20 @@ pub struct BigqueryCredentials {
     pub dataset_ids: Option<Vec<String>>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClickHouseCredentials {
+    pub host: String,
+    pub port: u16,
+    #[serde(alias = "user")]
+    pub username: String,
+    pub password: String,
+    #[serde(alias = "schema")]
+    pub database: String,
+    /// Connect over https. Defaults to true when the port is 8443.
+    pub secure: Option<bool>,
+    pub schemas: Option<Vec<String>>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DatabricksCredentials {
     pub host: String,
@@ -42,6 +52,15 @@ pub struct DatabricksCredentials {
     pub schemas: Option<Vec<String>>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DuckDbCredentials {
+    /// Path to a DuckDB database file inside `DUCKDB_DATA_DIR`, relative to it or absolute, or
+    /// `:memory:` for an empty in-memory database.
+    pub path: String,
+    pub read_only: Option<bool>,
+    pub schemas: Option<Vec<String>>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MariadbCredentials {
     pub host: String,
@@ -117,6 +136,23 @@ pub struct SqlServerCredentials {
     pub schemas: Option<Vec<String>>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrinoCredentials {
+    /// Host of the Trino (or Presto) coordinator.
+    pub host: String,
+    pub port: u16,
+    /// Connect over https. Defaults to true when the port is 443.
+    pub secure: Option<bool>,
+    pub catalog: String,
+    /// Default schema for unqualified table names.
+    pub schema: String,
+    #[serde(alias = "user")]
+    pub username: String,
+    /// Sent as basic auth, which Trino only accepts over https.
+    pub password: Option<String>,
+    pub schemas: Option<Vec<String>>,
+}
+
 impl Credential {
     pub fn get_type_string(&self) -> String {
         match self {
@@ -127,6 +163,10 @@ impl Credential {
             Credential::Redshift(_) => "redshift".to_string(),
             Credential::Databricks(_) => "databricks".to_string(),
             Credential::Snowflake(_) => "snowflake".to_string(),
+            Credential::DuckDb(_) => "duckdb".to_string(),
+            Credential::ClickHouse(_) => "clickhouse".to_string(),
+            Credential::StarRocks(_) => "starrocks".to_string(),
+            Credential::Trino(_) => "trino".to_string(),
         }
     }
 
@@ -139,6 +179,10 @@ impl Credential {
             Credential::Redshift(_) => DataSourceType::Redshift,
             Credential::Databricks(_) => DataSourceType::Databricks,
             Credential::Snowflake(_) => DataSourceType::Snowflake,
+            Credential::DuckDb(_) => DataSourceType::DuckDb,
+            Credential::ClickHouse(_) => DataSourceType::ClickHouse,
+            Credential::StarRocks(_) => DataSourceType::StarRocks,
+            Credential::Trino(_) => DataSourceType::Trino,
         }
     }
 }
@@ -167,6 +211,17 @@ pub async fn get_data_source_credentials(
                 Err(e) => return Err(anyhow!("Error deserializing BigQuery secret: {:?}", e)),
             }
         }
+        DataSourceType::ClickHouse => {
+            match serde_json::from_str::<ClickHouseCredentials>(&secret_string) {
+                Ok(mut credential) => {
+                    if redact_secret {
+                        credential.password = "[REDACTED]".to_string();
+                    }
+                    Credential::ClickHouse(credential)
+                }
+                Err(e) => return Err(anyhow!("Error deserializing ClickHouse secret: {:?}", e)),
+            }
+        }
         DataSourceType::Databricks => {
             match serde_json::from_str::<DatabricksCredentials>(&secret_string) {
                 Ok(mut credential) => {
@@ -178,6 +233,10 @@ pub async fn get_data_source_credentials(
                 Err(e) => return Err(anyhow!("Error deserializing Databricks secret: {:?}", e)),
             }
         }
+        DataSourceType::DuckDb => match serde_json::from_str::<DuckDbCredentials>(&secret_string) {
+            Ok(credential) => Credential::DuckDb(credential),
+            Err(e) => return Err(anyhow!("Error deserializing DuckDB secret: {:?}", e)),
+        },
         DataSourceType::MySql => match serde_json::from_str::<MySqlCredentials>(&secret_string) {
             Ok(mut credential) => {
                 if redact_secret {
@@ -248,6 +307,19 @@ pub async fn get_data_source_credentials(
                 Err(e) => return Err(anyhow!("Error deserializing SQL Server secret: {:?}", e)),
             }
         }
+        DataSourceType::StarRocks => {
+            match serde_json::from_str::<MySqlCredentials>(&secret_string) {
+                Ok(mut credential) => {
+                    if redact_secret {
+                        credential.password = "[REDACTED]".to_string();
+                        credential.ssh_private_key =
+                            credential.ssh_private_key.map(|_| "[REDACTED]".to_string());
+                    }
+                    Credential::StarRocks(credential)
+                }
+                Err(e) => return Err(anyhow!("Error deserializing StarRocks secret: {:?}", e)),
+            }
+        }
         DataSourceType::Supabase => {
             match serde_json::from_str::<PostgresCredentials>(&secret_string) {
                 Ok(mut credential) => {
@@ -261,6 +333,15 @@ pub async fn get_data_source_credentials(
                 Err(e) => return Err(anyhow!("Error deserializing Supabase secret: {:?}", e)),
             }
         }
+        DataSourceType::Trino => match serde_json::from_str::<TrinoCredentials>(&secret_string) {
+            Ok(mut credential) => {
+                if redact_secret {
+                    credential.password = credential.password.map(|_| "[REDACTED]".to_string());
+                }
+                Credential::Trino(credential)
+            }
+            Err(e) => return Err(anyhow!("Error deserializing Trino secret: {:?}", e)),
+        },
     };
     Ok(credential)
 }
@@ -0,0 +1,155 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::utils::query_engine::credentials::ClickHouseCredentials;
+
+pub async fn get_clickhouse_client(credentials: &ClickHouseCredentials) -> Result<ClickHouse> {
+    let clickhouse_client = ClickHouse::new(credentials);
+
+    Ok(clickhouse_client)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClickHouseColumn {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+}
+
+/// Body of a `JSONCompact` response: column metadata plus one array of values per row.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClickHouseResponse {
+    pub meta: Vec<ClickHouseColumn>,
+    pub data: Vec<Vec<Value>>,
+    pub rows: Option<u64>,
+}
+
+/// Minimal client for the ClickHouse HTTP interface.
+#[derive(Clone)]
+pub struct ClickHouse {
+    pub url: String,
+    pub username: String,
+    pub password: String,
+    pub database: String,
+}
+
+impl ClickHouse {
+    pub fn new(credentials: &ClickHouseCredentials) -> Self {
+        let secure = credentials.secure.unwrap_or(credentials.port == 8443);
+        let host = credentials
+            .host
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_end_matches('/');
+
+        ClickHouse {
+            url: format!(
+                "{scheme}://{host}:{port}/",
+                scheme = if secure { "https" } else { "http" },
+                host = host,
+                port = credentials.port
+            ),
+            username: credentials.username.clone(),
+            password: credentials.password.clone(),
+            database: credentials.database.clone(),
+        }
+    }
+
+    /// Runs `statement` and returns its results. When `limit` is set the server stops reading once
+    /// that many rows have been produced.
+    pub async fn query(&self, statement: String, limit: Option<i64>) -> Result<ClickHouseResponse> {
+        self.execute(statement, limit, None).await
+    }
+
+    /// Like `query`, but runs the statement under `query_id` so it can be stopped with
+    /// `kill_query`.
+    pub async fn query_with_id(
+        &self,
+        statement: String,
+        limit: Option<i64>,
+        query_id: &str,
+    ) -> Result<ClickHouseResponse> {
+        self.execute(statement, limit, Some(query_id)).await
+    }
+
+    pub async fn kill_query(&self, query_id: &str) -> Result<()> {
+        let query_id = query_id.replace('\\', "\\\\").replace('\'', "\\'");
+
+        self.query(
+            format!("KILL QUERY WHERE query_id = '{}' ASYNC", query_id),
+            None,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn execute(
+        &self,
+        statement: String,
+        limit: Option<i64>,
+        query_id: Option<&str>,
+    ) -> Result<ClickHouseResponse> {
+        let client = reqwest::Client::new();
+
+        let mut params = vec![
+            ("database", self.database.clone()),
+            ("default_format", "JSONCompact".to_string()),
+            ("output_format_json_quote_64bit_integers", "0".to_string()),
+            ("output_format_json_quote_decimals", "0".to_string()),
+            ("date_time_output_format", "iso".to_string()),
+        ];
+
+        if let Some(limit) = limit {
+            params.push(("max_result_rows", limit.to_string()));
+            params.push(("result_overflow_mode", "break".to_string()));
+        }
+
+        if let Some(query_id) = query_id {
+            params.push(("query_id", query_id.to_string()));
+        }
+
+        let query_result = match client
+            .post(&self.url)
+            .query(&params)
+            .header("X-ClickHouse-User", &self.username)
+            .header("X-ClickHouse-Key", &self.password)
+            .timeout(Duration::from_secs(300))
+            .body(statement)
+            .send()
+            .await
+        {
+            Ok(res) => res,
+            Err(e) => return Err(anyhow!(e.to_string())),
+        };
+
+        if !query_result.status().is_success() {
+            let status = query_result.status();
+            let body = query_result.text().await.unwrap_or_default();
+            return Err(anyhow!("ClickHouse returned {}: {}", status, body.trim()));
+        }
+
+        let body = match query_result.text().await {
+            Ok(body) => body,
+            Err(e) => return Err(anyhow!(e.to_string())),
+        };
+
+        // Statements that don't return rows (e.g. `CREATE VIEW`) come back with an empty body.
+        if body.trim().is_empty() {
+            return Ok(ClickHouseResponse {
+                meta: Vec::new(),
+                data: Vec::new(),
+                rows: Some(0),
+            });
+        }
+
+        let response: ClickHouseResponse = match serde_json::from_str(&body) {
+            Ok(res) => res,
+            Err(e) => return Err(anyhow!(e.to_string())),
+        };
+
+        Ok(response)
+    }
+}
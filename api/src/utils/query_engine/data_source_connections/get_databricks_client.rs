@@ -43,11 +43,19 @@ pub struct DatabricksQuery {
     pub warehouse_id: String,
     pub catalog: String,
     pub statement: String,
+    pub wait_timeout: String,
+    pub on_wait_timeout: String,
+}
+
+#[derive(Serialize, Debug, Deserialize, Clone)]
+pub struct StatementError {
+    pub message: Option<String>,
 }
 
 #[derive(Serialize, Debug, Deserialize, Clone)]
 pub struct Status {
     pub state: String,
+    pub error: Option<StatementError>,
 }
 
 #[derive(Serialize, Debug, Deserialize, Clone)]
@@ -58,9 +66,12 @@ pub struct Schema {
 
 #[derive(Serialize, Debug, Deserialize, Clone)]
 pub struct DatabricksResult {
+    pub chunk_index: Option<i32>,
     pub row_count: Option<i32>,
     pub row_offset: Option<i32>,
-    pub data_array: Option<Vec<Vec<String>>>,
+    pub data_array: Option<Vec<Vec<Option<String>>>>,
+    /// Set while more chunks of the result are left to fetch with `fetch_chunk`.
+    pub next_chunk_index: Option<i32>,
 }
 
 #[derive(Serialize, Debug, Deserialize, Clone)]
@@ -73,8 +84,9 @@ pub struct Manifest {
 pub struct QueryResponse {
     pub statement_id: String,
     pub status: Status,
-    pub manifest: Manifest,
-    pub result: DatabricksResult,
+    /// Only set once the statement has succeeded.
+    pub manifest: Option<Manifest>,
+    pub result: Option<DatabricksResult>,
 }
 
 impl Databricks {
@@ -87,33 +99,127 @@ impl Databricks {
         }
     }
 
-    pub async fn query(self, statement: String) -> Result<QueryResponse> {
-        let client = reqwest::Client::new();
+    /// Runs `statement` and waits for it to finish.
+    pub async fn query(&self, statement: String) -> Result<QueryResponse> {
+        let response = self.submit_statement(statement).await?;
+
+        self.wait_for_statement(response).await
+    }
 
+    /// Submits `statement` and returns once it finishes or has been running for a few seconds,
+    /// whichever comes first. Long statements keep running and are picked up with
+    /// `wait_for_statement`.
+    pub async fn submit_statement(&self, statement: String) -> Result<QueryResponse> {
         let databricks_query = DatabricksQuery {
-            warehouse_id: self.warehouse_id,
-            catalog: self.catalog_name,
+            warehouse_id: self.warehouse_id.clone(),
+            catalog: self.catalog_name.clone(),
             statement: statement,
+            wait_timeout: "10s".to_string(),
+            on_wait_timeout: "CONTINUE".to_string(),
+        };
+
+        let request = reqwest::Client::new()
+            .post(format!("{}/api/2.0/sql/statements/", self.base_url()))
+            .json(&databricks_query);
+
+        self.send(request).await
+    }
+
+    /// Polls the statement until it has succeeded. Failed and cancelled statements are returned
+    /// as errors.
+    pub async fn wait_for_statement(&self, mut response: QueryResponse) -> Result<QueryResponse> {
+        loop {
+            match response.status.state.as_str() {
+                "SUCCEEDED" => return Ok(response),
+                "PENDING" | "RUNNING" => (),
+                state => {
+                    let message = response
+                        .status
+                        .error
+                        .and_then(|error| error.message)
+                        .unwrap_or_default();
+                    return Err(anyhow!("Databricks statement {}: {}", state, message));
+                }
+            }
+
+            tokio::time::sleep(Duration::from_millis(500)).await;
+
+            let request = reqwest::Client::new().get(format!(
+                "{base_url}/api/2.0/sql/statements/{statement_id}",
+                base_url = self.base_url(),
+                statement_id = response.statement_id
+            ));
+
+            response = self.send(request).await?;
+        }
+    }
+
+    /// Fetches one chunk of a succeeded statement's result. Large results come back a chunk at a
+    /// time, with the first chunk inlined in the statement response.
+    pub async fn fetch_chunk(
+        &self,
+        statement_id: &str,
+        chunk_index: i32,
+    ) -> Result<DatabricksResult> {
+        let request = reqwest::Client::new().get(format!(
+            "{base_url}/api/2.0/sql/statements/{statement_id}/result/chunks/{chunk_index}",
+            base_url = self.base_url(),
+            statement_id = statement_id,
+            chunk_index = chunk_index
+        ));
+
+        let response = match self.authorize(request).send().await {
+            Ok(res) => res,
+            Err(e) => return Err(anyhow!(e.to_string())),
         };
 
-        let query_result = match client
-            .post(format!(
-                "https://{host}/api/2.0/sql/statements/",
-                host = self.host
-            ))
-            .headers({
-                let mut headers = reqwest::header::HeaderMap::new();
-                headers.insert(
-                    reqwest::header::AUTHORIZATION,
-                    format!("Bearer {}", self.api_key).parse().unwrap(),
-                );
-                headers
-            })
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Databricks returned {} fetching result chunk {}",
+                response.status(),
+                chunk_index
+            ));
+        }
+
+        match response.json::<DatabricksResult>().await {
+            Ok(res) => Ok(res),
+            Err(e) => Err(anyhow!(e.to_string())),
+        }
+    }
+
+    pub async fn cancel_statement(&self, statement_id: &str) -> Result<()> {
+        let request = reqwest::Client::new().post(format!(
+            "{base_url}/api/2.0/sql/statements/{statement_id}/cancel",
+            base_url = self.base_url(),
+            statement_id = statement_id
+        ));
+
+        match self.authorize(request).send().await {
+            Ok(res) if res.status().is_success() => Ok(()),
+            Ok(res) => Err(anyhow!("Databricks returned {} cancelling statement", res.status())),
+            Err(e) => Err(anyhow!(e.to_string())),
+        }
+    }
+
+    /// Hosts are usually bare workspace hostnames; a scheme is kept when one is given.
+    fn base_url(&self) -> String {
+        let host = self.host.trim_end_matches('/');
+
+        if host.starts_with("https://") || host.starts_with("http://") {
+            host.to_string()
+        } else {
+            format!("https://{}", host)
+        }
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        request
+            .bearer_auth(&self.api_key)
             .timeout(Duration::from_secs(300))
-            .json(&databricks_query)
-            .send()
-            .await
-        {
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<QueryResponse> {
+        let query_result = match self.authorize(request).send().await {
             Ok(res) => res,
             Err(e) => return Err(anyhow!(e.to_string())),
         };
@@ -0,0 +1,154 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use duckdb::{AccessMode, Config, Connection};
+
+use crate::utils::query_engine::credentials::DuckDbCredentials;
+
+pub const DUCKDB_IN_MEMORY_PATH: &str = ":memory:";
+
+/// Opens the DuckDB database described by the credentials. Database files are opened read-only
+/// unless `read_only` is explicitly turned off, so several processes can query the same file.
+/// DuckDB is embedded and blocking, so callers should use the connection from a blocking task.
+///
+/// DuckDB runs inside the API process, so it is opened without access to anything outside the
+/// database: database files have to live under `DUCKDB_DATA_DIR`, files and URLs can't be read
+/// from queries, extensions are never loaded and the configuration can't be changed with `SET`.
+pub fn get_duckdb_connection(credentials: &DuckDbCredentials) -> Result<Connection> {
+    let config = match locked_down_config() {
+        Ok(config) => config,
+        Err(e) => return Err(anyhow!("Error configuring duckdb connection: {}", e)),
+    };
+
+    if credentials.path == DUCKDB_IN_MEMORY_PATH {
+        return match Connection::open_in_memory_with_flags(config) {
+            Ok(conn) => Ok(conn),
+            Err(e) => Err(anyhow!("Error opening in-memory duckdb database: {}", e)),
+        };
+    }
+
+    let path = resolve_database_path(&credentials.path, &duckdb_data_dir()?)?;
+
+    let access_mode = if credentials.read_only.unwrap_or(true) {
+        AccessMode::ReadOnly
+    } else {
+        AccessMode::ReadWrite
+    };
+
+    let config = match config.access_mode(access_mode) {
+        Ok(config) => config,
+        Err(e) => return Err(anyhow!("Error configuring duckdb connection: {}", e)),
+    };
+
+    match Connection::open_with_flags(&path, config) {
+        Ok(conn) => Ok(conn),
+        Err(e) => Err(anyhow!(
+            "Error opening duckdb database at {}: {}",
+            credentials.path,
+            e
+        )),
+    }
+}
+
+fn locked_down_config() -> duckdb::Result<Config> {
+    Config::default()
+        .enable_external_access(false)?
+        .enable_autoload_extension(false)?
+        .with("lock_configuration", "true")
+}
+
+/// The directory DuckDB database files are allowed to be opened from. There is no default, so
+/// file backed DuckDB data sources are refused until one is configured.
+fn duckdb_data_dir() -> Result<PathBuf> {
+    match env::var("DUCKDB_DATA_DIR") {
+        Ok(data_dir) if !data_dir.is_empty() => Ok(PathBuf::from(data_dir)),
+        _ => Err(anyhow!(
+            "DUCKDB_DATA_DIR is not set, so DuckDB database files can't be opened"
+        )),
+    }
+}
+
+/// Resolves the database path against the data directory, following `..` and symlinks, and
+/// refuses anything that ends up outside of it. Files opened read-write may not exist yet, so
+/// their parent directory is resolved instead.
+fn resolve_database_path(path: &str, data_dir: &Path) -> Result<PathBuf> {
+    let data_dir = match data_dir.canonicalize() {
+        Ok(data_dir) => data_dir,
+        Err(e) => return Err(anyhow!("Error resolving DUCKDB_DATA_DIR: {}", e)),
+    };
+
+    let path = data_dir.join(path);
+
+    let resolved = match path.canonicalize() {
+        Ok(resolved) => resolved,
+        Err(_) => match (path.parent(), path.file_name()) {
+            (Some(parent), Some(file_name)) => match parent.canonicalize() {
+                Ok(parent) => parent.join(file_name),
+                Err(e) => return Err(anyhow!("Error resolving duckdb database path: {}", e)),
+            },
+            _ => return Err(anyhow!("Invalid duckdb database path")),
+        },
+    };
+
+    if !resolved.starts_with(&data_dir) {
+        return Err(anyhow!(
+            "DuckDB database files have to be inside DUCKDB_DATA_DIR"
+        ));
+    }
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn database_paths_stay_inside_the_data_dir() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+
+        std::fs::create_dir(data_dir.path().join("extracts")).unwrap();
+        std::fs::write(data_dir.path().join("extracts/orders.duckdb"), b"").unwrap();
+        std::fs::write(outside.path().join("secrets.duckdb"), b"").unwrap();
+
+        let data_dir_path = data_dir.path().canonicalize().unwrap();
+
+        assert_eq!(
+            resolve_database_path("extracts/orders.duckdb", data_dir.path()).unwrap(),
+            data_dir_path.join("extracts/orders.duckdb")
+        );
+        assert_eq!(
+            resolve_database_path("extracts/new.duckdb", data_dir.path()).unwrap(),
+            data_dir_path.join("extracts/new.duckdb")
+        );
+
+        let outside_file = outside.path().join("secrets.duckdb");
+
+        assert!(resolve_database_path(outside_file.to_str().unwrap(), data_dir.path()).is_err());
+        assert!(resolve_database_path("../../etc/passwd", data_dir.path()).is_err());
+        assert!(resolve_database_path("/proc/self/environ", data_dir.path()).is_err());
+    }
+
+    #[test]
+    fn queries_cannot_reach_outside_the_database() {
+        let conn = get_duckdb_connection(&DuckDbCredentials {
+            path: DUCKDB_IN_MEMORY_PATH.to_string(),
+            read_only: None,
+            schemas: None,
+        })
+        .unwrap();
+
+        for sql in [
+            "SELECT * FROM read_text('/proc/self/environ')",
+            "SELECT * FROM read_csv('http://169.254.169.254/latest/meta-data/')",
+            "SET enable_external_access = true",
+            "INSTALL httpfs",
+        ] {
+            assert!(conn.execute_batch(sql).is_err(), "{}", sql);
+        }
+    }
+}
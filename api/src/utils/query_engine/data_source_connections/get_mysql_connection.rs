@@ -1,7 +1,10 @@
-use std::{borrow::Cow, time::Duration};
+use std::{borrow::Cow, str::FromStr, time::Duration};
 
 use anyhow::{anyhow, Result};
-use sqlx::{mysql::MySqlPoolOptions, MySql, Pool};
+use sqlx::{
+    mysql::{MySqlConnectOptions, MySqlPoolOptions},
+    MySql, Pool,
+};
 use std::process::Child;
 use tempfile::NamedTempFile;
 use url::form_urlencoded::byte_serialize;
@@ -12,6 +15,32 @@ use super::ssh_tunneling::establish_ssh_tunnel;
 
 pub async fn get_mysql_connection(
     credentials: &MySqlCredentials,
+    max_connections: u32,
+) -> Result<(
+    Pool<MySql>,
+    Option<std::process::Child>,
+    Option<Vec<NamedTempFile>>,
+)> {
+    connect(credentials, max_connections, false).await
+}
+
+/// StarRocks speaks the MySQL protocol but rejects the `SET sql_mode=(SELECT ...)` and
+/// `SET NAMES ... COLLATE ...` statements sqlx runs on every new connection, so those are skipped.
+pub async fn get_starrocks_connection(
+    credentials: &MySqlCredentials,
+    max_connections: u32,
+) -> Result<(
+    Pool<MySql>,
+    Option<std::process::Child>,
+    Option<Vec<NamedTempFile>>,
+)> {
+    connect(credentials, max_connections, true).await
+}
+
+async fn connect(
+    credentials: &MySqlCredentials,
+    max_connections: u32,
+    starrocks: bool,
 ) -> Result<(
     Pool<MySql>,
     Option<std::process::Child>,
@@ -67,12 +96,24 @@ pub async fn get_mysql_connection(
         )
     }
 
+    let mut connect_options = match MySqlConnectOptions::from_str(connection_string.as_str()) {
+        Ok(connect_options) => connect_options,
+        Err(e) => return Err(anyhow!(e)),
+    };
+
+    if starrocks {
+        connect_options = connect_options
+            .pipes_as_concat(false)
+            .no_engine_substitution(false)
+            .set_names(false);
+    }
+
     let mysql_pool = match MySqlPoolOptions::new()
-        .max_connections(1)
+        .max_connections(max_connections)
         .acquire_timeout(Duration::from_secs(5))
         .max_lifetime(Duration::from_secs(180))
         .idle_timeout(Duration::from_secs(180))
-        .connect(connection_string.as_str())
+        .connect_with(connect_options)
         .await
     {
         Ok(mysql_pool) => mysql_pool,
@@ -12,6 +12,7 @@ use super::ssh_tunneling::establish_ssh_tunnel;
 
 pub async fn get_postgres_connection(
     credentials: &PostgresCredentials,
+    max_connections: u32,
 ) -> Result<(
     Pool<Postgres>,
     Option<std::process::Child>,
@@ -70,7 +71,7 @@ pub async fn get_postgres_connection(
     }
 
     let pg_pool = match PgPoolOptions::new()
-        .max_connections(1)
+        .max_connections(max_connections)
         .acquire_timeout(Duration::from_secs(5))
         .connect(connection_string.as_str())
         .await
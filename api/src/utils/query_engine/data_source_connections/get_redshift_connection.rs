@@ -8,7 +8,10 @@ use sqlx::{
 
 use crate::utils::query_engine::credentials::PostgresCredentials;
 
-pub async fn get_redshift_connection(credentials: &PostgresCredentials) -> Result<Pool<Postgres>> {
+pub async fn get_redshift_connection(
+    credentials: &PostgresCredentials,
+    max_connections: u32,
+) -> Result<Pool<Postgres>> {
     let options = PgConnectOptions::new()
         .host(credentials.host.as_str())
         .port(credentials.port)
@@ -18,7 +21,7 @@ pub async fn get_redshift_connection(credentials: &PostgresCredentials) -> Resul
         .extra_float_digits(2);
 
     let redshift_pool = match PgPoolOptions::new()
-        .max_connections(1)
+        .max_connections(max_connections)
         .acquire_timeout(Duration::from_secs(5))
         .connect_with(options)
         .await
@@ -1,4 +1,5 @@
 use anyhow::{anyhow, Error};
+use bb8_redis::bb8;
 use std::process::Child;
 use tempfile::NamedTempFile;
 use tiberius::{AuthMethod, Client, Config};
@@ -9,6 +10,85 @@ use crate::utils::query_engine::{
     credentials::SqlServerCredentials, data_source_connections::ssh_tunneling::establish_ssh_tunnel,
 };
 
+pub type SqlServerPool = bb8::Pool<SqlServerConnectionManager>;
+
+/// The connection config, pointed at the ssh tunnel when there is one, with the tunnel process and
+/// the key files it was opened with.
+type SqlServerConfig = (Config, Option<Child>, Option<Vec<NamedTempFile>>);
+
+/// A pooled SQL Server connection. `in_query` is set while a query runs and cleared only once it
+/// finishes cleanly, so a connection dropped mid-query (cancelled, timed out or failed) is thrown
+/// away by the pool instead of being handed out with half a result still on the wire.
+pub struct SqlServerConnection {
+    pub client: Client<Compat<TcpStream>>,
+    pub in_query: bool,
+}
+
+pub struct SqlServerConnectionManager {
+    config: Config,
+}
+
+impl SqlServerConnectionManager {
+    pub fn new(config: Config) -> Self {
+        SqlServerConnectionManager { config }
+    }
+}
+
+impl bb8::ManageConnection for SqlServerConnectionManager {
+    type Connection = SqlServerConnection;
+    type Error = tiberius::error::Error;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        let tcp = TcpStream::connect(self.config.get_addr()).await?;
+        tcp.set_nodelay(true)?;
+
+        let client = Client::connect(self.config.clone(), tcp.compat_write()).await?;
+
+        Ok(SqlServerConnection {
+            client,
+            in_query: false,
+        })
+    }
+
+    async fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        conn.client
+            .simple_query("SELECT 1")
+            .await?
+            .into_row()
+            .await?;
+        Ok(())
+    }
+
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        conn.in_query
+    }
+}
+
+/// Builds a pool of up to `max_connections` connections so queries against the same data source
+/// run side by side. One connection is opened up front so bad credentials fail here.
+pub async fn get_sql_server_pool(
+    credentials: &SqlServerCredentials,
+    max_connections: u32,
+) -> Result<(SqlServerPool, Option<Child>, Option<Vec<NamedTempFile>>), Error> {
+    let (config, ssh_tunnel, temp_files) = get_sql_server_config(credentials)?;
+
+    let pool = match bb8::Pool::builder()
+        .max_size(max_connections)
+        .min_idle(1)
+        .retry_connection(false)
+        .build(SqlServerConnectionManager::new(config))
+        .await
+    {
+        Ok(pool) => pool,
+        Err(e) => {
+            tracing::error!("There was an issue while connecting to the database: {}", e);
+            return Err(anyhow!(e));
+        }
+    };
+
+    Ok((pool, ssh_tunnel, temp_files))
+}
+
 pub async fn get_sql_server_connection(
     credentials: &SqlServerCredentials,
 ) -> Result<
@@ -19,6 +99,31 @@ pub async fn get_sql_server_connection(
     ),
     Error,
 > {
+    let (config, ssh_tunnel, temp_files) = get_sql_server_config(credentials)?;
+
+    let tcp = match TcpStream::connect(config.get_addr()).await {
+        Ok(tcp) => tcp,
+        Err(e) => {
+            tracing::error!("There was an issue while connecting to the database: {}", e);
+            return Err(anyhow!(e));
+        }
+    };
+
+    tcp.set_nodelay(true)?;
+
+    let client = match Client::connect(config, tcp.compat_write()).await {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!("There was an issue while connecting to the database: {}", e);
+            return Err(anyhow!(e));
+        }
+    };
+
+    Ok((client, ssh_tunnel, temp_files))
+}
+
+/// Opens the ssh tunnel, if the data source needs one, and points the connection config at it.
+fn get_sql_server_config(credentials: &SqlServerCredentials) -> Result<SqlServerConfig, Error> {
     let mut parent_ssh_tunnel: Option<Child> = None;
     let mut parent_temp_files: Option<Vec<NamedTempFile>> = None;
     let mut parent_local_port: Option<u16> = None;
@@ -67,23 +172,5 @@ pub async fn get_sql_server_connection(
         config.port(credentials.port);
     }
 
-    let tcp = match TcpStream::connect(config.get_addr()).await {
-        Ok(tcp) => tcp,
-        Err(e) => {
-            tracing::error!("There was an issue while connecting to the database: {}", e);
-            return Err(anyhow!(e));
-        }
-    };
-
-    tcp.set_nodelay(true)?;
-
-    let client = match Client::connect(config, tcp.compat_write()).await {
-        Ok(client) => client,
-        Err(e) => {
-            tracing::error!("There was an issue while connecting to the database: {}", e);
-            return Err(anyhow!(e));
-        }
-    };
-
-    Ok((client, parent_ssh_tunnel, parent_temp_files))
+    Ok((config, parent_ssh_tunnel, parent_temp_files))
 }
@@ -0,0 +1,229 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::utils::query_engine::{
+    credentials::TrinoCredentials,
+    query_cancellation::{NativeCancel, QueryHandle},
+};
+
+pub async fn get_trino_client(credentials: &TrinoCredentials) -> Result<Trino> {
+    let trino_client = Trino::new(credentials)?;
+
+    Ok(trino_client)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrinoColumn {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrinoError {
+    pub message: String,
+}
+
+/// One page of a Trino/Presto statement response. Results are paged; `next_uri` is set until
+/// the statement has finished.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementResponse {
+    pub id: String,
+    pub next_uri: Option<String>,
+    pub columns: Option<Vec<TrinoColumn>>,
+    pub data: Option<Vec<Vec<Value>>>,
+    pub error: Option<TrinoError>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrinoQueryResult {
+    pub columns: Vec<TrinoColumn>,
+    pub data: Vec<Vec<Value>>,
+}
+
+/// Client for the Trino statement protocol, which Presto coordinators speak as well.
+#[derive(Clone)]
+pub struct Trino {
+    pub url: String,
+    pub user: String,
+    pub password: Option<String>,
+    pub catalog: String,
+    pub schema: String,
+}
+
+impl Trino {
+    /// Fails when a password is set on a plain http connection, since Trino only accepts basic
+    /// auth over https and the password would otherwise be sent in the clear.
+    pub fn new(credentials: &TrinoCredentials) -> Result<Self> {
+        let secure = credentials.secure.unwrap_or(credentials.port == 443);
+        let host = credentials
+            .host
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_end_matches('/');
+
+        let password = credentials
+            .password
+            .clone()
+            .filter(|password| !password.is_empty());
+
+        if password.is_some() && !secure {
+            return Err(anyhow!("Trino passwords can only be sent over https"));
+        }
+
+        Ok(Trino {
+            url: format!(
+                "{scheme}://{host}:{port}",
+                scheme = if secure { "https" } else { "http" },
+                host = host,
+                port = credentials.port
+            ),
+            user: credentials.username.clone(),
+            password,
+            catalog: credentials.catalog.clone(),
+            schema: credentials.schema.clone(),
+        })
+    }
+
+    /// Submits `statement` and follows `nextUri` until the statement finishes or `limit` rows have
+    /// been read. Stopping early cancels the rest of the statement on the server.
+    pub async fn query(&self, statement: String, limit: Option<i64>) -> Result<TrinoQueryResult> {
+        self.execute(statement, limit, None).await
+    }
+
+    /// Like `query`, but registers the statement with `running_query` once the coordinator has
+    /// assigned it an id, so it can be cancelled while it runs.
+    pub async fn query_cancellable(
+        &self,
+        statement: String,
+        limit: Option<i64>,
+        running_query: &QueryHandle,
+    ) -> Result<TrinoQueryResult> {
+        self.execute(statement, limit, Some(running_query)).await
+    }
+
+    /// Kills a running statement by the id the coordinator gave it.
+    pub async fn cancel(&self, query_id: &str) -> Result<()> {
+        let client = reqwest::Client::new();
+        let request = client
+            .delete(format!("{}/v1/query/{}", self.url, query_id))
+            .header("X-Trino-User", &self.user);
+
+        match self.authorize(request).send().await {
+            Ok(res) if res.status().is_success() => Ok(()),
+            Ok(res) => Err(anyhow!("Trino returned {} cancelling query", res.status())),
+            Err(e) => Err(anyhow!(e.to_string())),
+        }
+    }
+
+    async fn execute(
+        &self,
+        statement: String,
+        limit: Option<i64>,
+        running_query: Option<&QueryHandle>,
+    ) -> Result<TrinoQueryResult> {
+        let client = reqwest::Client::new();
+
+        let request = client
+            .post(format!("{}/v1/statement", self.url))
+            .header("X-Trino-User", &self.user)
+            .header("X-Trino-Catalog", &self.catalog)
+            .header("X-Trino-Schema", &self.schema)
+            .header("X-Presto-User", &self.user)
+            .header("X-Presto-Catalog", &self.catalog)
+            .header("X-Presto-Schema", &self.schema)
+            .body(statement);
+
+        let mut response = self.send(request).await?;
+
+        if let Some(running_query) = running_query {
+            running_query.set_native_cancel(NativeCancel::Trino {
+                client: self.clone(),
+                query_id: response.id.clone(),
+            });
+        }
+
+        let mut columns = Vec::new();
+        let mut data = Vec::new();
+
+        loop {
+            if let Some(error) = response.error {
+                return Err(anyhow!("Trino query failed: {}", error.message));
+            }
+
+            if columns.is_empty() {
+                if let Some(response_columns) = response.columns {
+                    columns = response_columns;
+                }
+            }
+
+            if let Some(rows) = response.data {
+                data.extend(rows);
+            }
+
+            let next_uri = match response.next_uri {
+                Some(next_uri) => next_uri,
+                None => break,
+            };
+
+            if let Some(limit) = limit {
+                if data.len() as i64 >= limit {
+                    data.truncate(limit.max(0) as usize);
+                    let _ = self.authorize(client.delete(&next_uri)).send().await;
+                    break;
+                }
+            }
+
+            response = self.send(client.get(&next_uri)).await?;
+        }
+
+        Ok(TrinoQueryResult { columns, data })
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.password {
+            Some(password) => request.basic_auth(&self.user, Some(password)),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<StatementResponse> {
+        let request = self.authorize(request).timeout(Duration::from_secs(300));
+
+        // The coordinator answers 503 while it is busy; the protocol asks clients to retry.
+        let mut attempts = 0;
+        let response = loop {
+            let attempt = match request.try_clone() {
+                Some(attempt) => attempt,
+                None => return Err(anyhow!("Trino request can't be retried")),
+            };
+
+            let response = match attempt.send().await {
+                Ok(res) => res,
+                Err(e) => return Err(anyhow!(e.to_string())),
+            };
+
+            attempts += 1;
+            if response.status() == reqwest::StatusCode::SERVICE_UNAVAILABLE && attempts < 5 {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+
+            break response;
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Trino returned {}: {}", status, body.trim()));
+        }
+
+        match response.json::<StatementResponse>().await {
+            Ok(res) => Ok(res),
+            Err(e) => Err(anyhow!(e.to_string())),
+        }
+    }
+}
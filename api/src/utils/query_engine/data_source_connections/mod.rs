@@ -1,8 +1,11 @@
 pub mod get_bigquery_client;
+pub mod get_clickhouse_client;
 pub mod get_databricks_client;
+pub mod get_duckdb_connection;
 pub mod get_mysql_connection;
 pub mod get_postgres_connection;
 pub mod get_redshift_connection;
 pub mod get_snowflake_client;
 pub mod get_sql_server_connection;
+pub mod get_trino_client;
 pub mod ssh_tunneling;
@@ -1,17 +1,142 @@
+use std::time::Duration;
+
 use indexmap::IndexMap;
 
 use anyhow::{anyhow, Result};
 use chrono::{NaiveDate, NaiveTime};
-use gcp_bigquery_client::{model::query_request::QueryRequest, Client};
+use gcp_bigquery_client::{
+    model::{
+        get_query_results_parameters::GetQueryResultsParameters, query_request::QueryRequest,
+        table_row::TableRow, table_schema::TableSchema,
+    },
+    Client,
+};
 use serde_json::{Number, Value};
 
-use crate::utils::query_engine::data_types::DataType;
+use crate::utils::query_engine::{
+    arrow_conversion::RecordBatchSender,
+    data_types::DataType,
+    query_cancellation::{NativeCancel, QueryHandle},
+};
+
+/// How long each request waits on the job before we poll again. Jobs that outlive it keep running
+/// on BigQuery, which is what lets a cancel reach them through `jobs.cancel`.
+const JOB_POLL_TIMEOUT_MS: i32 = 10000;
+
+/// Rows per page of results. Pages after the first are read with the job's page token.
+const PAGE_SIZE: i32 = 500;
 
 pub async fn bigquery_query(
     client: Client,
     project_id: String,
     query: String,
+    running_query: &QueryHandle,
 ) -> Result<Vec<IndexMap<String, DataType>>> {
+    let mut pages = run_job(client, project_id, query, running_query).await?;
+
+    let mut rows = Vec::new();
+
+    while let Some(page) = pages.next_page().await? {
+        rows.extend(page);
+    }
+
+    Ok(rows)
+}
+
+/// Sends each page of the results down the stream as it is read, so only one page is held at a
+/// time. Pages past `limit` are never read.
+pub async fn bigquery_query_stream(
+    client: Client,
+    project_id: String,
+    query: String,
+    limit: Option<i64>,
+    sender: &mut RecordBatchSender,
+    running_query: &QueryHandle,
+) -> Result<()> {
+    let mut pages = run_job(client, project_id, query, running_query).await?;
+
+    let mut count = 0;
+
+    while let Some(mut rows) = pages.next_page().await? {
+        if let Some(limit) = limit {
+            rows.truncate((limit.max(0) as usize).saturating_sub(count));
+        }
+
+        count += rows.len();
+
+        if !sender.send_rows_chunked(&rows).await {
+            return Ok(());
+        }
+
+        if let Some(limit) = limit {
+            if count as i64 >= limit {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The results of a finished query job, read a page at a time.
+struct BigQueryPages {
+    client: Client,
+    project_id: String,
+    job_reference: Option<(String, Option<String>)>,
+    schema: Option<TableSchema>,
+    /// The first page, which comes back with the response that saw the job finish.
+    first_page: Option<Vec<TableRow>>,
+    page_token: Option<String>,
+}
+
+impl BigQueryPages {
+    async fn next_page(&mut self) -> Result<Option<Vec<IndexMap<String, DataType>>>> {
+        if let Some(rows) = self.first_page.take() {
+            return convert_rows(self.schema.as_ref(), Some(&rows)).map(Some);
+        }
+
+        let page_token = match self.page_token.take() {
+            Some(page_token) => page_token,
+            None => return Ok(None),
+        };
+
+        let (job_id, location) = match &self.job_reference {
+            Some(job_reference) => job_reference.clone(),
+            None => return Err(anyhow!("BigQuery did not return a job reference")),
+        };
+
+        let parameters = GetQueryResultsParameters {
+            location,
+            max_results: Some(PAGE_SIZE),
+            page_token: Some(page_token),
+            ..Default::default()
+        };
+
+        let result = match self
+            .client
+            .job()
+            .get_query_results(self.project_id.as_str(), job_id.as_str(), parameters)
+            .await
+        {
+            Ok(res) => res,
+            Err(e) => {
+                tracing::error!("There was an issue while fetching the query results: {}", e);
+                return Err(anyhow!(e));
+            }
+        };
+
+        self.page_token = result.page_token;
+
+        convert_rows(self.schema.as_ref(), result.rows.as_ref()).map(Some)
+    }
+}
+
+async fn run_job(
+    client: Client,
+    project_id: String,
+    query: String,
+    running_query: &QueryHandle,
+) -> Result<BigQueryPages> {
     let query_request = QueryRequest {
         connection_properties: None,
         default_dataset: None,
@@ -19,14 +144,14 @@ pub async fn bigquery_query(
         kind: None,
         labels: None,
         location: None,
-        max_results: Some(500),
+        max_results: Some(PAGE_SIZE),
         maximum_bytes_billed: None,
         parameter_mode: None,
         preserve_nulls: None,
         query: query,
         query_parameters: None,
         request_id: None,
-        timeout_ms: Some(120000),
+        timeout_ms: Some(JOB_POLL_TIMEOUT_MS),
         use_legacy_sql: false,
         use_query_cache: None,
         format_options: None,
@@ -40,13 +165,79 @@ pub async fn bigquery_query(
         }
     };
 
-    let fields = result.schema
-        .as_ref()
+    let job_reference = result.job_reference.and_then(|job_reference| {
+        job_reference
+            .job_id
+            .map(|job_id| (job_id, job_reference.location))
+    });
+
+    if result.job_complete.unwrap_or(true) {
+        return Ok(BigQueryPages {
+            client,
+            project_id,
+            job_reference,
+            schema: result.schema,
+            first_page: Some(result.rows.unwrap_or_default()),
+            page_token: result.page_token,
+        });
+    }
+
+    let (job_id, location) = match job_reference.clone() {
+        Some(job_reference) => job_reference,
+        None => return Err(anyhow!("BigQuery did not return a job id")),
+    };
+
+    running_query.set_native_cancel(NativeCancel::BigQuery {
+        client: client.clone(),
+        project_id: project_id.clone(),
+        job_id: job_id.clone(),
+        location: location.clone(),
+    });
+
+    loop {
+        let parameters = GetQueryResultsParameters {
+            location: location.clone(),
+            max_results: Some(PAGE_SIZE),
+            timeout_ms: Some(JOB_POLL_TIMEOUT_MS),
+            ..Default::default()
+        };
+
+        let result = match client
+            .job()
+            .get_query_results(project_id.as_str(), job_id.as_str(), parameters)
+            .await
+        {
+            Ok(res) => res,
+            Err(e) => {
+                tracing::error!("There was an issue while fetching the query results: {}", e);
+                return Err(anyhow!(e));
+            }
+        };
+
+        if result.job_complete.unwrap_or(false) {
+            return Ok(BigQueryPages {
+                client,
+                project_id,
+                job_reference,
+                schema: result.schema,
+                first_page: Some(result.rows.unwrap_or_default()),
+                page_token: result.page_token,
+            });
+        }
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+fn convert_rows(
+    schema: Option<&TableSchema>,
+    rows: Option<&Vec<TableRow>>,
+) -> Result<Vec<IndexMap<String, DataType>>> {
+    let fields = schema
         .and_then(|schema| schema.fields.as_ref())
         .ok_or_else(|| anyhow!("No schema found in response"))?;
 
-    let typed_rows = result.rows
-        .as_ref()
+    let typed_rows = rows
         .map(|rows| {
             rows.iter()
                 .map(|row| {
@@ -0,0 +1,239 @@
+use indexmap::IndexMap;
+
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::Value;
+
+use crate::utils::query_engine::{
+    data_source_connections::get_clickhouse_client::ClickHouse,
+    data_types::DataType,
+    query_cancellation::{NativeCancel, QueryHandle},
+};
+
+pub async fn clickhouse_query(
+    clickhouse_client: ClickHouse,
+    query: String,
+    limit: Option<i64>,
+    running_query: &QueryHandle,
+) -> Result<Vec<IndexMap<std::string::String, DataType>>, Error> {
+    let query_id = running_query.query_id().to_string();
+
+    running_query.set_native_cancel(NativeCancel::ClickHouse {
+        client: clickhouse_client.clone(),
+        query_id: query_id.clone(),
+    });
+
+    let results = match clickhouse_client
+        .query_with_id(query, limit, &query_id)
+        .await
+    {
+        Ok(results) => results,
+        Err(e) => {
+            tracing::error!("Error: {}", e);
+            return Err(anyhow!(e.to_string()));
+        }
+    };
+
+    let mut result: Vec<IndexMap<String, DataType>> = Vec::with_capacity(results.data.len());
+
+    for row in results.data {
+        let mut row_map: IndexMap<String, DataType> = IndexMap::new();
+
+        for (column, value) in results.meta.iter().zip(row) {
+            row_map.insert(column.name.clone(), convert_value(&column.type_, value));
+        }
+
+        result.push(row_map);
+    }
+
+    // `result_overflow_mode=break` stops at a block boundary, so trim any overshoot.
+    if let Some(limit) = limit {
+        result.truncate(limit.max(0) as usize);
+    }
+
+    Ok(result)
+}
+
+/// Strips the `LowCardinality(...)` and `Nullable(...)` wrappers, which don't change how a value
+/// is encoded in JSON.
+pub fn unwrap_clickhouse_type(type_name: &str) -> &str {
+    let mut type_name = type_name.trim();
+
+    loop {
+        let unwrapped = ["LowCardinality(", "Nullable("].iter().find_map(|wrapper| {
+            type_name
+                .strip_prefix(wrapper)
+                .and_then(|inner| inner.strip_suffix(')'))
+        });
+
+        match unwrapped {
+            Some(inner) => type_name = inner.trim(),
+            None => return type_name,
+        }
+    }
+}
+
+fn convert_value(type_name: &str, value: Value) -> DataType {
+    let type_name = unwrap_clickhouse_type(type_name);
+    let base_type = type_name.split('(').next().unwrap_or(type_name);
+
+    match base_type {
+        "Bool" => DataType::Bool(value.as_bool()),
+        "Int8" | "Int16" | "UInt8" => DataType::Int2(value.as_i64().map(|v| v as i16)),
+        "Int32" | "UInt16" => DataType::Int4(value.as_i64().map(|v| v as i32)),
+        "Int64" | "UInt32" => DataType::Int8(value.as_i64()),
+        // Values that don't fit in an i64 are kept as text rather than truncated.
+        "UInt64" | "Int128" | "UInt128" | "Int256" | "UInt256" => match value {
+            Value::Null => DataType::Int8(None),
+            value => match value.as_i64() {
+                Some(v) => DataType::Int8(Some(v)),
+                None => DataType::Text(value_to_string(value)),
+            },
+        },
+        "Float32" => DataType::Float4(value_to_f64(&value).map(|v| v as f32)),
+        "Float64" | "Decimal" | "Decimal32" | "Decimal64" | "Decimal128" | "Decimal256" => {
+            DataType::Float8(value_to_f64(&value))
+        }
+        "String" | "FixedString" | "Enum8" | "Enum16" | "IPv4" | "IPv6" => {
+            DataType::Text(value_to_string(value))
+        }
+        "UUID" => DataType::Uuid(value.as_str().and_then(|v| v.parse().ok())),
+        "Date" | "Date32" => DataType::Date(
+            value
+                .as_str()
+                .and_then(|v| NaiveDate::parse_from_str(v, "%Y-%m-%d").ok()),
+        ),
+        // `date_time_output_format=iso` renders every DateTime in UTC. Columns declared with a
+        // timezone keep it as a tz-aware timestamp.
+        "DateTime" | "DateTime64" => {
+            let timestamp = value
+                .as_str()
+                .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+                .map(|v| v.with_timezone(&Utc));
+
+            if has_timezone(type_name) {
+                DataType::Timestamptz(timestamp)
+            } else {
+                DataType::Timestamp(timestamp.map(|v| v.naive_utc()))
+            }
+        }
+        "Array" | "Tuple" | "Map" | "Nested" | "JSON" | "Object" => match value {
+            Value::Null => DataType::Json(None),
+            value => DataType::Json(Some(value)),
+        },
+        "Nothing" => DataType::Null,
+        _ => DataType::Unknown(value_to_string(value)),
+    }
+}
+
+/// `DateTime('UTC')` and `DateTime64(3, 'UTC')` carry a quoted timezone argument.
+fn has_timezone(type_name: &str) -> bool {
+    type_name.contains('\'')
+}
+
+fn value_to_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(v) => v.as_f64(),
+        Value::String(v) => v.parse().ok(),
+        _ => None,
+    }
+}
+
+fn value_to_string(value: Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(v) => Some(v),
+        value => Some(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::query_engine::{
+        credentials::ClickHouseCredentials,
+        data_source_connections::get_clickhouse_client::get_clickhouse_client,
+        query_cancellation::{register_query, QueryContext},
+    };
+    use axum::{routing::post, Router};
+    use chrono::TimeZone;
+
+    const RESPONSE: &str = r#"{
+        "meta": [
+            {"name": "event", "type": "LowCardinality(String)"},
+            {"name": "user_id", "type": "Nullable(UInt64)"},
+            {"name": "amount", "type": "Decimal(18, 2)"},
+            {"name": "created_at", "type": "DateTime64(3, 'UTC')"},
+            {"name": "tags", "type": "Array(String)"}
+        ],
+        "data": [
+            ["click", 42, 12.5, "2024-01-01T10:00:00.123Z", ["a", "b"]],
+            ["view", null, 0, "2024-01-02T00:00:00Z", []]
+        ],
+        "rows": 2
+    }"#;
+
+    #[test]
+    fn unwraps_nested_type_modifiers() {
+        assert_eq!(
+            unwrap_clickhouse_type("LowCardinality(Nullable(String))"),
+            "String"
+        );
+        assert_eq!(
+            unwrap_clickhouse_type("Nullable(DateTime64(3))"),
+            "DateTime64(3)"
+        );
+        assert_eq!(
+            unwrap_clickhouse_type("Array(Nullable(Int32))"),
+            "Array(Nullable(Int32))"
+        );
+    }
+
+    #[tokio::test]
+    async fn maps_clickhouse_types_from_http_response() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let app = Router::new().route("/", post(|| async { RESPONSE }));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = get_clickhouse_client(&ClickHouseCredentials {
+            host: "127.0.0.1".to_string(),
+            port,
+            username: "default".to_string(),
+            password: "".to_string(),
+            database: "default".to_string(),
+            secure: None,
+            schemas: None,
+        })
+        .await
+        .unwrap();
+
+        let running_query = register_query(&QueryContext::new()).unwrap();
+
+        let rows = clickhouse_query(
+            client,
+            "SELECT * FROM events".to_string(),
+            Some(10),
+            &running_query,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["event"], DataType::Text(Some("click".to_string())));
+        assert_eq!(rows[0]["user_id"], DataType::Int8(Some(42)));
+        assert_eq!(rows[1]["user_id"], DataType::Int8(None));
+        assert_eq!(rows[0]["amount"], DataType::Float8(Some(12.5)));
+        assert_eq!(
+            rows[0]["created_at"],
+            DataType::Timestamptz(Some(
+                Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap()
+                    + chrono::Duration::milliseconds(123)
+            ))
+        );
+        assert_eq!(
+            rows[0]["tags"],
+            DataType::Json(Some(serde_json::json!(["a", "b"])))
+        );
+    }
+}
@@ -4,14 +4,127 @@ use anyhow::{anyhow, Error};
 use serde_json::Value;
 
 use crate::utils::query_engine::{
-    data_source_connections::get_databricks_client::Databricks, data_types::DataType,
+    arrow_conversion::RecordBatchSender,
+    data_source_connections::get_databricks_client::{
+        Databricks, DatabricksColumn, DatabricksResult,
+    },
+    data_types::DataType,
+    query_cancellation::{NativeCancel, QueryHandle},
 };
 
 pub async fn databricks_query(
     databricks_client: Databricks,
     query: String,
+    running_query: &QueryHandle,
 ) -> Result<Vec<IndexMap<std::string::String, DataType>>, Error> {
-    let results = match databricks_client.query(query).await {
+    let mut chunks = run_statement(databricks_client, query, running_query).await?;
+
+    let mut result: Vec<IndexMap<String, DataType>> = Vec::new();
+
+    while let Some(rows) = chunks.next_chunk().await? {
+        result.extend(rows);
+    }
+
+    Ok(result)
+}
+
+/// Sends each chunk of the result down the stream as it is fetched, so only one chunk is held at
+/// a time. Chunks past `limit` are never fetched.
+pub async fn databricks_query_stream(
+    databricks_client: Databricks,
+    query: String,
+    limit: Option<i64>,
+    sender: &mut RecordBatchSender,
+    running_query: &QueryHandle,
+) -> Result<(), Error> {
+    let mut chunks = run_statement(databricks_client, query, running_query).await?;
+
+    let mut count = 0;
+
+    while let Some(mut rows) = chunks.next_chunk().await? {
+        if let Some(limit) = limit {
+            rows.truncate((limit.max(0) as usize).saturating_sub(count));
+        }
+
+        count += rows.len();
+
+        if !sender.send_rows_chunked(&rows).await {
+            return Ok(());
+        }
+
+        if let Some(limit) = limit {
+            if count as i64 >= limit {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The result of a succeeded statement, read a chunk at a time.
+struct DatabricksChunks {
+    client: Databricks,
+    statement_id: String,
+    columns: Vec<DatabricksColumn>,
+    /// The first chunk, which comes inlined in the statement response.
+    first_chunk: Option<DatabricksResult>,
+    next_chunk_index: Option<i32>,
+}
+
+impl DatabricksChunks {
+    async fn next_chunk(&mut self) -> Result<Option<Vec<IndexMap<String, DataType>>>, Error> {
+        let chunk = match self.first_chunk.take() {
+            Some(chunk) => chunk,
+            None => match self.next_chunk_index.take() {
+                Some(chunk_index) => {
+                    match self
+                        .client
+                        .fetch_chunk(&self.statement_id, chunk_index)
+                        .await
+                    {
+                        Ok(chunk) => chunk,
+                        Err(e) => {
+                            tracing::error!("Error: {}", e);
+                            return Err(anyhow!(e.to_string()));
+                        }
+                    }
+                }
+                None => return Ok(None),
+            },
+        };
+
+        self.next_chunk_index = chunk.next_chunk_index;
+
+        let rows = chunk.data_array.unwrap_or_default();
+
+        Ok(Some(
+            rows.into_iter()
+                .map(|row| convert_row(&self.columns, row))
+                .collect(),
+        ))
+    }
+}
+
+async fn run_statement(
+    databricks_client: Databricks,
+    query: String,
+    running_query: &QueryHandle,
+) -> Result<DatabricksChunks, Error> {
+    let submitted = match databricks_client.submit_statement(query).await {
+        Ok(submitted) => submitted,
+        Err(e) => {
+            tracing::error!("Error: {}", e);
+            return Err(anyhow!(e.to_string()));
+        }
+    };
+
+    running_query.set_native_cancel(NativeCancel::Databricks {
+        client: databricks_client.clone(),
+        statement_id: submitted.statement_id.clone(),
+    });
+
+    let results = match databricks_client.wait_for_statement(submitted).await {
         Ok(results) => results,
         Err(e) => {
             tracing::error!("Error: {}", e);
@@ -19,46 +132,191 @@ pub async fn databricks_query(
         }
     };
 
-    let mut result: Vec<IndexMap<String, DataType>> = Vec::new();
+    let (columns, first_chunk) = match results.manifest {
+        Some(manifest) => (manifest.schema.columns, results.result),
+        None => (Vec::new(), None),
+    };
+
+    Ok(DatabricksChunks {
+        client: databricks_client,
+        statement_id: results.statement_id,
+        columns,
+        first_chunk,
+        next_chunk_index: None,
+    })
+}
+
+fn convert_row(
+    columns: &[DatabricksColumn],
+    row: Vec<Option<String>>,
+) -> IndexMap<String, DataType> {
+    let mut row_map: IndexMap<String, DataType> = IndexMap::new();
 
-    let rows = match results.result.data_array {
-        Some(rows) => rows,
-        None => return Ok(Vec::new()),
+    for (column, value) in columns.iter().zip(row) {
+        let column_value = match column.type_name.as_str() {
+            "BIGINT" => DataType::Int8(value.and_then(|v| v.parse::<i64>().ok())),
+            "BOOL" | "BOOLEAN" => DataType::Bool(value.and_then(|v| v.parse::<bool>().ok())),
+            "DATE" => DataType::Date(value.and_then(|v| v.parse::<chrono::NaiveDate>().ok())),
+            "DECIMAL" | "DOUBLE" | "FLOAT" => {
+                DataType::Float8(value.and_then(|v| v.parse::<f64>().ok()))
+            }
+            "INT" => DataType::Int4(value.and_then(|v| v.parse::<i32>().ok())),
+            "VOID" => DataType::Unknown(Some(String::from("NULL"))),
+            "SMALLINT" | "TINYINT" => DataType::Int2(value.and_then(|v| v.parse::<i16>().ok())),
+            "STRING" => DataType::Text(value),
+            "TIMESTAMP" | "TIMESTAMP_NTZ" => {
+                DataType::Timestamp(value.and_then(|v| v.parse::<chrono::NaiveDateTime>().ok()))
+            }
+            "MAP" | "STRUCT" => {
+                DataType::Json(value.and_then(|v| serde_json::from_str::<Value>(&v).ok()))
+            }
+            _ => DataType::Unknown(value),
+        };
+
+        row_map.insert(column.name.clone(), column_value);
+    }
+
+    row_map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::query_engine::{
+        arrow_conversion::record_batch_channel,
+        credentials::DatabricksCredentials,
+        data_source_connections::get_databricks_client::get_databricks_client,
+        query_cancellation::{register_query, QueryContext},
+    };
+    use arrow::array::{Array, Int64Array};
+    use axum::{
+        extract::{Path, State},
+        routing::{get, post},
+        Json, Router,
     };
+    use futures::StreamExt;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
 
-    let columns = results.manifest.schema.columns;
-
-    for row in rows {
-        let columns_clone = columns.clone();
-
-        for (i, column) in columns_clone.iter().enumerate() {
-            let mut row_map: IndexMap<String, DataType> = IndexMap::new();
-            let column_name = column.name.clone();
-            let type_info = column.type_name.clone();
-
-            let column_value = match type_info.as_str() {
-                "BIGINT" => DataType::Int8(row[i].parse::<i64>().ok()),
-                "BOOL" => DataType::Bool(row[i].parse::<bool>().ok()),
-                "DATE" => DataType::Date(row[i].parse::<chrono::NaiveDate>().ok()),
-                "DECIMAL" => DataType::Float8(row[i].parse::<f64>().ok()),
-                "DOUBLE" => DataType::Float8(row[i].parse::<f64>().ok()),
-                "FLOAT" => DataType::Float8(row[i].parse::<f64>().ok()),
-                "INT" => DataType::Int4(row[i].parse::<i32>().ok()),
-                "VOID" => DataType::Unknown(Some(String::from("NULL"))),
-                "SMALLINT" => DataType::Int2(row[i].parse::<i16>().ok()),
-                "STRING" => DataType::Text(Some(row[i].clone())),
-                "TIMESTAMP" => DataType::Timestamp(row[i].parse::<chrono::NaiveDateTime>().ok()),
-                "TIMESTAMP_NTZ" => DataType::Timestamp(row[i].parse::<chrono::NaiveDateTime>().ok()),
-                "TINYINT" => DataType::Int2(row[i].parse::<i16>().ok()),
-                "MAP" => DataType::Json(serde_json::from_str::<Value>(row[i].as_str()).ok()),
-                "STRUCT" => DataType::Json(serde_json::from_str::<Value>(row[i].as_str()).ok()),
-                _ => DataType::Unknown(Some(row[i].to_string())),
-            };
-
-            row_map.insert(column_name.to_string(), column_value);
-
-            result.push(row_map);
-        }
+    type Requests = Arc<Mutex<Vec<i32>>>;
+
+    /// A statement whose result comes back in three chunks of two rows. Fetched chunk indexes are
+    /// recorded.
+    async fn stub_databricks() -> (Databricks, Requests) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests: Requests = Arc::new(Mutex::new(Vec::new()));
+
+        let app = Router::new()
+            .route(
+                "/api/2.0/sql/statements/",
+                post(|| async {
+                    Json(json!({
+                        "statement_id": "s1",
+                        "status": { "state": "SUCCEEDED" },
+                        "manifest": {
+                            "format": "JSON_ARRAY",
+                            "schema": {
+                                "column_count": 2,
+                                "columns": [
+                                    { "name": "id", "type_name": "BIGINT" },
+                                    { "name": "name", "type_name": "STRING" }
+                                ]
+                            }
+                        },
+                        "result": {
+                            "chunk_index": 0,
+                            "data_array": [["1", "a"], ["2", null]],
+                            "next_chunk_index": 1
+                        }
+                    }))
+                }),
+            )
+            .route(
+                "/api/2.0/sql/statements/s1/result/chunks/:chunk_index",
+                get(
+                    |State(requests): State<Requests>, Path(chunk_index): Path<i32>| async move {
+                        requests.lock().unwrap().push(chunk_index);
+
+                        let first = chunk_index as i64 * 2 + 1;
+                        let next_chunk_index = if chunk_index < 2 {
+                            Some(chunk_index + 1)
+                        } else {
+                            None
+                        };
+
+                        Json(json!({
+                            "chunk_index": chunk_index,
+                            "data_array": [[first.to_string(), "b"], [(first + 1).to_string(), "c"]],
+                            "next_chunk_index": next_chunk_index
+                        }))
+                    },
+                ),
+            )
+            .with_state(requests.clone());
+
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = get_databricks_client(&DatabricksCredentials {
+            host: format!("http://127.0.0.1:{}", port),
+            api_key: "token".to_string(),
+            warehouse_id: "w1".to_string(),
+            catalog_name: "main".to_string(),
+            schemas: None,
+        })
+        .await
+        .unwrap();
+
+        (client, requests)
+    }
+
+    #[tokio::test]
+    async fn follows_every_chunk_of_the_result() {
+        let (client, requests) = stub_databricks().await;
+        let running_query = register_query(&QueryContext::new()).unwrap();
+
+        let rows = databricks_query(client, "SELECT 1".to_string(), &running_query)
+            .await
+            .unwrap();
+
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0]["id"], DataType::Int8(Some(1)));
+        assert_eq!(rows[1]["name"], DataType::Text(None));
+        assert_eq!(rows[5]["id"], DataType::Int8(Some(6)));
+        assert_eq!(*requests.lock().unwrap(), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn streams_chunks_and_stops_fetching_at_the_limit() {
+        let (client, requests) = stub_databricks().await;
+        let running_query = register_query(&QueryContext::new()).unwrap();
+        let (mut sender, stream) = record_batch_channel();
+
+        databricks_query_stream(
+            client,
+            "SELECT 1".to_string(),
+            Some(3),
+            &mut sender,
+            &running_query,
+        )
+        .await
+        .unwrap();
+        drop(sender);
+
+        let batches = stream.map(|batch| batch.unwrap()).collect::<Vec<_>>().await;
+
+        // One batch per chunk, cut off partway through the second.
+        assert_eq!(
+            batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+
+        let ids = batches[1]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(ids.value(0), 3);
+        assert_eq!(*requests.lock().unwrap(), vec![1]);
     }
-    Ok(result)
 }
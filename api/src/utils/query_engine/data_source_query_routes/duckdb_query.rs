@@ -0,0 +1,275 @@
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime};
+use duckdb::{arrow::datatypes::DataType as ArrowDataType, types::Value, InterruptHandle};
+use indexmap::IndexMap;
+use num_traits::ToPrimitive;
+use serde_json::{Map, Value as JsonValue};
+use std::sync::Arc;
+use tokio::{sync::oneshot, task};
+
+use crate::utils::query_engine::{
+    credentials::DuckDbCredentials,
+    data_source_connections::get_duckdb_connection::get_duckdb_connection,
+    data_types::DataType,
+    query_cancellation::{NativeCancel, QueryHandle},
+};
+
+const DEFAULT_ROW_LIMIT: i64 = 5000;
+
+/// Runs a query against a DuckDB database. DuckDB is embedded and its API is blocking, so the
+/// connection is opened and read on a blocking task, which hands back an interrupt handle so the
+/// query can be cancelled.
+pub async fn duckdb_query(
+    credentials: DuckDbCredentials,
+    query: String,
+    limit: Option<i64>,
+    running_query: &QueryHandle,
+) -> Result<Vec<IndexMap<String, DataType>>, Error> {
+    let limit = limit.unwrap_or(DEFAULT_ROW_LIMIT);
+    let (interrupt_sender, interrupt_receiver) = oneshot::channel();
+
+    let query_task =
+        task::spawn_blocking(move || run_query(&credentials, &query, limit, interrupt_sender));
+
+    if let Ok(interrupt_handle) = interrupt_receiver.await {
+        running_query.set_native_cancel(NativeCancel::DuckDb { interrupt_handle });
+    }
+
+    match query_task.await {
+        Ok(result) => result,
+        Err(e) => Err(anyhow!("DuckDB query task failed: {}", e)),
+    }
+}
+
+fn run_query(
+    credentials: &DuckDbCredentials,
+    query: &str,
+    limit: i64,
+    interrupt_sender: oneshot::Sender<Arc<InterruptHandle>>,
+) -> Result<Vec<IndexMap<String, DataType>>, Error> {
+    let conn = get_duckdb_connection(credentials)?;
+    let _ = interrupt_sender.send(conn.interrupt_handle());
+
+    let mut stmt = conn.prepare(query)?;
+    let mut rows = stmt.query([])?;
+
+    let mut columns: Option<Vec<(String, ArrowDataType)>> = None;
+    let mut result: Vec<IndexMap<String, DataType>> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        // Column metadata is only available once the statement has been stepped.
+        let columns = columns.get_or_insert_with(|| {
+            let stmt: &duckdb::Statement = row.as_ref();
+            (0..stmt.column_count())
+                .map(|i| {
+                    let name = stmt
+                        .column_name(i)
+                        .map(|name| name.to_string())
+                        .unwrap_or_else(|_| format!("column_{}", i));
+                    (name, stmt.column_type(i))
+                })
+                .collect()
+        });
+
+        let mut row_map: IndexMap<String, DataType> = IndexMap::new();
+
+        for (i, (column_name, column_type)) in columns.iter().enumerate() {
+            let value = row.get::<_, Value>(i)?;
+            row_map.insert(column_name.clone(), convert_value(value, column_type));
+        }
+
+        result.push(row_map);
+
+        if result.len() as i64 >= limit {
+            break;
+        }
+    }
+
+    Ok(result)
+}
+
+fn convert_value(value: Value, column_type: &ArrowDataType) -> DataType {
+    match value {
+        Value::Null => DataType::Null,
+        Value::Boolean(v) => DataType::Bool(Some(v)),
+        Value::TinyInt(v) => DataType::Int2(Some(v as i16)),
+        Value::SmallInt(v) => DataType::Int2(Some(v)),
+        Value::UTinyInt(v) => DataType::Int2(Some(v as i16)),
+        Value::Int(v) => DataType::Int4(Some(v)),
+        Value::USmallInt(v) => DataType::Int4(Some(v as i32)),
+        Value::BigInt(v) => DataType::Int8(Some(v)),
+        Value::UInt(v) => DataType::Int8(Some(v as i64)),
+        Value::UBigInt(v) => match v.to_i64() {
+            Some(v) => DataType::Int8(Some(v)),
+            None => DataType::Text(Some(v.to_string())),
+        },
+        Value::HugeInt(v) => match v.to_i64() {
+            Some(v) => DataType::Int8(Some(v)),
+            None => DataType::Text(Some(v.to_string())),
+        },
+        Value::Float(v) => DataType::Float4(Some(v)),
+        Value::Double(v) => DataType::Float8(Some(v)),
+        Value::Decimal(v) => DataType::Float8(v.to_f64()),
+        Value::Text(v) | Value::Enum(v) => DataType::Text(Some(v)),
+        Value::Blob(v) => DataType::Bytea(Some(v)),
+        Value::Date32(days) => DataType::Date(
+            NaiveDate::from_ymd_opt(1970, 1, 1)
+                .and_then(|epoch| epoch.checked_add_signed(Duration::days(days as i64))),
+        ),
+        Value::Time64(unit, v) => {
+            let micros = unit.to_micros(v);
+            DataType::Time(NaiveTime::from_num_seconds_from_midnight_opt(
+                (micros / 1_000_000) as u32,
+                ((micros % 1_000_000) * 1_000) as u32,
+            ))
+        }
+        Value::Timestamp(unit, v) => {
+            let timestamp = DateTime::from_timestamp_micros(unit.to_micros(v));
+            match column_type {
+                ArrowDataType::Timestamp(_, Some(_)) => DataType::Timestamptz(timestamp),
+                _ => DataType::Timestamp(timestamp.map(|ts| ts.naive_utc())),
+            }
+        }
+        Value::Interval {
+            months,
+            days,
+            nanos,
+        } => DataType::Unknown(Some(format!(
+            "{} months {} days {} microseconds",
+            months,
+            days,
+            nanos / 1_000
+        ))),
+        value @ (Value::List(_)
+        | Value::Array(_)
+        | Value::Struct(_)
+        | Value::Map(_)
+        | Value::Union(_)) => DataType::Json(Some(value_to_json(value))),
+    }
+}
+
+/// Nested DuckDB values (lists, structs, maps) are surfaced as JSON.
+fn value_to_json(value: Value) -> JsonValue {
+    match value {
+        Value::List(values) | Value::Array(values) => {
+            JsonValue::Array(values.into_iter().map(value_to_json).collect())
+        }
+        Value::Struct(fields) => JsonValue::Object(
+            fields
+                .iter()
+                .map(|(name, value)| (name.clone(), value_to_json(value.clone())))
+                .collect::<Map<String, JsonValue>>(),
+        ),
+        Value::Map(entries) => JsonValue::Object(
+            entries
+                .iter()
+                .map(|(key, value)| {
+                    let key = match value_to_json(key.clone()) {
+                        JsonValue::String(key) => key,
+                        key => key.to_string(),
+                    };
+                    (key, value_to_json(value.clone()))
+                })
+                .collect::<Map<String, JsonValue>>(),
+        ),
+        Value::Union(value) => value_to_json(*value),
+        value => serde_json::to_value(convert_value(value, &ArrowDataType::Null))
+            .unwrap_or(JsonValue::Null),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::time::{Duration as StdDuration, Instant};
+
+    use crate::utils::query_engine::query_cancellation::{register_query, QueryContext};
+
+    fn in_memory() -> DuckDbCredentials {
+        DuckDbCredentials {
+            path: ":memory:".to_string(),
+            read_only: None,
+            schemas: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn converts_duckdb_values() {
+        let sql = "SELECT 1::INTEGER AS id, 'a' AS name, DATE '2024-01-02' AS day, \
+                   12.5::DECIMAL(4, 1) AS amount, [1, 2] AS ids, NULL AS missing";
+
+        let running_query = register_query(&QueryContext::new()).unwrap();
+
+        let rows = duckdb_query(in_memory(), sql.to_string(), None, &running_query)
+            .await
+            .unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["id"], DataType::Int4(Some(1)));
+        assert_eq!(rows[0]["name"], DataType::Text(Some("a".to_string())));
+        assert_eq!(
+            rows[0]["day"],
+            DataType::Date(NaiveDate::from_ymd_opt(2024, 1, 2))
+        );
+        assert_eq!(rows[0]["amount"], DataType::Float8(Some(12.5)));
+        assert_eq!(
+            rows[0]["ids"],
+            DataType::Json(Some(serde_json::json!([1, 2])))
+        );
+        assert_eq!(rows[0]["missing"], DataType::Null);
+    }
+
+    #[tokio::test]
+    async fn queries_database_files_with_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        std::env::set_var("DUCKDB_DATA_DIR", dir.path());
+        let path = dir.path().join("extracts.duckdb");
+
+        let conn = duckdb::Connection::open(&path).unwrap();
+        conn.execute_batch("CREATE TABLE orders AS SELECT range AS id FROM range(10);")
+            .unwrap();
+        drop(conn);
+
+        let credentials = DuckDbCredentials {
+            path: path.to_string_lossy().to_string(),
+            read_only: None,
+            schemas: None,
+        };
+
+        let running_query = register_query(&QueryContext::new()).unwrap();
+
+        let rows = duckdb_query(
+            credentials,
+            "SELECT id FROM orders".to_string(),
+            Some(3),
+            &running_query,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["id"], DataType::Int8(Some(0)));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn interrupts_queries_that_run_too_long() {
+        let running_query = register_query(&QueryContext::new()).unwrap();
+        let started = Instant::now();
+
+        let result = running_query
+            .run(
+                StdDuration::from_millis(200),
+                duckdb_query(
+                    in_memory(),
+                    "SELECT count(*) FROM range(1000000000000)".to_string(),
+                    None,
+                    &running_query,
+                ),
+            )
+            .await;
+
+        assert!(result.is_err());
+        assert!(started.elapsed() < StdDuration::from_secs(5));
+    }
+}
@@ -1,9 +1,12 @@
 pub mod bigquery_query;
+pub mod clickhouse_query;
 pub mod databricks_query;
+pub mod duckdb_query;
 pub mod mysql_query;
 pub mod postgres_query;
 pub mod query_router;
 pub mod redshift_query;
 pub mod snowflake_query;
 pub mod sql_server_query;
+pub mod trino_query;
 mod security_utils;
\ No newline at end of file
@@ -3,56 +3,30 @@ use indexmap::IndexMap;
 
 use anyhow::Error;
 use futures::{future::join_all, TryStreamExt};
-use sqlx::{Column, MySql, Pool, Row};
+use sqlx::{mysql::MySqlRow, Column, MySql, MySqlConnection, Pool, Row};
 use tokio::task;
 
+use crate::utils::query_engine::arrow_conversion::{RecordBatchSender, RECORD_BATCH_SIZE};
 use crate::utils::query_engine::data_types::DataType;
+use crate::utils::query_engine::query_cancellation::{NativeCancel, QueryHandle};
 
 pub async fn mysql_query(
     pg_pool: Pool<MySql>,
     query: String,
+    running_query: &QueryHandle,
 ) -> Result<Vec<IndexMap<std::string::String, DataType>>, Error> {
-    let mut stream = sqlx::query(&query).fetch(&pg_pool);
+    let mut conn = pg_pool.acquire().await?;
+    register_connection_id(&mut conn, &pg_pool, running_query).await?;
+
+    let mut stream = sqlx::query(&query).fetch(&mut *conn);
 
     let mut result: Vec<IndexMap<String, DataType>>= Vec::new();
 
     let mut count = 0;
 
     while let Some(row) = stream.try_next().await? {
-        let mut row_map: IndexMap<String, DataType> = IndexMap::new();
-
         let mut row_value_handlers = Vec::new();
-        row_value_handlers.push(task::spawn(async move {
-            for (i, column) in row.columns().iter().enumerate() {
-                let column_name = column.name();
-                let type_info = column.type_info().clone().to_string();
-
-                let column_value = match type_info.as_str() {
-                    "BOOL" | "BOOLEAN" => DataType::Bool(row.try_get::<bool, _>(i).ok()),
-                    "BIT" => DataType::Bytea(row.try_get::<Vec<u8>, _>(i).ok()),
-                    "CHAR" => DataType::Char(row.try_get::<String, _>(i).ok()),
-                    "BIGINT" => DataType::Int8(row.try_get::<i64, _>(i).ok()),
-                    "MEDIUMINT" | "INT" | "INTEGER" => DataType::Int4(row.try_get::<i32, _>(i).ok()),
-                    "TINYINT" | "SMALLINT" => DataType::Int2(row.try_get::<i16, _>(i).ok()),
-                    "TEXT" | "VARCHAR" => DataType::Text(row.try_get::<String, _>(i).ok()),
-                    "FLOAT" => DataType::Float4(row.try_get::<f32, _>(i).ok()),
-                    "DOUBLE" => DataType::Float8(row.try_get::<f64, _>(i).ok()),
-                    "DECIMAL" | "DEC" => DataType::Float8(row.try_get::<f64, _>(i).ok()),
-                    "UUID" => DataType::Uuid(row.try_get::<uuid::Uuid, _>(i).ok()),
-                    "TIMESTAMP" | "DATETIME" => DataType::Timestamp(row.try_get::<chrono::NaiveDateTime, _>(i).ok()),
-                    "DATE" => DataType::Date(row.try_get::<chrono::NaiveDate, _>(i).ok()),
-                    "TIME" => DataType::Time(row.try_get::<chrono::NaiveTime, _>(i).ok()),
-                    "TIMESTAMPTZ" => DataType::Timestamptz(row.try_get::<chrono::DateTime<Utc>, _>(i).ok()),
-                    "JSON" | "JSONB" => DataType::Json(row.try_get::<serde_json::Value, _>(i).ok()),
-                    _ => DataType::Unknown(row.try_get::<String, _>(i).ok()),
-                };
-
-                row_map.insert(column_name.to_string(), column_value);
-            }
-
-            row_map
-        }));
-
+        row_value_handlers.push(task::spawn(async move { process_row(row) }));
         let row_value_handlers_results = join_all(row_value_handlers).await;
 
         for row_value_handler_result in row_value_handlers_results {
@@ -67,3 +41,98 @@ pub async fn mysql_query(
     }
     Ok(result)
 }
+
+/// Streaming counterpart of `mysql_query`. Rows are converted and pushed to `sender` one
+/// `RecordBatch` at a time instead of being collected.
+pub async fn mysql_query_stream(
+    pool: Pool<MySql>,
+    query: String,
+    limit: Option<i64>,
+    sender: &mut RecordBatchSender,
+    running_query: &QueryHandle,
+) -> Result<(), Error> {
+    let mut conn = pool.acquire().await?;
+    register_connection_id(&mut conn, &pool, running_query).await?;
+
+    let mut stream = sqlx::query(&query).fetch(&mut *conn);
+
+    let mut count = 0;
+    let mut rows = Vec::with_capacity(RECORD_BATCH_SIZE);
+
+    while let Some(row) = stream.try_next().await? {
+        rows.push(process_row(row));
+        count += 1;
+
+        if rows.len() == RECORD_BATCH_SIZE {
+            if !sender.send_rows(&rows).await {
+                return Ok(());
+            }
+            rows.clear();
+        }
+
+        if let Some(limit) = limit {
+            if count >= limit {
+                break;
+            }
+        }
+    }
+
+    sender.send_rows(&rows).await;
+
+    Ok(())
+}
+
+/// Records the connection the query runs on so it can be stopped with `KILL QUERY`. The id is read
+/// back as text since StarRocks and MySQL disagree on its integer type.
+async fn register_connection_id(
+    conn: &mut MySqlConnection,
+    pool: &Pool<MySql>,
+    running_query: &QueryHandle,
+) -> Result<(), Error> {
+    let connection_id: String = sqlx::query_scalar("SELECT CAST(CONNECTION_ID() AS CHAR)")
+        .fetch_one(&mut *conn)
+        .await?;
+
+    match connection_id.parse::<u64>() {
+        Ok(connection_id) => running_query.set_native_cancel(NativeCancel::MySql {
+            pool: pool.clone(),
+            connection_id,
+        }),
+        Err(e) => tracing::warn!("Unexpected connection id {}: {}", connection_id, e),
+    }
+
+    Ok(())
+}
+
+fn process_row(row: MySqlRow) -> IndexMap<String, DataType> {
+    let mut row_map: IndexMap<String, DataType> = IndexMap::new();
+
+    for (i, column) in row.columns().iter().enumerate() {
+        let column_name = column.name();
+        let type_info = column.type_info().clone().to_string();
+
+        let column_value = match type_info.as_str() {
+            "BOOL" | "BOOLEAN" => DataType::Bool(row.try_get::<bool, _>(i).ok()),
+            "BIT" => DataType::Bytea(row.try_get::<Vec<u8>, _>(i).ok()),
+            "CHAR" => DataType::Char(row.try_get::<String, _>(i).ok()),
+            "BIGINT" => DataType::Int8(row.try_get::<i64, _>(i).ok()),
+            "MEDIUMINT" | "INT" | "INTEGER" => DataType::Int4(row.try_get::<i32, _>(i).ok()),
+            "TINYINT" | "SMALLINT" => DataType::Int2(row.try_get::<i16, _>(i).ok()),
+            "TEXT" | "VARCHAR" => DataType::Text(row.try_get::<String, _>(i).ok()),
+            "FLOAT" => DataType::Float4(row.try_get::<f32, _>(i).ok()),
+            "DOUBLE" => DataType::Float8(row.try_get::<f64, _>(i).ok()),
+            "DECIMAL" | "DEC" => DataType::Float8(row.try_get::<f64, _>(i).ok()),
+            "UUID" => DataType::Uuid(row.try_get::<uuid::Uuid, _>(i).ok()),
+            "TIMESTAMP" | "DATETIME" => DataType::Timestamp(row.try_get::<chrono::NaiveDateTime, _>(i).ok()),
+            "DATE" => DataType::Date(row.try_get::<chrono::NaiveDate, _>(i).ok()),
+            "TIME" => DataType::Time(row.try_get::<chrono::NaiveTime, _>(i).ok()),
+            "TIMESTAMPTZ" => DataType::Timestamptz(row.try_get::<chrono::DateTime<Utc>, _>(i).ok()),
+            "JSON" | "JSONB" => DataType::Json(row.try_get::<serde_json::Value, _>(i).ok()),
+            _ => DataType::Unknown(row.try_get::<String, _>(i).ok()),
+        };
+
+        row_map.insert(column_name.to_string(), column_value);
+    }
+
+    row_map
+}
@@ -4,11 +4,14 @@ use chrono::Utc;
 use futures::TryStreamExt;
 use indexmap::IndexMap;
 
-use anyhow::{Error, Result};
-use sqlx::{Column, Pool, Postgres, Row};
+use anyhow::{anyhow, Error, Result};
+use sqlx::{pool::PoolConnection, Column, PgConnection, Pool, Postgres, Row};
 use tokio::task;
 
+use crate::utils::query_engine::arrow_conversion::{RecordBatchSender, RECORD_BATCH_SIZE};
+use crate::utils::query_engine::connection_cache::CachedPool;
 use crate::utils::query_engine::data_types::DataType;
+use crate::utils::query_engine::query_cancellation::{NativeCancel, QueryHandle};
 use sqlparser::ast::{Expr, Ident, ObjectName, VisitMut, VisitorMut};
 use sqlparser::dialect::PostgreSqlDialect;
 use sqlparser::parser::Parser;
@@ -58,18 +61,14 @@ pub async fn postgres_query(
     pg_pool: Pool<Postgres>,
     query: String,
     limit: Option<i64>,
+    running_query: &QueryHandle,
 ) -> Result<Vec<IndexMap<std::string::String, DataType>>, Error> {
-    let dialect = PostgreSqlDialect {};
-    let mut ast = Parser::parse_sql(&dialect, &query)?;
+    let formatted_sql = quote_identifiers(&query)?;
 
-    let mut column_visitor = QuotedIdentifierColumnVisitor;
-    ast.visit(&mut column_visitor);
-    let mut table_visitor = QuotedIdentifierTableVisitor;
-    ast.visit(&mut table_visitor);
+    let mut conn = pg_pool.acquire().await?;
+    register_backend_pid(&mut conn, &pg_pool, running_query).await?;
 
-    let formatted_sql = ast[0].to_string();
-
-    let mut stream = sqlx::query(&formatted_sql).fetch(&pg_pool);
+    let mut stream = sqlx::query(&formatted_sql).fetch(&mut *conn);
 
     let mut result: Vec<IndexMap<String, DataType>> = Vec::new();
     let mut count = 0;
@@ -103,6 +102,190 @@ pub async fn postgres_query(
     Ok(result)
 }
 
+/// Streaming counterpart of `postgres_query`. Rows are converted and pushed to `sender` one
+/// `RecordBatch` at a time instead of being collected.
+pub async fn postgres_query_stream(
+    pg_pool: Pool<Postgres>,
+    query: String,
+    limit: Option<i64>,
+    sender: &mut RecordBatchSender,
+    running_query: &QueryHandle,
+) -> Result<(), Error> {
+    let formatted_sql = quote_identifiers(&query)?;
+
+    let mut conn = pg_pool.acquire().await?;
+    register_backend_pid(&mut conn, &pg_pool, running_query).await?;
+
+    let mut stream = sqlx::query(&formatted_sql).fetch(&mut *conn);
+
+    let mut count = 0;
+    let mut rows = Vec::with_capacity(RECORD_BATCH_SIZE);
+
+    while let Some(row) = stream.try_next().await? {
+        rows.push(row);
+        count += 1;
+
+        if rows.len() == RECORD_BATCH_SIZE {
+            let batch_result = process_batch(std::mem::take(&mut rows)).await?;
+            if !sender.send_rows(&batch_result).await {
+                return Ok(());
+            }
+        }
+
+        if let Some(limit) = limit {
+            if count >= limit {
+                break;
+            }
+        }
+    }
+
+    if !rows.is_empty() {
+        let batch_result = process_batch(rows).await?;
+        sender.send_rows(&batch_result).await;
+    }
+
+    Ok(())
+}
+
+/// A `SCROLL WITH HOLD` cursor over a query's results. It pins one pooled connection until it is
+/// closed, so pages can be read in any order without running the query again.
+pub struct PostgresCursor {
+    conn: Option<PoolConnection<Postgres>>,
+    pool: CachedPool<Pool<Postgres>>,
+    name: String,
+}
+
+/// Declares a cursor for `query` and returns it with the total row count. The results are
+/// materialized when the declaring transaction commits, which is what makes counting them cheap.
+pub async fn postgres_declare_cursor(
+    pg_pool: CachedPool<Pool<Postgres>>,
+    query: String,
+    cursor_name: String,
+    running_query: &QueryHandle,
+) -> Result<(PostgresCursor, i64), Error> {
+    let formatted_sql = quote_identifiers(&query)?;
+
+    let mut conn = pg_pool.acquire().await?;
+    register_backend_pid(&mut conn, &pg_pool, running_query).await?;
+
+    sqlx::query(&format!(
+        "DECLARE {} SCROLL CURSOR WITH HOLD FOR {}",
+        cursor_name, formatted_sql
+    ))
+    .execute(&mut *conn)
+    .await?;
+
+    let mut cursor = PostgresCursor {
+        conn: Some(conn),
+        pool: pg_pool,
+        name: cursor_name,
+    };
+
+    let total_rows = match cursor
+        .execute(&format!("MOVE FORWARD ALL IN {}", cursor.name))
+        .await
+    {
+        Ok(total_rows) => total_rows,
+        Err(e) => {
+            cursor.close().await;
+            return Err(e);
+        }
+    };
+
+    Ok((cursor, total_rows as i64))
+}
+
+impl PostgresCursor {
+    /// Reads up to `limit` rows starting `offset` rows into the results.
+    pub async fn fetch(
+        &mut self,
+        offset: i64,
+        limit: i64,
+        running_query: &QueryHandle,
+    ) -> Result<Vec<IndexMap<String, DataType>>, Error> {
+        let conn = match self.conn.as_mut() {
+            Some(conn) => conn,
+            None => return Err(anyhow!("Cursor is closed")),
+        };
+
+        register_backend_pid(conn, &self.pool, running_query).await?;
+
+        sqlx::query(&format!("MOVE ABSOLUTE {} IN {}", offset, self.name))
+            .execute(&mut **conn)
+            .await?;
+
+        let rows = sqlx::query(&format!("FETCH FORWARD {} FROM {}", limit, self.name))
+            .fetch_all(&mut **conn)
+            .await?;
+
+        process_batch(rows).await
+    }
+
+    /// Closes the cursor and hands the connection back to the pool.
+    pub async fn close(mut self) {
+        let name = self.name.clone();
+
+        if let Err(e) = self.execute(&format!("CLOSE {}", name)).await {
+            tracing::warn!("Unable to close cursor {}: {}", name, e);
+            return;
+        }
+
+        self.conn.take();
+    }
+
+    async fn execute(&mut self, statement: &str) -> Result<u64, Error> {
+        let conn = match self.conn.as_mut() {
+            Some(conn) => conn,
+            None => return Err(anyhow!("Cursor is closed")),
+        };
+
+        let result = sqlx::query(statement).execute(&mut **conn).await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+impl Drop for PostgresCursor {
+    /// A cursor that wasn't closed would outlive us on the pooled connection, so the connection is
+    /// taken out of the pool and closed with it.
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            drop(conn.detach());
+        }
+    }
+}
+
+/// Records the backend the query runs on so it can be stopped with `pg_cancel_backend`. Redshift
+/// supports the same functions.
+pub async fn register_backend_pid(
+    conn: &mut PgConnection,
+    pg_pool: &Pool<Postgres>,
+    running_query: &QueryHandle,
+) -> Result<(), Error> {
+    let backend_pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()")
+        .fetch_one(&mut *conn)
+        .await?;
+
+    running_query.set_native_cancel(NativeCancel::Postgres {
+        pool: pg_pool.clone(),
+        backend_pid,
+    });
+
+    Ok(())
+}
+
+fn quote_identifiers(query: &str) -> Result<String, Error> {
+    let dialect = PostgreSqlDialect {};
+    let mut ast = Parser::parse_sql(&dialect, query)?;
+
+    let mut column_visitor = QuotedIdentifierColumnVisitor;
+    ast.visit(&mut column_visitor);
+    let mut table_visitor = QuotedIdentifierTableVisitor;
+    ast.visit(&mut table_visitor);
+
+    Ok(ast[0].to_string())
+}
+
 async fn process_batch(
     rows: Vec<sqlx::postgres::PgRow>,
 ) -> Result<Vec<IndexMap<String, DataType>>, Error> {
@@ -1,3 +1,5 @@
+use std::env;
+
 use indexmap::IndexMap;
 
 use anyhow::{anyhow, Result};
@@ -7,58 +9,83 @@ use crate::{
     utils::{
         clients::supabase_vault::read_secret,
         query_engine::{
+            arrow_conversion::{
+                record_batch_channel, rows_to_record_batch_stream, RecordBatchStream,
+            },
+            connection_cache::{
+                get_cached_mysql_pool, get_cached_postgres_pool, get_cached_redshift_pool,
+                get_cached_sql_server_pool, get_cached_starrocks_pool,
+            },
             credentials::{
-                BigqueryCredentials, DatabricksCredentials, MySqlCredentials, PostgresCredentials, SnowflakeCredentials, SqlServerCredentials,
+                BigqueryCredentials, ClickHouseCredentials, DatabricksCredentials,
+                DuckDbCredentials, SnowflakeCredentials, TrinoCredentials,
             },
             data_source_connections::{
                 get_bigquery_client::get_bigquery_client,
+                get_clickhouse_client::get_clickhouse_client,
                 get_databricks_client::get_databricks_client,
-                get_mysql_connection::get_mysql_connection,
-                get_postgres_connection::get_postgres_connection,
-                get_redshift_connection::get_redshift_connection,
-                get_snowflake_client::get_snowflake_client,
-                get_sql_server_connection::get_sql_server_connection,
-                ssh_tunneling::kill_ssh_tunnel,
+                get_snowflake_client::get_snowflake_client, get_trino_client::get_trino_client,
             },
             data_types::DataType,
+            query_cancellation::{
+                max_execution_time, register_query, NativeCancel, QueryContext, QueryHandle,
+            },
+            query_pagination::page_sql,
         },
     },
 };
 
 use super::{
-    bigquery_query::bigquery_query,
-    databricks_query::databricks_query,
-    mysql_query::mysql_query,
-    postgres_query::postgres_query,
-    redshift_query::redshift_query,
+    bigquery_query::{bigquery_query, bigquery_query_stream},
+    clickhouse_query::clickhouse_query,
+    databricks_query::{databricks_query, databricks_query_stream},
+    duckdb_query::duckdb_query,
+    mysql_query::{mysql_query, mysql_query_stream},
+    postgres_query::{
+        postgres_declare_cursor, postgres_query, postgres_query_stream, PostgresCursor,
+    },
+    redshift_query::{redshift_query, redshift_query_stream},
     security_utils::{query_safety_filter, write_query_safety_filter},
-    snowflake_query::snowflake_query,
+    snowflake_query::{snowflake_query, snowflake_session_id},
     sql_server_query::sql_server_query,
+    trino_query::trino_query,
 };
 
+/// Most rows read into memory for sources that can't stream their results.
+const DEFAULT_STREAM_FALLBACK_MAX_ROWS: i64 = 100_000;
+
 pub async fn query_router(
     data_source: &DataSource,
     sql: &String,
     limit: Option<i64>,
     write_req: bool,
+    context: &QueryContext,
 ) -> Result<Vec<IndexMap<String, DataType>>> {
     let corrected_sql = sql.clone();
 
     let secure_sql = corrected_sql.clone();
 
     if write_req {
-        match write_query_safety_filter(secure_sql.clone()).await {
+        match write_query_safety_filter(secure_sql.clone(), &data_source.type_).await {
             Some(warning) => return Err(anyhow!(warning)),
             None => (),
         };
     } else {
-        match query_safety_filter(secure_sql.clone()).await {
+        match query_safety_filter(secure_sql.clone(), &data_source.type_).await {
             Some(warning) => return Err(anyhow!(warning)),
             None => (),
         };
     }
 
-    let results = match route_to_query(&data_source, &secure_sql, limit).await {
+    let query = register_query(context)?;
+
+    let mut results = match query
+        .run(
+            max_execution_time(data_source),
+            route_to_query(&data_source, &secure_sql, limit, &query),
+        )
+        .await
+    {
         Ok(results) => results,
         Err(e) => {
             tracing::error!(
@@ -69,13 +96,262 @@ pub async fn query_router(
         }
     };
 
+    // Not every client can stop reading early, so the limit is applied here for all of them.
+    if let Some(limit) = limit {
+        results.truncate(limit.max(0) as usize);
+    }
+
     Ok(results)
 }
 
+/// Runs one page of a read-only query. The paging clause is added in the data source's dialect,
+/// so every data source pages the same way.
+pub async fn query_router_page(
+    data_source: &DataSource,
+    sql: &String,
+    offset: i64,
+    limit: i64,
+    context: &QueryContext,
+) -> Result<Vec<IndexMap<String, DataType>>> {
+    if let Some(warning) = query_safety_filter(sql.clone(), &data_source.type_).await {
+        return Err(anyhow!(warning));
+    }
+
+    let page_sql = page_sql(sql, &data_source.type_, offset, limit)?;
+
+    query_router(data_source, &page_sql, Some(limit), false, context).await
+}
+
+/// Declares a server-side cursor over a read-only query. Returns `None` for data sources whose
+/// driver has no cursors; those are paged with `query_router_page` instead.
+pub async fn query_router_declare_cursor(
+    data_source: &DataSource,
+    sql: &String,
+    cursor_name: String,
+    context: &QueryContext,
+) -> Result<Option<(PostgresCursor, i64)>> {
+    match data_source.type_ {
+        DataSourceType::Postgres | DataSourceType::Supabase => (),
+        _ => return Ok(None),
+    }
+
+    if let Some(warning) = query_safety_filter(sql.clone(), &data_source.type_).await {
+        return Err(anyhow!(warning));
+    }
+
+    let credentials_string = read_secret(&data_source.secret_id).await?;
+    let pg_pool = get_cached_postgres_pool(&data_source.id, &credentials_string).await?;
+
+    let query = register_query(context)?;
+
+    let cursor = query
+        .run(
+            max_execution_time(data_source),
+            postgres_declare_cursor(pg_pool, sql.clone(), cursor_name, &query),
+        )
+        .await?;
+
+    Ok(Some(cursor))
+}
+
+/// Runs a read-only query and returns the results as a stream of Arrow `RecordBatch`es.
+///
+/// Postgres, Supabase, Redshift, MySQL, MariaDB and StarRocks stream rows straight off the
+/// connection, and BigQuery and Databricks stream the result a page or chunk at a time. The
+/// remaining sources' clients hand back whole result sets, so those are read into memory and
+/// chunked into batches after the fact. To bound that, they are capped at
+/// `STREAM_FALLBACK_MAX_ROWS` rows and fail rather than return a truncated result.
+pub async fn query_router_stream(
+    data_source: &DataSource,
+    sql: &String,
+    limit: Option<i64>,
+    context: &QueryContext,
+) -> Result<RecordBatchStream> {
+    if let Some(warning) = query_safety_filter(sql.clone(), &data_source.type_).await {
+        return Err(anyhow!(warning));
+    }
+
+    let query = register_query(context)?;
+    let max_execution_time = max_execution_time(data_source);
+
+    let (mut sender, stream) = record_batch_channel();
+    let sql = sql.clone();
+
+    match data_source.type_ {
+        DataSourceType::Postgres | DataSourceType::Supabase => {
+            let credentials_string = read_secret(&data_source.secret_id).await?;
+            let pg_pool = get_cached_postgres_pool(&data_source.id, &credentials_string).await?;
+
+            // The task owns the checked-out pool, so its ssh tunnel stays up until the stream ends.
+            tokio::spawn(async move {
+                let result = query
+                    .run(
+                        max_execution_time,
+                        postgres_query_stream(pg_pool.clone(), sql, limit, &mut sender, &query),
+                    )
+                    .await;
+
+                if let Err(e) = result {
+                    sender.send_error(e).await;
+                }
+            });
+        }
+        DataSourceType::Redshift => {
+            let credentials_string = read_secret(&data_source.secret_id).await?;
+            let redshift_client =
+                get_cached_redshift_pool(&data_source.id, &credentials_string).await?;
+
+            tokio::spawn(async move {
+                let result = query
+                    .run(
+                        max_execution_time,
+                        redshift_query_stream(
+                            redshift_client.clone(),
+                            sql,
+                            limit,
+                            &mut sender,
+                            &query,
+                        ),
+                    )
+                    .await;
+
+                if let Err(e) = result {
+                    sender.send_error(e).await;
+                }
+            });
+        }
+        DataSourceType::MySql | DataSourceType::Mariadb => {
+            let credentials_string = read_secret(&data_source.secret_id).await?;
+            let mysql_pool = get_cached_mysql_pool(&data_source.id, &credentials_string).await?;
+
+            tokio::spawn(async move {
+                let result = query
+                    .run(
+                        max_execution_time,
+                        mysql_query_stream(mysql_pool.clone(), sql, limit, &mut sender, &query),
+                    )
+                    .await;
+
+                if let Err(e) = result {
+                    sender.send_error(e).await;
+                }
+            });
+        }
+        DataSourceType::StarRocks => {
+            let credentials_string = read_secret(&data_source.secret_id).await?;
+            let starrocks_pool =
+                get_cached_starrocks_pool(&data_source.id, &credentials_string).await?;
+
+            tokio::spawn(async move {
+                let result = query
+                    .run(
+                        max_execution_time,
+                        mysql_query_stream(starrocks_pool.clone(), sql, limit, &mut sender, &query),
+                    )
+                    .await;
+
+                if let Err(e) = result {
+                    sender.send_error(e).await;
+                }
+            });
+        }
+        DataSourceType::BigQuery => {
+            let credentials_string = read_secret(&data_source.secret_id).await?;
+            let credentials: BigqueryCredentials = serde_json::from_str(&credentials_string)?;
+            let (bq_client, project_id) = get_bigquery_client(&credentials).await?;
+
+            tokio::spawn(async move {
+                let result = query
+                    .run(
+                        max_execution_time,
+                        bigquery_query_stream(
+                            bq_client,
+                            project_id,
+                            sql,
+                            limit,
+                            &mut sender,
+                            &query,
+                        ),
+                    )
+                    .await;
+
+                if let Err(e) = result {
+                    sender.send_error(e).await;
+                }
+            });
+        }
+        DataSourceType::Databricks => {
+            let credentials_string = read_secret(&data_source.secret_id).await?;
+            let credentials: DatabricksCredentials = serde_json::from_str(&credentials_string)?;
+            let databricks_client = get_databricks_client(&credentials).await?;
+
+            tokio::spawn(async move {
+                let result = query
+                    .run(
+                        max_execution_time,
+                        databricks_query_stream(databricks_client, sql, limit, &mut sender, &query),
+                    )
+                    .await;
+
+                if let Err(e) = result {
+                    sender.send_error(e).await;
+                }
+            });
+        }
+        _ => {
+            let max_rows = stream_fallback_max_rows();
+
+            // The cap is pushed down as a paging clause so the data source stops producing rows
+            // past it. One extra row is read to tell a result at the cap from one over it.
+            let (capped_sql, capped_limit) = match limit {
+                Some(limit) if limit <= max_rows => (sql, limit),
+                _ => (
+                    page_sql(&sql, &data_source.type_, 0, max_rows + 1)?,
+                    max_rows + 1,
+                ),
+            };
+
+            let mut rows = query
+                .run(
+                    max_execution_time,
+                    route_to_query(data_source, &capped_sql, Some(capped_limit), &query),
+                )
+                .await?;
+
+            if rows.len() as i64 > max_rows {
+                return Err(anyhow!(
+                    "Query returned more than {} rows, which is the most that can be streamed \
+                     from {}. Add a LIMIT or page through the results instead.",
+                    max_rows,
+                    data_source.type_.to_string()
+                ));
+            }
+
+            if let Some(limit) = limit {
+                rows.truncate(limit.max(0) as usize);
+            }
+
+            return Ok(rows_to_record_batch_stream(rows));
+        }
+    }
+
+    Ok(stream)
+}
+
+/// `STREAM_FALLBACK_MAX_ROWS` when set to a positive number.
+fn stream_fallback_max_rows() -> i64 {
+    env::var("STREAM_FALLBACK_MAX_ROWS")
+        .ok()
+        .and_then(|rows| rows.parse().ok())
+        .filter(|rows: &i64| *rows > 0)
+        .unwrap_or(DEFAULT_STREAM_FALLBACK_MAX_ROWS)
+}
+
 async fn route_to_query(
     data_source: &DataSource,
     sql: &String,
     limit: Option<i64>,
+    query: &QueryHandle,
 ) -> Result<Vec<IndexMap<String, DataType>>> {
     let credentials_string = match read_secret(&data_source.secret_id).await {
         Ok(credentials) => credentials,
@@ -84,12 +360,7 @@ async fn route_to_query(
 
     let results = match data_source.type_ {
         DataSourceType::Postgres | DataSourceType::Supabase => {
-            let credentials = match serde_json::from_str(&credentials_string) {
-                Ok(credentials) => credentials,
-                Err(e) => return Err(anyhow!(e)),
-            };
-
-            let (pg_pool, ssh_tunnel, temp_files) = match get_postgres_connection(&credentials)
+            let pg_pool = match get_cached_postgres_pool(&data_source.id, &credentials_string)
                 .await
             {
                 Ok(pg_pool) => pg_pool,
@@ -99,25 +370,20 @@ async fn route_to_query(
                 }
             };
 
-            let results = match postgres_query(pg_pool, sql.clone(), limit).await {
+            let results = match postgres_query(pg_pool.clone(), sql.clone(), limit, query).await {
                 Ok(results) => results,
                 Err(e) => {
                     return Err(anyhow!(e));
                 }
             };
 
-            if let (Some(mut ssh_tunnel), Some(temp_files)) = (ssh_tunnel, temp_files) {
-                let _ = kill_ssh_tunnel(&mut ssh_tunnel, temp_files).await;
-            };
-
             results
         }
         DataSourceType::Redshift => {
-            let credentials: PostgresCredentials = serde_json::from_str(&credentials_string)?;
+            let redshift_client =
+                get_cached_redshift_pool(&data_source.id, &credentials_string).await?;
 
-            let redshift_client = get_redshift_connection(&credentials).await?;
-
-            let results = match redshift_query(redshift_client, sql.clone()).await {
+            let results = match redshift_query(redshift_client.clone(), sql.clone(), query).await {
                 Ok(results) => results,
                 Err(e) => {
                     tracing::error!("There was an issue while fetching the tables: {}", e);
@@ -128,9 +394,7 @@ async fn route_to_query(
             results
         }
         DataSourceType::MySql | DataSourceType::Mariadb => {
-            let credentials: MySqlCredentials = serde_json::from_str(&credentials_string)?;
-
-            let (mysql_pool, ssh_tunnel, temp_files) = match get_mysql_connection(&credentials)
+            let mysql_pool = match get_cached_mysql_pool(&data_source.id, &credentials_string)
                 .await
             {
                 Ok(mysql_pool) => mysql_pool,
@@ -140,7 +404,7 @@ async fn route_to_query(
                 }
             };
 
-            let results = match mysql_query(mysql_pool, sql.clone()).await {
+            let results = match mysql_query(mysql_pool.clone(), sql.clone(), query).await {
                 Ok(results) => results,
                 Err(e) => {
                     tracing::error!("There was an issue while fetching the tables: {}", e);
@@ -148,8 +412,28 @@ async fn route_to_query(
                 }
             };
 
-            if let (Some(mut ssh_tunnel), Some(temp_files)) = (ssh_tunnel, temp_files) {
-                let _ = kill_ssh_tunnel(&mut ssh_tunnel, temp_files).await;
+            results
+        }
+        DataSourceType::StarRocks => {
+            let starrocks_pool = match get_cached_starrocks_pool(
+                &data_source.id,
+                &credentials_string,
+            )
+            .await
+            {
+                Ok(starrocks_pool) => starrocks_pool,
+                Err(e) => {
+                    tracing::error!("There was an issue while establishing a connection to the parent data source: {}", e);
+                    return Err(anyhow!(e));
+                }
+            };
+
+            let results = match mysql_query(starrocks_pool.clone(), sql.clone(), query).await {
+                Ok(results) => results,
+                Err(e) => {
+                    tracing::error!("There was an issue while fetching the tables: {}", e);
+                    return Err(anyhow!(e));
+                }
             };
 
             results
@@ -165,7 +449,7 @@ async fn route_to_query(
                 }
             };
 
-            let results = match bigquery_query(bq_client, project_id, sql.clone()).await {
+            let results = match bigquery_query(bq_client, project_id, sql.clone(), query).await {
                 Ok(results) => results,
                 Err(e) => {
                     tracing::error!("There was an issue while fetching the tables: {}", e);
@@ -176,10 +460,9 @@ async fn route_to_query(
             results
         }
         DataSourceType::SqlServer => {
-            let credentials: SqlServerCredentials = serde_json::from_str(&credentials_string)?;
-
-            let (sql_server_pool, ssh_tunnel, temp_files) = match get_sql_server_connection(
-                &credentials,
+            let sql_server_pool = match get_cached_sql_server_pool(
+                &data_source.id,
+                &credentials_string,
             )
             .await
             {
@@ -190,16 +473,22 @@ async fn route_to_query(
                 }
             };
 
-            let results = match sql_server_query(sql_server_pool, sql.clone()).await {
-                Ok(results) => results,
+            // SQL Server has no native cancel. A cancelled query drops its connection mid-query,
+            // and the pool closes it rather than taking it back, which makes the server abort it.
+            let mut sql_server_connection = match sql_server_pool.get().await {
+                Ok(sql_server_connection) => sql_server_connection,
                 Err(e) => {
-                    tracing::error!("There was an issue while fetching the tables: {}", e);
+                    tracing::error!("There was an issue while establishing a connection to the parent data source: {}", e);
                     return Err(anyhow!(e));
                 }
             };
 
-            if let (Some(mut ssh_tunnel), Some(temp_files)) = (ssh_tunnel, temp_files) {
-                let _ = kill_ssh_tunnel(&mut ssh_tunnel, temp_files).await;
+            let results = match sql_server_query(&mut sql_server_connection, sql.clone()).await {
+                Ok(results) => results,
+                Err(e) => {
+                    tracing::error!("There was an issue while fetching the tables: {}", e);
+                    return Err(anyhow!(e));
+                }
             };
 
             results
@@ -215,7 +504,7 @@ async fn route_to_query(
                 }
             };
 
-            let results = match databricks_query(databricks_client, sql.clone()).await {
+            let results = match databricks_query(databricks_client, sql.clone(), query).await {
                 Ok(results) => results,
                 Err(e) => {
                     tracing::error!("There was an issue while fetching the tables: {}", e);
@@ -236,6 +525,14 @@ async fn route_to_query(
                 }
             };
 
+            match snowflake_session_id(&snowflake_client).await {
+                Ok(session_id) => query.set_native_cancel(NativeCancel::Snowflake {
+                    credentials,
+                    session_id,
+                }),
+                Err(e) => tracing::warn!("Unable to get snowflake session id: {}", e),
+            }
+
             let results = match snowflake_query(snowflake_client, sql.clone()).await {
                 Ok(results) => results,
                 Err(e) => {
@@ -244,6 +541,61 @@ async fn route_to_query(
                 }
             };
 
+            results
+        }
+        DataSourceType::ClickHouse => {
+            let credentials: ClickHouseCredentials = serde_json::from_str(&credentials_string)?;
+
+            let clickhouse_client = match get_clickhouse_client(&credentials).await {
+                Ok(clickhouse_client) => clickhouse_client,
+                Err(e) => {
+                    tracing::error!("There was an issue while establishing a connection to the parent data source: {}", e);
+                    return Err(anyhow!(e));
+                }
+            };
+
+            let results = match clickhouse_query(clickhouse_client, sql.clone(), limit, query).await {
+                Ok(results) => results,
+                Err(e) => {
+                    tracing::error!("There was an issue while fetching the tables: {}", e);
+                    return Err(anyhow!(e));
+                }
+            };
+
+            results
+        }
+        DataSourceType::Trino => {
+            let credentials: TrinoCredentials = serde_json::from_str(&credentials_string)?;
+
+            let trino_client = match get_trino_client(&credentials).await {
+                Ok(trino_client) => trino_client,
+                Err(e) => {
+                    tracing::error!("There was an issue while establishing a connection to the parent data source: {}", e);
+                    return Err(anyhow!(e));
+                }
+            };
+
+            let results = match trino_query(trino_client, sql.clone(), limit, query).await {
+                Ok(results) => results,
+                Err(e) => {
+                    tracing::error!("There was an issue while fetching the tables: {}", e);
+                    return Err(anyhow!(e));
+                }
+            };
+
+            results
+        }
+        DataSourceType::DuckDb => {
+            let credentials: DuckDbCredentials = serde_json::from_str(&credentials_string)?;
+
+            let results = match duckdb_query(credentials, sql.clone(), limit, query).await {
+                Ok(results) => results,
+                Err(e) => {
+                    tracing::error!("There was an issue while fetching the tables: {}", e);
+                    return Err(anyhow!(e));
+                }
+            };
+
             results
         }
     };
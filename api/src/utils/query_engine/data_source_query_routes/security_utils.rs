@@ -1,114 +1,645 @@
-pub async fn query_safety_filter(sql: String) -> Option<String> {
-    let uppercase_sql = sql.to_uppercase();
+use std::ops::ControlFlow;
 
-    if uppercase_sql.contains("INFORMATION_SCHEMA") {
-        return Some("Access denied to information_schema.".to_string());
+use sqlparser::ast::{
+    Expr, ObjectName, ObjectType, Query, SetExpr, Spanned, Statement, Visit, Visitor,
+};
+use sqlparser::dialect::Dialect;
+use sqlparser::parser::Parser;
+use sqlparser::tokenizer::{Span, Token, Tokenizer};
+
+use crate::database::enums::DataSourceType;
+use crate::utils::query_engine::utils::get_sql_dialect;
+
+/// Functions that mutate state or reach outside of the warehouse even though
+/// they can be called from inside a `SELECT`.
+const DENIED_FUNCTIONS: [&str; 14] = [
+    "pg_terminate_backend",
+    "pg_cancel_backend",
+    "pg_reload_conf",
+    "pg_read_file",
+    "pg_read_binary_file",
+    "pg_ls_dir",
+    "lo_import",
+    "lo_export",
+    "dblink_exec",
+    "set_config",
+    "nextval",
+    "setval",
+    "read_text",
+    "read_blob",
+];
+
+/// DuckDB functions that read local files or URLs, run SQL passed in as a string or expose
+/// secrets. DuckDB runs inside the API process, so none of these may be called from a query.
+const FILE_AND_NETWORK_FUNCTIONS: [&str; 29] = [
+    "read_csv",
+    "read_csv_auto",
+    "sniff_csv",
+    "read_parquet",
+    "parquet_scan",
+    "parquet_metadata",
+    "parquet_schema",
+    "parquet_file_metadata",
+    "parquet_kv_metadata",
+    "read_json",
+    "read_json_auto",
+    "read_json_objects",
+    "read_ndjson",
+    "read_ndjson_auto",
+    "read_ndjson_objects",
+    "read_xlsx",
+    "glob",
+    "delta_scan",
+    "iceberg_scan",
+    "sqlite_scan",
+    "sqlite_attach",
+    "postgres_scan",
+    "postgres_attach",
+    "mysql_scan",
+    "query",
+    "query_table",
+    "getenv",
+    "duckdb_secrets",
+    "which_secret",
+];
+
+/// A single reason a query was rejected by the validator.
+#[derive(Debug, Clone)]
+pub struct QueryViolation {
+    /// The kind of top level statement the violation was found in, e.g. `SELECT` or `INSERT`.
+    pub statement_kind: String,
+    /// The offending AST node rendered back to SQL.
+    pub node: String,
+    /// Where the offending node sits in the original SQL. Empty when the parser
+    /// does not track a location for the node.
+    pub span: Span,
+    /// Message that is safe to surface to users and the LLM.
+    pub reason: String,
+}
+
+impl QueryViolation {
+    fn new(statement_kind: &str, node: String, span: Span, reason: &str) -> Self {
+        let node = if node.chars().count() > 120 {
+            format!("{}...", node.chars().take(120).collect::<String>())
+        } else {
+            node
+        };
+
+        QueryViolation {
+            statement_kind: statement_kind.to_string(),
+            node,
+            span,
+            reason: reason.to_string(),
+        }
     }
+}
 
-    if uppercase_sql.contains("UPDATE ") {
-        return Some(
-            "I'm not allowed to update the database. Please try another request.".to_string(),
-        );
+impl std::fmt::Display for QueryViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.span == Span::empty() {
+            write!(
+                f,
+                "{} ({}: `{}`)",
+                self.reason, self.statement_kind, self.node
+            )
+        } else {
+            write!(
+                f,
+                "{} ({} at line {}, column {}: `{}`)",
+                self.reason,
+                self.statement_kind,
+                self.span.start.line,
+                self.span.start.column,
+                self.node
+            )
+        }
     }
+}
 
-    if uppercase_sql.contains("DELETE ") {
-        return Some(
-            "I'm not allowed to delete from the database. Please try another request.".to_string(),
-        );
+pub async fn query_safety_filter(sql: String, data_source_type: &DataSourceType) -> Option<String> {
+    match validate_read_only_query(&sql, data_source_type) {
+        Ok(()) => None,
+        Err(violations) => Some(format_violations(&violations)),
     }
+}
 
-    if uppercase_sql.contains("INSERT ") {
-        return Some(
-            "I'm not allowed to insert into the database. Please try another request.".to_string(),
-        );
+pub async fn write_query_safety_filter(
+    sql: String,
+    data_source_type: &DataSourceType,
+) -> Option<String> {
+    match validate_view_write_query(&sql, data_source_type) {
+        Ok(()) => None,
+        Err(violations) => Some(format_violations(&violations)),
     }
+}
 
-    if uppercase_sql.contains("DROP ") {
-        return Some(
-            "I'm not allowed to drop tables in the database. Please try another request."
-                .to_string(),
-        );
+/// Parses `sql` with the dialect of the data source and only accepts a single
+/// read-only query. Anything that cannot be parsed is rejected.
+pub fn validate_read_only_query(
+    sql: &str,
+    data_source_type: &DataSourceType,
+) -> Result<(), Vec<QueryViolation>> {
+    let dialect = get_sql_dialect(data_source_type);
+    let statements = parse_statements(sql, dialect.as_ref())?;
+
+    let mut violations = Vec::new();
+
+    for statement in &statements {
+        match statement {
+            Statement::Query(query) => {
+                violations.extend(check_read_only_query(query, "SELECT", true));
+            }
+            _ => violations.push(statement_violation(statement)),
+        }
     }
 
-    if uppercase_sql.contains("CREATE ") {
-        return Some(
-            "I'm not allowed to create tables in the database. Please try another request."
-                .to_string(),
-        );
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
     }
+}
 
-    if uppercase_sql.contains("ALTER ") {
-        return Some(
-            "I'm not allowed to alter tables in the database. Please try another request."
-                .to_string(),
-        );
+/// Validator for the modeling write path. Only `CREATE [OR REPLACE] [MATERIALIZED] VIEW`
+/// and `DROP [MATERIALIZED] VIEW` are accepted, and the body of a view must itself be read-only.
+pub fn validate_view_write_query(
+    sql: &str,
+    data_source_type: &DataSourceType,
+) -> Result<(), Vec<QueryViolation>> {
+    let dialect = get_sql_dialect(data_source_type);
+
+    // sqlparser does not understand `DROP MATERIALIZED VIEW`, so we validate it as a regular
+    // `DROP VIEW` with the `MATERIALIZED` keyword removed.
+    let statements = match strip_drop_materialized(sql, dialect.as_ref()) {
+        Some(stripped_sql) => parse_statements(&stripped_sql, dialect.as_ref())?,
+        None => parse_statements(sql, dialect.as_ref())?,
+    };
+
+    let mut violations = Vec::new();
+
+    for statement in &statements {
+        match statement {
+            Statement::CreateView { query, to, .. } => {
+                if let Some(to) = to {
+                    violations.push(QueryViolation::new(
+                        "CREATE VIEW",
+                        to.to_string(),
+                        to.span(),
+                        "I'm not allowed to create views that write into other tables. Please try another request.",
+                    ));
+                }
+                violations.extend(check_read_only_query(query, "CREATE VIEW", false));
+            }
+            Statement::Drop {
+                object_type: ObjectType::View,
+                ..
+            } => (),
+            _ => violations.push(QueryViolation::new(
+                &statement_kind(statement),
+                statement.to_string(),
+                statement.span(),
+                "I'm only allowed to create, replace, or drop views or materialized views. Please try another request.",
+            )),
+        }
     }
 
-    if uppercase_sql.contains("GRANT ") {
-        return Some(
-            "I'm not allowed to grant permissions in the database. Please try another request."
-                .to_string(),
-        );
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
     }
+}
 
-    if uppercase_sql.contains("REVOKE ") {
-        return Some(
-            "I'm not allowed to revoke permissions in the database. Please try another request."
-                .to_string(),
-        );
+fn format_violations(violations: &[QueryViolation]) -> String {
+    violations
+        .iter()
+        .map(|violation| violation.to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn parse_statements(
+    sql: &str,
+    dialect: &dyn Dialect,
+) -> Result<Vec<Statement>, Vec<QueryViolation>> {
+    let statements = match Parser::parse_sql(dialect, sql) {
+        Ok(statements) => statements,
+        Err(e) => {
+            return Err(vec![QueryViolation::new(
+                "UNKNOWN",
+                e.to_string(),
+                Span::empty(),
+                "I could not parse this query, so I can't verify that it is safe to run. Please try another request.",
+            )])
+        }
+    };
+
+    if statements.is_empty() {
+        return Err(vec![QueryViolation::new(
+            "UNKNOWN",
+            String::new(),
+            Span::empty(),
+            "The query is empty. Please try another request.",
+        )]);
+    }
+
+    if statements.len() > 1 {
+        return Err(statements
+            .iter()
+            .skip(1)
+            .map(|statement| {
+                QueryViolation::new(
+                    &statement_kind(statement),
+                    statement.to_string(),
+                    statement.span(),
+                    "I'm only allowed to run a single statement at a time. Please try another request.",
+                )
+            })
+            .collect());
     }
 
-    None
+    Ok(statements)
 }
 
-pub async fn write_query_safety_filter(sql: String) -> Option<String> {
-    let uppercase_sql = sql.to_uppercase();
+fn strip_drop_materialized(sql: &str, dialect: &dyn Dialect) -> Option<String> {
+    let tokens = Tokenizer::new(dialect, sql).tokenize().ok()?;
+
+    let mut significant = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, token)| !matches!(token, Token::Whitespace(_)));
+
+    let is_keyword = |token: &Token, keyword: &str| match token {
+        Token::Word(word) => word.quote_style.is_none() && word.value.eq_ignore_ascii_case(keyword),
+        _ => false,
+    };
 
-    if !(uppercase_sql.contains("CREATE VIEW")
-        || uppercase_sql.contains("CREATE MATERIALIZED VIEW")
-        || uppercase_sql.contains("DROP VIEW IF EXISTS")
-        || uppercase_sql.contains("DROP MATERIALIZED VIEW IF EXISTS")
-        || uppercase_sql.contains("CREATE OR REPLACE VIEW")
-        || uppercase_sql.contains("CREATE MATERIALIZED VIEW IF NOT EXISTS"))
+    let (_, first) = significant.next()?;
+    let (materialized_index, second) = significant.next()?;
+    let (_, third) = significant.next()?;
+
+    if !(is_keyword(first, "DROP")
+        && is_keyword(second, "MATERIALIZED")
+        && is_keyword(third, "VIEW"))
     {
-        return Some(
-            "I'm only allowed to create, replace, or drop views or materialized views. Please try another request."
-                .to_string(),
-        );
+        return None;
     }
 
-    if uppercase_sql.contains("DELETE ") {
-        return Some(
-            "I'm not allowed to delete from the database. Please try another request.".to_string(),
-        );
-    }
+    Some(
+        tokens
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != materialized_index)
+            .map(|(_, token)| token.to_string())
+            .collect(),
+    )
+}
 
-    if uppercase_sql.contains("INSERT ") {
-        return Some(
-            "I'm not allowed to insert into the database. Please try another request.".to_string(),
-        );
+fn statement_kind(statement: &Statement) -> String {
+    match statement {
+        Statement::Query(_) => "SELECT".to_string(),
+        _ => statement
+            .to_string()
+            .split_whitespace()
+            .next()
+            .unwrap_or("UNKNOWN")
+            .to_uppercase(),
     }
+}
 
-    if uppercase_sql.contains("ALTER ") {
-        return Some(
+fn statement_violation(statement: &Statement) -> QueryViolation {
+    let reason = match statement {
+        Statement::Update { .. } => {
+            "I'm not allowed to update the database. Please try another request."
+        }
+        Statement::Delete(_) | Statement::Truncate { .. } => {
+            "I'm not allowed to delete from the database. Please try another request."
+        }
+        Statement::Insert(_) | Statement::Merge { .. } => {
+            "I'm not allowed to insert into the database. Please try another request."
+        }
+        Statement::Copy { .. } => {
+            "I'm not allowed to read or write files. Please try another request."
+        }
+        Statement::AttachDatabase { .. }
+        | Statement::AttachDuckDBDatabase { .. }
+        | Statement::DetachDuckDBDatabase { .. } => {
+            "I'm not allowed to attach other databases. Please try another request."
+        }
+        Statement::Install { .. } | Statement::Load { .. } => {
+            "I'm not allowed to install or load extensions. Please try another request."
+        }
+        Statement::Drop { .. }
+        | Statement::DropFunction { .. }
+        | Statement::DropProcedure { .. } => {
+            "I'm not allowed to drop tables in the database. Please try another request."
+        }
+        Statement::CreateTable(_)
+        | Statement::CreateView { .. }
+        | Statement::CreateIndex(_)
+        | Statement::CreateSchema { .. }
+        | Statement::CreateDatabase { .. }
+        | Statement::CreateFunction { .. }
+        | Statement::CreateProcedure { .. }
+        | Statement::CreateRole { .. } => {
+            "I'm not allowed to create tables in the database. Please try another request."
+        }
+        Statement::AlterTable { .. }
+        | Statement::AlterView { .. }
+        | Statement::AlterIndex { .. }
+        | Statement::AlterRole { .. } => {
             "I'm not allowed to alter tables in the database. Please try another request."
-                .to_string(),
-        );
+        }
+        Statement::Grant { .. } => {
+            "I'm not allowed to grant permissions in the database. Please try another request."
+        }
+        Statement::Revoke { .. } => {
+            "I'm not allowed to revoke permissions in the database. Please try another request."
+        }
+        _ => "I'm only allowed to run read-only queries. Please try another request.",
+    };
+
+    QueryViolation::new(
+        &statement_kind(statement),
+        statement.to_string(),
+        statement.span(),
+        reason,
+    )
+}
+
+fn check_read_only_query(
+    query: &Query,
+    statement_kind: &str,
+    deny_information_schema: bool,
+) -> Vec<QueryViolation> {
+    let mut visitor = ReadOnlyVisitor {
+        statement_kind,
+        deny_information_schema,
+        violations: Vec::new(),
+    };
+
+    let _ = query.visit(&mut visitor);
+
+    visitor.violations
+}
+
+struct ReadOnlyVisitor<'a> {
+    statement_kind: &'a str,
+    deny_information_schema: bool,
+    violations: Vec<QueryViolation>,
+}
+
+impl ReadOnlyVisitor<'_> {
+    fn push(&mut self, node: String, span: Span, reason: &str) {
+        self.violations
+            .push(QueryViolation::new(self.statement_kind, node, span, reason));
     }
 
-    if uppercase_sql.contains("GRANT ") {
-        return Some(
-            "I'm not allowed to grant permissions in the database. Please try another request."
-                .to_string(),
-        );
+    fn check_set_expr(&mut self, set_expr: &SetExpr) {
+        match set_expr {
+            SetExpr::Select(select) => {
+                if let Some(into) = &select.into {
+                    self.push(
+                        into.to_string(),
+                        into.span(),
+                        "I'm not allowed to write query results into tables. Please try another request.",
+                    );
+                }
+            }
+            SetExpr::Insert(statement) | SetExpr::Update(statement) => {
+                let violation = statement_violation(statement);
+                self.push(violation.node, violation.span, &violation.reason);
+            }
+            SetExpr::SetOperation { left, right, .. } => {
+                self.check_set_expr(left);
+                self.check_set_expr(right);
+            }
+            SetExpr::Query(_) | SetExpr::Values(_) | SetExpr::Table(_) => (),
+        }
     }
+}
 
-    if uppercase_sql.contains("REVOKE ") {
-        return Some(
-            "I'm not allowed to revoke permissions in the database. Please try another request."
-                .to_string(),
-        );
+impl Visitor for ReadOnlyVisitor<'_> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<Self::Break> {
+        self.check_set_expr(&query.body);
+
+        for lock in &query.locks {
+            self.push(
+                lock.to_string(),
+                query.span(),
+                "I'm not allowed to lock rows in the database. Please try another request.",
+            );
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_relation(&mut self, relation: &ObjectName) -> ControlFlow<Self::Break> {
+        if self.deny_information_schema
+            && relation
+                .0
+                .iter()
+                .any(|ident| ident.value.eq_ignore_ascii_case("information_schema"))
+        {
+            self.push(
+                relation.to_string(),
+                relation.span(),
+                "Access denied to information_schema.",
+            );
+        }
+
+        // Table functions such as DuckDB's `read_text(...)` are visited as relations.
+        if relation
+            .0
+            .last()
+            .is_some_and(|ident| is_denied_function(&ident.value))
+        {
+            self.push(
+                relation.to_string(),
+                relation.span(),
+                "I'm only allowed to run read-only queries. Please try another request.",
+            );
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<Self::Break> {
+        if let Expr::Function(function) = expr {
+            let is_denied = function
+                .name
+                .0
+                .last()
+                .is_some_and(|ident| is_denied_function(&ident.value));
+
+            if is_denied {
+                self.push(
+                    function.to_string(),
+                    function.span(),
+                    "I'm only allowed to run read-only queries. Please try another request.",
+                );
+            }
+        }
+
+        ControlFlow::Continue(())
+    }
+}
+
+fn is_denied_function(name: &str) -> bool {
+    DENIED_FUNCTIONS
+        .iter()
+        .chain(FILE_AND_NETWORK_FUNCTIONS.iter())
+        .any(|denied| name.eq_ignore_ascii_case(denied))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_only(sql: &str, data_source_type: DataSourceType) -> Result<(), Vec<QueryViolation>> {
+        validate_read_only_query(sql, &data_source_type)
+    }
+
+    #[test]
+    fn allows_selects_that_mention_write_keywords() {
+        let sql =
+            "SELECT last_update , 'DELETE FROM users' AS note FROM orders WHERE status = 'CREATE '";
+        assert!(read_only(sql, DataSourceType::Postgres).is_ok());
+    }
+
+    #[test]
+    fn allows_ctes_and_set_operations() {
+        let sql = "WITH a AS (SELECT id FROM x) SELECT id FROM a UNION ALL SELECT id FROM y";
+        assert!(read_only(sql, DataSourceType::Snowflake).is_ok());
+    }
+
+    #[test]
+    fn rejects_write_statements() {
+        for sql in [
+            "UPDATE users SET name = 'a'",
+            "DELETE FROM users",
+            "INSERT INTO users (id) VALUES (1)",
+            "DROP TABLE users",
+            "CREATE TABLE t (id INT)",
+            "COPY users TO '/tmp/users.csv'",
+            "SET ROLE admin",
+            "GRANT SELECT ON users TO bob",
+        ] {
+            let violations = read_only(sql, DataSourceType::Postgres).unwrap_err();
+            assert_eq!(violations.len(), 1, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn rejects_call_and_execute() {
+        assert!(read_only("CALL refresh_everything()", DataSourceType::MySql).is_err());
+        assert!(read_only("EXECUTE my_statement", DataSourceType::Postgres).is_err());
+    }
+
+    #[test]
+    fn rejects_multi_statement_batches() {
+        let violations =
+            read_only("SELECT 1; DROP TABLE users", DataSourceType::Postgres).unwrap_err();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].statement_kind, "DROP");
+    }
+
+    #[test]
+    fn rejects_select_into() {
+        let violations =
+            read_only("SELECT * INTO backup FROM users", DataSourceType::Postgres).unwrap_err();
+        assert_eq!(violations[0].statement_kind, "SELECT");
+        assert_eq!(violations[0].span.start.line, 1);
     }
 
-    None
+    #[test]
+    fn rejects_data_modifying_ctes() {
+        let sql = "WITH d AS (DELETE FROM users RETURNING *) SELECT * FROM d";
+        assert!(read_only(sql, DataSourceType::Postgres).is_err());
+    }
+
+    #[test]
+    fn rejects_information_schema() {
+        let sql = "SELECT * FROM information_schema.tables";
+        let violations = read_only(sql, DataSourceType::Redshift).unwrap_err();
+        assert_eq!(violations[0].node, "information_schema.tables");
+    }
+
+    #[test]
+    fn rejects_file_and_network_table_functions() {
+        for sql in [
+            "SELECT * FROM read_text('/proc/self/environ')",
+            "SELECT * FROM read_parquet('extracts/*.parquet')",
+            "SELECT * FROM read_csv('http://169.254.169.254/latest/meta-data/')",
+            "SELECT * FROM read_json_auto('s3://bucket/data.json')",
+            "SELECT * FROM glob('/home/*')",
+            "SELECT * FROM query('ATTACH ''/tmp/other.duckdb''')",
+            "SELECT content FROM orders WHERE id IN (SELECT 1 FROM read_blob('/etc/shadow'))",
+            "SELECT getenv('DATABASE_URL')",
+        ] {
+            assert!(read_only(sql, DataSourceType::DuckDb).is_err(), "{}", sql);
+        }
+
+        assert!(read_only("SELECT * FROM orders", DataSourceType::DuckDb).is_ok());
+    }
+
+    #[test]
+    fn rejects_attach_extensions_and_copy() {
+        for sql in [
+            "ATTACH '/tmp/other.duckdb' AS other",
+            "DETACH other",
+            "INSTALL httpfs",
+            "LOAD httpfs",
+            "COPY orders TO '/tmp/orders.csv'",
+            "COPY orders FROM '/tmp/orders.csv'",
+        ] {
+            let violations = read_only(sql, DataSourceType::DuckDb).unwrap_err();
+            assert_eq!(violations.len(), 1, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn rejects_unparseable_sql() {
+        assert!(read_only("SELEC * FRM", DataSourceType::Postgres).is_err());
+    }
+
+    #[test]
+    fn write_path_only_allows_views() {
+        let data_source_type = DataSourceType::Postgres;
+
+        assert!(validate_view_write_query(
+            "CREATE OR REPLACE VIEW public.orders_v AS SELECT * FROM orders",
+            &data_source_type
+        )
+        .is_ok());
+        assert!(validate_view_write_query(
+            "CREATE MATERIALIZED VIEW public.orders_v AS SELECT * FROM orders",
+            &data_source_type
+        )
+        .is_ok());
+        assert!(validate_view_write_query(
+            "DROP VIEW IF EXISTS public.orders_v",
+            &data_source_type
+        )
+        .is_ok());
+        assert!(validate_view_write_query(
+            "DROP MATERIALIZED VIEW IF EXISTS public.orders_v",
+            &data_source_type
+        )
+        .is_ok());
+
+        assert!(validate_view_write_query("DROP TABLE public.orders", &data_source_type).is_err());
+        assert!(validate_view_write_query(
+            "CREATE VIEW v AS SELECT 1; DROP TABLE orders",
+            &data_source_type
+        )
+        .is_err());
+        assert!(validate_view_write_query(
+            "CREATE VIEW v AS WITH d AS (DELETE FROM orders RETURNING *) SELECT * FROM d",
+            &data_source_type
+        )
+        .is_err());
+    }
 }
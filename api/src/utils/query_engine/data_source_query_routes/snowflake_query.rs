@@ -9,6 +9,40 @@ use serde_json::Value;
 
 use crate::utils::query_engine::data_types::DataType;
 
+/// Id of the client's session, which a second session can pass to `SYSTEM$CANCEL_ALL_QUERIES` to
+/// stop whatever this client is running.
+pub async fn snowflake_session_id(snowflake_client: &SnowflakeApi) -> Result<String, Error> {
+    let result = match snowflake_client.exec("SELECT CURRENT_SESSION()").await {
+        Ok(result) => result,
+        Err(e) => return Err(anyhow!(e)),
+    };
+
+    let session_id = match result {
+        snowflake_api::QueryResult::Arrow(batches) => batches.first().and_then(|batch| {
+            if batch.num_rows() == 0 || batch.num_columns() == 0 {
+                return None;
+            }
+
+            batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<arrow::array::StringArray>()
+                .map(|array| array.value(0).to_string())
+        }),
+        snowflake_api::QueryResult::Json(result) => result
+            .value
+            .pointer("/0/0")
+            .and_then(|value| value.as_str())
+            .map(|value| value.to_string()),
+        _ => None,
+    };
+
+    match session_id {
+        Some(session_id) if session_id.chars().all(|c| c.is_ascii_digit()) => Ok(session_id),
+        _ => Err(anyhow!("Snowflake did not return a session id")),
+    }
+}
+
 pub async fn snowflake_query(
     snowflake_client: SnowflakeApi,
     query: String,
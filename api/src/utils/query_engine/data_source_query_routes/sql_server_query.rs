@@ -1,17 +1,21 @@
-use crate::utils::query_engine::data_types::DataType;
+use crate::utils::query_engine::{
+    data_source_connections::get_sql_server_connection::SqlServerConnection, data_types::DataType,
+};
 use anyhow::{anyhow, Error, Result};
 use chrono::NaiveDateTime;
 use futures::future::join_all;
 use indexmap::IndexMap;
-use tiberius::{numeric::Decimal, Client, ColumnType};
-use tokio::{net::TcpStream, task};
-use tokio_util::compat::Compat;
+use tiberius::{numeric::Decimal, ColumnType};
+use tokio::task;
 
 pub async fn sql_server_query(
-    mut client: Client<Compat<TcpStream>>,
+    connection: &mut SqlServerConnection,
     query: String,
 ) -> Result<Vec<IndexMap<std::string::String, DataType>>, Error> {
-    let rows = match client.query(query, &[]).await {
+    // Left set if this returns early or is dropped, so the pool discards the connection.
+    connection.in_query = true;
+
+    let rows = match connection.client.query(query, &[]).await {
         Ok(rows) => rows,
         Err(e) => {
             tracing::error!("Unable to execute query: {:?}", e);
@@ -88,5 +92,8 @@ pub async fn sql_server_query(
             result.push(row);
         }
     }
+
+    connection.in_query = false;
+
     Ok(result)
 }
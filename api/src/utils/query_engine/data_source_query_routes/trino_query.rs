@@ -0,0 +1,409 @@
+use indexmap::IndexMap;
+
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde_json::Value;
+
+use crate::utils::query_engine::{
+    data_source_connections::get_trino_client::Trino, data_types::DataType,
+    query_cancellation::QueryHandle,
+};
+
+pub async fn trino_query(
+    trino_client: Trino,
+    query: String,
+    limit: Option<i64>,
+    running_query: &QueryHandle,
+) -> Result<Vec<IndexMap<std::string::String, DataType>>, Error> {
+    let results = match trino_client
+        .query_cancellable(query, limit, running_query)
+        .await
+    {
+        Ok(results) => results,
+        Err(e) => {
+            tracing::error!("Error: {}", e);
+            return Err(anyhow!(e.to_string()));
+        }
+    };
+
+    let mut result: Vec<IndexMap<String, DataType>> = Vec::with_capacity(results.data.len());
+
+    for row in results.data {
+        let mut row_map: IndexMap<String, DataType> = IndexMap::new();
+
+        for (column, value) in results.columns.iter().zip(row) {
+            row_map.insert(column.name.clone(), convert_value(&column.type_, value));
+        }
+
+        result.push(row_map);
+    }
+
+    Ok(result)
+}
+
+fn convert_value(type_name: &str, value: Value) -> DataType {
+    let type_name = type_name.to_lowercase();
+    let base_type = type_name.split('(').next().unwrap_or(&type_name).trim();
+
+    match base_type {
+        "boolean" => DataType::Bool(value.as_bool()),
+        "tinyint" | "smallint" => DataType::Int2(value.as_i64().map(|v| v as i16)),
+        "integer" | "int" => DataType::Int4(value.as_i64().map(|v| v as i32)),
+        "bigint" => DataType::Int8(value.as_i64()),
+        "real" => DataType::Float4(value_to_f64(&value).map(|v| v as f32)),
+        "double" | "decimal" => DataType::Float8(value_to_f64(&value)),
+        "varchar" | "char" | "ipaddress" => DataType::Text(value_to_string(value)),
+        "uuid" => DataType::Uuid(value.as_str().and_then(|v| v.parse().ok())),
+        "date" => DataType::Date(
+            value
+                .as_str()
+                .and_then(|v| NaiveDate::parse_from_str(v, "%Y-%m-%d").ok()),
+        ),
+        "time" => DataType::Time(
+            value
+                .as_str()
+                .and_then(|v| NaiveTime::parse_from_str(v, "%H:%M:%S%.f").ok()),
+        ),
+        "timestamp" if type_name.ends_with("with time zone") => {
+            DataType::Timestamptz(value.as_str().and_then(parse_timestamptz))
+        }
+        "timestamp" => DataType::Timestamp(
+            value
+                .as_str()
+                .and_then(|v| NaiveDateTime::parse_from_str(v, "%Y-%m-%d %H:%M:%S%.f").ok()),
+        ),
+        "json" => DataType::Json(value.as_str().and_then(|v| serde_json::from_str(v).ok())),
+        "array" | "map" | "row" => match value {
+            Value::Null => DataType::Json(None),
+            value => DataType::Json(Some(value)),
+        },
+        _ => DataType::Unknown(value_to_string(value)),
+    }
+}
+
+/// Zoned timestamps come back as `2024-01-01 10:00:00.000 UTC`. Only UTC and numeric offsets can
+/// be resolved without a timezone database; anything else is dropped.
+fn parse_timestamptz(value: &str) -> Option<DateTime<Utc>> {
+    let (timestamp, zone) = value.rsplit_once(' ')?;
+    let timestamp = NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S%.f").ok()?;
+
+    if zone == "UTC" || zone == "Z" {
+        return Some(timestamp.and_utc());
+    }
+
+    DateTime::parse_from_str(
+        &format!("{} {}", timestamp, zone),
+        "%Y-%m-%d %H:%M:%S%.f %:z",
+    )
+    .ok()
+    .map(|v| v.with_timezone(&Utc))
+}
+
+fn value_to_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(v) => v.as_f64(),
+        Value::String(v) => v.parse().ok(),
+        _ => None,
+    }
+}
+
+fn value_to_string(value: Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(v) => Some(v),
+        value => Some(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::query_engine::{
+        credentials::TrinoCredentials,
+        data_source_connections::get_trino_client::{get_trino_client, Trino},
+        query_cancellation::{register_query, QueryContext},
+    };
+    use axum::{
+        extract::{Path, State},
+        http::{Method, StatusCode, Uri},
+        routing::{delete, get, post},
+        Json, Router,
+    };
+    use chrono::TimeZone;
+    use serde_json::json;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tokio::sync::Notify;
+
+    #[derive(Clone)]
+    struct Stub {
+        base: String,
+        requests: Arc<Mutex<Vec<String>>>,
+        cancelled: Arc<Notify>,
+    }
+
+    impl Stub {
+        fn record(&self, method: &Method, uri: &Uri) {
+            self.requests
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", method, uri.path()));
+        }
+
+        fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    async fn serve(app: impl FnOnce(Stub) -> Router) -> (Trino, Stub) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let stub = Stub {
+            base: format!("http://127.0.0.1:{}", port),
+            requests: Arc::new(Mutex::new(Vec::new())),
+            cancelled: Arc::new(Notify::new()),
+        };
+
+        let app = app(stub.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = get_trino_client(&credentials(port, None)).await.unwrap();
+
+        (client, stub)
+    }
+
+    fn credentials(port: u16, password: Option<&str>) -> TrinoCredentials {
+        TrinoCredentials {
+            host: "127.0.0.1".to_string(),
+            port,
+            secure: None,
+            catalog: "hive".to_string(),
+            schema: "default".to_string(),
+            username: "buster".to_string(),
+            password: password.map(String::from),
+            schemas: None,
+        }
+    }
+
+    /// The first response is queued with no data, mirroring how Trino starts a statement.
+    fn queued(stub: &Stub) -> Json<Value> {
+        Json(json!({ "id": "q1", "nextUri": format!("{}/v1/statement/q1/1", stub.base) }))
+    }
+
+    async fn run(
+        client: Trino,
+        limit: Option<i64>,
+    ) -> Result<Vec<IndexMap<String, DataType>>, Error> {
+        let running_query = register_query(&QueryContext::new()).unwrap();
+
+        trino_query(
+            client,
+            "SELECT * FROM orders".to_string(),
+            limit,
+            &running_query,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn follows_next_uri_and_maps_types() {
+        let (client, _) = serve(|stub| {
+            Router::new()
+                .route(
+                    "/v1/statement",
+                    post(|State(stub): State<Stub>| async move { queued(&stub) }),
+                )
+                .route(
+                    "/v1/statement/q1/1",
+                    get(|State(stub): State<Stub>| async move {
+                        Json(json!({
+                            "id": "q1",
+                            "nextUri": format!("{}/v1/statement/q1/2", stub.base),
+                            "columns": [
+                                {"name": "id", "type": "bigint"},
+                                {"name": "amount", "type": "decimal(10,2)"},
+                                {"name": "created_at", "type": "timestamp(3) with time zone"}
+                            ],
+                            "data": [[1, "12.50", "2024-01-01 10:00:00.000 UTC"]]
+                        }))
+                    }),
+                )
+                .route(
+                    "/v1/statement/q1/2",
+                    get(|| async { Json(json!({ "id": "q1", "data": [[2, null, null]] })) }),
+                )
+                .with_state(stub)
+        })
+        .await;
+
+        let rows = run(client, None).await.unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["id"], DataType::Int8(Some(1)));
+        assert_eq!(rows[0]["amount"], DataType::Float8(Some(12.5)));
+        assert_eq!(
+            rows[0]["created_at"],
+            DataType::Timestamptz(Some(Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap()))
+        );
+        assert_eq!(rows[1]["amount"], DataType::Float8(None));
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_limit_and_cancels_the_rest_of_the_statement() {
+        let (client, stub) = serve(|stub| {
+            Router::new()
+                .route(
+                    "/v1/statement",
+                    post(|State(stub): State<Stub>| async move { queued(&stub) }),
+                )
+                .route(
+                    "/v1/statement/q1/:page",
+                    get(
+                        |State(stub): State<Stub>,
+                         Path(page): Path<u32>,
+                         method: Method,
+                         uri: Uri| async move {
+                            stub.record(&method, &uri);
+                            Json(json!({
+                                "id": "q1",
+                                "nextUri": format!("{}/v1/statement/q1/{}", stub.base, page + 1),
+                                "columns": [{"name": "id", "type": "integer"}],
+                                "data": [[page * 2 - 1], [page * 2]]
+                            }))
+                        },
+                    )
+                    .delete(
+                        |State(stub): State<Stub>, method: Method, uri: Uri| async move {
+                            stub.record(&method, &uri);
+                            StatusCode::NO_CONTENT
+                        },
+                    ),
+                )
+                .with_state(stub)
+        })
+        .await;
+
+        let rows = run(client, Some(3)).await.unwrap();
+
+        assert_eq!(
+            rows.iter().map(|row| row["id"].clone()).collect::<Vec<_>>(),
+            vec![
+                DataType::Int4(Some(1)),
+                DataType::Int4(Some(2)),
+                DataType::Int4(Some(3))
+            ]
+        );
+        assert_eq!(
+            stub.requests(),
+            vec![
+                "GET /v1/statement/q1/1",
+                "GET /v1/statement/q1/2",
+                "DELETE /v1/statement/q1/3"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn surfaces_query_errors() {
+        let (client, _) = serve(|stub| {
+            Router::new()
+                .route(
+                    "/v1/statement",
+                    post(|State(stub): State<Stub>| async move { queued(&stub) }),
+                )
+                .route(
+                    "/v1/statement/q1/1",
+                    get(|| async {
+                        Json(json!({
+                            "id": "q1",
+                            "error": { "message": "line 1:8: Column 'amount' cannot be resolved" }
+                        }))
+                    }),
+                )
+                .with_state(stub)
+        })
+        .await;
+
+        let error = run(client, None).await.unwrap_err().to_string();
+
+        assert!(error.contains("Trino query failed: line 1:8: Column 'amount' cannot be resolved"));
+    }
+
+    #[tokio::test]
+    async fn surfaces_http_errors() {
+        let (client, _) = serve(|stub| {
+            Router::new()
+                .route(
+                    "/v1/statement",
+                    post(|| async { (StatusCode::UNAUTHORIZED, "Unauthorized") }),
+                )
+                .with_state(stub)
+        })
+        .await;
+
+        let error = run(client, None).await.unwrap_err().to_string();
+
+        assert!(error.contains("Trino returned 401 Unauthorized: Unauthorized"));
+    }
+
+    #[tokio::test]
+    async fn cancels_running_statements_on_the_coordinator() {
+        // The first page only answers once the statement has been killed, like a long scan.
+        let (client, stub) = serve(|stub| {
+            Router::new()
+                .route(
+                    "/v1/statement",
+                    post(|State(stub): State<Stub>| async move { queued(&stub) }),
+                )
+                .route(
+                    "/v1/statement/q1/1",
+                    get(|State(stub): State<Stub>| async move {
+                        stub.cancelled.notified().await;
+                        Json(json!({ "id": "q1", "error": { "message": "Query was canceled" } }))
+                    }),
+                )
+                .route(
+                    "/v1/query/:id",
+                    delete(
+                        |State(stub): State<Stub>, method: Method, uri: Uri| async move {
+                            stub.record(&method, &uri);
+                            stub.cancelled.notify_one();
+                            StatusCode::NO_CONTENT
+                        },
+                    ),
+                )
+                .with_state(stub)
+        })
+        .await;
+
+        let running_query = register_query(&QueryContext::new()).unwrap();
+
+        let result = running_query
+            .run(
+                Duration::from_millis(200),
+                trino_query(
+                    client,
+                    "SELECT * FROM orders".to_string(),
+                    None,
+                    &running_query,
+                ),
+            )
+            .await;
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("maximum execution time"));
+        assert_eq!(stub.requests(), vec!["DELETE /v1/query/q1"]);
+    }
+
+    #[test]
+    fn refuses_passwords_over_http() {
+        assert!(Trino::new(&credentials(8080, Some("secret"))).is_err());
+        assert!(Trino::new(&credentials(8080, Some(""))).is_ok());
+        assert!(Trino::new(&credentials(443, Some("secret"))).is_ok());
+    }
+}
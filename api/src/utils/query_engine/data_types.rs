@@ -1,4 +1,6 @@
+use arrow::datatypes::{DataType as ArrowDataType, TimeUnit};
 use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use num_traits::ToPrimitive;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tiberius::numeric::Decimal;
@@ -99,4 +101,80 @@ impl DataType {
             DataType::Oid(_) => Some("string".to_string()),
         }
     }
+
+    /// The Arrow type used for this value when results are streamed as `RecordBatch`es.
+    /// `None` for values that carry no type information.
+    pub fn arrow_data_type(&self) -> Option<ArrowDataType> {
+        match self {
+            DataType::Bool(_) => Some(ArrowDataType::Boolean),
+            DataType::Bytea(_) => Some(ArrowDataType::Binary),
+            DataType::Char(_) => Some(ArrowDataType::Utf8),
+            DataType::Int8(_) => Some(ArrowDataType::Int64),
+            DataType::Int4(_) => Some(ArrowDataType::Int32),
+            DataType::Int2(_) => Some(ArrowDataType::Int16),
+            DataType::Text(_) => Some(ArrowDataType::Utf8),
+            DataType::Oid(_) => Some(ArrowDataType::UInt32),
+            DataType::Float4(_) => Some(ArrowDataType::Float32),
+            DataType::Float8(_) => Some(ArrowDataType::Float64),
+            // Decimals are already surfaced as floats by most routes, keep that consistent.
+            DataType::Decimal(_) => Some(ArrowDataType::Float64),
+            DataType::Uuid(_) => Some(ArrowDataType::Utf8),
+            DataType::Timestamp(_) => Some(ArrowDataType::Timestamp(TimeUnit::Microsecond, None)),
+            DataType::Timestamptz(_) => Some(ArrowDataType::Timestamp(
+                TimeUnit::Microsecond,
+                Some("UTC".into()),
+            )),
+            DataType::Date(_) => Some(ArrowDataType::Date32),
+            DataType::Time(_) => Some(ArrowDataType::Time64(TimeUnit::Microsecond)),
+            DataType::Json(_) => Some(ArrowDataType::Utf8),
+            DataType::Unknown(_) => Some(ArrowDataType::Utf8),
+            DataType::Null => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            DataType::Int8(v) => *v,
+            DataType::Int4(v) => v.map(|v| v as i64),
+            DataType::Int2(v) => v.map(|v| v as i64),
+            DataType::Oid(v) => v.map(|v| v as i64),
+            DataType::Bool(v) => v.map(|v| v as i64),
+            DataType::Float4(v) => v.and_then(|v| v.to_i64()),
+            DataType::Float8(v) => v.and_then(|v| v.to_i64()),
+            DataType::Decimal(v) => v.and_then(|v| v.to_i64()),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            DataType::Float4(v) => v.map(|v| v as f64),
+            DataType::Float8(v) => *v,
+            DataType::Decimal(v) => v.and_then(|v| v.to_f64()),
+            _ => self.as_i64().map(|v| v as f64),
+        }
+    }
+
+    /// Renders the value as a string, `None` for nulls.
+    pub fn as_string(&self) -> Option<String> {
+        match self {
+            DataType::Bool(v) => v.map(|v| v.to_string()),
+            DataType::Bytea(v) => v.as_ref().map(|v| String::from_utf8_lossy(v).to_string()),
+            DataType::Char(v) | DataType::Text(v) | DataType::Unknown(v) => v.clone(),
+            DataType::Int8(v) => v.map(|v| v.to_string()),
+            DataType::Int4(v) => v.map(|v| v.to_string()),
+            DataType::Int2(v) => v.map(|v| v.to_string()),
+            DataType::Oid(v) => v.map(|v| v.to_string()),
+            DataType::Float4(v) => v.map(|v| v.to_string()),
+            DataType::Float8(v) => v.map(|v| v.to_string()),
+            DataType::Decimal(v) => v.map(|v| v.to_string()),
+            DataType::Uuid(v) => v.map(|v| v.to_string()),
+            DataType::Timestamp(v) => v.map(|v| v.to_string()),
+            DataType::Timestamptz(v) => v.map(|v| v.to_rfc3339()),
+            DataType::Date(v) => v.map(|v| v.to_string()),
+            DataType::Time(v) => v.map(|v| v.to_string()),
+            DataType::Json(v) => v.as_ref().map(|v| v.to_string()),
+            DataType::Null => None,
+        }
+    }
 }
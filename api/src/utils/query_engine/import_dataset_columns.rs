@@ -2,14 +2,18 @@ use crate::database::{lib::get_pg_pool, models::DatasetColumn, schema::dataset_c
 
 use super::{
     credentials::{
-        BigqueryCredentials, Credential, MySqlCredentials, PostgresCredentials,
-        SnowflakeCredentials,
+        BigqueryCredentials, ClickHouseCredentials, Credential, DuckDbCredentials,
+        MySqlCredentials, PostgresCredentials, SnowflakeCredentials, TrinoCredentials,
     },
     data_source_connections::{
-        get_bigquery_client::get_bigquery_client, get_mysql_connection::get_mysql_connection,
+        get_bigquery_client::get_bigquery_client, get_clickhouse_client::get_clickhouse_client,
+        get_duckdb_connection::get_duckdb_connection,
+        get_mysql_connection::{get_mysql_connection, get_starrocks_connection},
         get_postgres_connection::get_postgres_connection,
         get_snowflake_client::get_snowflake_client,
+        get_trino_client::get_trino_client,
     },
+    data_source_query_routes::clickhouse_query::unwrap_clickhouse_type,
 };
 use anyhow::{anyhow, Result};
 use arrow::array::Array;
@@ -132,6 +136,30 @@ pub async fn retrieve_dataset_columns(
                 Err(e) => return Err(e),
             }
         }
+        Credential::ClickHouse(credentials) => {
+            match get_clickhouse_columns(dataset_name, schema_name, credentials).await {
+                Ok(cols) => cols,
+                Err(e) => return Err(e),
+            }
+        }
+        Credential::DuckDb(credentials) => {
+            match get_duckdb_columns(dataset_name, schema_name, credentials).await {
+                Ok(cols) => cols,
+                Err(e) => return Err(e),
+            }
+        }
+        Credential::Trino(credentials) => {
+            match get_trino_columns(dataset_name, schema_name, credentials).await {
+                Ok(cols) => cols,
+                Err(e) => return Err(e),
+            }
+        }
+        Credential::StarRocks(credentials) => {
+            match get_starrocks_columns(dataset_name, schema_name, credentials).await {
+                Ok(cols) => cols,
+                Err(e) => return Err(e),
+            }
+        }
         _ => return Err(anyhow!("Unsupported data source type")),
     };
 
@@ -143,7 +171,7 @@ async fn get_postgres_columns(
     schema_name: &String,
     credentials: &PostgresCredentials,
 ) -> Result<Vec<DatasetColumnRecord>> {
-    let (postgres_conn, child_process, tempfile) = match get_postgres_connection(credentials).await
+    let (postgres_conn, child_process, tempfile) = match get_postgres_connection(credentials, 1).await
     {
         Ok(conn) => conn,
         Err(e) => return Err(e),
@@ -187,11 +215,113 @@ ORDER BY
     Ok(cols)
 }
 
+async fn get_clickhouse_columns(
+    dataset_name: &str,
+    schema_name: &str,
+    credentials: &ClickHouseCredentials,
+) -> Result<Vec<DatasetColumnRecord>> {
+    let clickhouse_client = get_clickhouse_client(credentials).await?;
+
+    let sql = format!(
+        "SELECT
+    name,
+    type AS type_,
+    startsWith(type, 'Nullable(') OR startsWith(type, 'LowCardinality(Nullable(') AS nullable,
+    comment
+FROM system.columns
+WHERE table = '{dataset_name}'
+    AND database = '{schema_name}'
+ORDER BY position;",
+        dataset_name = dataset_name.replace('\'', "\\'"),
+        schema_name = schema_name.replace('\'', "\\'"),
+    );
+
+    let response = match clickhouse_client.query(sql, None).await {
+        Ok(response) => response,
+        Err(e) => return Err(anyhow!("Error fetching columns: {:?}", e)),
+    };
+
+    let mut cols = Vec::new();
+
+    for row in response.data {
+        let [name, type_, nullable, comment, ..] = row.as_slice() else {
+            return Err(anyhow!("Unexpected column record: {:?}", row));
+        };
+
+        let name = name
+            .as_str()
+            .ok_or_else(|| anyhow!("Missing column name"))?
+            .to_string();
+
+        // Nullable and LowCardinality are captured by `nullable` and don't matter downstream.
+        let type_ = type_
+            .as_str()
+            .map(unwrap_clickhouse_type)
+            .ok_or_else(|| anyhow!("Missing column type"))?
+            .to_string();
+
+        let nullable = nullable.as_bool().unwrap_or(nullable.as_u64() == Some(1));
+
+        cols.push(DatasetColumnRecord {
+            name,
+            type_,
+            nullable,
+            comment: comment.as_str().map(String::from),
+        });
+    }
+
+    Ok(cols)
+}
+
+async fn get_duckdb_columns(
+    dataset_name: &str,
+    schema_name: &str,
+    credentials: &DuckDbCredentials,
+) -> Result<Vec<DatasetColumnRecord>> {
+    let credentials = credentials.clone();
+    let dataset_name = dataset_name.to_string();
+    let schema_name = schema_name.to_string();
+
+    let sql = "SELECT
+    column_name AS name,
+    data_type AS type_,
+    is_nullable AS nullable,
+    comment
+FROM duckdb_columns()
+WHERE table_name = ? AND schema_name = ?
+ORDER BY column_index;";
+
+    let cols = tokio::task::spawn_blocking(move || -> Result<Vec<DatasetColumnRecord>> {
+        let conn = get_duckdb_connection(&credentials)?;
+        let mut stmt = conn.prepare(sql)?;
+
+        let cols = stmt
+            .query_map([&dataset_name, &schema_name], |row| {
+                Ok(DatasetColumnRecord {
+                    name: row.get(0)?,
+                    type_: row.get(1)?,
+                    nullable: row.get(2)?,
+                    comment: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<DatasetColumnRecord>, duckdb::Error>>()?;
+
+        Ok(cols)
+    })
+    .await;
+
+    match cols {
+        Ok(Ok(cols)) => Ok(cols),
+        Ok(Err(e)) => Err(anyhow!("Error fetching columns: {:?}", e)),
+        Err(e) => Err(anyhow!("Error fetching columns: {:?}", e)),
+    }
+}
+
 async fn get_mysql_columns(
     dataset_name: &String,
     credentials: &MySqlCredentials,
 ) -> Result<Vec<DatasetColumnRecord>> {
-    let (mysql_conn, child_process, tempfile) = match get_mysql_connection(credentials).await {
+    let (mysql_conn, child_process, tempfile) = match get_mysql_connection(credentials, 1).await {
         Ok(conn) => conn,
         Err(e) => return Err(e),
     };
@@ -226,6 +356,98 @@ async fn get_mysql_columns(
     Ok(cols)
 }
 
+async fn get_starrocks_columns(
+    dataset_name: &str,
+    schema_name: &str,
+    credentials: &MySqlCredentials,
+) -> Result<Vec<DatasetColumnRecord>> {
+    let (starrocks_conn, child_process, tempfile) =
+        match get_starrocks_connection(credentials, 1).await {
+            Ok(conn) => conn,
+            Err(e) => return Err(e),
+        };
+
+    let sql = format!(
+        "SELECT
+            CAST(COLUMN_NAME AS CHAR) as name,
+            CAST(DATA_TYPE AS CHAR) as type_,
+            CASE WHEN IS_NULLABLE = 'YES' THEN true ELSE false END as nullable,
+            CAST(COLUMN_COMMENT AS CHAR) as comment
+        FROM
+            information_schema.columns
+        WHERE
+            TABLE_NAME = '{dataset_name}'
+            AND TABLE_SCHEMA = '{schema_name}'
+        ORDER BY
+            ORDINAL_POSITION;",
+        dataset_name = dataset_name.replace('\'', "\\'"),
+        schema_name = schema_name.replace('\'', "\\'"),
+    );
+
+    let cols = sqlx::query_as::<_, DatasetColumnRecord>(&sql)
+        .fetch_all(&starrocks_conn)
+        .await
+        .map_err(|e| anyhow!("Error fetching columns: {:?}", e))?;
+
+    if let (Some(mut child_process), Some(tempfile)) = (child_process, tempfile) {
+        child_process.kill()?;
+        for file in tempfile {
+            file.close()?;
+        }
+    }
+
+    Ok(cols)
+}
+
+async fn get_trino_columns(
+    dataset_name: &str,
+    schema_name: &str,
+    credentials: &TrinoCredentials,
+) -> Result<Vec<DatasetColumnRecord>> {
+    let trino_client = get_trino_client(credentials).await?;
+
+    let sql = format!(
+        "SELECT
+    column_name AS name,
+    data_type AS type_,
+    is_nullable = 'YES' AS nullable
+FROM information_schema.columns
+WHERE table_name = '{dataset_name}'
+    AND table_schema = '{schema_name}'
+ORDER BY ordinal_position",
+        dataset_name = dataset_name.replace('\'', "''"),
+        schema_name = schema_name.replace('\'', "''"),
+    );
+
+    let response = match trino_client.query(sql, None).await {
+        Ok(response) => response,
+        Err(e) => return Err(anyhow!("Error fetching columns: {:?}", e)),
+    };
+
+    let mut cols = Vec::new();
+
+    for row in response.data {
+        let [name, type_, nullable, ..] = row.as_slice() else {
+            return Err(anyhow!("Unexpected column record: {:?}", row));
+        };
+
+        cols.push(DatasetColumnRecord {
+            name: name
+                .as_str()
+                .ok_or_else(|| anyhow!("Missing column name"))?
+                .to_string(),
+            type_: type_
+                .as_str()
+                .ok_or_else(|| anyhow!("Missing column type"))?
+                .to_string(),
+            nullable: nullable.as_bool().unwrap_or(true),
+            comment: None,
+        });
+    }
+
+    Ok(cols)
+}
+
 async fn get_bigquery_columns(
     dataset_name: &String,
     credentials: &BigqueryCredentials,
@@ -14,12 +14,16 @@ use crate::{
 
 use super::{
     credentials::{
-        BigqueryCredentials, MySqlCredentials, PostgresCredentials, SnowflakeCredentials,
+        BigqueryCredentials, ClickHouseCredentials, DuckDbCredentials, MySqlCredentials,
+        PostgresCredentials, SnowflakeCredentials, TrinoCredentials,
     },
     data_source_connections::{
-        get_bigquery_client::get_bigquery_client, get_mysql_connection::get_mysql_connection,
+        get_bigquery_client::get_bigquery_client, get_clickhouse_client::get_clickhouse_client,
+        get_duckdb_connection::get_duckdb_connection,
+        get_mysql_connection::{get_mysql_connection, get_starrocks_connection},
         get_postgres_connection::get_postgres_connection,
         get_snowflake_client::get_snowflake_client,
+        get_trino_client::get_trino_client,
     },
 };
 
@@ -81,6 +85,7 @@ async fn create_datasets(
             updated_at: Utc::now(),
             deleted_at: None,
             yml_file: None,
+            cache_ttl_seconds: None,
             model: None,
         })
         .collect::<Vec<Dataset>>();
@@ -106,6 +111,10 @@ async fn retrieve_datasets(credential: &Credential) -> Result<Vec<DatasetRecord>
         Credential::MySQL(credential) => get_mysql_tables_and_views(credential).await?,
         Credential::Bigquery(credential) => get_bigquery_tables_and_views(credential).await?,
         Credential::Snowflake(credential) => get_snowflake_tables_and_views(credential).await?,
+        Credential::DuckDb(credential) => get_duckdb_tables_and_views(credential).await?,
+        Credential::ClickHouse(credential) => get_clickhouse_tables_and_views(credential).await?,
+        Credential::Trino(credential) => get_trino_tables_and_views(credential).await?,
+        Credential::StarRocks(credential) => get_starrocks_tables_and_views(credential).await?,
         _ => return Err(anyhow!("Unsupported database type")),
     };
 
@@ -115,7 +124,7 @@ async fn retrieve_datasets(credential: &Credential) -> Result<Vec<DatasetRecord>
 async fn get_postgres_tables_and_views(
     credentials: &PostgresCredentials,
 ) -> Result<Vec<DatasetRecord>> {
-    let (postgres_conn, child_process, tempfile) = match get_postgres_connection(credentials).await
+    let (postgres_conn, child_process, tempfile) = match get_postgres_connection(credentials, 1).await
     {
         Ok(conn) => conn,
         Err(e) => return Err(e),
@@ -163,7 +172,7 @@ async fn get_postgres_tables_and_views(
 }
 
 async fn get_mysql_tables_and_views(credentials: &MySqlCredentials) -> Result<Vec<DatasetRecord>> {
-    let (mysql_conn, child_process, tempfile) = match get_mysql_connection(credentials).await {
+    let (mysql_conn, child_process, tempfile) = match get_mysql_connection(credentials, 1).await {
         Ok(conn) => conn,
         Err(e) => return Err(e),
     };
@@ -224,6 +233,79 @@ async fn get_mysql_tables_and_views(credentials: &MySqlCredentials) -> Result<Ve
     Ok(table_and_views_records)
 }
 
+async fn get_starrocks_tables_and_views(
+    credentials: &MySqlCredentials,
+) -> Result<Vec<DatasetRecord>> {
+    let (starrocks_conn, child_process, tempfile) =
+        match get_starrocks_connection(credentials, 1).await {
+            Ok(conn) => conn,
+            Err(e) => return Err(e),
+        };
+
+    let schema_string = if let Some(schemas) = &credentials.databases {
+        format!(
+            "IN ({})",
+            schemas
+                .iter()
+                .map(|s| format!("'{}'", s.replace('\'', "\\'")))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    } else {
+        "NOT IN ('information_schema', '_statistics_', 'sys', 'starrocks_monitor')".to_string()
+    };
+
+    // StarRocks reports materialized views as `VIEW` in `TABLES`; they're only told apart by
+    // appearing in `MATERIALIZED_VIEWS`.
+    let tables_and_views_query = format!(
+        "
+    SELECT
+        CAST(t.TABLE_NAME AS CHAR) AS name,
+        CAST(t.TABLE_SCHEMA AS CHAR) AS `schema`,
+        CAST(
+            CASE
+                WHEN mv.TABLE_NAME IS NOT NULL THEN mv.MATERIALIZED_VIEW_DEFINITION
+                WHEN t.TABLE_TYPE = 'VIEW' THEN v.VIEW_DEFINITION
+                ELSE NULL
+            END AS CHAR
+        ) AS definition,
+        CAST(
+            CASE
+                WHEN mv.TABLE_NAME IS NOT NULL THEN 'materializedView'
+                WHEN t.TABLE_TYPE = 'VIEW' THEN 'view'
+                ELSE 'table'
+            END AS CHAR
+        ) AS type_
+    FROM information_schema.tables t
+    LEFT JOIN information_schema.views v
+        ON v.TABLE_SCHEMA = t.TABLE_SCHEMA AND v.TABLE_NAME = t.TABLE_NAME
+    LEFT JOIN information_schema.materialized_views mv
+        ON mv.TABLE_SCHEMA = t.TABLE_SCHEMA AND mv.TABLE_NAME = t.TABLE_NAME
+    WHERE t.TABLE_SCHEMA {}
+    AND t.TABLE_TYPE IN ('BASE TABLE', 'VIEW')
+    ORDER BY `schema`, name;
+    ",
+        schema_string
+    );
+
+    let table_and_views_records = match sqlx::query_as::<_, DatasetRecord>(&tables_and_views_query)
+        .fetch_all(&starrocks_conn)
+        .await
+    {
+        Ok(records) => records,
+        Err(e) => return Err(anyhow!("Error fetching table and views records: {:?}", e)),
+    };
+
+    if let (Some(mut child_process), Some(tempfile)) = (child_process, tempfile) {
+        child_process.kill()?;
+        for file in tempfile {
+            file.close()?;
+        }
+    }
+
+    Ok(table_and_views_records)
+}
+
 async fn get_bigquery_tables_and_views(
     credentials: &BigqueryCredentials,
 ) -> Result<Vec<DatasetRecord>> {
@@ -443,6 +525,177 @@ async fn get_snowflake_tables_and_views(
     Ok(tables_and_views)
 }
 
+async fn get_duckdb_tables_and_views(
+    credentials: &DuckDbCredentials,
+) -> Result<Vec<DatasetRecord>> {
+    let credentials = credentials.clone();
+
+    let schema_string = if let Some(schemas) = &credentials.schemas {
+        format!(
+            "IN ({})",
+            schemas
+                .iter()
+                .map(|s| format!("'{}'", s.replace('\'', "''")))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    } else {
+        "NOT IN ('information_schema', 'pg_catalog')".to_string()
+    };
+
+    let tables_and_views_query = format!(
+        r#"
+    SELECT table_name AS name, schema_name AS "schema", NULL AS definition, 'table' AS type_
+    FROM duckdb_tables()
+    WHERE NOT internal AND schema_name {schema_string}
+    UNION ALL
+    SELECT view_name AS name, schema_name AS "schema", sql AS definition, 'view' AS type_
+    FROM duckdb_views()
+    WHERE NOT internal AND schema_name {schema_string}
+    ORDER BY "schema", name;
+    "#
+    );
+
+    let records = tokio::task::spawn_blocking(move || -> Result<Vec<DatasetRecord>> {
+        let conn = get_duckdb_connection(&credentials)?;
+        let mut stmt = conn.prepare(&tables_and_views_query)?;
+
+        let records = stmt
+            .query_map([], |row| {
+                Ok(DatasetRecord {
+                    name: row.get(0)?,
+                    schema: row.get(1)?,
+                    definition: row.get(2)?,
+                    type_: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<DatasetRecord>, duckdb::Error>>()?;
+
+        Ok(records)
+    })
+    .await;
+
+    match records {
+        Ok(Ok(records)) => Ok(records),
+        Ok(Err(e)) => Err(anyhow!("Error fetching table and views records: {:?}", e)),
+        Err(e) => Err(anyhow!("Error fetching table and views records: {:?}", e)),
+    }
+}
+
+async fn get_clickhouse_tables_and_views(
+    credentials: &ClickHouseCredentials,
+) -> Result<Vec<DatasetRecord>> {
+    let clickhouse_client = get_clickhouse_client(credentials).await?;
+
+    let schema_string = if let Some(schemas) = &credentials.schemas {
+        format!(
+            "IN ({})",
+            schemas
+                .iter()
+                .map(|s| format!("'{}'", s.replace('\'', "\\'")))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    } else {
+        "NOT IN ('system', 'information_schema', 'INFORMATION_SCHEMA')".to_string()
+    };
+
+    let tables_and_views_query = format!(
+        "
+    SELECT
+        name,
+        database AS schema,
+        if(engine IN ('View', 'MaterializedView'), as_select, NULL) AS definition,
+        multiIf(
+            engine = 'View', 'view',
+            engine = 'MaterializedView', 'materializedView',
+            'table'
+        ) AS type_
+    FROM system.tables
+    WHERE database {schema_string}
+    AND NOT is_temporary
+    ORDER BY schema, name;
+    "
+    );
+
+    let response = match clickhouse_client.query(tables_and_views_query, None).await {
+        Ok(response) => response,
+        Err(e) => return Err(anyhow!("Error fetching table and views records: {:?}", e)),
+    };
+
+    let mut tables_and_views = Vec::new();
+
+    for row in response.data {
+        let value = |i: usize| row.get(i).and_then(|v| v.as_str()).map(String::from);
+
+        tables_and_views.push(DatasetRecord {
+            name: value(0).ok_or_else(|| anyhow!("Error fetching table name"))?,
+            schema: value(1).ok_or_else(|| anyhow!("Error fetching table schema"))?,
+            definition: value(2),
+            type_: value(3).ok_or_else(|| anyhow!("Error fetching table type"))?,
+        });
+    }
+
+    Ok(tables_and_views)
+}
+
+async fn get_trino_tables_and_views(
+    credentials: &TrinoCredentials,
+) -> Result<Vec<DatasetRecord>> {
+    let trino_client = get_trino_client(credentials).await?;
+
+    let schema_string = if let Some(schemas) = &credentials.schemas {
+        format!(
+            "IN ({})",
+            schemas
+                .iter()
+                .map(|s| format!("'{}'", s.replace('\'', "''")))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    } else {
+        "<> 'information_schema'".to_string()
+    };
+
+    let tables_and_views_query = format!(
+        "
+    SELECT
+        t.table_name AS name,
+        t.table_schema AS schema,
+        v.view_definition AS definition,
+        CASE
+            WHEN t.table_type = 'VIEW' THEN 'view'
+            ELSE 'table'
+        END AS type_
+    FROM information_schema.tables t
+    LEFT JOIN information_schema.views v
+        ON v.table_schema = t.table_schema AND v.table_name = t.table_name
+    WHERE t.table_schema {schema_string}
+    ORDER BY schema, name
+    "
+    );
+
+    let response = match trino_client.query(tables_and_views_query, None).await {
+        Ok(response) => response,
+        Err(e) => return Err(anyhow!("Error fetching table and views records: {:?}", e)),
+    };
+
+    let mut tables_and_views = Vec::new();
+
+    for row in response.data {
+        let value = |i: usize| row.get(i).and_then(|v| v.as_str()).map(String::from);
+
+        tables_and_views.push(DatasetRecord {
+            name: value(0).ok_or_else(|| anyhow!("Error fetching table name"))?,
+            schema: value(1).ok_or_else(|| anyhow!("Error fetching table schema"))?,
+            definition: value(2),
+            type_: value(3).ok_or_else(|| anyhow!("Error fetching table type"))?,
+        });
+    }
+
+    Ok(tables_and_views)
+}
+
 // pub async fn get_databricks_tables_and_views(
 //     credentials: &DatabricksCredentials,
 // ) -> Result<Vec<DatasetRecord>> {
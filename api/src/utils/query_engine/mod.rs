@@ -1,10 +1,17 @@
+pub mod arrow_conversion;
+pub mod column_level_security;
+pub mod connection_cache;
 pub mod credentials;
 mod data_source_connections;
 mod data_source_query_routes;
 pub mod data_types;
 pub mod import_dataset_columns;
 pub mod import_datasets;
+pub mod query_cache;
+pub mod query_cancellation;
+pub mod query_pagination;
 pub mod query_engine;
+pub mod row_level_security;
 pub mod test_data_source_connections;
 mod utils;
 pub mod values_index;
@@ -0,0 +1,345 @@
+use std::env;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use indexmap::IndexMap;
+use redis::AsyncCommands;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlparser::parser::Parser;
+use tiberius::numeric::Decimal;
+use uuid::Uuid;
+
+use crate::database::{
+    enums::DataSourceType,
+    lib::{get_pg_pool, get_redis_pool},
+    schema::datasets,
+};
+
+use super::{data_types::DataType, utils::get_sql_dialect};
+
+const DEFAULT_CACHE_TTL_SECONDS: i64 = 3600;
+
+/// Results bigger than this are returned but not cached.
+const MAX_CACHED_RESULT_BYTES: usize = 16 * 1024 * 1024;
+
+/// Whether a result came from the query cache, sent alongside the data so clients can show its age.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueryCacheMetadata {
+    pub hit: bool,
+    /// When the cached rows were read from the data source. Only set on a hit.
+    pub cached_at: Option<DateTime<Utc>>,
+}
+
+impl QueryCacheMetadata {
+    pub fn hit(cached_at: DateTime<Utc>) -> Self {
+        QueryCacheMetadata {
+            hit: true,
+            cached_at: Some(cached_at),
+        }
+    }
+
+    pub fn miss() -> Self {
+        QueryCacheMetadata {
+            hit: false,
+            cached_at: None,
+        }
+    }
+}
+
+pub struct CachedQuery {
+    pub cached_at: DateTime<Utc>,
+    pub rows: Vec<IndexMap<String, DataType>>,
+}
+
+/// Builds the cache key for `sql` run against a data source. The SQL is parsed and printed back
+/// out, so formatting, comments and keyword casing don't produce different keys. Returns `None`
+/// when the SQL doesn't parse; those queries aren't cached.
+pub fn query_cache_key(
+    data_source_id: &Uuid,
+    data_source_type: &DataSourceType,
+    sql: &str,
+) -> Option<String> {
+    let dialect = get_sql_dialect(data_source_type);
+
+    let statements = match Parser::parse_sql(dialect.as_ref(), sql) {
+        Ok(statements) => statements,
+        Err(_) => return None,
+    };
+
+    let canonical_sql = statements
+        .iter()
+        .map(|statement| statement.to_string())
+        .collect::<Vec<String>>()
+        .join(";\n");
+
+    let hash = Sha256::digest(canonical_sql.as_bytes());
+
+    Some(format!("query_cache:{}:{:x}", data_source_id, hash))
+}
+
+/// TTL for cached results of a dataset's queries. The dataset's `cache_ttl_seconds` wins over
+/// `QUERY_CACHE_TTL_SECONDS`; zero or less turns caching off.
+pub async fn get_dataset_cache_ttl(dataset_id: &Uuid) -> Result<i64> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting connection from pool: {}", e)),
+    };
+
+    let dataset_ttl = match datasets::table
+        .filter(datasets::id.eq(dataset_id))
+        .select(datasets::cache_ttl_seconds)
+        .first::<Option<i32>>(&mut conn)
+        .await
+    {
+        Ok(dataset_ttl) => dataset_ttl,
+        Err(e) => return Err(anyhow!("Error getting dataset cache ttl: {}", e)),
+    };
+
+    Ok(resolve_cache_ttl(dataset_ttl))
+}
+
+fn resolve_cache_ttl(dataset_ttl: Option<i32>) -> i64 {
+    let ttl = match dataset_ttl {
+        Some(ttl) => ttl as i64,
+        None => env::var("QUERY_CACHE_TTL_SECONDS")
+            .ok()
+            .and_then(|ttl| ttl.parse().ok())
+            .unwrap_or(DEFAULT_CACHE_TTL_SECONDS),
+    };
+
+    ttl.max(0)
+}
+
+pub async fn get_cached_query(cache_key: &str) -> Result<Option<CachedQuery>> {
+    let mut redis_conn = match get_redis_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting redis connection: {}", e)),
+    };
+
+    let cached: Option<String> = match redis_conn.get(cache_key).await {
+        Ok(cached) => cached,
+        Err(e) => return Err(anyhow!("Error reading cached query: {}", e)),
+    };
+
+    let cached = match cached {
+        Some(cached) => cached,
+        None => return Ok(None),
+    };
+
+    match serde_json::from_str::<StoredQuery>(&cached) {
+        Ok(stored) => Ok(Some(CachedQuery {
+            cached_at: stored.cached_at,
+            rows: stored
+                .rows
+                .into_iter()
+                .map(|row| row.into_iter().map(|(k, v)| (k, v.0)).collect())
+                .collect(),
+        })),
+        Err(e) => Err(anyhow!("Error deserializing cached query: {}", e)),
+    }
+}
+
+/// Stores `rows` under `cache_key` and records the key against the dataset so a redeploy can drop
+/// it.
+pub async fn set_cached_query(
+    cache_key: &str,
+    dataset_id: &Uuid,
+    rows: &[IndexMap<String, DataType>],
+    ttl_seconds: i64,
+) -> Result<()> {
+    if ttl_seconds <= 0 {
+        return Ok(());
+    }
+
+    let stored = StoredQueryRef {
+        cached_at: Utc::now(),
+        rows: rows
+            .iter()
+            .map(|row| row.iter().map(|(k, v)| (k.as_str(), DataTypeRef(v))).collect())
+            .collect(),
+    };
+
+    let serialized = match serde_json::to_string(&stored) {
+        Ok(serialized) => serialized,
+        Err(e) => return Err(anyhow!("Error serializing query results: {}", e)),
+    };
+
+    if serialized.len() > MAX_CACHED_RESULT_BYTES {
+        return Ok(());
+    }
+
+    let mut redis_conn = match get_redis_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting redis connection: {}", e)),
+    };
+
+    let index_key = dataset_index_key(dataset_id);
+
+    match redis::pipe()
+        .set_ex(cache_key, serialized, ttl_seconds as u64)
+        .ignore()
+        .sadd(&index_key, cache_key)
+        .ignore()
+        .expire(&index_key, ttl_seconds)
+        .ignore()
+        .query_async::<()>(&mut *redis_conn)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(anyhow!("Error caching query results: {}", e)),
+    }
+}
+
+/// Drops every cached result of the dataset's queries.
+pub async fn invalidate_dataset_query_cache(dataset_id: &Uuid) -> Result<()> {
+    let mut redis_conn = match get_redis_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting redis connection: {}", e)),
+    };
+
+    let index_key = dataset_index_key(dataset_id);
+
+    let mut keys: Vec<String> = match redis_conn.smembers(&index_key).await {
+        Ok(keys) => keys,
+        Err(e) => return Err(anyhow!("Error reading cached query keys: {}", e)),
+    };
+
+    keys.push(index_key);
+
+    match redis_conn.del::<_, ()>(keys).await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(anyhow!("Error invalidating cached queries: {}", e)),
+    }
+}
+
+fn dataset_index_key(dataset_id: &Uuid) -> String {
+    format!("query_cache:dataset:{}", dataset_id)
+}
+
+// `DataType` serializes untagged for API responses, which can't be read back into the same
+// variants. Cached rows use this externally tagged mirror instead so hits keep their types.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "DataType")]
+enum DataTypeDef {
+    Bool(Option<bool>),
+    Bytea(Option<Vec<u8>>),
+    Char(Option<String>),
+    Int8(Option<i64>),
+    Int4(Option<i32>),
+    Int2(Option<i16>),
+    Text(Option<String>),
+    Oid(Option<u32>),
+    Float4(Option<f32>),
+    Float8(Option<f64>),
+    Decimal(Option<Decimal>),
+    Uuid(Option<Uuid>),
+    Timestamp(Option<NaiveDateTime>),
+    Timestamptz(Option<DateTime<Utc>>),
+    Date(Option<NaiveDate>),
+    Time(Option<NaiveTime>),
+    Json(Option<Value>),
+    Unknown(Option<String>),
+    Null,
+}
+
+struct DataTypeRef<'a>(&'a DataType);
+
+impl Serialize for DataTypeRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DataTypeDef::serialize(self.0, serializer)
+    }
+}
+
+struct OwnedDataType(DataType);
+
+impl<'de> Deserialize<'de> for OwnedDataType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        DataTypeDef::deserialize(deserializer).map(OwnedDataType)
+    }
+}
+
+#[derive(Serialize)]
+struct StoredQueryRef<'a> {
+    cached_at: DateTime<Utc>,
+    rows: Vec<IndexMap<&'a str, DataTypeRef<'a>>>,
+}
+
+#[derive(Deserialize)]
+struct StoredQuery {
+    cached_at: DateTime<Utc>,
+    rows: Vec<IndexMap<String, OwnedDataType>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_key_ignores_formatting() {
+        let data_source_id = Uuid::new_v4();
+
+        let key = query_cache_key(
+            &data_source_id,
+            &DataSourceType::Postgres,
+            "SELECT id, amount FROM orders WHERE amount > 10",
+        );
+        let reformatted = query_cache_key(
+            &data_source_id,
+            &DataSourceType::Postgres,
+            "select id,\n    amount\nfrom orders -- big ones\nwhere amount > 10",
+        );
+        let different = query_cache_key(
+            &data_source_id,
+            &DataSourceType::Postgres,
+            "SELECT id, amount FROM orders WHERE amount > 20",
+        );
+
+        assert!(key.is_some());
+        assert_eq!(key, reformatted);
+        assert_ne!(key, different);
+        assert_eq!(
+            query_cache_key(&data_source_id, &DataSourceType::Postgres, "SELEC nope"),
+            None
+        );
+    }
+
+    #[test]
+    fn stored_rows_keep_their_types() {
+        let row: IndexMap<String, DataType> = IndexMap::from([
+            ("id".to_string(), DataType::Int4(Some(1))),
+            (
+                "created_at".to_string(),
+                DataType::Timestamp(Some(
+                    NaiveDate::from_ymd_opt(2024, 1, 1)
+                        .unwrap()
+                        .and_hms_opt(10, 0, 0)
+                        .unwrap(),
+                )),
+            ),
+            ("name".to_string(), DataType::Text(None)),
+        ]);
+
+        let stored = StoredQueryRef {
+            cached_at: Utc::now(),
+            rows: vec![row.iter().map(|(k, v)| (k.as_str(), DataTypeRef(v))).collect()],
+        };
+
+        let serialized = serde_json::to_string(&stored).unwrap();
+        let restored: StoredQuery = serde_json::from_str(&serialized).unwrap();
+
+        for (column, value) in &restored.rows[0] {
+            assert_eq!(value.0.to_string(), row[column].to_string());
+            assert_eq!(&value.0, &row[column]);
+        }
+    }
+
+    #[test]
+    fn dataset_ttl_overrides_default() {
+        assert_eq!(resolve_cache_ttl(Some(60)), 60);
+        assert_eq!(resolve_cache_ttl(Some(-5)), 0);
+    }
+}
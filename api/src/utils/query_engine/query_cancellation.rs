@@ -0,0 +1,359 @@
+use std::{
+    collections::HashMap,
+    env,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use serde_json::{Map, Value};
+use sqlx::{MySql, Pool, Postgres};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::database::models::DataSource;
+
+use super::{
+    credentials::SnowflakeCredentials,
+    data_source_connections::{
+        get_clickhouse_client::ClickHouse, get_databricks_client::Databricks,
+        get_snowflake_client::get_snowflake_client, get_trino_client::Trino,
+    },
+};
+
+const DEFAULT_MAX_EXECUTION_TIME_SECONDS: i64 = 600;
+
+/// How long a stopped query gets to unwind after the data source has been told to cancel it, so
+/// pooled connections go back to the pool in a clean state.
+const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+static RUNNING_QUERIES: Lazy<Mutex<HashMap<Uuid, RunningQuery>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct RunningQuery {
+    user_id: Option<Uuid>,
+    cancel_token: CancellationToken,
+}
+
+/// Identifies a query so it can be cancelled while it runs. Queries started on behalf of a user
+/// can only be cancelled by that user; background queries can't be cancelled from the API.
+#[derive(Debug, Clone)]
+pub struct QueryContext {
+    pub query_id: Uuid,
+    pub user_id: Option<Uuid>,
+    /// Runs the query as if the user had these attributes instead of their own. Callers must
+    /// check the user is allowed to preview.
+    pub preview_attributes: Option<Map<String, Value>>,
+}
+
+impl QueryContext {
+    pub fn new() -> Self {
+        QueryContext {
+            query_id: Uuid::new_v4(),
+            user_id: None,
+            preview_attributes: None,
+        }
+    }
+
+    /// Uses the id the client sent, if any, so it can cancel the query before the results arrive.
+    pub fn for_user(query_id: Option<Uuid>, user_id: &Uuid) -> Self {
+        QueryContext {
+            query_id: query_id.unwrap_or_else(Uuid::new_v4),
+            user_id: Some(*user_id),
+            preview_attributes: None,
+        }
+    }
+
+    pub fn with_preview_attributes(mut self, attributes: Option<Map<String, Value>>) -> Self {
+        self.preview_attributes = attributes;
+        self
+    }
+}
+
+impl Default for QueryContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How to stop a query on the data source itself. Dropping the future only stops us waiting on
+/// the results; without this the warehouse keeps running the query.
+pub enum NativeCancel {
+    /// Postgres, Supabase and Redshift: `pg_cancel_backend` on the backend running the query.
+    Postgres {
+        pool: Pool<Postgres>,
+        backend_pid: i32,
+    },
+    /// MySQL, MariaDB and StarRocks: `KILL QUERY` on the connection running the query.
+    MySql {
+        pool: Pool<MySql>,
+        connection_id: u64,
+    },
+    ClickHouse {
+        client: ClickHouse,
+        query_id: String,
+    },
+    /// Cancelled from a second session, since the running session is busy with the query.
+    Snowflake {
+        credentials: SnowflakeCredentials,
+        session_id: String,
+    },
+    BigQuery {
+        client: gcp_bigquery_client::Client,
+        project_id: String,
+        job_id: String,
+        location: Option<String>,
+    },
+    Databricks {
+        client: Databricks,
+        statement_id: String,
+    },
+    Trino {
+        client: Trino,
+        query_id: String,
+    },
+    DuckDb {
+        interrupt_handle: Arc<duckdb::InterruptHandle>,
+    },
+}
+
+impl NativeCancel {
+    async fn cancel(self) -> Result<()> {
+        match self {
+            NativeCancel::Postgres { pool, backend_pid } => {
+                sqlx::query("SELECT pg_cancel_backend($1)")
+                    .bind(backend_pid)
+                    .execute(&pool)
+                    .await?;
+            }
+            NativeCancel::MySql {
+                pool,
+                connection_id,
+            } => {
+                sqlx::query(&format!("KILL QUERY {}", connection_id))
+                    .execute(&pool)
+                    .await?;
+            }
+            NativeCancel::ClickHouse { client, query_id } => {
+                client.kill_query(&query_id).await?;
+            }
+            NativeCancel::Snowflake {
+                credentials,
+                session_id,
+            } => {
+                let snowflake_client = get_snowflake_client(&credentials).await?;
+                snowflake_client
+                    .exec(&format!("SELECT SYSTEM$CANCEL_ALL_QUERIES({})", session_id))
+                    .await?;
+            }
+            NativeCancel::BigQuery {
+                client,
+                project_id,
+                job_id,
+                location,
+            } => {
+                client
+                    .job()
+                    .cancel_job(&project_id, &job_id, location.as_deref())
+                    .await?;
+            }
+            NativeCancel::Databricks {
+                client,
+                statement_id,
+            } => {
+                client.cancel_statement(&statement_id).await?;
+            }
+            NativeCancel::Trino { client, query_id } => {
+                client.cancel(&query_id).await?;
+            }
+            NativeCancel::DuckDb { interrupt_handle } => {
+                interrupt_handle.interrupt();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A query in the cancellation registry. It is removed from the registry when dropped.
+pub struct QueryHandle {
+    query_id: Uuid,
+    cancel_token: CancellationToken,
+    native_cancel: Mutex<Option<NativeCancel>>,
+}
+
+impl QueryHandle {
+    pub fn query_id(&self) -> &Uuid {
+        &self.query_id
+    }
+
+    /// Called by the data source routes once they know how to stop the query on the server.
+    pub fn set_native_cancel(&self, native_cancel: NativeCancel) {
+        if let Ok(mut current) = self.native_cancel.lock() {
+            *current = Some(native_cancel);
+        }
+    }
+
+    /// Runs `query` until it finishes, is cancelled, or exceeds `max_execution_time`. A stopped
+    /// query is cancelled on the data source before this returns.
+    pub async fn run<T, F>(&self, max_execution_time: Duration, query: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        tokio::pin!(query);
+
+        let reason = tokio::select! {
+            result = &mut query => return result,
+            _ = self.cancel_token.cancelled() => anyhow!("Query was cancelled"),
+            _ = tokio::time::sleep(max_execution_time) => anyhow!(
+                "Query exceeded the maximum execution time of {} seconds",
+                max_execution_time.as_secs()
+            ),
+        };
+
+        let native_cancel = match self.native_cancel.lock() {
+            Ok(mut native_cancel) => native_cancel.take(),
+            Err(_) => None,
+        };
+
+        if let Some(native_cancel) = native_cancel {
+            if let Err(e) = native_cancel.cancel().await {
+                tracing::error!("Unable to cancel query {} on the data source: {}", self.query_id, e);
+            }
+        }
+
+        let _ = tokio::time::timeout(CANCEL_GRACE_PERIOD, &mut query).await;
+
+        Err(reason)
+    }
+}
+
+impl Drop for QueryHandle {
+    fn drop(&mut self) {
+        if let Ok(mut running_queries) = RUNNING_QUERIES.lock() {
+            running_queries.remove(&self.query_id);
+        }
+    }
+}
+
+/// Adds a query to the cancellation registry. Fails if a query with the same id is already running.
+pub fn register_query(context: &QueryContext) -> Result<QueryHandle> {
+    let mut running_queries = match RUNNING_QUERIES.lock() {
+        Ok(running_queries) => running_queries,
+        Err(e) => return Err(anyhow!("Error locking query registry: {}", e)),
+    };
+
+    if running_queries.contains_key(&context.query_id) {
+        return Err(anyhow!("Query {} is already running", context.query_id));
+    }
+
+    let cancel_token = CancellationToken::new();
+
+    running_queries.insert(
+        context.query_id,
+        RunningQuery {
+            user_id: context.user_id,
+            cancel_token: cancel_token.clone(),
+        },
+    );
+
+    Ok(QueryHandle {
+        query_id: context.query_id,
+        cancel_token,
+        native_cancel: Mutex::new(None),
+    })
+}
+
+/// Cancels a running query started by `user_id`. The query stops on the data source in the
+/// background; this returns as soon as the cancellation has been requested.
+pub fn cancel_query(query_id: &Uuid, user_id: &Uuid) -> Result<()> {
+    let running_queries = match RUNNING_QUERIES.lock() {
+        Ok(running_queries) => running_queries,
+        Err(e) => return Err(anyhow!("Error locking query registry: {}", e)),
+    };
+
+    match running_queries.get(query_id) {
+        Some(running_query) if running_query.user_id.as_ref() == Some(user_id) => {
+            running_query.cancel_token.cancel();
+            Ok(())
+        }
+        _ => Err(anyhow!("Query not found")),
+    }
+}
+
+/// The data source's `max_execution_time_seconds` wins over `QUERY_MAX_EXECUTION_TIME_SECONDS`.
+pub fn max_execution_time(data_source: &DataSource) -> Duration {
+    resolve_max_execution_time(data_source.max_execution_time_seconds)
+}
+
+fn resolve_max_execution_time(data_source_max: Option<i32>) -> Duration {
+    let seconds = match data_source_max {
+        Some(seconds) if seconds > 0 => seconds as i64,
+        _ => env::var("QUERY_MAX_EXECUTION_TIME_SECONDS")
+            .ok()
+            .and_then(|seconds| seconds.parse().ok())
+            .filter(|seconds: &i64| *seconds > 0)
+            .unwrap_or(DEFAULT_MAX_EXECUTION_TIME_SECONDS),
+    };
+
+    Duration::from_secs(seconds as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cancelled_queries_stop_and_leave_the_registry() {
+        let user_id = Uuid::new_v4();
+        let context = QueryContext::for_user(None, &user_id);
+        let query_id = context.query_id;
+
+        let handle = register_query(&context).unwrap();
+        assert!(register_query(&context).is_err());
+        assert!(cancel_query(&query_id, &Uuid::new_v4()).is_err());
+
+        let canceller = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            cancel_query(&query_id, &user_id)
+        });
+
+        let result = handle
+            .run(Duration::from_secs(60), async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            })
+            .await;
+
+        assert!(canceller.await.unwrap().is_ok());
+        assert_eq!(result.unwrap_err().to_string(), "Query was cancelled");
+
+        drop(handle);
+        assert!(cancel_query(&query_id, &user_id).is_err());
+    }
+
+    #[tokio::test]
+    async fn queries_time_out() {
+        let handle = register_query(&QueryContext::new()).unwrap();
+
+        let result = handle
+            .run(Duration::from_millis(20), async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            })
+            .await;
+
+        assert!(result.unwrap_err().to_string().contains("maximum execution time"));
+    }
+
+    #[test]
+    fn data_source_max_execution_time_overrides_default() {
+        assert_eq!(resolve_max_execution_time(Some(30)), Duration::from_secs(30));
+        assert_eq!(
+            resolve_max_execution_time(Some(0)),
+            resolve_max_execution_time(None)
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use diesel::{BoolExpressionMethods, ExpressionMethods, JoinOnDsl, QueryDsl};
 use diesel_async::RunQueryDsl;
+use futures::StreamExt;
 use indexmap::IndexMap;
 use uuid::Uuid;
 
@@ -9,31 +10,205 @@ use crate::database::lib::get_pg_pool;
 use crate::database::models::DataSource;
 use crate::database::schema::{data_sources, users_to_organizations};
 
-use super::data_source_query_routes::query_router::query_router;
+use super::arrow_conversion::RecordBatchStream;
+use super::data_source_query_routes::query_router::{query_router, query_router_stream};
 use super::data_types::DataType;
+use super::query_cache::{
+    get_cached_query, get_dataset_cache_ttl, query_cache_key, set_cached_query,
+    QueryCacheMetadata,
+};
+use super::query_cancellation::QueryContext;
+use super::query_pagination::{start_paged_query, QueryPage};
+use super::row_level_security::apply_user_dataset_policies;
 
+/// Runs a query for a user, applying the dataset row policies that bind them.
 pub async fn query_engine(
     dataset_id: &Uuid,
     sql: &String,
+    user_id: &Uuid,
 ) -> Result<Vec<IndexMap<String, DataType>>> {
+    let (results, _) =
+        cached_query_engine(dataset_id, sql, &QueryContext::for_user(None, user_id)).await?;
+
+    Ok(results)
+}
+
+/// Runs a read-only query against the dataset's data source, skipping the query cache.
+pub async fn uncached_query_engine(
+    dataset_id: &Uuid,
+    sql: &String,
+) -> Result<Vec<IndexMap<String, DataType>>> {
+    let data_source = match DataSource::find_by_dataset_id(dataset_id).await? {
+        Some(data_source) => data_source,
+        None => return Err(anyhow::anyhow!("Data source not found")),
+    };
+
+    query_router(&data_source, sql, None, false, &QueryContext::new()).await
+}
+
+/// Runs a read-only query against the dataset's data source, serving it from the query cache when
+/// an unexpired result exists. Cache errors are logged and the query falls through to the data
+/// source. When the context has a user, the row policies that bind them are applied first, so the
+/// cache key covers the filtered query.
+pub async fn cached_query_engine(
+    dataset_id: &Uuid,
+    sql: &String,
+    context: &QueryContext,
+) -> Result<(Vec<IndexMap<String, DataType>>, QueryCacheMetadata)> {
     let data_source = match DataSource::find_by_dataset_id(dataset_id).await? {
         Some(data_source) => data_source,
         None => return Err(anyhow::anyhow!("Data source not found")),
     };
 
-    let results = match query_router(&data_source, sql, None, false).await {
+    let (sql, masking_key) = apply_user_dataset_policies(&data_source, sql, context).await?;
+    let sql = &sql;
+
+    let cache_ttl = match get_dataset_cache_ttl(dataset_id).await {
+        Ok(cache_ttl) => cache_ttl,
+        Err(e) => {
+            tracing::warn!("Unable to get query cache ttl: {}", e);
+            0
+        }
+    };
+
+    let cache_key = if cache_ttl > 0 {
+        query_cache_key(&data_source.id, &data_source.type_, sql)
+    } else {
+        None
+    };
+
+    if let Some(cache_key) = &cache_key {
+        match get_cached_query(cache_key).await {
+            Ok(Some(cached)) => return Ok((cached.rows, QueryCacheMetadata::hit(cached.cached_at))),
+            Ok(None) => (),
+            Err(e) => tracing::warn!("Unable to read query cache: {}", e),
+        }
+    }
+
+    let results = match query_router(&data_source, sql, None, false, context).await {
         Ok(results) => results,
         Err(e) => return Err(e),
     };
 
-    Ok(results)
+    let results = match &masking_key {
+        Some(masking_key) => masking_key.mask_rows(results),
+        None => results,
+    };
+
+    if let Some(cache_key) = &cache_key {
+        if let Err(e) = set_cached_query(cache_key, dataset_id, &results, cache_ttl).await {
+            tracing::warn!("Unable to write query cache: {}", e);
+        }
+    }
+
+    Ok((results, QueryCacheMetadata::miss()))
+}
+
+pub async fn query_engine_stream(
+    dataset_id: &Uuid,
+    sql: &String,
+    context: &QueryContext,
+) -> Result<RecordBatchStream> {
+    let data_source = match DataSource::find_by_dataset_id(dataset_id).await? {
+        Some(data_source) => data_source,
+        None => return Err(anyhow::anyhow!("Data source not found")),
+    };
+
+    let (sql, masking_key) = apply_user_dataset_policies(&data_source, sql, context).await?;
+
+    let stream = query_router_stream(&data_source, &sql, None, context).await?;
+
+    match masking_key {
+        Some(masking_key) => {
+            Ok(Box::pin(stream.map(move |batch| {
+                batch.and_then(|batch| masking_key.mask_record_batch(batch))
+            })))
+        }
+        None => Ok(stream),
+    }
+}
+
+/// Paged counterpart of `cached_query_engine`. Pages are read from the data source, not the query
+/// cache.
+pub async fn paged_query_engine(
+    dataset_id: &Uuid,
+    sql: &String,
+    page_size: Option<i64>,
+    context: &QueryContext,
+) -> Result<QueryPage> {
+    let data_source = match DataSource::find_by_dataset_id(dataset_id).await? {
+        Some(data_source) => data_source,
+        None => return Err(anyhow::anyhow!("Data source not found")),
+    };
+
+    let (sql, masking_key) = apply_user_dataset_policies(&data_source, sql, context).await?;
+
+    start_paged_query(data_source, &sql, page_size, masking_key, context).await
 }
 
+/// Runs against the data source directly for workspace and data admins building datasets, so
+/// dataset row policies don't apply.
 pub async fn modeling_query_engine(
     data_source_id: &Uuid,
     sql: &String,
     user_id: &Uuid,
+    query_id: Option<Uuid>,
 ) -> Result<Vec<IndexMap<String, DataType>>> {
+    check_modeling_access(data_source_id, user_id).await?;
+
+    let data_source = match DataSource::find_by_id(data_source_id).await? {
+        Some(data_source) => data_source,
+        None => return Err(anyhow::anyhow!("Data source not found")),
+    };
+
+    let context = QueryContext::for_user(query_id, user_id);
+
+    let results = match query_router(&data_source, sql, Some(25), false, &context).await {
+        Ok(results) => results,
+        Err(e) => return Err(e),
+    };
+
+    Ok(results)
+}
+
+pub async fn modeling_query_engine_stream(
+    data_source_id: &Uuid,
+    sql: &String,
+    user_id: &Uuid,
+    query_id: Option<Uuid>,
+) -> Result<RecordBatchStream> {
+    check_modeling_access(data_source_id, user_id).await?;
+
+    let data_source = match DataSource::find_by_id(data_source_id).await? {
+        Some(data_source) => data_source,
+        None => return Err(anyhow::anyhow!("Data source not found")),
+    };
+
+    let context = QueryContext::for_user(query_id, user_id);
+
+    query_router_stream(&data_source, sql, Some(25), &context).await
+}
+
+pub async fn modeling_paged_query_engine(
+    data_source_id: &Uuid,
+    sql: &String,
+    user_id: &Uuid,
+    page_size: Option<i64>,
+    query_id: Option<Uuid>,
+) -> Result<QueryPage> {
+    check_modeling_access(data_source_id, user_id).await?;
+
+    let data_source = match DataSource::find_by_id(data_source_id).await? {
+        Some(data_source) => data_source,
+        None => return Err(anyhow::anyhow!("Data source not found")),
+    };
+
+    let context = QueryContext::for_user(query_id, user_id);
+
+    start_paged_query(data_source, sql, page_size, None, &context).await
+}
+
+async fn check_modeling_access(data_source_id: &Uuid, user_id: &Uuid) -> Result<()> {
     let mut conn = match get_pg_pool().get().await {
         Ok(conn) => conn,
         Err(e) => {
@@ -67,15 +242,5 @@ pub async fn modeling_query_engine(
         ));
     }
 
-    let data_source = match DataSource::find_by_id(data_source_id).await? {
-        Some(data_source) => data_source,
-        None => return Err(anyhow::anyhow!("Data source not found")),
-    };
-
-    let results = match query_router(&data_source, sql, Some(25), false).await {
-        Ok(results) => results,
-        Err(e) => return Err(e),
-    };
-
-    Ok(results)
+    Ok(())
 }
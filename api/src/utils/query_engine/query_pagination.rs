@@ -0,0 +1,470 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use sqlparser::ast::{SetExpr, Statement};
+use sqlparser::parser::Parser;
+use uuid::Uuid;
+
+use crate::database::{enums::DataSourceType, models::DataSource};
+
+use super::{
+    column_level_security::MaskingKey,
+    data_source_query_routes::{
+        postgres_query::PostgresCursor,
+        query_router::{query_router_declare_cursor, query_router_page},
+    },
+    data_types::DataType,
+    query_cancellation::{max_execution_time, register_query, QueryContext},
+    utils::get_sql_dialect,
+};
+
+pub const DEFAULT_PAGE_SIZE: i64 = 500;
+
+/// Stays under the 5000 row cap of the MySQL route, since one extra row is read per page to tell
+/// whether another page follows.
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+/// Paged queries that haven't been read from for this long are dropped and their cursors closed.
+const PAGED_QUERY_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Each cursor holds a pooled connection, so only a few per data source are kept open. Paged
+/// queries over the limit fall back to offset paging.
+const MAX_CURSORS_PER_DATA_SOURCE: usize = 2;
+
+static PAGED_QUERIES: Lazy<Mutex<HashMap<Uuid, PagedQuery>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static SWEEPER: Lazy<()> = Lazy::new(|| {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            evict_idle_paged_queries();
+        }
+    });
+});
+
+#[derive(Clone)]
+struct PagedQuery {
+    user_id: Option<Uuid>,
+    data_source: DataSource,
+    sql: String,
+    page_size: i64,
+    masking_key: Option<MaskingKey>,
+    has_cursor: bool,
+    state: Arc<tokio::sync::Mutex<PageState>>,
+}
+
+struct PageState {
+    total_rows: Option<i64>,
+    cursor: Option<PostgresCursor>,
+    last_used: Instant,
+}
+
+/// One page of a paged query. `query_handle` and `next_cursor` are used to fetch the pages after it.
+#[derive(Debug, Serialize)]
+pub struct QueryPage {
+    pub query_handle: Uuid,
+    pub rows: Vec<IndexMap<String, DataType>>,
+    pub offset: i64,
+    pub page_size: i64,
+    /// Known up front when the data source has server-side cursors, otherwise once the last page
+    /// has been read.
+    pub total_rows: Option<i64>,
+    pub has_more: bool,
+    pub next_cursor: Option<String>,
+}
+
+/// Starts a paged query and returns its first page. Postgres and Supabase page through a
+/// server-side cursor; every other data source re-runs the query with an offset per page. Every
+/// page is masked with the masking key, if there is one.
+pub async fn start_paged_query(
+    data_source: DataSource,
+    sql: &String,
+    page_size: Option<i64>,
+    masking_key: Option<MaskingKey>,
+    context: &QueryContext,
+) -> Result<QueryPage> {
+    Lazy::force(&SWEEPER);
+
+    let page_size = resolve_page_size(page_size)?;
+    let query_handle = Uuid::new_v4();
+
+    let cursor = if cursor_available(&data_source.id)? {
+        query_router_declare_cursor(&data_source, sql, cursor_name(&query_handle), context).await?
+    } else {
+        None
+    };
+
+    let (cursor, total_rows) = match cursor {
+        Some((cursor, total_rows)) => (Some(cursor), Some(total_rows)),
+        None => (None, None),
+    };
+
+    let paged_query = PagedQuery {
+        user_id: context.user_id,
+        data_source,
+        sql: sql.clone(),
+        page_size,
+        masking_key,
+        has_cursor: cursor.is_some(),
+        state: Arc::new(tokio::sync::Mutex::new(PageState {
+            total_rows,
+            cursor,
+            last_used: Instant::now(),
+        })),
+    };
+
+    match PAGED_QUERIES.lock() {
+        Ok(mut paged_queries) => paged_queries.insert(query_handle, paged_query.clone()),
+        Err(e) => return Err(anyhow!("Error locking paged queries: {}", e)),
+    };
+
+    let page = fetch_page(query_handle, &paged_query, 0, context).await;
+
+    if page.is_err() {
+        remove_paged_query(&query_handle);
+    }
+
+    page
+}
+
+/// Fetches the page of a paged query that starts `offset` rows into its results.
+pub async fn get_query_page(
+    query_handle: &Uuid,
+    offset: i64,
+    context: &QueryContext,
+) -> Result<QueryPage> {
+    if offset < 0 {
+        return Err(anyhow!("Offset must not be negative"));
+    }
+
+    let paged_query = find_paged_query(query_handle, context.user_id)?;
+
+    fetch_page(*query_handle, &paged_query, offset, context).await
+}
+
+/// Fetches the page a `next_cursor` from an earlier page points at.
+pub async fn get_query_page_at_cursor(cursor: &str, context: &QueryContext) -> Result<QueryPage> {
+    let (query_handle, offset) = decode_cursor(cursor)?;
+
+    get_query_page(&query_handle, offset, context).await
+}
+
+/// Drops a paged query before it idles out, closing its cursor.
+pub fn close_paged_query(query_handle: &Uuid, user_id: &Uuid) -> Result<()> {
+    find_paged_query(query_handle, Some(*user_id))?;
+    remove_paged_query(query_handle);
+
+    Ok(())
+}
+
+async fn fetch_page(
+    query_handle: Uuid,
+    paged_query: &PagedQuery,
+    offset: i64,
+    context: &QueryContext,
+) -> Result<QueryPage> {
+    let mut state = paged_query.state.lock().await;
+    state.last_used = Instant::now();
+
+    let page_size = paged_query.page_size;
+
+    // One row past the page tells us whether another page follows.
+    let mut rows = match state.cursor.as_mut() {
+        Some(cursor) => {
+            let query = register_query(context)?;
+            query
+                .run(
+                    max_execution_time(&paged_query.data_source),
+                    cursor.fetch(offset, page_size + 1, &query),
+                )
+                .await?
+        }
+        None => {
+            query_router_page(
+                &paged_query.data_source,
+                &paged_query.sql,
+                offset,
+                page_size + 1,
+                context,
+            )
+            .await?
+        }
+    };
+
+    let has_more = rows.len() as i64 > page_size;
+    rows.truncate(page_size as usize);
+
+    if let Some(masking_key) = &paged_query.masking_key {
+        rows = masking_key.mask_rows(rows);
+    }
+
+    if state.total_rows.is_none() && !has_more && (offset == 0 || !rows.is_empty()) {
+        state.total_rows = Some(offset + rows.len() as i64);
+    }
+
+    state.last_used = Instant::now();
+
+    Ok(QueryPage {
+        query_handle,
+        offset,
+        page_size,
+        total_rows: state.total_rows,
+        has_more,
+        next_cursor: if has_more {
+            Some(encode_cursor(&query_handle, offset + rows.len() as i64))
+        } else {
+            None
+        },
+        rows,
+    })
+}
+
+/// Builds the SQL for one page of `sql`. Queries without a row limit of their own get the paging
+/// clause appended, so their `ORDER BY` still decides what lands on each page. Anything else is
+/// wrapped in a subquery first.
+pub fn page_sql(
+    sql: &str,
+    data_source_type: &DataSourceType,
+    offset: i64,
+    limit: i64,
+) -> Result<String> {
+    let sql = sql
+        .trim()
+        .trim_end_matches(|c: char| c == ';' || c.is_whitespace());
+
+    let dialect = get_sql_dialect(data_source_type);
+    let statements = Parser::parse_sql(dialect.as_ref(), sql)?;
+
+    let query = match statements.as_slice() {
+        [Statement::Query(query)] => query,
+        _ => return Err(anyhow!("Only a single SELECT query can be paged")),
+    };
+
+    let has_top = matches!(query.body.as_ref(), SetExpr::Select(select) if select.top.is_some());
+
+    let appendable = query.limit.is_none()
+        && query.limit_by.is_empty()
+        && query.offset.is_none()
+        && query.fetch.is_none()
+        && query.for_clause.is_none()
+        && query.settings.is_none()
+        && query.format_clause.is_none()
+        && !has_top;
+
+    // Newlines keep a trailing line comment from swallowing the paging clause.
+    let (body, ordered) = if appendable {
+        (sql.to_string(), query.order_by.is_some())
+    } else {
+        (format!("SELECT * FROM (\n{}\n) AS buster_page", sql), false)
+    };
+
+    let page_sql = match data_source_type {
+        DataSourceType::SqlServer => {
+            // OFFSET ... FETCH is only allowed after an ORDER BY.
+            let order_by = if ordered {
+                ""
+            } else {
+                "\nORDER BY (SELECT NULL)"
+            };
+            format!(
+                "{}{}\nOFFSET {} ROWS FETCH NEXT {} ROWS ONLY",
+                body, order_by, offset, limit
+            )
+        }
+        // Trino wants the OFFSET before the LIMIT.
+        DataSourceType::Trino => format!("{}\nOFFSET {} LIMIT {}", body, offset, limit),
+        _ => format!("{}\nLIMIT {} OFFSET {}", body, limit, offset),
+    };
+
+    Ok(page_sql)
+}
+
+fn resolve_page_size(page_size: Option<i64>) -> Result<i64> {
+    match page_size {
+        None => Ok(DEFAULT_PAGE_SIZE),
+        Some(page_size) if page_size > 0 && page_size <= MAX_PAGE_SIZE => Ok(page_size),
+        Some(_) => Err(anyhow!("Page size must be between 1 and {}", MAX_PAGE_SIZE)),
+    }
+}
+
+fn cursor_name(query_handle: &Uuid) -> String {
+    format!("buster_page_{}", query_handle.simple())
+}
+
+fn encode_cursor(query_handle: &Uuid, offset: i64) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}:{}", query_handle, offset))
+}
+
+fn decode_cursor(cursor: &str) -> Result<(Uuid, i64)> {
+    let decoded = match URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+    {
+        Some(decoded) => decoded,
+        None => return Err(anyhow!("Invalid cursor")),
+    };
+
+    let (query_handle, offset) = match decoded.split_once(':') {
+        Some(parts) => parts,
+        None => return Err(anyhow!("Invalid cursor")),
+    };
+
+    match (Uuid::parse_str(query_handle), offset.parse::<i64>()) {
+        (Ok(query_handle), Ok(offset)) if offset >= 0 => Ok((query_handle, offset)),
+        _ => Err(anyhow!("Invalid cursor")),
+    }
+}
+
+fn cursor_available(data_source_id: &Uuid) -> Result<bool> {
+    let paged_queries = match PAGED_QUERIES.lock() {
+        Ok(paged_queries) => paged_queries,
+        Err(e) => return Err(anyhow!("Error locking paged queries: {}", e)),
+    };
+
+    let open_cursors = paged_queries
+        .values()
+        .filter(|paged_query| {
+            paged_query.has_cursor && paged_query.data_source.id == *data_source_id
+        })
+        .count();
+
+    Ok(open_cursors < MAX_CURSORS_PER_DATA_SOURCE)
+}
+
+/// Paged queries can only be read by the user that started them.
+fn find_paged_query(query_handle: &Uuid, user_id: Option<Uuid>) -> Result<PagedQuery> {
+    let paged_queries = match PAGED_QUERIES.lock() {
+        Ok(paged_queries) => paged_queries,
+        Err(e) => return Err(anyhow!("Error locking paged queries: {}", e)),
+    };
+
+    match paged_queries.get(query_handle) {
+        Some(paged_query) if user_id.is_some() && paged_query.user_id == user_id => {
+            Ok(paged_query.clone())
+        }
+        _ => Err(anyhow!("Query handle not found")),
+    }
+}
+
+fn remove_paged_query(query_handle: &Uuid) {
+    let removed = match PAGED_QUERIES.lock() {
+        Ok(mut paged_queries) => paged_queries.remove(query_handle),
+        Err(_) => None,
+    };
+
+    if let Some(paged_query) = removed {
+        tokio::spawn(async move {
+            if let Some(cursor) = paged_query.state.lock().await.cursor.take() {
+                cursor.close().await;
+            }
+        });
+    }
+}
+
+fn evict_idle_paged_queries() {
+    let evicted = match PAGED_QUERIES.lock() {
+        Ok(mut paged_queries) => {
+            // Paged queries in the middle of a fetch are locked and never idle.
+            let evicted_handles = paged_queries
+                .iter()
+                .filter_map(
+                    |(query_handle, paged_query)| match paged_query.state.try_lock() {
+                        Ok(state) if state.last_used.elapsed() > PAGED_QUERY_IDLE_TIMEOUT => {
+                            Some(*query_handle)
+                        }
+                        _ => None,
+                    },
+                )
+                .collect::<Vec<Uuid>>();
+
+            evicted_handles
+                .iter()
+                .filter_map(|query_handle| paged_queries.remove(query_handle))
+                .collect::<Vec<PagedQuery>>()
+        }
+        Err(_) => return,
+    };
+
+    for paged_query in evicted {
+        tokio::spawn(async move {
+            if let Some(cursor) = paged_query.state.lock().await.cursor.take() {
+                cursor.close().await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_the_paging_clause_to_ordered_queries() {
+        let sql = "SELECT id, name FROM users ORDER BY name;";
+
+        assert_eq!(
+            page_sql(sql, &DataSourceType::Postgres, 500, 501).unwrap(),
+            "SELECT id, name FROM users ORDER BY name\nLIMIT 501 OFFSET 500"
+        );
+        assert_eq!(
+            page_sql(sql, &DataSourceType::Trino, 500, 501).unwrap(),
+            "SELECT id, name FROM users ORDER BY name\nOFFSET 500 LIMIT 501"
+        );
+        assert_eq!(
+            page_sql(sql, &DataSourceType::SqlServer, 500, 501).unwrap(),
+            "SELECT id, name FROM users ORDER BY name\nOFFSET 500 ROWS FETCH NEXT 501 ROWS ONLY"
+        );
+    }
+
+    #[test]
+    fn wraps_queries_that_limit_themselves() {
+        assert_eq!(
+            page_sql(
+                "SELECT id FROM users LIMIT 10",
+                &DataSourceType::Snowflake,
+                0,
+                5
+            )
+            .unwrap(),
+            "SELECT * FROM (\nSELECT id FROM users LIMIT 10\n) AS buster_page\nLIMIT 5 OFFSET 0"
+        );
+        assert_eq!(
+            page_sql("SELECT TOP 10 id FROM users", &DataSourceType::SqlServer, 0, 5).unwrap(),
+            "SELECT * FROM (\nSELECT TOP 10 id FROM users\n) AS buster_page\nORDER BY (SELECT NULL)\nOFFSET 0 ROWS FETCH NEXT 5 ROWS ONLY"
+        );
+    }
+
+    #[test]
+    fn rejects_statements_that_are_not_queries() {
+        assert!(page_sql("DELETE FROM users", &DataSourceType::Postgres, 0, 5).is_err());
+    }
+
+    #[test]
+    fn cursors_round_trip() {
+        let query_handle = Uuid::new_v4();
+        let cursor = encode_cursor(&query_handle, 1000);
+
+        assert_eq!(decode_cursor(&cursor).unwrap(), (query_handle, 1000));
+        assert!(decode_cursor("not a cursor").is_err());
+    }
+
+    #[test]
+    fn page_sizes_are_bounded() {
+        assert_eq!(resolve_page_size(None).unwrap(), DEFAULT_PAGE_SIZE);
+        assert_eq!(resolve_page_size(Some(50)).unwrap(), 50);
+        assert!(resolve_page_size(Some(0)).is_err());
+        assert!(resolve_page_size(Some(MAX_PAGE_SIZE + 1)).is_err());
+    }
+}
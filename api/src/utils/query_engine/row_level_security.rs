@@ -0,0 +1,1121 @@
+use std::{collections::HashSet, ops::ControlFlow};
+
+use anyhow::{anyhow, Result};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, JoinOnDsl, QueryDsl, TextExpressionMethods,
+};
+use diesel_async::RunQueryDsl;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde_json::{Map, Value};
+use sqlparser::ast::{
+    visit_relations, ObjectName, Query, SetExpr, Statement, TableFactor, VisitMut, VisitorMut,
+};
+use sqlparser::dialect::Dialect;
+use sqlparser::parser::Parser;
+use uuid::Uuid;
+
+use crate::database::{
+    enums::{IdentityType, RowPolicyIdentityType},
+    lib::get_pg_pool,
+    models::DataSource,
+    schema::{
+        dataset_row_policies, dataset_row_policies_to_identities, datasets,
+        permission_groups_to_identities, teams_to_users, users,
+    },
+};
+
+use crate::utils::user::user_attributes::get_user_attributes;
+
+use super::{
+    column_level_security::{get_user_column_projections, MaskingKey},
+    query_cancellation::QueryContext,
+    utils::get_sql_dialect,
+};
+
+/// Used in place of a policy that can't be filled in for a user, e.g. because an attribute it
+/// refers to isn't set. It matches no rows and is valid in every dialect we query.
+const DENY_ALL_FILTER: &str = "1 = 0";
+
+static TEMPLATE_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{\s*user\.([A-Za-z0-9_.]+)\s*\}\}").unwrap());
+
+/// The values a policy filter or templated SQL can refer to.
+#[derive(Debug, Clone)]
+pub struct TemplateUser {
+    pub id: Uuid,
+    pub email: String,
+    pub attributes: Value,
+}
+
+/// Loads what templates are filled in with for a user. Previewing replaces the user's attributes
+/// with the given ones.
+pub async fn get_template_user(
+    user_id: &Uuid,
+    preview_attributes: Option<&Map<String, Value>>,
+) -> Result<TemplateUser> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let email = match users::table
+        .filter(users::id.eq(user_id))
+        .select(users::email)
+        .first::<String>(&mut conn)
+        .await
+    {
+        Ok(email) => email,
+        Err(e) => return Err(anyhow!("Error getting user: {}", e)),
+    };
+
+    let attributes = match preview_attributes {
+        Some(preview_attributes) => preview_attributes.clone(),
+        None => get_user_attributes(user_id).await?,
+    };
+
+    Ok(TemplateUser {
+        id: *user_id,
+        email,
+        attributes: Value::Object(attributes),
+    })
+}
+
+/// What a user may read from one table. A row is visible if it matches any of the filters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TablePolicy {
+    pub schema: String,
+    pub table: String,
+    pub filters: Vec<String>,
+    /// The select list used instead of `*` when some columns are hidden or masked from the user.
+    pub columns: Option<Vec<String>>,
+    /// The dataset's SQL filled in for the user, read instead of the table when the dataset's
+    /// definition is templated.
+    pub source: Option<String>,
+}
+
+impl TablePolicy {
+    fn predicate(&self) -> Option<String> {
+        if self.filters.is_empty() {
+            return None;
+        }
+
+        Some(
+            self.filters
+                .iter()
+                .map(|filter| format!("({})", filter))
+                .collect::<Vec<String>>()
+                .join(" OR "),
+        )
+    }
+
+    fn projection(&self) -> String {
+        match &self.columns {
+            Some(columns) => columns.join(", "),
+            None => "*".to_string(),
+        }
+    }
+
+    /// The subquery the table is replaced with.
+    fn subquery(&self, name: &ObjectName) -> String {
+        let name = match &self.source {
+            Some(source) => format!("({}) AS {}", source, self.table),
+            None => name.to_string(),
+        };
+
+        // With every column hidden there is nothing to select, so the table reads as empty.
+        if matches!(&self.columns, Some(columns) if columns.is_empty()) {
+            return format!(
+                "SELECT NULL AS hidden FROM {} WHERE {}",
+                name, DENY_ALL_FILTER
+            );
+        }
+
+        match self.predicate() {
+            Some(predicate) => format!(
+                "SELECT {} FROM {} WHERE {}",
+                self.projection(),
+                name,
+                predicate
+            ),
+            None => format!("SELECT {} FROM {}", self.projection(), name),
+        }
+    }
+
+    fn matches(&self, name: &ObjectName) -> bool {
+        table_name_matches(name, &self.schema, &self.table)
+    }
+}
+
+/// The table or view a dataset is read from on its data source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetTable {
+    pub schema: String,
+    pub table: String,
+}
+
+/// Unqualified names match in any schema, since we can't tell which schema the data source will
+/// resolve them to.
+fn table_name_matches(name: &ObjectName, schema: &str, table: &str) -> bool {
+    let mut parts = name.0.iter().rev();
+
+    let table_matches = match parts.next() {
+        Some(name_table) => name_table.value.eq_ignore_ascii_case(table),
+        None => false,
+    };
+
+    let schema_matches = match parts.next() {
+        Some(name_schema) => name_schema.value.eq_ignore_ascii_case(schema),
+        None => true,
+    };
+
+    table_matches && schema_matches
+}
+
+/// Applies the row policies and column policies that bind the query's user to tables of the data
+/// source, and fills in templated dataset definitions for them. Queries without a user, such as
+/// background syncs, are returned unchanged.
+///
+/// Users bound by any policy can only query the data source's datasets, otherwise they could
+/// read the tables behind a dataset without its policies.
+///
+/// When columns are hash masked, the masking key their values have to be finished with once the
+/// results are fetched is returned alongside the query.
+pub async fn apply_user_dataset_policies(
+    data_source: &DataSource,
+    sql: &str,
+    context: &QueryContext,
+) -> Result<(String, Option<MaskingKey>)> {
+    let user_id = match context.user_id {
+        Some(user_id) => user_id,
+        None => return Ok((sql.to_string(), None)),
+    };
+
+    let (user, identities) = tokio::try_join!(
+        get_template_user(&user_id, context.preview_attributes.as_ref()),
+        get_user_identities(&user_id),
+    )?;
+
+    let (row_policies, (column_policies, masking_key), dataset_sources) = tokio::try_join!(
+        get_user_row_policies(&user, &identities, &data_source.id),
+        get_user_column_projections(&identities, data_source),
+        get_user_dataset_sources(&user, &data_source.id),
+    )?;
+
+    let policies = merge_table_policies(vec![row_policies, column_policies, dataset_sources]);
+
+    if policies.is_empty() {
+        return Ok((sql.to_string(), None));
+    }
+
+    let dataset_tables = get_dataset_tables(&data_source.id).await?;
+
+    let dialect = get_sql_dialect(&data_source.type_);
+
+    let sql = apply_table_policies(dialect.as_ref(), sql, &policies, &dataset_tables)?;
+
+    Ok((sql, masking_key))
+}
+
+pub async fn get_dataset_tables(data_source_id: &Uuid) -> Result<Vec<DatasetTable>> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match datasets::table
+        .filter(datasets::data_source_id.eq(data_source_id))
+        .filter(datasets::deleted_at.is_null())
+        .select((datasets::schema, datasets::database_name))
+        .load::<(String, String)>(&mut conn)
+        .await
+    {
+        Ok(dataset_tables) => Ok(dataset_tables
+            .into_iter()
+            .map(|(schema, table)| DatasetTable { schema, table })
+            .collect()),
+        Err(e) => Err(anyhow!("Error getting datasets: {}", e)),
+    }
+}
+
+/// The teams and permission groups a user belongs to, directly or through a team.
+#[derive(Debug, Clone, Default)]
+pub struct UserIdentities {
+    pub team_ids: Vec<Uuid>,
+    pub permission_group_ids: Vec<Uuid>,
+}
+
+pub async fn get_user_identities(user_id: &Uuid) -> Result<UserIdentities> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let team_ids = match teams_to_users::table
+        .filter(teams_to_users::user_id.eq(user_id))
+        .filter(teams_to_users::deleted_at.is_null())
+        .select(teams_to_users::team_id)
+        .load::<Uuid>(&mut conn)
+        .await
+    {
+        Ok(team_ids) => team_ids,
+        Err(e) => return Err(anyhow!("Error getting user teams: {}", e)),
+    };
+
+    let permission_group_ids = match permission_groups_to_identities::table
+        .filter(
+            permission_groups_to_identities::identity_type
+                .eq(IdentityType::User)
+                .and(permission_groups_to_identities::identity_id.eq(user_id))
+                .or(permission_groups_to_identities::identity_type
+                    .eq(IdentityType::Team)
+                    .and(permission_groups_to_identities::identity_id.eq_any(&team_ids))),
+        )
+        .filter(permission_groups_to_identities::deleted_at.is_null())
+        .select(permission_groups_to_identities::permission_group_id)
+        .distinct()
+        .load::<Uuid>(&mut conn)
+        .await
+    {
+        Ok(permission_group_ids) => permission_group_ids,
+        Err(e) => return Err(anyhow!("Error getting user permission groups: {}", e)),
+    };
+
+    Ok(UserIdentities {
+        team_ids,
+        permission_group_ids,
+    })
+}
+
+/// Combines the policies for the same table into one, so each table reference is wrapped once.
+fn merge_table_policies(policy_sets: Vec<Vec<TablePolicy>>) -> Vec<TablePolicy> {
+    let mut policies: Vec<TablePolicy> = Vec::new();
+
+    for new_policy in policy_sets.into_iter().flatten() {
+        match policies
+            .iter_mut()
+            .find(|policy| policy.schema == new_policy.schema && policy.table == new_policy.table)
+        {
+            Some(policy) => {
+                policy.filters.extend(new_policy.filters);
+
+                if new_policy.columns.is_some() {
+                    policy.columns = new_policy.columns;
+                }
+
+                if new_policy.source.is_some() {
+                    policy.source = new_policy.source;
+                }
+            }
+            None => policies.push(new_policy),
+        }
+    }
+
+    policies
+}
+
+/// Loads the data source's datasets whose definitions are templated, filled in for the user. These
+/// datasets have no view on the data source, so references to them are replaced by their SQL.
+pub async fn get_user_dataset_sources(
+    user: &TemplateUser,
+    data_source_id: &Uuid,
+) -> Result<Vec<TablePolicy>> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let dataset_records = match datasets::table
+        .filter(datasets::data_source_id.eq(data_source_id))
+        .filter(datasets::definition.like("%{{%"))
+        .filter(datasets::deleted_at.is_null())
+        .select((
+            datasets::name,
+            datasets::schema,
+            datasets::database_name,
+            datasets::definition,
+        ))
+        .load::<(String, String, String, String)>(&mut conn)
+        .await
+    {
+        Ok(dataset_records) => dataset_records,
+        Err(e) => return Err(anyhow!("Error getting templated datasets: {}", e)),
+    };
+
+    let mut sources = Vec::new();
+
+    for (name, schema, table, definition) in dataset_records {
+        if !has_user_template(&definition) {
+            continue;
+        }
+
+        let source = match render_user_template(&definition, user) {
+            Some(source) => source,
+            None => {
+                return Err(anyhow!(
+                    "Dataset {} refers to user attributes that aren't set for this user",
+                    name
+                ))
+            }
+        };
+
+        sources.push(TablePolicy {
+            schema,
+            table,
+            filters: vec![],
+            columns: None,
+            source: Some(source),
+        });
+    }
+
+    Ok(sources)
+}
+
+/// Loads the policies on the data source's datasets that are bound to the user directly, to one of
+/// their teams, or to a permission group they or their teams are in. Policies bound to nobody the
+/// user belongs to don't restrict them.
+pub async fn get_user_row_policies(
+    user: &TemplateUser,
+    identities: &UserIdentities,
+    data_source_id: &Uuid,
+) -> Result<Vec<TablePolicy>> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let policy_records = match dataset_row_policies::table
+        .inner_join(
+            dataset_row_policies_to_identities::table
+                .on(dataset_row_policies_to_identities::row_policy_id.eq(dataset_row_policies::id)),
+        )
+        .inner_join(datasets::table.on(datasets::id.eq(dataset_row_policies::dataset_id)))
+        .filter(datasets::data_source_id.eq(data_source_id))
+        .filter(
+            dataset_row_policies_to_identities::identity_type
+                .eq(RowPolicyIdentityType::User)
+                .and(dataset_row_policies_to_identities::identity_id.eq(user.id))
+                .or(dataset_row_policies_to_identities::identity_type
+                    .eq(RowPolicyIdentityType::Team)
+                    .and(
+                        dataset_row_policies_to_identities::identity_id
+                            .eq_any(&identities.team_ids),
+                    ))
+                .or(dataset_row_policies_to_identities::identity_type
+                    .eq(RowPolicyIdentityType::PermissionGroup)
+                    .and(
+                        dataset_row_policies_to_identities::identity_id
+                            .eq_any(&identities.permission_group_ids),
+                    )),
+        )
+        .filter(dataset_row_policies::deleted_at.is_null())
+        .filter(dataset_row_policies_to_identities::deleted_at.is_null())
+        .filter(datasets::deleted_at.is_null())
+        .select((
+            dataset_row_policies::id,
+            datasets::schema,
+            datasets::database_name,
+            dataset_row_policies::filter,
+        ))
+        .distinct()
+        .load::<(Uuid, String, String, String)>(&mut conn)
+        .await
+    {
+        Ok(policy_records) => policy_records,
+        Err(e) => return Err(anyhow!("Error getting row policies: {}", e)),
+    };
+
+    let mut policies: Vec<TablePolicy> = Vec::new();
+
+    for (_, schema, table, filter) in policy_records {
+        let filter = render_row_policy_filter(&filter, user);
+
+        match policies
+            .iter_mut()
+            .find(|policy| policy.schema == schema && policy.table == table)
+        {
+            Some(policy) => policy.filters.push(filter),
+            None => policies.push(TablePolicy {
+                schema,
+                table,
+                filters: vec![filter],
+                columns: None,
+                source: None,
+            }),
+        }
+    }
+
+    Ok(policies)
+}
+
+/// The datasets among `dataset_ids` that have a row policy for the user, bound the same way as in
+/// `get_user_row_policies`.
+pub async fn get_user_row_restricted_dataset_ids(
+    user_id: &Uuid,
+    identities: &UserIdentities,
+    dataset_ids: &[Uuid],
+) -> Result<HashSet<Uuid>> {
+    if dataset_ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match dataset_row_policies::table
+        .inner_join(
+            dataset_row_policies_to_identities::table
+                .on(dataset_row_policies_to_identities::row_policy_id.eq(dataset_row_policies::id)),
+        )
+        .filter(dataset_row_policies::dataset_id.eq_any(dataset_ids))
+        .filter(
+            dataset_row_policies_to_identities::identity_type
+                .eq(RowPolicyIdentityType::User)
+                .and(dataset_row_policies_to_identities::identity_id.eq(user_id))
+                .or(dataset_row_policies_to_identities::identity_type
+                    .eq(RowPolicyIdentityType::Team)
+                    .and(
+                        dataset_row_policies_to_identities::identity_id
+                            .eq_any(&identities.team_ids),
+                    ))
+                .or(dataset_row_policies_to_identities::identity_type
+                    .eq(RowPolicyIdentityType::PermissionGroup)
+                    .and(
+                        dataset_row_policies_to_identities::identity_id
+                            .eq_any(&identities.permission_group_ids),
+                    )),
+        )
+        .filter(dataset_row_policies::deleted_at.is_null())
+        .filter(dataset_row_policies_to_identities::deleted_at.is_null())
+        .select(dataset_row_policies::dataset_id)
+        .distinct()
+        .load::<Uuid>(&mut conn)
+        .await
+    {
+        Ok(dataset_ids) => Ok(dataset_ids.into_iter().collect()),
+        Err(e) => Err(anyhow!("Error getting row restricted datasets: {}", e)),
+    }
+}
+
+/// If any placeholder can't be filled in the whole filter is replaced with one that matches no rows.
+pub fn render_row_policy_filter(filter: &str, user: &TemplateUser) -> String {
+    match render_user_template(filter, user) {
+        Some(filter) => filter,
+        None => DENY_ALL_FILTER.to_string(),
+    }
+}
+
+pub fn has_user_template(sql: &str) -> bool {
+    TEMPLATE_REGEX.is_match(sql)
+}
+
+/// Fills in the `{{user.id}}`, `{{user.email}}` and `{{user.attributes.<key>}}` placeholders of
+/// SQL as literals. Array attributes become a list for use with `IN`. Returns `None` if any
+/// placeholder can't be filled in.
+pub fn render_user_template(sql: &str, user: &TemplateUser) -> Option<String> {
+    let mut unresolved = false;
+
+    let rendered = TEMPLATE_REGEX.replace_all(sql, |captures: &Captures| {
+        let value = match &captures[1] {
+            "id" => Some(Value::String(user.id.to_string())),
+            "email" => Some(Value::String(user.email.clone())),
+            path => match path.strip_prefix("attributes.") {
+                Some(key) => user.attributes.get(key).cloned(),
+                None => None,
+            },
+        };
+
+        match value.as_ref().and_then(sql_literal) {
+            Some(literal) => literal,
+            None => {
+                unresolved = true;
+                String::new()
+            }
+        }
+    });
+
+    if unresolved {
+        return None;
+    }
+
+    Some(rendered.into_owned())
+}
+
+/// Strings with backslashes are refused rather than escaped, because some of the warehouses we
+/// query treat a backslash inside a string literal as an escape character and others don't.
+fn sql_literal(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => {
+            if value.contains('\\') || value.contains('\0') {
+                return None;
+            }
+
+            Some(format!("'{}'", value.replace('\'', "''")))
+        }
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(true) => Some("TRUE".to_string()),
+        Value::Bool(false) => Some("FALSE".to_string()),
+        Value::Array(values) if !values.is_empty() => {
+            let literals = values
+                .iter()
+                .map(|value| match value {
+                    Value::Array(_) => None,
+                    value => sql_literal(value),
+                })
+                .collect::<Option<Vec<String>>>()?;
+
+            Some(format!("({})", literals.join(", ")))
+        }
+        _ => None,
+    }
+}
+
+/// Replaces every reference to a table with policies by a subquery that only returns the rows and
+/// columns the policies allow, keeping the reference's alias so the rest of the query is
+/// unaffected. This covers joins, subqueries and CTEs, however the table is reached. A CTE that
+/// shares its name with a table is filtered too, which may fail but never returns data the
+/// policies exclude.
+///
+/// When there are policies, the query can only be a `SELECT` and only read from the datasets'
+/// tables and its own CTEs. Anything else, including table functions, is refused.
+pub fn apply_table_policies(
+    dialect: &dyn Dialect,
+    sql: &str,
+    policies: &[TablePolicy],
+    dataset_tables: &[DatasetTable],
+) -> Result<String> {
+    let mut statements = match Parser::parse_sql(dialect, sql) {
+        Ok(statements) => statements,
+        Err(e) => return Err(anyhow!("Unable to apply dataset policies to query: {}", e)),
+    };
+
+    if !policies.is_empty()
+        && !statements
+            .iter()
+            .all(|statement| matches!(statement, Statement::Query(_)))
+    {
+        return Err(anyhow!(
+            "Only SELECT queries can be run against datasets with policies"
+        ));
+    }
+
+    let mut visitor = TablePolicyVisitor {
+        dialect,
+        policies,
+        dataset_tables,
+        cte_scopes: Vec::new(),
+    };
+
+    if let ControlFlow::Break(e) = statements.visit(&mut visitor) {
+        return Err(e);
+    }
+
+    Ok(statements
+        .iter()
+        .map(|statement| statement.to_string())
+        .collect::<Vec<String>>()
+        .join(";\n"))
+}
+
+struct TablePolicyVisitor<'a> {
+    dialect: &'a dyn Dialect,
+    policies: &'a [TablePolicy],
+    dataset_tables: &'a [DatasetTable],
+    /// The names of the CTEs of each query the visitor is inside of.
+    cte_scopes: Vec<Vec<String>>,
+}
+
+impl TablePolicyVisitor<'_> {
+    fn is_cte(&self, name: &ObjectName) -> bool {
+        match name.0.as_slice() {
+            [name] => self
+                .cte_scopes
+                .iter()
+                .flatten()
+                .any(|cte| cte.eq_ignore_ascii_case(&name.value)),
+            _ => false,
+        }
+    }
+
+    fn is_dataset_table(&self, name: &ObjectName) -> bool {
+        self.policies.iter().any(|policy| policy.matches(name))
+            || self.dataset_tables.iter().any(|dataset_table| {
+                table_name_matches(name, &dataset_table.schema, &dataset_table.table)
+            })
+    }
+}
+
+impl VisitorMut for TablePolicyVisitor<'_> {
+    type Break = anyhow::Error;
+
+    fn pre_visit_query(&mut self, query: &mut Query) -> ControlFlow<Self::Break> {
+        let ctes = match &query.with {
+            Some(with) => with.cte_tables.as_slice(),
+            None => &[],
+        };
+
+        let names = ctes
+            .iter()
+            .map(|cte| cte.alias.name.value.clone())
+            .collect::<Vec<String>>();
+
+        // Without RECURSIVE a CTE can only read the ones before it. A reference to itself or a
+        // later one is to a table of the same name, which would get past the check below.
+        let recursive = query.with.as_ref().is_some_and(|with| with.recursive);
+
+        if !self.policies.is_empty() && !recursive {
+            for (index, cte) in ctes.iter().enumerate() {
+                let visible_later = &names[index..];
+
+                visit_relations(cte.query.as_ref(), |name| match name.0.as_slice() {
+                    [name]
+                        if visible_later
+                            .iter()
+                            .any(|cte| cte.eq_ignore_ascii_case(&name.value)) =>
+                    {
+                        ControlFlow::Break(anyhow!(
+                            "CTE {} refers to {} before it is defined",
+                            cte.alias.name,
+                            name
+                        ))
+                    }
+                    _ => ControlFlow::Continue(()),
+                })?;
+            }
+        }
+
+        self.cte_scopes.push(names);
+
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, _query: &mut Query) -> ControlFlow<Self::Break> {
+        self.cte_scopes.pop();
+
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_table_factor(
+        &mut self,
+        table_factor: &mut TableFactor,
+    ) -> ControlFlow<Self::Break> {
+        if self.policies.is_empty() {
+            return ControlFlow::Continue(());
+        }
+
+        match table_factor {
+            TableFactor::Table {
+                name, args: None, ..
+            } => {
+                if self.is_cte(name) || self.is_dataset_table(name) {
+                    ControlFlow::Continue(())
+                } else {
+                    ControlFlow::Break(anyhow!("{} is not a dataset on this data source", name))
+                }
+            }
+            TableFactor::Table { .. }
+            | TableFactor::TableFunction { .. }
+            | TableFactor::Function { .. }
+            | TableFactor::JsonTable { .. }
+            | TableFactor::OpenJsonTable { .. } => ControlFlow::Break(anyhow!(
+                "Table functions can't be used with datasets with policies"
+            )),
+            _ => ControlFlow::Continue(()),
+        }
+    }
+
+    fn post_visit_table_factor(
+        &mut self,
+        table_factor: &mut TableFactor,
+    ) -> ControlFlow<Self::Break> {
+        let (name, alias) = match table_factor {
+            TableFactor::Table {
+                name,
+                alias,
+                args: None,
+                ..
+            } => (name, alias),
+            _ => return ControlFlow::Continue(()),
+        };
+
+        let policy = match self.policies.iter().find(|policy| policy.matches(name)) {
+            Some(policy) => policy,
+            None => return ControlFlow::Continue(()),
+        };
+
+        let alias = match alias {
+            Some(alias) => alias.to_string(),
+            None => match name.0.last() {
+                Some(table) => table.to_string(),
+                None => return ControlFlow::Continue(()),
+            },
+        };
+
+        // The subquery is parsed rather than built so it is valid for the dialect.
+        let wrapper_sql = format!("SELECT * FROM ({}) AS {}", policy.subquery(name), alias);
+
+        match filtered_table_factor(self.dialect, &wrapper_sql) {
+            Ok(filtered) => *table_factor = filtered,
+            Err(e) => return ControlFlow::Break(e),
+        }
+
+        ControlFlow::Continue(())
+    }
+}
+
+fn filtered_table_factor(dialect: &dyn Dialect, wrapper_sql: &str) -> Result<TableFactor> {
+    let mut statements = match Parser::parse_sql(dialect, wrapper_sql) {
+        Ok(statements) => statements,
+        Err(e) => return Err(anyhow!("Unable to parse dataset policy: {}", e)),
+    };
+
+    let query = match statements.pop() {
+        Some(Statement::Query(query)) if statements.is_empty() => query,
+        _ => return Err(anyhow!("Dataset policy is not a single table")),
+    };
+
+    match *query.body {
+        SetExpr::Select(select) => match select.from.into_iter().next() {
+            Some(table) if table.joins.is_empty() => Ok(table.relation),
+            _ => Err(anyhow!("Dataset policy is not a single table")),
+        },
+        _ => Err(anyhow!("Dataset policy is not a single table")),
+    }
+}
+
+/// Checks that a policy filter is a single SQL condition. Placeholders are filled in with sample
+/// values, so this doesn't depend on any user's attributes.
+pub fn validate_row_policy_filter(filter: &str) -> Result<()> {
+    let sample_filter = TEMPLATE_REGEX.replace_all(filter, "'placeholder'");
+
+    let dialect = sqlparser::dialect::GenericDialect {};
+
+    let mut parser = match Parser::new(&dialect).try_with_sql(&sample_filter) {
+        Ok(parser) => parser,
+        Err(e) => return Err(anyhow!("Invalid row policy filter: {}", e)),
+    };
+
+    if let Err(e) = parser.parse_expr() {
+        return Err(anyhow!("Invalid row policy filter: {}", e));
+    }
+
+    match parser.peek_token().token {
+        sqlparser::tokenizer::Token::EOF => Ok(()),
+        token => Err(anyhow!(
+            "Invalid row policy filter: unexpected `{}` after the condition",
+            token
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+    use sqlparser::dialect::PostgreSqlDialect;
+
+    fn sales_rep(attributes: Value) -> TemplateUser {
+        TemplateUser {
+            id: Uuid::nil(),
+            email: "rep@example.com".to_string(),
+            attributes,
+        }
+    }
+
+    fn sales_datasets() -> Vec<DatasetTable> {
+        [
+            ("sales", "orders"),
+            ("sales", "customers"),
+            ("marketing", "orders"),
+        ]
+        .iter()
+        .map(|(schema, table)| DatasetTable {
+            schema: schema.to_string(),
+            table: table.to_string(),
+        })
+        .collect()
+    }
+
+    fn orders_policy(filter: &str) -> TablePolicy {
+        TablePolicy {
+            schema: "sales".to_string(),
+            table: "orders".to_string(),
+            filters: vec![filter.to_string()],
+            columns: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn renders_attributes_as_literals() {
+        let user = sales_rep(json!({ "region": "O'Hare", "regions": ["east", "west"], "tier": 2 }));
+
+        assert_eq!(
+            render_row_policy_filter("region = {{user.attributes.region}}", &user),
+            "region = 'O''Hare'"
+        );
+        assert_eq!(
+            render_row_policy_filter("region IN {{ user.attributes.regions }}", &user),
+            "region IN ('east', 'west')"
+        );
+        assert_eq!(
+            render_row_policy_filter("tier <= {{user.attributes.tier}}", &user),
+            "tier <= 2"
+        );
+        assert_eq!(
+            render_row_policy_filter("owner = {{user.email}}", &user),
+            "owner = 'rep@example.com'"
+        );
+    }
+
+    #[test]
+    fn unresolved_placeholders_deny_all_rows() {
+        let user = sales_rep(json!({ "region": "east\\' OR 1=1 --", "empty": [] }));
+
+        assert_eq!(
+            render_row_policy_filter("territory = {{user.attributes.territory}}", &user),
+            DENY_ALL_FILTER
+        );
+        assert_eq!(
+            render_row_policy_filter("region = {{user.attributes.region}}", &user),
+            DENY_ALL_FILTER
+        );
+        assert_eq!(
+            render_row_policy_filter("region IN {{user.attributes.empty}}", &user),
+            DENY_ALL_FILTER
+        );
+        assert_eq!(
+            render_row_policy_filter("region = {{user.name}}", &user),
+            DENY_ALL_FILTER
+        );
+    }
+
+    #[test]
+    fn wraps_every_reference_to_the_table() {
+        let sql = "SELECT o.id, c.name FROM sales.orders o \
+                   JOIN customers c ON c.id = o.customer_id \
+                   WHERE o.id IN (SELECT id FROM orders)";
+
+        let rewritten = apply_table_policies(
+            &PostgreSqlDialect {},
+            sql,
+            &[orders_policy("region = 'east'")],
+            &sales_datasets(),
+        )
+        .unwrap();
+
+        assert!(rewritten.starts_with(
+            "SELECT o.id, c.name FROM (SELECT * FROM sales.orders WHERE (region = 'east')) AS o"
+        ));
+        assert!(rewritten.contains("customers AS c"));
+        assert!(rewritten.ends_with(
+            "WHERE o.id IN (SELECT id FROM (SELECT * FROM orders WHERE (region = 'east')) AS orders)"
+        ));
+    }
+
+    #[test]
+    fn combines_policies_with_or() {
+        let mut policy = orders_policy("region = 'east'");
+        policy.filters.push("owner = 'rep@example.com'".to_string());
+
+        let rewritten = apply_table_policies(
+            &PostgreSqlDialect {},
+            "WITH recent AS (SELECT * FROM orders) SELECT count(*) FROM recent",
+            &[policy],
+            &sales_datasets(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            rewritten,
+            "WITH recent AS (SELECT * FROM (SELECT * FROM orders WHERE (region = 'east') OR \
+             (owner = 'rep@example.com')) AS orders) SELECT count(*) FROM recent"
+        );
+    }
+
+    #[test]
+    fn leaves_other_schemas_and_tables_alone() {
+        let sql = "SELECT * FROM marketing.orders, sales.customers";
+
+        let rewritten = apply_table_policies(
+            &PostgreSqlDialect {},
+            sql,
+            &[orders_policy("1 = 0")],
+            &sales_datasets(),
+        )
+        .unwrap();
+
+        assert_eq!(rewritten, sql);
+    }
+
+    #[test]
+    fn projects_only_the_allowed_columns() {
+        let mut policy = orders_policy("region = 'east'");
+        policy.columns = Some(vec!["id".to_string(), "md5(email) AS email".to_string()]);
+
+        let rewritten = apply_table_policies(
+            &PostgreSqlDialect {},
+            "SELECT * FROM orders",
+            &[policy],
+            &sales_datasets(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            rewritten,
+            "SELECT * FROM (SELECT id, md5(email) AS email FROM orders WHERE (region = 'east')) \
+             AS orders"
+        );
+    }
+
+    #[test]
+    fn tables_with_every_column_hidden_read_as_empty() {
+        let policy = TablePolicy {
+            schema: "sales".to_string(),
+            table: "orders".to_string(),
+            filters: vec![],
+            columns: Some(vec![]),
+            source: None,
+        };
+
+        let rewritten = apply_table_policies(
+            &PostgreSqlDialect {},
+            "SELECT count(*) FROM orders",
+            &[policy],
+            &sales_datasets(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            rewritten,
+            "SELECT count(*) FROM (SELECT NULL AS hidden FROM orders WHERE 1 = 0) AS orders"
+        );
+    }
+
+    #[test]
+    fn merges_row_and_column_policies_for_the_same_table() {
+        let mut column_policy = orders_policy("unused");
+        column_policy.filters = vec![];
+        column_policy.columns = Some(vec!["id".to_string()]);
+
+        let merged = merge_table_policies(vec![
+            vec![orders_policy("region = 'east'")],
+            vec![column_policy],
+        ]);
+
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].filters, vec!["region = 'east'".to_string()]);
+        assert_eq!(merged[0].columns, Some(vec!["id".to_string()]));
+    }
+
+    #[test]
+    fn templated_datasets_read_from_their_sql() {
+        let user = sales_rep(json!({ "region": "east" }));
+
+        let mut policy = orders_policy("status = 'open'");
+        policy.source = render_user_template(
+            "SELECT * FROM raw.orders WHERE region = {{user.attributes.region}}",
+            &user,
+        );
+
+        let rewritten = apply_table_policies(
+            &PostgreSqlDialect {},
+            "SELECT count(*) FROM sales.orders",
+            &[policy],
+            &sales_datasets(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            rewritten,
+            "SELECT count(*) FROM (SELECT * FROM (SELECT * FROM raw.orders WHERE region = 'east') \
+             AS orders WHERE (status = 'open')) AS orders"
+        );
+        assert_eq!(
+            render_user_template("SELECT {{user.attributes.team}}", &user),
+            None
+        );
+    }
+
+    #[test]
+    fn tables_behind_datasets_are_refused() {
+        let policies = [orders_policy("region = 'east'")];
+
+        for sql in [
+            "SELECT * FROM raw.orders",
+            "SELECT o.id FROM sales.orders o JOIN raw.payments p ON p.order_id = o.id",
+            "SELECT * FROM sales.orders WHERE id IN (SELECT order_id FROM raw.refunds)",
+            "SELECT * FROM (WITH raw_orders AS (SELECT 1) SELECT * FROM raw_orders) x, raw_orders",
+            "WITH orders_raw AS (SELECT * FROM orders_raw) SELECT * FROM orders_raw",
+            "SELECT * FROM generate_series(1, 10)",
+            "DELETE FROM sales.orders",
+        ] {
+            assert!(
+                apply_table_policies(&PostgreSqlDialect {}, sql, &policies, &sales_datasets())
+                    .is_err(),
+                "{} was not refused",
+                sql
+            );
+        }
+
+        assert!(apply_table_policies(
+            &PostgreSqlDialect {},
+            "WITH recent AS (SELECT * FROM sales.orders) SELECT * FROM recent JOIN customers \
+             ON customers.id = recent.customer_id",
+            &policies,
+            &sales_datasets()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn column_policies_refuse_tables_behind_datasets() {
+        let policy = TablePolicy {
+            schema: "sales".to_string(),
+            table: "customers".to_string(),
+            filters: vec![],
+            columns: Some(vec!["id".to_string()]),
+            source: None,
+        };
+
+        assert!(apply_table_policies(
+            &PostgreSqlDialect {},
+            "SELECT email FROM raw.customers",
+            &[policy.clone()],
+            &sales_datasets()
+        )
+        .is_err());
+        assert_eq!(
+            apply_table_policies(
+                &PostgreSqlDialect {},
+                "SELECT id FROM sales.customers",
+                &[policy],
+                &sales_datasets()
+            )
+            .unwrap(),
+            "SELECT id FROM (SELECT id FROM sales.customers) AS customers"
+        );
+    }
+
+    #[test]
+    fn unparseable_sql_is_refused() {
+        assert!(apply_table_policies(
+            &PostgreSqlDialect {},
+            "SELECT * FROM orders WHERE",
+            &[orders_policy("region = 'east'")],
+            &sales_datasets()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn filters_must_be_a_single_condition() {
+        assert!(validate_row_policy_filter("region = {{user.attributes.region}}").is_ok());
+        assert!(validate_row_policy_filter("region IN ('a', 'b') AND tier > 1").is_ok());
+        assert!(validate_row_policy_filter("true) UNION SELECT * FROM orders --").is_err());
+        assert!(validate_row_policy_filter("region = 'a'; DROP TABLE orders").is_err());
+    }
+}
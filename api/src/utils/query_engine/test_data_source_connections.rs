@@ -3,12 +3,15 @@ use crate::database::enums::DataSourceType;
 use super::{
     credentials::Credential,
     data_source_connections::{
-        get_bigquery_client::get_bigquery_client, get_databricks_client::get_databricks_client,
-        get_mysql_connection::get_mysql_connection,
+        get_bigquery_client::get_bigquery_client, get_clickhouse_client::get_clickhouse_client,
+        get_databricks_client::get_databricks_client,
+        get_duckdb_connection::get_duckdb_connection,
+        get_mysql_connection::{get_mysql_connection, get_starrocks_connection},
         get_postgres_connection::get_postgres_connection,
         get_redshift_connection::get_redshift_connection,
         get_snowflake_client::get_snowflake_client,
         get_sql_server_connection::get_sql_server_connection,
+        get_trino_client::get_trino_client,
     },
 };
 use anyhow::{anyhow, Result};
@@ -18,6 +21,24 @@ pub async fn test_data_source_connection(
     credential: &Credential,
 ) -> Result<()> {
     match type_ {
+        DataSourceType::Trino => {
+            let credential = match credential {
+                Credential::Trino(credential) => credential,
+                _ => return Err(anyhow!("Invalid credential type")),
+            };
+
+            let client = match get_trino_client(&credential).await {
+                Ok(client) => client,
+                Err(e) => return Err(anyhow!("Error getting trino client: {:?}", e)),
+            };
+
+            match client.query("SELECT 1".to_string(), None).await {
+                Ok(_) => (),
+                Err(e) => return Err(anyhow!("Error executing test query: {:?}", e)),
+            }
+
+            Ok(())
+        }
         DataSourceType::BigQuery => {
             let credential = match credential {
                 Credential::Bigquery(credential) => credential,
@@ -31,6 +52,24 @@ pub async fn test_data_source_connection(
 
             Ok(())
         }
+        DataSourceType::ClickHouse => {
+            let credential = match credential {
+                Credential::ClickHouse(credential) => credential,
+                _ => return Err(anyhow!("Invalid credential type")),
+            };
+
+            let client = match get_clickhouse_client(&credential).await {
+                Ok(client) => client,
+                Err(e) => return Err(anyhow!("Error getting clickhouse client: {:?}", e)),
+            };
+
+            match client.query("SELECT 1".to_string(), None).await {
+                Ok(_) => (),
+                Err(e) => return Err(anyhow!("Error executing test query: {:?}", e)),
+            }
+
+            Ok(())
+        }
         DataSourceType::Databricks => {
             let credential = match credential {
                 Credential::Databricks(credential) => credential,
@@ -49,13 +88,32 @@ pub async fn test_data_source_connection(
 
             Ok(())
         }
+        DataSourceType::DuckDb => {
+            let credential = match credential {
+                Credential::DuckDb(credential) => credential.clone(),
+                _ => return Err(anyhow!("Invalid credential type")),
+            };
+
+            let result = tokio::task::spawn_blocking(move || -> Result<()> {
+                let conn = get_duckdb_connection(&credential)?;
+                conn.execute_batch("SELECT 1")?;
+                Ok(())
+            })
+            .await;
+
+            match result {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(e)) => Err(anyhow!("Error getting duckdb connection: {:?}", e)),
+                Err(e) => Err(anyhow!("Error executing test query: {:?}", e)),
+            }
+        }
         DataSourceType::MySql | DataSourceType::Mariadb => {
             let credential = match credential {
                 Credential::MySQL(credential) => credential,
                 _ => return Err(anyhow!("Invalid credential type")),
             };
 
-            match get_mysql_connection(credential).await {
+            match get_mysql_connection(credential, 1).await {
                 Ok(client) => client,
                 Err(e) => return Err(anyhow!("Error getting mysql client: {:?}", e)),
             };
@@ -68,7 +126,7 @@ pub async fn test_data_source_connection(
                 _ => return Err(anyhow!("Invalid credential type")),
             };
 
-            match get_postgres_connection(&credential).await {
+            match get_postgres_connection(&credential, 1).await {
                 Ok(client) => client,
                 Err(e) => return Err(anyhow!("Error getting postgres client: {:?}", e)),
             };
@@ -81,7 +139,7 @@ pub async fn test_data_source_connection(
                 return Err(anyhow!("Invalid credential type: {:?}", credential));
             };
 
-            get_redshift_connection(&credential)
+            get_redshift_connection(&credential, 1)
                 .await
                 .map_err(|e| anyhow!("Error getting redshift client: {:?}", e))?;
 
@@ -113,6 +171,19 @@ pub async fn test_data_source_connection(
                 Err(e) => return Err(anyhow!("Error getting sqlserver client: {:?}", e)),
             };
 
+            Ok(())
+        }
+        DataSourceType::StarRocks => {
+            let credential = match credential {
+                Credential::StarRocks(credential) => credential,
+                _ => return Err(anyhow!("Invalid credential type")),
+            };
+
+            match get_starrocks_connection(credential, 1).await {
+                Ok(client) => client,
+                Err(e) => return Err(anyhow!("Error getting starrocks client: {:?}", e)),
+            };
+
             Ok(())
         }
     }
@@ -1,5 +1,9 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use sqlparser::dialect::{
+    BigQueryDialect, ClickHouseDialect, DatabricksDialect, Dialect, DuckDbDialect, GenericDialect,
+    MsSqlDialect, MySqlDialect, PostgreSqlDialect, RedshiftSqlDialect, SnowflakeDialect,
+};
 use tokio::process::Command;
 
 use crate::database::enums::DataSourceType;
@@ -7,15 +11,18 @@ use crate::database::enums::DataSourceType;
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum TargetDialect {
-    Athena,
     BigQuery,
+    ClickHouse,
     Databricks,
+    DuckDb,
     MySql,
     Postgres,
     Redshift,
     Snowflake,
+    StarRocks,
     #[serde(rename = "tsql")]
     SqlServer,
+    Trino,
     #[serde(rename = "mysql")]
     MariaDb,
     #[serde(rename = "postgres")]
@@ -26,18 +33,40 @@ impl From<DataSourceType> for TargetDialect {
     fn from(data_source_type: DataSourceType) -> Self {
         match data_source_type {
             DataSourceType::BigQuery => TargetDialect::BigQuery,
+            DataSourceType::ClickHouse => TargetDialect::ClickHouse,
             DataSourceType::Databricks => TargetDialect::Databricks,
+            DataSourceType::DuckDb => TargetDialect::DuckDb,
             DataSourceType::MySql => TargetDialect::MySql,
             DataSourceType::Postgres => TargetDialect::Postgres,
             DataSourceType::Redshift => TargetDialect::Redshift,
             DataSourceType::Snowflake => TargetDialect::Snowflake,
             DataSourceType::SqlServer => TargetDialect::SqlServer,
+            DataSourceType::StarRocks => TargetDialect::StarRocks,
             DataSourceType::Mariadb => TargetDialect::MariaDb,
             DataSourceType::Supabase => TargetDialect::Supabase,
+            DataSourceType::Trino => TargetDialect::Trino,
         }
     }
 }
 
+pub fn get_sql_dialect(data_source_type: &DataSourceType) -> Box<dyn Dialect> {
+    match data_source_type {
+        DataSourceType::BigQuery => Box::new(BigQueryDialect {}),
+        DataSourceType::ClickHouse => Box::new(ClickHouseDialect {}),
+        DataSourceType::Databricks => Box::new(DatabricksDialect {}),
+        DataSourceType::DuckDb => Box::new(DuckDbDialect {}),
+        DataSourceType::MySql | DataSourceType::Mariadb | DataSourceType::StarRocks => {
+            Box::new(MySqlDialect {})
+        }
+        DataSourceType::Postgres | DataSourceType::Supabase => Box::new(PostgreSqlDialect {}),
+        DataSourceType::Redshift => Box::new(RedshiftSqlDialect {}),
+        DataSourceType::Snowflake => Box::new(SnowflakeDialect {}),
+        DataSourceType::SqlServer => Box::new(MsSqlDialect {}),
+        // sqlparser has no Trino dialect; the generic one accepts its syntax.
+        DataSourceType::Trino => Box::new(GenericDialect {}),
+    }
+}
+
 pub async fn transpile_sql(sql: &String, target_dialect: TargetDialect) -> Result<String> {
     let serialized_dialect = serde_json::to_string(&target_dialect).unwrap();
 
@@ -12,21 +12,52 @@ use diesel::{update, AsChangeset, ExpressionMethods, JoinOnDsl, QueryDsl};
 use diesel_async::RunQueryDsl;
 
 use serde::Serialize;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-use super::{data_types::DataType, query_engine::query_engine};
+use super::{data_types::DataType, query_engine::uncached_query_engine};
 
 #[derive(Debug, AsChangeset)]
 #[diesel(table_name = dataset_columns)]
 pub struct DatasetColumnChangeset {
     pub stored_values: Option<bool>,
     pub stored_values_status: Option<StoredValuesStatus>,
-    pub stored_values_error: Option<String>,
+    /// `Some(None)` clears the error left by an earlier failed sync.
+    pub stored_values_error: Option<Option<String>>,
     pub stored_values_count: Option<i64>,
     pub stored_values_last_synced: Option<chrono::DateTime<Utc>>,
     pub updated_at: chrono::DateTime<Utc>,
 }
 
+impl DatasetColumnChangeset {
+    fn status(status: StoredValuesStatus) -> Self {
+        DatasetColumnChangeset {
+            stored_values: None,
+            stored_values_status: Some(status),
+            stored_values_error: None,
+            stored_values_count: None,
+            stored_values_last_synced: None,
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn failed(error: String) -> Self {
+        DatasetColumnChangeset {
+            stored_values_error: Some(Some(error)),
+            ..Self::status(StoredValuesStatus::Failed)
+        }
+    }
+
+    fn success(stored_values_count: i64) -> Self {
+        DatasetColumnChangeset {
+            stored_values_error: Some(None),
+            stored_values_count: Some(stored_values_count),
+            stored_values_last_synced: Some(Utc::now()),
+            ..Self::status(StoredValuesStatus::Success)
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct StoredValue {
     pub id: Uuid,
@@ -35,92 +66,111 @@ pub struct StoredValue {
     pub dataset_column_id: Uuid,
 }
 
-// TODO: This whole function and process needs to be more robust.  We just need something to move fast.
-pub async fn start_stored_values_sync(dataset_column_id: &Uuid) -> Result<()> {
+/// Syncs the distinct values of a column into its dataset's search index. Unless `force` is set,
+/// the values of a column that last synced successfully are only re-read when its distinct value
+/// count has changed.
+pub async fn start_stored_values_sync(dataset_column_id: &Uuid, force: bool) -> Result<()> {
     let mut conn = match get_pg_pool().get().await {
         Ok(conn) => conn,
         Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
     };
 
-    let (dataset_id, schema_name, database_name, dataset_column_name) = match dataset_columns::table
+    let (
+        dataset_id,
+        schema_name,
+        database_name,
+        dataset_column_name,
+        stored_values_status,
+        stored_values_count,
+    ) = match dataset_columns::table
         .inner_join(datasets::table.on(dataset_columns::dataset_id.eq(datasets::id)))
         .select((
             dataset_columns::dataset_id,
             datasets::schema,
             datasets::database_name,
             dataset_columns::name,
+            dataset_columns::stored_values_status,
+            dataset_columns::stored_values_count,
         ))
         .filter(dataset_columns::id.eq(dataset_column_id))
-        .first::<(Uuid, String, String, String)>(&mut conn)
+        .first::<(
+            Uuid,
+            String,
+            String,
+            String,
+            Option<StoredValuesStatus>,
+            Option<i64>,
+        )>(&mut conn)
         .await
     {
         Ok(dataset_record) => dataset_record,
         Err(e) => return Err(anyhow!("Error getting dataset id: {}", e)),
     };
 
+    drop(conn);
+
+    update_collection_record(
+        dataset_column_id,
+        DatasetColumnChangeset::status(StoredValuesStatus::Syncing),
+    )
+    .await?;
+
+    if !force && stored_values_status == Some(StoredValuesStatus::Success) {
+        if let Some(stored_values_count) = stored_values_count {
+            let distinct_count_query = format!(
+                "SELECT COUNT(DISTINCT {}) AS distinct_count FROM {}.{}",
+                dataset_column_name, schema_name, database_name
+            );
+
+            let distinct_count = match uncached_query_engine(&dataset_id, &distinct_count_query)
+                .await
+            {
+                Ok(results) => results
+                    .as_slice()
+                    .first()
+                    .and_then(|row| row.get("distinct_count"))
+                    .and_then(count_value),
+                Err(e) => {
+                    return fail_sync(dataset_column_id, format!("Error querying engine: {}", e))
+                        .await
+                }
+            };
+
+            if distinct_count == Some(stored_values_count) {
+                return update_collection_record(
+                    dataset_column_id,
+                    DatasetColumnChangeset::success(stored_values_count),
+                )
+                .await;
+            }
+        }
+    }
+
     let dataset_columns_values_query = format!(
         "SELECT DISTINCT {} FROM {}.{} WHERE {} IS NOT NULL",
         dataset_column_name, schema_name, database_name, dataset_column_name
     );
 
-    let mut dataset_column_changeset = DatasetColumnChangeset {
-        stored_values: Some(true),
-        stored_values_status: Some(StoredValuesStatus::Syncing),
-        stored_values_error: None,
-        stored_values_count: None,
-        stored_values_last_synced: Some(Utc::now()),
-        updated_at: Utc::now(),
-    };
-
-    let results = match query_engine(&dataset_id, &dataset_columns_values_query).await {
+    let results = match uncached_query_engine(&dataset_id, &dataset_columns_values_query).await {
         Ok(results) => results,
         Err(e) => {
-            dataset_column_changeset.stored_values_error = Some(e.to_string());
-            dataset_column_changeset.stored_values_status = Some(StoredValuesStatus::Failed);
-            update_collection_record(dataset_column_id, dataset_column_changeset).await?;
-            return Err(anyhow!("Error querying engine: {}", e));
+            return fail_sync(dataset_column_id, format!("Error querying engine: {}", e)).await
         }
     };
 
-    dataset_column_changeset.stored_values_count = Some(results.len() as i64);
-    dataset_column_changeset.stored_values_last_synced = Some(Utc::now());
-
     let mut documents = Vec::new();
 
     for result in results {
-        let value = match result.get::<String>(&dataset_column_name) {
-            Some(value) => match value {
-                DataType::Text(value) => match value {
-                    Some(value) => value.clone(),
-                    None => {
-                        dataset_column_changeset.stored_values_error =
-                            Some("Value is not a string".to_string());
-                        dataset_column_changeset.stored_values_status =
-                            Some(StoredValuesStatus::Failed);
-                        update_collection_record(dataset_column_id, dataset_column_changeset)
-                            .await?;
-                        return Err(anyhow!("Value is not a string"));
-                    }
-                },
-                _ => {
-                    dataset_column_changeset.stored_values_error =
-                        Some("Value is not a string".to_string());
-                    dataset_column_changeset.stored_values_status =
-                        Some(StoredValuesStatus::Failed);
-                    update_collection_record(dataset_column_id, dataset_column_changeset).await?;
-                    return Err(anyhow!("Value is not a string"));
-                }
-            },
-            None => {
-                dataset_column_changeset.stored_values_error = Some("Value not found".to_string());
-                dataset_column_changeset.stored_values_status = Some(StoredValuesStatus::Failed);
-                update_collection_record(dataset_column_id, dataset_column_changeset).await?;
-                return Err(anyhow!("Value not found"));
+        let value = match result.get(&dataset_column_name) {
+            Some(DataType::Text(Some(value))) => value.clone(),
+            Some(_) => {
+                return fail_sync(dataset_column_id, "Value is not a string".to_string()).await
             }
+            None => return fail_sync(dataset_column_id, "Value not found".to_string()).await,
         };
 
         documents.push(StoredValue {
-            id: Uuid::new_v4(),
+            id: stored_value_id(dataset_column_id, &value),
             value,
             dataset_id,
             dataset_column_id: dataset_column_id.clone(),
@@ -129,20 +179,34 @@ pub async fn start_stored_values_sync(dataset_column_id: &Uuid) -> Result<()> {
 
     let collection_name = format!("dataset_index_{}", dataset_id);
 
-    match typesense::bulk_insert_documents(&collection_name, &documents).await {
-        Ok(_) => {
-            dataset_column_changeset.stored_values_status = Some(StoredValuesStatus::Success);
-        }
-        Err(e) => {
-            dataset_column_changeset.stored_values_error =
-                Some(format!("Error inserting documents: {}", e));
-            dataset_column_changeset.stored_values_status = Some(StoredValuesStatus::Failed);
-            update_collection_record(dataset_column_id, dataset_column_changeset).await?;
-            return Err(anyhow!("Error inserting documents: {}", e));
-        }
-    };
+    // Values that have disappeared from the column are dropped before the current ones go back in.
+    if let Err(e) = typesense::delete_collection(
+        &collection_name,
+        &format!("dataset_column_id:={}", dataset_column_id),
+    )
+    .await
+    {
+        return fail_sync(
+            dataset_column_id,
+            format!("Error deleting documents: {}", e),
+        )
+        .await;
+    }
+
+    if let Err(e) = typesense::bulk_insert_documents(&collection_name, &documents).await {
+        return fail_sync(
+            dataset_column_id,
+            format!("Error inserting documents: {}", e),
+        )
+        .await;
+    }
 
-    match update_collection_record(dataset_column_id, dataset_column_changeset).await {
+    match update_collection_record(
+        dataset_column_id,
+        DatasetColumnChangeset::success(documents.len() as i64),
+    )
+    .await
+    {
         Ok(_) => (),
         Err(e) => return Err(anyhow!("Error updating collection record: {}", e)),
     };
@@ -150,6 +214,43 @@ pub async fn start_stored_values_sync(dataset_column_id: &Uuid) -> Result<()> {
     Ok(())
 }
 
+async fn fail_sync(dataset_column_id: &Uuid, error: String) -> Result<()> {
+    update_collection_record(
+        dataset_column_id,
+        DatasetColumnChangeset::failed(error.clone()),
+    )
+    .await?;
+
+    Err(anyhow!(error))
+}
+
+/// Ids are derived from the column and value so that syncing the same value twice replaces the
+/// document instead of duplicating it.
+fn stored_value_id(dataset_column_id: &Uuid, value: &str) -> Uuid {
+    let mut hasher = Sha256::new();
+    hasher.update(dataset_column_id.as_bytes());
+    hasher.update(value.as_bytes());
+
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hasher.finalize()[..16]);
+
+    Uuid::from_bytes(bytes)
+}
+
+/// `COUNT` comes back as a different type from every data source.
+fn count_value(value: &DataType) -> Option<i64> {
+    match value {
+        DataType::Int8(Some(count)) => Some(*count),
+        DataType::Int4(Some(count)) => Some(*count as i64),
+        DataType::Int2(Some(count)) => Some(*count as i64),
+        DataType::Float4(Some(count)) => Some(*count as i64),
+        DataType::Float8(Some(count)) => Some(*count as i64),
+        DataType::Decimal(Some(count)) => count.to_string().parse::<f64>().ok().map(|c| c as i64),
+        DataType::Text(Some(count)) | DataType::Unknown(Some(count)) => count.parse().ok(),
+        _ => None,
+    }
+}
+
 async fn update_collection_record(
     dataset_column_id: &Uuid,
     changeset: DatasetColumnChangeset,
@@ -168,3 +269,37 @@ async fn update_collection_record(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stored_value_ids_are_stable() {
+        let dataset_column_id = Uuid::new_v4();
+
+        assert_eq!(
+            stored_value_id(&dataset_column_id, "shipped"),
+            stored_value_id(&dataset_column_id, "shipped")
+        );
+        assert_ne!(
+            stored_value_id(&dataset_column_id, "shipped"),
+            stored_value_id(&dataset_column_id, "returned")
+        );
+        assert_ne!(
+            stored_value_id(&dataset_column_id, "shipped"),
+            stored_value_id(&Uuid::new_v4(), "shipped")
+        );
+    }
+
+    #[test]
+    fn reads_counts_of_any_numeric_type() {
+        assert_eq!(count_value(&DataType::Int8(Some(12))), Some(12));
+        assert_eq!(count_value(&DataType::Float8(Some(12.0))), Some(12));
+        assert_eq!(
+            count_value(&DataType::Text(Some("12".to_string()))),
+            Some(12)
+        );
+        assert_eq!(count_value(&DataType::Int8(None)), None);
+    }
+}
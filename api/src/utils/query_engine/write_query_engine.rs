@@ -6,6 +6,7 @@ use crate::database::models::DataSource;
 
 use super::data_source_query_routes::query_router::query_router;
 use super::data_types::DataType;
+use super::query_cancellation::QueryContext;
 
 pub async fn write_query_engine(
     dataset_id: &Uuid,
@@ -16,7 +17,7 @@ pub async fn write_query_engine(
         None => return Err(anyhow::anyhow!("Data source not found")),
     };
 
-    let results = match query_router(&data_source, sql, None, true).await {
+    let results = match query_router(&data_source, sql, None, true, &QueryContext::new()).await {
         Ok(results) => results,
         Err(e) => return Err(e),
     };
@@ -0,0 +1,114 @@
+use std::collections::{HashMap, HashSet};
+
+use once_cell::sync::Lazy;
+
+use super::rerank_engine::rank_by_scores;
+
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+static STOP_WORDS: Lazy<HashSet<String>> = Lazy::new(|| {
+    stop_words::get(stop_words::LANGUAGE::English)
+        .into_iter()
+        .collect()
+});
+
+/// Splits identifiers like `order_items` into words and drops plural endings, so questions
+/// about "orders" match tables named `order`.
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(|token| token.to_lowercase())
+        .filter(|token| !token.is_empty() && !STOP_WORDS.contains(token))
+        .map(|token| {
+            if token.len() > 3
+                && token.ends_with('s')
+                && !token.ends_with("ss")
+                && !token.ends_with("us")
+            {
+                token[..token.len() - 1].to_string()
+            } else {
+                token
+            }
+        })
+        .collect()
+}
+
+/// Scores documents with Okapi BM25. Runs locally, so it works without any provider.
+pub fn bm25_rerank(query: &str, documents: &[String], top_n: usize) -> Vec<usize> {
+    let documents = documents
+        .iter()
+        .map(|document| tokenize(document))
+        .collect::<Vec<Vec<String>>>();
+    let query_terms = tokenize(query).into_iter().collect::<HashSet<String>>();
+
+    let document_count = documents.len() as f64;
+    let average_length =
+        documents.iter().map(|d| d.len()).sum::<usize>() as f64 / document_count.max(1.0);
+
+    let term_frequencies = documents
+        .iter()
+        .map(|document| {
+            let mut frequencies = HashMap::new();
+            for term in document {
+                *frequencies.entry(term.as_str()).or_insert(0) += 1;
+            }
+            frequencies
+        })
+        .collect::<Vec<HashMap<&str, usize>>>();
+
+    let scores = documents
+        .iter()
+        .zip(&term_frequencies)
+        .map(|(document, frequencies)| {
+            let length_norm = 1.0 - B + B * document.len() as f64 / average_length.max(1.0);
+
+            query_terms
+                .iter()
+                .map(|term| {
+                    let frequency = *frequencies.get(term.as_str()).unwrap_or(&0) as f64;
+                    if frequency == 0.0 {
+                        return 0.0;
+                    }
+
+                    let containing = term_frequencies
+                        .iter()
+                        .filter(|frequencies| frequencies.contains_key(term.as_str()))
+                        .count() as f64;
+                    let idf = (1.0 + (document_count - containing + 0.5) / (containing + 0.5)).ln();
+
+                    idf * frequency * (K1 + 1.0) / (frequency + K1 * length_norm)
+                })
+                .sum::<f64>()
+        })
+        .collect::<Vec<f64>>();
+
+    rank_by_scores(&scores, top_n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_identifiers_and_drops_plurals() {
+        assert_eq!(
+            tokenize("How many ORDERS per order_items.status?"),
+            vec!["many", "order", "per", "order", "item", "status"]
+        );
+    }
+
+    #[test]
+    fn ranks_matching_datasets_first() {
+        let documents = vec![
+            "CREATE TABLE customers (id uuid, name text, country text)".to_string(),
+            "CREATE TABLE orders (id uuid, customer_id uuid, total numeric)".to_string(),
+            "CREATE TABLE web_sessions (id uuid, started_at timestamp)".to_string(),
+        ];
+
+        assert_eq!(
+            bm25_rerank("total of orders by customer", &documents, 2),
+            vec![1, 0]
+        );
+        assert_eq!(bm25_rerank("unrelated", &documents, 5), vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,33 @@
+use std::env;
+
+use anyhow::{anyhow, Result};
+use cohere_rust::{
+    api::rerank::{ReRankModel, ReRankRequest},
+    Cohere,
+};
+
+pub async fn cohere_rerank(query: &str, documents: &[String], top_n: usize) -> Result<Vec<usize>> {
+    // `Cohere::default` panics without a key.
+    match env::var("COHERE_API_KEY") {
+        Ok(api_key) if !api_key.is_empty() => (),
+        _ => return Err(anyhow!("COHERE_API_KEY is not set")),
+    }
+
+    let co = Cohere::default();
+
+    let request = ReRankRequest {
+        query,
+        documents,
+        model: ReRankModel::EnglishV3,
+        top_n: Some(top_n as u64),
+        max_chunks_per_doc: None,
+    };
+
+    match co.rerank(&request).await {
+        Ok(results) => Ok(results
+            .into_iter()
+            .map(|result| result.index as usize)
+            .collect()),
+        Err(e) => Err(anyhow!("Error reranking with Cohere: {}", e)),
+    }
+}
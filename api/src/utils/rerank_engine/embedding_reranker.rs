@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Result};
+
+use crate::utils::clients::ai::embedding_router::embedding_router;
+
+use super::rerank_engine::rank_by_scores;
+
+/// Long DDL is cut short so it fits in the embedding models' context.
+const MAX_DOCUMENT_CHARS: usize = 8_000;
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
+    let dot = a
+        .iter()
+        .zip(b)
+        .map(|(a, b)| *a as f64 * *b as f64)
+        .sum::<f64>();
+    let norm_a = a.iter().map(|a| (*a as f64).powi(2)).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|b| (*b as f64).powi(2)).sum::<f64>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+
+    dot / (norm_a * norm_b)
+}
+
+pub async fn embedding_rerank(
+    query: &str,
+    documents: &[String],
+    top_n: usize,
+) -> Result<Vec<usize>> {
+    let documents = documents
+        .iter()
+        .map(|document| {
+            document
+                .chars()
+                .take(MAX_DOCUMENT_CHARS)
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>();
+    let document_count = documents.len();
+
+    let (query_embeddings, document_embeddings) = tokio::try_join!(
+        embedding_router(vec![query.to_string()], true),
+        embedding_router(documents, false)
+    )?;
+
+    let query_embedding = match query_embeddings.into_iter().next() {
+        Some(query_embedding) => query_embedding,
+        None => return Err(anyhow!("No embedding returned for the query")),
+    };
+
+    if document_embeddings.len() != document_count {
+        return Err(anyhow!(
+            "Expected {} document embeddings, got {}",
+            document_count,
+            document_embeddings.len()
+        ));
+    }
+
+    let scores = document_embeddings
+        .iter()
+        .map(|document_embedding| cosine_similarity(&query_embedding, document_embedding))
+        .collect::<Vec<f64>>();
+
+    Ok(rank_by_scores(&scores, top_n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_by_direction_not_length() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[3.0, 0.0]) - 1.0).abs() < 1e-9);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 2.0]).abs() < 1e-9);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+    }
+}
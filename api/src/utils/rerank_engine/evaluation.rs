@@ -0,0 +1,130 @@
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{database::enums::Reranker, utils::clients::ai::llm_usage::LlmUsageScope};
+
+use super::rerank_engine::RERANK_TOP_N;
+
+/// A question and the datasets that answer it.
+#[derive(Deserialize, Debug, Clone)]
+pub struct LabelledQuestion {
+    pub question: String,
+    pub dataset_ids: Vec<Uuid>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RerankerEvaluation {
+    pub reranker: Reranker,
+    pub questions: usize,
+    /// Questions the reranker failed on. They score zero rather than falling back to BM25.
+    pub errors: usize,
+    pub recall_at_1: f64,
+    pub recall_at_5: f64,
+    /// Recall over everything that's passed on to the agents.
+    pub recall_at_top_n: f64,
+    pub mean_reciprocal_rank: f64,
+    pub mean_latency_ms: f64,
+}
+
+/// The share of the relevant datasets that made the first `k`.
+pub fn recall_at(ranking: &[Uuid], relevant: &[Uuid], k: usize) -> f64 {
+    if relevant.is_empty() {
+        return 0.0;
+    }
+
+    let found = relevant
+        .iter()
+        .filter(|id| ranking.iter().take(k).any(|ranked| ranked == *id))
+        .count();
+
+    found as f64 / relevant.len() as f64
+}
+
+/// One over the position of the first relevant dataset, or zero when there's none.
+pub fn reciprocal_rank(ranking: &[Uuid], relevant: &[Uuid]) -> f64 {
+    match ranking.iter().position(|id| relevant.contains(id)) {
+        Some(position) => 1.0 / (position + 1) as f64,
+        None => 0.0,
+    }
+}
+
+/// Runs every question through the reranker, one at a time so hosted rerankers aren't rate
+/// limited. `documents` are the datasets' DDL, in the same order as `dataset_ids`.
+pub async fn evaluate_reranker(
+    reranker: Reranker,
+    questions: &[LabelledQuestion],
+    dataset_ids: &[Uuid],
+    documents: &[String],
+    user_id: &Uuid,
+) -> RerankerEvaluation {
+    let session_id = Uuid::new_v4();
+
+    let mut errors = 0;
+    let mut recall_at_1 = 0.0;
+    let mut recall_at_5 = 0.0;
+    let mut recall_at_top_n = 0.0;
+    let mut reciprocal_ranks = 0.0;
+
+    let start_time = Instant::now();
+
+    for question in questions {
+        let ranking = match reranker
+            .rerank(
+                &question.question,
+                documents,
+                RERANK_TOP_N,
+                user_id,
+                &session_id,
+                LlmUsageScope::default(),
+            )
+            .await
+        {
+            Ok(ranking) => ranking
+                .into_iter()
+                .map(|index| dataset_ids[index])
+                .collect::<Vec<Uuid>>(),
+            Err(e) => {
+                tracing::warn!("{:?} failed on \"{}\": {}", reranker, question.question, e);
+                errors += 1;
+                continue;
+            }
+        };
+
+        recall_at_1 += recall_at(&ranking, &question.dataset_ids, 1);
+        recall_at_5 += recall_at(&ranking, &question.dataset_ids, 5);
+        recall_at_top_n += recall_at(&ranking, &question.dataset_ids, RERANK_TOP_N);
+        reciprocal_ranks += reciprocal_rank(&ranking, &question.dataset_ids);
+    }
+
+    let question_count = questions.len().max(1) as f64;
+
+    RerankerEvaluation {
+        reranker,
+        questions: questions.len(),
+        errors,
+        recall_at_1: recall_at_1 / question_count,
+        recall_at_5: recall_at_5 / question_count,
+        recall_at_top_n: recall_at_top_n / question_count,
+        mean_reciprocal_rank: reciprocal_ranks / question_count,
+        mean_latency_ms: start_time.elapsed().as_millis() as f64 / question_count,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_rankings_against_labels() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let ranking = vec![c, a, b];
+
+        assert_eq!(recall_at(&ranking, &[a], 1), 0.0);
+        assert_eq!(recall_at(&ranking, &[a, b], 2), 0.5);
+        assert_eq!(recall_at(&ranking, &[a, b], 5), 1.0);
+        assert_eq!(reciprocal_rank(&ranking, &[a, b]), 0.5);
+        assert_eq!(reciprocal_rank(&ranking[..1], &[a]), 0.0);
+    }
+}
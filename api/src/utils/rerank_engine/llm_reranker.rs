@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::utils::clients::ai::{
+    langfuse::PromptName,
+    llm_router::{llm_chat, LlmMessage, LlmModel, LlmRole},
+    llm_usage::LlmUsageScope,
+    openai::OpenAiChatModel,
+};
+
+/// Keeps the prompt a manageable size when there are many datasets.
+const MAX_DOCUMENT_CHARS: usize = 2_000;
+
+#[derive(Deserialize, Debug)]
+struct LlmRerankResponse {
+    ranking: Vec<usize>,
+}
+
+/// Keeps the model's ranking, minus numbers it made up or repeated, and fills the rest with the
+/// datasets it left out in their original order.
+fn complete_ranking(ranking: Vec<usize>, document_count: usize, top_n: usize) -> Vec<usize> {
+    let mut seen = HashSet::new();
+
+    ranking
+        .into_iter()
+        .chain(0..document_count)
+        .filter(|index| *index < document_count && seen.insert(*index))
+        .take(top_n)
+        .collect()
+}
+
+/// Asks the LLM to rank all of the datasets in one prompt.
+pub async fn llm_rerank(
+    query: &str,
+    documents: &[String],
+    top_n: usize,
+    user_id: &Uuid,
+    session_id: &Uuid,
+    usage_scope: LlmUsageScope,
+) -> Result<Vec<usize>> {
+    let formatted_documents = documents
+        .iter()
+        .enumerate()
+        .map(|(index, document)| {
+            format!(
+                "[{}]\n{}",
+                index,
+                document
+                    .chars()
+                    .take(MAX_DOCUMENT_CHARS)
+                    .collect::<String>()
+            )
+        })
+        .collect::<Vec<String>>();
+
+    let system_prompt = format!(
+        "### DATASETS
+{datasets}
+
+### TASK
+Rank the datasets by how useful they are for answering the user's question, most useful first. Leave out datasets that can't help.
+
+### OUTPUT
+Output in json with the key of 'ranking' and the value of a list of at most {top_n} dataset numbers, e.g. {{\"ranking\": [3, 0, 7]}}",
+        datasets = formatted_documents.join("\n\n"),
+        top_n = top_n,
+    );
+
+    let user_prompt = format!(
+        "### QUESTION
+{}",
+        query
+    );
+
+    let messages = vec![
+        LlmMessage {
+            role: LlmRole::System,
+            content: system_prompt,
+        },
+        LlmMessage {
+            role: LlmRole::User,
+            content: user_prompt,
+        },
+    ];
+
+    let response = llm_chat(
+        LlmModel::OpenAi(OpenAiChatModel::Gpt4oMini),
+        &messages,
+        0.0,
+        500,
+        30,
+        None,
+        true,
+        None,
+        session_id,
+        user_id,
+        PromptName::RerankDatasets,
+        usage_scope,
+    )
+    .await?;
+
+    let response = match serde_json::from_str::<LlmRerankResponse>(&response) {
+        Ok(response) => response,
+        Err(e) => return Err(anyhow!("Unable to parse reranking from the LLM: {}", e)),
+    };
+
+    Ok(complete_ranking(response.ranking, documents.len(), top_n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_made_up_and_repeated_datasets() {
+        assert_eq!(complete_ranking(vec![2, 9, 2, 0], 4, 10), vec![2, 0, 1, 3]);
+        assert_eq!(complete_ranking(vec![3, 1], 4, 1), vec![3]);
+    }
+}
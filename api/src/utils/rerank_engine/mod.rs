@@ -0,0 +1,6 @@
+mod bm25_reranker;
+mod cohere_reranker;
+mod embedding_reranker;
+pub mod evaluation;
+mod llm_reranker;
+pub mod rerank_engine;
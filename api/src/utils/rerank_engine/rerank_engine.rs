@@ -0,0 +1,166 @@
+use std::env;
+
+use anyhow::{anyhow, Result};
+use diesel::{ExpressionMethods, JoinOnDsl, QueryDsl};
+use diesel_async::RunQueryDsl;
+use once_cell::sync::Lazy;
+use uuid::Uuid;
+
+use crate::{
+    database::{
+        enums::Reranker,
+        lib::get_pg_pool,
+        schema::{reranker_settings, users_to_organizations},
+    },
+    utils::clients::{ai::llm_usage::LlmUsageScope, sentry_utils::send_sentry_error},
+};
+
+use super::{
+    bm25_reranker::bm25_rerank, cohere_reranker::cohere_rerank,
+    embedding_reranker::embedding_rerank, llm_reranker::llm_rerank,
+};
+
+/// How many datasets are passed on to the agents.
+pub const RERANK_TOP_N: usize = 20;
+
+/// Used by organizations that haven't picked a reranker. `RERANKER` wins, then Cohere when
+/// there's a key for it, so deployments without one still get BM25.
+static DEFAULT_RERANKER: Lazy<Reranker> = Lazy::new(|| {
+    if let Some(reranker) = env::var("RERANKER")
+        .ok()
+        .and_then(|name| Reranker::from_name(&name))
+    {
+        return reranker;
+    }
+
+    match env::var("COHERE_API_KEY") {
+        Ok(api_key) if !api_key.is_empty() => Reranker::Cohere,
+        _ => Reranker::Bm25,
+    }
+});
+
+impl Reranker {
+    pub fn from_name(name: &str) -> Option<Reranker> {
+        match name {
+            "cohere" => Some(Reranker::Cohere),
+            "embedding" => Some(Reranker::Embedding),
+            "llm" => Some(Reranker::Llm),
+            "bm25" => Some(Reranker::Bm25),
+            _ => None,
+        }
+    }
+
+    pub fn all() -> Vec<Reranker> {
+        vec![
+            Reranker::Cohere,
+            Reranker::Embedding,
+            Reranker::Llm,
+            Reranker::Bm25,
+        ]
+    }
+
+    /// Returns the indexes of the most relevant documents, most relevant first. The user and
+    /// session are only used by the LLM reranker, which is traced and billed like other prompts.
+    pub async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+        top_n: usize,
+        user_id: &Uuid,
+        session_id: &Uuid,
+        usage_scope: LlmUsageScope,
+    ) -> Result<Vec<usize>> {
+        if documents.is_empty() {
+            return Ok(vec![]);
+        }
+
+        match self {
+            Reranker::Cohere => cohere_rerank(query, documents, top_n).await,
+            Reranker::Embedding => embedding_rerank(query, documents, top_n).await,
+            Reranker::Llm => {
+                llm_rerank(query, documents, top_n, user_id, session_id, usage_scope).await
+            }
+            Reranker::Bm25 => Ok(bm25_rerank(query, documents, top_n)),
+        }
+    }
+}
+
+/// Orders indexes by score, highest first. Ties keep their original order.
+pub fn rank_by_scores(scores: &[f64], top_n: usize) -> Vec<usize> {
+    let mut ranking = (0..scores.len()).collect::<Vec<usize>>();
+    ranking.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+    ranking.truncate(top_n);
+    ranking
+}
+
+/// The reranker the user's organization picked, or the default one.
+pub async fn organization_reranker(user_id: &Uuid) -> Result<Reranker> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match reranker_settings::table
+        .inner_join(
+            users_to_organizations::table
+                .on(users_to_organizations::organization_id.eq(reranker_settings::organization_id)),
+        )
+        .filter(users_to_organizations::user_id.eq(user_id))
+        .filter(users_to_organizations::deleted_at.is_null())
+        .filter(reranker_settings::deleted_at.is_null())
+        .select(reranker_settings::reranker)
+        .first::<Reranker>(&mut conn)
+        .await
+    {
+        Ok(reranker) => Ok(reranker),
+        Err(diesel::NotFound) => Ok(*DEFAULT_RERANKER),
+        Err(e) => Err(anyhow!("Error getting reranker: {}", e)),
+    }
+}
+
+/// Reranks with the organization's reranker. When it fails, BM25 is used instead so the
+/// documents are still ranked against the query.
+pub async fn rerank_documents(
+    query: &str,
+    documents: &[String],
+    top_n: usize,
+    user_id: &Uuid,
+    session_id: &Uuid,
+    usage_scope: LlmUsageScope,
+) -> Vec<usize> {
+    let reranker = match organization_reranker(user_id).await {
+        Ok(reranker) => reranker,
+        Err(e) => {
+            tracing::error!("Error getting reranker: {:?}", e);
+            *DEFAULT_RERANKER
+        }
+    };
+
+    match reranker
+        .rerank(query, documents, top_n, user_id, session_id, usage_scope)
+        .await
+    {
+        Ok(ranking) => ranking,
+        Err(e) => {
+            let err = anyhow!(
+                "Error reranking with {:?}, falling back to BM25: {}",
+                reranker,
+                e
+            );
+            tracing::error!("{}", err);
+            send_sentry_error(&err.to_string(), Some(user_id));
+            bm25_rerank(query, documents, top_n)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_by_score_keeping_ties_in_order() {
+        assert_eq!(rank_by_scores(&[0.1, 0.9, 0.5, 0.9], 3), vec![1, 3, 2]);
+        assert_eq!(rank_by_scores(&[0.0, 0.0], 5), vec![0, 1]);
+    }
+}
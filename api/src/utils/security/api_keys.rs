@@ -0,0 +1,354 @@
+use std::{
+    env,
+    net::{IpAddr, SocketAddr},
+};
+
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::{HeaderMap, Method},
+};
+use chrono::{DateTime, Utc};
+use diesel::{BoolExpressionMethods, ExpressionMethods, JoinOnDsl, QueryDsl};
+use diesel_async::RunQueryDsl;
+use lazy_static::lazy_static;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    models::{ApiKey, User},
+    schema::{api_keys, users},
+};
+
+/// Keys we issue start with this so they can be told apart from JWTs without decoding them.
+pub const API_KEY_PREFIX: &str = "bst_";
+
+const API_KEY_SECRET_LENGTH: usize = 40;
+
+/// Length of the start of a key that is kept in plain text so users can tell their keys apart.
+const DISPLAY_PREFIX_LENGTH: usize = 12;
+
+/// `last_used_at` is only written once a minute for a key that is in constant use.
+const LAST_USED_RESOLUTION_SECONDS: i64 = 60;
+
+lazy_static! {
+    /// Addresses of the load balancers in front of the API, comma separated.
+    static ref TRUSTED_PROXIES: Vec<IpAddr> = env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(|proxy| proxy.trim())
+        .filter(|proxy| !proxy.is_empty())
+        .map(|proxy| {
+            proxy
+                .parse()
+                .expect("TRUSTED_PROXIES must be a comma separated list of IP addresses")
+        })
+        .collect();
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiKeyScope {
+    /// Everything the owner can do. Keys issued before scopes existed have this scope.
+    #[serde(rename = "*")]
+    All,
+    #[serde(rename = "datasets:read")]
+    DatasetsRead,
+    #[serde(rename = "datasets:write")]
+    DatasetsWrite,
+    #[serde(rename = "datasets:deploy")]
+    DatasetsDeploy,
+    #[serde(rename = "data_sources:write")]
+    DataSourcesWrite,
+    #[serde(rename = "permissions:write")]
+    PermissionsWrite,
+    #[serde(rename = "sql:run")]
+    SqlRun,
+    #[serde(rename = "threads:read")]
+    ThreadsRead,
+    #[serde(rename = "threads:write")]
+    ThreadsWrite,
+    #[serde(rename = "dashboards:read")]
+    DashboardsRead,
+    #[serde(rename = "dashboards:write")]
+    DashboardsWrite,
+    /// Provisioning users and teams over SCIM. Unlike the other scopes it is not included in `*`,
+    /// so it has to be granted explicitly.
+    #[serde(rename = "scim")]
+    Scim,
+}
+
+/// Added to the request extensions when a request is authenticated with an API key. Requests made
+/// with a user session have no `ApiKeyAccess` and are not limited by scopes.
+#[derive(Debug, Clone)]
+pub struct ApiKeyAccess {
+    pub api_key_id: Uuid,
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+impl ApiKeyAccess {
+    pub fn from_api_key(api_key: &ApiKey) -> Self {
+        ApiKeyAccess {
+            api_key_id: api_key.id,
+            scopes: api_key_scopes(api_key),
+        }
+    }
+
+    pub fn allows(&self, scope: &ApiKeyScope) -> bool {
+        self.scopes.iter().any(|granted| {
+            granted == scope || (*granted == ApiKeyScope::All && *scope != ApiKeyScope::Scim)
+        })
+    }
+}
+
+pub fn api_key_scopes(api_key: &ApiKey) -> Vec<ApiKeyScope> {
+    serde_json::from_value(api_key.scopes.clone()).unwrap_or_default()
+}
+
+pub fn generate_api_key() -> String {
+    let secret: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(API_KEY_SECRET_LENGTH)
+        .map(char::from)
+        .collect();
+
+    format!("{}{}", API_KEY_PREFIX, secret)
+}
+
+/// Only this hash of a key is stored.
+pub fn hash_api_key(api_key: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(api_key.as_bytes());
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn api_key_display_prefix(api_key: &str) -> String {
+    api_key.chars().take(DISPLAY_PREFIX_LENGTH).collect()
+}
+
+/// The scope an API key needs for a REST route, by the path below `/api/v1`. Routes that are not
+/// listed need the `*` scope, so new routes are closed to scoped keys until they are added here.
+/// The websocket is checked per message instead, see `WsRoutes::api_key_scope`.
+pub fn required_rest_scope(method: &Method, path: &str) -> Option<ApiKeyScope> {
+    let path = path.strip_prefix("/api/v1").unwrap_or(path);
+    let first_segment = path.split('/').nth(1).unwrap_or_default();
+
+    match first_segment {
+        "ws" => None,
+        "datasets" if path == "/datasets/deploy" => Some(ApiKeyScope::DatasetsDeploy),
+        "datasets" if method == Method::GET => Some(ApiKeyScope::DatasetsRead),
+        "datasets" => Some(ApiKeyScope::DatasetsWrite),
+        "data_sources" => Some(ApiKeyScope::DataSourcesWrite),
+        "permission_groups" | "dataset_groups" => Some(ApiKeyScope::PermissionsWrite),
+        "sql" => Some(ApiKeyScope::SqlRun),
+        _ => Some(ApiKeyScope::All),
+    }
+}
+
+/// Looks up the owner of an active key. Revoked and expired keys are not found.
+pub async fn find_api_key(api_key: &str) -> Result<Option<(User, ApiKey)>> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match users::table
+        .inner_join(api_keys::table.on(api_keys::owner_id.eq(users::id)))
+        .filter(api_keys::key_hash.eq(hash_api_key(api_key)))
+        .filter(api_keys::deleted_at.is_null())
+        .filter(
+            api_keys::expires_at
+                .is_null()
+                .or(api_keys::expires_at.gt(Utc::now())),
+        )
+        .select((users::all_columns, api_keys::all_columns))
+        .first::<(User, ApiKey)>(&mut conn)
+        .await
+    {
+        Ok(user_and_api_key) => Ok(Some(user_and_api_key)),
+        Err(diesel::NotFound) => Ok(None),
+        Err(e) => Err(anyhow!("Error querying API key: {}", e)),
+    }
+}
+
+pub async fn record_api_key_use(api_key_id: &Uuid, ip: Option<String>) -> Result<()> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let used_before: DateTime<Utc> =
+        Utc::now() - chrono::Duration::seconds(LAST_USED_RESOLUTION_SECONDS);
+
+    match diesel::update(api_keys::table)
+        .filter(api_keys::id.eq(api_key_id))
+        .filter(
+            api_keys::last_used_at
+                .is_null()
+                .or(api_keys::last_used_at.lt(used_before)),
+        )
+        .set((
+            api_keys::last_used_at.eq(Some(Utc::now())),
+            api_keys::last_used_ip.eq(ip),
+        ))
+        .execute(&mut conn)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(anyhow!("Error recording API key use: {}", e)),
+    }
+}
+
+/// The address of the client that made the request.
+///
+/// Forwarding headers are only honoured when the connection comes from one of the proxies in
+/// `TRUSTED_PROXIES`, otherwise anyone could pick the address we record for their API key.
+pub fn client_ip(headers: &HeaderMap, peer: Option<IpAddr>) -> Option<String> {
+    resolve_client_ip(headers, peer, &TRUSTED_PROXIES)
+}
+
+/// The address of the socket the request arrived on.
+pub fn peer_ip(req: &Request) -> Option<IpAddr> {
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|connect_info| connect_info.0.ip())
+}
+
+fn resolve_client_ip(
+    headers: &HeaderMap,
+    peer: Option<IpAddr>,
+    trusted_proxies: &[IpAddr],
+) -> Option<String> {
+    let peer = peer?;
+
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer.to_string());
+    }
+
+    // Each proxy appends the address it received the request from, so the right-most entry
+    // that is not one of our own proxies is the client.
+    let forwarded_for = headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            value
+                .rsplit(',')
+                .map(|hop| hop.trim().parse::<IpAddr>())
+                .take_while(|hop| hop.is_ok())
+                .filter_map(|hop| hop.ok())
+                .find(|hop| !trusted_proxies.contains(hop))
+        });
+
+    let real_ip = || {
+        headers
+            .get("x-real-ip")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<IpAddr>().ok())
+    };
+
+    Some(forwarded_for.or_else(real_ip).unwrap_or(peer).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_keys_are_prefixed_and_unique() {
+        let api_key = generate_api_key();
+
+        assert!(api_key.starts_with(API_KEY_PREFIX));
+        assert_eq!(api_key.len(), API_KEY_PREFIX.len() + API_KEY_SECRET_LENGTH);
+        assert_ne!(api_key, generate_api_key());
+        assert_eq!(api_key_display_prefix(&api_key), api_key[..12]);
+    }
+
+    #[test]
+    fn hashes_are_stable_hex() {
+        assert_eq!(hash_api_key("bst_abc"), hash_api_key("bst_abc"));
+        assert_ne!(hash_api_key("bst_abc"), hash_api_key("bst_abd"));
+        assert_eq!(hash_api_key("bst_abc").len(), 64);
+    }
+
+    #[test]
+    fn scopes_round_trip_as_strings() {
+        let scopes: Vec<ApiKeyScope> =
+            serde_json::from_value(serde_json::json!(["datasets:deploy", "*"])).unwrap();
+
+        assert_eq!(scopes, vec![ApiKeyScope::DatasetsDeploy, ApiKeyScope::All]);
+    }
+
+    #[test]
+    fn deploy_only_keys_can_only_deploy() {
+        let access = ApiKeyAccess {
+            api_key_id: Uuid::new_v4(),
+            scopes: vec![ApiKeyScope::DatasetsDeploy],
+        };
+
+        let deploy = required_rest_scope(&Method::POST, "/api/v1/datasets/deploy").unwrap();
+        let list = required_rest_scope(&Method::GET, "/api/v1/datasets").unwrap();
+        let run_sql = required_rest_scope(&Method::POST, "/api/v1/sql/run").unwrap();
+        let users = required_rest_scope(&Method::GET, "/api/v1/users").unwrap();
+
+        assert!(access.allows(&deploy));
+        assert!(!access.allows(&list));
+        assert!(!access.allows(&run_sql));
+        assert!(!access.allows(&users));
+        assert_eq!(required_rest_scope(&Method::GET, "/api/v1/ws"), None);
+    }
+
+    #[test]
+    fn full_access_allows_everything() {
+        let access = ApiKeyAccess {
+            api_key_id: Uuid::new_v4(),
+            scopes: vec![ApiKeyScope::All],
+        };
+
+        assert!(access.allows(&ApiKeyScope::ThreadsRead));
+        assert!(access.allows(&ApiKeyScope::All));
+        assert!(!access.allows(&ApiKeyScope::Scim));
+    }
+
+    #[test]
+    fn forwarded_headers_from_untrusted_peers_are_ignored() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "1.2.3.4".parse().unwrap());
+        headers.insert("x-real-ip", "1.2.3.4".parse().unwrap());
+
+        let peer = "203.0.113.7".parse().ok();
+
+        assert_eq!(
+            resolve_client_ip(&headers, peer, &[]).as_deref(),
+            Some("203.0.113.7")
+        );
+        assert_eq!(resolve_client_ip(&headers, None, &[]), None);
+    }
+
+    #[test]
+    fn forwarded_for_skips_trusted_hops_from_the_right() {
+        let trusted: Vec<IpAddr> = vec!["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()];
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            "6.6.6.6, 198.51.100.4, 10.0.0.2".parse().unwrap(),
+        );
+
+        assert_eq!(
+            resolve_client_ip(&headers, Some(trusted[0]), &trusted).as_deref(),
+            Some("198.51.100.4")
+        );
+
+        headers.insert("x-forwarded-for", "not-an-ip, 10.0.0.2".parse().unwrap());
+        assert_eq!(
+            resolve_client_ip(&headers, Some(trusted[0]), &trusted).as_deref(),
+            Some("10.0.0.1")
+        );
+    }
+}
@@ -1,6 +1,13 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
 use anyhow::Result;
 use diesel::{ExpressionMethods, QueryDsl};
 use diesel_async::RunQueryDsl;
+use once_cell::sync::Lazy;
 use uuid::Uuid;
 
 use crate::database::{
@@ -10,6 +17,19 @@ use crate::database::{
     schema::users_to_organizations,
 };
 
+/// How long a user's memberships are trusted before they are read again. Changes made through this
+/// instance are seen straight away; this bounds how long other instances take to notice them.
+const MEMBERSHIPS_CACHE_TTL: Duration = Duration::from_secs(30);
+/// Expired entries are swept out once the cache grows past this many users.
+const MEMBERSHIPS_CACHE_SWEEP_SIZE: usize = 10_000;
+
+type Membership = (Uuid, UserOrganizationStatus, bool);
+
+/// Every request checks whether its user is deactivated, so memberships are cached rather than
+/// read from Postgres each time.
+static MEMBERSHIPS_CACHE: Lazy<Mutex<HashMap<Uuid, (Instant, Vec<Membership>)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
 /// Checks if a user has workspace admin or data admin privileges
 ///
 /// # Arguments
@@ -37,3 +57,162 @@ pub async fn is_user_workspace_admin_or_data_admin(user_id: &Uuid) -> Result<boo
     Ok(user.role == UserOrganizationRole::WorkspaceAdmin
         || user.role == UserOrganizationRole::DataAdmin)
 }
+
+/// Checks if a user has workspace admin privileges
+///
+/// # Arguments
+/// * `user_id` - UUID of the user to check permissions for
+///
+/// # Returns
+/// * `bool` - True if user is workspace admin, false otherwise
+///
+/// # Errors
+/// * Database connection errors
+/// * User not found errors
+pub async fn is_user_workspace_admin(user_id: &Uuid) -> Result<bool> {
+    let mut conn = get_pg_pool().get().await.map_err(|e| anyhow::anyhow!(e))?;
+
+    let user = users_to_organizations::table
+        .filter(users_to_organizations::user_id.eq(user_id))
+        .filter(users_to_organizations::status.eq(UserOrganizationStatus::Active))
+        .filter(users_to_organizations::deleted_at.is_null())
+        .first::<UserToOrganization>(&mut conn)
+        .await?;
+
+    Ok(user.role == UserOrganizationRole::WorkspaceAdmin)
+}
+
+/// Checks if a user has been deactivated, e.g. by SCIM provisioning
+///
+/// A user is deactivated when they have been a member of an organization but no longer have a
+/// membership that isn't inactive or removed. Users that never joined an organization are not.
+/// Sessions scoped to an organization, e.g. from signing in with its identity provider, need an
+/// active membership of that organization.
+///
+/// # Arguments
+/// * `user_id` - UUID of the user to check
+/// * `session_organization_id` - The organization the session is scoped to, if any
+///
+/// # Returns
+/// * `bool` - True if the user should no longer be let in, false otherwise
+///
+/// # Errors
+/// * Database connection errors
+pub async fn is_user_deactivated(
+    user_id: &Uuid,
+    session_organization_id: Option<&Uuid>,
+) -> Result<bool> {
+    let memberships = match cached_memberships(user_id) {
+        Some(memberships) => memberships,
+        None => {
+            let mut conn = get_pg_pool().get().await.map_err(|e| anyhow::anyhow!(e))?;
+
+            let memberships = users_to_organizations::table
+                .filter(users_to_organizations::user_id.eq(user_id))
+                .select((
+                    users_to_organizations::organization_id,
+                    users_to_organizations::status,
+                    users_to_organizations::deleted_at.is_null(),
+                ))
+                .load::<Membership>(&mut conn)
+                .await?;
+
+            cache_memberships(user_id, memberships.clone());
+
+            memberships
+        }
+    };
+
+    Ok(memberships_are_deactivated(
+        &memberships,
+        session_organization_id,
+    ))
+}
+
+/// Drops the cached memberships of a user. Call this after changing their status in, or removing
+/// them from, an organization so the change applies to their next request.
+pub fn forget_user_memberships(user_id: &Uuid) {
+    if let Ok(mut cache) = MEMBERSHIPS_CACHE.lock() {
+        cache.remove(user_id);
+    }
+}
+
+fn cached_memberships(user_id: &Uuid) -> Option<Vec<Membership>> {
+    let cache = MEMBERSHIPS_CACHE.lock().ok()?;
+
+    match cache.get(user_id) {
+        Some((cached_at, memberships)) if cached_at.elapsed() < MEMBERSHIPS_CACHE_TTL => {
+            Some(memberships.clone())
+        }
+        _ => None,
+    }
+}
+
+fn cache_memberships(user_id: &Uuid, memberships: Vec<Membership>) {
+    if let Ok(mut cache) = MEMBERSHIPS_CACHE.lock() {
+        if cache.len() >= MEMBERSHIPS_CACHE_SWEEP_SIZE {
+            cache.retain(|_, (cached_at, _)| cached_at.elapsed() < MEMBERSHIPS_CACHE_TTL);
+        }
+
+        cache.insert(*user_id, (Instant::now(), memberships));
+    }
+}
+
+fn memberships_are_deactivated(
+    memberships: &[Membership],
+    session_organization_id: Option<&Uuid>,
+) -> bool {
+    let is_active = |(organization_id, status, is_current): &Membership| {
+        *is_current
+            && *status != UserOrganizationStatus::Inactive
+            && (session_organization_id.is_none()
+                || session_organization_id == Some(organization_id))
+    };
+
+    match session_organization_id {
+        Some(_) => !memberships.iter().any(is_active),
+        None => !memberships.is_empty() && !memberships.iter().any(is_active),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_scoped_to_an_organization_need_to_be_active_in_it() {
+        let acme = Uuid::new_v4();
+        let globex = Uuid::new_v4();
+
+        let memberships = vec![
+            (acme, UserOrganizationStatus::Inactive, true),
+            (globex, UserOrganizationStatus::Active, true),
+        ];
+
+        assert!(!memberships_are_deactivated(&memberships, None));
+        assert!(!memberships_are_deactivated(&memberships, Some(&globex)));
+        assert!(memberships_are_deactivated(&memberships, Some(&acme)));
+        assert!(memberships_are_deactivated(&[], Some(&acme)));
+        assert!(!memberships_are_deactivated(&[], None));
+    }
+
+    #[test]
+    fn cached_memberships_expire_and_can_be_forgotten() {
+        let user_id = Uuid::new_v4();
+        let memberships = vec![(Uuid::new_v4(), UserOrganizationStatus::Active, true)];
+
+        assert_eq!(cached_memberships(&user_id), None);
+
+        cache_memberships(&user_id, memberships.clone());
+        assert_eq!(cached_memberships(&user_id), Some(memberships.clone()));
+
+        forget_user_memberships(&user_id);
+        assert_eq!(cached_memberships(&user_id), None);
+
+        MEMBERSHIPS_CACHE.lock().unwrap().insert(
+            user_id,
+            (Instant::now() - MEMBERSHIPS_CACHE_TTL, memberships),
+        );
+        assert_eq!(cached_memberships(&user_id), None);
+    }
+}
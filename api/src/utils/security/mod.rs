@@ -1,2 +1,5 @@
+pub mod api_keys;
 pub mod dataset_security;
-pub mod checks;
\ No newline at end of file
+pub mod checks;
+pub mod sso;
+pub mod webhook_signatures;
\ No newline at end of file
@@ -0,0 +1,267 @@
+pub mod oidc;
+pub mod provisioning;
+pub mod saml;
+
+use anyhow::{anyhow, Result};
+use axum::http::{header, HeaderMap};
+use chrono::Utc;
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use uuid::Uuid;
+
+use self::{oidc::OidcConfig, saml::SamlConfig};
+
+lazy_static::lazy_static! {
+    static ref JWT_SECRET: String = env::var("JWT_SECRET").expect("JWT_SECRET is not set");
+    static ref BUSTER_API_URL: String = env::var("BUSTER_API_URL").expect("BUSTER_API_URL must be set");
+    static ref BUSTER_URL: String = env::var("BUSTER_URL").expect("BUSTER_URL must be set");
+}
+
+/// How long a sign in with an identity provider lasts before the user has to sign in again.
+const SSO_SESSION_SECONDS: i64 = 60 * 60 * 12;
+
+/// How long a user has to finish signing in at the identity provider.
+const SSO_STATE_SECONDS: i64 = 60 * 10;
+
+const SSO_STATE_AUDIENCE: &str = "sso";
+
+/// Holds the secret that ties a sign in to the browser that started it.
+const SSO_BROWSER_COOKIE: &str = "buster_sso";
+
+/// The audience the `auth` middleware accepts for user sessions.
+const SESSION_AUDIENCE: &str = "authenticated";
+
+/// An organization's identity provider, stored in `identity_providers.config`. Each protocol
+/// knows how to send the user to the provider and how to read who signed in from its response.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "protocol", rename_all = "lowercase")]
+pub enum IdentityProviderConfig {
+    Oidc(OidcConfig),
+    Saml(SamlConfig),
+}
+
+/// Members of `group` at the identity provider are put on the team, and taken off it when they
+/// leave the group.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SsoGroupMapping {
+    pub group: String,
+    pub team_id: Uuid,
+}
+
+/// Who the identity provider says signed in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SsoIdentity {
+    pub email: String,
+    pub name: Option<String>,
+    pub groups: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SsoState {
+    aud: String,
+    sub: String,
+    nonce: String,
+    /// Hash of the secret in the browser's `buster_sso` cookie.
+    browser: String,
+    exp: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SessionClaims {
+    aud: String,
+    sub: String,
+    /// The organization the user signed in to. The `auth` middleware only accepts the session
+    /// while the user is still an active member of it.
+    organization_id: Uuid,
+    iat: i64,
+    exp: i64,
+}
+
+/// Where the identity provider sends the user back to, e.g. `callback` for OIDC and `acs` for
+/// SAML.
+pub fn sso_endpoint_url(provider_id: &Uuid, endpoint: &str) -> String {
+    format!(
+        "{}/auth/sso/{}/{}",
+        BUSTER_API_URL.trim_end_matches('/'),
+        provider_id,
+        endpoint
+    )
+}
+
+pub fn sso_nonce() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+/// What a browser secret is sent through the identity provider as. Only the browser knows the
+/// secret itself, so someone who starts a sign in can't have another browser finish it.
+pub fn sso_browser_binding(browser_secret: &str) -> String {
+    hex::encode(Sha256::digest(browser_secret.as_bytes()))
+}
+
+/// Sets the browser secret for a sign in with the provider. SAML responses are posted back
+/// from the identity provider's site, so the cookie has to be sent on cross-site requests.
+pub fn sso_browser_cookie(provider_id: &Uuid, browser_secret: &str, cross_site: bool) -> String {
+    let secure = BUSTER_API_URL.starts_with("https://");
+
+    let same_site = match (cross_site, secure) {
+        (true, true) => "None",
+        _ => "Lax",
+    };
+
+    format!(
+        "{}={}; Path=/auth/sso/{}; Max-Age={}; HttpOnly; SameSite={}{}",
+        SSO_BROWSER_COOKIE,
+        browser_secret,
+        provider_id,
+        SSO_STATE_SECONDS,
+        same_site,
+        if secure { "; Secure" } else { "" }
+    )
+}
+
+/// Removes the browser secret once the sign in is finished, so it can't be used again.
+pub fn clear_sso_browser_cookie(provider_id: &Uuid) -> String {
+    format!(
+        "{}=; Path=/auth/sso/{}; Max-Age=0; HttpOnly",
+        SSO_BROWSER_COOKIE, provider_id
+    )
+}
+
+pub fn read_sso_browser_cookie(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SSO_BROWSER_COOKIE)
+        .map(|(_, value)| value.to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// The state sent through the identity provider is signed rather than stored, so the sign in
+/// can be finished by any API instance. It carries the OIDC nonce or the SAML request id, and
+/// is bound to the browser secret set in the `buster_sso` cookie when the sign in started.
+pub fn sign_sso_state(provider_id: &Uuid, nonce: &str, browser_secret: &str) -> Result<String> {
+    let state = SsoState {
+        aud: SSO_STATE_AUDIENCE.to_string(),
+        sub: provider_id.to_string(),
+        nonce: nonce.to_string(),
+        browser: sso_browser_binding(browser_secret),
+        exp: Utc::now().timestamp() + SSO_STATE_SECONDS,
+    };
+
+    match encode(
+        &Header::new(Algorithm::HS256),
+        &state,
+        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+    ) {
+        Ok(state) => Ok(state),
+        Err(e) => Err(anyhow!("Error signing SSO state: {}", e)),
+    }
+}
+
+/// Returns the nonce of a state signed for the provider, if the browser finishing the sign in
+/// is the one that started it.
+pub fn verify_sso_state(state: &str, provider_id: &Uuid, browser_secret: &str) -> Result<String> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_audience(&[SSO_STATE_AUDIENCE]);
+
+    let state = match decode::<SsoState>(
+        state,
+        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
+        &validation,
+    ) {
+        Ok(state) => state.claims,
+        Err(e) => return Err(anyhow!("Invalid SSO state: {}", e)),
+    };
+
+    if state.sub != provider_id.to_string() {
+        return Err(anyhow!("SSO state is for another identity provider"));
+    }
+
+    if state.browser != sso_browser_binding(browser_secret) {
+        return Err(anyhow!("SSO state was started in another browser"));
+    }
+
+    Ok(state.nonce)
+}
+
+/// Signs the user in the same way Supabase does, so the `auth` middleware accepts the session
+/// without knowing how the user signed in. The session is scoped to the provider's organization.
+pub fn issue_session_token(user_id: &Uuid, organization_id: &Uuid) -> Result<String> {
+    let now = Utc::now().timestamp();
+
+    let claims = SessionClaims {
+        aud: SESSION_AUDIENCE.to_string(),
+        sub: user_id.to_string(),
+        organization_id: *organization_id,
+        iat: now,
+        exp: now + SSO_SESSION_SECONDS,
+    };
+
+    match encode(
+        &Header::new(Algorithm::HS256),
+        &claims,
+        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+    ) {
+        Ok(token) => Ok(token),
+        Err(e) => Err(anyhow!("Error signing session token: {}", e)),
+    }
+}
+
+/// The web app reads the session from the fragment, which isn't sent to servers or logged.
+pub fn session_redirect_url(token: &str) -> String {
+    format!(
+        "{}/auth/sso/callback#access_token={}&token_type=bearer&expires_in={}",
+        BUSTER_URL.trim_end_matches('/'),
+        token,
+        SSO_SESSION_SECONDS
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn states_can_only_be_finished_by_the_browser_that_started_them() {
+        env::set_var(
+            "JWT_SECRET",
+            "super-secret-jwt-token-with-at-least-32-characters",
+        );
+
+        let provider_id = Uuid::new_v4();
+        let browser_secret = sso_nonce();
+        let nonce = sso_browser_binding(&browser_secret);
+        let state = sign_sso_state(&provider_id, &nonce, &browser_secret).unwrap();
+
+        assert_eq!(
+            verify_sso_state(&state, &provider_id, &browser_secret).unwrap(),
+            nonce
+        );
+        assert!(verify_sso_state(&state, &provider_id, &sso_nonce()).is_err());
+        assert!(verify_sso_state(&state, &Uuid::new_v4(), &browser_secret).is_err());
+    }
+
+    #[test]
+    fn reads_the_browser_secret_from_cookies() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            "theme=dark; buster_sso=abc123; other=1".parse().unwrap(),
+        );
+
+        assert_eq!(read_sso_browser_cookie(&headers).as_deref(), Some("abc123"));
+
+        headers.insert(header::COOKIE, "buster_sso=".parse().unwrap());
+        assert_eq!(read_sso_browser_cookie(&headers), None);
+    }
+}
@@ -0,0 +1,329 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use diesel::{
+    define_sql_function, insert_into, sql_types::Text, update, upsert::excluded, ExpressionMethods,
+    OptionalExtension, QueryDsl,
+};
+use diesel_async::RunQueryDsl;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    database::{
+        enums::{SharingSetting, TeamToUserRole, UserOrganizationStatus},
+        lib::{get_pg_pool, UserConfig},
+        models::{IdentityProvider, TeamToUser, User, UserToOrganization},
+        schema::{organizations, teams_to_users, users, users_to_organizations},
+    },
+    utils::security::checks::forget_user_memberships,
+};
+
+use super::{SsoGroupMapping, SsoIdentity};
+
+define_sql_function!(fn lower(x: Text) -> Text);
+
+pub enum SsoSignIn {
+    SignedIn(Uuid),
+    /// The user was deactivated in the organization, e.g. over SCIM.
+    Deactivated,
+    /// The email is not at the organization's domain, so the provider can't vouch for it.
+    DomainNotAllowed,
+    /// The email belongs to a member of another organization. Signing in with the provider
+    /// would hand their account over to this one, so they have to be moved by an admin instead.
+    OtherOrganization,
+}
+
+/// Signs the identity in to the provider's organization. Users are created the first time they
+/// sign in and join with the provider's default role. Existing members keep their role, but
+/// their membership of mapped teams follows their groups at the provider.
+///
+/// Providers only sign in emails at their organization's domain, which is set by us when the
+/// organization is verified rather than by its admins. Existing users are only signed in if they
+/// don't belong to another organization.
+pub async fn provision_sso_user(
+    provider: &IdentityProvider,
+    identity: &SsoIdentity,
+) -> Result<SsoSignIn> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let organization_domain = match organizations::table
+        .filter(organizations::id.eq(provider.organization_id))
+        .select(organizations::domain)
+        .first::<Option<String>>(&mut conn)
+        .await
+    {
+        Ok(organization_domain) => organization_domain,
+        Err(e) => return Err(anyhow!("Error getting organization domain: {}", e)),
+    };
+
+    match organization_domain {
+        Some(domain) if email_is_at_domain(&identity.email, &domain) => (),
+        _ => return Ok(SsoSignIn::DomainNotAllowed),
+    }
+
+    let existing_user = match users::table
+        .filter(lower(users::email).eq(identity.email.to_lowercase()))
+        .first::<User>(&mut conn)
+        .await
+        .optional()
+    {
+        Ok(existing_user) => existing_user,
+        Err(e) => return Err(anyhow!("Error getting user: {}", e)),
+    };
+
+    let user_id = match existing_user {
+        Some(user) => {
+            let other_memberships = match users_to_organizations::table
+                .filter(users_to_organizations::user_id.eq(user.id))
+                .filter(users_to_organizations::organization_id.ne(provider.organization_id))
+                .filter(users_to_organizations::deleted_at.is_null())
+                .count()
+                .get_result::<i64>(&mut conn)
+                .await
+            {
+                Ok(other_memberships) => other_memberships,
+                Err(e) => return Err(anyhow!("Error getting user memberships: {}", e)),
+            };
+
+            if other_memberships > 0 {
+                return Ok(SsoSignIn::OtherOrganization);
+            }
+
+            if user.name.is_none() && identity.name.is_some() {
+                if let Err(e) = update(users::table)
+                    .filter(users::id.eq(user.id))
+                    .set((
+                        users::name.eq(&identity.name),
+                        users::updated_at.eq(Utc::now()),
+                    ))
+                    .execute(&mut conn)
+                    .await
+                {
+                    return Err(anyhow!("Error updating user: {}", e));
+                }
+            }
+
+            user.id
+        }
+        None => {
+            let user_config = UserConfig {
+                color_palettes: None,
+                last_used_color_palette: None,
+            };
+
+            let user = User {
+                id: Uuid::new_v4(),
+                email: identity.email.clone(),
+                name: identity.name.clone(),
+                config: json!(user_config),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                attributes: json!({}),
+            };
+
+            if let Err(e) = insert_into(users::table)
+                .values(&user)
+                .execute(&mut conn)
+                .await
+            {
+                return Err(anyhow!("Error creating user: {}", e));
+            }
+
+            user.id
+        }
+    };
+
+    let membership = match users_to_organizations::table
+        .filter(users_to_organizations::user_id.eq(user_id))
+        .filter(users_to_organizations::organization_id.eq(provider.organization_id))
+        .select((
+            users_to_organizations::status,
+            users_to_organizations::deleted_at.is_null(),
+        ))
+        .first::<(UserOrganizationStatus, bool)>(&mut conn)
+        .await
+        .optional()
+    {
+        Ok(membership) => membership,
+        Err(e) => return Err(anyhow!("Error getting user membership: {}", e)),
+    };
+
+    match membership {
+        Some((UserOrganizationStatus::Inactive, true)) => return Ok(SsoSignIn::Deactivated),
+        Some((_, true)) => (),
+        // Users removed from the organization join it again, as the provider still lets them in.
+        Some((_, false)) | None => {
+            let user_to_organization = UserToOrganization {
+                user_id,
+                organization_id: provider.organization_id,
+                role: provider.default_role,
+                sharing_setting: SharingSetting::Public,
+                edit_sql: true,
+                upload_csv: true,
+                export_assets: true,
+                email_slack_enabled: true,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                deleted_at: None,
+                created_by: provider.created_by,
+                updated_by: provider.created_by,
+                deleted_by: None,
+                status: UserOrganizationStatus::Active,
+            };
+
+            if let Err(e) = insert_into(users_to_organizations::table)
+                .values(&user_to_organization)
+                .on_conflict((
+                    users_to_organizations::user_id,
+                    users_to_organizations::organization_id,
+                ))
+                .do_update()
+                .set((
+                    users_to_organizations::role.eq(excluded(users_to_organizations::role)),
+                    users_to_organizations::status.eq(excluded(users_to_organizations::status)),
+                    users_to_organizations::deleted_at
+                        .eq(excluded(users_to_organizations::deleted_at)),
+                    users_to_organizations::deleted_by
+                        .eq(excluded(users_to_organizations::deleted_by)),
+                    users_to_organizations::updated_at
+                        .eq(excluded(users_to_organizations::updated_at)),
+                    users_to_organizations::updated_by
+                        .eq(excluded(users_to_organizations::updated_by)),
+                ))
+                .execute(&mut conn)
+                .await
+            {
+                return Err(anyhow!("Error adding user to organization: {}", e));
+            }
+
+            forget_user_memberships(&user_id);
+        }
+    }
+
+    let group_mappings: Vec<SsoGroupMapping> =
+        serde_json::from_value(provider.group_mappings.clone()).unwrap_or_default();
+
+    let (joined_teams, left_teams) = team_changes(&group_mappings, &identity.groups);
+
+    if !left_teams.is_empty() {
+        if let Err(e) = update(teams_to_users::table)
+            .filter(teams_to_users::user_id.eq(user_id))
+            .filter(teams_to_users::team_id.eq_any(&left_teams))
+            .filter(teams_to_users::deleted_at.is_null())
+            .set((
+                teams_to_users::deleted_at.eq(Some(Utc::now())),
+                teams_to_users::updated_at.eq(Utc::now()),
+            ))
+            .execute(&mut conn)
+            .await
+        {
+            return Err(anyhow!("Error removing user from teams: {}", e));
+        }
+    }
+
+    if !joined_teams.is_empty() {
+        let team_to_users = joined_teams
+            .iter()
+            .map(|team_id| TeamToUser {
+                team_id: *team_id,
+                user_id,
+                role: TeamToUserRole::Member,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                deleted_at: None,
+            })
+            .collect::<Vec<TeamToUser>>();
+
+        if let Err(e) = insert_into(teams_to_users::table)
+            .values(&team_to_users)
+            .on_conflict((teams_to_users::team_id, teams_to_users::user_id))
+            .do_update()
+            .set((
+                teams_to_users::deleted_at.eq(excluded(teams_to_users::deleted_at)),
+                teams_to_users::updated_at.eq(excluded(teams_to_users::updated_at)),
+            ))
+            .execute(&mut conn)
+            .await
+        {
+            return Err(anyhow!("Error adding user to teams: {}", e));
+        }
+    }
+
+    Ok(SsoSignIn::SignedIn(user_id))
+}
+
+fn email_is_at_domain(email: &str, domain: &str) -> bool {
+    match email.rsplit_once('@') {
+        Some((local_part, email_domain)) => {
+            !local_part.is_empty()
+                && email_domain.eq_ignore_ascii_case(domain.trim().trim_start_matches('@'))
+        }
+        None => false,
+    }
+}
+
+/// The mapped teams the user should be on and the ones they should be taken off. Teams that no
+/// group maps to are left alone.
+fn team_changes(mappings: &[SsoGroupMapping], groups: &[String]) -> (Vec<Uuid>, Vec<Uuid>) {
+    let mut joined_teams: Vec<Uuid> = Vec::new();
+
+    for mapping in mappings {
+        if groups.contains(&mapping.group) && !joined_teams.contains(&mapping.team_id) {
+            joined_teams.push(mapping.team_id);
+        }
+    }
+
+    let mut left_teams: Vec<Uuid> = Vec::new();
+
+    for mapping in mappings {
+        if !joined_teams.contains(&mapping.team_id) && !left_teams.contains(&mapping.team_id) {
+            left_teams.push(mapping.team_id);
+        }
+    }
+
+    (joined_teams, left_teams)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emails_have_to_be_at_the_organization_domain() {
+        assert!(email_is_at_domain("jane@Example.com", "example.com"));
+        assert!(!email_is_at_domain("jane@example.com.io", "example.com"));
+        assert!(!email_is_at_domain("jane@sub.example.com", "example.com"));
+        assert!(!email_is_at_domain("example.com", "example.com"));
+        assert!(!email_is_at_domain("@example.com", "example.com"));
+    }
+
+    #[test]
+    fn mapped_teams_follow_groups() {
+        let sales = Uuid::new_v4();
+        let finance = Uuid::new_v4();
+
+        let mappings = vec![
+            SsoGroupMapping {
+                group: "Sales".to_string(),
+                team_id: sales,
+            },
+            SsoGroupMapping {
+                group: "Sales EMEA".to_string(),
+                team_id: sales,
+            },
+            SsoGroupMapping {
+                group: "Finance".to_string(),
+                team_id: finance,
+            },
+        ];
+
+        assert_eq!(
+            team_changes(&mappings, &["Sales EMEA".to_string(), "Eng".to_string()]),
+            (vec![sales], vec![finance])
+        );
+        assert_eq!(team_changes(&mappings, &[]), (vec![], vec![sales, finance]));
+    }
+}
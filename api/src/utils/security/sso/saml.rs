@@ -0,0 +1,588 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use samael::{
+    metadata::{EntityDescriptor, HTTP_REDIRECT_BINDING},
+    schema::Assertion,
+    service_provider::{ServiceProvider, ServiceProviderBuilder},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::database::lib::get_redis_pool;
+
+use super::{sign_sso_state, sso_endpoint_url, verify_sso_state, SsoIdentity, SSO_STATE_SECONDS};
+
+/// A SAML 2.0 identity provider, signed in to with an SP-initiated request over the
+/// HTTP-Redirect binding. Responses are posted back to the `acs` endpoint and their signatures
+/// are checked against the certificates in the provider's metadata.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SamlConfig {
+    /// The metadata XML the identity provider publishes.
+    pub idp_metadata: String,
+    /// The attribute with the user's email. The subject's NameID is used when it isn't set.
+    pub email_attribute: Option<String>,
+    pub name_attribute: Option<String>,
+    #[serde(default = "default_groups_attribute")]
+    pub groups_attribute: String,
+}
+
+fn default_groups_attribute() -> String {
+    "groups".to_string()
+}
+
+/// The entity id identity providers know us by, which is unique per configured provider.
+pub fn sp_entity_id(provider_id: &Uuid) -> String {
+    sso_endpoint_url(provider_id, "metadata")
+}
+
+/// What identity providers need to know about us, served at the `metadata` endpoint.
+pub fn sp_metadata_xml(provider_id: &Uuid) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<md:EntityDescriptor xmlns:md="urn:oasis:names:tc:SAML:2.0:metadata" entityID="{}">
+  <md:SPSSODescriptor AuthnRequestsSigned="false" WantAssertionsSigned="true" protocolSupportEnumeration="urn:oasis:names:tc:SAML:2.0:protocol">
+    <md:NameIDFormat>urn:oasis:names:tc:SAML:1.1:nameid-format:emailAddress</md:NameIDFormat>
+    <md:AssertionConsumerService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST" Location="{}" index="0" isDefault="true"/>
+  </md:SPSSODescriptor>
+</md:EntityDescriptor>
+"#,
+        sp_entity_id(provider_id),
+        sso_endpoint_url(provider_id, "acs")
+    )
+}
+
+fn service_provider(provider_id: &Uuid, config: &SamlConfig) -> Result<ServiceProvider> {
+    let idp_metadata = match config.idp_metadata.parse::<EntityDescriptor>() {
+        Ok(idp_metadata) => idp_metadata,
+        Err(e) => return Err(anyhow!("Invalid identity provider metadata: {}", e)),
+    };
+
+    match ServiceProviderBuilder::default()
+        .entity_id(sp_entity_id(provider_id))
+        .acs_url(sso_endpoint_url(provider_id, "acs"))
+        .idp_metadata(idp_metadata)
+        .allow_idp_initiated(false)
+        .build()
+    {
+        Ok(service_provider) => Ok(service_provider),
+        Err(e) => Err(anyhow!("Invalid SAML configuration: {}", e)),
+    }
+}
+
+/// Checks that the metadata can be used before it is saved.
+pub fn validate_config(provider_id: &Uuid, config: &SamlConfig) -> Result<()> {
+    let service_provider = service_provider(provider_id, config)?;
+
+    match service_provider.sso_binding_location(HTTP_REDIRECT_BINDING) {
+        Some(_) => Ok(()),
+        None => Err(anyhow!(
+            "Identity provider metadata has no HTTP-Redirect sign in endpoint"
+        )),
+    }
+}
+
+pub fn authn_request_url(
+    provider_id: &Uuid,
+    config: &SamlConfig,
+    browser_secret: &str,
+) -> Result<String> {
+    let service_provider = service_provider(provider_id, config)?;
+
+    let destination = match service_provider.sso_binding_location(HTTP_REDIRECT_BINDING) {
+        Some(destination) => destination,
+        None => {
+            return Err(anyhow!(
+                "Identity provider metadata has no HTTP-Redirect sign in endpoint"
+            ))
+        }
+    };
+
+    let authn_request = match service_provider.make_authentication_request(&destination) {
+        Ok(authn_request) => authn_request,
+        Err(e) => return Err(anyhow!("Error creating SAML request: {}", e)),
+    };
+
+    let relay_state = sign_sso_state(provider_id, &authn_request.id, browser_secret)?;
+
+    match authn_request.redirect(&relay_state) {
+        Ok(Some(url)) => Ok(url.to_string()),
+        Ok(None) => Err(anyhow!("SAML request has no destination")),
+        Err(e) => Err(anyhow!("Error encoding SAML request: {}", e)),
+    }
+}
+
+/// Reads the user from the response the identity provider posted back, which has to answer the
+/// request the relay state was signed for. Each request can only be answered once, so a response
+/// can't be posted again to sign in a second time.
+pub async fn complete_login(
+    provider_id: &Uuid,
+    config: &SamlConfig,
+    saml_response: &str,
+    relay_state: &str,
+    browser_secret: &str,
+) -> Result<SsoIdentity> {
+    let (request_id, identity) = verify_response(
+        provider_id,
+        config,
+        saml_response,
+        relay_state,
+        browser_secret,
+    )?;
+
+    if !claim_saml_request(&request_id).await? {
+        return Err(anyhow!("SAML response was already used"));
+    }
+
+    Ok(identity)
+}
+
+/// Checks the response's signature, destination, audience and validity period, returning the id
+/// of the request it answers with the user it signs in.
+fn verify_response(
+    provider_id: &Uuid,
+    config: &SamlConfig,
+    saml_response: &str,
+    relay_state: &str,
+    browser_secret: &str,
+) -> Result<(String, SsoIdentity)> {
+    let request_id = verify_sso_state(relay_state, provider_id, browser_secret)?;
+    let service_provider = service_provider(provider_id, config)?;
+
+    let assertion =
+        match service_provider.parse_base64_response(saml_response, Some(&[request_id.as_str()])) {
+            Ok(assertion) => assertion,
+            Err(e) => return Err(anyhow!("Invalid SAML response: {}", e)),
+        };
+
+    // The audience is only checked when the assertion has one, so one without an audience
+    // restriction could have been issued to any service provider.
+    let has_audience = assertion
+        .conditions
+        .as_ref()
+        .and_then(|conditions| conditions.audience_restrictions.as_ref())
+        .is_some_and(|restrictions| !restrictions.is_empty());
+
+    if !has_audience {
+        return Err(anyhow!("SAML assertion has no audience"));
+    }
+
+    let name_id = assertion
+        .subject
+        .as_ref()
+        .and_then(|subject| subject.name_id.as_ref())
+        .map(|name_id| name_id.value.clone());
+
+    let identity = identity_from_attributes(name_id, assertion_attributes(&assertion), config)?;
+
+    Ok((request_id, identity))
+}
+
+/// Records that a request was answered, returning false if it already had been. Requests are
+/// remembered for as long as their relay state is accepted.
+async fn claim_saml_request(request_id: &str) -> Result<bool> {
+    let mut redis_conn = match get_redis_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting redis connection: {}", e)),
+    };
+
+    match redis::cmd("SET")
+        .arg(format!("saml_request:{}", request_id))
+        .arg(Utc::now().timestamp())
+        .arg("NX")
+        .arg("EX")
+        .arg(SSO_STATE_SECONDS)
+        .query_async::<Option<String>>(&mut *redis_conn)
+        .await
+    {
+        Ok(set) => Ok(set.is_some()),
+        Err(e) => Err(anyhow!("Error recording SAML response: {}", e)),
+    }
+}
+
+/// Attribute values by attribute name and, where it has one, friendly name.
+fn assertion_attributes(assertion: &Assertion) -> HashMap<String, Vec<String>> {
+    let mut attributes: HashMap<String, Vec<String>> = HashMap::new();
+
+    for statement in assertion.attribute_statements.iter().flatten() {
+        for attribute in &statement.attributes {
+            let values = attribute
+                .values
+                .iter()
+                .filter_map(|value| value.value.clone())
+                .collect::<Vec<String>>();
+
+            for name in [&attribute.name, &attribute.friendly_name]
+                .into_iter()
+                .flatten()
+            {
+                attributes
+                    .entry(name.clone())
+                    .or_default()
+                    .extend(values.clone());
+            }
+        }
+    }
+
+    attributes
+}
+
+fn identity_from_attributes(
+    name_id: Option<String>,
+    attributes: HashMap<String, Vec<String>>,
+    config: &SamlConfig,
+) -> Result<SsoIdentity> {
+    let first_value = |attribute: &str| {
+        attributes
+            .get(attribute)
+            .and_then(|values| values.first())
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let email = match &config.email_attribute {
+        Some(email_attribute) => first_value(email_attribute),
+        None => name_id.filter(|name_id| name_id.contains('@')),
+    };
+
+    let email = match email {
+        Some(email) => email,
+        None => return Err(anyhow!("SAML response has no email")),
+    };
+
+    let name = config.name_attribute.as_deref().and_then(first_value);
+
+    let groups = attributes
+        .get(&config.groups_attribute)
+        .cloned()
+        .unwrap_or_default();
+
+    Ok(SsoIdentity {
+        email,
+        name,
+        groups,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use chrono::Duration;
+    use samael::{
+        crypto::sign_xml,
+        idp::{
+            response_builder::{build_response_template, ResponseAttribute},
+            sp_extractor::RequiredAttribute,
+            CertificateParams, IdentityProvider, KeyType, Rsa,
+        },
+        schema::Response,
+        traits::ToXml,
+    };
+
+    use crate::utils::security::sso::sso_nonce;
+
+    const IDP_ENTITY_ID: &str = "https://idp.example.com";
+
+    fn set_test_env() {
+        env::set_var(
+            "JWT_SECRET",
+            "super-secret-jwt-token-with-at-least-32-characters",
+        );
+        env::set_var("BUSTER_API_URL", "http://localhost:3001");
+    }
+
+    /// An identity provider with a freshly generated signing key and certificate.
+    struct StubIdp {
+        idp: IdentityProvider,
+        cert: Vec<u8>,
+    }
+
+    impl StubIdp {
+        fn new() -> Self {
+            let idp = IdentityProvider::generate_new(KeyType::Rsa(Rsa::Rsa2048)).unwrap();
+            let cert = idp
+                .create_certificate(&CertificateParams {
+                    common_name: IDP_ENTITY_ID,
+                    issuer_name: IDP_ENTITY_ID,
+                    days_until_expiration: 1,
+                })
+                .unwrap();
+
+            StubIdp { idp, cert }
+        }
+
+        fn config(&self) -> SamlConfig {
+            SamlConfig {
+                idp_metadata: format!(
+                    r#"<md:EntityDescriptor xmlns:md="urn:oasis:names:tc:SAML:2.0:metadata" xmlns:ds="http://www.w3.org/2000/09/xmldsig#" entityID="{}">
+  <md:IDPSSODescriptor protocolSupportEnumeration="urn:oasis:names:tc:SAML:2.0:protocol">
+    <md:KeyDescriptor use="signing">
+      <ds:KeyInfo>
+        <ds:X509Data>
+          <ds:X509Certificate>{}</ds:X509Certificate>
+        </ds:X509Data>
+      </ds:KeyInfo>
+    </md:KeyDescriptor>
+    <md:SingleSignOnService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-Redirect" Location="{}/sso"/>
+  </md:IDPSSODescriptor>
+</md:EntityDescriptor>"#,
+                    IDP_ENTITY_ID,
+                    STANDARD.encode(&self.cert),
+                    IDP_ENTITY_ID
+                ),
+                email_attribute: None,
+                name_attribute: None,
+                groups_attribute: default_groups_attribute(),
+            }
+        }
+
+        /// Signs a response to `request_id` for `audience`, after `edit` has had a chance to
+        /// change it, and encodes it the way it is posted to the `acs` endpoint.
+        fn signed_response(
+            &self,
+            provider_id: &Uuid,
+            request_id: &str,
+            audience: &str,
+            edit: impl FnOnce(&mut Response),
+        ) -> String {
+            let mut response = build_response_template(
+                &self.cert,
+                "jane@example.com",
+                audience,
+                IDP_ENTITY_ID,
+                &sso_endpoint_url(provider_id, "acs"),
+                request_id,
+                &[ResponseAttribute {
+                    required_attribute: RequiredAttribute {
+                        name: "groups".to_string(),
+                        format: None,
+                    },
+                    value: "Sales",
+                }],
+            );
+
+            edit(&mut response);
+
+            let signed = sign_xml(
+                response.to_string().unwrap(),
+                &self.idp.export_private_key_der().unwrap(),
+            )
+            .unwrap();
+
+            STANDARD.encode(signed)
+        }
+    }
+
+    #[test]
+    fn signed_responses_sign_the_user_in() {
+        set_test_env();
+
+        let stub_idp = StubIdp::new();
+        let provider_id = Uuid::new_v4();
+        let browser_secret = sso_nonce();
+        let request_id = format!("id-{}", Uuid::new_v4());
+        let relay_state = sign_sso_state(&provider_id, &request_id, &browser_secret).unwrap();
+
+        let saml_response = stub_idp.signed_response(
+            &provider_id,
+            &request_id,
+            &sp_entity_id(&provider_id),
+            |_| (),
+        );
+
+        assert_eq!(
+            verify_response(
+                &provider_id,
+                &stub_idp.config(),
+                &saml_response,
+                &relay_state,
+                &browser_secret
+            )
+            .unwrap(),
+            (
+                request_id,
+                SsoIdentity {
+                    email: "jane@example.com".to_string(),
+                    name: None,
+                    groups: vec!["Sales".to_string()],
+                }
+            )
+        );
+
+        // Signed by another identity provider.
+        assert!(verify_response(
+            &provider_id,
+            &StubIdp::new().config(),
+            &saml_response,
+            &relay_state,
+            &browser_secret
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn responses_for_another_audience_are_refused() {
+        set_test_env();
+
+        let stub_idp = StubIdp::new();
+        let provider_id = Uuid::new_v4();
+        let browser_secret = sso_nonce();
+        let request_id = format!("id-{}", Uuid::new_v4());
+        let relay_state = sign_sso_state(&provider_id, &request_id, &browser_secret).unwrap();
+
+        let for_another_provider = stub_idp.signed_response(
+            &provider_id,
+            &request_id,
+            &sp_entity_id(&Uuid::new_v4()),
+            |_| (),
+        );
+        let without_audience = stub_idp.signed_response(
+            &provider_id,
+            &request_id,
+            &sp_entity_id(&provider_id),
+            |response| {
+                if let Some(conditions) = response
+                    .assertion
+                    .as_mut()
+                    .and_then(|assertion| assertion.conditions.as_mut())
+                {
+                    conditions.audience_restrictions = None;
+                }
+            },
+        );
+
+        for saml_response in [for_another_provider, without_audience] {
+            assert!(verify_response(
+                &provider_id,
+                &stub_idp.config(),
+                &saml_response,
+                &relay_state,
+                &browser_secret
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn expired_responses_are_refused() {
+        set_test_env();
+
+        let stub_idp = StubIdp::new();
+        let provider_id = Uuid::new_v4();
+        let browser_secret = sso_nonce();
+        let request_id = format!("id-{}", Uuid::new_v4());
+        let relay_state = sign_sso_state(&provider_id, &request_id, &browser_secret).unwrap();
+        let audience = sp_entity_id(&provider_id);
+        let an_hour_ago = Utc::now() - Duration::hours(1);
+
+        let past_its_conditions =
+            stub_idp.signed_response(&provider_id, &request_id, &audience, |response| {
+                if let Some(conditions) = response
+                    .assertion
+                    .as_mut()
+                    .and_then(|assertion| assertion.conditions.as_mut())
+                {
+                    conditions.not_on_or_after = Some(an_hour_ago);
+                }
+            });
+        let issued_long_ago =
+            stub_idp.signed_response(&provider_id, &request_id, &audience, |response| {
+                response.issue_instant = an_hour_ago;
+                if let Some(assertion) = response.assertion.as_mut() {
+                    assertion.issue_instant = an_hour_ago;
+                }
+            });
+
+        for saml_response in [past_its_conditions, issued_long_ago] {
+            assert!(verify_response(
+                &provider_id,
+                &stub_idp.config(),
+                &saml_response,
+                &relay_state,
+                &browser_secret
+            )
+            .is_err());
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the local redis"]
+    async fn responses_can_only_be_used_once() {
+        set_test_env();
+
+        let stub_idp = StubIdp::new();
+        let provider_id = Uuid::new_v4();
+        let browser_secret = sso_nonce();
+        let request_id = format!("id-{}", Uuid::new_v4());
+        let relay_state = sign_sso_state(&provider_id, &request_id, &browser_secret).unwrap();
+
+        let saml_response = stub_idp.signed_response(
+            &provider_id,
+            &request_id,
+            &sp_entity_id(&provider_id),
+            |_| (),
+        );
+
+        let config = stub_idp.config();
+
+        let sign_in = || {
+            complete_login(
+                &provider_id,
+                &config,
+                &saml_response,
+                &relay_state,
+                &browser_secret,
+            )
+        };
+
+        assert!(sign_in().await.is_ok());
+        assert!(sign_in().await.is_err());
+    }
+
+    #[test]
+    fn reads_identity_from_attributes() {
+        let config = SamlConfig {
+            idp_metadata: String::new(),
+            email_attribute: None,
+            name_attribute: Some("displayName".to_string()),
+            groups_attribute: "memberOf".to_string(),
+        };
+
+        let attributes = HashMap::from([
+            ("displayName".to_string(), vec!["Jane Doe".to_string()]),
+            (
+                "memberOf".to_string(),
+                vec!["Sales".to_string(), "Finance".to_string()],
+            ),
+        ]);
+
+        assert_eq!(
+            identity_from_attributes(Some("jane@example.com".to_string()), attributes, &config)
+                .unwrap(),
+            SsoIdentity {
+                email: "jane@example.com".to_string(),
+                name: Some("Jane Doe".to_string()),
+                groups: vec!["Sales".to_string(), "Finance".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn opaque_name_ids_need_an_email_attribute() {
+        let config = SamlConfig {
+            idp_metadata: String::new(),
+            email_attribute: None,
+            name_attribute: None,
+            groups_attribute: default_groups_attribute(),
+        };
+
+        assert!(
+            identity_from_attributes(Some("00u1a2b3c4".to_string()), HashMap::new(), &config)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use std::env;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::database::lib::get_redis_pool;
+
+/// Header senders put the signature in, formatted as `t=<unix seconds>,v1=<hex hmac>`. While a
+/// key is being rotated senders may sign with both keys and send a `v1` for each.
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "Buster-Signature";
+
+/// How far a request's timestamp may be from our clock. Deliveries are remembered for twice as
+/// long so a request can't be replayed while its timestamp would still be accepted.
+const WEBHOOK_TOLERANCE_SECONDS: i64 = 60 * 5;
+
+/// Anything that calls our webhooks. Each integration signs with its own keys, read from
+/// `WEBHOOK_SIGNING_KEYS_<INTEGRATION>` as a comma separated list. To rotate a key, add the new
+/// one to the list, move the sender over and then drop the old one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebhookIntegration {
+    Embeddings,
+}
+
+impl WebhookIntegration {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookIntegration::Embeddings => "embeddings",
+        }
+    }
+
+    pub fn signing_keys(&self) -> Vec<String> {
+        let var = format!("WEBHOOK_SIGNING_KEYS_{}", self.as_str().to_uppercase());
+
+        env::var(var)
+            .unwrap_or_default()
+            .split(',')
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty())
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WebhookSignatureError {
+    NoSigningKeys,
+    Malformed,
+    Expired,
+    Invalid,
+}
+
+/// The signature of a request body sent at `timestamp`, as hex.
+pub fn sign_webhook(key: &str, timestamp: i64, body: &[u8]) -> String {
+    hex::encode(webhook_mac(key, timestamp, body).finalize().into_bytes())
+}
+
+fn webhook_mac(key: &str, timestamp: i64, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC can take a key of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+fn parse_signature_header(header: &str) -> Option<(i64, Vec<Vec<u8>>)> {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = Some(value.parse::<i64>().ok()?),
+            Some(("v1", value)) => signatures.push(hex::decode(value).ok()?),
+            _ => (),
+        }
+    }
+
+    match (timestamp, signatures.is_empty()) {
+        (Some(timestamp), false) => Some((timestamp, signatures)),
+        _ => None,
+    }
+}
+
+/// Checks that the body was signed with one of the keys within the tolerance of `now`, returning
+/// the timestamp it was signed at.
+pub fn verify_webhook_signature(
+    keys: &[String],
+    header: &str,
+    body: &[u8],
+    now: i64,
+) -> Result<i64, WebhookSignatureError> {
+    if keys.is_empty() {
+        return Err(WebhookSignatureError::NoSigningKeys);
+    }
+
+    let (timestamp, signatures) = match parse_signature_header(header) {
+        Some(parsed) => parsed,
+        None => return Err(WebhookSignatureError::Malformed),
+    };
+
+    if (now - timestamp).abs() > WEBHOOK_TOLERANCE_SECONDS {
+        return Err(WebhookSignatureError::Expired);
+    }
+
+    for key in keys {
+        for signature in &signatures {
+            // `verify_slice` compares in constant time.
+            if webhook_mac(key, timestamp, body)
+                .verify_slice(signature)
+                .is_ok()
+            {
+                return Ok(timestamp);
+            }
+        }
+    }
+
+    Err(WebhookSignatureError::Invalid)
+}
+
+/// Records that a signed request was handled, returning false if it already had been.
+pub async fn claim_webhook_delivery(
+    integration: WebhookIntegration,
+    timestamp: i64,
+    body: &[u8],
+) -> Result<bool> {
+    let mut redis_conn = match get_redis_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting redis connection: {}", e)),
+    };
+
+    let key = webhook_delivery_key(integration, timestamp, body);
+
+    match redis::cmd("SET")
+        .arg(&key)
+        .arg(Utc::now().timestamp())
+        .arg("NX")
+        .arg("EX")
+        .arg(WEBHOOK_TOLERANCE_SECONDS * 2)
+        .query_async::<Option<String>>(&mut *redis_conn)
+        .await
+    {
+        Ok(set) => Ok(set.is_some()),
+        Err(e) => Err(anyhow!("Error recording webhook delivery: {}", e)),
+    }
+}
+
+/// Deliveries are told apart by what was signed rather than by the signature, so a request that
+/// is signed with several keys during a rotation is still only accepted once.
+fn webhook_delivery_key(integration: WebhookIntegration, timestamp: i64, body: &[u8]) -> String {
+    format!(
+        "webhook_delivery:{}:{}:{}",
+        integration.as_str(),
+        timestamp,
+        hex::encode(Sha256::digest(body))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_737_300_000;
+
+    fn keys() -> Vec<String> {
+        vec!["new-key".to_string(), "old-key".to_string()]
+    }
+
+    #[test]
+    fn accepts_any_current_key() {
+        let body = br#"{"table":"datasets"}"#;
+        let header = format!("t={},v1={}", NOW, sign_webhook("old-key", NOW, body));
+
+        assert_eq!(
+            verify_webhook_signature(&keys(), &header, body, NOW + 10),
+            Ok(NOW)
+        );
+    }
+
+    #[test]
+    fn deliveries_are_keyed_by_what_was_signed() {
+        let body = br#"{"table":"datasets"}"#;
+        let key = webhook_delivery_key(WebhookIntegration::Embeddings, NOW, body);
+
+        for signing_key in keys() {
+            let header = format!("t={},v1={}", NOW, sign_webhook(&signing_key, NOW, body));
+            let timestamp = verify_webhook_signature(&keys(), &header, body, NOW).unwrap();
+
+            assert_eq!(
+                webhook_delivery_key(WebhookIntegration::Embeddings, timestamp, body),
+                key
+            );
+        }
+
+        assert_ne!(
+            webhook_delivery_key(WebhookIntegration::Embeddings, NOW + 1, body),
+            key
+        );
+        assert_ne!(
+            webhook_delivery_key(
+                WebhookIntegration::Embeddings,
+                NOW,
+                br#"{"table":"messages"}"#
+            ),
+            key
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_stale_and_unknown_signatures() {
+        let body = br#"{"table":"datasets"}"#;
+        let header = format!("t={},v1={}", NOW, sign_webhook("new-key", NOW, body));
+
+        assert_eq!(
+            verify_webhook_signature(&keys(), &header, br#"{"table":"messages"}"#, NOW),
+            Err(WebhookSignatureError::Invalid)
+        );
+        assert_eq!(
+            verify_webhook_signature(&keys(), &header, body, NOW + WEBHOOK_TOLERANCE_SECONDS + 1),
+            Err(WebhookSignatureError::Expired)
+        );
+
+        let header = format!("t={},v1={}", NOW, sign_webhook("revoked-key", NOW, body));
+        assert_eq!(
+            verify_webhook_signature(&keys(), &header, body, NOW),
+            Err(WebhookSignatureError::Invalid)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        for header in [
+            "",
+            "t=abc,v1=00",
+            "v1=00",
+            "t=1737300000",
+            "t=1737300000,v1=zz",
+        ] {
+            assert_eq!(
+                verify_webhook_signature(&keys(), header, b"", NOW),
+                Err(WebhookSignatureError::Malformed)
+            );
+        }
+    }
+}
@@ -9,7 +9,7 @@ use diesel::{
     ExpressionMethods, JoinOnDsl, NullableExpressionMethods, QueryDsl,
 };
 use diesel_async::RunQueryDsl;
-use serde_json::json;
+use serde_json::{json, Value};
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
@@ -20,7 +20,7 @@ use uuid::Uuid;
 
 use crate::{
     database::{
-        enums::{AssetPermissionRole, AssetType, IdentityType},
+        enums::{AssetPermissionRole, AssetType, AuditAction, IdentityType},
         lib::get_pg_pool,
         models::{AssetPermission, CollectionToAsset, User},
         schema::{
@@ -28,9 +28,14 @@ use crate::{
             organizations, teams, teams_to_users, threads, user_favorites, users,
         },
     },
-    utils::clients::{
-        email::resend::{send_email, CollectionInvite, DashboardInvite, EmailType, ThreadInvite},
-        sentry_utils::send_sentry_error,
+    utils::{
+        audit::audit_log::{spawn_audit_event, AuditRequestMetadata, NewAuditEvent},
+        clients::{
+            email::resend::{
+                send_email, CollectionInvite, DashboardInvite, EmailType, ThreadInvite,
+            },
+            sentry_utils::send_sentry_error,
+        },
     },
 };
 
@@ -77,7 +82,10 @@ pub async fn update_asset_permissions(
     user_permissions: Option<Vec<ShareWithUsersReqObject>>,
     remove_teams: Option<Vec<Uuid>>,
     remove_users: Option<Vec<Uuid>>,
+    request_metadata: AuditRequestMetadata,
 ) -> Result<()> {
+    let before = get_asset_sharing_snapshot(Arc::clone(&asset_id), asset_type).await;
+
     let user_id = Arc::new(user.id);
     let team_permissions_handle = if let Some(team_permissions) = team_permissions {
         let asset_id = Arc::clone(&asset_id);
@@ -155,9 +163,34 @@ pub async fn update_asset_permissions(
         }
     };
 
+    spawn_audit_event(NewAuditEvent {
+        actor_id: user.id,
+        action: AuditAction::AssetPermissionsUpdated,
+        target_type: asset_type.into(),
+        target_id: Some(*asset_id),
+        before,
+        after: get_asset_sharing_snapshot(Arc::clone(&asset_id), asset_type).await,
+        request_metadata,
+    });
+
     Ok(())
 }
 
+/// Who an asset is shared with, for the audit log.
+async fn get_asset_sharing_snapshot(asset_id: Arc<Uuid>, asset_type: AssetType) -> Option<Value> {
+    match get_asset_sharing_info(asset_id, asset_type).await {
+        Ok(sharing_info) => Some(json!({
+            "individual_permissions": sharing_info.individual_permissions,
+            "team_permissions": sharing_info.team_permissions,
+            "organization_permissions": sharing_info.organization_permissions,
+        })),
+        Err(e) => {
+            tracing::error!("Error getting asset sharing info: {}", e);
+            None
+        }
+    }
+}
+
 async fn grant_team_access_to_asset(
     team_permissions: &Vec<ShareWithTeamsReqObject>,
     asset_id: Arc<Uuid>,
@@ -696,6 +729,7 @@ async fn create_new_users_and_add_permissions(
             config: json!({}),
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
+            attributes: json!({}),
         };
 
         let permission = AssetPermission {
@@ -1 +1,2 @@
+pub mod user_attributes;
 pub mod user_info;
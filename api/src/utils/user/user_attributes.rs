@@ -0,0 +1,87 @@
+use anyhow::{anyhow, Result};
+use diesel::{ExpressionMethods, JoinOnDsl, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::database::{
+    lib::get_pg_pool,
+    schema::{teams, teams_to_users, users},
+};
+
+/// The attributes that apply to a user: the ones set on them, plus the ones their teams set for
+/// keys they don't. When teams disagree on a key, the team the user joined first wins.
+pub async fn get_user_attributes(user_id: &Uuid) -> Result<Map<String, Value>> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let user_attributes = match users::table
+        .filter(users::id.eq(user_id))
+        .select(users::attributes)
+        .first::<Value>(&mut conn)
+        .await
+    {
+        Ok(user_attributes) => user_attributes,
+        Err(e) => return Err(anyhow!("Error getting user attributes: {}", e)),
+    };
+
+    let team_attributes = match teams_to_users::table
+        .inner_join(teams::table.on(teams::id.eq(teams_to_users::team_id)))
+        .filter(teams_to_users::user_id.eq(user_id))
+        .filter(teams_to_users::deleted_at.is_null())
+        .filter(teams::deleted_at.is_null())
+        .order(teams_to_users::created_at.asc())
+        .select(teams::attributes)
+        .load::<Value>(&mut conn)
+        .await
+    {
+        Ok(team_attributes) => team_attributes,
+        Err(e) => return Err(anyhow!("Error getting team attributes: {}", e)),
+    };
+
+    Ok(merge_user_attributes(&user_attributes, &team_attributes))
+}
+
+/// Team attributes are given in the order the user joined the teams.
+pub fn merge_user_attributes(
+    user_attributes: &Value,
+    team_attributes: &[Value],
+) -> Map<String, Value> {
+    let mut attributes = Map::new();
+
+    for source in std::iter::once(user_attributes).chain(team_attributes.iter()) {
+        if let Value::Object(source) = source {
+            for (key, value) in source {
+                if !attributes.contains_key(key) {
+                    attributes.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn user_attributes_override_team_attributes() {
+        let attributes = merge_user_attributes(
+            &json!({"region": "EMEA"}),
+            &[
+                json!({"region": "AMER", "department": "Sales"}),
+                json!({"department": "Finance", "cost_center": 4100}),
+            ],
+        );
+
+        assert_eq!(
+            Value::Object(attributes),
+            json!({"region": "EMEA", "department": "Sales", "cost_center": 4100})
+        );
+    }
+}
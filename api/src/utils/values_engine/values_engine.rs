@@ -1,7 +1,433 @@
-use anyhow::Result;
+use std::{collections::HashSet, env, time::Duration};
 
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use diesel::{
+    insert_into, sql_query, sql_types::Text, update, BoolExpressionMethods, ExpressionMethods,
+    JoinOnDsl, OptionalExtension, QueryDsl,
+};
+use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
+use uuid::Uuid;
+
+use crate::{
+    database::{
+        enums::StoredValuesStatus,
+        lib::get_pg_pool,
+        schema::{dataset_columns, datasets, stored_values_syncs},
+    },
+    utils::query_engine::values_index::start_stored_values_sync,
+};
+
+/// How often the scheduler looks for columns that are due.
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+
+const DEFAULT_SYNC_INTERVAL_SECONDS: i64 = 86400;
+const DEFAULT_CONCURRENCY_PER_DATA_SOURCE: usize = 2;
+
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(60);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// How long a claim on a column holds. A replica that dies mid-sync leaves its claim behind, so
+/// the column can be claimed again once this has passed.
+const CLAIM_LEASE: Duration = Duration::from_secs(60 * 60);
+
+/// How long a sync started right away waits before trying again when its data source is busy.
+const CLAIM_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// When a column's current claim runs out and when it may be retried after a failure.
+type CurrentClaim = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+/// Whether a column can be synced. Syncs are claimed in `stored_values_syncs`, so every replica
+/// sees the same claims, failures and per data source limit.
+#[derive(Debug, PartialEq)]
+enum Claim {
+    Claimed,
+    AlreadySyncing,
+    RetryNotDue,
+    DataSourceBusy,
+}
+
+/// Runs forever, re-syncing every column with `stored_values` turned on once
+/// `STORED_VALUES_SYNC_INTERVAL_SECONDS` have passed since its last sync. Failed syncs are retried
+/// with exponential backoff.
 pub async fn sync_values() -> Result<()> {
-    
+    let mut interval = tokio::time::interval(SCHEDULER_TICK);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = sync_due_columns().await {
+            tracing::error!("Error scheduling stored values sync: {}", e);
+        }
+    }
+}
+
+/// Syncs a column right away, e.g. when stored values have just been turned on for it. Shares the
+/// per data source concurrency limit with the scheduler.
+pub async fn sync_column_values_now(dataset_column_id: &Uuid) -> Result<()> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let data_source_id = match dataset_columns::table
+        .inner_join(datasets::table.on(dataset_columns::dataset_id.eq(datasets::id)))
+        .select(datasets::data_source_id)
+        .filter(dataset_columns::id.eq(dataset_column_id))
+        .first::<Uuid>(&mut conn)
+        .await
+    {
+        Ok(data_source_id) => data_source_id,
+        Err(e) => return Err(anyhow!("Error getting data source id: {}", e)),
+    };
+
+    drop(conn);
+
+    sync_column(*dataset_column_id, data_source_id, true).await
+}
+
+async fn sync_due_columns() -> Result<()> {
+    let synced_before = Utc::now() - chrono::Duration::seconds(sync_interval_seconds());
+
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let due_columns = match dataset_columns::table
+        .inner_join(datasets::table.on(dataset_columns::dataset_id.eq(datasets::id)))
+        .select((dataset_columns::id, datasets::data_source_id))
+        .filter(dataset_columns::stored_values.eq(true))
+        .filter(dataset_columns::deleted_at.is_null())
+        .filter(datasets::deleted_at.is_null())
+        .filter(
+            dataset_columns::stored_values_last_synced
+                .is_null()
+                .or(dataset_columns::stored_values_last_synced.lt(synced_before))
+                .or(dataset_columns::stored_values_status.eq(StoredValuesStatus::Failed)),
+        )
+        .load::<(Uuid, Uuid)>(&mut conn)
+        .await
+    {
+        Ok(due_columns) => due_columns,
+        Err(e) => return Err(anyhow!("Error getting columns to sync: {}", e)),
+    };
+
+    drop(conn);
+
+    prune_idle_syncs(&due_columns.iter().map(|(id, _)| *id).collect()).await?;
+
+    for (dataset_column_id, data_source_id) in due_columns {
+        tokio::spawn(async move {
+            let _ = sync_column(dataset_column_id, data_source_id, false).await;
+        });
+    }
 
     Ok(())
 }
+
+async fn sync_column(dataset_column_id: Uuid, data_source_id: Uuid, force: bool) -> Result<()> {
+    loop {
+        match claim_column(&dataset_column_id, &data_source_id, force).await? {
+            Claim::Claimed => break,
+            Claim::AlreadySyncing => {
+                return Err(anyhow!("Stored values are already syncing for this column"))
+            }
+            Claim::DataSourceBusy if force => tokio::time::sleep(CLAIM_RETRY_INTERVAL).await,
+            // The scheduler picks these up again on a later tick.
+            Claim::RetryNotDue | Claim::DataSourceBusy => return Ok(()),
+        }
+    }
+
+    match start_stored_values_sync(&dataset_column_id, force).await {
+        Ok(_) => release_claim(&dataset_column_id).await,
+        Err(e) => {
+            let retry_delay = release_failed_claim(&dataset_column_id).await?;
+            tracing::warn!(
+                "Stored values sync failed for column {}, retrying in {} seconds: {}",
+                dataset_column_id,
+                retry_delay.as_secs(),
+                e
+            );
+
+            Err(e)
+        }
+    }
+}
+
+/// Claims a column for this replica. Claims on the same data source are taken under a
+/// transaction level advisory lock, so replicas can't both take its last free slot.
+async fn claim_column(
+    dataset_column_id: &Uuid,
+    data_source_id: &Uuid,
+    force: bool,
+) -> Result<Claim> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let dataset_column_id = *dataset_column_id;
+    let data_source_id = *data_source_id;
+    let concurrency = concurrency_per_data_source() as i64;
+
+    let claim = conn
+        .transaction::<Claim, diesel::result::Error, _>(|conn| {
+            async move {
+                sql_query("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
+                    .bind::<Text, _>(data_source_id.to_string())
+                    .execute(conn)
+                    .await?;
+
+                let now = Utc::now();
+
+                let current_claim = stored_values_syncs::table
+                    .filter(stored_values_syncs::dataset_column_id.eq(dataset_column_id))
+                    .select((
+                        stored_values_syncs::claimed_until,
+                        stored_values_syncs::retry_at,
+                    ))
+                    .first::<CurrentClaim>(conn)
+                    .await
+                    .optional()?;
+
+                let active_claims = stored_values_syncs::table
+                    .filter(stored_values_syncs::data_source_id.eq(data_source_id))
+                    .filter(stored_values_syncs::claimed_until.gt(now))
+                    .count()
+                    .get_result::<i64>(conn)
+                    .await?;
+
+                let claim = decide_claim(current_claim, active_claims, concurrency, force, now);
+
+                if claim == Claim::Claimed {
+                    let claimed_until = now + chrono::Duration::from_std(CLAIM_LEASE).unwrap();
+
+                    insert_into(stored_values_syncs::table)
+                        .values((
+                            stored_values_syncs::dataset_column_id.eq(dataset_column_id),
+                            stored_values_syncs::data_source_id.eq(data_source_id),
+                            stored_values_syncs::claimed_until.eq(claimed_until),
+                            stored_values_syncs::updated_at.eq(now),
+                        ))
+                        .on_conflict(stored_values_syncs::dataset_column_id)
+                        .do_update()
+                        .set((
+                            stored_values_syncs::data_source_id.eq(data_source_id),
+                            stored_values_syncs::claimed_until.eq(claimed_until),
+                            stored_values_syncs::updated_at.eq(now),
+                        ))
+                        .execute(conn)
+                        .await?;
+                }
+
+                Ok(claim)
+            }
+            .scope_boxed()
+        })
+        .await;
+
+    match claim {
+        Ok(claim) => Ok(claim),
+        Err(e) => Err(anyhow!(
+            "Error claiming column for a stored values sync: {}",
+            e
+        )),
+    }
+}
+
+/// Decides whether a column can be claimed, given its current claim and retry time, if it has
+/// been synced before, and the number of active claims on its data source. Syncs started right
+/// away skip the retry backoff.
+fn decide_claim(
+    current_claim: Option<CurrentClaim>,
+    active_claims: i64,
+    concurrency: i64,
+    force: bool,
+    now: DateTime<Utc>,
+) -> Claim {
+    if let Some((claimed_until, retry_at)) = current_claim {
+        if claimed_until.is_some_and(|claimed_until| claimed_until > now) {
+            return Claim::AlreadySyncing;
+        }
+
+        if !force && retry_at.is_some_and(|retry_at| retry_at > now) {
+            return Claim::RetryNotDue;
+        }
+    }
+
+    if active_claims >= concurrency {
+        return Claim::DataSourceBusy;
+    }
+
+    Claim::Claimed
+}
+
+async fn release_claim(dataset_column_id: &Uuid) -> Result<()> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    match update(stored_values_syncs::table)
+        .filter(stored_values_syncs::dataset_column_id.eq(dataset_column_id))
+        .set((
+            stored_values_syncs::claimed_until.eq(None::<DateTime<Utc>>),
+            stored_values_syncs::failed_attempts.eq(0),
+            stored_values_syncs::retry_at.eq(None::<DateTime<Utc>>),
+            stored_values_syncs::updated_at.eq(Utc::now()),
+        ))
+        .execute(&mut conn)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(anyhow!("Error releasing stored values sync: {}", e)),
+    }
+}
+
+/// Releases the claim on a column whose sync failed, backing off before it is retried. Returns
+/// how long the retry is delayed.
+async fn release_failed_claim(dataset_column_id: &Uuid) -> Result<Duration> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let failed_attempts = match update(stored_values_syncs::table)
+        .filter(stored_values_syncs::dataset_column_id.eq(dataset_column_id))
+        .set(stored_values_syncs::failed_attempts.eq(stored_values_syncs::failed_attempts + 1))
+        .returning(stored_values_syncs::failed_attempts)
+        .get_result::<i32>(&mut conn)
+        .await
+    {
+        Ok(failed_attempts) => failed_attempts,
+        Err(e) => return Err(anyhow!("Error recording stored values sync failure: {}", e)),
+    };
+
+    let delay = retry_delay(failed_attempts.max(1) as u32);
+    let now = Utc::now();
+
+    match update(stored_values_syncs::table)
+        .filter(stored_values_syncs::dataset_column_id.eq(dataset_column_id))
+        .set((
+            stored_values_syncs::claimed_until.eq(None::<DateTime<Utc>>),
+            stored_values_syncs::retry_at.eq(now + chrono::Duration::from_std(delay).unwrap()),
+            stored_values_syncs::updated_at.eq(now),
+        ))
+        .execute(&mut conn)
+        .await
+    {
+        Ok(_) => Ok(delay),
+        Err(e) => Err(anyhow!("Error releasing stored values sync: {}", e)),
+    }
+}
+
+/// Forgets the failures of unclaimed columns that are no longer due, e.g. because stored values
+/// were turned off or the column was deleted, so failures don't pile up for columns that are
+/// never retried.
+async fn prune_idle_syncs(due_column_ids: &HashSet<Uuid>) -> Result<()> {
+    let mut conn = match get_pg_pool().get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(anyhow!("Error getting pg connection: {}", e)),
+    };
+
+    let due_column_ids = due_column_ids.iter().copied().collect::<Vec<Uuid>>();
+
+    match diesel::delete(stored_values_syncs::table)
+        .filter(stored_values_syncs::dataset_column_id.ne_all(due_column_ids))
+        .filter(
+            stored_values_syncs::claimed_until
+                .is_null()
+                .or(stored_values_syncs::claimed_until.lt(Utc::now())),
+        )
+        .execute(&mut conn)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(anyhow!("Error pruning stored values syncs: {}", e)),
+    }
+}
+
+fn retry_delay(attempts: u32) -> Duration {
+    let exponent = attempts.saturating_sub(1).min(16);
+
+    RETRY_BASE_DELAY
+        .saturating_mul(2u32.pow(exponent))
+        .min(RETRY_MAX_DELAY)
+}
+
+fn sync_interval_seconds() -> i64 {
+    env::var("STORED_VALUES_SYNC_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .filter(|seconds: &i64| *seconds > 0)
+        .unwrap_or(DEFAULT_SYNC_INTERVAL_SECONDS)
+}
+
+fn concurrency_per_data_source() -> usize {
+    env::var("STORED_VALUES_SYNC_CONCURRENCY")
+        .ok()
+        .and_then(|concurrency| concurrency.parse().ok())
+        .filter(|concurrency: &usize| *concurrency > 0)
+        .unwrap_or(DEFAULT_CONCURRENCY_PER_DATA_SOURCE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_back_off_exponentially_up_to_the_max() {
+        assert_eq!(retry_delay(1), RETRY_BASE_DELAY);
+        assert_eq!(retry_delay(2), RETRY_BASE_DELAY * 2);
+        assert_eq!(retry_delay(3), RETRY_BASE_DELAY * 4);
+        assert_eq!(retry_delay(50), RETRY_MAX_DELAY);
+    }
+
+    #[test]
+    fn a_column_only_syncs_once_at_a_time() {
+        let now = Utc::now();
+        let later = now + chrono::Duration::minutes(5);
+        let earlier = now - chrono::Duration::minutes(5);
+
+        assert_eq!(decide_claim(None, 0, 2, false, now), Claim::Claimed);
+        assert_eq!(
+            decide_claim(Some((Some(later), None)), 1, 2, true, now),
+            Claim::AlreadySyncing
+        );
+        // A claim left behind by a replica that died mid-sync has expired.
+        assert_eq!(
+            decide_claim(Some((Some(earlier), None)), 0, 2, false, now),
+            Claim::Claimed
+        );
+    }
+
+    #[test]
+    fn failed_columns_wait_for_their_retry_unless_forced() {
+        let now = Utc::now();
+        let later = now + chrono::Duration::minutes(5);
+        let earlier = now - chrono::Duration::minutes(5);
+
+        assert_eq!(
+            decide_claim(Some((None, Some(later))), 0, 2, false, now),
+            Claim::RetryNotDue
+        );
+        assert_eq!(
+            decide_claim(Some((None, Some(later))), 0, 2, true, now),
+            Claim::Claimed
+        );
+        assert_eq!(
+            decide_claim(Some((None, Some(earlier))), 0, 2, false, now),
+            Claim::Claimed
+        );
+    }
+
+    #[test]
+    fn data_sources_are_limited_to_their_concurrency() {
+        let now = Utc::now();
+
+        assert_eq!(decide_claim(None, 1, 2, false, now), Claim::Claimed);
+        assert_eq!(decide_claim(None, 2, 2, false, now), Claim::DataSourceBusy);
+        assert_eq!(decide_claim(None, 2, 2, true, now), Claim::DataSourceBusy);
+    }
+}
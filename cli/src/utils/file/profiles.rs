@@ -41,25 +41,23 @@ pub enum Credential {
     Databricks(DatabricksCredentials),
     Snowflake(SnowflakeCredentials),
     Starrocks(MySqlCredentials),
+    DuckDb(DuckDbCredentials),
+    ClickHouse(ClickHouseCredentials),
+    Trino(TrinoCredentials),
 }
 
 impl Credential {
     pub fn get_schema(&self) -> String {
         match self {
             Credential::Postgres(cred) => cred.schema.clone(),
+            Credential::DuckDb(cred) => cred.schema.clone().unwrap_or("main".to_string()),
+            Credential::ClickHouse(cred) => cred.database.clone(),
+            Credential::Trino(cred) => cred.schema.clone(),
             _ => "".to_string(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct AthenaCredentials {
-    pub data_source: String,
-    pub db_database: String,
-    pub aws_access_key: String,
-    pub aws_secret_access: String,
-}
-
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BigqueryCredentials {
     pub credentials_json: Value,
@@ -67,6 +65,29 @@ pub struct BigqueryCredentials {
     pub dataset_ids: Option<Vec<String>>,
 }
 
+// Field names follow dbt-clickhouse profiles; ClickHouse databases double as dbt schemas.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClickHouseCredentials {
+    pub host: String,
+    #[serde(default = "default_clickhouse_port")]
+    pub port: u16,
+    #[serde(alias = "user")]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    #[serde(alias = "schema", default = "default_clickhouse_database")]
+    pub database: String,
+    pub secure: Option<bool>,
+}
+
+fn default_clickhouse_port() -> u16 {
+    8123
+}
+
+fn default_clickhouse_database() -> String {
+    "default".to_string()
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DatabricksCredentials {
     pub host: String,
@@ -76,6 +97,19 @@ pub struct DatabricksCredentials {
     pub schemas: Option<Vec<String>>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DuckDbCredentials {
+    // dbt-duckdb falls back to an in-memory database when no path is given.
+    #[serde(default = "default_duckdb_path")]
+    pub path: String,
+    pub schema: Option<String>,
+    pub read_only: Option<bool>,
+}
+
+fn default_duckdb_path() -> String {
+    ":memory:".to_string()
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MariadbCredentials {
     pub host: String,
@@ -142,6 +176,25 @@ pub struct SqlServerCredentials {
     pub schemas: Option<Vec<String>>,
 }
 
+// Field names follow dbt-trino profiles, where the catalog is called the database.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrinoCredentials {
+    pub host: String,
+    #[serde(default = "default_trino_port")]
+    pub port: u16,
+    pub secure: Option<bool>,
+    #[serde(alias = "database")]
+    pub catalog: String,
+    pub schema: String,
+    #[serde(alias = "user")]
+    pub username: String,
+    pub password: Option<String>,
+}
+
+fn default_trino_port() -> u16 {
+    443
+}
+
 pub async fn get_dbt_profiles_yml() -> Result<DbtProfiles> {
     let mut path = home_dir().unwrap_or_default();
     path.push(".dbt");